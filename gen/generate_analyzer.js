@@ -6,7 +6,7 @@ const fs = require("fs");
 const path = require("path");
 const x86_table = require("./x86_table");
 const rust_ast = require("./rust_ast");
-const { hex, mkdirpSync, get_switch_value, get_switch_exist, finalize_table_rust } = require("./util");
+const { hex, is_three_byte_opcode, mkdirpSync, get_switch_value, get_switch_exist, finalize_table_rust } = require("./util");
 
 const OUT_DIR = path.join(__dirname, "..", "src/rust/gen/");
 
@@ -17,11 +17,13 @@ const gen_all = get_switch_exist("--all");
 const to_generate = {
     analyzer: gen_all || table_arg === "analyzer",
     analyzer0f: gen_all || table_arg === "analyzer0f",
+    analyzer0f38: gen_all || table_arg === "analyzer0f38",
+    analyzer0f3a: gen_all || table_arg === "analyzer0f3a",
 };
 
 assert(
     Object.keys(to_generate).some(k => to_generate[k]),
-    "Pass --table [analyzer|analyzer0f] or --all to pick which tables to generate"
+    "Pass --table [analyzer|analyzer0f|analyzer0f38|analyzer0f3a] or --all to pick which tables to generate"
 );
 
 gen_table();
@@ -77,12 +79,22 @@ function gen_call(name, args)
 
 /*
  * Current naming scheme:
- * instr(16|32|)_(66|F2|F3)?0F?[0-9a-f]{2}(_[0-7])?(_mem|_reg|)
+ * instr(16|32|)_(66|F2|F3)?(0F|0F38|0F3A)?[0-9a-f]{2}(_[0-7])?(_mem|_reg|)
  */
 function make_instruction_name(encoding, size)
 {
     const suffix = encoding.os ? String(size) : "";
     const opcode_hex = hex(encoding.opcode & 0xFF, 2);
+
+    if(is_three_byte_opcode(encoding.opcode))
+    {
+        const escape = hex(encoding.opcode >>> 8 & 0xFFFF, 4);
+        const prefix = (encoding.opcode >>> 24) === 0 ? "" : hex(encoding.opcode >>> 24, 2);
+        assert(encoding.fixed_g === undefined);
+        assert(prefix === "" || prefix === "66" || prefix === "F2" || prefix === "F3");
+        return `instr${suffix}_${prefix}${escape}${opcode_hex}`;
+    }
+
     const first_prefix = (encoding.opcode & 0xFF00) === 0 ? "" : hex(encoding.opcode >> 8 & 0xFF, 2);
     const second_prefix = (encoding.opcode & 0xFF0000) === 0 ? "" : hex(encoding.opcode >> 16 & 0xFF, 2);
     const fixed_g_suffix = encoding.fixed_g === undefined ? "" : `_${encoding.fixed_g}`;
@@ -104,7 +116,15 @@ function gen_instruction_body(encodings, size)
 
     for(let e of encodings)
     {
-        if((e.opcode >>> 16) === 0x66) has_66.push(e);
+        if(is_three_byte_opcode(e.opcode))
+        {
+            const prefix = e.opcode >>> 24;
+            if(prefix === 0x66) has_66.push(e);
+            else if(prefix === 0xF2) has_F2.push(e);
+            else if(prefix === 0xF3) has_F3.push(e);
+            else no_prefix.push(e);
+        }
+        else if((e.opcode >>> 16) === 0x66) has_66.push(e);
         else if((e.opcode >>> 8 & 0xFF) === 0xF2 || (e.opcode >>> 16) === 0xF2) has_F2.push(e);
         else if((e.opcode >>> 8 & 0xFF) === 0xF3 || (e.opcode >>> 16) === 0xF3) has_F3.push(e);
         else no_prefix.push(e);
@@ -112,12 +132,12 @@ function gen_instruction_body(encodings, size)
 
     if(has_F2.length || has_F3.length)
     {
-        assert((encoding.opcode & 0xFF0000) === 0 || (encoding.opcode & 0xFF00) === 0x0F00);
+        assert((encoding.opcode & 0xFF0000) === 0 || (encoding.opcode & 0xFF00) === 0x0F00 || is_three_byte_opcode(encoding.opcode));
     }
 
     if(has_66.length)
     {
-        assert((encoding.opcode & 0xFF00) === 0x0F00);
+        assert((encoding.opcode & 0xFF00) === 0x0F00 || is_three_byte_opcode(encoding.opcode));
     }
 
     const code = [];
@@ -145,7 +165,11 @@ function gen_instruction_body(encodings, size)
         }
 
         const else_block = {
-            body: gen_instruction_body_after_prefix(no_prefix, size),
+            body: no_prefix.length ? gen_instruction_body_after_prefix(no_prefix, size) : [
+                // some three-byte opcodes only exist with a mandatory prefix
+                "analysis.ty = ::analysis::AnalysisType::BlockBoundary;",
+                "analysis.no_next_instruction = true;",
+            ],
         };
 
         return [].concat(
@@ -256,7 +280,7 @@ function gen_instruction_body_after_fixed_g(encoding, size)
         const reg_postfix = [];
         const mem_postfix = [];
 
-        if(encoding.mem_ud)
+        if(encoding.mem_ud || encoding.mem_block_boundary)
         {
             mem_postfix.push(
                 "analysis.ty = ::analysis::AnalysisType::BlockBoundary;"
@@ -351,33 +375,19 @@ function gen_instruction_body_after_fixed_g(encoding, size)
     }
 }
 
-function gen_table()
+function gen_cases(by_opcode, sparse)
 {
-    let by_opcode = Object.create(null);
-    let by_opcode0f = Object.create(null);
-
-    for(let o of x86_table)
+    const cases = [];
+    for(let opcode = 0; opcode < 0x100; opcode++)
     {
-        let opcode = o.opcode;
+        let encoding = by_opcode[opcode];
 
-        if((opcode & 0xFF00) === 0x0F00)
-        {
-            opcode &= 0xFF;
-            by_opcode0f[opcode] = by_opcode0f[opcode] || [];
-            by_opcode0f[opcode].push(o);
-        }
-        else
+        if(sparse && !encoding)
         {
-            opcode &= 0xFF;
-            by_opcode[opcode] = by_opcode[opcode] || [];
-            by_opcode[opcode].push(o);
+            // handled by the default case
+            continue;
         }
-    }
 
-    let cases = [];
-    for(let opcode = 0; opcode < 0x100; opcode++)
-    {
-        let encoding = by_opcode[opcode];
         assert(encoding && encoding.length);
 
         let opcode_hex = hex(opcode, 2);
@@ -402,10 +412,43 @@ function gen_table()
             });
         }
     }
+    return cases;
+}
+
+function gen_table()
+{
+    let by_opcode = Object.create(null);
+    let by_opcode0f = Object.create(null);
+    let by_opcode0f38 = Object.create(null);
+    let by_opcode0f3a = Object.create(null);
+
+    for(let o of x86_table)
+    {
+        let opcode = o.opcode;
+        let table;
+
+        if(is_three_byte_opcode(opcode))
+        {
+            table = (opcode >>> 8 & 0xFF) === 0x38 ? by_opcode0f38 : by_opcode0f3a;
+        }
+        else if((opcode & 0xFF00) === 0x0F00)
+        {
+            table = by_opcode0f;
+        }
+        else
+        {
+            table = by_opcode;
+        }
+
+        opcode &= 0xFF;
+        table[opcode] = table[opcode] || [];
+        table[opcode].push(o);
+    }
+
     const table = {
         type: "switch",
         condition: "opcode",
-        cases,
+        cases: gen_cases(by_opcode, false),
         default_case: {
             body: ["dbg_assert!(false);"]
         },
@@ -427,41 +470,10 @@ function gen_table()
         );
     }
 
-    const cases0f = [];
-    for(let opcode = 0; opcode < 0x100; opcode++)
-    {
-        let encoding = by_opcode0f[opcode];
-
-        assert(encoding && encoding.length);
-
-        let opcode_hex = hex(opcode, 2);
-        let opcode_high_hex = hex(opcode | 0x100, 2);
-
-        if(encoding[0].os)
-        {
-            cases0f.push({
-                conditions: [`0x${opcode_hex}`],
-                body: gen_instruction_body(encoding, 16),
-            });
-            cases0f.push({
-                conditions: [`0x${opcode_high_hex}`],
-                body: gen_instruction_body(encoding, 32),
-            });
-        }
-        else
-        {
-            let block = {
-                conditions: [`0x${opcode_hex}`, `0x${opcode_high_hex}`],
-                body: gen_instruction_body(encoding, undefined),
-            };
-            cases0f.push(block);
-        }
-    }
-
     const table0f = {
         type: "switch",
         condition: "opcode",
-        cases: cases0f,
+        cases: gen_cases(by_opcode0f, false),
         default_case: {
             body: ["dbg_assert!(false);"]
         },
@@ -483,4 +495,38 @@ function gen_table()
             rust_ast.print_syntax_tree([].concat(code)).join("\n") + "\n"
         );
     }
+
+    for(const [name, by_opcode_three_byte] of [["analyzer0f38", by_opcode0f38], ["analyzer0f3a", by_opcode0f3a]])
+    {
+        if(!to_generate[name])
+        {
+            continue;
+        }
+
+        const table_three_byte = {
+            type: "switch",
+            condition: "opcode",
+            cases: gen_cases(by_opcode_three_byte, true),
+            default_case: {
+                body: [
+                    "analysis.ty = ::analysis::AnalysisType::BlockBoundary;",
+                    "analysis.no_next_instruction = true;",
+                ]
+            },
+        };
+
+        const code = [
+            "#![allow(unused)]",
+            "#[cfg_attr(rustfmt, rustfmt_skip)]",
+            "pub fn analyzer(opcode: u32, cpu: &mut ::cpu_context::CpuContext, analysis: &mut ::analysis::Analysis) {",
+            table_three_byte,
+            "}"
+        ];
+
+        finalize_table_rust(
+            OUT_DIR,
+            name + ".rs",
+            rust_ast.print_syntax_tree([].concat(code)).join("\n") + "\n"
+        );
+    }
 }
@@ -6,7 +6,7 @@ const fs = require("fs");
 const path = require("path");
 const x86_table = require("./x86_table");
 const rust_ast = require("./rust_ast");
-const { hex, mkdirpSync, get_switch_value, get_switch_exist, finalize_table_rust } = require("./util");
+const { hex, is_three_byte_opcode, cpuid_feature_const, mkdirpSync, get_switch_value, get_switch_exist, finalize_table_rust } = require("./util");
 
 const OUT_DIR = path.join(__dirname, "..", "src/rust/gen/");
 
@@ -17,11 +17,13 @@ const gen_all = get_switch_exist("--all");
 const to_generate = {
     interpreter: gen_all || table_arg === "interpreter",
     interpreter0f: gen_all || table_arg === "interpreter0f",
+    interpreter0f38: gen_all || table_arg === "interpreter0f38",
+    interpreter0f3a: gen_all || table_arg === "interpreter0f3a",
 };
 
 assert(
     Object.keys(to_generate).some(k => to_generate[k]),
-    "Pass --table [interpreter|interpreter0f] or --all to pick which tables to generate"
+    "Pass --table [interpreter|interpreter0f|interpreter0f38|interpreter0f3a] or --all to pick which tables to generate"
 );
 
 gen_table();
@@ -82,12 +84,22 @@ function gen_call(name, args)
 
 /*
  * Current naming scheme:
- * instr(16|32|)_(66|F2|F3)?0F?[0-9a-f]{2}(_[0-7])?(_mem|_reg|)
+ * instr(16|32|)_(66|F2|F3)?(0F|0F38|0F3A)?[0-9a-f]{2}(_[0-7])?(_mem|_reg|)
  */
 function make_instruction_name(encoding, size)
 {
     const suffix = encoding.os ? String(size) : "";
     const opcode_hex = hex(encoding.opcode & 0xFF, 2);
+
+    if(is_three_byte_opcode(encoding.opcode))
+    {
+        const escape = hex(encoding.opcode >>> 8 & 0xFFFF, 4);
+        const prefix = (encoding.opcode >>> 24) === 0 ? "" : hex(encoding.opcode >>> 24, 2);
+        assert(encoding.fixed_g === undefined);
+        assert(prefix === "" || prefix === "66" || prefix === "F2" || prefix === "F3");
+        return `instructions_0f::instr${suffix}_${prefix}${escape}${opcode_hex}`;
+    }
+
     const first_prefix = (encoding.opcode & 0xFF00) === 0 ? "" : hex(encoding.opcode >> 8 & 0xFF, 2);
     const second_prefix = (encoding.opcode & 0xFF0000) === 0 ? "" : hex(encoding.opcode >> 16 & 0xFF, 2);
     const fixed_g_suffix = encoding.fixed_g === undefined ? "" : `_${encoding.fixed_g}`;
@@ -110,7 +122,15 @@ function gen_instruction_body(encodings, size)
 
     for(let e of encodings)
     {
-        if((e.opcode >>> 16) === 0x66) has_66.push(e);
+        if(is_three_byte_opcode(e.opcode))
+        {
+            const prefix = e.opcode >>> 24;
+            if(prefix === 0x66) has_66.push(e);
+            else if(prefix === 0xF2) has_F2.push(e);
+            else if(prefix === 0xF3) has_F3.push(e);
+            else no_prefix.push(e);
+        }
+        else if((e.opcode >>> 16) === 0x66) has_66.push(e);
         else if((e.opcode >>> 8 & 0xFF) === 0xF2 || (e.opcode >>> 16) === 0xF2) has_F2.push(e);
         else if((e.opcode >>> 8 & 0xFF) === 0xF3 || (e.opcode >>> 16) === 0xF3) has_F3.push(e);
         else no_prefix.push(e);
@@ -118,12 +138,12 @@ function gen_instruction_body(encodings, size)
 
     if(has_F2.length || has_F3.length)
     {
-        assert((encoding.opcode & 0xFF0000) === 0 || (encoding.opcode & 0xFF00) === 0x0F00);
+        assert((encoding.opcode & 0xFF0000) === 0 || (encoding.opcode & 0xFF00) === 0x0F00 || is_three_byte_opcode(encoding.opcode));
     }
 
     if(has_66.length)
     {
-        assert((encoding.opcode & 0xFF00) === 0x0F00);
+        assert((encoding.opcode & 0xFF00) === 0x0F00 || is_three_byte_opcode(encoding.opcode));
     }
 
     const code = [];
@@ -153,10 +173,13 @@ function gen_instruction_body(encodings, size)
         const check_prefixes = encoding.sse ? "(::prefix::PREFIX_66 | ::prefix::PREFIX_F2 | ::prefix::PREFIX_F3)" : "(::prefix::PREFIX_F2 | ::prefix::PREFIX_F3)";
 
         const else_block = {
-            body: [].concat(
+            body: no_prefix.length ? [].concat(
                 "dbg_assert!((prefixes_ & " + check_prefixes + ") == 0);",
                 gen_instruction_body_after_prefix(no_prefix, size)
-            )
+            ) : [
+                // some three-byte opcodes only exist with a mandatory prefix
+                "trigger_ud();",
+            ]
         };
 
         return [].concat(
@@ -224,6 +247,19 @@ function gen_instruction_body_after_prefix(encodings, size)
     }
 }
 
+function gen_cpuid_test(feature)
+{
+    return {
+        type: "if-else",
+        if_blocks: [
+            {
+                condition: `!::cpu::cpuid::has_feature(${cpuid_feature_const(feature)})`,
+                body: ["trigger_ud();", "return;"],
+            }
+        ],
+    };
+}
+
 function gen_instruction_body_after_fixed_g(encoding, size)
 {
     const instruction_prefix = [];
@@ -232,6 +268,11 @@ function gen_instruction_body_after_fixed_g(encoding, size)
         (!encoding.custom && encoding.e) ?
         ["after_block_boundary();"] : [];
 
+    if(encoding.cpuid)
+    {
+        instruction_prefix.push(gen_cpuid_test(encoding.cpuid));
+    }
+
     if(encoding.task_switch_test || encoding.sse)
     {
         instruction_prefix.push(
@@ -246,6 +287,25 @@ function gen_instruction_body_after_fixed_g(encoding, size)
             });
     }
 
+    if(encoding.is_fpu)
+    {
+        // waiting fpu instructions deliver pending unmasked fpu exceptions
+        const test = "!fpu_pending_exception_test()";
+        instruction_prefix.push(
+            {
+                type: "if-else",
+                if_blocks: [
+                    {
+                        condition:
+                            encoding.nowait_mem ? "modrm_byte >= 0xC0 && " + test :
+                            encoding.nowait_reg ? "modrm_byte < 0xC0 && " + test :
+                            test,
+                        body: ["return;"],
+                    }
+                ],
+            });
+    }
+
     const imm_read = gen_read_imm_call(encoding, size);
     const instruction_name = make_instruction_name(encoding, size);
 
@@ -271,6 +331,7 @@ function gen_instruction_body_after_fixed_g(encoding, size)
         else
         {
             let mem_args;
+            let mem_resolve = [];
 
             if(encoding.custom_modrm_resolve)
             {
@@ -279,7 +340,11 @@ function gen_instruction_body_after_fixed_g(encoding, size)
             }
             else
             {
-                mem_args = ["match modrm_resolve(modrm_byte) { Ok(a) => a, Err(()) => return }"];
+                // the segment register of the operand is passed along for the segment checks
+                mem_resolve = [
+                    "let (seg, addr) = match modrm_resolve(modrm_byte) { Ok(a) => a, Err(()) => return };",
+                ];
+                mem_args = ["seg", "addr"];
             }
 
             const reg_args = ["modrm_byte & 7"];
@@ -304,12 +369,17 @@ function gen_instruction_body_after_fixed_g(encoding, size)
                         {
                             condition: "modrm_byte < 0xC0",
                             body: [].concat(
+                                encoding.cpuid_mem ? gen_cpuid_test(encoding.cpuid_mem) : [],
+                                mem_resolve,
                                 gen_call(`${instruction_name}_mem`, mem_args)
                             ),
                         }
                     ],
                     else_block: {
-                        body: [gen_call(`${instruction_name}_reg`, reg_args)],
+                        body: [].concat(
+                            encoding.cpuid_reg ? gen_cpuid_test(encoding.cpuid_reg) : [],
+                            gen_call(`${instruction_name}_reg`, reg_args)
+                        ),
                     },
                 },
                 instruction_postfix
@@ -344,33 +414,19 @@ function gen_instruction_body_after_fixed_g(encoding, size)
     }
 }
 
-function gen_table()
+function gen_cases(by_opcode, sparse)
 {
-    let by_opcode = Object.create(null);
-    let by_opcode0f = Object.create(null);
-
-    for(let o of x86_table)
+    const cases = [];
+    for(let opcode = 0; opcode < 0x100; opcode++)
     {
-        let opcode = o.opcode;
+        let encoding = by_opcode[opcode];
 
-        if((opcode & 0xFF00) === 0x0F00)
-        {
-            opcode &= 0xFF;
-            by_opcode0f[opcode] = by_opcode0f[opcode] || [];
-            by_opcode0f[opcode].push(o);
-        }
-        else
+        if(sparse && !encoding)
         {
-            opcode &= 0xFF;
-            by_opcode[opcode] = by_opcode[opcode] || [];
-            by_opcode[opcode].push(o);
+            // handled by the default case
+            continue;
         }
-    }
 
-    let cases = [];
-    for(let opcode = 0; opcode < 0x100; opcode++)
-    {
-        let encoding = by_opcode[opcode];
         assert(encoding && encoding.length);
 
         let opcode_hex = hex(opcode, 2);
@@ -395,10 +451,43 @@ function gen_table()
             });
         }
     }
+    return cases;
+}
+
+function gen_table()
+{
+    let by_opcode = Object.create(null);
+    let by_opcode0f = Object.create(null);
+    let by_opcode0f38 = Object.create(null);
+    let by_opcode0f3a = Object.create(null);
+
+    for(let o of x86_table)
+    {
+        let opcode = o.opcode;
+        let table;
+
+        if(is_three_byte_opcode(opcode))
+        {
+            table = (opcode >>> 8 & 0xFF) === 0x38 ? by_opcode0f38 : by_opcode0f3a;
+        }
+        else if((opcode & 0xFF00) === 0x0F00)
+        {
+            table = by_opcode0f;
+        }
+        else
+        {
+            table = by_opcode;
+        }
+
+        opcode &= 0xFF;
+        table[opcode] = table[opcode] || [];
+        table[opcode].push(o);
+    }
+
     const table = {
         type: "switch",
         condition: "opcode",
-        cases,
+        cases: gen_cases(by_opcode, false),
         default_case: {
             body: ["assert!(false);"]
         },
@@ -410,7 +499,7 @@ function gen_table()
 
             "use cpu::cpu::{after_block_boundary, modrm_resolve};",
             "use cpu::cpu::{read_imm8, read_imm8s, read_imm16, read_imm32s, read_moffs};",
-            "use cpu::cpu::{task_switch_test, trigger_ud, DEBUG};",
+            "use cpu::cpu::{fpu_pending_exception_test, task_switch_test, trigger_ud, DEBUG};",
             "use cpu::instructions;",
             "use cpu::global_pointers::{instruction_pointer, prefixes};",
 
@@ -426,41 +515,10 @@ function gen_table()
         );
     }
 
-    const cases0f = [];
-    for(let opcode = 0; opcode < 0x100; opcode++)
-    {
-        let encoding = by_opcode0f[opcode];
-
-        assert(encoding && encoding.length);
-
-        let opcode_hex = hex(opcode, 2);
-        let opcode_high_hex = hex(opcode | 0x100, 2);
-
-        if(encoding[0].os)
-        {
-            cases0f.push({
-                conditions: [`0x${opcode_hex}`],
-                body: gen_instruction_body(encoding, 16),
-            });
-            cases0f.push({
-                conditions: [`0x${opcode_high_hex}`],
-                body: gen_instruction_body(encoding, 32),
-            });
-        }
-        else
-        {
-            let block = {
-                conditions: [`0x${opcode_hex}`, `0x${opcode_high_hex}`],
-                body: gen_instruction_body(encoding, undefined),
-            };
-            cases0f.push(block);
-        }
-    }
-
     const table0f = {
         type: "switch",
         condition: "opcode",
-        cases: cases0f,
+        cases: gen_cases(by_opcode0f, false),
         default_case: {
             body: ["assert!(false);"]
         },
@@ -489,4 +547,43 @@ function gen_table()
             rust_ast.print_syntax_tree([].concat(code)).join("\n") + "\n"
         );
     }
+
+    for(const [name, by_opcode_three_byte] of [["interpreter0f38", by_opcode0f38], ["interpreter0f3a", by_opcode0f3a]])
+    {
+        if(!to_generate[name])
+        {
+            continue;
+        }
+
+        const table_three_byte = {
+            type: "switch",
+            condition: "opcode",
+            cases: gen_cases(by_opcode_three_byte, true),
+            default_case: {
+                body: ["trigger_ud();"]
+            },
+        };
+
+        const code = [
+            "#![cfg_attr(rustfmt, rustfmt_skip)]",
+            "#![allow(unused_imports)]",
+
+            "use cpu::cpu::{after_block_boundary, modrm_resolve};",
+            "use cpu::cpu::{read_imm8, read_imm16, read_imm32s};",
+            "use cpu::cpu::{task_switch_test, task_switch_test_mmx, trigger_ud};",
+            "use cpu::cpu::DEBUG;",
+            "use cpu::instructions_0f;",
+            "use cpu::global_pointers::{instruction_pointer, prefixes};",
+
+            "pub unsafe fn run(opcode: u32) {",
+            table_three_byte,
+            "}",
+        ];
+
+        finalize_table_rust(
+            OUT_DIR,
+            name + ".rs",
+            rust_ast.print_syntax_tree([].concat(code)).join("\n") + "\n"
+        );
+    }
 }
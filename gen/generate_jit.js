@@ -6,7 +6,7 @@ const fs = require("fs");
 const path = require("path");
 const x86_table = require("./x86_table");
 const rust_ast = require("./rust_ast");
-const { hex, mkdirpSync, get_switch_value, get_switch_exist, finalize_table_rust } = require("./util");
+const { hex, is_three_byte_opcode, cpuid_feature_const, mkdirpSync, get_switch_value, get_switch_exist, finalize_table_rust } = require("./util");
 
 const OUT_DIR = path.join(__dirname, "..", "src/rust/gen/");
 
@@ -17,11 +17,13 @@ const gen_all = get_switch_exist("--all");
 const to_generate = {
     jit: gen_all || table_arg === "jit",
     jit0f: gen_all || table_arg === "jit0f",
+    jit0f38: gen_all || table_arg === "jit0f38",
+    jit0f3a: gen_all || table_arg === "jit0f3a",
 };
 
 assert(
     Object.keys(to_generate).some(k => to_generate[k]),
-    "Pass --table [jit|jit0f] or --all to pick which tables to generate"
+    "Pass --table [jit|jit0f|jit0f38|jit0f3a] or --all to pick which tables to generate"
 );
 
 gen_table();
@@ -77,12 +79,22 @@ function gen_call(name, args)
 
 /*
  * Current naming scheme:
- * instr(16|32|)_(66|F2|F3)?0F?[0-9a-f]{2}(_[0-7])?(_mem|_reg|)
+ * instr(16|32|)_(66|F2|F3)?(0F|0F38|0F3A)?[0-9a-f]{2}(_[0-7])?(_mem|_reg|)
  */
 function make_instruction_name(encoding, size)
 {
     const suffix = encoding.os ? String(size) : "";
     const opcode_hex = hex(encoding.opcode & 0xFF, 2);
+
+    if(is_three_byte_opcode(encoding.opcode))
+    {
+        const escape = hex(encoding.opcode >>> 8 & 0xFFFF, 4);
+        const prefix = (encoding.opcode >>> 24) === 0 ? "" : hex(encoding.opcode >>> 24, 2);
+        assert(encoding.fixed_g === undefined);
+        assert(prefix === "" || prefix === "66" || prefix === "F2" || prefix === "F3");
+        return `instr${suffix}_${prefix}${escape}${opcode_hex}`;
+    }
+
     const first_prefix = (encoding.opcode & 0xFF00) === 0 ? "" : hex(encoding.opcode >> 8 & 0xFF, 2);
     const second_prefix = (encoding.opcode & 0xFF0000) === 0 ? "" : hex(encoding.opcode >> 16 & 0xFF, 2);
     const fixed_g_suffix = encoding.fixed_g === undefined ? "" : `_${encoding.fixed_g}`;
@@ -104,7 +116,15 @@ function gen_instruction_body(encodings, size)
 
     for(let e of encodings)
     {
-        if((e.opcode >>> 16) === 0x66) has_66.push(e);
+        if(is_three_byte_opcode(e.opcode))
+        {
+            const prefix = e.opcode >>> 24;
+            if(prefix === 0x66) has_66.push(e);
+            else if(prefix === 0xF2) has_F2.push(e);
+            else if(prefix === 0xF3) has_F3.push(e);
+            else no_prefix.push(e);
+        }
+        else if((e.opcode >>> 16) === 0x66) has_66.push(e);
         else if((e.opcode >>> 8 & 0xFF) === 0xF2 || (e.opcode >>> 16) === 0xF2) has_F2.push(e);
         else if((e.opcode >>> 8 & 0xFF) === 0xF3 || (e.opcode >>> 16) === 0xF3) has_F3.push(e);
         else no_prefix.push(e);
@@ -112,12 +132,12 @@ function gen_instruction_body(encodings, size)
 
     if(has_F2.length || has_F3.length)
     {
-        assert((encoding.opcode & 0xFF0000) === 0 || (encoding.opcode & 0xFF00) === 0x0F00);
+        assert((encoding.opcode & 0xFF0000) === 0 || (encoding.opcode & 0xFF00) === 0x0F00 || is_three_byte_opcode(encoding.opcode));
     }
 
     if(has_66.length)
     {
-        assert((encoding.opcode & 0xFF00) === 0x0F00);
+        assert((encoding.opcode & 0xFF00) === 0x0F00 || is_three_byte_opcode(encoding.opcode));
     }
 
     const code = [];
@@ -145,7 +165,11 @@ function gen_instruction_body(encodings, size)
         }
 
         const else_block = {
-            body: gen_instruction_body_after_prefix(no_prefix, size),
+            body: no_prefix.length ? gen_instruction_body_after_prefix(no_prefix, size) : [
+                // some three-byte opcodes only exist with a mandatory prefix
+                gen_call("::codegen::gen_trigger_ud", ["ctx"]),
+                "*instr_flags |= ::jit::JIT_INSTR_BLOCK_BOUNDARY_FLAG;",
+            ],
         };
 
         return [].concat(
@@ -212,6 +236,21 @@ function gen_instruction_body_after_prefix(encodings, size)
     }
 }
 
+// Features are fixed while code is compiled (changing the cpu model clears the jit cache), so a
+// masked-off feature unconditionally generates #ud
+function gen_cpuid_test(feature)
+{
+    return {
+        type: "if-else",
+        if_blocks: [
+            {
+                condition: `!::cpu::cpuid::has_feature(${cpuid_feature_const(feature)})`,
+                body: [gen_call("::codegen::gen_trigger_ud", ["ctx"])],
+            }
+        ],
+    };
+}
+
 function gen_instruction_body_after_fixed_g(encoding, size)
 {
     const instruction_postfix = [];
@@ -223,6 +262,11 @@ function gen_instruction_body_after_fixed_g(encoding, size)
 
     const instruction_prefix = [];
 
+    if(encoding.cpuid)
+    {
+        instruction_prefix.push(gen_cpuid_test(encoding.cpuid));
+    }
+
     if(encoding.task_switch_test || encoding.sse)
     {
         instruction_prefix.push(
@@ -230,6 +274,29 @@ function gen_instruction_body_after_fixed_g(encoding, size)
         );
     }
 
+    if(encoding.is_fpu)
+    {
+        // waiting fpu instructions deliver pending unmasked fpu exceptions
+        const test = gen_call("::codegen::gen_fpu_pending_exception_test", ["ctx"]);
+        if(encoding.nowait_mem || encoding.nowait_reg)
+        {
+            instruction_prefix.push(
+                {
+                    type: "if-else",
+                    if_blocks: [
+                        {
+                            condition: encoding.nowait_mem ? "modrm_byte >= 0xC0" : "modrm_byte < 0xC0",
+                            body: [test],
+                        }
+                    ],
+                });
+        }
+        else
+        {
+            instruction_prefix.push(test);
+        }
+    }
+
     const imm_read = gen_read_imm_call(encoding, size);
     const imm_read_bindings = [];
     if(imm_read)
@@ -260,7 +327,7 @@ function gen_instruction_body_after_fixed_g(encoding, size)
         const reg_postfix = [];
         const mem_postfix = [];
 
-        if(encoding.mem_ud)
+        if(encoding.mem_ud || encoding.mem_block_boundary)
         {
             mem_postfix.push(
                 "*instr_flags |= ::jit::JIT_INSTR_BLOCK_BOUNDARY_FLAG;"
@@ -314,6 +381,7 @@ function gen_instruction_body_after_fixed_g(encoding, size)
                         condition: "modrm_byte < 0xC0",
                         body: [].concat(
                             "let addr = ::modrm::decode(ctx.cpu, modrm_byte);",
+                            encoding.cpuid_mem ? gen_cpuid_test(encoding.cpuid_mem) : [],
                             imm_read_bindings,
                             gen_call(`::jit_instructions::${instruction_name}_mem_jit`, mem_args),
                             mem_postfix
@@ -321,6 +389,7 @@ function gen_instruction_body_after_fixed_g(encoding, size)
                     }],
                     else_block: {
                         body: [].concat(
+                            encoding.cpuid_reg ? gen_cpuid_test(encoding.cpuid_reg) : [],
                             imm_read_bindings,
                             gen_call(`::jit_instructions::${instruction_name}_reg_jit`, reg_args),
                             reg_postfix
@@ -355,7 +424,8 @@ function gen_instruction_body_after_fixed_g(encoding, size)
                         condition: "modrm_byte < 0xC0",
                         body: [].concat(
                             "let addr = ::modrm::decode(ctx.cpu, modrm_byte);",
-                            gen_call(`::codegen::gen_modrm_resolve`, ["ctx", "addr"]),
+                            encoding.cpuid_mem ? gen_cpuid_test(encoding.cpuid_mem) : [],
+                            gen_call(`::codegen::gen_modrm_resolve_with_segment`, ["ctx", "addr"]),
                             imm_read_bindings,
                             gen_call(`::codegen::gen_modrm_fn${mem_args.length - 2}`, mem_args),
                             mem_postfix
@@ -363,6 +433,7 @@ function gen_instruction_body_after_fixed_g(encoding, size)
                     }],
                     else_block: {
                         body: [].concat(
+                            encoding.cpuid_reg ? gen_cpuid_test(encoding.cpuid_reg) : [],
                             imm_read_bindings,
                             gen_call(`::codegen::gen_fn${reg_args.length - 2}_const`, reg_args),
                             reg_postfix
@@ -429,33 +500,19 @@ function gen_instruction_body_after_fixed_g(encoding, size)
     }
 }
 
-function gen_table()
+function gen_cases(by_opcode, sparse)
 {
-    let by_opcode = Object.create(null);
-    let by_opcode0f = Object.create(null);
-
-    for(let o of x86_table)
+    const cases = [];
+    for(let opcode = 0; opcode < 0x100; opcode++)
     {
-        let opcode = o.opcode;
+        let encoding = by_opcode[opcode];
 
-        if((opcode & 0xFF00) === 0x0F00)
+        if(sparse && !encoding)
         {
-            opcode &= 0xFF;
-            by_opcode0f[opcode] = by_opcode0f[opcode] || [];
-            by_opcode0f[opcode].push(o);
+            // handled by the default case
+            continue;
         }
-        else
-        {
-            opcode &= 0xFF;
-            by_opcode[opcode] = by_opcode[opcode] || [];
-            by_opcode[opcode].push(o);
-        }
-    }
 
-    let cases = [];
-    for(let opcode = 0; opcode < 0x100; opcode++)
-    {
-        let encoding = by_opcode[opcode];
         assert(encoding && encoding.length);
 
         let opcode_hex = hex(opcode, 2);
@@ -480,10 +537,43 @@ function gen_table()
             });
         }
     }
+    return cases;
+}
+
+function gen_table()
+{
+    let by_opcode = Object.create(null);
+    let by_opcode0f = Object.create(null);
+    let by_opcode0f38 = Object.create(null);
+    let by_opcode0f3a = Object.create(null);
+
+    for(let o of x86_table)
+    {
+        let opcode = o.opcode;
+        let table;
+
+        if(is_three_byte_opcode(opcode))
+        {
+            table = (opcode >>> 8 & 0xFF) === 0x38 ? by_opcode0f38 : by_opcode0f3a;
+        }
+        else if((opcode & 0xFF00) === 0x0F00)
+        {
+            table = by_opcode0f;
+        }
+        else
+        {
+            table = by_opcode;
+        }
+
+        opcode &= 0xFF;
+        table[opcode] = table[opcode] || [];
+        table[opcode].push(o);
+    }
+
     const table = {
         type: "switch",
         condition: "opcode",
-        cases,
+        cases: gen_cases(by_opcode, false),
         default_case: {
             body: ["assert!(false);"]
         },
@@ -505,41 +595,10 @@ function gen_table()
         );
     }
 
-    const cases0f = [];
-    for(let opcode = 0; opcode < 0x100; opcode++)
-    {
-        let encoding = by_opcode0f[opcode];
-
-        assert(encoding && encoding.length);
-
-        let opcode_hex = hex(opcode, 2);
-        let opcode_high_hex = hex(opcode | 0x100, 2);
-
-        if(encoding[0].os)
-        {
-            cases0f.push({
-                conditions: [`0x${opcode_hex}`],
-                body: gen_instruction_body(encoding, 16),
-            });
-            cases0f.push({
-                conditions: [`0x${opcode_high_hex}`],
-                body: gen_instruction_body(encoding, 32),
-            });
-        }
-        else
-        {
-            let block = {
-                conditions: [`0x${opcode_hex}`, `0x${opcode_high_hex}`],
-                body: gen_instruction_body(encoding, undefined),
-            };
-            cases0f.push(block);
-        }
-    }
-
     const table0f = {
         type: "switch",
         condition: "opcode",
-        cases: cases0f,
+        cases: gen_cases(by_opcode0f, false),
         default_case: {
             body: ["assert!(false);"]
         },
@@ -560,4 +619,37 @@ function gen_table()
             rust_ast.print_syntax_tree([].concat(code)).join("\n") + "\n"
         );
     }
+
+    for(const [name, by_opcode_three_byte] of [["jit0f38", by_opcode0f38], ["jit0f3a", by_opcode0f3a]])
+    {
+        if(!to_generate[name])
+        {
+            continue;
+        }
+
+        const table_three_byte = {
+            type: "switch",
+            condition: "opcode",
+            cases: gen_cases(by_opcode_three_byte, true),
+            default_case: {
+                body: [
+                    gen_call("::codegen::gen_trigger_ud", ["ctx"]),
+                    "*instr_flags |= ::jit::JIT_INSTR_BLOCK_BOUNDARY_FLAG;",
+                ]
+            },
+        };
+
+        const code = [
+            "#[cfg_attr(rustfmt, rustfmt_skip)]",
+            "pub fn jit(opcode: u32, ctx: &mut ::jit::JitContext, instr_flags: &mut u32) {",
+            table_three_byte,
+            "}",
+        ];
+
+        finalize_table_rust(
+            OUT_DIR,
+            name + ".rs",
+            rust_ast.print_syntax_tree([].concat(code)).join("\n") + "\n"
+        );
+    }
 }
@@ -16,6 +16,19 @@ function hex(n, pad)
     return s;
 }
 
+// Three-byte opcodes (0F38xx and 0F3Axx), optionally preceded by a mandatory 66, F2 or F3 prefix
+function is_three_byte_opcode(opcode)
+{
+    const escape = opcode >>> 8 & 0xFFFF;
+    return escape === 0x0F38 || escape === 0x0F3A;
+}
+
+// Rust constant for a cpuid feature name from the x86 table, e.g. "sse4.1" -> FEATURE_SSE4_1
+function cpuid_feature_const(feature)
+{
+    return "::cpu::cpuid::FEATURE_" + feature.toUpperCase().replace(".", "_");
+}
+
 function mkdirpSync(dir)
 {
     fs.mkdirSync(dir, { recursive: true });
@@ -47,6 +60,8 @@ function finalize_table_rust(out_dir, name, contents)
 
 module.exports = {
     hex,
+    is_three_byte_opcode,
+    cpuid_feature_const,
     mkdirpSync,
     get_switch_value,
     get_switch_exist,
@@ -1,6 +1,6 @@
 "use strict";
 
-const { hex } = require("./util");
+const { hex, is_three_byte_opcode } = require("./util");
 
 // http://ref.x86asm.net/coder32.html
 
@@ -57,7 +57,10 @@ const TESTS_ASSUME_INTEL = false;
 // imm8, imm8s, imm16, imm1632, immaddr, extra_imm8, extra_imm16: one or two immediate bytes follows the instruction
 // custom: will callback jit to generate custom code
 // block_boundary: may change eip in a way not handled by the jit
+// mem_block_boundary: like block_boundary, for only the memory form
 // no_next_instruction: jit will stop analysing after instruction (e.g., unconditional jump, ret)
+// cpuid: #ud if the given cpuid feature (see src/rust/cpu/cpuid.rs) is masked off
+// cpuid_mem, cpuid_reg: like cpuid, for only the memory or register form
 const encodings = [
     { opcode: 0x06, os: 1, custom: 1 },
     { opcode: 0x07, os: 1, skip: 1, block_boundary: 1 }, // pop es: block_boundary since it uses non-raising cpu exceptions
@@ -183,7 +186,7 @@ const encodings = [
     { opcode: 0xC2, custom: 1, block_boundary: 1, no_next_instruction: 1, os: 1, absolute_jump: 1, imm16: 1, skip: 1 }, // ret
     { opcode: 0xC3, custom: 1, block_boundary: 1, no_next_instruction: 1, os: 1, absolute_jump: 1, skip: 1 },
 
-    { opcode: 0xC4, block_boundary: 1, os: 1, e: 1, skip: 1 }, // les
+    { opcode: 0xC4, os: 1, prefix: 1 }, // les, or vex prefix in protected mode (bmi1, bmi2)
     { opcode: 0xC5, block_boundary: 1, os: 1, e: 1, skip: 1 }, // lds
 
     { opcode: 0xC6, custom: 1, e: 1, fixed_g: 0, imm8: 1 },
@@ -221,25 +224,25 @@ const encodings = [
     { opcode: 0xD9, e: 1, fixed_g: 3, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1 },
     { opcode: 0xD9, e: 1, fixed_g: 4, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1, skip_mem: 1 }, // fldenv (mem)
     { opcode: 0xD9, e: 1, fixed_g: 5, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1 },
-    { opcode: 0xD9, e: 1, fixed_g: 6, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1, skip: 1 }, // fstenv (mem), fprem (reg)
-    { opcode: 0xD9, e: 1, fixed_g: 7, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1, skip_reg: 1 }, // fprem, fyl2xp1 (precision issues)
+    { opcode: 0xD9, e: 1, fixed_g: 6, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1, skip: 1, nowait_mem: 1 }, // fnstenv (mem), fprem (reg)
+    { opcode: 0xD9, e: 1, fixed_g: 7, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1, skip_reg: 1, nowait_mem: 1 }, // fnstcw (mem), fprem, fyl2xp1 (reg, precision issues)
 
-    { opcode: 0xDA, e: 1, fixed_g: 0, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDA, e: 1, fixed_g: 1, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDA, e: 1, fixed_g: 2, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDA, e: 1, fixed_g: 3, custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDA, e: 1, fixed_g: 0, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDA, e: 1, fixed_g: 1, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDA, e: 1, fixed_g: 2, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDA, e: 1, fixed_g: 3, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
     { opcode: 0xDA, e: 1, fixed_g: 4, custom: 1, is_fpu: 1, task_switch_test: 1 },
     { opcode: 0xDA, e: 1, fixed_g: 5, custom: 1, is_fpu: 1, task_switch_test: 1 },
     { opcode: 0xDA, e: 1, fixed_g: 6, custom: 1, is_fpu: 1, task_switch_test: 1 },
     { opcode: 0xDA, e: 1, fixed_g: 7, custom: 1, is_fpu: 1, task_switch_test: 1 },
 
-    { opcode: 0xDB, e: 1, fixed_g: 0, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDB, e: 1, fixed_g: 1, custom: 1, is_fpu: 1, task_switch_test: 1 }, // fisttp (sse3)
-    { opcode: 0xDB, e: 1, fixed_g: 2, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDB, e: 1, fixed_g: 3, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDB, e: 1, fixed_g: 4, custom: 0, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDB, e: 1, fixed_g: 5, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDB, e: 1, fixed_g: 6, custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDB, e: 1, fixed_g: 0, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDB, e: 1, fixed_g: 1, cpuid_reg: "cmov", cpuid_mem: "sse3", custom: 1, is_fpu: 1, task_switch_test: 1 }, // fisttp (sse3)
+    { opcode: 0xDB, e: 1, fixed_g: 2, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDB, e: 1, fixed_g: 3, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDB, e: 1, fixed_g: 4, custom: 0, is_fpu: 1, task_switch_test: 1, nowait_reg: 1 }, // fnclex, fninit (reg)
+    { opcode: 0xDB, e: 1, fixed_g: 5, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDB, e: 1, fixed_g: 6, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
     { opcode: 0xDB, e: 1, fixed_g: 7, custom: 0, is_fpu: 1, task_switch_test: 1 },
 
     { opcode: 0xDC, e: 1, fixed_g: 0, custom: 1, is_fpu: 1, task_switch_test: 1 },
@@ -252,13 +255,13 @@ const encodings = [
     { opcode: 0xDC, e: 1, fixed_g: 7, custom: 1, is_fpu: 1, task_switch_test: 1 },
 
     { opcode: 0xDD, e: 1, fixed_g: 0, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1 },
-    { opcode: 0xDD, e: 1, fixed_g: 1, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1 }, // fisttp (sse3)
+    { opcode: 0xDD, e: 1, fixed_g: 1, cpuid_mem: "sse3", custom: 1, is_fpu: 1, task_switch_test: 1, os: 1 }, // fisttp (sse3)
     { opcode: 0xDD, e: 1, fixed_g: 2, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1 },
     { opcode: 0xDD, e: 1, fixed_g: 3, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1 },
     { opcode: 0xDD, e: 1, fixed_g: 4, custom: 0, is_fpu: 1, task_switch_test: 1, os: 1, skip_mem: 1 }, // frstor
     { opcode: 0xDD, e: 1, fixed_g: 5, custom: 1, is_fpu: 1, task_switch_test: 1, os: 1 },
-    { opcode: 0xDD, e: 1, fixed_g: 6, custom: 0, is_fpu: 1, task_switch_test: 1, os: 1, skip_mem: 1 }, // fsave
-    { opcode: 0xDD, e: 1, fixed_g: 7, custom: 0, is_fpu: 1, task_switch_test: 1, os: 1, skip_mem: 1 }, // fstsw (denormal flag)
+    { opcode: 0xDD, e: 1, fixed_g: 6, custom: 0, is_fpu: 1, task_switch_test: 1, os: 1, skip_mem: 1, nowait_mem: 1 }, // fnsave
+    { opcode: 0xDD, e: 1, fixed_g: 7, custom: 0, is_fpu: 1, task_switch_test: 1, os: 1, skip_mem: 1, nowait_mem: 1 }, // fnstsw (denormal flag)
 
     { opcode: 0xDE, e: 1, fixed_g: 0, custom: 1, is_fpu: 1, task_switch_test: 1 },
     { opcode: 0xDE, e: 1, fixed_g: 1, custom: 1, is_fpu: 1, task_switch_test: 1 },
@@ -270,12 +273,12 @@ const encodings = [
     { opcode: 0xDE, e: 1, fixed_g: 7, custom: 1, is_fpu: 1, task_switch_test: 1 },
 
     { opcode: 0xDF, e: 1, fixed_g: 0, custom: 0, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDF, e: 1, fixed_g: 1, custom: 1, is_fpu: 1, task_switch_test: 1 }, // fisttp (sse3)
+    { opcode: 0xDF, e: 1, fixed_g: 1, cpuid_mem: "sse3", custom: 1, is_fpu: 1, task_switch_test: 1 }, // fisttp (sse3)
     { opcode: 0xDF, e: 1, fixed_g: 2, custom: 1, is_fpu: 1, task_switch_test: 1 },
     { opcode: 0xDF, e: 1, fixed_g: 3, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDF, e: 1, fixed_g: 4, custom: 1, is_fpu: 1, task_switch_test: 1, skip: 1 }, // unimplemented: Binary Coded Decimals / fsts (denormal flag)
-    { opcode: 0xDF, e: 1, fixed_g: 5, custom: 1, is_fpu: 1, task_switch_test: 1 },
-    { opcode: 0xDF, e: 1, fixed_g: 6, custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDF, e: 1, fixed_g: 4, custom: 1, is_fpu: 1, task_switch_test: 1, skip: 1, nowait_reg: 1 }, // unimplemented: Binary Coded Decimals / fnstsw ax (denormal flag)
+    { opcode: 0xDF, e: 1, fixed_g: 5, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
+    { opcode: 0xDF, e: 1, fixed_g: 6, cpuid_reg: "cmov", custom: 1, is_fpu: 1, task_switch_test: 1 },
     { opcode: 0xDF, e: 1, fixed_g: 7, custom: 1, is_fpu: 1, task_switch_test: 1 },
 
     // loop, jcxz, etc.
@@ -301,7 +304,7 @@ const encodings = [
     { opcode: 0xEF, block_boundary: 1, os: 1, skip: 1 },
 
     { opcode: 0xF0, prefix: 1 },
-    { opcode: 0xF1, skip: 1 },
+    { opcode: 0xF1, block_boundary: 1, skip: 1 }, // int1
     { opcode: 0xF2, prefix: 1 },
     { opcode: 0xF3, prefix: 1 },
     { opcode: 0xF4, block_boundary: 1, no_next_instruction: 1, skip: 1 }, // hlt
@@ -352,19 +355,19 @@ const encodings = [
     { opcode: 0x0F00, fixed_g: 5, e: 1, skip: 1, block_boundary: 1, os: 1 },
 
     { opcode: 0x0F01, fixed_g: 0, e: 1, skip: 1, block_boundary: 1, os: 1 }, // sgdt, ...
-    { opcode: 0x0F01, fixed_g: 1, e: 1, skip: 1, block_boundary: 1, os: 1 },
+    { opcode: 0x0F01, fixed_g: 1, e: 1, skip: 1, block_boundary: 1, os: 1, custom: 1 }, // sidt, monitor, mwait, clac, stac
     { opcode: 0x0F01, fixed_g: 2, e: 1, skip: 1, block_boundary: 1, os: 1 },
     { opcode: 0x0F01, fixed_g: 3, e: 1, skip: 1, block_boundary: 1, os: 1 },
     { opcode: 0x0F01, fixed_g: 4, e: 1, skip: 1, block_boundary: 1, os: 1 },
     { opcode: 0x0F01, fixed_g: 6, e: 1, skip: 1, block_boundary: 1, os: 1 },
-    { opcode: 0x0F01, fixed_g: 7, e: 1, skip: 1, block_boundary: 1, os: 1 },
+    { opcode: 0x0F01, fixed_g: 7, e: 1, skip: 1, block_boundary: 1, os: 1, cpuid_reg: "rdtscp" }, // invlpg, rdtscp
 
     { opcode: 0x0F02, os: 1, e: 1, skip: 1, block_boundary: 1 }, // lar
     { opcode: 0x0F03, os: 1, e: 1, skip: 1, block_boundary: 1 }, // lsl
     { opcode: 0x0F04, skip: 1, block_boundary: 1 },
-    { opcode: 0x0F05, skip: 1, block_boundary: 1 },
+    { opcode: 0x0F05, cpuid: "syscall", skip: 1, block_boundary: 1, no_next_instruction: 1 }, // syscall
     { opcode: 0x0F06, skip: 1, block_boundary: 1 }, // clts
-    { opcode: 0x0F07, skip: 1, block_boundary: 1 },
+    { opcode: 0x0F07, cpuid: "syscall", skip: 1, block_boundary: 1, no_next_instruction: 1 }, // sysret
     { opcode: 0x0F08, skip: 1, block_boundary: 1 },
     { opcode: 0x0F09, skip: 1, block_boundary: 1 }, // wbinvd
     { opcode: 0x0F0A, skip: 1, block_boundary: 1 },
@@ -396,20 +399,20 @@ const encodings = [
     { opcode: 0x0F26, skip: 1, block_boundary: 1 },
     { opcode: 0x0F27, skip: 1, block_boundary: 1 },
 
-    { opcode: 0x0F30, skip: 1, block_boundary: 1 }, // wrmsr
-    { opcode: 0x0F31, skip: 1, custom: 1 }, // rdtsc
-    { opcode: 0x0F32, skip: 1, block_boundary: 1 }, // rdmsr
+    { opcode: 0x0F30, cpuid: "msr", skip: 1, block_boundary: 1 }, // wrmsr
+    { opcode: 0x0F31, cpuid: "tsc", skip: 1, custom: 1 }, // rdtsc
+    { opcode: 0x0F32, cpuid: "msr", skip: 1, block_boundary: 1 }, // rdmsr
     { opcode: 0x0F33, skip: 1, block_boundary: 1 }, // rdpmc
-    { opcode: 0x0F34, skip: 1, block_boundary: 1, no_next_instruction: 1 }, // sysenter
-    { opcode: 0x0F35, skip: 1, block_boundary: 1, no_next_instruction: 1 }, // sysexit
+    { opcode: 0x0F34, cpuid: "sep", skip: 1, block_boundary: 1, no_next_instruction: 1 }, // sysenter
+    { opcode: 0x0F35, cpuid: "sep", skip: 1, block_boundary: 1, no_next_instruction: 1 }, // sysexit
 
     { opcode: 0x0F36, skip: 1, block_boundary: 1 }, // ud
     { opcode: 0x0F37, skip: 1, block_boundary: 1 }, // getsec
 
-    // ssse3+
-    { opcode: 0x0F38, skip: 1, block_boundary: 1 },
+    // ssse3+: three-byte opcodes, see below
+    { opcode: 0x0F38, os: 1, prefix: 1 },
     { opcode: 0x0F39, skip: 1, block_boundary: 1 },
-    { opcode: 0x0F3A, skip: 1, block_boundary: 1 },
+    { opcode: 0x0F3A, os: 1, prefix: 1 },
     { opcode: 0x0F3B, skip: 1, block_boundary: 1 },
     { opcode: 0x0F3C, skip: 1, block_boundary: 1 },
     { opcode: 0x0F3D, skip: 1, block_boundary: 1 },
@@ -436,6 +439,9 @@ const encodings = [
 
     { opcode: 0x0FBC, os: 1, e: 1, mask_flags: of | sf | af | pf | cf, custom: 1 }, // bsf
     { opcode: 0x0FBD, os: 1, e: 1, mask_flags: of | sf | af | pf | cf, custom: 1 },
+    // executed as bsf and bsr if the feature is masked off, like on older cpus
+    { opcode: 0xF30FBC, os: 1, e: 1, mask_flags: of | sf | af | pf, custom: 1 }, // tzcnt
+    { opcode: 0xF30FBD, os: 1, e: 1, mask_flags: of | sf | af | pf, custom: 1 }, // lzcnt
 
     // note: overflow flag only undefined if shift is > 1
     { opcode: 0x0FA4, os: 1, e: 1, custom: 1, imm8: 1, mask_flags: af | of }, // shld
@@ -448,22 +454,23 @@ const encodings = [
 
     { opcode: 0x0FAA, skip: 1 },
 
-    { opcode: 0x0FAE, e: 1, fixed_g: 0, reg_ud: 1, task_switch_test: 1, skip: 1, block_boundary: 1 }, // fxsave
-    { opcode: 0x0FAE, e: 1, fixed_g: 1, reg_ud: 1, task_switch_test: 1, skip: 1, block_boundary: 1 }, // fxrstor
-    { opcode: 0x0FAE, e: 1, fixed_g: 2, reg_ud: 1, sse: 1, skip: 1, block_boundary: 1 }, // ldmxcsr
-    { opcode: 0x0FAE, e: 1, fixed_g: 3, reg_ud: 1, sse: 1, skip: 1, block_boundary: 1 }, // stmxcsr
+    { opcode: 0x0FAE, e: 1, fixed_g: 0, cpuid: "fxsr", reg_ud: 1, task_switch_test: 1, skip: 1, block_boundary: 1 }, // fxsave
+    { opcode: 0x0FAE, e: 1, fixed_g: 1, cpuid: "fxsr", reg_ud: 1, task_switch_test: 1, skip: 1, block_boundary: 1 }, // fxrstor
+    { opcode: 0x0FAE, e: 1, fixed_g: 2, reg_ud: 1, sse: 1, cpuid: "sse", skip: 1, block_boundary: 1 }, // ldmxcsr
+    { opcode: 0x0FAE, e: 1, fixed_g: 3, reg_ud: 1, sse: 1, cpuid: "sse", skip: 1, block_boundary: 1 }, // stmxcsr
 
-    { opcode: 0x0FAE, e: 1, fixed_g: 4, reg_ud: 1, skip: 1, block_boundary: 1 }, // xsave (mem, not implemented)
-    { opcode: 0x0FAE, e: 1, fixed_g: 5, skip: 1, custom: 1 }, // lfence (reg, only 0), xrstor (mem, not implemented)
-    { opcode: 0x0FAE, e: 1, fixed_g: 6, skip: 1, block_boundary: 1 }, // mfence (reg, only 0), xsaveopt (mem, not implemented)
-    { opcode: 0x0FAE, e: 1, fixed_g: 7, skip: 1, block_boundary: 1 }, // sfence (reg, only 0), clflush (mem)
+    { opcode: 0x0FAE, e: 1, fixed_g: 4, cpuid: "xsave", reg_ud: 1, skip: 1, block_boundary: 1 }, // xsave
+    { opcode: 0x0FAE, e: 1, fixed_g: 5, cpuid_reg: "sse2", cpuid_mem: "xsave", mem_block_boundary: 1, skip: 1, custom: 1 }, // lfence (reg, only 0), xrstor (mem)
+    { opcode: 0x0FAE, e: 1, fixed_g: 6, cpuid_reg: "sse2", cpuid_mem: "xsaveopt", skip: 1, block_boundary: 1 }, // mfence (reg, only 0), xsaveopt (mem)
+    { opcode: 0x0FAE, e: 1, fixed_g: 7, cpuid_reg: "sse", cpuid_mem: "clfsh", skip: 1, block_boundary: 1 }, // sfence (reg, only 0), clflush (mem)
 
     { opcode: 0x0FAF, os: 1, e: 1, mask_flags: TESTS_ASSUME_INTEL ? af | zf : sf | zf | af | pf, custom: 1 }, // imul
 
     { opcode: 0x0FB0, e: 1 }, // cmxchg
     { opcode: 0x0FB1, os: 1, e: 1, custom: 1 },
-    { opcode: 0x0FC7, e: 1, fixed_g: 1, os: 1, reg_ud: 1, custom: 1 }, // cmpxchg8b (memory)
-    { opcode: 0x0FC7, e: 1, fixed_g: 6, os: 1, mem_ud: 1, skip: 1 }, // rdrand
+    { opcode: 0x0FC7, e: 1, fixed_g: 1, cpuid: "cx8", os: 1, reg_ud: 1, custom: 1 }, // cmpxchg8b (memory)
+    { opcode: 0x0FC7, e: 1, fixed_g: 4, cpuid: "xsavec", reg_ud: 1, skip: 1, block_boundary: 1 }, // xsavec
+    { opcode: 0x0FC7, e: 1, fixed_g: 6, cpuid: "rdrand", os: 1, mem_ud: 1, skip: 1 }, // rdrand
 
     { opcode: 0x0FB2, block_boundary: 1, os: 1, e: 1, skip: 1 }, // lss
     { opcode: 0x0FB4, block_boundary: 1, os: 1, e: 1, skip: 1 }, // lfs
@@ -472,7 +479,7 @@ const encodings = [
     { opcode: 0x0FB6, os: 1, e: 1, custom: 1 }, // movzx
     { opcode: 0x0FB7, os: 1, e: 1, custom: 1 },
 
-    { opcode: 0xF30FB8, os: 1, e: 1, custom: 1 }, // popcnt
+    { opcode: 0xF30FB8, cpuid: "popcnt", os: 1, e: 1, custom: 1 }, // popcnt
     { opcode: 0x0FB8, os: 1, e: 1, block_boundary: 1 }, // ud
 
     { opcode: 0x0FB9, block_boundary: 1 }, // ud2
@@ -495,183 +502,183 @@ const encodings = [
 
     // mmx, sse
 
-    { sse: 1, opcode: 0x0F10, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F10, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F10, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F10, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F11, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F11, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F11, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F11, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F12, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F12, reg_ud: 1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F12, e: 1, custom: 1 }, // sse3
-    { sse: 1, opcode: 0xF30F12, e: 1, custom: 1 }, // sse3
-    { sse: 1, opcode: 0x0F13, reg_ud: 1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F13, reg_ud: 1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F14, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F14, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F15, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F15, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F16, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F16, reg_ud: 1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F16,  e: 1, custom: 1 }, // sse3
-    { sse: 1, opcode: 0x0F17, reg_ud: 1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F17, reg_ud: 1, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F28, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F28, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F29, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F29, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F2A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F2A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F2A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F2A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F2B, reg_ud: 1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F2B, reg_ud: 1, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F2C, e: 1 },
-    { sse: 1, opcode: 0x660F2C, e: 1 },
-    { sse: 1, opcode: 0xF20F2C, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F2C, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F2D, e: 1 },
-    { sse: 1, opcode: 0x660F2D, e: 1 },
-    { sse: 1, opcode: 0xF20F2D, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F2D, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F2E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F2E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F2F, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F2F, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F50, mem_ud: 1, e: 1 },
-    { sse: 1, opcode: 0x660F50, mem_ud: 1, e: 1 },
-    { sse: 1, opcode: 0x0F51, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F51, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F51, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F51, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F10, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F10, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F10, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F10, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F11, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F11, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F11, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F11, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F12, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F12, reg_ud: 1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse3", opcode: 0xF20F12, e: 1, custom: 1 }, // sse3
+    { sse: 1, cpuid: "sse3", opcode: 0xF30F12, e: 1, custom: 1 }, // sse3
+    { sse: 1, cpuid: "sse", opcode: 0x0F13, reg_ud: 1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F13, reg_ud: 1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F14, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F14, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F15, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F15, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F16, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F16, reg_ud: 1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse3", opcode: 0xF30F16,  e: 1, custom: 1 }, // sse3
+    { sse: 1, cpuid: "sse", opcode: 0x0F17, reg_ud: 1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F17, reg_ud: 1, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F28, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F28, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F29, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F29, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F2A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F2A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F2A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F2A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F2B, reg_ud: 1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F2B, reg_ud: 1, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F2C, e: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F2C, e: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F2C, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F2C, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F2D, e: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F2D, e: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F2D, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F2D, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F2E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F2E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F2F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F2F, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F50, mem_ud: 1, e: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F50, mem_ud: 1, e: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F51, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F51, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F51, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F51, e: 1, custom: 1 },
 
     // approximation of 1/sqrt(x). Skipped because our approximation doesn't match intel's
-    { sse: 1, opcode: 0x0F52, e: 1, skip: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F52, e: 1, skip: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F52, e: 1, skip: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F52, e: 1, skip: 1, custom: 1 },
 
     // reciprocal: approximation of 1/x. Skipped because our approximation doesn't match intel's
-    { sse: 1, opcode: 0x0F53, e: 1, skip: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F53, e: 1, skip: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F54, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F54, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F55, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F55, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F56, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F56, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F57, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F57, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F58, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F58, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F58, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F58, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F59, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F59, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F59, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F59, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F5A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F5A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F5A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F5A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F5B, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F5B, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F53, e: 1, skip: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F53, e: 1, skip: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F54, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F54, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F55, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F55, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F56, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F56, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F57, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F57, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F58, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F58, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F58, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F58, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F59, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F59, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F59, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F59, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F5A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F5A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F5A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F5A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F5B, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F5B, e: 1, custom: 1 },
     // no F2 variant
-    { sse: 1, opcode: 0xF30F5B, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F5C, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F5C, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F5C, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F5C, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F5D, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F5D, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F5D, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F5D, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F5E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F5E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F5E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F5E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F5F, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F5F, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F5F, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F5F, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x660F60, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F60, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F61, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F61, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F62, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F62, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F63, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F63, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F64, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F64, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F65, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F65, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F66, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F66, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F67, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F67, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x660F68, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F68, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F69, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F69, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F6A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F6A, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F6B, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F6B, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F6C, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F5B, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F5C, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F5C, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F5C, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F5C, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F5D, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F5D, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F5D, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F5D, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F5E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F5E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F5E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F5E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0F5F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F5F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F5F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F5F, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse2", opcode: 0x660F60, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F60, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F61, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F61, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F62, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F62, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F63, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F63, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F64, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F64, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F65, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F65, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F66, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F66, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F67, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F67, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse2", opcode: 0x660F68, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F68, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F69, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F69, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F6A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F6A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F6B, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F6B, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F6C, e: 1, custom: 1 },
     { sse: 1, opcode: 0x0F6C, e: 1, block_boundary: 1 }, // ud
-    { sse: 1, opcode: 0x660F6D, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F6D, e: 1, custom: 1 },
     { sse: 1, opcode: 0x0F6D, e: 1, block_boundary: 1 }, // ud
-    { sse: 1, opcode: 0x660F6E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F6E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F6F, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F6F, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F6F, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F70, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F70, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20F70, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F70, e: 1, imm8: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F71, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F71, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F71, e: 1, fixed_g: 4, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F71, e: 1, fixed_g: 4, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F71, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F71, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F72, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F72, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F72, e: 1, fixed_g: 4, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F72, e: 1, fixed_g: 4, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F72, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F72, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F73, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F73, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F73, e: 1, fixed_g: 3, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F73, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F73, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F73, e: 1, fixed_g: 7, imm8: 1, mem_ud: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0F74, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F74, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F75, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F75, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F76, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F76, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F77, skip: 1 }, // emms (skip as it breaks gdb printing of float registers)
+    { sse: 1, cpuid: "sse2", opcode: 0x660F6E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F6E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F6F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F6F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F6F, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0F70, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F70, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20F70, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F70, e: 1, imm8: 1, custom: 1 },
+
+    { sse: 1, cpuid: "mmx", opcode: 0x0F71, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F71, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F71, e: 1, fixed_g: 4, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F71, e: 1, fixed_g: 4, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F71, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F71, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
+
+    { sse: 1, cpuid: "mmx", opcode: 0x0F72, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F72, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F72, e: 1, fixed_g: 4, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F72, e: 1, fixed_g: 4, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F72, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F72, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
+
+    { sse: 1, cpuid: "mmx", opcode: 0x0F73, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F73, e: 1, fixed_g: 2, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F73, e: 1, fixed_g: 3, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F73, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F73, e: 1, fixed_g: 6, imm8: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F73, e: 1, fixed_g: 7, imm8: 1, mem_ud: 1, custom: 1 },
+
+    { sse: 1, cpuid: "mmx", opcode: 0x0F74, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F74, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F75, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F75, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F76, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F76, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F77, skip: 1 }, // emms (skip as it breaks gdb printing of float registers)
 
     // vmx instructions
     { opcode: 0x0F78, skip: 1, block_boundary: 1 },
@@ -680,146 +687,244 @@ const encodings = [
     { opcode: 0x0F7A, skip: 1, block_boundary: 1 }, // ud
     { opcode: 0x0F7B, skip: 1, block_boundary: 1 }, // ud
 
-    { sse: 1, opcode: 0x660F7C, e: 1, custom: 1 }, // sse3
-    { sse: 1, opcode: 0xF20F7C, e: 1, custom: 1 }, // sse3
-    { sse: 1, opcode: 0x660F7D, e: 1, custom: 1 }, // sse3
-    { sse: 1, opcode: 0xF20F7D, e: 1, custom: 1 }, // sse3
+    { sse: 1, cpuid: "sse3", opcode: 0x660F7C, e: 1, custom: 1 }, // sse3
+    { sse: 1, cpuid: "sse3", opcode: 0xF20F7C, e: 1, custom: 1 }, // sse3
+    { sse: 1, cpuid: "sse3", opcode: 0x660F7D, e: 1, custom: 1 }, // sse3
+    { sse: 1, cpuid: "sse3", opcode: 0xF20F7D, e: 1, custom: 1 }, // sse3
 
     { opcode: 0x0F7C, skip: 1, block_boundary: 1 }, // ud
     { opcode: 0x0F7D, skip: 1, block_boundary: 1 }, // ud
 
-    { sse: 1, opcode: 0x0F7E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F7E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F7E, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0F7F, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660F7F, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30F7F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F7E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F7E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F7E, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0F7F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660F7F, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30F7F, e: 1, custom: 1 },
 
-    { sse: 1, opcode: 0x0FC2, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FC2, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20FC2, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30FC2, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FC2, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FC2, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20FC2, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30FC2, e: 1, imm8: 1, custom: 1 },
 
-    { opcode: 0x0FC3, e: 1, custom: 1, reg_ud: 1 }, // movnti: Uses normal registers, hence not marked as sse
+    { opcode: 0x0FC3, cpuid: "sse2", e: 1, custom: 1, reg_ud: 1 }, // movnti: Uses normal registers, hence not marked as sse
 
-    { sse: 1, opcode: 0x0FC4, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FC4, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FC5, e: 1, mem_ud: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FC5, e: 1, mem_ud: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FC4, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FC4, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FC5, e: 1, mem_ud: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FC5, e: 1, mem_ud: 1, imm8: 1, custom: 1 },
 
-    { sse: 1, opcode: 0x0FC6, e: 1, imm8: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FC6, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FC6, e: 1, imm8: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FC6, e: 1, imm8: 1, custom: 1 },
 
     { sse: 1, opcode: 0x0FD0, skip: 1, block_boundary: 1 }, // sse3
 
-    { sse: 1, opcode: 0x0FD1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FD1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FD2, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FD2, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FD3, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FD3, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FD4, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FD4, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FD5, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FD5, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x660FD6, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20FD6, mem_ud: 1, e: 1 },
-    { sse: 1, opcode: 0xF30FD6, mem_ud: 1, e: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FD1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FD2, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD2, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FD3, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD3, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x0FD4, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD4, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FD5, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD5, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD6, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20FD6, mem_ud: 1, e: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30FD6, mem_ud: 1, e: 1 },
     { sse: 1, opcode: 0x0FD6, e: 1, block_boundary: 1 }, // ud
 
-    { sse: 1, opcode: 0x0FD7, e: 1, mem_ud: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FD7, e: 1, mem_ud: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0FD8, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FD8, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FD9, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FD9, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FDA, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FDA, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FDB, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FDB, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FDC, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FDC, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FDD, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FDD, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FDE, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FDE, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FDF, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FDF, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0FE0, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FE0, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FE1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FE1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FE2, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FE2, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FE3, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FE3, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FE4, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FE4, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FE5, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FE5, e: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x660FE6, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF20FE6, e: 1, custom: 1 },
-    { sse: 1, opcode: 0xF30FE6, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FD7, e: 1, mem_ud: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD7, e: 1, mem_ud: 1, custom: 1 },
+
+    { sse: 1, cpuid: "mmx", opcode: 0x0FD8, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD8, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FD9, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FD9, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FDA, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FDA, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FDB, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FDB, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FDC, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FDC, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FDD, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FDD, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FDE, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FDE, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FDF, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FDF, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse", opcode: 0x0FE0, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE0, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FE1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FE2, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE2, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FE3, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE3, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FE4, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE4, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FE5, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE5, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE6, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF20FE6, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0xF30FE6, e: 1, custom: 1 },
     { sse: 1, opcode: 0x0FE6, e: 1, block_boundary: 1 }, // ud
-    { sse: 1, opcode: 0x0FE7, e: 1, reg_ud: 1 },
-    { sse: 1, opcode: 0x660FE7, e: 1, reg_ud: 1, custom: 1 },
-
-    { sse: 1, opcode: 0x0FE8, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FE8, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FE9, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FE9, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FEA, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FEA, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FEB, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FEB, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FEC, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FEC, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FED, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FED, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FEE, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FEE, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FEF, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FEF, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FE7, e: 1, reg_ud: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE7, e: 1, reg_ud: 1, custom: 1 },
+
+    { sse: 1, cpuid: "mmx", opcode: 0x0FE8, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE8, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FE9, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FE9, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FEA, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FEA, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FEB, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FEB, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FEC, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FEC, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FED, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FED, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FEE, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FEE, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FEF, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FEF, e: 1, custom: 1 },
 
     { sse: 1, opcode: 0x0FF0, skip: 1, block_boundary: 1 }, // sse3
 
-    { sse: 1, opcode: 0x0FF1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FF1, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FF2, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FF2, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FF3, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FF3, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FF4, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FF4, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FF5, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FF5, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FF6, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FF6, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FF1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FF2, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF2, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FF3, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF3, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x0FF4, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF4, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FF5, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF5, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FF6, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF6, e: 1, custom: 1 },
     // maskmovq (0FF7), maskmovdqu (660FF7) tested manually
     // Generated tests don't setup EDI as required (yet)
-    { sse: 1, opcode: 0x0FF7, mem_ud: 1, e: 1, custom: 1, skip: 1 },
-    { sse: 1, opcode: 0x660FF7, mem_ud: 1, e: 1, custom: 1, skip: 1 },
-
-    { sse: 1, opcode: 0x0FF8, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FF8, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FF9, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FF9, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FFA, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FFA, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FFB, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FFB, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FFC, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FFC, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FFD, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FFD, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x0FFE, e: 1, custom: 1 },
-    { sse: 1, opcode: 0x660FFE, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse", opcode: 0x0FF7, mem_ud: 1, e: 1, custom: 1, skip: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF7, mem_ud: 1, e: 1, custom: 1, skip: 1 },
+
+    { sse: 1, cpuid: "mmx", opcode: 0x0FF8, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF8, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FF9, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FF9, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FFA, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FFA, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x0FFB, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FFB, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FFC, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FFC, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FFD, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FFD, e: 1, custom: 1 },
+    { sse: 1, cpuid: "mmx", opcode: 0x0FFE, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse2", opcode: 0x660FFE, e: 1, custom: 1 },
 
     { opcode: 0x0FFF, block_boundary: 1 }, // ud
+
+    // ssse3
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3800, e: 1, custom: 1 }, // pshufb
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3800, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3801, e: 1, custom: 1 }, // phaddw
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3801, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3802, e: 1, custom: 1 }, // phaddd
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3802, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3803, e: 1, custom: 1 }, // phaddsw
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3803, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3804, e: 1, custom: 1 }, // pmaddubsw
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3804, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3805, e: 1, custom: 1 }, // phsubw
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3805, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3806, e: 1, custom: 1 }, // phsubd
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3806, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3807, e: 1, custom: 1 }, // phsubsw
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3807, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3808, e: 1, custom: 1 }, // psignb
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3808, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3809, e: 1, custom: 1 }, // psignw
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3809, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F380A, e: 1, custom: 1 }, // psignd
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F380A, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F380B, e: 1, custom: 1 }, // pmulhrsw
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F380B, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F381C, e: 1, custom: 1 }, // pabsb
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F381C, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F381D, e: 1, custom: 1 }, // pabsw
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F381D, e: 1, custom: 1 },
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F381E, e: 1, custom: 1 }, // pabsd
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F381E, e: 1, custom: 1 },
+
+    { sse: 1, cpuid: "ssse3", opcode: 0x0F3A0F, e: 1, imm8: 1, custom: 1 }, // palignr
+    { sse: 1, cpuid: "ssse3", opcode: 0x660F3A0F, e: 1, imm8: 1, custom: 1 },
+
+    // sse4.1
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3810, e: 1, custom: 1 }, // pblendvb
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3814, e: 1, custom: 1 }, // blendvps
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3815, e: 1, custom: 1 }, // blendvpd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3817, e: 1, custom: 1 }, // ptest
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3820, e: 1, custom: 1 }, // pmovsxbw
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3821, e: 1, custom: 1 }, // pmovsxbd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3822, e: 1, custom: 1 }, // pmovsxbq
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3823, e: 1, custom: 1 }, // pmovsxwd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3824, e: 1, custom: 1 }, // pmovsxwq
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3825, e: 1, custom: 1 }, // pmovsxdq
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3828, e: 1, custom: 1 }, // pmuldq
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3829, e: 1, custom: 1 }, // pcmpeqq
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F382A, reg_ud: 1, e: 1, custom: 1 }, // movntdqa
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F382B, e: 1, custom: 1 }, // packusdw
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3830, e: 1, custom: 1 }, // pmovzxbw
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3831, e: 1, custom: 1 }, // pmovzxbd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3832, e: 1, custom: 1 }, // pmovzxbq
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3833, e: 1, custom: 1 }, // pmovzxwd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3834, e: 1, custom: 1 }, // pmovzxwq
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3835, e: 1, custom: 1 }, // pmovzxdq
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3838, e: 1, custom: 1 }, // pminsb
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3839, e: 1, custom: 1 }, // pminsd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F383A, e: 1, custom: 1 }, // pminuw
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F383B, e: 1, custom: 1 }, // pminud
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F383C, e: 1, custom: 1 }, // pmaxsb
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F383D, e: 1, custom: 1 }, // pmaxsd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F383E, e: 1, custom: 1 }, // pmaxuw
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F383F, e: 1, custom: 1 }, // pmaxud
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3840, e: 1, custom: 1 }, // pmulld
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3841, e: 1, custom: 1 }, // phminposuw
+
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A08, e: 1, imm8: 1, custom: 1 }, // roundps
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A09, e: 1, imm8: 1, custom: 1 }, // roundpd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A0A, e: 1, imm8: 1, custom: 1 }, // roundss
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A0B, e: 1, imm8: 1, custom: 1 }, // roundsd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A0C, e: 1, imm8: 1, custom: 1 }, // blendps
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A0D, e: 1, imm8: 1, custom: 1 }, // blendpd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A0E, e: 1, imm8: 1, custom: 1 }, // pblendw
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A14, e: 1, imm8: 1, custom: 1 }, // pextrb
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A15, e: 1, imm8: 1, custom: 1 }, // pextrw
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A16, e: 1, imm8: 1, custom: 1 }, // pextrd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A17, e: 1, imm8: 1, custom: 1 }, // extractps
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A20, e: 1, imm8: 1, custom: 1 }, // pinsrb
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A21, e: 1, imm8: 1, custom: 1 }, // insertps
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A22, e: 1, imm8: 1, custom: 1 }, // pinsrd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A40, e: 1, imm8: 1, custom: 1 }, // dpps
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A41, e: 1, imm8: 1, custom: 1 }, // dppd
+    { sse: 1, cpuid: "sse4.1", opcode: 0x660F3A42, e: 1, imm8: 1, custom: 1 }, // mpsadbw
+
+    // movbe (must precede crc32, so that the shared opcodes are generated per operand size)
+    { opcode: 0x0F38F0, cpuid: "movbe", os: 1, e: 1, reg_ud: 1, custom: 1 },
+    { opcode: 0x0F38F1, cpuid: "movbe", os: 1, e: 1, reg_ud: 1, custom: 1 },
+
+    // sse4.2
+    { sse: 1, cpuid: "sse4.2", opcode: 0x660F3837, e: 1, custom: 1 }, // pcmpgtq
+    { opcode: 0xF20F38F0, cpuid: "sse4.2", e: 1, custom: 1 }, // crc32
+    { opcode: 0xF20F38F1, cpuid: "sse4.2", os: 1, e: 1, custom: 1 },
+    { sse: 1, cpuid: "sse4.2", opcode: 0x660F3A60, e: 1, imm8: 1, custom: 1 }, // pcmpestrm
+    { sse: 1, cpuid: "sse4.2", opcode: 0x660F3A61, e: 1, imm8: 1, custom: 1 }, // pcmpestri
+    { sse: 1, cpuid: "sse4.2", opcode: 0x660F3A62, e: 1, imm8: 1, custom: 1 }, // pcmpistrm
+    { sse: 1, cpuid: "sse4.2", opcode: 0x660F3A63, e: 1, imm8: 1, custom: 1 }, // pcmpistri
 ];
 
 for(let i = 0; i < 8; i++)
@@ -858,8 +963,8 @@ for(let i = 0; i < 8; i++)
         { opcode: 0xD2, e: 1, fixed_g: i, mask_flags: of | af, custom: 1 },
         { opcode: 0xD3, os: 1, e: 1, fixed_g: i, mask_flags: of | af, custom: 1 },
 
-        { opcode: 0x0F40 | i, e: 1, os: 1, custom: 1 },
-        { opcode: 0x0F48 | i, e: 1, os: 1, custom: 1 },
+        { opcode: 0x0F40 | i, e: 1, os: 1, cpuid: "cmov", custom: 1 },
+        { opcode: 0x0F48 | i, e: 1, os: 1, cpuid: "cmov", custom: 1 },
 
         { opcode: 0x0F80 | i, block_boundary: 1, no_block_boundary_in_interpreted: 1, jump_offset_imm: 1, conditional_jump: 1, imm1632: 1, os: 1, custom: 1, skip: 1 },
         { opcode: 0x0F88 | i, block_boundary: 1, no_block_boundary_in_interpreted: 1, jump_offset_imm: 1, conditional_jump: 1, imm1632: 1, os: 1, custom: 1, skip: 1 },
@@ -869,9 +974,15 @@ for(let i = 0; i < 8; i++)
     ]);
 }
 
+function opcode_sort_key(opcode)
+{
+    if(is_three_byte_opcode(opcode)) return opcode & 0xFFFFFF;
+    return (opcode & 0xFF00) === 0x0F00 ? opcode & 0xFFFF : opcode & 0xFF;
+}
+
 encodings.sort((e1, e2) => {
-    let o1 = (e1.opcode & 0xFF00) === 0x0F00 ? e1.opcode & 0xFFFF : e1.opcode & 0xFF;
-    let o2 = (e2.opcode & 0xFF00) === 0x0F00 ? e2.opcode & 0xFFFF : e2.opcode & 0xFF;
+    let o1 = opcode_sort_key(e1.opcode);
+    let o2 = opcode_sort_key(e2.opcode);
     return o1 - o2 || e1.fixed_g - e2.fixed_g;
 });
 
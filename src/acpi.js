@@ -37,7 +37,7 @@ function ACPI(cpu)
     this.status = 1;
     this.pm1_status = 0;
     this.pm1_enable = 0;
-    this.last_timer = this.get_timer(v86.microtick());
+    this.last_timer = this.get_timer(this.cpu.microtick());
 
     this.gpe = new Uint8Array(4);
 
@@ -78,7 +78,7 @@ function ACPI(cpu)
     // ACPI, pmtimer
     io.register_read(0xB008, this, undefined, undefined, function()
     {
-        var value = this.get_timer(v86.microtick()) & 0xFFFFFF;
+        var value = this.get_timer(this.cpu.microtick()) & 0xFFFFFF;
         //dbg_log("pmtimer read: " + h(value >>> 0), LOG_ACPI);
         return value;
     });
@@ -54,7 +54,10 @@ function APIC(cpu)
     this.timer_initial_count = 0;
     this.timer_current_count = 0;
 
-    this.next_tick = v86.microtick();
+    // time (as returned by microtick) of the tsc deadline, 0 if disarmed
+    this.tsc_deadline = 0;
+
+    this.next_tick = this.cpu.microtick();
 
     this.lvt_timer = IOAPIC_CONFIG_MASKED;
     this.lvt_perf_counter = IOAPIC_CONFIG_MASKED;
@@ -323,6 +326,13 @@ APIC.prototype.write32 = function(addr, value)
 
         case 0x320:
             dbg_log("timer lvt: " + h(value >>> 0, 8), LOG_APIC);
+            if(((this.lvt_timer & APIC_TIMER_MODE_MASK) === APIC_TIMER_MODE_TSC) !==
+                ((value & APIC_TIMER_MODE_MASK) === APIC_TIMER_MODE_TSC))
+            {
+                // switching to or from tsc-deadline mode disarms the timer
+                this.timer_current_count = 0;
+                this.tsc_deadline = 0;
+            }
             this.lvt_timer = value;
             break;
 
@@ -356,10 +366,15 @@ APIC.prototype.write32 = function(addr, value)
 
         case 0x380:
             dbg_log("timer initial: " + h(value >>> 0, 8), LOG_APIC);
+            if((this.lvt_timer & APIC_TIMER_MODE_MASK) === APIC_TIMER_MODE_TSC)
+            {
+                // ignored in tsc-deadline mode
+                break;
+            }
             this.timer_initial_count = value >>> 0;
             this.timer_current_count = value >>> 0;
 
-            this.next_tick = v86.microtick();
+            this.next_tick = this.cpu.microtick();
             this.timer_active = true;
             break;
 
@@ -376,6 +391,28 @@ APIC.prototype.write32 = function(addr, value)
 
 APIC.prototype.timer = function(now)
 {
+    if((this.lvt_timer & APIC_TIMER_MODE_MASK) === APIC_TIMER_MODE_TSC)
+    {
+        if(this.tsc_deadline === 0)
+        {
+            return 100;
+        }
+
+        if(now < this.tsc_deadline)
+        {
+            return this.tsc_deadline - now;
+        }
+
+        this.tsc_deadline = 0;
+        dbg_log("APIC tsc deadline reached", LOG_APIC);
+
+        if((this.lvt_timer & IOAPIC_CONFIG_MASKED) === 0)
+        {
+            this.deliver(this.lvt_timer & 0xFF, IOAPIC_DELIVERY_FIXED, false);
+        }
+        return 100;
+    }
+
     if(this.timer_current_count === 0)
     {
         return 100;
@@ -422,6 +459,37 @@ APIC.prototype.timer = function(now)
     return Math.max(0, this.timer_current_count / freq);
 };
 
+/**
+ * Called by the cpu when the IA32_TSC_DEADLINE msr is written
+ * @param {number} time as returned by microtick, 0 disarms the timer
+ */
+APIC.prototype.set_tsc_deadline = function(time)
+{
+    if((this.lvt_timer & APIC_TIMER_MODE_MASK) !== APIC_TIMER_MODE_TSC)
+    {
+        // writes are ignored in the other timer modes
+        return;
+    }
+    dbg_log("tsc deadline: " + time, LOG_APIC);
+    this.tsc_deadline = time;
+
+    const now = this.cpu.microtick();
+    if(time !== 0 && time <= now)
+    {
+        // a deadline in the past fires right away
+        this.timer(now);
+    }
+};
+
+APIC.prototype.get_tsc_deadline = function()
+{
+    if((this.lvt_timer & APIC_TIMER_MODE_MASK) !== APIC_TIMER_MODE_TSC)
+    {
+        return 0;
+    }
+    return this.tsc_deadline;
+};
+
 APIC.prototype.route = function(vector, mode, is_level, destination, destination_mode)
 {
     // TODO
@@ -511,6 +579,15 @@ APIC.prototype.check_vector = function()
     this.cpu.handle_irqs();
 };
 
+APIC.prototype.has_pending_irq = function()
+{
+    var highest_irr = this.highest_irr();
+
+    return highest_irr !== -1 &&
+        highest_irr > this.highest_isr() &&
+        (highest_irr & 0xF0) > (this.tpr & 0xF0);
+};
+
 APIC.prototype.acknowledge_irq = function()
 {
     var highest_irr = this.highest_irr();
@@ -571,6 +648,7 @@ APIC.prototype.get_state = function()
     state[19] = this.local_destination;
     state[20] = this.error;
     state[21] = this.read_error;
+    state[22] = this.tsc_deadline;
 
     return state;
 };
@@ -599,6 +677,7 @@ APIC.prototype.set_state = function(state)
     this.local_destination = state[19];
     this.error = state[20];
     this.read_error = state[21];
+    this.tsc_deadline = state[22] || 0;
 };
 
 // functions operating on 256-bit registers (for irr, isr, tmr)
@@ -60,6 +60,22 @@
  *   - `container HTMLElement` - An HTMLElement, see above.
  *   - `disable_autoscale boolean` (false) - Disable automatic scaling of small resolutions.
  *
+ * - `icount number` (Disabled) - Derive time (the timestamp counter and the
+ *   timers) from the number of executed instructions, at the given number of
+ *   instructions per second, instead of the host's clock. Runs are
+ *   reproducible, except for input from the host (keyboard, network, disk
+ *   images loaded with `async: true`). Disables the JIT.
+ *
+ * - `record boolean` (false) - Record the nondeterministic inputs of the CPU
+ *   (interrupts, reads from ports and memory-mapped devices, `rdtsc` and
+ *   `rdrand`) from boot or from `initial_state`, see `get_record_log`.
+ *   Disables the JIT.
+ *
+ * - `replay ArrayBuffer` (No replay) - A log created by `record`, whose inputs
+ *   are fed to the CPU instead of the ones of the devices, so that a recorded
+ *   run is reproduced exactly. Needs the same configuration, disk images and
+ *   `initial_state` as the recording. Disables the JIT.
+ *
  * ***
  *
  * There are two ways to load images (`bios`, `vga_bios`, `cdrom`, `hda`, ...):
@@ -137,6 +153,9 @@ function V86(options)
         "microtick": v86.microtick,
         "get_rand_int": function() { return v86util.get_rand_int(); },
         "apic_acknowledge_irq": function() { return cpu.devices.apic.acknowledge_irq(); },
+        "apic_has_pending_irq": function() { return cpu.devices.apic.has_pending_irq(); },
+        "apic_set_tsc_deadline": function(t) { cpu.devices.apic.set_tsc_deadline(t); },
+        "apic_get_tsc_deadline": function() { return cpu.devices.apic.get_tsc_deadline(); },
         "stop_idling": function() { return cpu.stop_idling(); },
 
         "io_port_read8": function(addr) { return cpu.io.port_read8(addr); },
@@ -303,6 +322,12 @@ V86.prototype.continue_init = async function(emulator, options)
     settings.preserve_mac_from_state_image = options.preserve_mac_from_state_image;
     settings.mac_address_translation = options.mac_address_translation;
     settings.cpuid_level = options.cpuid_level;
+    settings.cpu_model = options.cpu_model;
+    settings.segment_checks = options.segment_checks;
+    settings.stop_on_triple_fault = options.stop_on_triple_fault;
+    settings.icount = options.icount;
+    settings.record = options.record;
+    settings.replay = options.replay;
     settings.virtio_console = options.virtio_console;
     settings.virtio_net = options.virtio_net;
     settings.screen_options = options.screen_options;
@@ -694,7 +719,8 @@ V86.prototype.zstd_decompress_worker = async function(decompressed_size, src)
                     const env = Object.fromEntries([
                         "cpu_exception_hook", "run_hardware_timers",
                         "cpu_event_halt", "microtick", "get_rand_int",
-                        "apic_acknowledge_irq", "stop_idling",
+                        "apic_acknowledge_irq", "apic_has_pending_irq", "stop_idling",
+                        "apic_set_tsc_deadline", "apic_get_tsc_deadline",
                         "io_port_read8", "io_port_read16", "io_port_read32",
                         "io_port_write8", "io_port_write16", "io_port_write32",
                         "mmap_read8", "mmap_read16", "mmap_read32",
@@ -922,6 +948,56 @@ V86.prototype.get_instruction_counter = function()
     }
 };
 
+/**
+ * The log recorded so far when started with `record: true`, to be passed as
+ * `replay` to reproduce this run.
+ *
+ * @return {ArrayBuffer}
+ * @export
+ */
+V86.prototype.get_record_log = function()
+{
+    return this.v86.cpu.get_record_log();
+};
+
+/**
+ * Whether a replay is still running. Ends at the end of the log, or when the
+ * execution diverges from the log (see `replay_diverged`), after which the
+ * emulator continues with the inputs of the devices.
+ *
+ * @return {boolean}
+ * @export
+ */
+V86.prototype.is_replaying = function()
+{
+    return this.v86.cpu.replay_get_mode() === 2;
+};
+
+/**
+ * @return {boolean}
+ * @export
+ */
+V86.prototype.replay_diverged = function()
+{
+    return this.v86.cpu.replay_get_mode() === 3;
+};
+
+/**
+ * Why the cpu was last shut down by a triple fault: The vectors of the
+ * exception, of the one that turned it into a double fault and of the one
+ * raised while delivering the double fault, and eip and cr2 at that time.
+ * null if no triple fault has happened. Also sent with the
+ * `cpu-event-shutdown` event, after which the emulator reboots unless started
+ * with `stop_on_triple_fault: true`.
+ *
+ * @return {?{vectors: !Array<number>, eip: number, cr2: number}}
+ * @export
+ */
+V86.prototype.get_shutdown_reason = function()
+{
+    return this.v86.cpu.get_shutdown_reason();
+};
+
 /**
  * @return {boolean}
  * @export
@@ -27,6 +27,7 @@ function CPU(bus, wm, stop_idling)
     this.segment_offsets = v86util.view(Int32Array, memory, 736, 8);
     this.segment_limits = v86util.view(Uint32Array, memory, 768, 8);
     this.segment_access_bytes = v86util.view(Uint8Array, memory, 512, 8);
+    this.segment_is_big = v86util.view(Uint8Array, memory, 520, 8);
 
     /**
      * Wheter or not in protected mode
@@ -67,6 +68,9 @@ function CPU(bus, wm, stop_idling)
     this.last_virt_eip = v86util.view(Int32Array, memory, 620, 1);
     this.eip_phys = v86util.view(Int32Array, memory, 624, 1);
 
+    this.efer = v86util.view(Int32Array, memory, 628, 1);
+    this.tsc_aux = v86util.view(Int32Array, memory, 632, 1);
+
 
     this.sysenter_cs = v86util.view(Int32Array, memory, 636, 1);
 
@@ -76,6 +80,9 @@ function CPU(bus, wm, stop_idling)
 
     this.prefixes = v86util.view(Int32Array, memory, 648, 1);
 
+    this.xcr0 = v86util.view(Int32Array, memory, 652, 1);
+    this.star = v86util.view(Int32Array, memory, 656, 2);
+
     this.flags = v86util.view(Int32Array, memory, 120, 1);
 
     /**
@@ -96,6 +103,12 @@ function CPU(bus, wm, stop_idling)
     /** @type {!Object} */
     this.devices = {};
 
+    // instructions per second in icount mode, 0 if disabled
+    this.icount = 0;
+
+    // stay in the shutdown state after a triple fault instead of rebooting
+    this.stop_on_triple_fault = false;
+
     this.instruction_pointer = v86util.view(Int32Array, memory, 556, 1);
     this.previous_ip = v86util.view(Int32Array, memory, 560, 1);
 
@@ -227,7 +240,7 @@ CPU.prototype.wasm_patch = function()
 
     this.handle_irqs = get_import("handle_irqs");
 
-    this.main_loop = get_import("main_loop");
+    this.main_loop_internal = get_import("main_loop");
 
     this.set_jit_config = get_import("set_jit_config");
 
@@ -256,7 +269,31 @@ CPU.prototype.wasm_patch = function()
     this.set_tsc = get_import("set_tsc");
     this.store_current_tsc = get_import("store_current_tsc");
 
+    // current time in milliseconds for the hardware timers (see cpu_microtick)
+    this.microtick = get_import("cpu_microtick");
+    this.set_icount = get_import("set_icount");
+    this.get_icount_instructions = get_import("get_icount_instructions");
+    this.set_icount_instructions = get_import("set_icount_instructions");
+    this.get_icount_rand_state = get_import("get_icount_rand_state");
+    this.set_icount_rand_state = get_import("set_icount_rand_state");
+
+    this.replay_record_start = get_import("replay_record_start");
+    this.replay_allocate_log = get_import("replay_allocate_log");
+    this.replay_start = get_import("replay_start");
+    this.replay_get_mode = get_import("replay_get_mode");
+    this.replay_log_pointer = get_import("replay_log_pointer");
+    this.replay_log_length = get_import("replay_log_length");
+
     this.set_cpuid_level = get_import("set_cpuid_level");
+    this.cpuid_set_model = get_import("cpuid_set_model");
+    this.cpuid_set_leaf = get_import("cpuid_set_leaf");
+    this.cpuid_get_leaf = get_import("cpuid_get_leaf");
+    this.set_segment_checks = get_import("set_segment_checks");
+    this.has_shutdown_reason = get_import("has_shutdown_reason");
+    this.take_shutdown_pending = get_import("take_shutdown_pending");
+    this.get_shutdown_reason_field = get_import("get_shutdown_reason");
+    this.get_mwait_state = get_import("get_mwait_state");
+    this.set_mwait_state = get_import("set_mwait_state");
 
     this.pic_set_irq = get_import("pic_set_irq");
     this.pic_clear_irq = get_import("pic_clear_irq");
@@ -420,6 +457,17 @@ CPU.prototype.get_state = function()
     state[82] = this.devices.virtio_console;
     state[83] = this.devices.virtio_net;
 
+    state[84] = this.efer[0];
+    state[85] = this.segment_is_big;
+    state[86] = this.tsc_aux[0];
+    state[87] = this.icount ? this.get_icount_instructions() : 0;
+    state[88] = this.xcr0[0];
+    state[89] = this.star;
+    state[90] = this.get_mwait_state(0);
+    state[91] = this.get_mwait_state(1);
+    state[92] = this.get_mwait_state(2);
+    state[93] = this.icount ? this.get_icount_rand_state() : 0;
+
     return state;
 };
 
@@ -526,6 +574,12 @@ CPU.prototype.set_state = function(state)
     this.dreg.set(state[41]);
     state[42] && this.reg_pdpte.set(state[42]);
 
+    if(this.icount)
+    {
+        // before the tsc, which is derived from the time
+        this.set_icount_instructions(state[87] || 0);
+    }
+
     this.set_tsc(state[43][0], state[43][1]);
 
     this.devices.virtio_9p && this.devices.virtio_9p.set_state(state[45]);
@@ -552,6 +606,14 @@ CPU.prototype.set_state = function(state)
     this.devices.virtio_console && this.devices.virtio_console.set_state(state[82]);
     this.devices.virtio_net && this.devices.virtio_net.set_state(state[83]);
 
+    this.efer[0] = state[84] || 0;
+    state[85] && this.segment_is_big.set(state[85]);
+    this.tsc_aux[0] = state[86] || 0;
+    this.xcr0[0] = state[88] || 1;
+    state[89] && this.star.set(state[89]);
+    this.set_mwait_state(state[90] === undefined ? -1 : state[90], !!state[91], !!state[92]);
+    this.icount && state[93] !== undefined && this.set_icount_rand_state(state[93]);
+
     this.fw_value = state[62];
 
     this.devices.ioapic && this.devices.ioapic.set_state(state[63]);
@@ -745,15 +807,29 @@ CPU.prototype.init = function(settings, device_bus)
         settings.initrd ? 64 * 1024 * 1024 : 1024 * 1024,
     );
 
-    if(settings.disable_jit)
+    if(settings.disable_jit || settings.icount || settings.record || settings.replay)
     {
+        // the jit compiles asynchronously, which makes runs in icount mode irreproducible, and
+        // doesn't count instructions exactly, which recording and replay rely on
         this.set_jit_config(0, 1);
     }
 
+    if(settings.icount)
+    {
+        // before the devices are created, which read the current time
+        this.icount = settings.icount;
+        this.set_icount(settings.icount);
+    }
+
     settings.cpuid_level && this.set_cpuid_level(settings.cpuid_level);
+    settings.segment_checks && this.set_segment_checks(true);
+    this.stop_on_triple_fault = !!settings.stop_on_triple_fault;
 
     this.acpi_enabled[0] = +settings.acpi;
 
+    // after acpi_enabled, which determines the apic feature bit
+    settings.cpu_model && this.set_cpu_model(settings.cpu_model);
+
     this.reset_cpu();
 
     var io = new IO(this);
@@ -841,7 +917,8 @@ CPU.prototype.init = function(settings, device_bus)
         }
         else if(value === FW_CFG_RAM_SIZE)
         {
-            this.fw_value = i32(this.memory_size[0]);
+            // 64-bit value (kvm-unit-test reads all of it)
+            this.fw_value = new Uint8Array(Int32Array.of(this.memory_size[0], 0).buffer);
         }
         else if(value === FW_CFG_NB_CPUS)
         {
@@ -916,6 +993,12 @@ CPU.prototype.init = function(settings, device_bus)
     io.register_write(0xA0, this, this.portA0_write);
     io.register_write(0xA1, this, this.portA1_write);
 
+    // Clears the latched ferr# signal (irq13) of the fpu
+    io.register_write(0xF0, this, function(out_byte)
+    {
+        this.pic_clear_irq(13);
+    });
+
     io.register_read(0x4D0, this, this.port4D0_read);
     io.register_read(0x4D1, this, this.port4D1_read);
     io.register_write(0x4D0, this, this.port4D0_write);
@@ -1019,12 +1102,223 @@ CPU.prototype.init = function(settings, device_bus)
         }
     }
 
+    if(settings.record)
+    {
+        this.replay_record_start();
+    }
+    else if(settings.replay)
+    {
+        this.start_replay(settings.replay);
+    }
+
     if(DEBUG)
     {
         this.debug.init();
     }
 };
 
+/**
+ * Run the cpu for a while, see main_loop in cpu.rs. Returns the time until
+ * the next call
+ * @return {number}
+ */
+CPU.prototype.main_loop = function()
+{
+    const t = this.main_loop_internal();
+
+    // A triple fault is handled here, as the cpu may have been deep inside of
+    // an exception or jitted code when it happened
+    if(this.take_shutdown_pending())
+    {
+        this.handle_shutdown();
+        return 0;
+    }
+
+    return t;
+};
+
+/**
+ * Called after a triple fault. Reboots like a pc, unless the
+ * stop_on_triple_fault setting is set
+ */
+CPU.prototype.handle_shutdown = function()
+{
+    const reason = this.get_shutdown_reason();
+    dbg_log("Triple fault: exceptions=" + reason.vectors.map(v => h(v)).join(",") +
+            " eip=" + h(reason.eip) + " cr2=" + h(reason.cr2), LOG_CPU);
+
+    this.bus.send("cpu-event-shutdown", reason);
+
+    if(!this.stop_on_triple_fault)
+    {
+        this.reboot_internal();
+    }
+};
+
+/**
+ * The reason of the last triple fault, or null if there was none
+ * @return {?{vectors: !Array<number>, eip: number, cr2: number}}
+ */
+CPU.prototype.get_shutdown_reason = function()
+{
+    if(!this.has_shutdown_reason())
+    {
+        return null;
+    }
+
+    return {
+        vectors: [0, 1, 2].map(i => this.get_shutdown_reason_field(i)),
+        eip: this.get_shutdown_reason_field(3) >>> 0,
+        cr2: this.get_shutdown_reason_field(4) >>> 0,
+    };
+};
+
+/**
+ * The log of the current or last recording, see cpu/replay.rs
+ * @return {ArrayBuffer}
+ */
+CPU.prototype.get_record_log = function()
+{
+    const ptr = this.replay_log_pointer() >>> 0;
+    return new Uint8Array(this.wasm_memory.buffer, ptr, this.replay_log_length()).slice().buffer;
+};
+
+/**
+ * @param {ArrayBuffer} log
+ */
+CPU.prototype.start_replay = function(log)
+{
+    const ptr = this.replay_allocate_log(log.byteLength) >>> 0;
+    new Uint8Array(this.wasm_memory.buffer, ptr, log.byteLength).set(new Uint8Array(log));
+
+    if(!this.replay_start())
+    {
+        console.warn("Note: Invalid or unsupported replay log, running without replay");
+    }
+};
+
+// date of the real time clock when starting in icount mode
+const ICOUNT_START_DATE = Date.UTC(2000, 0, 1);
+
+/**
+ * Milliseconds since the epoch, for the real time clock
+ */
+CPU.prototype.wall_clock = function()
+{
+    return this.icount ? ICOUNT_START_DATE + this.microtick() : Date.now();
+};
+
+// see cpu/cpuid.rs
+const CPU_MODELS = {
+    "builtin": 0,
+    "486": 1,
+    "pentium": 2,
+    "pentium3": 3,
+    "pentium4": 4,
+    "athlon": 5,
+};
+
+/**
+ * Install the cpuid model: Either the name of a preset (see CPU_MODELS) or an object like
+ * {
+ *     base: "pentium3", // preset to start from, the built-in model if not given
+ *     vendor: "GenuineIntel",
+ *     family: 6, model: 8, stepping: 1,
+ *     brand: "Intel(R) Pentium(R) III CPU", // leaves 0x80000002 to 0x80000004
+ *     leaves: [
+ *         // Registers replace the values of the base model, masks are applied afterwards.
+ *         // Without subleaf, the leaf applies to all subleaves
+ *         { leaf: 2, eax: 0x03020101, ebx: 0, ecx: 0, edx: 0x0C040843 },
+ *         { leaf: 1, mask_edx: ~(1 << 25) },
+ *     ],
+ * }
+ * Instructions of features that are masked off raise #ud.
+ */
+CPU.prototype.set_cpu_model = function(model)
+{
+    if(typeof model === "string")
+    {
+        model = { base: model };
+    }
+
+    const base = model.base || "builtin";
+    dbg_assert(CPU_MODELS[base] !== undefined, "Unknown cpu model: " + base);
+    this.cpuid_set_model(CPU_MODELS[base]);
+
+    const get_leaf = (leaf, subleaf) =>
+        [0, 1, 2, 3].map(i => this.cpuid_get_leaf(leaf, subleaf || 0, i));
+    const set_leaf = (leaf, subleaf, regs) =>
+        this.cpuid_set_leaf(leaf, subleaf === undefined ? -1 : subleaf, regs[0], regs[1], regs[2], regs[3]);
+
+    // little-endian dwords of a string, padded with zeros
+    const string_dwords = (str, count) =>
+    {
+        const dwords = [];
+        for(let i = 0; i < 4 * count; i += 4)
+        {
+            dwords.push(
+                str.charCodeAt(i) | str.charCodeAt(i + 1) << 8 |
+                str.charCodeAt(i + 2) << 16 | str.charCodeAt(i + 3) << 24
+            );
+        }
+        return dwords;
+    };
+
+    if(model.vendor !== undefined)
+    {
+        dbg_assert(model.vendor.length === 12);
+        const vendor = string_dwords(model.vendor, 3);
+        const regs = get_leaf(0);
+        set_leaf(0, undefined, [regs[0], vendor[0], vendor[2], vendor[1]]);
+    }
+
+    if(model.family !== undefined || model.model !== undefined || model.stepping !== undefined)
+    {
+        const regs = get_leaf(1);
+        const eax = regs[0];
+        const family = model.family !== undefined ? model.family : (eax >> 8 & 0xF) + (eax >> 20 & 0xFF);
+        const model_number = model.model !== undefined ? model.model : (eax >> 4 & 0xF) | (eax >> 16 & 0xF) << 4;
+        const stepping = model.stepping !== undefined ? model.stepping : eax & 0xF;
+        regs[0] = eax & ~0x0FFF0FFF |
+            stepping & 0xF |
+            (model_number & 0xF) << 4 |
+            Math.min(family, 15) << 8 |
+            model_number >> 4 << 16 |
+            Math.max(family - 15, 0) << 20;
+        set_leaf(1, undefined, regs);
+    }
+
+    if(model.brand !== undefined)
+    {
+        dbg_assert(model.brand.length < 48);
+        const brand = string_dwords(model.brand, 12);
+        for(let i = 0; i < 3; i++)
+        {
+            set_leaf(0x80000002 + i, undefined, brand.slice(4 * i, 4 * i + 4));
+        }
+        const regs = get_leaf(0x80000000);
+        regs[0] = Math.max(regs[0] >>> 0, 0x80000004);
+        set_leaf(0x80000000, undefined, regs);
+    }
+
+    for(const leaf of model.leaves || [])
+    {
+        const regs = get_leaf(leaf.leaf, leaf.subleaf);
+        ["eax", "ebx", "ecx", "edx"].forEach(function(name, i)
+        {
+            if(leaf[name] !== undefined)
+            {
+                regs[i] = leaf[name];
+            }
+            if(leaf["mask_" + name] !== undefined)
+            {
+                regs[i] &= leaf["mask_" + name];
+            }
+        });
+        set_leaf(leaf.leaf, leaf.subleaf, regs);
+    }
+};
+
 CPU.prototype.load_multiboot = function (buffer)
 {
     if(this.bios.main)
@@ -1297,9 +1591,10 @@ CPU.prototype.load_multiboot_option_rom = function(buffer, initrd, cmdline)
                 cpu.segment_offsets[i] = 0;
                 cpu.segment_limits[i] = 0xFFFFFFFF;
                 // cpu.segment_access_bytes[i]
-                // Value doesn't matter, OS isn't allowed to reload without setting
-                // up a proper GDT
-                cpu.sreg[i] = 0xB002;
+                // The OS isn't allowed to reload without setting up a proper GDT. Use
+                // the same selectors as qemu, as some kernels (and kvm-unit-test) save
+                // them in a TSS and reload them on a task switch
+                cpu.sreg[i] = i === REG_CS ? 0x08 : 0x10;
             }
             cpu.instruction_pointer[0] = cpu.get_seg_cs() + entrypoint | 0;
             cpu.update_state_flags();
@@ -37,7 +37,7 @@ function PIT(cpu, bus)
 
     cpu.io.register_read(0x61, this, function()
     {
-        var now = v86.microtick();
+        var now = this.cpu.microtick();
 
         var ref_toggle = (now * (1000 * 1000 / 15000)) & 1;
         var counter2_out = this.did_rollover(2, now);
@@ -215,7 +215,7 @@ PIT.prototype.counter_read = function(i)
             this.counter_next_low[i] ^= 1;
         }
 
-        var value = this.get_counter_value(i, v86.microtick());
+        var value = this.get_counter_value(i, this.cpu.microtick());
 
         if(next_low)
         {
@@ -252,7 +252,7 @@ PIT.prototype.counter_write = function(i, value)
 
         this.counter_enabled[i] = true;
 
-        this.counter_start_time[i] = v86.microtick();
+        this.counter_start_time[i] = this.cpu.microtick();
 
         dbg_log("counter" + i + " reload=" + h(this.counter_reload[i]) +
                 " tick=" + (this.counter_reload[i] || 0x10000) / OSCILLATOR_FREQ + "ms", LOG_PIT);
@@ -286,7 +286,7 @@ PIT.prototype.port43_write = function(reg_byte)
     {
         // latch
         this.counter_latch[i] = 2;
-        var value = this.get_counter_value(i, v86.microtick());
+        var value = this.get_counter_value(i, this.cpu.microtick());
         dbg_log("latch: " + value, LOG_PIT);
         this.counter_latch_value[i] = value ? value - 1 : 0;
 
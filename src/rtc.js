@@ -60,7 +60,7 @@ function RTC(cpu)
     this.cmos_data = new Uint8Array(128);
 
     // used for cmos entries
-    this.rtc_time = Date.now();
+    this.rtc_time = this.cpu.wall_clock();
     this.last_update = this.rtc_time;
 
     // used for periodic interrupt
@@ -128,7 +128,7 @@ RTC.prototype.set_state = function(state)
 
 RTC.prototype.timer = function(time, legacy_mode)
 {
-    time = Date.now(); // XXX
+    time = this.cpu.wall_clock(); // XXX
     this.rtc_time += time - this.last_update;
     this.last_update = time;
 
@@ -256,7 +256,7 @@ RTC.prototype.cmos_port_read = function()
             return this.encode_time(new Date(this.rtc_time).getUTCFullYear() % 100);
 
         case CMOS_STATUS_A:
-            if(v86.microtick() % 1000 >= 999)
+            if(this.cpu.microtick() % 1000 >= 999)
             {
                 // Set update-in-progress for one millisecond every second (we
                 // may not have precision higher than that in browser
@@ -312,12 +312,12 @@ RTC.prototype.cmos_port_write = function(data_byte)
             this.cmos_b = data_byte;
             if(this.cmos_b & 0x40)
             {
-                this.next_interrupt = Date.now();
+                this.next_interrupt = this.cpu.wall_clock();
             }
 
             if(this.cmos_b & 0x20)
             {
-                const now = new Date();
+                const now = new Date(this.cpu.wall_clock());
 
                 const seconds = this.decode_time(this.cmos_data[CMOS_RTC_SECONDS_ALARM]);
                 const minutes = this.decode_time(this.cmos_data[CMOS_RTC_MINUTES_ALARM]);
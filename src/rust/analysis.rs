@@ -3,6 +3,7 @@
 use cpu_context::CpuContext;
 use prefix::{PREFIX_66, PREFIX_67, PREFIX_F2, PREFIX_F3};
 use regs::{CS, DS, ES, FS, GS, SS};
+use vex::Vex;
 
 #[derive(PartialEq, Eq)]
 pub enum AnalysisType {
@@ -57,6 +58,44 @@ pub fn instr16_0F_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
 pub fn instr32_0F_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
     ::gen::analyzer0f::analyzer(cpu.read_imm8() as u32 | 0x100, cpu, analysis)
 }
+pub fn instr16_0F38_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
+    ::gen::analyzer0f38::analyzer(cpu.read_imm8() as u32, cpu, analysis)
+}
+pub fn instr32_0F38_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
+    ::gen::analyzer0f38::analyzer(cpu.read_imm8() as u32 | 0x100, cpu, analysis)
+}
+pub fn instr16_0F3A_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
+    ::gen::analyzer0f3a::analyzer(cpu.read_imm8() as u32, cpu, analysis)
+}
+pub fn instr32_0F3A_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
+    ::gen::analyzer0f3a::analyzer(cpu.read_imm8() as u32 | 0x100, cpu, analysis)
+}
+pub fn instr16_C4_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
+    les_or_vex_analyze(cpu, analysis)
+}
+pub fn instr32_C4_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
+    les_or_vex_analyze(cpu, analysis)
+}
+fn les_or_vex_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
+    let modrm_byte = cpu.read_imm8();
+    if modrm_byte < 0xC0 {
+        // les
+        modrm_analyze(cpu, modrm_byte);
+        analysis.ty = AnalysisType::BlockBoundary;
+    }
+    else if cpu.protected_mode() {
+        let vex = Vex::decode(modrm_byte, cpu.read_imm8());
+        let _opcode = cpu.read_imm8();
+        let modrm_byte = cpu.read_imm8();
+        modrm_analyze(cpu, modrm_byte);
+        if vex.has_imm8() {
+            cpu.read_imm8();
+        }
+    }
+    else {
+        analysis.ty = AnalysisType::BlockBoundary;
+    }
+}
 pub fn instr_26_analyze(cpu: &mut CpuContext, analysis: &mut Analysis) {
     analyze_step_handle_segment_prefix(ES, cpu, analysis)
 }
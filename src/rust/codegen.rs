@@ -1,6 +1,7 @@
 use cpu::cpu::{
     tlb_data, FLAG_CARRY, FLAG_OVERFLOW, FLAG_SIGN, FLAG_ZERO, OPSIZE_16, OPSIZE_32, OPSIZE_8,
-    TLB_GLOBAL, TLB_HAS_CODE, TLB_NO_USER, TLB_READONLY, TLB_VALID,
+    TLB_GLOBAL, TLB_HAS_CODE, TLB_NO_EXECUTE, TLB_NO_USER, TLB_READONLY, TLB_SMAP, TLB_SMEP,
+    TLB_VALID,
 };
 use cpu::global_pointers;
 use cpu::memory;
@@ -427,30 +428,37 @@ pub fn gen_fn2_const(builder: &mut WasmBuilder, name: &str, arg0: u32, arg1: u32
 
 // helper functions for gen/generate_jit.js
 pub fn gen_modrm_fn0(builder: &mut WasmBuilder, name: &str) {
-    // generates: fn( _ )
-    builder.call_fn1(name);
+    // generates: fn( _, _ )
+    builder.call_fn2(name);
 }
 pub fn gen_modrm_fn1(builder: &mut WasmBuilder, name: &str, arg0: u32) {
-    // generates: fn( _, arg0 )
+    // generates: fn( _, _, arg0 )
     builder.const_i32(arg0 as i32);
-    builder.call_fn2(name);
+    builder.call_fn3(name);
 }
 
 pub fn gen_modrm_resolve(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     modrm::gen(ctx, modrm_byte, 0)
 }
+/// Like gen_modrm_resolve, but pushes the segment register of the operand before its address
+pub fn gen_modrm_resolve_with_segment(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    ctx.builder
+        .const_i32(modrm::segment(ctx, &modrm_byte) as i32);
+    gen_modrm_resolve(ctx, modrm_byte);
+}
 pub fn gen_modrm_resolve_with_local(
     ctx: &mut JitContext,
     modrm_byte: ModrmByte,
-    gen: &dyn Fn(&mut JitContext, &WasmLocal),
+    gen: &dyn Fn(&mut JitContext, u32, &WasmLocal),
 ) {
+    let seg = modrm::segment(ctx, &modrm_byte);
     if let Some(r) = modrm::get_as_reg_index_if_possible(ctx, &modrm_byte) {
-        gen(ctx, &ctx.reg(r));
+        gen(ctx, seg, &ctx.reg(r));
     }
     else {
         gen_modrm_resolve(ctx, modrm_byte);
         let address = ctx.builder.set_new_local();
-        gen(ctx, &address);
+        gen(ctx, seg, &address);
         ctx.builder.free_local(address);
     }
 }
@@ -485,41 +493,54 @@ pub fn gen_set_reg32_r(ctx: &mut JitContext, dest: u32, src: u32) {
 }
 
 pub fn gen_modrm_resolve_safe_read8(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| gen_safe_read8(ctx, addr));
+    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        gen_safe_read8(ctx, seg, addr)
+    });
 }
 pub fn gen_modrm_resolve_safe_read16(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| gen_safe_read16(ctx, addr));
+    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        gen_safe_read16(ctx, seg, addr)
+    });
 }
 pub fn gen_modrm_resolve_safe_read32(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| gen_safe_read32(ctx, addr));
+    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        gen_safe_read32(ctx, seg, addr)
+    });
 }
 pub fn gen_modrm_resolve_safe_read64(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| gen_safe_read64(ctx, addr));
+    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        gen_safe_read64(ctx, seg, addr)
+    });
 }
 pub fn gen_modrm_resolve_safe_read128(
     ctx: &mut JitContext,
     modrm_byte: ModrmByte,
     where_to_write: u32,
 ) {
-    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-        gen_safe_read128(ctx, addr, where_to_write)
+    gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        gen_safe_read128(ctx, seg, addr, where_to_write)
     });
 }
 
-pub fn gen_safe_read8(ctx: &mut JitContext, address_local: &WasmLocal) {
-    gen_safe_read(ctx, BitSize::BYTE, address_local, None);
+pub fn gen_safe_read8(ctx: &mut JitContext, seg: u32, address_local: &WasmLocal) {
+    gen_safe_read(ctx, BitSize::BYTE, seg, address_local, None);
 }
-pub fn gen_safe_read16(ctx: &mut JitContext, address_local: &WasmLocal) {
-    gen_safe_read(ctx, BitSize::WORD, address_local, None);
+pub fn gen_safe_read16(ctx: &mut JitContext, seg: u32, address_local: &WasmLocal) {
+    gen_safe_read(ctx, BitSize::WORD, seg, address_local, None);
 }
-pub fn gen_safe_read32(ctx: &mut JitContext, address_local: &WasmLocal) {
-    gen_safe_read(ctx, BitSize::DWORD, address_local, None);
+pub fn gen_safe_read32(ctx: &mut JitContext, seg: u32, address_local: &WasmLocal) {
+    gen_safe_read(ctx, BitSize::DWORD, seg, address_local, None);
 }
-pub fn gen_safe_read64(ctx: &mut JitContext, address_local: &WasmLocal) {
-    gen_safe_read(ctx, BitSize::QWORD, &address_local, None);
+pub fn gen_safe_read64(ctx: &mut JitContext, seg: u32, address_local: &WasmLocal) {
+    gen_safe_read(ctx, BitSize::QWORD, seg, &address_local, None);
 }
-pub fn gen_safe_read128(ctx: &mut JitContext, address_local: &WasmLocal, where_to_write: u32) {
-    gen_safe_read(ctx, BitSize::DQWORD, &address_local, Some(where_to_write));
+pub fn gen_safe_read128(
+    ctx: &mut JitContext,
+    seg: u32,
+    address_local: &WasmLocal,
+    where_to_write: u32,
+) {
+    gen_safe_read(ctx, BitSize::DQWORD, seg, &address_local, Some(where_to_write));
 }
 
 // only used internally for gen_safe_write
@@ -554,38 +575,82 @@ impl BitSize {
     }
 }
 
-pub fn gen_safe_write8(ctx: &mut JitContext, address_local: &WasmLocal, value_local: &WasmLocal) {
+/// Push whether the access of the given size at address_local may use the fast path: It must
+/// not cross a page boundary, and if alignment checking is enabled, it must be aligned (which
+/// implies the former). Misaligned accesses go to the slow path, which raises #AC
+fn gen_fast_path_alignment_condition(
+    ctx: &mut JitContext,
+    bits: BitSize,
+    address_local: &WasmLocal,
+) {
+    // 128-bit accesses are not subject to alignment checking
+    if ctx.cpu.alignment_check() && bits != BitSize::DQWORD {
+        ctx.builder.get_local(address_local);
+        ctx.builder.const_i32(bits.bytes() as i32 - 1);
+        ctx.builder.and_i32();
+        ctx.builder.eqz_i32();
+    }
+    else {
+        ctx.builder.get_local(address_local);
+        ctx.builder.const_i32(0xFFF);
+        ctx.builder.and_i32();
+        ctx.builder.const_i32(0x1000 - bits.bytes() as i32);
+        ctx.builder.le_i32();
+    }
+}
+
+pub fn gen_safe_write8(
+    ctx: &mut JitContext,
+    seg: u32,
+    address_local: &WasmLocal,
+    value_local: &WasmLocal,
+) {
     gen_safe_write(
         ctx,
         BitSize::BYTE,
+        seg,
         address_local,
         GenSafeWriteValue::I32(value_local),
     )
 }
-pub fn gen_safe_write16(ctx: &mut JitContext, address_local: &WasmLocal, value_local: &WasmLocal) {
+pub fn gen_safe_write16(
+    ctx: &mut JitContext,
+    seg: u32,
+    address_local: &WasmLocal,
+    value_local: &WasmLocal,
+) {
     gen_safe_write(
         ctx,
         BitSize::WORD,
+        seg,
         address_local,
         GenSafeWriteValue::I32(value_local),
     )
 }
-pub fn gen_safe_write32(ctx: &mut JitContext, address_local: &WasmLocal, value_local: &WasmLocal) {
+pub fn gen_safe_write32(
+    ctx: &mut JitContext,
+    seg: u32,
+    address_local: &WasmLocal,
+    value_local: &WasmLocal,
+) {
     gen_safe_write(
         ctx,
         BitSize::DWORD,
+        seg,
         address_local,
         GenSafeWriteValue::I32(value_local),
     )
 }
 pub fn gen_safe_write64(
     ctx: &mut JitContext,
+    seg: u32,
     address_local: &WasmLocal,
     value_local: &WasmLocalI64,
 ) {
     gen_safe_write(
         ctx,
         BitSize::QWORD,
+        seg,
         address_local,
         GenSafeWriteValue::I64(value_local),
     )
@@ -593,6 +658,7 @@ pub fn gen_safe_write64(
 
 pub fn gen_safe_write128(
     ctx: &mut JitContext,
+    seg: u32,
     address_local: &WasmLocal,
     value_local_low: &WasmLocalI64,
     value_local_high: &WasmLocalI64,
@@ -600,26 +666,60 @@ pub fn gen_safe_write128(
     gen_safe_write(
         ctx,
         BitSize::DQWORD,
+        seg,
         address_local,
         GenSafeWriteValue::TwoI64s(value_local_low, value_local_high),
     )
 }
 
+/// Check an access through the segment register `seg` against the segment's limit and type (see
+/// check_segment_access). With flat segmentation, only writes through cs may fault
+fn gen_segment_check(
+    ctx: &mut JitContext,
+    seg: u32,
+    bits: BitSize,
+    address_local: &WasmLocal,
+    is_write: bool,
+) {
+    if !ctx.cpu.segment_checks()
+        || ctx.cpu.has_flat_segmentation() && !(is_write && seg == regs::CS)
+    {
+        return;
+    }
+
+    ctx.builder.const_i32(seg as i32);
+    ctx.builder.get_local(address_local);
+    ctx.builder.const_i32(bits.bytes() as i32);
+    ctx.builder
+        .const_i32(ctx.start_of_current_instruction as i32 & 0xFFF);
+    if is_write {
+        ctx.builder.call_fn4_ret("check_segment_write_jit");
+    }
+    else {
+        ctx.builder.call_fn4_ret("check_segment_read_jit");
+    }
+    ctx.builder.br_if(ctx.exit_with_fault_label);
+}
+
 fn gen_safe_read(
     ctx: &mut JitContext,
     bits: BitSize,
+    seg: u32,
     address_local: &WasmLocal,
     where_to_write: Option<u32>,
 ) {
     // Execute a virtual memory read. All slow paths (memory-mapped IO, tlb miss, page fault and
     // read across page boundary are handled in safe_read_jit_slow
 
+    //   if segment check fails: goto exit-with-fault
     //   entry <- tlb_data[addr >> 12 << 2]
     //   if entry & MASK == TLB_VALID && (addr & 0xFFF) <= 0x1000 - bytes: goto fast
     //   entry <- safe_read_jit_slow(addr, instruction_pointer)
     //   if page_fault: goto exit-with-pagefault
     //   fast: mem[(entry & ~0xFFF) ^ addr]
 
+    gen_segment_check(ctx, seg, bits, address_local, false);
+
     let cont = ctx.builder.block_void();
     ctx.builder.get_local(&address_local);
 
@@ -632,12 +732,16 @@ fn gen_safe_read(
         .load_aligned_i32(unsafe { &tlb_data[0] as *const i32 as u32 });
     let entry_local = ctx.builder.tee_new_local();
 
+    // Note: TLB_SMAP is part of the mask in supervisor mode, so that eflags.AC is checked by
+    // safe_read_slow_jit
     ctx.builder.const_i32(
         (0xFFF
             & !TLB_READONLY
             & !TLB_GLOBAL
             & !TLB_HAS_CODE
-            & !(if ctx.cpu.cpl3() { 0 } else { TLB_NO_USER })) as i32,
+            & !TLB_NO_EXECUTE
+            & !TLB_SMEP
+            & !(if ctx.cpu.cpl3() { TLB_SMAP } else { TLB_NO_USER })) as i32,
     );
     ctx.builder.and_i32();
 
@@ -645,12 +749,7 @@ fn gen_safe_read(
     ctx.builder.eq_i32();
 
     if bits != BitSize::BYTE {
-        ctx.builder.get_local(&address_local);
-        ctx.builder.const_i32(0xFFF);
-        ctx.builder.and_i32();
-        ctx.builder.const_i32(0x1000 - bits.bytes() as i32);
-        ctx.builder.le_i32();
-
+        gen_fast_path_alignment_condition(ctx, bits, &address_local);
         ctx.builder.and_i32();
     }
 
@@ -769,12 +868,15 @@ pub fn gen_get_phys_eip_plus_mem(ctx: &mut JitContext, address_local: &WasmLocal
         .load_aligned_i32(unsafe { &tlb_data[0] as *const i32 as u32 });
     let entry_local = ctx.builder.tee_new_local();
 
+    // Note: TLB_NO_EXECUTE and TLB_SMEP are part of the mask, so that non-executable pages go
+    // through get_phys_eip_slow_jit, which triggers the page fault
     ctx.builder.const_i32(
         (0xFFF
             & !TLB_READONLY
             & !TLB_GLOBAL
             & !TLB_HAS_CODE
-            & !(if ctx.cpu.cpl3() { 0 } else { TLB_NO_USER })) as i32,
+            & !TLB_SMAP
+            & !(if ctx.cpu.cpl3() { TLB_SMEP } else { TLB_NO_USER })) as i32,
     );
     ctx.builder.and_i32();
 
@@ -824,18 +926,22 @@ pub fn gen_get_phys_eip_plus_mem(ctx: &mut JitContext, address_local: &WasmLocal
 fn gen_safe_write(
     ctx: &mut JitContext,
     bits: BitSize,
+    seg: u32,
     address_local: &WasmLocal,
     value_local: GenSafeWriteValue,
 ) {
     // Execute a virtual memory write. All slow paths (memory-mapped IO, tlb miss, page fault,
     // write across page boundary and page containing jitted code are handled in safe_write_jit_slow
 
+    //   if segment check fails: goto exit-with-fault
     //   entry <- tlb_data[addr >> 12 << 2]
     //   if entry & MASK == TLB_VALID && (addr & 0xFFF) <= 0x1000 - bytes: goto fast
     //   entry <- safe_write_jit_slow(addr, value, instruction_pointer)
     //   if page_fault: goto exit-with-pagefault
     //   fast: mem[(entry & ~0xFFF) ^ addr] <- value
 
+    gen_segment_check(ctx, seg, bits, address_local, true);
+
     let cont = ctx.builder.block_void();
     ctx.builder.get_local(&address_local);
 
@@ -849,19 +955,20 @@ fn gen_safe_write(
     let entry_local = ctx.builder.tee_new_local();
 
     ctx.builder
-        .const_i32((0xFFF & !TLB_GLOBAL & !(if ctx.cpu.cpl3() { 0 } else { TLB_NO_USER })) as i32);
+        .const_i32(
+            (0xFFF
+                & !TLB_GLOBAL
+                & !TLB_NO_EXECUTE
+                & !TLB_SMEP
+                & !(if ctx.cpu.cpl3() { TLB_SMAP } else { TLB_NO_USER })) as i32,
+        );
     ctx.builder.and_i32();
 
     ctx.builder.const_i32(TLB_VALID as i32);
     ctx.builder.eq_i32();
 
     if bits != BitSize::BYTE {
-        ctx.builder.get_local(&address_local);
-        ctx.builder.const_i32(0xFFF);
-        ctx.builder.and_i32();
-        ctx.builder.const_i32(0x1000 - bits.bytes() as i32);
-        ctx.builder.le_i32();
-
+        gen_fast_path_alignment_condition(ctx, bits, &address_local);
         ctx.builder.and_i32();
     }
 
@@ -967,6 +1074,7 @@ fn gen_safe_write(
 pub fn gen_safe_read_write(
     ctx: &mut JitContext,
     bits: BitSize,
+    seg: u32,
     address_local: &WasmLocal,
     f: &dyn Fn(&mut JitContext),
 ) {
@@ -974,6 +1082,7 @@ pub fn gen_safe_read_write(
     // write across page boundary and page containing jitted code are handled in
     // safe_read_write_jit_slow
 
+    //   if segment check fails: goto exit-with-fault
     //   entry <- tlb_data[addr >> 12 << 2]
     //   can_use_fast_path <- entry & MASK == TLB_VALID && (addr & 0xFFF) <= 0x1000 - bytes
     //   if can_use_fast_path: goto fast
@@ -983,6 +1092,8 @@ pub fn gen_safe_read_write(
     //   if !can_use_fast_path { safe_write_jit_slow(addr, value, instruction_pointer) }
     //   mem[(entry & ~0xFFF) ^ addr] <- value
 
+    gen_segment_check(ctx, seg, bits, address_local, true);
+
     let cont = ctx.builder.block_void();
     ctx.builder.get_local(address_local);
 
@@ -996,18 +1107,20 @@ pub fn gen_safe_read_write(
     let entry_local = ctx.builder.tee_new_local();
 
     ctx.builder
-        .const_i32((0xFFF & !TLB_GLOBAL & !(if ctx.cpu.cpl3() { 0 } else { TLB_NO_USER })) as i32);
+        .const_i32(
+            (0xFFF
+                & !TLB_GLOBAL
+                & !TLB_NO_EXECUTE
+                & !TLB_SMEP
+                & !(if ctx.cpu.cpl3() { TLB_SMAP } else { TLB_NO_USER })) as i32,
+        );
     ctx.builder.and_i32();
 
     ctx.builder.const_i32(TLB_VALID as i32);
     ctx.builder.eq_i32();
 
     if bits != BitSize::BYTE {
-        ctx.builder.get_local(&address_local);
-        ctx.builder.const_i32(0xFFF);
-        ctx.builder.and_i32();
-        ctx.builder.const_i32(0x1000 - bits.bytes() as i32);
-        ctx.builder.le_i32();
+        gen_fast_path_alignment_condition(ctx, bits, &address_local);
         ctx.builder.and_i32();
     }
 
@@ -1111,8 +1224,7 @@ pub fn gen_safe_read_write(
             GenSafeReadWriteValue::I64(l) => ctx.builder.get_local_i64(l),
         }
 
-        ctx.builder
-            .const_i32(ctx.start_of_current_instruction as i32 & 0xFFF);
+        ctx.builder.const_i32(ctx.start_of_current_instruction as i32 & 0xFFF);
 
         match bits {
             BitSize::BYTE => {
@@ -1239,7 +1351,7 @@ pub fn gen_pop16_ss16(ctx: &mut JitContext) {
 
     // result = safe_read16(sp)
     let address_local = ctx.builder.set_new_local();
-    gen_safe_read16(ctx, &address_local);
+    gen_safe_read16(ctx, regs::SS, &address_local);
     ctx.builder.free_local(address_local);
 
     // reg16[SP] += 2;
@@ -1262,7 +1374,7 @@ pub fn gen_pop16_ss32(ctx: &mut JitContext) {
 
     // result = safe_read16(esp)
     let address_local = ctx.builder.set_new_local();
-    gen_safe_read16(ctx, &address_local);
+    gen_safe_read16(ctx, regs::SS, &address_local);
     ctx.builder.free_local(address_local);
 
     // reg32[ESP] += 2;
@@ -1294,7 +1406,7 @@ pub fn gen_pop32s_ss16(ctx: &mut JitContext) {
     }
 
     let address_local = ctx.builder.set_new_local();
-    gen_safe_read32(ctx, &address_local);
+    gen_safe_read32(ctx, regs::SS, &address_local);
     ctx.builder.free_local(address_local);
 
     // reg16[SP] = sp + 4;
@@ -1312,12 +1424,12 @@ pub fn gen_pop32s_ss32(ctx: &mut JitContext) {
         gen_get_ss_offset(ctx);
         ctx.builder.add_i32();
         let address_local = ctx.builder.set_new_local();
-        gen_safe_read32(ctx, &address_local);
+        gen_safe_read32(ctx, regs::SS, &address_local);
         ctx.builder.free_local(address_local);
     }
     else {
         let reg = ctx.register_locals[regs::ESP as usize].unsafe_clone();
-        gen_safe_read32(ctx, &reg);
+        gen_safe_read32(ctx, regs::SS, &reg);
     }
 
     gen_get_reg32(ctx, regs::ESP);
@@ -1370,13 +1482,13 @@ pub fn gen_leave(ctx: &mut JitContext, os32: bool) {
     }
     if os32 {
         let address_local = ctx.builder.set_new_local();
-        gen_safe_read32(ctx, &address_local);
+        gen_safe_read32(ctx, regs::SS, &address_local);
         ctx.builder.free_local(address_local);
         gen_set_reg32(ctx, regs::EBP);
     }
     else {
         let address_local = ctx.builder.set_new_local();
-        gen_safe_read16(ctx, &address_local);
+        gen_safe_read16(ctx, regs::SS, &address_local);
         ctx.builder.free_local(address_local);
         gen_set_reg16(ctx, regs::BP);
     }
@@ -1443,6 +1555,32 @@ pub fn gen_task_switch_test_mmx(ctx: &mut JitContext) {
     ctx.builder.block_end();
 }
 
+pub fn gen_fpu_pending_exception_test(ctx: &mut JitContext) {
+    // generate if(fpu_status_word & ~fpu_control_word & 0x3F) {
+    //     if(fpu_pending_exception_test_jit()) goto exit_with_fault;
+    // }
+    ctx.builder.load_fixed_u16(global_pointers::fpu_status_word as u32);
+    ctx.builder.load_fixed_u16(global_pointers::fpu_control_word as u32);
+    ctx.builder.const_i32(-1);
+    ctx.builder.xor_i32();
+    ctx.builder.and_i32();
+    ctx.builder.const_i32(0x3F);
+    ctx.builder.and_i32();
+
+    ctx.builder.if_void();
+    {
+        ctx.builder.const_i32(ctx.start_of_current_instruction as i32 & 0xFFF);
+        ctx.builder.call_fn1_ret("fpu_pending_exception_test_jit");
+        ctx.builder.if_void();
+        {
+            gen_debug_track_jit_exit(ctx.builder, ctx.start_of_current_instruction);
+            ctx.builder.br(ctx.exit_with_fault_label);
+        }
+        ctx.builder.block_end();
+    }
+    ctx.builder.block_end();
+}
+
 pub fn gen_push16(ctx: &mut JitContext, value_local: &WasmLocal) {
     if ctx.cpu.ssize_32() {
         gen_get_reg32(ctx, regs::ESP);
@@ -1467,7 +1605,7 @@ pub fn gen_push16(ctx: &mut JitContext, value_local: &WasmLocal) {
         }
 
         let sp_local = ctx.builder.set_new_local();
-        gen_safe_write16(ctx, &sp_local, &value_local);
+        gen_safe_write16(ctx, regs::SS, &sp_local, &value_local);
         ctx.builder.free_local(sp_local);
 
         ctx.builder.get_local(&reg_updated_local);
@@ -1476,7 +1614,7 @@ pub fn gen_push16(ctx: &mut JitContext, value_local: &WasmLocal) {
     else {
         // short path: The address written to is equal to ESP/SP minus two
         let reg_updated_local = ctx.builder.tee_new_local();
-        gen_safe_write16(ctx, &reg_updated_local, &value_local);
+        gen_safe_write16(ctx, regs::SS, &reg_updated_local, &value_local);
         reg_updated_local
     };
 
@@ -1514,7 +1652,7 @@ pub fn gen_push32(ctx: &mut JitContext, value_local: &WasmLocal) {
 
         let sp_local = ctx.builder.set_new_local();
 
-        gen_safe_write32(ctx, &sp_local, &value_local);
+        gen_safe_write32(ctx, regs::SS, &sp_local, &value_local);
         ctx.builder.free_local(sp_local);
 
         ctx.builder.get_local(&new_sp_local);
@@ -1523,7 +1661,7 @@ pub fn gen_push32(ctx: &mut JitContext, value_local: &WasmLocal) {
     else {
         // short path: The address written to is equal to ESP/SP minus four
         let new_sp_local = ctx.builder.tee_new_local();
-        gen_safe_write32(ctx, &new_sp_local, &value_local);
+        gen_safe_write32(ctx, regs::SS, &new_sp_local, &value_local);
         new_sp_local
     };
 
@@ -1564,7 +1702,7 @@ pub fn gen_push32_sreg(ctx: &mut JitContext, reg: u32) {
 
         let sp_local = ctx.builder.set_new_local();
 
-        gen_safe_write16(ctx, &sp_local, &value_local);
+        gen_safe_write16(ctx, regs::SS, &sp_local, &value_local);
         ctx.builder.free_local(sp_local);
 
         ctx.builder.get_local(&new_sp_local);
@@ -1573,7 +1711,7 @@ pub fn gen_push32_sreg(ctx: &mut JitContext, reg: u32) {
     else {
         // short path: The address written to is equal to ESP/SP minus four
         let new_sp_local = ctx.builder.tee_new_local();
-        gen_safe_write16(ctx, &new_sp_local, &value_local);
+        gen_safe_write16(ctx, regs::SS, &new_sp_local, &value_local);
         new_sp_local
     };
 
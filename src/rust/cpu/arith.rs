@@ -1011,30 +1011,36 @@ pub unsafe fn btr_reg(bit_base: i32, bit_offset: i32) -> i32 {
     return bit_base & !(1 << bit_offset);
 }
 
-pub unsafe fn bt_mem(virt_addr: i32, mut bit_offset: i32) {
-    let bit_base = return_on_pagefault!(safe_read8(virt_addr + (bit_offset >> 3)));
+pub unsafe fn bt_mem(seg: i32, virt_addr: i32, mut bit_offset: i32) {
+    let bit_base = return_on_pagefault!(safe_read8_seg(seg, virt_addr + (bit_offset >> 3)));
     bit_offset &= 7;
     *flags = *flags & !1 | bit_base >> bit_offset & 1;
     *flags_changed &= !1;
 }
-pub unsafe fn btc_mem(virt_addr: i32, mut bit_offset: i32) {
-    let phys_addr = return_on_pagefault!(translate_address_write(virt_addr + (bit_offset >> 3)));
+pub unsafe fn btc_mem(seg: i32, virt_addr: i32, mut bit_offset: i32) {
+    let addr = virt_addr + (bit_offset >> 3);
+    return_on_pagefault!(check_segment_access(seg, addr, 1, true));
+    let phys_addr = return_on_pagefault!(translate_address_write(addr));
     let bit_base = read8(phys_addr);
     bit_offset &= 7;
     *flags = *flags & !1 | bit_base >> bit_offset & 1;
     *flags_changed &= !1;
     write8(phys_addr, bit_base ^ 1 << bit_offset);
 }
-pub unsafe fn btr_mem(virt_addr: i32, mut bit_offset: i32) {
-    let phys_addr = return_on_pagefault!(translate_address_write(virt_addr + (bit_offset >> 3)));
+pub unsafe fn btr_mem(seg: i32, virt_addr: i32, mut bit_offset: i32) {
+    let addr = virt_addr + (bit_offset >> 3);
+    return_on_pagefault!(check_segment_access(seg, addr, 1, true));
+    let phys_addr = return_on_pagefault!(translate_address_write(addr));
     let bit_base = read8(phys_addr);
     bit_offset &= 7;
     *flags = *flags & !1 | bit_base >> bit_offset & 1;
     *flags_changed &= !1;
     write8(phys_addr, bit_base & !(1 << bit_offset));
 }
-pub unsafe fn bts_mem(virt_addr: i32, mut bit_offset: i32) {
-    let phys_addr = return_on_pagefault!(translate_address_write(virt_addr + (bit_offset >> 3)));
+pub unsafe fn bts_mem(seg: i32, virt_addr: i32, mut bit_offset: i32) {
+    let addr = virt_addr + (bit_offset >> 3);
+    return_on_pagefault!(check_segment_access(seg, addr, 1, true));
+    let phys_addr = return_on_pagefault!(translate_address_write(addr));
     let bit_base = read8(phys_addr);
     bit_offset &= 7;
     *flags = *flags & !1 | bit_base >> bit_offset & 1;
@@ -1120,6 +1126,115 @@ pub unsafe fn popcnt(v: i32) -> i32 {
     };
 }
 
+unsafe fn bit_count_flags(source_is_zero: bool, result: i32) -> i32 {
+    // cf: source is zero, zf: result is zero, the others are undefined
+    *flags_changed = 0;
+    *flags &= !FLAGS_ALL;
+    if source_is_zero {
+        *flags |= FLAG_CARRY;
+    }
+    if result == 0 {
+        *flags |= FLAG_ZERO;
+    }
+    return result;
+}
+#[no_mangle]
+pub unsafe fn lzcnt16(v: i32) -> i32 {
+    return bit_count_flags(v & 0xFFFF == 0, (v as u16).leading_zeros() as i32);
+}
+#[no_mangle]
+pub unsafe fn lzcnt32(v: i32) -> i32 { return bit_count_flags(v == 0, v.leading_zeros() as i32); }
+#[no_mangle]
+pub unsafe fn tzcnt16(v: i32) -> i32 {
+    return bit_count_flags(v & 0xFFFF == 0, (v as u16).trailing_zeros() as i32);
+}
+#[no_mangle]
+pub unsafe fn tzcnt32(v: i32) -> i32 { return bit_count_flags(v == 0, v.trailing_zeros() as i32); }
+
+unsafe fn bmi_flags(result: i32, carry: bool) -> i32 {
+    // sf and zf from the result, of cleared, cf as given
+    and(result, result, OPSIZE_32);
+    if carry {
+        *flags |= FLAG_CARRY;
+    }
+    return result;
+}
+#[no_mangle]
+pub unsafe fn andn(x: i32, y: i32) -> i32 { return and(!x, y, OPSIZE_32); }
+#[no_mangle]
+pub unsafe fn bextr(source: i32, control: i32) -> i32 {
+    let start = control & 0xFF;
+    let length = control >> 8 & 0xFF;
+    let result = if start >= 32 {
+        0
+    }
+    else if length >= 32 {
+        (source as u32 >> start) as i32
+    }
+    else {
+        (source as u32 >> start) as i32 & ((1 << length) - 1)
+    };
+    return bmi_flags(result, false);
+}
+#[no_mangle]
+pub unsafe fn blsi(x: i32) -> i32 { return bmi_flags(x & x.wrapping_neg(), x != 0); }
+#[no_mangle]
+pub unsafe fn blsmsk(x: i32) -> i32 { return bmi_flags(x ^ x.wrapping_sub(1), x == 0); }
+#[no_mangle]
+pub unsafe fn blsr(x: i32) -> i32 { return bmi_flags(x & x.wrapping_sub(1), x == 0); }
+#[no_mangle]
+pub unsafe fn bzhi(source: i32, index: i32) -> i32 {
+    let index = index & 0xFF;
+    if index >= 32 {
+        return bmi_flags(source, true);
+    }
+    else {
+        return bmi_flags(source & ((1u32 << index) - 1) as i32, false);
+    };
+}
+#[no_mangle]
+pub fn pdep(source: i32, mask: i32) -> i32 {
+    let mut result = 0;
+    let mut mask = mask;
+    let mut bit = 1;
+    while mask != 0 {
+        let lowest = mask & mask.wrapping_neg();
+        if source & bit != 0 {
+            result |= lowest;
+        }
+        mask &= !lowest;
+        bit <<= 1;
+    }
+    return result;
+}
+#[no_mangle]
+pub fn pext(source: i32, mask: i32) -> i32 {
+    let mut result = 0;
+    let mut mask = mask;
+    let mut bit = 1;
+    while mask != 0 {
+        let lowest = mask & mask.wrapping_neg();
+        if source & lowest != 0 {
+            result |= bit;
+        }
+        mask &= !lowest;
+        bit <<= 1;
+    }
+    return result;
+}
+#[no_mangle]
+pub fn crc32(crc: i32, v: i32, bytes: i32) -> i32 {
+    // crc32c (Castagnoli polynomial, bit-reflected), as used by the crc32 instruction
+    let mut crc = crc as u32;
+    for i in 0..bytes {
+        crc ^= (v >> (8 * i)) as u32 & 0xFF;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { crc >> 1 ^ 0x82F6_3B78 } else { crc >> 1 };
+        }
+    }
+    return crc as i32;
+}
+
 pub unsafe fn saturate_sw_to_ub(v: u16) -> u8 {
     let mut ret = v;
     if ret >= 32768 {
@@ -1200,3 +1315,43 @@ pub unsafe fn saturate_uw(v: u32) -> u16 {
     dbg_assert!(ret & 0xFFFF_0000 == 0);
     return ret as u16;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::crc32;
+
+    fn crc32c(data: &[u8]) -> u32 {
+        let mut crc = !0;
+        for &b in data {
+            crc = crc32(crc, b as i32, 1);
+        }
+        !crc as u32
+    }
+
+    #[test]
+    fn crc32_known_vectors() {
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(b""), 0);
+
+        // RFC 3720, B.4
+        assert_eq!(crc32c(&[0; 32]), 0x8A91_36AA);
+        assert_eq!(crc32c(&[0xFF; 32]), 0x62A8_AB43);
+        let ascending: Vec<u8> = (0..32).collect();
+        assert_eq!(crc32c(&ascending), 0x46DD_794E);
+        let descending: Vec<u8> = (0..32).rev().collect();
+        assert_eq!(crc32c(&descending), 0x113F_DB5C);
+    }
+
+    #[test]
+    fn crc32_operand_sizes() {
+        let data = [0x12, 0x34, 0x56, 0x78];
+        let mut crc = 0x1234_5678;
+        for &b in &data {
+            crc = crc32(crc, b, 1);
+        }
+        assert_eq!(crc32(0x1234_5678, 0x7856_3412, 4), crc);
+        assert_eq!(crc32(crc32(0x1234_5678, 0x3412, 2), 0x7856, 2), crc);
+        // only the low bytes of the source are used
+        assert_eq!(crc32(0, 0x7F12, 1), crc32(0, 0x12, 1));
+    }
+}
@@ -7,29 +7,37 @@ extern "C" {
     pub fn run_hardware_timers(acpi_enabled: bool, t: f64) -> f64;
     pub fn cpu_event_halt();
     pub fn apic_acknowledge_irq() -> i32;
+    pub fn apic_has_pending_irq() -> bool;
+    pub fn apic_set_tsc_deadline(time: f64);
+    pub fn apic_get_tsc_deadline() -> f64;
     pub fn stop_idling();
 
-    pub fn io_port_read8(port: i32) -> i32;
-    pub fn io_port_read16(port: i32) -> i32;
-    pub fn io_port_read32(port: i32) -> i32;
-
     pub fn io_port_write8(port: i32, value: i32);
     pub fn io_port_write16(port: i32, value: i32);
     pub fn io_port_write32(port: i32, value: i32);
 }
 
+mod ext {
+    extern "C" {
+        pub fn io_port_read8(port: i32) -> i32;
+        pub fn io_port_read16(port: i32) -> i32;
+        pub fn io_port_read32(port: i32) -> i32;
+    }
+}
+
 use config;
-use cpu::fpu::fpu_set_tag_word;
+use cpu::fpu::{fpu_exception_pending, fpu_set_tag_word};
 use cpu::global_pointers::*;
 use cpu::memory;
 use cpu::memory::mem8;
 use cpu::memory::{in_mapped_range, read128, read16, read32s, read64s, read8, write8};
 use cpu::misc_instr::{
     adjust_stack_reg, get_stack_pointer, getaf, getcf, getof, getpf, getsf, getzf, pop16, pop32s,
-    push16, push32,
+    push16, push32, XSTATE_X87,
 };
 use cpu::modrm::{resolve_modrm16, resolve_modrm32};
 use cpu::pic;
+use cpu::replay;
 use jit;
 use jit::is_near_end_of_page;
 use page::Page;
@@ -162,6 +170,7 @@ pub const CR0_MP: i32 = 1 << 1;
 pub const CR0_EM: i32 = 1 << 2;
 pub const CR0_TS: i32 = 1 << 3;
 pub const CR0_ET: i32 = 1 << 4;
+pub const CR0_NE: i32 = 1 << 5;
 pub const CR0_WP: i32 = 1 << 16;
 pub const CR0_AM: i32 = 1 << 18;
 pub const CR0_NW: i32 = 1 << 29;
@@ -176,7 +185,27 @@ pub const CR4_PAE: i32 = 1 << 5;
 pub const CR4_PGE: i32 = 1 << 7;
 pub const CR4_OSFXSR: i32 = 1 << 9;
 pub const CR4_OSXMMEXCPT: i32 = 1 << 10;
+pub const CR4_OSXSAVE: i32 = 1 << 18;
 pub const CR4_SMEP: i32 = 1 << 20;
+pub const CR4_SMAP: i32 = 1 << 21;
+
+pub const DR6_B0_B3: i32 = 0xF;
+pub const DR6_BD: i32 = 1 << 13;
+pub const DR6_BS: i32 = 1 << 14;
+pub const DR6_BT: i32 = 1 << 15;
+// bits that always read as 1
+pub const DR6_RESERVED: i32 = 0xFFFF0FF0u32 as i32;
+
+pub const DR7_ENABLE_MASK: i32 = 0xFF;
+pub const DR7_GD: i32 = 1 << 13;
+// bit 10 always reads as 1, bits 11, 12, 14 and 15 always read as 0
+pub const DR7_RESERVED: i32 = 1 << 10;
+pub const DR7_RESERVED_ZERO: i32 = 1 << 11 | 1 << 12 | 1 << 14 | 1 << 15;
+
+pub const DR7_RW_EXECUTE: i32 = 0;
+pub const DR7_RW_WRITE: i32 = 1;
+pub const DR7_RW_IO: i32 = 2;
+pub const DR7_RW_READ_WRITE: i32 = 3;
 
 pub const TSR_BACKLINK: i32 = 0x00;
 pub const TSR_CR3: i32 = 0x1C;
@@ -199,6 +228,26 @@ pub const TSR_DS: i32 = 0x54;
 pub const TSR_FS: i32 = 0x58;
 pub const TSR_GS: i32 = 0x5c;
 pub const TSR_LDT: i32 = 0x60;
+pub const TSR_TRAP: i32 = 0x64;
+
+// 286 (16-bit) TSS layout
+pub const TSR16_IP: i32 = 0x0E;
+pub const TSR16_FLAGS: i32 = 0x10;
+
+pub const TSR16_AX: i32 = 0x12;
+pub const TSR16_CX: i32 = 0x14;
+pub const TSR16_DX: i32 = 0x16;
+pub const TSR16_BX: i32 = 0x18;
+pub const TSR16_SP: i32 = 0x1A;
+pub const TSR16_BP: i32 = 0x1C;
+pub const TSR16_SI: i32 = 0x1E;
+pub const TSR16_DI: i32 = 0x20;
+
+pub const TSR16_ES: i32 = 0x22;
+pub const TSR16_CS: i32 = 0x24;
+pub const TSR16_SS: i32 = 0x26;
+pub const TSR16_DS: i32 = 0x28;
+pub const TSR16_LDT: i32 = 0x2A;
 
 pub const IA32_TIME_STAMP_COUNTER: i32 = 0x10;
 pub const IA32_PLATFORM_ID: i32 = 0x17;
@@ -221,7 +270,11 @@ pub const IA32_MISC_ENABLE: i32 = 0x1A0;
 pub const IA32_PAT: i32 = 0x277;
 pub const IA32_RTIT_CTL: i32 = 0x570;
 pub const MSR_PKG_C2_RESIDENCY: i32 = 0x60D;
+pub const IA32_TSC_DEADLINE: i32 = 0x6E0;
+pub const IA32_EFER: i32 = 0xC0000080u32 as i32;
+pub const MSR_STAR: i32 = 0xC0000081u32 as i32;
 pub const IA32_KERNEL_GS_BASE: i32 = 0xC0000101u32 as i32;
+pub const IA32_TSC_AUX: i32 = 0xC0000103u32 as i32;
 pub const MSR_AMD64_LS_CFG: i32 = 0xC0011020u32 as i32;
 pub const MSR_AMD64_DE_CFG: i32 = 0xC0011029u32 as i32;
 
@@ -231,9 +284,14 @@ pub const IA32_APIC_BASE_EN: i32 = 1 << 11;
 
 pub const APIC_ADDRESS: i32 = 0xFEE00000u32 as i32;
 
+pub const EFER_SCE: i32 = 1 << 0;
+pub const EFER_NXE: i32 = 1 << 11;
+
 pub const MXCSR_MASK: i32 = 0xffff;
+pub const MXCSR_DEFAULT: i32 = 0x1F80;
 pub const MXCSR_FZ: i32 = 1 << 15;
 pub const MXCSR_DAZ: i32 = 1 << 6;
+pub const MXCSR_PE: i32 = 1 << 5;
 pub const MXCSR_RC_SHIFT: i32 = 13;
 
 pub const VALID_TLB_ENTRY_MAX: i32 = 10000;
@@ -243,7 +301,12 @@ pub const TLB_NO_USER: i32 = 1 << 2;
 pub const TLB_IN_MAPPED_RANGE: i32 = 1 << 3;
 pub const TLB_GLOBAL: i32 = 1 << 4;
 pub const TLB_HAS_CODE: i32 = 1 << 5;
-pub const IVT_SIZE: u32 = 0x400;
+pub const TLB_NO_EXECUTE: i32 = 1 << 6;
+pub const TLB_BREAKPOINT: i32 = 1 << 7;
+// User page while cr4.SMEP is set: Not executable in supervisor mode
+pub const TLB_SMEP: i32 = 1 << 8;
+// User page while cr4.SMAP is set: Not accessible in supervisor mode, unless eflags.AC is set
+pub const TLB_SMAP: i32 = 1 << 9;
 pub const CPU_EXCEPTION_DE: i32 = 0;
 pub const CPU_EXCEPTION_DB: i32 = 1;
 pub const CPU_EXCEPTION_NMI: i32 = 2;
@@ -273,8 +336,21 @@ pub const TSC_RATE: f64 = 1_000_000.0;
 
 pub static mut cpuid_level: u32 = 0x16;
 
+// Check segment limits and types on data accesses in protected mode (opt-in, via the
+// segment_checks setting)
+pub static mut segment_checks: bool = false;
+
 pub static mut jit_block_boundary: bool = false;
 
+// The physical address of the cache line armed by monitor. The tlb entries of its page are marked
+// with TLB_HAS_CODE, so that writes to it go through jit_dirty_page
+pub static mut monitor_line: Option<u32> = None;
+pub const MONITOR_LINE_SIZE: u32 = 64;
+// Set together with in_hlt while waiting in mwait
+pub static mut in_mwait: bool = false;
+// mwait with ecx bit 0: interrupts end the wait even if they are masked
+pub static mut mwait_break_on_masked_interrupts: bool = false;
+
 const TSC_ENABLE_IMPRECISE_BROWSER_WORKAROUND: bool = true;
 
 #[cfg(debug_assertions)]
@@ -296,6 +372,20 @@ pub static mut tsc_speed: u64 = 1;
 // used for restoring the state
 pub static mut tsc_offset: u64 = 0;
 
+// the number of instructions executed since startup, see instruction_count
+static mut instruction_count_total: u64 = 0;
+static mut instruction_count_last_counter: u32 = 0;
+
+// icount mode: Instead of following the host's clock, time advances by one millisecond every
+// icount_rate instructions, making runs reproducible. 0 if disabled
+pub static mut icount_rate: f64 = 0.0;
+// added to instruction_count to get the instructions executed (or skipped over while halted)
+// since icount mode was enabled
+static mut icount_offset: u64 = 0;
+// state of the generator used by rdrand in icount mode
+pub const ICOUNT_RAND_SEED: u32 = 0x2545F491;
+pub static mut icount_rand_state: u32 = ICOUNT_RAND_SEED;
+
 pub struct Code {
     pub wasm_table_index: jit::WasmTableIndex,
     pub state_flags: CachedStateFlags,
@@ -312,6 +402,26 @@ pub static mut in_jit: bool = false;
 
 pub static mut jit_fault: Option<(i32, Option<i32>)> = None;
 
+// DR6 bits of debug traps caused by the current instruction (data breakpoints, single-step, task
+// switch). #DB is raised after the instruction has completed, and discarded if the instruction
+// faults
+pub static mut debug_trap_pending: i32 = 0;
+// Set by mov ss and pop ss: Pending debug traps are delayed until after the next instruction
+pub static mut debug_traps_inhibited: bool = false;
+// Set when jitted code exited due to a data breakpoint, the instruction is rerun in the interpreter
+pub static mut debug_force_interpreter: bool = false;
+
+// The exception that call_interrupt_vector is delivering, None while delivering software and
+// external interrupts. Exceptions raised during the delivery are combined with it according to
+// their classes (see "Interrupt 8 - Double Fault Exception" in the SDM)
+pub static mut exception_in_delivery: Option<i32> = None;
+// The two exceptions that caused the double fault that is being delivered
+pub static mut double_fault_cause: (i32, i32) = (0, 0);
+// Set by the last triple fault, which put the cpu into the shutdown state
+pub static mut shutdown_reason: Option<ShutdownReason> = None;
+// Set by a triple fault until the embedder has handled the shutdown, see take_shutdown_pending
+pub static mut shutdown_pending: bool = false;
+
 pub enum LastJump {
     Interrupt {
         phys_addr: u32,
@@ -368,6 +478,40 @@ pub enum SelectorNullOrInvalid {
     OutsideOfTableLimit,
 }
 
+#[derive(PartialEq)]
+enum ExceptionClass {
+    Benign,
+    Contributory,
+    PageFault,
+    DoubleFault,
+}
+
+fn exception_class(vector: i32) -> ExceptionClass {
+    match vector {
+        CPU_EXCEPTION_DE | CPU_EXCEPTION_TS | CPU_EXCEPTION_NP | CPU_EXCEPTION_SS
+        | CPU_EXCEPTION_GP => ExceptionClass::Contributory,
+        CPU_EXCEPTION_PF | CPU_EXCEPTION_VE => ExceptionClass::PageFault,
+        CPU_EXCEPTION_DF => ExceptionClass::DoubleFault,
+        _ => ExceptionClass::Benign,
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct ShutdownReason {
+    // The first exception, the one that turned it into a double fault and the one raised while
+    // delivering the double fault
+    pub vectors: [i32; 3],
+    pub eip: i32,
+    pub cr2: i32,
+}
+
+#[derive(PartialEq)]
+pub enum TaskSwitchSource {
+    Jmp,
+    CallOrInt,
+    Iret,
+}
+
 pub struct SegmentDescriptor {
     pub raw: u64,
 }
@@ -407,6 +551,11 @@ impl SegmentDescriptor {
             raw: self.raw | 2 << 40,
         }
     }
+    pub fn clear_busy(&self) -> SegmentDescriptor {
+        SegmentDescriptor {
+            raw: self.raw & !(2 << 40),
+        }
+    }
 }
 
 pub struct InterruptDescriptor {
@@ -438,11 +587,14 @@ pub unsafe fn switch_cs_real_mode(selector: i32) {
     update_cs_size(false);
 }
 
-unsafe fn get_tss_ss_esp(dpl: u8) -> OrPageFault<(i32, i32)> {
+/// Read the stack of privilege level dpl from the tss. Raises #TS(tr) if it's outside of the tss
+unsafe fn get_tss_ss_esp(dpl: u8, ext: i32) -> OrPageFault<(i32, i32)> {
     Ok(if *tss_size_32 {
         let tss_stack_offset = ((dpl << 3) + 4) as u32;
         if tss_stack_offset + 7 > *segment_limits.offset(TR as isize) {
-            panic!("#TS handler");
+            dbg_log!("#ts stack for dpl={} outside of tss", dpl);
+            trigger_ts(*sreg.offset(TR as isize) as i32 & !3 | ext);
+            return Err(());
         }
         let addr = translate_address_system_read(
             *segment_offsets.offset(TR as isize) + tss_stack_offset as i32,
@@ -453,7 +605,9 @@ unsafe fn get_tss_ss_esp(dpl: u8) -> OrPageFault<(i32, i32)> {
     else {
         let tss_stack_offset = ((dpl << 2) + 2) as u32;
         if tss_stack_offset + 3 > *segment_limits.offset(TR as isize) {
-            panic!("#TS handler");
+            dbg_log!("#ts stack for dpl={} outside of tss", dpl);
+            trigger_ts(*sreg.offset(TR as isize) as i32 & !3 | ext);
+            return Err(());
         }
         let addr = translate_address_system_read(
             *segment_offsets.offset(TR as isize) + tss_stack_offset as i32,
@@ -463,6 +617,153 @@ unsafe fn get_tss_ss_esp(dpl: u8) -> OrPageFault<(i32, i32)> {
     })
 }
 
+/// Look up and check a stack segment that is loaded along with a change of the privilege level,
+/// before any state is modified. An unusable selector raises `fault` (#TS if the selector was read
+/// from the tss, #GP on returns to an outer level), a segment that isn't present raises #SS
+unsafe fn lookup_new_ss(
+    selector_raw: i32,
+    new_cpl: u8,
+    fault: unsafe fn(i32),
+    ext: i32,
+) -> OrPageFault<SegmentDescriptor> {
+    let selector = SegmentSelector::of_u16(selector_raw as u16);
+    let descriptor = match lookup_segment_selector(selector)? {
+        Ok((desc, _)) => desc,
+        Err(SelectorNullOrInvalid::IsNull) => {
+            dbg_log!("null ss for cpl={}", new_cpl);
+            fault(ext);
+            return Err(());
+        },
+        Err(SelectorNullOrInvalid::OutsideOfTableLimit) => {
+            dbg_log!("invalid ss sel={:x}", selector_raw);
+            fault(selector_raw & !3 | ext);
+            return Err(());
+        },
+    };
+
+    if descriptor.is_system()
+        || selector.rpl() != new_cpl
+        || !descriptor.is_writable()
+        || descriptor.dpl() != new_cpl
+    {
+        dbg_log!("unusable ss for cpl={} sel={:x}", new_cpl, selector_raw);
+        fault(selector_raw & !3 | ext);
+        return Err(());
+    }
+
+    if !descriptor.is_present() {
+        dbg_log!("#SS for loading non-present in SS sel={:x}", selector_raw);
+        trigger_ss(selector_raw & !3 | ext);
+        return Err(());
+    }
+
+    Ok(descriptor)
+}
+
+/// The offsets and sizes written by pushing size bytes below esp, push_size bytes at a time. On
+/// 16-bit stacks, sp wraps around between two pushes, which splits them into two ranges.
+/// Otherwise, the second range is empty
+fn stack_push_ranges(is_big: bool, esp: i32, size: i32, push_size: i32) -> [(i32, i32); 2] {
+    let sp = esp & 0xFFFF;
+    if is_big {
+        [(esp - size, size), (0, 0)]
+    }
+    else if sp == 0 || sp >= size || sp % push_size != 0 {
+        // a push that crosses offset 0xFFFF doesn't wrap, and is outside of the limit
+        [(sp - size & 0xFFFF, size), (0, 0)]
+    }
+    else {
+        [(0, sp), (0x10000 - (size - sp), size - sp)]
+    }
+}
+
+/// Whether size bytes can be pushed below esp, push_size bytes at a time, onto a stack segment with
+/// the given access byte, limit and B bit
+fn stack_push_within_limit(
+    access: u8,
+    limit: u32,
+    is_big: bool,
+    esp: i32,
+    size: i32,
+    push_size: i32,
+) -> bool {
+    stack_push_ranges(is_big, esp, size, push_size).iter().all(|&(start, size)| {
+        size == 0 || segment_range_within_limit(access, limit, is_big, start as u32, size as u32)
+    })
+}
+
+unsafe fn current_stack_push_within_limit(size: i32, push_size: i32) -> bool {
+    stack_push_within_limit(
+        *segment_access_bytes.offset(SS as isize),
+        *segment_limits.offset(SS as isize),
+        *stack_size_32,
+        read_reg32(ESP),
+        size,
+        push_size,
+    )
+}
+
+/// Translate the pages written by pushing size bytes below esp onto a new stack with the given
+/// base, with supervisor access
+unsafe fn translate_stack_push_system(
+    base: i32,
+    is_big: bool,
+    esp: i32,
+    size: i32,
+    push_size: i32,
+) -> OrPageFault<()> {
+    for &(start, size) in stack_push_ranges(is_big, esp, size, push_size).iter() {
+        if size != 0 {
+            translate_address_system_write(base + start)?;
+            translate_address_system_write(base + start + size - 1)?;
+        }
+    }
+    Ok(())
+}
+
+/// Like writable_or_pagefault, for pushing size bytes onto the current stack
+unsafe fn writable_or_pagefault_stack_push(size: i32, push_size: i32) -> OrPageFault<()> {
+    let ranges = stack_push_ranges(*stack_size_32, read_reg32(ESP), size, push_size);
+    for &(start, size) in ranges.iter() {
+        if size != 0 {
+            writable_or_pagefault(get_seg_ss() + start, size)?;
+        }
+    }
+    Ok(())
+}
+
+/// Load cs from a descriptor that has already been checked, with the cpl as the rpl
+unsafe fn load_cs(selector_raw: i32, descriptor: &SegmentDescriptor) {
+    *sreg.offset(CS as isize) = selector_raw as u16 & !3 | *cpl as u16;
+    *segment_is_null.offset(CS as isize) = false;
+    *segment_limits.offset(CS as isize) = descriptor.effective_limit();
+    *segment_offsets.offset(CS as isize) = descriptor.base();
+    *segment_access_bytes.offset(CS as isize) = descriptor.access_byte();
+    update_cs_size(descriptor.is_32());
+}
+
+/// After a return to an outer privilege level: Data segments that the new cpl can't access are set
+/// to null
+unsafe fn clear_inaccessible_data_segments() {
+    for reg in [ES, DS, FS, GS] {
+        let access = *segment_access_bytes.offset(reg as isize);
+        let dpl = access >> 5 & 3;
+        let executable = access & 8 == 8;
+        let conforming = access & 4 == 4;
+        if dpl < *cpl && !(executable && conforming) {
+            dbg_log!(
+                "set segment to null sreg={} dpl={} executable={} conforming={}",
+                reg,
+                dpl,
+                executable,
+                conforming
+            );
+            *segment_is_null.offset(reg as isize) = true;
+            *sreg.offset(reg as isize) = 0;
+        }
+    }
+}
+
 pub unsafe fn iret16() { iret(true); }
 pub unsafe fn iret32() { iret(false); }
 
@@ -474,24 +775,40 @@ pub unsafe fn iret(is_16: bool) {
         return;
     }
 
+    if *protected_mode && !vm86_mode() && *flags & FLAG_NT != 0 {
+        // nested task: return to the task in the backlink field of the current tss
+        let backlink = return_on_pagefault!(tss_read16(
+            *segment_offsets.offset(TR as isize) + TSR_BACKLINK
+        ));
+        if !SegmentSelector::of_u16(backlink as u16).is_gdt() {
+            dbg_log!("#ts iret to task in ldt: {:x}", backlink);
+            trigger_ts(backlink & !3);
+            return;
+        }
+        do_task_switch(backlink, TaskSwitchSource::Iret, None);
+        return;
+    }
+
     let (new_eip, new_cs, mut new_flags) = if is_16 {
         (
-            return_on_pagefault!(safe_read16(get_stack_pointer(0))),
-            return_on_pagefault!(safe_read16(get_stack_pointer(2))),
-            return_on_pagefault!(safe_read16(get_stack_pointer(4))),
+            return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0))),
+            return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(2))),
+            return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(4))),
         )
     }
     else {
         (
-            return_on_pagefault!(safe_read32s(get_stack_pointer(0))),
-            return_on_pagefault!(safe_read16(get_stack_pointer(4))),
-            return_on_pagefault!(safe_read32s(get_stack_pointer(8))),
+            return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0))),
+            return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(4))),
+            return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(8))),
         )
     };
 
     if !*protected_mode || (vm86_mode() && getiopl() == 3) {
         if new_eip as u32 & 0xFFFF0000 != 0 {
-            panic!("#GP handler");
+            dbg_log!("#gp iret: eip above 64k: {:x}", new_eip as u32);
+            trigger_gp(0);
+            return;
         }
 
         switch_cs_real_mode(new_cs);
@@ -518,14 +835,6 @@ pub unsafe fn iret(is_16: bool) {
 
     dbg_assert!(!vm86_mode());
 
-    if *flags & FLAG_NT != 0 {
-        if DEBUG {
-            panic!("NT");
-        }
-        trigger_gp(0);
-        return;
-    }
-
     if new_flags & FLAG_VM != 0 {
         if *cpl == 0 {
             // return to virtual 8086 mode
@@ -533,13 +842,13 @@ pub unsafe fn iret(is_16: bool) {
             // vm86 cannot be set in 16 bit flag
             dbg_assert!(!is_16);
 
-            let temp_esp = return_on_pagefault!(safe_read32s(get_stack_pointer(12)));
-            let temp_ss = return_on_pagefault!(safe_read16(get_stack_pointer(16)));
+            let temp_esp = return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(12)));
+            let temp_ss = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(16)));
 
-            let new_es = return_on_pagefault!(safe_read16(get_stack_pointer(20)));
-            let new_ds = return_on_pagefault!(safe_read16(get_stack_pointer(24)));
-            let new_fs = return_on_pagefault!(safe_read16(get_stack_pointer(28)));
-            let new_gs = return_on_pagefault!(safe_read16(get_stack_pointer(32)));
+            let new_es = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(20)));
+            let new_ds = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(24)));
+            let new_fs = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(28)));
+            let new_gs = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(32)));
 
             // no exceptions below
 
@@ -554,7 +863,7 @@ pub unsafe fn iret(is_16: bool) {
                 || !switch_seg(FS, new_fs)
                 || !switch_seg(GS, new_gs)
             {
-                // XXX: Should be checked before side effects
+                // can't fail in vm86 mode
                 dbg_assert!(false);
             }
 
@@ -562,7 +871,7 @@ pub unsafe fn iret(is_16: bool) {
 
             write_reg32(ESP, temp_esp);
             if !switch_seg(SS, temp_ss) {
-                // XXX
+                // can't fail in vm86 mode
                 dbg_assert!(false);
             }
 
@@ -586,37 +895,34 @@ pub unsafe fn iret(is_16: bool) {
     let cs_selector = SegmentSelector::of_u16(new_cs as u16);
     let cs_descriptor = match return_on_pagefault!(lookup_segment_selector(cs_selector)) {
         Ok((desc, _)) => desc,
-        Err(selector_unusable) => match selector_unusable {
-            SelectorNullOrInvalid::IsNull => {
-                panic!("Unimplemented: CS selector is null");
-            },
-            SelectorNullOrInvalid::OutsideOfTableLimit => {
-                panic!("Unimplemented: CS selector is invalid");
-            },
+        Err(SelectorNullOrInvalid::IsNull) => {
+            dbg_log!("#gp iret: null cs");
+            trigger_gp(0);
+            return;
+        },
+        Err(SelectorNullOrInvalid::OutsideOfTableLimit) => {
+            dbg_log!("#gp iret: invalid cs: {:x}", new_cs);
+            trigger_gp(new_cs & !3);
+            return;
         },
     };
 
-    if new_eip as u32 > cs_descriptor.effective_limit() {
-        dbg_log!(
-            "#gp iret: new_eip > cs_descriptor.effective_limit, new_eip={:x} cs_descriptor.effective_limit={:x}",
-            new_eip as u32,
-            cs_descriptor.effective_limit()
-        );
+    if cs_descriptor.is_system() || !cs_descriptor.is_executable() {
+        dbg_log!("#gp iret: non-executable cs: {:x}", new_cs);
         trigger_gp(new_cs & !3);
         return;
     }
 
-    if !cs_descriptor.is_present() {
-        panic!("not present");
-    }
-    if !cs_descriptor.is_executable() {
-        panic!("not exec");
-    }
     if cs_selector.rpl() < *cpl {
-        panic!("rpl < cpl");
+        dbg_log!("#gp iret: cs rpl < cpl: {:x}", new_cs);
+        trigger_gp(new_cs & !3);
+        return;
     }
+
     if cs_descriptor.is_dc() && cs_descriptor.dpl() > cs_selector.rpl() {
-        panic!("conforming and dpl > rpl");
+        dbg_log!("#gp iret: conforming cs and dpl > rpl: {:x}", new_cs);
+        trigger_gp(new_cs & !3);
+        return;
     }
 
     if !cs_descriptor.is_dc() && cs_selector.rpl() != cs_descriptor.dpl() {
@@ -629,131 +935,84 @@ pub unsafe fn iret(is_16: bool) {
         return;
     }
 
-    if cs_selector.rpl() > *cpl {
+    if !cs_descriptor.is_present() {
+        dbg_log!("#np iret: cs not present: {:x}", new_cs);
+        trigger_np(new_cs & !3);
+        return;
+    }
+
+    let new_cpl = cs_selector.rpl();
+
+    let outer_stack = if new_cpl > *cpl {
         // outer privilege return
         let (temp_esp, temp_ss) = if is_16 {
             (
-                return_on_pagefault!(safe_read16(get_stack_pointer(6))),
-                return_on_pagefault!(safe_read16(get_stack_pointer(8))),
+                return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(6))),
+                return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(8))),
             )
         }
         else {
             (
-                return_on_pagefault!(safe_read32s(get_stack_pointer(12))),
-                return_on_pagefault!(safe_read16(get_stack_pointer(16))),
+                return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(12))),
+                return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(16))),
             )
         };
 
-        let ss_selector = SegmentSelector::of_u16(temp_ss as u16);
-        let ss_descriptor = match return_on_pagefault!(lookup_segment_selector(ss_selector)) {
-            Ok((desc, _)) => desc,
-            Err(selector_unusable) => match selector_unusable {
-                SelectorNullOrInvalid::IsNull => {
-                    dbg_log!("#GP for loading 0 in SS sel={:x}", temp_ss);
-                    dbg_trace();
-                    trigger_gp(0);
-                    return;
-                },
-                SelectorNullOrInvalid::OutsideOfTableLimit => {
-                    dbg_log!("#GP for loading invalid in SS sel={:x}", temp_ss);
-                    trigger_gp(temp_ss & !3);
-                    return;
-                },
-            },
-        };
-        let new_cpl = cs_selector.rpl();
+        let ss_descriptor = return_on_pagefault!(lookup_new_ss(temp_ss, new_cpl, trigger_gp, 0));
+        Some((temp_esp, temp_ss, ss_descriptor))
+    }
+    else {
+        None
+    };
 
-        if ss_descriptor.is_system()
-            || ss_selector.rpl() != new_cpl
-            || !ss_descriptor.is_writable()
-            || ss_descriptor.dpl() != new_cpl
-        {
-            dbg_log!("#GP for loading invalid in SS sel={:x}", temp_ss);
-            dbg_trace();
-            trigger_gp(temp_ss & !3);
-            return;
-        }
+    if new_eip as u32 > cs_descriptor.effective_limit() {
+        dbg_log!(
+            "#gp iret: new_eip > cs_descriptor.effective_limit, new_eip={:x} cs_descriptor.effective_limit={:x}",
+            new_eip as u32,
+            cs_descriptor.effective_limit()
+        );
+        trigger_gp(0);
+        return;
+    }
 
-        if !ss_descriptor.is_present() {
-            dbg_log!("#SS for loading non-present in SS sel={:x}", temp_ss);
-            dbg_trace();
-            trigger_ss(temp_ss & !3);
-            return;
-        }
+    // no exceptions below
 
-        // no exceptions below
+    if is_16 {
+        update_eflags(new_flags | *flags & !0xFFFF);
+    }
+    else {
+        update_eflags(new_flags);
+    }
 
-        if is_16 {
-            update_eflags(new_flags | *flags & !0xFFFF);
-        }
-        else {
-            update_eflags(new_flags);
-        }
+    // update vip and vif, which are not changed by update_eflags
+    if *cpl == 0 && !is_16 {
+        *flags = *flags & !FLAG_VIF & !FLAG_VIP | (new_flags & (FLAG_VIF | FLAG_VIP));
+    }
 
-        *cpl = cs_selector.rpl();
+    if let Some((temp_esp, temp_ss, ss_descriptor)) = outer_stack {
+        *cpl = new_cpl;
         cpl_changed();
 
-        if !switch_seg(SS, temp_ss) {
-            // XXX
-            dbg_assert!(false);
-        }
-
+        load_segment(SS, temp_ss, &ss_descriptor);
         set_stack_reg(temp_esp);
 
-        if *cpl == 0 && !is_16 {
-            *flags = *flags & !FLAG_VIF & !FLAG_VIP | (new_flags & (FLAG_VIF | FLAG_VIP));
-        }
-
-        for reg in [ES, DS, FS, GS] {
-            let access = *segment_access_bytes.offset(reg as isize);
-            let dpl = access >> 5 & 3;
-            let executable = access & 8 == 8;
-            let conforming = access & 4 == 4;
-            if dpl < *cpl && !(executable && conforming) {
-                dbg_log!(
-                    "set segment to null sreg={} dpl={} executable={} conforming={}",
-                    reg,
-                    dpl,
-                    executable,
-                    conforming
-                );
-                *segment_is_null.offset(reg as isize) = true;
-                *sreg.offset(reg as isize) = 0;
-            }
-        }
-    }
-    else if cs_selector.rpl() == *cpl {
-        // same privilege return
-        // no exceptions below
-        if is_16 {
-            adjust_stack_reg(3 * 2);
-            update_eflags(new_flags | *flags & !0xFFFF);
-        }
-        else {
-            adjust_stack_reg(3 * 4);
-            update_eflags(new_flags);
-        }
-
-        // update vip and vif, which are not changed by update_eflags
-        if *cpl == 0 && !is_16 {
-            *flags = *flags & !FLAG_VIF & !FLAG_VIP | (new_flags & (FLAG_VIF | FLAG_VIP));
-        }
+        clear_inaccessible_data_segments();
     }
     else {
-        dbg_assert!(false);
+        // same privilege return
+        adjust_stack_reg(if is_16 { 3 * 2 } else { 3 * 4 });
     }
 
-    *sreg.offset(CS as isize) = new_cs as u16;
-    dbg_assert!((new_cs & 3) == *cpl as i32);
-
-    update_cs_size(cs_descriptor.is_32());
-
-    *segment_limits.offset(CS as isize) = cs_descriptor.effective_limit();
-    *segment_offsets.offset(CS as isize) = cs_descriptor.base();
-    *segment_access_bytes.offset(CS as isize) = cs_descriptor.access_byte();
+    dbg_assert!(new_cpl == *cpl);
+    load_cs(new_cs, &cs_descriptor);
 
     *instruction_pointer = new_eip + get_seg_cs();
 
+    if !is_16 {
+        // The resume flag is not changed by update_eflags (popf), but restored by iret
+        *flags = *flags & !FLAG_RF | new_flags & FLAG_RF;
+    }
+
     update_state_flags();
 
     // iret end
@@ -766,6 +1025,104 @@ pub unsafe fn call_interrupt_vector(
     is_software_int: bool,
     error_code: Option<i32>,
 ) {
+    // Debug traps of an instruction that faults or executes int n are not reported. External
+    // interrupts are delivered between instructions and don't discard them
+    debug_trap_pending = 0;
+
+    let (interrupt_nr, error_code) = match exception_in_delivery {
+        Some(first) if !is_software_int => {
+            let first_class = exception_class(first);
+            let second_class = exception_class(interrupt_nr);
+            let second_is_fault = second_class == ExceptionClass::Contributory
+                || second_class == ExceptionClass::PageFault;
+
+            if first_class == ExceptionClass::DoubleFault && second_is_fault {
+                triple_fault(interrupt_nr);
+                return;
+            }
+            else if (first_class == ExceptionClass::Contributory
+                && second_class == ExceptionClass::Contributory)
+                || (first_class == ExceptionClass::PageFault && second_is_fault)
+            {
+                dbg_log!("#df: exception {:x} while delivering {:x}", interrupt_nr, first);
+                double_fault_cause = (first, interrupt_nr);
+                (CPU_EXCEPTION_DF, Some(0))
+            }
+            else {
+                // handled serially
+                (interrupt_nr, error_code)
+            }
+        },
+        _ => (interrupt_nr, error_code),
+    };
+
+    let outer_exception = exception_in_delivery;
+    exception_in_delivery = if is_software_int { None } else { Some(interrupt_nr) };
+    deliver_interrupt(interrupt_nr, is_software_int, error_code);
+    exception_in_delivery = outer_exception;
+}
+
+/// External interrupts are benign, exceptions raised while delivering them are handled serially
+pub unsafe fn call_external_interrupt_vector(interrupt_nr: i32) {
+    let outer_exception = exception_in_delivery.take();
+    deliver_interrupt(interrupt_nr, false, None);
+    exception_in_delivery = outer_exception;
+}
+
+/// A contributory exception or page fault while delivering a double fault: Stop executing
+/// instructions, like hlt with interrupts disabled, until the embedder resets the cpu. This may
+/// run inside of jitted code, so the embedder is only notified after main_loop has returned
+unsafe fn triple_fault(interrupt_nr: i32) {
+    let (first, second) = double_fault_cause;
+    let reason = ShutdownReason {
+        vectors: [first, second, interrupt_nr],
+        eip: get_real_eip(),
+        cr2: *cr.offset(2),
+    };
+    dbg_log!(
+        "triple fault: exceptions={:x?} eip={:x} cr2={:x}",
+        reason.vectors,
+        reason.eip,
+        reason.cr2
+    );
+    shutdown_reason = Some(reason);
+
+    *flags &= !FLAG_INTERRUPT;
+    *in_hlt = true;
+    shutdown_pending = true;
+    // Like hlt, stop the interpreter, which would otherwise retry the faulting instruction
+    jit_block_boundary = true;
+}
+
+/// Whether a triple fault happened since the last call, called after main_loop
+#[no_mangle]
+pub unsafe fn take_shutdown_pending() -> bool {
+    let pending = shutdown_pending;
+    shutdown_pending = false;
+    pending
+}
+
+/// Whether the cpu has been shut down by a triple fault, see get_shutdown_reason
+#[no_mangle]
+pub unsafe fn has_shutdown_reason() -> bool { shutdown_reason.is_some() }
+
+/// Field 0 to 2 of the reason of the last shutdown are the exceptions that caused it, 3 is eip
+/// and 4 is cr2
+#[no_mangle]
+pub unsafe fn get_shutdown_reason(field: u32) -> i32 {
+    let reason = shutdown_reason.unwrap();
+    match field {
+        0..=2 => reason.vectors[field as usize],
+        3 => reason.eip,
+        4 => reason.cr2,
+        _ => {
+            dbg_assert!(false);
+            0
+        },
+    }
+}
+
+unsafe fn deliver_interrupt(interrupt_nr: i32, is_software_int: bool, error_code: Option<i32>) {
     if *protected_mode {
         if vm86_mode() && *cr.offset(4) & CR4_VME != 0 {
             panic!("Unimplemented: VME");
@@ -778,10 +1135,14 @@ pub unsafe fn call_interrupt_vector(
             return;
         }
 
+        // the external event bit of error codes: Set unless raised by an int instruction
+        let ext = if is_software_int { 0 } else { 1 };
+
         if interrupt_nr << 3 | 7 > *idtr_size {
-            dbg_log!("interrupt_nr={:x} idtr_size={:x}", interrupt_nr, *idtr_size);
+            dbg_log!("#gp interrupt_nr={:x} idtr_size={:x}", interrupt_nr, *idtr_size);
             dbg_trace();
-            panic!("Unimplemented: #GP handler");
+            trigger_gp(interrupt_nr << 3 | 2 | ext);
+            return;
         }
 
         let descriptor_address = return_on_pagefault!(translate_address_system_read(
@@ -790,7 +1151,7 @@ pub unsafe fn call_interrupt_vector(
 
         let descriptor = InterruptDescriptor::of_u64(read64s(descriptor_address) as u64);
 
-        let mut offset = descriptor.offset();
+        let offset = descriptor.offset();
         let selector = descriptor.selector() as i32;
         let dpl = descriptor.dpl();
         let gate_type = descriptor.gate_type();
@@ -802,34 +1163,38 @@ pub unsafe fn call_interrupt_vector(
             return;
         }
 
-        if gate_type != InterruptDescriptor::TRAP_GATE
+        // task gates have no 32-bit variant, type 0xD is reserved
+        if (gate_type != InterruptDescriptor::TRAP_GATE
             && gate_type != InterruptDescriptor::INTERRUPT_GATE
-            && gate_type != InterruptDescriptor::TASK_GATE
+            && gate_type != InterruptDescriptor::TASK_GATE)
+            || (gate_type == InterruptDescriptor::TASK_GATE && descriptor.is_32())
         {
             // invalid gate_type
             dbg_log!(
-                "gate type invalid. gate_type=0b{:b} raw={:b}",
+                "#gp gate type invalid. gate_type=0b{:b} raw={:b}",
                 gate_type,
                 descriptor.raw
             );
             dbg_trace();
-            panic!("Unimplemented: #GP handler");
+            trigger_gp(interrupt_nr << 3 | 2 | ext);
+            return;
         }
 
         if !descriptor.reserved_zeros_are_valid() {
             dbg_log!(
-                "reserved 0s violated. gate_type=0b{:b} raw={:b}",
+                "#gp reserved 0s violated. gate_type=0b{:b} raw={:b}",
                 gate_type,
                 descriptor.raw
             );
             dbg_trace();
-            panic!("Unimplemented: #GP handler");
+            trigger_gp(interrupt_nr << 3 | 2 | ext);
+            return;
         }
 
         if !descriptor.is_present() {
             // present bit not set
             dbg_log!("#np int descriptor not present, int={}", interrupt_nr);
-            trigger_np(interrupt_nr << 3 | 2);
+            trigger_np(interrupt_nr << 3 | 2 | ext);
             return;
         }
 
@@ -842,9 +1207,8 @@ pub unsafe fn call_interrupt_vector(
                 dpl
             );
             dbg_trace();
-            dbg_assert!(descriptor.is_32(), "TODO: Check this (likely #GP)");
-            dbg_assert!(offset == 0, "TODO: Check this (likely #GP)");
-            do_task_switch(selector, error_code);
+            // the offset of task gates is unused
+            do_task_switch(selector, TaskSwitchSource::CallOrInt, error_code);
             return;
         }
 
@@ -852,116 +1216,147 @@ pub unsafe fn call_interrupt_vector(
             SegmentSelector::of_u16(selector as u16)
         )) {
             Ok((desc, _)) => desc,
-            Err(selector_unusable) => match selector_unusable {
-                SelectorNullOrInvalid::IsNull => {
-                    dbg_log!("is null");
-                    panic!("Unimplemented: #GP handler");
-                },
-                SelectorNullOrInvalid::OutsideOfTableLimit => {
-                    dbg_log!("is invalid");
-                    panic!("Unimplemented: #GP handler (error code)");
-                },
+            Err(SelectorNullOrInvalid::IsNull) => {
+                dbg_log!("#gp interrupt gate with null cs");
+                trigger_gp(ext);
+                return;
+            },
+            Err(SelectorNullOrInvalid::OutsideOfTableLimit) => {
+                dbg_log!("#gp interrupt gate with invalid cs: {:x}", selector);
+                trigger_gp(selector & !3 | ext);
+                return;
             },
         };
 
-        dbg_assert!(offset as u32 <= cs_segment_descriptor.effective_limit());
-
-        if !cs_segment_descriptor.is_executable() || cs_segment_descriptor.dpl() > *cpl {
-            dbg_log!("not exec");
-            panic!("Unimplemented: #GP handler");
+        if cs_segment_descriptor.is_system()
+            || !cs_segment_descriptor.is_executable()
+            || cs_segment_descriptor.dpl() > *cpl
+        {
+            dbg_log!("#gp interrupt gate with non-executable cs or dpl > cpl: {:x}", selector);
+            trigger_gp(selector & !3 | ext);
+            return;
         }
         if !cs_segment_descriptor.is_present() {
             // kvm-unit-test
-            dbg_log!("not present");
-            trigger_np(interrupt_nr << 3 | 2);
+            dbg_log!("#np interrupt gate with non-present cs: {:x}", selector);
+            trigger_np(selector & !3 | ext);
+            return;
+        }
+
+        let offset = if descriptor.is_32() { offset } else { offset & 0xFFFF };
+
+        if offset as u32 > cs_segment_descriptor.effective_limit() {
+            dbg_log!("#gp interrupt gate offset outside of cs limit: {:x}", offset as u32);
+            trigger_gp(ext);
             return;
         }
 
         let old_flags = get_eflags();
 
-        if !cs_segment_descriptor.is_dc() && cs_segment_descriptor.dpl() < *cpl {
+        let bytes_per_arg = if descriptor.is_32() { 4 } else { 2 };
+        let error_code_space = if error_code.is_some() { 1 } else { 0 };
+
+        let new_stack = if !cs_segment_descriptor.is_dc() && cs_segment_descriptor.dpl() < *cpl {
             // inter privilege level interrupt
             // interrupt from vm86 mode
 
             if old_flags & FLAG_VM != 0 && cs_segment_descriptor.dpl() != 0 {
-                panic!("Unimplemented: #GP handler for non-0 cs segment dpl when in vm86 mode");
+                dbg_log!("#gp interrupt from vm86 mode to cs with dpl != 0: {:x}", selector);
+                trigger_gp(selector & !3 | ext);
+                return;
             }
 
             let (new_ss, new_esp) =
-                return_on_pagefault!(get_tss_ss_esp(cs_segment_descriptor.dpl()));
+                return_on_pagefault!(get_tss_ss_esp(cs_segment_descriptor.dpl(), ext));
 
-            let ss_segment_selector = SegmentSelector::of_u16(new_ss as u16);
-            let ss_segment_descriptor =
-                match return_on_pagefault!(lookup_segment_selector(ss_segment_selector)) {
-                    Ok((desc, _)) => desc,
-                    Err(_) => {
-                        panic!("Unimplemented: #TS handler");
-                    },
-                };
+            let ss_segment_descriptor = return_on_pagefault!(lookup_new_ss(
+                new_ss,
+                cs_segment_descriptor.dpl(),
+                trigger_ts,
+                ext
+            ));
 
-            dbg_assert!(!ss_segment_descriptor.is_dc(), "TODO: Handle direction bit");
-            dbg_assert!(new_esp as u32 <= ss_segment_descriptor.effective_limit());
-            dbg_assert!(!ss_segment_descriptor.is_system() && ss_segment_descriptor.is_writable());
+            let vm86_space = if (old_flags & FLAG_VM) == FLAG_VM { 4 } else { 0 };
+            let stack_space = bytes_per_arg * (5 + error_code_space + vm86_space);
 
-            if ss_segment_selector.rpl() != cs_segment_descriptor.dpl() {
-                panic!("Unimplemented: #TS handler");
-            }
-            if ss_segment_descriptor.dpl() != cs_segment_descriptor.dpl()
-                || !ss_segment_descriptor.is_rw()
-            {
-                panic!("Unimplemented: #TS handler");
-            }
-            if !ss_segment_descriptor.is_present() {
-                panic!("Unimplemented: #TS handler");
+            if !stack_push_within_limit(
+                ss_segment_descriptor.access_byte(),
+                ss_segment_descriptor.effective_limit(),
+                ss_segment_descriptor.is_32(),
+                new_esp,
+                stack_space,
+                bytes_per_arg,
+            ) {
+                dbg_log!("#ss interrupt stack outside of limit ss={:x} esp={:x}", new_ss, new_esp);
+                trigger_ss(new_ss & !3 | ext);
+                return;
             }
 
-            let old_esp = read_reg32(ESP);
-            let old_ss = *sreg.offset(SS as isize) as i32;
+            return_on_pagefault!(translate_stack_push_system(
+                ss_segment_descriptor.base(),
+                ss_segment_descriptor.is_32(),
+                new_esp,
+                stack_space,
+                bytes_per_arg
+            ));
 
-            let error_code_space = if error_code.is_some() { 1 } else { 0 };
-            let vm86_space = if (old_flags & FLAG_VM) == FLAG_VM { 4 } else { 0 };
-            let bytes_per_arg = if descriptor.is_32() { 4 } else { 2 };
+            Some((new_ss, new_esp, ss_segment_descriptor))
+        }
+        else {
+            // intra privilege level interrupt
 
-            let stack_space = bytes_per_arg * (5 + error_code_space + vm86_space);
-            let new_stack_pointer = ss_segment_descriptor.base()
-                + if ss_segment_descriptor.is_32() {
-                    new_esp - stack_space
-                }
-                else {
-                    new_esp - stack_space & 0xFFFF
-                };
+            //dbg_log!("Intra privilege interrupt gate=" + h(selector, 4) + ":" + h(offset >>> 0, 8) +
+            //        " gate_type=" + gate_type + " 16bit=" + descriptor.is_32() +
+            //        " cpl=" + *cpl + " dpl=" + segment_descriptor.dpl() + " conforming=" + +segment_descriptor.is_dc(), );
+            //debug.dump_regs_short();
 
-            return_on_pagefault!(translate_address_system_write(new_stack_pointer));
-            return_on_pagefault!(translate_address_system_write(
-                ss_segment_descriptor.base() + new_esp - 1
-            ));
+            if old_flags & FLAG_VM != 0 {
+                dbg_log!("#gp interrupt from vm86 mode without privilege change: {:x}", selector);
+                trigger_gp(selector & !3 | ext);
+                return;
+            }
+
+            let stack_space = bytes_per_arg * (3 + error_code_space);
+
+            if !current_stack_push_within_limit(stack_space, bytes_per_arg) {
+                dbg_log!("#ss interrupt stack outside of limit");
+                trigger_ss(ext);
+                return;
+            }
+
+            // XXX: with current cpl or with cpl 0?
+            return_on_pagefault!(writable_or_pagefault_stack_push(stack_space, bytes_per_arg));
+
+            None
+        };
+
+        // no exceptions below
+
+        if let Some((new_ss, new_esp, ss_segment_descriptor)) = new_stack {
+            let old_esp = read_reg32(ESP);
+            let old_ss = *sreg.offset(SS as isize) as i32;
 
-            // no exceptions below
             *cpl = cs_segment_descriptor.dpl();
             cpl_changed();
 
-            update_cs_size(cs_segment_descriptor.is_32());
-
             *flags &= !FLAG_VM & !FLAG_RF;
 
-            if !switch_seg(SS, new_ss) {
-                // XXX
-                dbg_assert!(false);
-            }
+            load_segment(SS, new_ss, &ss_segment_descriptor);
             set_stack_reg(new_esp);
 
-            // XXX: #SS if stack would cross stack limit
-
             if old_flags & FLAG_VM != 0 {
-                if !descriptor.is_32() {
-                    dbg_assert!(false);
-                }
-                else {
+                if descriptor.is_32() {
                     push32(*sreg.offset(GS as isize) as i32).unwrap();
                     push32(*sreg.offset(FS as isize) as i32).unwrap();
                     push32(*sreg.offset(DS as isize) as i32).unwrap();
                     push32(*sreg.offset(ES as isize) as i32).unwrap();
                 }
+                else {
+                    push16(*sreg.offset(GS as isize) as i32).unwrap();
+                    push16(*sreg.offset(FS as isize) as i32).unwrap();
+                    push16(*sreg.offset(DS as isize) as i32).unwrap();
+                    push16(*sreg.offset(ES as isize) as i32).unwrap();
+                }
             }
 
             if descriptor.is_32() {
@@ -973,38 +1368,7 @@ pub unsafe fn call_interrupt_vector(
                 push16(old_esp).unwrap();
             }
         }
-        else if cs_segment_descriptor.is_dc() || cs_segment_descriptor.dpl() == *cpl {
-            // intra privilege level interrupt
-
-            //dbg_log!("Intra privilege interrupt gate=" + h(selector, 4) + ":" + h(offset >>> 0, 8) +
-            //        " gate_type=" + gate_type + " 16bit=" + descriptor.is_32() +
-            //        " cpl=" + *cpl + " dpl=" + segment_descriptor.dpl() + " conforming=" + +segment_descriptor.is_dc(), );
-            //debug.dump_regs_short();
-
-            if *flags & FLAG_VM != 0 {
-                dbg_assert!(false, "check error code");
-                trigger_gp(selector & !3);
-                return;
-            }
-
-            let bytes_per_arg = if descriptor.is_32() { 4 } else { 2 };
-            let error_code_space = if error_code.is_some() { 1 } else { 0 };
-
-            let stack_space = bytes_per_arg * (3 + error_code_space);
-
-            // XXX: with current cpl or with cpl 0?
-            return_on_pagefault!(writable_or_pagefault(
-                get_stack_pointer(-stack_space),
-                stack_space
-            ));
-
-        // no exceptions below
-        }
-        else {
-            panic!("Unimplemented: #GP handler");
-        }
 
-        // XXX: #SS if stack would cross stack limit
         if descriptor.is_32() {
             push32(old_flags).unwrap();
             push32(*sreg.offset(CS as isize) as i32).unwrap();
@@ -1022,8 +1386,6 @@ pub unsafe fn call_interrupt_vector(
             if let Some(ec) = error_code {
                 push16(ec).unwrap();
             }
-
-            offset &= 0xFFFF;
         }
 
         if old_flags & FLAG_VM != 0 {
@@ -1034,14 +1396,7 @@ pub unsafe fn call_interrupt_vector(
             }
         }
 
-        *sreg.offset(CS as isize) = (selector as u16) & !3 | *cpl as u16;
-        dbg_assert!((*sreg.offset(CS as isize) & 3) == *cpl as u16);
-
-        update_cs_size(cs_segment_descriptor.is_32());
-
-        *segment_limits.offset(CS as isize) = cs_segment_descriptor.effective_limit();
-        *segment_offsets.offset(CS as isize) = cs_segment_descriptor.base();
-        *segment_access_bytes.offset(CS as isize) = cs_segment_descriptor.access_byte();
+        load_cs(selector, &cs_segment_descriptor);
 
         *instruction_pointer = get_seg_cs() + offset;
 
@@ -1060,20 +1415,25 @@ pub unsafe fn call_interrupt_vector(
         update_state_flags();
     }
     else {
-        // call 4 byte cs:ip interrupt vector from ivt at cpu.memory 0
+        // call 4 byte cs:ip interrupt vector from the ivt, which lidt can move and shrink
 
-        let index = (interrupt_nr << 2) as u32;
-        let new_ip = read16(index);
-        let new_cs = read16(index + 2);
+        let index = interrupt_nr << 2;
+        if index | 3 > *idtr_size {
+            dbg_log!("#gp interrupt_nr={:x} idtr_size={:x}", interrupt_nr, *idtr_size);
+            trigger_gp(0);
+            return;
+        }
 
-        dbg_assert!(
-            index | 3 <= IVT_SIZE,
-            "Unimplemented: #GP for interrupt number out of IVT bounds"
-        );
+        if !current_stack_push_within_limit(6, 2) {
+            dbg_log!("#ss interrupt_nr={:x} sp={:x}", interrupt_nr, read_reg16(SP));
+            trigger_ss(0);
+            return;
+        }
 
-        // XXX: #SS if stack would cross stack limit
+        let new_ip = read16((*idtr_offset + index) as u32);
+        let new_cs = read16((*idtr_offset + index + 2) as u32);
 
-        // push flags, cs:ip
+        // push flags, cs:ip (can't fail: no paging, and the limit has been checked)
         push16(get_eflags()).unwrap();
         push16(*sreg.offset(CS as isize) as i32).unwrap();
         push16(get_real_eip()).unwrap();
@@ -1092,13 +1452,13 @@ pub unsafe fn far_jump(eip: i32, selector: i32, is_call: bool, is_osize_32: bool
     if !*protected_mode || vm86_mode() {
         if is_call {
             if is_osize_32 {
-                return_on_pagefault!(writable_or_pagefault(get_stack_pointer(-8), 8));
+                return_on_pagefault!(writable_or_pagefault_seg(SS, get_stack_pointer(-8), 8));
 
                 push32(*sreg.offset(CS as isize) as i32).unwrap();
                 push32(get_real_eip()).unwrap();
             }
             else {
-                return_on_pagefault!(writable_or_pagefault(get_stack_pointer(-4), 4));
+                return_on_pagefault!(writable_or_pagefault_seg(SS, get_stack_pointer(-4), 4));
 
                 push16(*sreg.offset(CS as isize) as i32).unwrap();
                 push16(get_real_eip()).unwrap();
@@ -1128,7 +1488,57 @@ pub unsafe fn far_jump(eip: i32, selector: i32, is_call: bool, is_osize_32: bool
     };
 
     if info.is_system() {
-        dbg_assert!(is_call, "TODO: Jump");
+        let system_type = info.system_type();
+        if system_type & !2 == 1 || system_type & !2 == 9 || system_type == 5 {
+            // tss or task gate
+            if info.dpl() < *cpl || info.dpl() < cs_selector.rpl() {
+                dbg_log!("#gp task dpl < cpl or dpl < rpl: {:x}", selector);
+                trigger_gp(selector & !3);
+                return;
+            }
+
+            if !info.is_present() {
+                dbg_log!("#NP for loading not-present tss or task gate sel={:x}", selector);
+                trigger_np(selector & !3);
+                return;
+            }
+
+            let tss_selector = if system_type == 5 {
+                let tss_selector = (info.raw >> 16 & 0xFFFF) as i32;
+                let tss_descriptor = match return_on_pagefault!(lookup_segment_selector(
+                    SegmentSelector::of_u16(tss_selector as u16)
+                )) {
+                    Ok((desc, _)) => desc,
+                    Err(_) => {
+                        dbg_log!("#gp invalid tss in task gate: {:x}", tss_selector);
+                        trigger_gp(tss_selector & !3);
+                        return;
+                    },
+                };
+                let tss_type = tss_descriptor.system_type();
+                if !SegmentSelector::of_u16(tss_selector as u16).is_gdt()
+                    || !tss_descriptor.is_system()
+                    || (tss_type != 1 && tss_type != 9)
+                {
+                    dbg_log!("#gp task gate to non-available tss: {:x}", tss_selector);
+                    trigger_gp(tss_selector & !3);
+                    return;
+                }
+                tss_selector
+            }
+            else {
+                selector
+            };
+
+            let source = if is_call {
+                TaskSwitchSource::CallOrInt
+            }
+            else {
+                TaskSwitchSource::Jmp
+            };
+            do_task_switch(tss_selector, source, None);
+            return;
+        }
 
         dbg_log!("system type cs: {:x}", selector);
 
@@ -1148,7 +1558,7 @@ pub unsafe fn far_jump(eip: i32, selector: i32, is_call: bool, is_osize_32: bool
                 return;
             }
 
-            let cs_selector = (info.raw >> 16) as i32;
+            let cs_selector = (info.raw >> 16 & 0xFFFF) as i32;
 
             let cs_info = match return_on_pagefault!(lookup_segment_selector(
                 SegmentSelector::of_u16(cs_selector as u16)
@@ -1161,14 +1571,14 @@ pub unsafe fn far_jump(eip: i32, selector: i32, is_call: bool, is_osize_32: bool
                         return;
                     },
                     SelectorNullOrInvalid::OutsideOfTableLimit => {
-                        dbg_log!("#gp invalid cs: {:x}", selector);
-                        trigger_gp(selector & !3);
+                        dbg_log!("#gp invalid cs: {:x}", cs_selector);
+                        trigger_gp(cs_selector & !3);
                         return;
                     },
                 },
             };
 
-            if !cs_info.is_executable() {
+            if cs_info.is_system() || !cs_info.is_executable() {
                 dbg_log!("#gp non-executable cs: {:x}", cs_selector);
                 trigger_gp(cs_selector & !3);
                 return;
@@ -1186,83 +1596,91 @@ pub unsafe fn far_jump(eip: i32, selector: i32, is_call: bool, is_osize_32: bool
                 return;
             }
 
+            if !is_call && !cs_info.is_dc() && cs_info.dpl() < *cpl {
+                // jumps through call gates can't change the privilege level
+                dbg_log!("#gp jump through call gate to cs with dpl < cpl: {:x}", cs_selector);
+                trigger_gp(cs_selector & !3);
+                return;
+            }
+
+            // Note: eip from call is ignored
+            let mut new_eip = (info.raw & 0xFFFF) as i32;
+            if !is_16 {
+                new_eip |= ((info.raw >> 32) & 0xFFFF0000) as i32;
+            }
+
+            if new_eip as u32 > cs_info.effective_limit() {
+                dbg_log!("#gp call gate eip outside of cs limit: {:x}", new_eip as u32);
+                trigger_gp(0);
+                return;
+            }
+
             if !cs_info.is_dc() && cs_info.dpl() < *cpl {
+                dbg_assert!(is_call);
                 dbg_log!(
                     "more privilege call gate is_16={} from={} to={}",
                     is_16,
                     *cpl,
                     cs_info.dpl()
                 );
-                let (new_ss, new_esp) = return_on_pagefault!(get_tss_ss_esp(cs_info.dpl()));
-
-                let ss_selector = SegmentSelector::of_u16(new_ss as u16);
-                let ss_info = match return_on_pagefault!(lookup_segment_selector(ss_selector)) {
-                    Ok((desc, _)) => desc,
-                    Err(selector_unusable) => match selector_unusable {
-                        SelectorNullOrInvalid::IsNull => {
-                            panic!("null ss: {}", new_ss);
-                        },
-                        SelectorNullOrInvalid::OutsideOfTableLimit => {
-                            panic!("invalid ss: {}", new_ss);
-                        },
-                    },
-                };
+                let (new_ss, new_esp) = return_on_pagefault!(get_tss_ss_esp(cs_info.dpl(), 0));
 
-                dbg_assert!(!ss_info.is_dc(), "TODO: Handle direction bit");
-                dbg_assert!(new_esp as u32 <= ss_info.effective_limit());
-                dbg_assert!(!ss_info.is_system() && ss_info.is_writable());
-
-                if ss_selector.rpl() != cs_info.dpl()
-                // xxx: 0 in v86 mode
-                {
-                    panic!("#TS handler");
-                }
-                if ss_info.dpl() != cs_info.dpl() || !ss_info.is_writable() {
-                    panic!("#TS handler");
-                }
-                if !ss_info.is_present() {
-                    panic!("#SS handler");
-                }
+                let ss_info =
+                    return_on_pagefault!(lookup_new_ss(new_ss, cs_info.dpl(), trigger_ts, 0));
 
+                // ss, esp, the parameters, cs and eip, as words for 16-bit gates
                 let parameter_count = (info.raw >> 32 & 0x1F) as i32;
-                let mut stack_space = if is_16 { 4 } else { 8 };
-                if is_call {
-                    stack_space +=
-                        if is_16 { 4 + 2 * parameter_count } else { 8 + 4 * parameter_count };
-                }
-                if ss_info.is_32() {
-                    return_on_pagefault!(writable_or_pagefault(
-                        ss_info.base() + new_esp - stack_space,
-                        stack_space
-                    )); // , cs_info.dpl
+                let bytes_per_arg = if is_16 { 2 } else { 4 };
+                let stack_space = bytes_per_arg * (4 + parameter_count);
+
+                if !stack_push_within_limit(
+                    ss_info.access_byte(),
+                    ss_info.effective_limit(),
+                    ss_info.is_32(),
+                    new_esp,
+                    stack_space,
+                    bytes_per_arg,
+                ) {
+                    dbg_log!(
+                        "#ss call gate stack outside of limit ss={:x} esp={:x}",
+                        new_ss,
+                        new_esp
+                    );
+                    trigger_ss(new_ss & !3);
+                    return;
                 }
-                else {
-                    return_on_pagefault!(writable_or_pagefault(
-                        ss_info.base() + (new_esp - stack_space & 0xFFFF),
-                        stack_space
-                    )); // , cs_info.dpl
+
+                return_on_pagefault!(translate_stack_push_system(
+                    ss_info.base(),
+                    ss_info.is_32(),
+                    new_esp,
+                    stack_space,
+                    bytes_per_arg
+                ));
+
+                // read the parameters from the old stack before it is replaced
+                let mut parameters = [0; 0x1F];
+                for i in 0..parameter_count {
+                    let addr = get_stack_pointer(bytes_per_arg * i);
+                    parameters[i as usize] = if is_16 {
+                        return_on_pagefault!(safe_read16_seg(SS, addr))
+                    }
+                    else {
+                        return_on_pagefault!(safe_read32s_seg(SS, addr))
+                    };
                 }
 
                 let old_esp = read_reg32(ESP);
                 let old_ss = *sreg.offset(SS as isize);
-                let old_stack_pointer = get_stack_pointer(0);
 
-                //dbg_log!("old_esp=" + h(old_esp));
+                // no exceptions below
 
                 *cpl = cs_info.dpl();
                 cpl_changed();
 
-                update_cs_size(cs_info.is_32());
-
-                // XXX: Should be checked before side effects
-                if !switch_seg(SS, new_ss) {
-                    dbg_assert!(false);
-                };
+                load_segment(SS, new_ss, &ss_info);
                 set_stack_reg(new_esp);
 
-                //dbg_log!("parameter_count=" + parameter_count);
-                //dbg_assert!(parameter_count == 0, "TODO");
-
                 if is_16 {
                     push16(old_ss as i32).unwrap();
                     push16(old_esp).unwrap();
@@ -1272,34 +1690,28 @@ pub unsafe fn far_jump(eip: i32, selector: i32, is_call: bool, is_osize_32: bool
                     push32(old_esp).unwrap();
                 }
 
-                if is_call {
+                for &parameter in parameters[..parameter_count as usize].iter().rev() {
                     if is_16 {
-                        for i in (0..parameter_count).rev() {
-                            //for(let i = parameter_count - 1; i >= 0; i--)
-                            let parameter = safe_read16(old_stack_pointer + 2 * i).unwrap();
-                            push16(parameter).unwrap();
-                        }
-
-                        //writable_or_pagefault(get_stack_pointer(-4), 4);
-                        push16(*sreg.offset(CS as isize) as i32).unwrap();
-                        push16(get_real_eip()).unwrap();
+                        push16(parameter).unwrap();
                     }
                     else {
-                        for i in (0..parameter_count).rev() {
-                            //for(let i = parameter_count - 1; i >= 0; i--)
-                            let parameter = safe_read32s(old_stack_pointer + 4 * i).unwrap();
-                            push32(parameter).unwrap();
-                        }
-
-                        //writable_or_pagefault(get_stack_pointer(-8), 8);
-                        push32(*sreg.offset(CS as isize) as i32).unwrap();
-                        push32(get_real_eip()).unwrap();
+                        push32(parameter).unwrap();
                     }
                 }
+
+                if is_16 {
+                    push16(*sreg.offset(CS as isize) as i32).unwrap();
+                    push16(get_real_eip()).unwrap();
+                }
+                else {
+                    push32(*sreg.offset(CS as isize) as i32).unwrap();
+                    push32(get_real_eip()).unwrap();
+                }
             }
             else {
                 dbg_log!(
-                    "same privilege call gate is_16={} from={} to={} conforming={}",
+                    "same privilege call gate is_call={} is_16={} from={} to={} conforming={}",
+                    is_call,
                     is_16,
                     *cpl,
                     cs_info.dpl(),
@@ -1308,52 +1720,46 @@ pub unsafe fn far_jump(eip: i32, selector: i32, is_call: bool, is_osize_32: bool
                 // ok
 
                 if is_call {
-                    if is_16 {
-                        return_on_pagefault!(writable_or_pagefault(get_stack_pointer(-4), 4));
+                    let push_size = if is_16 { 2 } else { 4 };
+                    let stack_space = 2 * push_size;
 
+                    if !current_stack_push_within_limit(stack_space, push_size) {
+                        dbg_log!("#ss call gate stack outside of limit");
+                        trigger_ss(0);
+                        return;
+                    }
+
+                    return_on_pagefault!(writable_or_pagefault_stack_push(stack_space, push_size));
+
+                    // no exceptions below
+
+                    if is_16 {
                         push16(*sreg.offset(CS as isize) as i32).unwrap();
                         push16(get_real_eip()).unwrap();
                     }
                     else {
-                        return_on_pagefault!(writable_or_pagefault(get_stack_pointer(-8), 8));
-
                         push32(*sreg.offset(CS as isize) as i32).unwrap();
                         push32(get_real_eip()).unwrap();
                     }
                 }
             }
 
-            // Note: eip from call is ignored
-            let mut new_eip = (info.raw & 0xFFFF) as i32;
-            if !is_16 {
-                new_eip |= ((info.raw >> 32) & 0xFFFF0000) as i32;
-            }
-
             dbg_log!(
                 "call gate eip={:x} cs={:x} conforming={}",
                 new_eip as u32,
                 cs_selector,
                 cs_info.is_dc()
             );
-            dbg_assert!((new_eip as u32) <= cs_info.effective_limit(), "todo: #gp");
-
-            update_cs_size(cs_info.is_32());
 
-            *segment_is_null.offset(CS as isize) = false;
-            *segment_limits.offset(CS as isize) = cs_info.effective_limit();
-            *segment_offsets.offset(CS as isize) = cs_info.base();
-            *segment_access_bytes.offset(CS as isize) = cs_info.access_byte();
-            *sreg.offset(CS as isize) = cs_selector as u16 & !3 | *cpl as u16;
-            dbg_assert!(*sreg.offset(CS as isize) & 3 == *cpl as u16);
+            load_cs(cs_selector, &cs_info);
 
             *instruction_pointer = get_seg_cs() + new_eip;
 
             update_state_flags();
         }
         else {
-            dbg_assert!(false);
-            //let types = { 9: "Available 386 TSS", 0xb: "Busy 386 TSS", 4: "286 Call Gate", 0xc: "386 Call Gate" };
-            //throw debug.unimpl("load system segment descriptor, type = " + (info.access & 15) + " (" + types[info.access & 15] + ")");
+            dbg_log!("#gp far jump to system segment of type {:x}", info.system_type());
+            trigger_gp(selector & !3);
         }
     }
     else {
@@ -1388,31 +1794,37 @@ pub unsafe fn far_jump(eip: i32, selector: i32, is_call: bool, is_osize_32: bool
             return;
         }
 
+        if eip as u32 > info.effective_limit() {
+            dbg_log!("#gp far jump eip outside of cs limit: {:x}", eip as u32);
+            trigger_gp(0);
+            return;
+        }
+
         if is_call {
-            if is_osize_32 {
-                return_on_pagefault!(writable_or_pagefault(get_stack_pointer(-8), 8));
+            let push_size = if is_osize_32 { 4 } else { 2 };
+            let stack_space = 2 * push_size;
+
+            if !current_stack_push_within_limit(stack_space, push_size) {
+                dbg_log!("#ss far call stack outside of limit");
+                trigger_ss(0);
+                return;
+            }
+
+            return_on_pagefault!(writable_or_pagefault_stack_push(stack_space, push_size));
+
+            // no exceptions below
 
+            if is_osize_32 {
                 push32(*sreg.offset(CS as isize) as i32).unwrap();
                 push32(get_real_eip()).unwrap();
             }
             else {
-                return_on_pagefault!(writable_or_pagefault(get_stack_pointer(-4), 4));
-
                 push16(*sreg.offset(CS as isize) as i32).unwrap();
                 push16(get_real_eip()).unwrap();
             }
         }
 
-        dbg_assert!((eip as u32) <= info.effective_limit(), "todo: #gp");
-
-        update_cs_size(info.is_32());
-
-        *segment_is_null.offset(CS as isize) = false;
-        *segment_limits.offset(CS as isize) = info.effective_limit();
-        *segment_access_bytes.offset(CS as isize) = info.access_byte();
-
-        *segment_offsets.offset(CS as isize) = info.base();
-        *sreg.offset(CS as isize) = selector as u16 & !3 | *cpl as u16;
+        load_cs(selector, &info);
 
         *instruction_pointer = get_seg_cs() + eip;
 
@@ -1453,7 +1865,7 @@ pub unsafe fn far_return(eip: i32, selector: i32, stack_adjust: i32, is_osize_32
     };
 
     if info.is_system() {
-        dbg_assert!(false, "is system in far return");
+        dbg_log!("far return: #gp system cs: {:x}", selector);
         trigger_gp(selector & !3);
         return;
     }
@@ -1489,293 +1901,507 @@ pub unsafe fn far_return(eip: i32, selector: i32, stack_adjust: i32, is_osize_32
         return;
     }
 
-    if cs_selector.rpl() > *cpl {
+    let new_cpl = cs_selector.rpl();
+
+    let outer_stack = if new_cpl > *cpl {
         dbg_log!(
             "far return privilege change cs: {:x} from={} to={} is_16={}",
             selector,
             *cpl,
-            cs_selector.rpl(),
+            new_cpl,
             is_osize_32
         );
 
-        let temp_esp;
-        let temp_ss;
-        if is_osize_32 {
-            //dbg_log!("esp read from " + h(translate_address_system_read(get_stack_pointer(stack_adjust + 8))))
-            temp_esp = safe_read32s(get_stack_pointer(stack_adjust + 8)).unwrap();
-            //dbg_log!("esp=" + h(temp_esp));
-            temp_ss = safe_read16(get_stack_pointer(stack_adjust + 12)).unwrap();
+        let (temp_esp, temp_ss) = if is_osize_32 {
+            (
+                return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(stack_adjust + 8))),
+                return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(stack_adjust + 12))),
+            )
         }
         else {
-            //dbg_log!("esp read from " + h(translate_address_system_read(get_stack_pointer(stack_adjust + 4))));
-            temp_esp = safe_read16(get_stack_pointer(stack_adjust + 4)).unwrap();
-            //dbg_log!("esp=" + h(temp_esp));
-            temp_ss = safe_read16(get_stack_pointer(stack_adjust + 6)).unwrap();
-        }
+            (
+                return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(stack_adjust + 4))),
+                return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(stack_adjust + 6))),
+            )
+        };
+
+        let ss_info = return_on_pagefault!(lookup_new_ss(temp_ss, new_cpl, trigger_gp, 0));
+        Some((temp_esp, temp_ss, ss_info))
+    }
+    else {
+        None
+    };
+
+    if eip as u32 > info.effective_limit() {
+        dbg_log!("far return: #gp eip outside of cs limit: {:x}", eip as u32);
+        trigger_gp(0);
+        return;
+    }
 
-        *cpl = cs_selector.rpl();
+    // no exceptions below
+
+    if let Some((temp_esp, temp_ss, ss_info)) = outer_stack {
+        *cpl = new_cpl;
         cpl_changed();
 
-        // XXX: This failure should be checked before side effects
-        if !switch_seg(SS, temp_ss) {
-            dbg_assert!(false);
-        }
+        load_segment(SS, temp_ss, &ss_info);
         set_stack_reg(temp_esp + stack_adjust);
 
-        //if(is_osize_32)
-        //{
-        //    adjust_stack_reg(2 * 4);
-        //}
-        //else
-        //{
-        //    adjust_stack_reg(2 * 2);
-        //}
+        clear_inaccessible_data_segments();
+    }
+    else {
+        adjust_stack_reg(2 * (if is_osize_32 { 4 } else { 2 }) + stack_adjust);
+    }
+
+    load_cs(selector, &info);
+    dbg_assert!(selector & 3 == *cpl as i32);
 
-        //throw debug.unimpl("privilege change");
+    *instruction_pointer = get_seg_cs() + eip;
 
-        //adjust_stack_reg(stack_adjust);
+    update_state_flags();
+}
+
+/// The state of a task as stored in its tss, in the layout of a 32-bit tss
+struct TaskState {
+    eip: i32,
+    eflags: i32,
+    regs: [i32; 8],
+    sregs: [i32; 6],
+    ldt: i32,
+    cr3: Option<i32>,
+    trap: bool,
+}
 
-        // TODO: invalidate segments that are not accessible at this cpl (see iret)
+/// Read a word of a tss. Tss accesses are supervisor accesses and may cross a page boundary
+unsafe fn tss_read16(addr: i32) -> OrPageFault<i32> {
+    Ok(read8(translate_address_system_read(addr)?)
+        | read8(translate_address_system_read(addr + 1)?) << 8)
+}
+
+unsafe fn tss_read32s(addr: i32) -> OrPageFault<i32> {
+    Ok(tss_read16(addr)? | tss_read16(addr + 2)? << 16)
+}
+
+unsafe fn tss_write16(addr: i32, value: i32) -> OrPageFault<()> {
+    write8(translate_address_system_write(addr)?, value);
+    write8(translate_address_system_write(addr + 1)?, value >> 8);
+    Ok(())
+}
+
+unsafe fn tss_write32(addr: i32, value: i32) -> OrPageFault<()> {
+    tss_write16(addr, value)?;
+    tss_write16(addr + 2, value >> 16)
+}
+
+unsafe fn read_task_state(tss: i32, tss_is_16: bool) -> OrPageFault<TaskState> {
+    let mut state = TaskState {
+        eip: 0,
+        eflags: 0,
+        regs: [0; 8],
+        sregs: [0; 6],
+        ldt: 0,
+        cr3: None,
+        trap: false,
+    };
+
+    if tss_is_16 {
+        state.eip = tss_read16(tss + TSR16_IP)?;
+        state.eflags = tss_read16(tss + TSR16_FLAGS)?;
+        for reg in 0..8 {
+            // The upper halves of the registers are not part of a 16-bit tss and are set to all
+            // ones, as on real hardware
+            state.regs[reg] = tss_read16(tss + TSR16_AX + 2 * reg as i32)? | 0xFFFF_0000u32 as i32;
+        }
+        for reg in [ES, CS, SS, DS] {
+            state.sregs[reg as usize] = tss_read16(tss + TSR16_ES + 2 * reg)?;
+        }
+        state.ldt = tss_read16(tss + TSR16_LDT)?;
     }
     else {
-        if is_osize_32 {
-            adjust_stack_reg(2 * 4 + stack_adjust);
+        state.eip = tss_read32s(tss + TSR_EIP)?;
+        state.eflags = tss_read32s(tss + TSR_EFLAGS)?;
+        for reg in 0..8 {
+            state.regs[reg] = tss_read32s(tss + TSR_EAX + 4 * reg as i32)?;
         }
-        else {
-            adjust_stack_reg(2 * 2 + stack_adjust);
+        for reg in 0..6 {
+            state.sregs[reg] = tss_read16(tss + TSR_ES + 4 * reg as i32)?;
         }
+        state.ldt = tss_read16(tss + TSR_LDT)?;
+        state.cr3 = Some(tss_read32s(tss + TSR_CR3)?);
+        state.trap = tss_read16(tss + TSR_TRAP)? & 1 != 0;
     }
 
-    //dbg_assert(*cpl == info.dpl);
+    Ok(state)
+}
 
-    update_cs_size(info.is_32());
+/// Check a segment selector of a new task other than cs before any state is modified. Unusable
+/// selectors raise #TS, non-present segments #NP, or #SS for the stack segment. Returns None for
+/// null data segments
+unsafe fn lookup_task_segment(
+    reg: i32,
+    selector_raw: i32,
+    new_cpl: u8,
+    ldt_offset: i32,
+    ldt_limit: u32,
+) -> OrPageFault<Option<SegmentDescriptor>> {
+    dbg_assert!(reg != CS);
+    let selector = SegmentSelector::of_u16(selector_raw as u16);
+    let descriptor = match lookup_segment_selector_with_ldt(selector, ldt_offset, ldt_limit)? {
+        Ok((desc, _)) => desc,
+        Err(SelectorNullOrInvalid::IsNull) if reg != SS => return Ok(None),
+        Err(_) => {
+            dbg_log!("#ts task switch: invalid selector seg={} sel={:x}", reg, selector_raw);
+            trigger_ts(selector_raw & !3);
+            return Err(());
+        },
+    };
 
-    *segment_is_null.offset(CS as isize) = false;
-    *segment_limits.offset(CS as isize) = info.effective_limit();
-    *segment_access_bytes.offset(CS as isize) = info.access_byte();
+    let usable = if reg == SS {
+        !descriptor.is_system()
+            && descriptor.is_writable()
+            && selector.rpl() == new_cpl
+            && descriptor.dpl() == new_cpl
+    }
+    else {
+        !descriptor.is_system()
+            && descriptor.is_readable()
+            && (descriptor.is_conforming_executable()
+                || (selector.rpl() <= descriptor.dpl() && new_cpl <= descriptor.dpl()))
+    };
 
-    *segment_offsets.offset(CS as isize) = info.base();
-    *sreg.offset(CS as isize) = selector as u16;
-    dbg_assert!(selector & 3 == *cpl as i32);
+    if !usable {
+        dbg_log!("#ts task switch: unusable segment seg={} sel={:x}", reg, selector_raw);
+        trigger_ts(selector_raw & !3);
+        return Err(());
+    }
 
-    *instruction_pointer = get_seg_cs() + eip;
+    if !descriptor.is_present() {
+        dbg_log!("task switch: segment not present seg={} sel={:x}", reg, selector_raw);
+        if reg == SS {
+            trigger_ss(selector_raw & !3);
+        }
+        else {
+            trigger_np(selector_raw & !3);
+        }
+        return Err(());
+    }
 
-    update_state_flags();
+    Ok(Some(descriptor))
 }
 
-pub unsafe fn do_task_switch(selector: i32, error_code: Option<i32>) {
+/// Switch to the task of the tss selector. All faults of the new tss, its ldt and its segments are
+/// raised before the state of the current task is saved
+pub unsafe fn do_task_switch(selector: i32, source: TaskSwitchSource, error_code: Option<i32>) {
     dbg_log!("do_task_switch sel={:x}", selector);
 
-    dbg_assert!(*tss_size_32, "TODO: 16-bit TSS in task switch");
+    // iret raises #TS for an unusable backlink, jmp, call and task gates raise #GP
+    let fault: unsafe fn(i32) = if source == TaskSwitchSource::Iret {
+        trigger_ts
+    }
+    else {
+        trigger_gp
+    };
 
     let selector = SegmentSelector::of_u16(selector as u16);
+    if !selector.is_gdt() {
+        dbg_log!("task switch to tss selector in ldt: {:x}", selector.raw);
+        fault(selector.raw as i32 & !3);
+        return;
+    }
+
     let (descriptor, descriptor_address) =
-        match lookup_segment_selector(selector).expect("TODO: handle pagefault") {
+        match return_on_pagefault!(lookup_segment_selector(selector)) {
             Ok(desc) => desc,
             Err(_) => {
-                panic!("#GP handler");
+                dbg_log!("task switch to invalid tss selector: {:x}", selector.raw);
+                fault(selector.raw as i32 & !3);
+                return;
             },
         };
 
-    dbg_assert!(selector.is_gdt());
-    dbg_assert!((descriptor.system_type() & !2) == 1 || (descriptor.system_type() & !2) == 9);
+    if !descriptor.is_system()
+        || (descriptor.system_type() & !2 != 1 && descriptor.system_type() & !2 != 9)
+    {
+        dbg_log!("task switch to non-tss descriptor: {:x}", selector.raw);
+        fault(selector.raw as i32 & !3);
+        return;
+    }
+
     let tss_is_16 = descriptor.system_type() <= 3;
     let tss_is_busy = (descriptor.system_type() & 2) == 2;
 
-    if (descriptor.system_type() & 2) == 2 {
-        // is busy
-        panic!("#GP handler");
+    if tss_is_busy != (source == TaskSwitchSource::Iret) {
+        // iret must return to a busy task, all other sources require an available task
+        dbg_log!("task switch to tss with wrong busy bit: {:x}", selector.raw);
+        fault(selector.raw as i32 & !3);
+        return;
     }
 
     if !descriptor.is_present() {
-        panic!("#NP handler");
+        dbg_log!("#np task switch to non-present tss: {:x}", selector.raw);
+        trigger_np(selector.raw as i32 & !3);
+        return;
     }
 
-    if descriptor.effective_limit() < 103 {
-        panic!("#NP handler");
+    if descriptor.effective_limit() < if tss_is_16 { 0x2B } else { 0x67 } {
+        dbg_log!("#ts task switch to tss with too small limit: {:x}", selector.raw);
+        trigger_ts(selector.raw as i32 & !3);
+        return;
     }
 
-    let _tsr_size = *segment_limits.offset(TR as isize);
-    let tsr_offset = *segment_offsets.offset(TR as isize);
+    let new_tsr_offset = descriptor.base();
+    let new_state = return_on_pagefault!(read_task_state(new_tsr_offset, tss_is_16));
 
-    let mut old_eflags = get_eflags();
+    let mut new_eflags = new_state.eflags;
+    if source == TaskSwitchSource::CallOrInt {
+        new_eflags |= FLAG_NT;
+    }
+    let to_vm86 = new_eflags & FLAG_VM != 0;
+
+    // The descriptors of the new task are checked with the paging structures of the current task,
+    // as the gdt and the ldt are usually mapped the same in all tasks
+
+    let new_ldt = SegmentSelector::of_u16(new_state.ldt as u16);
+    let (ldt_offset, ldt_limit) = if new_ldt.descriptor_offset() == 0 {
+        (0, 0)
+    }
+    else {
+        let ldt_descriptor = if new_ldt.is_gdt() {
+            return_on_pagefault!(lookup_segment_selector(new_ldt)).ok()
+        }
+        else {
+            None
+        };
+        let ldt_descriptor = match ldt_descriptor {
+            Some((desc, _)) => desc,
+            None => {
+                dbg_log!("#ts task switch: invalid ldt selector {:x}", new_state.ldt);
+                trigger_ts(new_state.ldt & !3);
+                return;
+            },
+        };
+        if !ldt_descriptor.is_system()
+            || ldt_descriptor.system_type() != 2
+            || !ldt_descriptor.is_present()
+        {
+            dbg_log!("#ts task switch: unusable ldt {:x}", new_state.ldt);
+            trigger_ts(new_state.ldt & !3);
+            return;
+        }
+        (ldt_descriptor.base(), ldt_descriptor.effective_limit())
+    };
+
+    let new_cs = new_state.sregs[CS as usize];
+    let new_cpl;
+    let mut new_descriptors = [None, None, None, None, None, None];
+
+    if to_vm86 {
+        new_cpl = 3;
+
+        if new_state.eip as u32 > 0xFFFF {
+            dbg_log!("#gp task switch: vm86 eip outside of cs limit: {:x}", new_state.eip);
+            trigger_gp(0);
+            return;
+        }
+    }
+    else {
+        let new_cs_selector = SegmentSelector::of_u16(new_cs as u16);
+        let new_cs_descriptor = match return_on_pagefault!(lookup_segment_selector_with_ldt(
+            new_cs_selector,
+            ldt_offset,
+            ldt_limit
+        )) {
+            Ok((desc, _)) => desc,
+            Err(_) => {
+                dbg_log!("#ts task switch: invalid cs {:x}", new_cs);
+                trigger_ts(new_cs & !3);
+                return;
+            },
+        };
 
-    if tss_is_busy {
-        old_eflags &= !FLAG_NT;
-    }
+        if new_cs_descriptor.is_system()
+            || !new_cs_descriptor.is_executable()
+            || (new_cs_descriptor.is_dc() && new_cs_descriptor.dpl() > new_cs_selector.rpl())
+            || (!new_cs_descriptor.is_dc() && new_cs_descriptor.dpl() != new_cs_selector.rpl())
+        {
+            dbg_log!("#ts task switch: unusable cs {:x}", new_cs);
+            trigger_ts(new_cs & !3);
+            return;
+        }
 
-    writable_or_pagefault(tsr_offset, 0x66).unwrap();
+        if !new_cs_descriptor.is_present() {
+            dbg_log!("#np task switch: cs not present {:x}", new_cs);
+            trigger_np(new_cs & !3);
+            return;
+        }
 
-    //safe_write32(tsr_offset + TSR_CR3, *cr.offset(3));
+        // The cpl is the rpl of cs, which may be larger than the dpl of a conforming segment
+        new_cpl = new_cs_selector.rpl();
 
-    // TODO: Write 16 bit values if old tss is 16 bit
-    safe_write32(tsr_offset + TSR_EIP, get_real_eip()).unwrap();
-    safe_write32(tsr_offset + TSR_EFLAGS, old_eflags).unwrap();
+        for reg in [SS, DS, ES, FS, GS] {
+            match lookup_task_segment(
+                reg,
+                new_state.sregs[reg as usize],
+                new_cpl,
+                ldt_offset,
+                ldt_limit,
+            ) {
+                Ok(desc) => new_descriptors[reg as usize] = desc,
+                Err(()) => return,
+            }
+        }
 
-    safe_write32(tsr_offset + TSR_EAX, read_reg32(EAX)).unwrap();
-    safe_write32(tsr_offset + TSR_ECX, read_reg32(ECX)).unwrap();
-    safe_write32(tsr_offset + TSR_EDX, read_reg32(EDX)).unwrap();
-    safe_write32(tsr_offset + TSR_EBX, read_reg32(EBX)).unwrap();
+        if new_state.eip as u32 > new_cs_descriptor.effective_limit() {
+            dbg_log!("#gp task switch: eip outside of cs limit: {:x}", new_state.eip);
+            trigger_gp(0);
+            return;
+        }
 
-    safe_write32(tsr_offset + TSR_ESP, read_reg32(ESP)).unwrap();
-    safe_write32(tsr_offset + TSR_EBP, read_reg32(EBP)).unwrap();
-    safe_write32(tsr_offset + TSR_ESI, read_reg32(ESI)).unwrap();
-    safe_write32(tsr_offset + TSR_EDI, read_reg32(EDI)).unwrap();
+        new_descriptors[CS as usize] = Some(new_cs_descriptor);
+    }
 
-    safe_write32(tsr_offset + TSR_ES, *sreg.offset(ES as isize) as i32).unwrap();
-    safe_write32(tsr_offset + TSR_CS, *sreg.offset(CS as isize) as i32).unwrap();
-    safe_write32(tsr_offset + TSR_SS, *sreg.offset(SS as isize) as i32).unwrap();
-    safe_write32(tsr_offset + TSR_DS, *sreg.offset(DS as isize) as i32).unwrap();
-    safe_write32(tsr_offset + TSR_FS, *sreg.offset(FS as isize) as i32).unwrap();
-    safe_write32(tsr_offset + TSR_GS, *sreg.offset(GS as isize) as i32).unwrap();
+    // Check all writes below, so that the switch can't fail halfway
 
-    //safe_write32(tsr_offset + TSR_LDT, *sreg.offset(reg_ldtr));
+    let tsr_offset = *segment_offsets.offset(TR as isize);
+    let old_tr = *sreg.offset(TR as isize) as i32;
+    let old_descriptor_address = *gdtr_offset + (old_tr & !7);
 
-    if true
-    /* is jump or call or int */
-    {
-        safe_write64(descriptor_address, descriptor.set_busy().raw).unwrap();
+    let (save_start, save_end) = if *tss_size_32 {
+        (TSR_EIP, TSR_GS + 2)
     }
+    else {
+        (TSR16_IP, TSR16_DS + 2)
+    };
+    return_on_pagefault!(translate_address_system_write(tsr_offset + save_start));
+    return_on_pagefault!(translate_address_system_write(tsr_offset + save_end - 1));
 
-    //let new_tsr_size = descriptor.effective_limit;
-    let new_tsr_offset = descriptor.base();
-
-    dbg_assert!(!tss_is_16, "unimplemented");
-
-    if true
-    /* is call or int */
-    {
-        safe_write16(
-            new_tsr_offset + TSR_BACKLINK,
-            *sreg.offset(TR as isize) as i32,
-        )
-        .unwrap();
+    if source != TaskSwitchSource::CallOrInt {
+        return_on_pagefault!(translate_address_system_write(old_descriptor_address + 5));
+    }
+    else {
+        return_on_pagefault!(translate_address_system_write(new_tsr_offset + TSR_BACKLINK));
+        return_on_pagefault!(translate_address_system_write(new_tsr_offset + TSR_BACKLINK + 1));
     }
 
-    let new_cr3 = safe_read32s(new_tsr_offset + TSR_CR3).unwrap();
+    if source != TaskSwitchSource::Iret {
+        return_on_pagefault!(translate_address_system_write(descriptor_address + 5));
+    }
 
-    *flags &= !FLAG_VM;
+    // no exceptions below, except for pushing the error code in the new task
 
-    let new_eip = safe_read32s(new_tsr_offset + TSR_EIP).unwrap();
-    let new_cs = safe_read16(new_tsr_offset + TSR_CS).unwrap();
-    let new_cs_selector = SegmentSelector::of_u16(new_cs as u16);
-    let new_cs_descriptor =
-        match lookup_segment_selector(new_cs_selector).expect("TODO: handle pagefault") {
-            Ok((desc, _)) => desc,
-            Err(SelectorNullOrInvalid::IsNull) => {
-                dbg_log!("null cs");
-                panic!("#TS handler");
-            },
-            Err(SelectorNullOrInvalid::OutsideOfTableLimit) => {
-                dbg_log!("invalid cs: {:x}", new_cs);
-                panic!("#TS handler");
-            },
-        };
+    let mut old_eflags = get_eflags();
 
-    if new_cs_descriptor.is_system() {
-        panic!("#TS handler");
+    if source == TaskSwitchSource::Iret {
+        old_eflags &= !FLAG_NT;
     }
 
-    if !new_cs_descriptor.is_executable() {
-        panic!("#TS handler");
+    if *tss_size_32 {
+        return_on_pagefault!(tss_write32(tsr_offset + TSR_EIP, get_real_eip()));
+        return_on_pagefault!(tss_write32(tsr_offset + TSR_EFLAGS, old_eflags));
+        for reg in 0..8 {
+            return_on_pagefault!(tss_write32(tsr_offset + TSR_EAX + 4 * reg, read_reg32(reg)));
+        }
+        for reg in 0..6 {
+            let value = *sreg.offset(reg as isize) as i32;
+            return_on_pagefault!(tss_write16(tsr_offset + TSR_ES + 4 * reg, value));
+        }
     }
-
-    if new_cs_descriptor.is_dc() && new_cs_descriptor.dpl() > new_cs_selector.rpl() {
-        dbg_log!("cs conforming and dpl > rpl: {:x}", selector.raw);
-        panic!("#TS handler");
+    else {
+        return_on_pagefault!(tss_write16(tsr_offset + TSR16_IP, get_real_eip()));
+        return_on_pagefault!(tss_write16(tsr_offset + TSR16_FLAGS, old_eflags));
+        for reg in 0..8 {
+            return_on_pagefault!(tss_write16(tsr_offset + TSR16_AX + 2 * reg, read_reg32(reg)));
+        }
+        for reg in [ES, CS, SS, DS] {
+            let value = *sreg.offset(reg as isize) as i32;
+            return_on_pagefault!(tss_write16(tsr_offset + TSR16_ES + 2 * reg, value));
+        }
     }
 
-    if !new_cs_descriptor.is_dc() && new_cs_descriptor.dpl() != new_cs_selector.rpl() {
-        dbg_log!("cs non-conforming and dpl != rpl: {:x}", selector.raw);
-        panic!("#TS handler");
+    if source != TaskSwitchSource::CallOrInt {
+        // jmp and iret leave the old task, so it is no longer busy
+        let addr = return_on_pagefault!(translate_address_system_write(old_descriptor_address + 5));
+        write8(addr, read8(addr) & !2);
     }
-
-    if !new_cs_descriptor.is_present() {
-        dbg_log!("#NP for loading not-present in cs sel={:x}", selector.raw);
-        panic!("#TS handler");
+    else {
+        return_on_pagefault!(tss_write16(new_tsr_offset + TSR_BACKLINK, old_tr));
     }
 
-    *segment_is_null.offset(CS as isize) = false;
-    *segment_limits.offset(CS as isize) = new_cs_descriptor.effective_limit();
-    *segment_offsets.offset(CS as isize) = new_cs_descriptor.base();
-    *segment_access_bytes.offset(CS as isize) = new_cs_descriptor.access_byte();
-    *sreg.offset(CS as isize) = new_cs as u16;
-
-    *cpl = new_cs_descriptor.dpl();
-    cpl_changed();
-
-    dbg_assert!((*sreg.offset(CS as isize) & 3) as u8 == *cpl);
-
-    dbg_assert!(
-        new_eip as u32 <= new_cs_descriptor.effective_limit(),
-        "todo: #gp"
-    );
-    update_cs_size(new_cs_descriptor.is_32());
+    if source != TaskSwitchSource::Iret {
+        let addr = return_on_pagefault!(translate_address_system_write(descriptor_address + 5));
+        write8(addr, descriptor.set_busy().access_byte() as i32);
+    }
 
-    let mut new_eflags = safe_read32s(new_tsr_offset + TSR_EFLAGS).unwrap();
+    *segment_offsets.offset(TR as isize) = descriptor.base();
+    *segment_limits.offset(TR as isize) = descriptor.effective_limit();
+    *sreg.offset(TR as isize) = selector.raw;
+    *tss_size_32 = !tss_is_16;
 
-    if true
-    /* is call or int */
-    {
-        safe_write32(tsr_offset + TSR_BACKLINK, selector.raw as i32).unwrap();
-        new_eflags |= FLAG_NT;
+    if let Some(new_cr3) = new_state.cr3 {
+        set_cr3(new_cr3);
     }
 
-    if new_eflags & FLAG_VM != 0 {
-        panic!("task switch to VM mode");
-    }
+    *segment_offsets.offset(LDTR as isize) = ldt_offset;
+    *segment_limits.offset(LDTR as isize) = ldt_limit;
+    *sreg.offset(LDTR as isize) = new_state.ldt as u16;
 
-    update_eflags(new_eflags);
+    // All flags are loaded, independent of the cpl and iopl
+    *flags = new_eflags & FLAGS_MASK | FLAGS_DEFAULT;
+    *flags_changed = 0;
 
-    if true
-    /* call or int */
-    {
-        *flags |= FLAG_NT;
+    for reg in 0..8 {
+        write_reg32(reg, new_state.regs[reg as usize]);
     }
 
-    let new_ldt = safe_read16(new_tsr_offset + TSR_LDT).unwrap();
-    load_ldt(new_ldt).unwrap();
-
-    write_reg32(EAX, safe_read32s(new_tsr_offset + TSR_EAX).unwrap());
-    write_reg32(ECX, safe_read32s(new_tsr_offset + TSR_ECX).unwrap());
-    write_reg32(EDX, safe_read32s(new_tsr_offset + TSR_EDX).unwrap());
-    write_reg32(EBX, safe_read32s(new_tsr_offset + TSR_EBX).unwrap());
-
-    write_reg32(ESP, safe_read32s(new_tsr_offset + TSR_ESP).unwrap());
-    write_reg32(EBP, safe_read32s(new_tsr_offset + TSR_EBP).unwrap());
-    write_reg32(ESI, safe_read32s(new_tsr_offset + TSR_ESI).unwrap());
-    write_reg32(EDI, safe_read32s(new_tsr_offset + TSR_EDI).unwrap());
+    *cpl = new_cpl;
+    cpl_changed();
 
-    if !switch_seg(ES, safe_read16(new_tsr_offset + TSR_ES).unwrap())
-        || !switch_seg(SS, safe_read16(new_tsr_offset + TSR_SS).unwrap())
-        || !switch_seg(DS, safe_read16(new_tsr_offset + TSR_DS).unwrap())
-        || !switch_seg(FS, safe_read16(new_tsr_offset + TSR_FS).unwrap())
-        || !switch_seg(GS, safe_read16(new_tsr_offset + TSR_GS).unwrap())
-    {
-        // XXX: Should be checked before side effects
-        dbg_assert!(false);
+    if to_vm86 {
+        switch_cs_real_mode(new_cs);
+        for reg in [ES, SS, DS, FS, GS] {
+            if !switch_seg(reg, new_state.sregs[reg as usize]) {
+                // can't fail in vm86 mode
+                dbg_assert!(false);
+            }
+        }
+    }
+    else {
+        for reg in 0..6 {
+            let selector_raw = new_state.sregs[reg as usize];
+            match &new_descriptors[reg as usize] {
+                Some(desc) if reg == CS => load_cs(selector_raw, desc),
+                Some(desc) => load_segment(reg, selector_raw, desc),
+                None => {
+                    *segment_is_null.offset(reg as isize) = true;
+                    *sreg.offset(reg as isize) = selector_raw as u16;
+                },
+            }
+        }
     }
 
-    *instruction_pointer = get_seg_cs() + new_eip;
-
-    *segment_offsets.offset(TR as isize) = descriptor.base();
-    *segment_limits.offset(TR as isize) = descriptor.effective_limit();
-    *sreg.offset(TR as isize) = selector.raw;
+    dbg_assert!((*sreg.offset(CS as isize) & 3) as u8 == *cpl || to_vm86);
 
-    set_cr3(new_cr3);
+    *instruction_pointer = get_seg_cs() + new_state.eip;
+    // Faults from here on are raised in the context of the new task
+    *previous_ip = *instruction_pointer;
 
     *cr.offset(0) |= CR0_TS;
 
+    update_state_flags();
+
     if let Some(error_code) = error_code {
         if tss_is_16 {
-            push16(error_code & 0xFFFF).unwrap();
+            return_on_pagefault!(push16(error_code & 0xFFFF));
         }
         else {
-            push32(error_code).unwrap();
+            return_on_pagefault!(push32(error_code));
         }
     }
 
-    update_state_flags();
+    if new_state.trap {
+        // T flag of the new task
+        debug_trap_pending |= DR6_BT;
+    }
 }
 
 pub unsafe fn after_block_boundary() { jit_block_boundary = true; }
@@ -1838,6 +2464,25 @@ pub unsafe fn translate_address_read_jit(address: i32) -> OrPageFault<u32> {
     translate_address(address, false, *cpl == 3, true, true)
 }
 
+pub fn translate_address_execute_no_side_effects(address: i32) -> OrPageFault<u32> {
+    unsafe { translate_address_execute(address, false, false) }
+}
+pub unsafe fn translate_address_execute(
+    address: i32,
+    jit: bool,
+    side_effects: bool,
+) -> OrPageFault<u32> {
+    // like translate_address_read, but also fails on pages marked as non-executable
+    let user = *cpl == 3;
+    let mut entry = tlb_data[(address as u32 >> 12) as usize];
+    if entry & (TLB_VALID | if user { TLB_NO_USER } else { TLB_SMEP } | TLB_NO_EXECUTE)
+        != TLB_VALID
+    {
+        entry = do_page_walk(address, false, true, user, jit, side_effects)?.get();
+    }
+    Ok((entry & !0xFFF ^ address) as u32 - memory::mem8 as u32)
+}
+
 pub unsafe fn translate_address_write(address: i32) -> OrPageFault<u32> {
     translate_address(address, true, *cpl == 3, false, true)
 }
@@ -1846,8 +2491,8 @@ pub unsafe fn translate_address_write_jit_and_can_skip_dirty(
 ) -> OrPageFault<(u32, bool)> {
     let mut entry = tlb_data[(address as u32 >> 12) as usize];
     let user = *cpl == 3;
-    if entry & (TLB_VALID | if user { TLB_NO_USER } else { 0 } | TLB_READONLY) != TLB_VALID {
-        entry = do_page_walk(address, true, user, true, true)?.get();
+    if entry & (TLB_VALID | tlb_no_access_mask(user) | TLB_READONLY) != TLB_VALID {
+        entry = do_page_walk(address, true, false, user, true, true)?.get();
     }
     Ok((
         (entry & !0xFFF ^ address) as u32 - memory::mem8 as u32,
@@ -1872,21 +2517,44 @@ pub unsafe fn translate_address(
 ) -> OrPageFault<u32> {
     let mut entry = tlb_data[(address as u32 >> 12) as usize];
     if entry
-        & (TLB_VALID
-            | if user { TLB_NO_USER } else { 0 }
-            | if for_writing { TLB_READONLY } else { 0 })
+        & (TLB_VALID | tlb_no_access_mask(user) | if for_writing { TLB_READONLY } else { 0 })
         != TLB_VALID
     {
-        entry = do_page_walk(address, for_writing, user, jit, side_effects)?.get();
+        entry = do_page_walk(address, for_writing, false, user, jit, side_effects)?.get();
     }
     Ok((entry & !0xFFF ^ address) as u32 - memory::mem8 as u32)
 }
 
+/// The tlb bits that prevent data accesses by user or supervisor (SMAP) code
+#[inline(always)]
+unsafe fn tlb_no_access_mask(user: bool) -> i32 {
+    if user {
+        TLB_NO_USER
+    }
+    else if *flags & FLAG_AC == 0 {
+        TLB_SMAP
+    }
+    else {
+        0
+    }
+}
+
+/// Whether a supervisor access to a user page is prevented by SMEP (instruction fetches) or SMAP
+/// (data accesses, unless eflags.AC is set)
+unsafe fn is_smep_or_smap_violation(cr4: i32, for_executing: bool) -> bool {
+    if for_executing {
+        cr4 & CR4_SMEP != 0
+    }
+    else {
+        cr4 & CR4_SMAP != 0 && *flags & FLAG_AC == 0
+    }
+}
+
 pub unsafe fn translate_address_write_and_can_skip_dirty(address: i32) -> OrPageFault<(u32, bool)> {
     let mut entry = tlb_data[(address as u32 >> 12) as usize];
     let user = *cpl == 3;
-    if entry & (TLB_VALID | if user { TLB_NO_USER } else { 0 } | TLB_READONLY) != TLB_VALID {
-        entry = do_page_walk(address, true, user, false, true)?.get();
+    if entry & (TLB_VALID | tlb_no_access_mask(user) | TLB_READONLY) != TLB_VALID {
+        entry = do_page_walk(address, true, false, user, false, true)?.get();
     }
     Ok((
         (entry & !0xFFF ^ address) as u32 - memory::mem8 as u32,
@@ -1904,17 +2572,20 @@ pub unsafe fn translate_address_write_and_can_skip_dirty(address: i32) -> OrPage
 //
 // Note that PAE entries are 64-bit, and can describe physical addresses over 32
 // bits. However, since we support only 32-bit physical addresses, we require
-// the high half of the entry to be 0.
+// the high half of the entry to be 0, except for the execute-disable bit (bit 63),
+// which is honoured when EFER.NXE is set and causes a reserved bit page fault otherwise.
 #[cold]
 pub unsafe fn do_page_walk(
     addr: i32,
     for_writing: bool,
+    for_executing: bool,
     user: bool,
     jit: bool,
     side_effects: bool,
 ) -> OrPageFault<std::num::NonZeroI32> {
     let global;
     let mut allow_user = true;
+    let mut allow_execute = true;
     let page = (addr as u32 >> 12) as i32;
     let high;
 
@@ -1930,12 +2601,23 @@ pub unsafe fn do_page_walk(
         profiler::stat_increment(TLB_MISS);
 
         let pae = cr4 & CR4_PAE != 0;
+        let nx = pae && *efer & EFER_NXE != 0;
+        // the I/D bit of the error code is only reported if execute-disable or SMEP are enabled
+        let instruction_fetch = for_executing && (nx || cr4 & CR4_SMEP != 0);
 
-        let (page_dir_addr, page_dir_entry) = if pae {
+        let (page_dir_addr, page_dir_entry, page_dir_reserved) = if pae {
             let pdpt_entry = *reg_pdpte.offset(((addr as u32) >> 30) as isize);
             if pdpt_entry as i32 & PAGE_TABLE_PRESENT_MASK == 0 {
                 if side_effects {
-                    trigger_pagefault(addr, false, for_writing, user, jit);
+                    trigger_pagefault(
+                        addr,
+                        false,
+                        false,
+                        for_writing,
+                        user,
+                        instruction_fetch,
+                        jit,
+                    );
                 }
                 return Err(());
             }
@@ -1947,22 +2629,27 @@ pub unsafe fn do_page_walk(
                 page_dir_entry as u64 & 0x7FFF_FFFF_0000_0000 == 0,
                 "Unsupported: Page directory entry larger than 32 bits"
             );
-            dbg_assert!(
-                page_dir_entry & 0x8000_0000_0000_0000u64 as i64 == 0,
-                "Unsupported: NX bit"
-            );
+            // execute-disable bit, reserved if EFER.NXE is clear
+            allow_execute &= !nx || page_dir_entry >= 0;
 
-            (page_dir_addr, page_dir_entry as i32)
+            (page_dir_addr, page_dir_entry as i32, !nx && page_dir_entry < 0)
         }
         else {
             let page_dir_addr = *cr.offset(3) as u32 + (((addr as u32) >> 22) << 2);
             let page_dir_entry = read32s(page_dir_addr);
-            (page_dir_addr, page_dir_entry)
+            (page_dir_addr, page_dir_entry, false)
         };
 
         if page_dir_entry & PAGE_TABLE_PRESENT_MASK == 0 {
             if side_effects {
-                trigger_pagefault(addr, false, for_writing, user, jit);
+                trigger_pagefault(addr, false, false, for_writing, user, instruction_fetch, jit);
+            }
+            return Err(());
+        }
+
+        if page_dir_reserved {
+            if side_effects {
+                trigger_pagefault(addr, true, true, for_writing, user, instruction_fetch, jit);
             }
             return Err(());
         }
@@ -1974,9 +2661,13 @@ pub unsafe fn do_page_walk(
         if 0 != page_dir_entry & PAGE_TABLE_PSE_MASK && 0 != cr4 & CR4_PSE {
             // size bit is set
 
-            if for_writing && !allow_write && !kernel_write_override || user && !allow_user {
+            if for_writing && !allow_write && !kernel_write_override
+                || user && !allow_user
+                || !user && allow_user && is_smep_or_smap_violation(cr4, for_executing)
+                || for_executing && !allow_execute
+            {
                 if side_effects {
-                    trigger_pagefault(addr, true, for_writing, user, jit);
+                    trigger_pagefault(addr, true, false, for_writing, user, instruction_fetch, jit);
                 }
                 return Err(());
             }
@@ -2000,7 +2691,7 @@ pub unsafe fn do_page_walk(
             global = page_dir_entry & PAGE_TABLE_GLOBAL_MASK == PAGE_TABLE_GLOBAL_MASK
         }
         else {
-            let (page_table_addr, page_table_entry) = if pae {
+            let (page_table_addr, page_table_entry, page_table_reserved) = if pae {
                 let page_table_addr =
                     (page_dir_entry as u32 & 0xFFFFF000) + (((addr as u32 >> 12) & 0x1FF) << 3);
                 let page_table_entry = read64s(page_table_addr);
@@ -2008,30 +2699,45 @@ pub unsafe fn do_page_walk(
                     page_table_entry as u64 & 0x7FFF_FFFF_0000_0000 == 0,
                     "Unsupported: Page table entry larger than 32 bits"
                 );
-                dbg_assert!(
-                    page_table_entry & 0x8000_0000_0000_0000u64 as i64 == 0,
-                    "Unsupported: NX bit"
-                );
+                allow_execute &= !nx || page_table_entry >= 0;
 
-                (page_table_addr, page_table_entry as i32)
+                (page_table_addr, page_table_entry as i32, !nx && page_table_entry < 0)
             }
             else {
                 let page_table_addr =
                     (page_dir_entry as u32 & 0xFFFFF000) + (((addr as u32 >> 12) & 0x3FF) << 2);
                 let page_table_entry = read32s(page_table_addr);
-                (page_table_addr, page_table_entry)
+                (page_table_addr, page_table_entry, false)
             };
 
             let present = page_table_entry & PAGE_TABLE_PRESENT_MASK != 0;
+
+            if present && page_table_reserved {
+                if side_effects {
+                    trigger_pagefault(addr, true, true, for_writing, user, instruction_fetch, jit);
+                }
+                return Err(());
+            }
+
             allow_write &= page_table_entry & PAGE_TABLE_RW_MASK != 0;
             allow_user &= page_table_entry & PAGE_TABLE_USER_MASK != 0;
 
             if !present
                 || for_writing && !allow_write && !kernel_write_override
                 || user && !allow_user
+                || !user && allow_user && is_smep_or_smap_violation(cr4, for_executing)
+                || for_executing && !allow_execute
             {
                 if side_effects {
-                    trigger_pagefault(addr, present, for_writing, user, jit);
+                    trigger_pagefault(
+                        addr,
+                        present,
+                        false,
+                        for_writing,
+                        user,
+                        instruction_fetch,
+                        jit,
+                    );
                 }
                 return Err(());
             }
@@ -2083,13 +2789,24 @@ pub unsafe fn do_page_walk(
     }
 
     let is_in_mapped_range = in_mapped_range(high);
-    let has_code = !is_in_mapped_range && jit::jit_page_has_code(Page::page_of(high));
+    let has_code = !is_in_mapped_range
+        && (jit::jit_page_has_code(Page::page_of(high)) || is_monitored_page(Page::page_of(high)));
+    let is_user_page = allow_user && cr0 & CR0_PG != 0;
     let info_bits = TLB_VALID
         | if for_writing { 0 } else { TLB_READONLY }
         | if allow_user { 0 } else { TLB_NO_USER }
         | if is_in_mapped_range { TLB_IN_MAPPED_RANGE } else { 0 }
         | if global && 0 != cr4 & CR4_PGE { TLB_GLOBAL } else { 0 }
-        | if has_code { TLB_HAS_CODE } else { 0 };
+        | if has_code { TLB_HAS_CODE } else { 0 }
+        | if allow_execute { 0 } else { TLB_NO_EXECUTE }
+        | if is_user_page && cr4 & CR4_SMEP != 0 { TLB_SMEP } else { 0 }
+        | if is_user_page && cr4 & CR4_SMAP != 0 { TLB_SMAP } else { 0 }
+        | if breakpoints_enabled() && page_has_breakpoint(page as u32) {
+            TLB_BREAKPOINT
+        }
+        else {
+            0
+        };
 
     let tlb_entry = (high + memory::mem8 as u32) as i32 ^ page << 12 | info_bits as i32;
 
@@ -2181,6 +2898,14 @@ pub unsafe fn trigger_nm_jit(eip_offset_in_page: i32) {
     jit_fault = Some((CPU_EXCEPTION_NM, None))
 }
 
+#[no_mangle]
+pub unsafe fn trigger_mf_jit(eip_offset_in_page: i32) {
+    dbg_log!("#mf in jit mode");
+    dbg_assert!(eip_offset_in_page >= 0 && eip_offset_in_page < 0x1000);
+    *instruction_pointer = *instruction_pointer & !0xFFF | eip_offset_in_page;
+    jit_fault = Some((CPU_EXCEPTION_MF, None))
+}
+
 #[no_mangle]
 pub unsafe fn trigger_gp_jit(code: i32, eip_offset_in_page: i32) {
     dbg_log!("#gp in jit mode");
@@ -2191,7 +2916,14 @@ pub unsafe fn trigger_gp_jit(code: i32, eip_offset_in_page: i32) {
 
 #[no_mangle]
 pub unsafe fn trigger_fault_end_jit() {
-    let (code, error_code) = jit_fault.take().unwrap();
+    let (code, error_code) = match jit_fault.take() {
+        Some(fault) => fault,
+        None => {
+            // Exit without an exception, in order to handle breakpoints in the interpreter
+            dbg_assert!(breakpoints_enabled());
+            return;
+        },
+    };
     if DEBUG {
         if cpu_exception_hook(code) {
             return;
@@ -2212,14 +2944,24 @@ pub unsafe fn trigger_fault_end_jit() {
 ///   and finally calls trigger_fault_end_jit, which does the interrupt
 ///
 /// Non-jit resets the instruction pointer and does the PF interrupt directly
-pub unsafe fn trigger_pagefault(addr: i32, present: bool, write: bool, user: bool, jit: bool) {
+pub unsafe fn trigger_pagefault(
+    addr: i32,
+    present: bool,
+    reserved: bool,
+    write: bool,
+    user: bool,
+    instruction_fetch: bool,
+    jit: bool,
+) {
     if config::LOG_PAGE_FAULTS {
         dbg_log!(
-            "page fault{} w={} u={} p={} eip={:x} cr2={:x}",
+            "page fault{} w={} u={} p={} r={} i={} eip={:x} cr2={:x}",
             if jit { "jit" } else { "" },
             write as i32,
             user as i32,
             present as i32,
+            reserved as i32,
+            instruction_fetch as i32,
             *previous_ip,
             addr
         );
@@ -2231,7 +2973,11 @@ pub unsafe fn trigger_pagefault(addr: i32, present: bool, write: bool, user: boo
     let page = ((addr as u32) >> 12) as i32;
     clear_tlb_code(page);
     tlb_data[page as usize] = 0;
-    let error_code = (user as i32) << 2 | (write as i32) << 1 | present as i32;
+    let error_code = (instruction_fetch as i32) << 4
+        | (reserved as i32) << 3
+        | (user as i32) << 2
+        | (write as i32) << 1
+        | present as i32;
     if jit {
         jit_fault = Some((CPU_EXCEPTION_PF, Some(error_code)));
     }
@@ -2314,7 +3060,7 @@ pub const DISABLE_EIP_TRANSLATION_OPTIMISATION: bool = false;
 pub unsafe fn read_imm8() -> OrPageFault<i32> {
     let eip = *instruction_pointer;
     if DISABLE_EIP_TRANSLATION_OPTIMISATION || 0 != eip & !0xFFF ^ *last_virt_eip {
-        *eip_phys = (translate_address_read(eip)? ^ eip as u32) as i32;
+        *eip_phys = (translate_address_execute(eip, false, true)? ^ eip as u32) as i32;
         *last_virt_eip = eip & !0xFFF
     }
     dbg_assert!(!in_mapped_range((*eip_phys ^ eip) as u32));
@@ -2367,6 +3113,20 @@ pub unsafe fn is_asize_32() -> bool {
 
 pub unsafe fn lookup_segment_selector(
     selector: SegmentSelector,
+) -> OrPageFault<Result<(SegmentDescriptor, i32), SelectorNullOrInvalid>> {
+    lookup_segment_selector_with_ldt(
+        selector,
+        *segment_offsets.offset(LDTR as isize),
+        *segment_limits.offset(LDTR as isize),
+    )
+}
+
+/// Like lookup_segment_selector, but with an ldt other than the loaded one (the ldt of the new
+/// task in a task switch)
+pub unsafe fn lookup_segment_selector_with_ldt(
+    selector: SegmentSelector,
+    ldt_offset: i32,
+    ldt_limit: u32,
 ) -> OrPageFault<Result<(SegmentDescriptor, i32), SelectorNullOrInvalid>> {
     if selector.is_null() {
         return Ok(Err(SelectorNullOrInvalid::IsNull));
@@ -2376,13 +3136,12 @@ pub unsafe fn lookup_segment_selector(
         (*gdtr_offset as u32, *gdtr_size as u16)
     }
     else {
-        (
-            *segment_offsets.offset(LDTR as isize) as u32,
-            *segment_limits.offset(LDTR as isize) as u16,
-        )
+        (ldt_offset as u32, ldt_limit as u16)
     };
 
-    if selector.descriptor_offset() > table_limit {
+    // The whole descriptor must be within the limit, which also rejects any ldt selector while
+    // the ldtr is null
+    if selector.descriptor_offset() as u32 + 7 > table_limit as u32 {
         return Ok(Err(SelectorNullOrInvalid::OutsideOfTableLimit));
     }
 
@@ -2471,8 +3230,6 @@ pub unsafe fn switch_seg(reg: i32, selector_raw: i32) -> bool {
             trigger_ss(selector_raw & !3);
             return false;
         }
-
-        *stack_size_32 = descriptor.is_32();
     }
     else if reg == CS {
         // handled by switch_cs_real_mode, far_return or far_jump
@@ -2514,29 +3271,51 @@ pub unsafe fn switch_seg(reg: i32, selector_raw: i32) -> bool {
         }
     }
 
+    load_segment(reg, selector_raw, &descriptor);
+
+    true
+}
+
+/// Load a segment register other than cs from a descriptor that has already been checked
+unsafe fn load_segment(reg: i32, selector_raw: i32, descriptor: &SegmentDescriptor) {
+    dbg_assert!(reg != CS);
+
+    if reg == SS {
+        *stack_size_32 = descriptor.is_32();
+    }
+
     *segment_is_null.offset(reg as isize) = false;
     *segment_limits.offset(reg as isize) = descriptor.effective_limit();
     *segment_offsets.offset(reg as isize) = descriptor.base();
     *segment_access_bytes.offset(reg as isize) = descriptor.access_byte();
+    *segment_is_big.offset(reg as isize) = descriptor.is_32();
     *sreg.offset(reg as isize) = selector_raw as u16;
 
     update_state_flags();
-
-    true
 }
 
-pub unsafe fn load_tr(selector: i32) {
-    let selector = SegmentSelector::of_u16(selector as u16);
-    dbg_assert!(selector.is_gdt(), "TODO: TR can only be loaded from GDT");
+pub unsafe fn load_tr(selector_raw: i32) {
+    let selector = SegmentSelector::of_u16(selector_raw as u16);
+
+    if selector.descriptor_offset() == 0 {
+        dbg_log!("#GP | ltr: null selector sel={:x}", selector_raw);
+        trigger_gp(0);
+        return;
+    }
+
+    if !selector.is_gdt() {
+        dbg_log!("#GP | ltr: selector in ldt sel={:x}", selector_raw);
+        trigger_gp(selector_raw & !3);
+        return;
+    }
 
     let (descriptor, descriptor_address) =
         match return_on_pagefault!(lookup_segment_selector(selector)) {
             Ok((desc, addr)) => (desc, addr),
-            Err(SelectorNullOrInvalid::IsNull) => {
-                panic!("TODO: null TR");
-            },
-            Err(SelectorNullOrInvalid::OutsideOfTableLimit) => {
-                panic!("TODO: TR selector outside of table limit");
+            Err(_) => {
+                dbg_log!("#GP | ltr: selector outside of table limit sel={:x}", selector_raw);
+                trigger_gp(selector_raw & !3);
+                return;
             },
         };
 
@@ -2548,69 +3327,80 @@ pub unsafe fn load_tr(selector: i32) {
     //    descriptor.system_type() == 9,
     //);
 
-    if !descriptor.is_system() {
-        panic!("#GP | ltr: not a system entry (happens when running kvm-unit-test without ACPI)");
-    }
-
-    if descriptor.system_type() != 9 && descriptor.system_type() != 1 {
-        // 0xB: busy 386 TSS (GP)
-        // 0x9: 386 TSS
-        // 0x3: busy 286 TSS (GP)
-        // 0x1: 286 TSS (??)
-        panic!(
-            "#GP | ltr: invalid type (type = 0x{:x})",
+    // 0xB: busy 386 TSS (GP)
+    // 0x9: 386 TSS
+    // 0x3: busy 286 TSS (GP)
+    // 0x1: 286 TSS
+    if !descriptor.is_system()
+        || (descriptor.system_type() != 9 && descriptor.system_type() != 1)
+    {
+        dbg_log!(
+            "#GP | ltr: invalid descriptor sel={:x} sys={} type=0x{:x}",
+            selector_raw,
+            descriptor.is_system(),
             descriptor.system_type()
         );
+        trigger_gp(selector_raw & !3);
+        return;
     }
 
     if !descriptor.is_present() {
-        panic!("#NT | present bit not set (ltr)");
+        dbg_log!("#NP | ltr: present bit not set sel={:x}", selector_raw);
+        trigger_np(selector_raw & !3);
+        return;
     }
 
+    // Mark task as busy
+    return_on_pagefault!(safe_write64(descriptor_address, descriptor.set_busy().raw));
+
     *tss_size_32 = descriptor.system_type() == 9;
     *segment_limits.offset(TR as isize) = descriptor.effective_limit();
     *segment_offsets.offset(TR as isize) = descriptor.base();
     *sreg.offset(TR as isize) = selector.raw;
-
-    // Mark task as busy
-    safe_write64(descriptor_address, descriptor.set_busy().raw).unwrap();
 }
 
-pub unsafe fn load_ldt(selector: i32) -> OrPageFault<()> {
-    let selector = SegmentSelector::of_u16(selector as u16);
+/// Load the ldtr by lldt. Invalid selectors raise #GP and #NP
+pub unsafe fn load_ldt(selector_raw: i32) -> OrPageFault<()> {
+    let selector = SegmentSelector::of_u16(selector_raw as u16);
 
-    if selector.is_null() {
+    // The table indicator is ignored for null selectors
+    if selector.descriptor_offset() == 0 {
         *segment_limits.offset(LDTR as isize) = 0;
         *segment_offsets.offset(LDTR as isize) = 0;
         *sreg.offset(LDTR as isize) = selector.raw;
         return Ok(());
     }
 
-    dbg_assert!(selector.is_gdt(), "TODO: LDT can only be loaded from GDT");
+    if !selector.is_gdt() {
+        dbg_log!("#GP | lldt: selector in ldt sel={:x}", selector_raw);
+        trigger_gp(selector_raw & !3);
+        return Err(());
+    }
 
-    let (descriptor, _) = match lookup_segment_selector(selector)? {
-        Ok((desc, addr)) => (desc, addr),
-        Err(SelectorNullOrInvalid::IsNull) => {
-            panic!("TODO: null TR");
-        },
-        Err(SelectorNullOrInvalid::OutsideOfTableLimit) => {
-            panic!("TODO: TR selector outside of table limit");
+    let descriptor = match lookup_segment_selector(selector)? {
+        Ok((desc, _)) => desc,
+        Err(_) => {
+            dbg_log!("#GP | lldt: selector outside of table limit sel={:x}", selector_raw);
+            trigger_gp(selector_raw & !3);
+            return Err(());
         },
     };
 
-    if !descriptor.is_present() {
-        panic!("#NT | present bit not set (lldt)");
-    }
-
-    if !descriptor.is_system() {
-        panic!("#GP | lldt: not a system entry");
-    }
-
-    if descriptor.system_type() != 2 {
-        panic!(
-            "#GP | lldt: invalid type (type = 0x{:x})",
+    if !descriptor.is_system() || descriptor.system_type() != 2 {
+        dbg_log!(
+            "#GP | lldt: invalid descriptor sel={:x} sys={} type=0x{:x}",
+            selector_raw,
+            descriptor.is_system(),
             descriptor.system_type()
         );
+        trigger_gp(selector_raw & !3);
+        return Err(());
+    }
+
+    if !descriptor.is_present() {
+        dbg_log!("#NP | lldt: present bit not set sel={:x}", selector_raw);
+        trigger_np(selector_raw & !3);
+        return Err(());
     }
 
     *segment_limits.offset(LDTR as isize) = descriptor.effective_limit();
@@ -2646,9 +3436,6 @@ pub unsafe fn get_seg(segment: i32) -> OrPageFault<i32> {
 pub unsafe fn set_cr0(cr0: i32) {
     let old_cr0 = *cr;
 
-    if old_cr0 & CR0_AM == 0 && cr0 & CR0_AM != 0 {
-        dbg_log!("Warning: Unimplemented: cr0 alignment mask");
-    }
     if (cr0 & (CR0_PE | CR0_PG)) == CR0_PG {
         panic!("cannot load PG without PE");
     }
@@ -2668,6 +3455,8 @@ pub unsafe fn set_cr0(cr0: i32) {
 
     *protected_mode = (*cr & CR0_PE) == CR0_PE;
     *segment_access_bytes.offset(CS as isize) = 0x80 | 0x10 | 0x08 | 0x02; // P dpl0 S E RW
+
+    update_state_flags();
 }
 
 pub unsafe fn set_cr3(mut cr3: i32) {
@@ -2716,6 +3505,9 @@ pub unsafe fn update_cs_size(new_size: bool) {
 
 #[inline(never)]
 pub unsafe fn test_privileges_for_io(port: i32, size: i32) -> bool {
+    // Discarded by call_interrupt_vector if the access faults
+    check_io_breakpoints(port, size);
+
     if *protected_mode && (*cpl > getiopl() as u8 || (*flags & FLAG_VM != 0)) {
         if !*tss_size_32 {
             dbg_log!("#GP for port io, 16-bit TSS  port={:x} size={}", port, size);
@@ -2760,7 +3552,7 @@ pub unsafe fn test_privileges_for_io(port: i32, size: i32) -> bool {
 }
 
 pub unsafe fn popa16() {
-    return_on_pagefault!(readable_or_pagefault(get_stack_pointer(0), 16));
+    return_on_pagefault!(readable_or_pagefault_seg(SS, get_stack_pointer(0), 16));
 
     write_reg16(DI, pop16().unwrap());
     write_reg16(SI, pop16().unwrap());
@@ -2773,7 +3565,7 @@ pub unsafe fn popa16() {
 }
 
 pub unsafe fn popa32() {
-    return_on_pagefault!(readable_or_pagefault(get_stack_pointer(0), 32));
+    return_on_pagefault!(readable_or_pagefault_seg(SS, get_stack_pointer(0), 32));
 
     write_reg32(EDI, pop32s().unwrap());
     write_reg32(ESI, pop32s().unwrap());
@@ -2819,15 +3611,29 @@ pub unsafe fn get_seg_prefix(default_segment: i32) -> OrPageFault<i32> {
     };
 }
 
-pub unsafe fn get_seg_prefix_ds(offset: i32) -> OrPageFault<i32> {
-    Ok(get_seg_prefix(DS)? + offset)
+/// The segment register of a memory operand (after applying the segment prefix) and the linear
+/// address of its offset. The segment is meaningless with SEG_PREFIX_ZERO (lea), as nothing is
+/// accessed through it
+pub unsafe fn get_seg_prefix_address(default_segment: i32, offset: i32) -> OrPageFault<(i32, i32)> {
+    dbg_assert!(!in_jit);
+    let prefix = *prefixes & prefix::PREFIX_MASK_SEGMENT;
+    if prefix == prefix::SEG_PREFIX_ZERO {
+        return Ok((default_segment, offset));
+    }
+    let segment = if 0 != prefix { prefix as i32 - 1 } else { default_segment };
+    Ok((segment, get_seg(segment)? + offset))
+}
+
+pub unsafe fn get_seg_prefix_ds(offset: i32) -> OrPageFault<(i32, i32)> {
+    get_seg_prefix_address(DS, offset)
 }
 
-pub unsafe fn get_seg_prefix_ss(offset: i32) -> OrPageFault<i32> {
-    Ok(get_seg_prefix(SS)? + offset)
+pub unsafe fn get_seg_prefix_ss(offset: i32) -> OrPageFault<(i32, i32)> {
+    get_seg_prefix_address(SS, offset)
 }
 
-pub unsafe fn modrm_resolve(modrm_byte: i32) -> OrPageFault<i32> {
+/// Resolve a memory operand to its segment register and linear address
+pub unsafe fn modrm_resolve(modrm_byte: i32) -> OrPageFault<(i32, i32)> {
     if is_asize_32() {
         resolve_modrm32(modrm_byte)
     }
@@ -2839,6 +3645,14 @@ pub unsafe fn modrm_resolve(modrm_byte: i32) -> OrPageFault<i32> {
 pub unsafe fn run_instruction(opcode: i32) { ::gen::interpreter::run(opcode as u32) }
 pub unsafe fn run_instruction0f_16(opcode: i32) { ::gen::interpreter0f::run(opcode as u32) }
 pub unsafe fn run_instruction0f_32(opcode: i32) { ::gen::interpreter0f::run(opcode as u32 | 0x100) }
+pub unsafe fn run_instruction0f38_16(opcode: i32) { ::gen::interpreter0f38::run(opcode as u32) }
+pub unsafe fn run_instruction0f38_32(opcode: i32) {
+    ::gen::interpreter0f38::run(opcode as u32 | 0x100)
+}
+pub unsafe fn run_instruction0f3a_16(opcode: i32) { ::gen::interpreter0f3a::run(opcode as u32) }
+pub unsafe fn run_instruction0f3a_32(opcode: i32) {
+    ::gen::interpreter0f3a::run(opcode as u32 | 0x100)
+}
 
 pub unsafe fn cycle_internal() {
     profiler::stat_increment(CYCLE_INTERNAL);
@@ -2884,6 +3698,16 @@ pub unsafe fn cycle_internal() {
         },
     }
 
+    // Single-stepping, the resume flag, i/o breakpoints and data breakpoints hit in jitted code
+    // are handled by the interpreter
+    let force_interpreter = *flags & FLAG_TRAP != 0
+        || debug_trap_pending != 0
+        || breakpoints_enabled()
+            && (debug_force_interpreter || *flags & FLAG_RF != 0 || io_breakpoints_enabled());
+    if force_interpreter {
+        jit_entry = None;
+    }
+
     if let Some((wasm_table_index, initial_state)) = jit_entry {
         if jit::CHECK_JIT_STATE_INVARIANTS {
             match get_phys_eip() {
@@ -2909,6 +3733,9 @@ pub unsafe fn cycle_internal() {
         {
             in_jit = false;
         }
+        if debug_trap_pending != 0 {
+            trigger_db_trap();
+        }
         profiler::stat_increment_by(
             RUN_FROM_CACHE_STEPS,
             (*instruction_counter - initial_instruction_counter) as u64,
@@ -2947,6 +3774,14 @@ pub unsafe fn cycle_internal() {
     }
     else {
         *previous_ip = initial_eip;
+
+        if breakpoints_enabled() {
+            debug_force_interpreter = false;
+            if check_instruction_breakpoints() {
+                return;
+            }
+        }
+
         let phys_addr = return_on_pagefault!(get_phys_eip());
 
         match tlb_code[(initial_eip as u32 >> 12) as usize] {
@@ -2956,6 +3791,7 @@ pub unsafe fn cycle_internal() {
 
                 if initial_state_flags == c.state_flags
                     && c.state_table[initial_eip as usize & 0xFFF] != u16::MAX
+                    && !force_interpreter
                 {
                     profiler::stat_increment(RUN_INTERPRETED_PAGE_HAS_ENTRY_AFTER_PAGE_WALK);
                     return;
@@ -2995,7 +3831,7 @@ pub unsafe fn cycle_internal() {
 pub unsafe fn get_phys_eip() -> OrPageFault<u32> {
     let eip = *instruction_pointer;
     if 0 != eip & !0xFFF ^ *last_virt_eip {
-        *eip_phys = (translate_address_read(eip)? ^ eip as u32) as i32;
+        *eip_phys = (translate_address_execute(eip, false, true)? ^ eip as u32) as i32;
         *last_virt_eip = eip & !0xFFF
     }
     let phys_addr = (*eip_phys ^ eip) as u32;
@@ -3019,32 +3855,52 @@ unsafe fn jit_run_interpreted(mut phys_addr: u32) {
         }
 
         i += 1;
+        // counted right away, as time is derived from it in icount mode
+        *instruction_counter += 1;
         let start_eip = *instruction_pointer;
         let opcode = *mem8.offset(phys_addr as isize) as i32;
         *instruction_pointer += 1;
+        if *flags & FLAG_TRAP != 0 {
+            // Single-step: Trap after this instruction, unless it raises an exception or interrupt
+            debug_trap_pending |= DR6_BS;
+        }
         dbg_assert!(*prefixes == 0);
         run_instruction(opcode | (*is_32 as i32) << 8);
         dbg_assert!(*prefixes == 0);
 
+        if debug_trap_pending != 0 {
+            if debug_traps_inhibited {
+                debug_traps_inhibited = false;
+            }
+            else {
+                trigger_db_trap();
+                break;
+            }
+        }
+
         if jit_block_boundary
             || Page::page_of(start_eip as u32) != Page::page_of(*instruction_pointer as u32)
                 // Limit the number of iterations, as jumps within the same page are not counted as
                 // block boundaries for the interpreter, but only on the next backwards jump
             || (i >= INTERPRETER_ITERATION_LIMIT
                 && (start_eip as u32) >= (*instruction_pointer as u32))
+            || replay::irq_due()
         {
             break;
         }
 
         *previous_ip = *instruction_pointer;
+
+        if breakpoints_enabled() && check_instruction_breakpoints() {
+            break;
+        }
+
         phys_addr = return_on_pagefault!(get_phys_eip()) as u32;
     }
 
     if cfg!(debug_assertions) {
         debug_last_jump = LastJump::Interpreted { phys_addr };
     }
-
-    *instruction_counter += i;
 }
 
 #[no_mangle]
@@ -3054,7 +3910,10 @@ pub fn update_state_flags() {
             (*is_32 as u32) << 0
                 | (*stack_size_32 as u32) << 1
                 | ((*cpl == 3) as u32) << 2
-                | (has_flat_segmentation() as u32) << 3,
+                | (has_flat_segmentation() as u32) << 3
+                | (alignment_check_enabled() as u32) << 4
+                | ((*protected_mode && !vm86_mode()) as u32) << 5
+                | (segment_checks_active() as u32) << 6,
         )
     }
 }
@@ -3065,7 +3924,24 @@ pub unsafe fn has_flat_segmentation() -> bool {
     return *segment_offsets.offset(SS as isize) == 0
         && !*segment_is_null.offset(DS as isize)
         && *segment_offsets.offset(DS as isize) == 0
-        && *segment_offsets.offset(CS as isize) == 0;
+        && *segment_offsets.offset(CS as isize) == 0
+        // jitted code only checks segment limits if the segmentation isn't flat, see
+        // gen_segment_check
+        && (!segment_checks_active() || (0..6).all(|reg| segment_is_unrestricted(reg)));
+}
+
+pub unsafe fn segment_checks_active() -> bool {
+    segment_checks && *protected_mode && !vm86_mode()
+}
+
+/// Whether no data access through the segment register `reg` can fault due to the segment's limit
+/// or type: A flat writable data segment, a flat readable code segment in cs, or a null segment
+/// (which faults when the address is computed)
+pub unsafe fn segment_is_unrestricted(reg: i32) -> bool {
+    let access = *segment_access_bytes.offset(reg as isize);
+    *segment_is_null.offset(reg as isize)
+        || *segment_limits.offset(reg as isize) == 0xFFFF_FFFF
+            && (access & 0x0E == 0x02 || reg == CS && access & 0x0A == 0x0A)
 }
 
 pub unsafe fn run_prefix_instruction() {
@@ -3086,12 +3962,13 @@ pub unsafe fn main_loop() -> f64 {
     let start = microtick();
 
     if *in_hlt {
-        if *flags & FLAG_INTERRUPT != 0 {
-            let t = run_hardware_timers(*acpi_enabled, start);
+        if *flags & FLAG_INTERRUPT != 0 || in_mwait {
+            // mwait also ends on writes to the monitored line and, optionally, on masked interrupts
+            let t = run_hardware_timers(*acpi_enabled, cpu_microtick());
             handle_irqs();
             if *in_hlt {
                 profiler::stat_increment(MAIN_LOOP_IDLE);
-                return t;
+                return idle(t);
             }
         }
         else {
@@ -3104,10 +3981,10 @@ pub unsafe fn main_loop() -> f64 {
         do_many_cycles_native();
 
         let now = microtick();
-        let t = run_hardware_timers(*acpi_enabled, now);
+        let t = run_hardware_timers(*acpi_enabled, cpu_microtick());
         handle_irqs();
         if *in_hlt {
-            return t;
+            return idle(t);
         }
 
         if now - start > TIME_PER_FRAME {
@@ -3118,11 +3995,24 @@ pub unsafe fn main_loop() -> f64 {
     return 0.0;
 }
 
+/// The time to wait until the next timer is due while halted. In icount mode, time jumps forward
+/// to the timer instead
+unsafe fn idle(t: f64) -> f64 {
+    if icount_rate == 0.0 {
+        return t;
+    }
+    // at least one instruction, so that time always advances
+    icount_offset = icount_offset.wrapping_add(u64::max(1, (t * icount_rate).ceil() as u64));
+    0.0
+}
+
 pub unsafe fn do_many_cycles_native() {
     profiler::stat_increment(DO_MANY_CYCLES);
     let initial_instruction_counter = *instruction_counter;
+    // A triple fault also sets in_hlt
     while (*instruction_counter).wrapping_sub(initial_instruction_counter) < LOOP_COUNTER as u32
         && !*in_hlt
+        && !replay::irq_due()
     {
         cycle_internal();
     }
@@ -3137,20 +4027,33 @@ pub unsafe fn trigger_de() {
             return;
         }
     }
-    call_interrupt_vector(CPU_EXCEPTION_DE, false, None);
+    call_interrupt_vector(CPU_EXCEPTION_DE, false, None);
+}
+
+#[inline(never)]
+pub unsafe fn trigger_ud() {
+    dbg_log!("#ud");
+    dbg_trace();
+    *instruction_pointer = *previous_ip;
+    if DEBUG {
+        if cpu_exception_hook(CPU_EXCEPTION_UD) {
+            return;
+        }
+    }
+    call_interrupt_vector(CPU_EXCEPTION_UD, false, None);
 }
 
 #[inline(never)]
-pub unsafe fn trigger_ud() {
-    dbg_log!("#ud");
+pub unsafe fn trigger_mf() {
+    dbg_log!("#mf eip={:x}", *previous_ip);
     dbg_trace();
     *instruction_pointer = *previous_ip;
     if DEBUG {
-        if cpu_exception_hook(CPU_EXCEPTION_UD) {
+        if cpu_exception_hook(CPU_EXCEPTION_MF) {
             return;
         }
     }
-    call_interrupt_vector(CPU_EXCEPTION_UD, false, None);
+    call_interrupt_vector(CPU_EXCEPTION_MF, false, None);
 }
 
 #[inline(never)]
@@ -3178,6 +4081,267 @@ pub unsafe fn trigger_gp(code: i32) {
     call_interrupt_vector(CPU_EXCEPTION_GP, false, Some(code));
 }
 
+#[cold]
+pub unsafe fn trigger_db() {
+    // Instruction breakpoints and general detect: Raised before the instruction is executed
+    dbg_log!("#db dr6={:x}", *dreg.offset(6));
+    *instruction_pointer = *previous_ip;
+    if DEBUG {
+        if cpu_exception_hook(CPU_EXCEPTION_DB) {
+            return;
+        }
+    }
+    call_interrupt_vector(CPU_EXCEPTION_DB, false, None);
+}
+
+#[cold]
+pub unsafe fn trigger_db_trap() {
+    // Data breakpoints: Raised after the instruction has completed
+    dbg_assert!(debug_trap_pending != 0);
+    *dreg.offset(6) = *dreg.offset(6) & !DR6_B0_B3 | debug_trap_pending;
+    debug_trap_pending = 0;
+    debug_traps_inhibited = false;
+    dbg_log!("#db (trap) dr6={:x}", *dreg.offset(6));
+    if DEBUG {
+        if cpu_exception_hook(CPU_EXCEPTION_DB) {
+            return;
+        }
+    }
+    call_interrupt_vector(CPU_EXCEPTION_DB, false, None);
+}
+
+pub unsafe fn inhibit_debug_traps() {
+    if debug_trap_pending != 0 {
+        debug_traps_inhibited = true;
+    }
+}
+
+pub unsafe fn breakpoints_enabled() -> bool { *dreg.offset(7) & DR7_ENABLE_MASK != 0 }
+
+/// Returns the (aligned) linear address or port, length and type of breakpoint i, if it is
+/// enabled
+unsafe fn get_breakpoint(i: i32) -> Option<(u32, u32, i32)> {
+    let dr7 = *dreg.offset(7);
+    if dr7 >> (2 * i) & 3 == 0 {
+        return None;
+    }
+    let rw = dr7 >> (16 + 4 * i) & 3;
+    if rw == DR7_RW_IO && *cr.offset(4) & CR4_DE == 0 {
+        // Only defined with cr4.DE
+        return None;
+    }
+    let len = match dr7 >> (18 + 4 * i) & 3 {
+        0 => 1,
+        1 => 2,
+        2 => 8,
+        _ => 4,
+    };
+    Some((*dreg.offset(i as isize) as u32 & !(len - 1), len, rw))
+}
+
+pub unsafe fn page_has_breakpoint(page: u32) -> bool {
+    (0..4).any(|i| {
+        get_breakpoint(i).map_or(false, |(addr, _, rw)| rw != DR7_RW_IO && addr >> 12 == page)
+    })
+}
+
+pub unsafe fn io_breakpoints_enabled() -> bool {
+    breakpoints_enabled()
+        && (0..4).any(|i| get_breakpoint(i).map_or(false, |(_, _, rw)| rw == DR7_RW_IO))
+}
+
+fn breakpoint_overlaps(bp_addr: u32, len: u32, addr: i32, size: i32) -> bool {
+    (addr as u32).wrapping_sub(bp_addr) < len || bp_addr.wrapping_sub(addr as u32) < size as u32
+}
+
+/// Returns the B0-B3 bits of the data breakpoints matched by an access of `size` bytes at the
+/// linear address `addr`
+pub unsafe fn data_breakpoint_hits(addr: i32, size: i32, is_write: bool) -> i32 {
+    let mut hits = 0;
+    for i in 0..4 {
+        if let Some((bp_addr, len, rw)) = get_breakpoint(i) {
+            let matches_type = rw == DR7_RW_READ_WRITE || rw == DR7_RW_WRITE && is_write;
+            if matches_type && breakpoint_overlaps(bp_addr, len, addr, size) {
+                hits |= 1 << i;
+            }
+        }
+    }
+    hits
+}
+
+/// I/O breakpoints: Like data breakpoints, raised after the in, out, ins or outs instruction has
+/// completed. Jitted code isn't run while they are enabled
+pub unsafe fn check_io_breakpoints(port: i32, size: i32) {
+    if !breakpoints_enabled() {
+        return;
+    }
+    for i in 0..4 {
+        if let Some((bp_port, len, DR7_RW_IO)) = get_breakpoint(i) {
+            if breakpoint_overlaps(bp_port, len, port, size) {
+                debug_trap_pending |= 1 << i;
+            }
+        }
+    }
+}
+
+#[inline(always)]
+pub unsafe fn check_data_breakpoints(addr: i32, size: i32, is_write: bool) {
+    if breakpoints_enabled() {
+        debug_trap_pending |= data_breakpoint_hits(addr, size, is_write);
+    }
+}
+
+/// Whether misaligned data accesses raise #AC: At cpl 3, with cr0.AM and eflags.AC set
+pub unsafe fn alignment_check_enabled() -> bool {
+    *flags & FLAG_AC != 0 && *cpl == 3 && *cr & CR0_AM != 0
+}
+
+#[inline(always)]
+pub unsafe fn check_alignment(addr: i32, size: i32) -> OrPageFault<()> {
+    if addr & (size - 1) != 0 && alignment_check_enabled() {
+        dbg_log!("#ac: misaligned access addr={:x} size={}", addr, size);
+        trigger_ac();
+        return Err(());
+    }
+    Ok(())
+}
+
+/// Like check_alignment, for the slow paths of jitted memory accesses. Returns true if #AC has
+/// been prepared in jit_fault
+unsafe fn check_alignment_jit(addr: i32, size: i32, eip_offset_in_page: i32) -> bool {
+    if addr & (size - 1) != 0 && alignment_check_enabled() {
+        dbg_log!("#ac in jit mode: misaligned access addr={:x} size={}", addr, size);
+        *instruction_pointer = *instruction_pointer & !0xFFF | eip_offset_in_page;
+        jit_fault = Some((CPU_EXCEPTION_AC, Some(0)));
+        return true;
+    }
+    false
+}
+
+/// Check an access of `size` bytes at the linear address `addr` against the limit and type of the
+/// segment register `seg`. Raises #SS(0) for stack segment violations and #GP(0) otherwise
+#[inline(always)]
+pub unsafe fn check_segment_access(
+    seg: i32,
+    addr: i32,
+    size: i32,
+    is_write: bool,
+) -> OrPageFault<()> {
+    if segment_checks_active() && !segment_access_ok(seg, addr, size, is_write) {
+        if seg == SS {
+            trigger_ss(0);
+        }
+        else {
+            trigger_gp(0);
+        }
+        Err(())
+    }
+    else {
+        Ok(())
+    }
+}
+
+/// Whether the offsets from start to start + size - 1 are within the limit of a segment
+fn segment_range_within_limit(access: u8, limit: u32, is_big: bool, start: u32, size: u32) -> bool {
+    let is_code = access & 0x08 != 0;
+    let end = start.wrapping_add(size - 1);
+    if !is_code && access & 0x04 != 0 {
+        // expand-down: valid offsets are above the limit, up to 64k or 4G depending on the B bit
+        let upper = if is_big { 0xFFFF_FFFF } else { 0xFFFF };
+        start > limit && end >= start && end <= upper
+    }
+    else {
+        limit == 0xFFFF_FFFF || end >= start && end <= limit
+    }
+}
+
+/// Whether an access of `size` bytes at the linear address `addr` is allowed by the limit and type
+/// of the segment register `seg`
+#[inline(never)]
+unsafe fn segment_access_ok(seg: i32, addr: i32, size: i32, is_write: bool) -> bool {
+    dbg_assert!(seg >= 0 && seg < 6);
+
+    let access = *segment_access_bytes.offset(seg as isize);
+    let is_code = access & 0x08 != 0;
+    let type_ok = if is_write {
+        !is_code && access & 0x02 != 0
+    }
+    else {
+        // execute-only code segments can't be read
+        !is_code || access & 0x02 != 0
+    };
+
+    let limit = *segment_limits.offset(seg as isize);
+    let start = (addr - *segment_offsets.offset(seg as isize)) as u32;
+    let is_big = if seg == SS { *stack_size_32 } else { *segment_is_big.offset(seg as isize) };
+    let limit_ok = segment_range_within_limit(access, limit, is_big, start, size as u32);
+
+    if !(type_ok && limit_ok) {
+        dbg_log!(
+            "#{} segment check seg={} offset={:x} size={} write={} limit={:x} access={:x}",
+            if seg == SS { "ss" } else { "gp" },
+            seg,
+            start,
+            size,
+            is_write,
+            limit,
+            access,
+        );
+    }
+    type_ok && limit_ok
+}
+
+/// Like check_segment_access, for jitted code (see gen_segment_check). Returns true if the fault
+/// has been prepared in jit_fault
+unsafe fn check_segment_access_jit(
+    seg: i32,
+    addr: i32,
+    size: i32,
+    is_write: bool,
+    eip_offset_in_page: i32,
+) -> bool {
+    dbg_assert!(eip_offset_in_page >= 0 && eip_offset_in_page < 0x1000);
+    if segment_access_ok(seg, addr, size, is_write) {
+        return false;
+    }
+    *instruction_pointer = *instruction_pointer & !0xFFF | eip_offset_in_page;
+    jit_fault = Some((if seg == SS { CPU_EXCEPTION_SS } else { CPU_EXCEPTION_GP }, Some(0)));
+    true
+}
+#[no_mangle]
+pub unsafe fn check_segment_read_jit(seg: i32, addr: i32, size: i32, eip_offset: i32) -> bool {
+    check_segment_access_jit(seg, addr, size, false, eip_offset)
+}
+#[no_mangle]
+pub unsafe fn check_segment_write_jit(seg: i32, addr: i32, size: i32, eip_offset: i32) -> bool {
+    check_segment_access_jit(seg, addr, size, true, eip_offset)
+}
+
+/// Check for instruction breakpoints at the current instruction pointer, before the instruction
+/// is executed. Returns true if #DB has been raised
+pub unsafe fn check_instruction_breakpoints() -> bool {
+    if *flags & FLAG_RF != 0 {
+        // Set by the #DB handler (via iret) to continue after an instruction breakpoint
+        *flags &= !FLAG_RF;
+        return false;
+    }
+    let mut hits = 0;
+    for i in 0..4 {
+        if let Some((addr, _, DR7_RW_EXECUTE)) = get_breakpoint(i) {
+            if addr == *instruction_pointer as u32 {
+                hits |= 1 << i;
+            }
+        }
+    }
+    if hits == 0 {
+        return false;
+    }
+    *dreg.offset(6) = *dreg.offset(6) & !DR6_B0_B3 | hits;
+    *previous_ip = *instruction_pointer;
+    trigger_db();
+    true
+}
+
 #[cold]
 pub unsafe fn virt_boundary_read16(low: u32, high: u32) -> i32 {
     dbg_assert!(low & 0xFFF == 0xFFF);
@@ -3240,9 +4404,14 @@ pub unsafe fn virt_boundary_write32(low: u32, high: u32, value: i32) {
     write8(high as u32, value >> 24);
 }
 
-pub unsafe fn safe_read8(addr: i32) -> OrPageFault<i32> { Ok(read8(translate_address_read(addr)?)) }
+pub unsafe fn safe_read8(addr: i32) -> OrPageFault<i32> {
+    check_data_breakpoints(addr, 1, false);
+    Ok(read8(translate_address_read(addr)?))
+}
 
 pub unsafe fn safe_read16(addr: i32) -> OrPageFault<i32> {
+    check_alignment(addr, 2)?;
+    check_data_breakpoints(addr, 2, false);
     if addr & 0xFFF == 0xFFF {
         Ok(safe_read8(addr)? | safe_read8(addr + 1)? << 8)
     }
@@ -3252,6 +4421,8 @@ pub unsafe fn safe_read16(addr: i32) -> OrPageFault<i32> {
 }
 
 pub unsafe fn safe_read32s(addr: i32) -> OrPageFault<i32> {
+    check_alignment(addr, 4)?;
+    check_data_breakpoints(addr, 4, false);
     if addr & 0xFFF >= 0xFFD {
         Ok(safe_read16(addr)? | safe_read16(addr + 2)? << 16)
     }
@@ -3265,6 +4436,8 @@ pub unsafe fn safe_read_f32(addr: i32) -> OrPageFault<f32> {
 }
 
 pub unsafe fn safe_read64s(addr: i32) -> OrPageFault<u64> {
+    check_alignment(addr, 8)?;
+    check_data_breakpoints(addr, 8, false);
     if addr & 0xFFF > 0x1000 - 8 {
         Ok(safe_read32s(addr)? as u32 as u64 | (safe_read32s(addr + 4)? as u32 as u64) << 32)
     }
@@ -3274,6 +4447,7 @@ pub unsafe fn safe_read64s(addr: i32) -> OrPageFault<u64> {
 }
 
 pub unsafe fn safe_read128s(addr: i32) -> OrPageFault<reg128> {
+    check_data_breakpoints(addr, 16, false);
     if addr & 0xFFF > 0x1000 - 16 {
         Ok(reg128 {
             u64: [safe_read64s(addr)?, safe_read64s(addr + 8)?],
@@ -3299,6 +4473,9 @@ pub fn report_safe_read_jit_slow(address: u32, entry: i32) {
     else if address & 0xFFF > 0x1000 - 16 {
         profiler::stat_increment(SAFE_READ_SLOW_PAGE_CROSSED);
     }
+    else if entry & TLB_SMAP != 0 {
+        profiler::stat_increment(SAFE_READ_SLOW_SMAP);
+    }
     else {
         dbg_log!("Unexpected entry bit: {:x} (read at {:x})", entry, address);
         dbg_assert!(false);
@@ -3326,6 +4503,9 @@ pub fn report_safe_write_jit_slow(address: u32, entry: i32) {
     else if address & 0xFFF > 0x1000 - 16 {
         profiler::stat_increment(SAFE_WRITE_SLOW_PAGE_CROSSED);
     }
+    else if entry & TLB_SMAP != 0 {
+        profiler::stat_increment(SAFE_WRITE_SLOW_SMAP);
+    }
     else {
         dbg_assert!(false);
     }
@@ -3352,6 +4532,9 @@ pub fn report_safe_read_write_jit_slow(address: u32, entry: i32) {
     else if address & 0xFFF > 0x1000 - 16 {
         profiler::stat_increment(SAFE_READ_WRITE_SLOW_PAGE_CROSSED);
     }
+    else if entry & TLB_SMAP != 0 {
+        profiler::stat_increment(SAFE_READ_WRITE_SLOW_SMAP);
+    }
     else {
         dbg_assert!(false);
     }
@@ -3368,6 +4551,10 @@ pub unsafe fn safe_read_slow_jit(
     is_write: bool,
 ) -> i32 {
     dbg_assert!(eip_offset_in_page >= 0 && eip_offset_in_page < 0x1000);
+    // 128-bit accesses are not subject to alignment checking
+    if bitsize != 128 && check_alignment_jit(addr, bitsize / 8, eip_offset_in_page) {
+        return 1;
+    }
     if is_write && Page::page_of(*instruction_pointer as u32) == Page::page_of(addr as u32) {
         // XXX: Check based on virtual address
         dbg_log!(
@@ -3390,6 +4577,12 @@ pub unsafe fn safe_read_slow_jit(
         },
         Ok(addr) => addr,
     };
+    if breakpoints_enabled() && data_breakpoint_hits(addr, bitsize / 8, is_write) != 0 {
+        // Leave jitted code, the instruction is rerun in the interpreter, which raises #DB
+        *instruction_pointer = *instruction_pointer & !0xFFF | eip_offset_in_page;
+        debug_force_interpreter = true;
+        return 1;
+    }
     if crosses_page {
         let boundary_addr = (addr | 0xFFF) + 1;
         let addr_high = match if is_write {
@@ -3478,8 +4671,13 @@ pub unsafe fn safe_read128s_slow_jit(addr: i32, eip: i32) -> i32 {
 
 #[no_mangle]
 pub unsafe fn get_phys_eip_slow_jit(addr: i32) -> i32 {
-    match translate_address_read_jit(addr) {
+    match translate_address_execute(addr, true, true) {
         Err(()) => 1,
+        Ok(_) if tlb_data[(addr as u32 >> 12) as usize] & TLB_BREAKPOINT != 0 => {
+            // Don't enter pages with breakpoints from jitted code
+            dbg_assert!(jit_fault.is_none());
+            1
+        },
         Ok(addr_low) => {
             dbg_assert!(!in_mapped_range(addr_low as u32)); // same assumption as in read_imm8
             ((addr_low as i32 + memory::mem8 as i32) ^ addr) & !0xFFF
@@ -3512,6 +4710,9 @@ pub unsafe fn safe_write_slow_jit(
     eip_offset_in_page: i32,
 ) -> i32 {
     dbg_assert!(eip_offset_in_page >= 0 && eip_offset_in_page < 0x1000);
+    if bitsize != 128 && check_alignment_jit(addr, bitsize / 8, eip_offset_in_page) {
+        return 1;
+    }
     if Page::page_of(*instruction_pointer as u32) == Page::page_of(addr as u32) {
         // XXX: Check based on virtual address
         dbg_log!(
@@ -3530,6 +4731,12 @@ pub unsafe fn safe_write_slow_jit(
         },
         Ok(x) => x,
     };
+    if breakpoints_enabled() && data_breakpoint_hits(addr, bitsize / 8, true) != 0 {
+        // Leave jitted code, the instruction is rerun in the interpreter, which raises #DB
+        *instruction_pointer = *instruction_pointer & !0xFFF | eip_offset_in_page;
+        debug_force_interpreter = true;
+        return 1;
+    }
     if crosses_page {
         let (addr_high, _) =
             match translate_address_write_jit_and_can_skip_dirty((addr | 0xFFF) + 1) {
@@ -3621,6 +4828,7 @@ pub unsafe fn safe_write128_slow_jit(
 }
 
 pub unsafe fn safe_write8(addr: i32, value: i32) -> OrPageFault<()> {
+    check_data_breakpoints(addr, 1, true);
     let (phys_addr, can_skip_dirty_page) = translate_address_write_and_can_skip_dirty(addr)?;
     if in_mapped_range(phys_addr) {
         memory::mmap_write8(phys_addr, value);
@@ -3638,6 +4846,8 @@ pub unsafe fn safe_write8(addr: i32, value: i32) -> OrPageFault<()> {
 }
 
 pub unsafe fn safe_write16(addr: i32, value: i32) -> OrPageFault<()> {
+    check_alignment(addr, 2)?;
+    check_data_breakpoints(addr, 2, true);
     let (phys_addr, can_skip_dirty_page) = translate_address_write_and_can_skip_dirty(addr)?;
     if addr & 0xFFF == 0xFFF {
         virt_boundary_write16(phys_addr, translate_address_write(addr + 1)?, value);
@@ -3658,6 +4868,8 @@ pub unsafe fn safe_write16(addr: i32, value: i32) -> OrPageFault<()> {
 }
 
 pub unsafe fn safe_write32(addr: i32, value: i32) -> OrPageFault<()> {
+    check_alignment(addr, 4)?;
+    check_data_breakpoints(addr, 4, true);
     let (phys_addr, can_skip_dirty_page) = translate_address_write_and_can_skip_dirty(addr)?;
     if addr & 0xFFF > 0x1000 - 4 {
         virt_boundary_write32(
@@ -3682,6 +4894,8 @@ pub unsafe fn safe_write32(addr: i32, value: i32) -> OrPageFault<()> {
 }
 
 pub unsafe fn safe_write64(addr: i32, value: u64) -> OrPageFault<()> {
+    check_alignment(addr, 8)?;
+    check_data_breakpoints(addr, 8, true);
     if addr & 0xFFF > 0x1000 - 8 {
         writable_or_pagefault(addr, 8)?;
         safe_write32(addr, value as i32).unwrap();
@@ -3706,6 +4920,7 @@ pub unsafe fn safe_write64(addr: i32, value: u64) -> OrPageFault<()> {
 }
 
 pub unsafe fn safe_write128(addr: i32, value: reg128) -> OrPageFault<()> {
+    check_data_breakpoints(addr, 16, true);
     if addr & 0xFFF > 0x1000 - 16 {
         writable_or_pagefault(addr, 16)?;
         safe_write64(addr, value.u64[0]).unwrap();
@@ -3731,6 +4946,7 @@ pub unsafe fn safe_write128(addr: i32, value: reg128) -> OrPageFault<()> {
 
 #[inline(always)]
 pub unsafe fn safe_read_write8(addr: i32, instruction: &dyn Fn(i32) -> i32) {
+    check_data_breakpoints(addr, 1, true);
     let (phys_addr, can_skip_dirty_page) =
         return_on_pagefault!(translate_address_write_and_can_skip_dirty(addr));
     let x = memory::read8(phys_addr);
@@ -3751,6 +4967,8 @@ pub unsafe fn safe_read_write8(addr: i32, instruction: &dyn Fn(i32) -> i32) {
 
 #[inline(always)]
 pub unsafe fn safe_read_write16(addr: i32, instruction: &dyn Fn(i32) -> i32) {
+    return_on_pagefault!(check_alignment(addr, 2));
+    check_data_breakpoints(addr, 2, true);
     let (phys_addr, can_skip_dirty_page) =
         return_on_pagefault!(translate_address_write_and_can_skip_dirty(addr));
     if phys_addr & 0xFFF == 0xFFF {
@@ -3778,6 +4996,8 @@ pub unsafe fn safe_read_write16(addr: i32, instruction: &dyn Fn(i32) -> i32) {
 
 #[inline(always)]
 pub unsafe fn safe_read_write32(addr: i32, instruction: &dyn Fn(i32) -> i32) {
+    return_on_pagefault!(check_alignment(addr, 4));
+    check_data_breakpoints(addr, 4, true);
     let (phys_addr, can_skip_dirty_page) =
         return_on_pagefault!(translate_address_write_and_can_skip_dirty(addr));
     if phys_addr & 0xFFF >= 0xFFD {
@@ -3804,6 +5024,92 @@ pub unsafe fn safe_read_write32(addr: i32, instruction: &dyn Fn(i32) -> i32) {
     }
 }
 
+// Accesses to memory operands through the segment register `seg`, which are checked against the
+// segment's limit and type. The functions above take linear addresses and don't check segments
+
+pub unsafe fn safe_read8_seg(seg: i32, addr: i32) -> OrPageFault<i32> {
+    check_segment_access(seg, addr, 1, false)?;
+    safe_read8(addr)
+}
+
+pub unsafe fn safe_read16_seg(seg: i32, addr: i32) -> OrPageFault<i32> {
+    check_segment_access(seg, addr, 2, false)?;
+    safe_read16(addr)
+}
+
+pub unsafe fn safe_read32s_seg(seg: i32, addr: i32) -> OrPageFault<i32> {
+    check_segment_access(seg, addr, 4, false)?;
+    safe_read32s(addr)
+}
+
+pub unsafe fn safe_read_f32_seg(seg: i32, addr: i32) -> OrPageFault<f32> {
+    check_segment_access(seg, addr, 4, false)?;
+    safe_read_f32(addr)
+}
+
+pub unsafe fn safe_read64s_seg(seg: i32, addr: i32) -> OrPageFault<u64> {
+    check_segment_access(seg, addr, 8, false)?;
+    safe_read64s(addr)
+}
+
+pub unsafe fn safe_read128s_seg(seg: i32, addr: i32) -> OrPageFault<reg128> {
+    check_segment_access(seg, addr, 16, false)?;
+    safe_read128s(addr)
+}
+
+pub unsafe fn safe_write8_seg(seg: i32, addr: i32, value: i32) -> OrPageFault<()> {
+    check_segment_access(seg, addr, 1, true)?;
+    safe_write8(addr, value)
+}
+
+pub unsafe fn safe_write16_seg(seg: i32, addr: i32, value: i32) -> OrPageFault<()> {
+    check_segment_access(seg, addr, 2, true)?;
+    safe_write16(addr, value)
+}
+
+pub unsafe fn safe_write32_seg(seg: i32, addr: i32, value: i32) -> OrPageFault<()> {
+    check_segment_access(seg, addr, 4, true)?;
+    safe_write32(addr, value)
+}
+
+pub unsafe fn safe_write64_seg(seg: i32, addr: i32, value: u64) -> OrPageFault<()> {
+    check_segment_access(seg, addr, 8, true)?;
+    safe_write64(addr, value)
+}
+
+pub unsafe fn safe_write128_seg(seg: i32, addr: i32, value: reg128) -> OrPageFault<()> {
+    check_segment_access(seg, addr, 16, true)?;
+    safe_write128(addr, value)
+}
+
+#[inline(always)]
+pub unsafe fn safe_read_write8_seg(seg: i32, addr: i32, instruction: &dyn Fn(i32) -> i32) {
+    return_on_pagefault!(check_segment_access(seg, addr, 1, true));
+    safe_read_write8(addr, instruction)
+}
+
+#[inline(always)]
+pub unsafe fn safe_read_write16_seg(seg: i32, addr: i32, instruction: &dyn Fn(i32) -> i32) {
+    return_on_pagefault!(check_segment_access(seg, addr, 2, true));
+    safe_read_write16(addr, instruction)
+}
+
+#[inline(always)]
+pub unsafe fn safe_read_write32_seg(seg: i32, addr: i32, instruction: &dyn Fn(i32) -> i32) {
+    return_on_pagefault!(check_segment_access(seg, addr, 4, true));
+    safe_read_write32(addr, instruction)
+}
+
+pub unsafe fn readable_or_pagefault_seg(seg: i32, addr: i32, size: i32) -> OrPageFault<()> {
+    check_segment_access(seg, addr, size, false)?;
+    readable_or_pagefault(addr, size)
+}
+
+pub unsafe fn writable_or_pagefault_seg(seg: i32, addr: i32, size: i32) -> OrPageFault<()> {
+    check_segment_access(seg, addr, size, true)?;
+    writable_or_pagefault(addr, size)
+}
+
 fn get_reg8_index(index: i32) -> i32 { return index << 2 & 12 | index >> 2 & 1; }
 
 pub unsafe fn read_reg8(index: i32) -> i32 {
@@ -3890,6 +5196,21 @@ pub unsafe fn task_switch_test() -> bool {
     };
 }
 
+/// Called before waiting fpu instructions. An unmasked fpu exception from a previous instruction is
+/// delivered as #MF if cr0.ne is set, otherwise ferr# is signalled to the pic as irq13 (PC/AT
+/// compatible error reporting) and the instruction continues
+pub unsafe fn fpu_pending_exception_test() -> bool {
+    if !fpu_exception_pending() {
+        return true;
+    }
+    if *cr & CR0_NE != 0 {
+        trigger_mf();
+        return false;
+    }
+    pic::pic_set_irq(13);
+    return true;
+}
+
 pub unsafe fn set_mxcsr(new_mxcsr: i32) {
     dbg_assert!(new_mxcsr & !MXCSR_MASK == 0); // checked by caller
 
@@ -3900,10 +5221,12 @@ pub unsafe fn set_mxcsr(new_mxcsr: i32) {
         dbg_log!("Warning: Unimplemented MXCSR bit: Flush To Zero");
     }
 
+    // The rounding mode is used by conversions and round{ps,pd,ss,sd} (see sse_integer_round),
+    // but not by arithmetic instructions
     let rounding_mode = new_mxcsr >> MXCSR_RC_SHIFT & 3;
     if *mxcsr >> MXCSR_RC_SHIFT & 3 == 0 && rounding_mode != 0 {
         dbg_log!(
-            "Warning: Unimplemented MXCSR rounding mode: {}",
+            "Warning: MXCSR rounding mode only partially implemented: {}",
             rounding_mode
         );
     }
@@ -3926,6 +5249,18 @@ pub unsafe fn task_switch_test_jit(eip_offset_in_page: i32) {
     trigger_nm_jit(eip_offset_in_page);
 }
 
+#[no_mangle]
+pub unsafe fn fpu_pending_exception_test_jit(eip_offset_in_page: i32) -> bool {
+    dbg_assert!(fpu_exception_pending());
+    dbg_assert!(eip_offset_in_page >= 0 && eip_offset_in_page < 0x1000);
+    if *cr & CR0_NE != 0 {
+        trigger_mf_jit(eip_offset_in_page);
+        return true;
+    }
+    pic::pic_set_irq(13);
+    return false;
+}
+
 pub unsafe fn task_switch_test_mmx() -> bool {
     if *cr.offset(4) & CR4_OSFXSR == 0 {
         dbg_log!("Warning: Unimplemented task switch test with cr4.osfxsr=0");
@@ -4029,15 +5364,67 @@ pub unsafe fn decr_ecx_asize(is_asize_32: bool) -> i32 {
 #[no_mangle]
 pub unsafe fn set_tsc(low: u32, high: u32) {
     let new_value = low as u64 | (high as u64) << 32;
-    let current_value = read_tsc();
+    let current_value = read_tsc_from_clock();
     tsc_offset = current_value - new_value;
 }
 
+/// The current time in milliseconds, from which the tsc and the hardware timers are derived:
+/// Either the host's clock, or in icount mode, the number of executed instructions
+#[no_mangle]
+pub unsafe fn cpu_microtick() -> f64 {
+    if icount_rate == 0.0 {
+        return microtick();
+    }
+    instruction_count().wrapping_add(icount_offset) as f64 / icount_rate
+}
+
+/// The number of instructions executed since startup. Unlike instruction_counter, this doesn't
+/// wrap around. Only exact when the jit is disabled, as jitted code updates instruction_counter
+/// at the end of each block
+pub unsafe fn instruction_count() -> u64 {
+    instruction_count_total +=
+        (*instruction_counter).wrapping_sub(instruction_count_last_counter) as u64;
+    instruction_count_last_counter = *instruction_counter;
+    instruction_count_total
+}
+
+/// Enable icount mode with the given number of instructions per second (the jit must be disabled
+/// by the caller, as it compiles asynchronously)
+#[no_mangle]
+pub unsafe fn set_icount(instructions_per_second: f64) {
+    dbg_assert!(instructions_per_second >= 1000.0);
+    icount_rate = instructions_per_second / 1000.0;
+    icount_offset = instruction_count().wrapping_neg();
+}
+
+/// For saving and restoring the state, so that restored runs continue reproducibly
+#[no_mangle]
+pub unsafe fn get_icount_instructions() -> f64 {
+    instruction_count().wrapping_add(icount_offset) as f64
+}
+#[no_mangle]
+pub unsafe fn set_icount_instructions(instructions: f64) {
+    icount_offset = (instructions as u64).wrapping_sub(instruction_count());
+}
+#[no_mangle]
+pub unsafe fn get_icount_rand_state() -> u32 { icount_rand_state }
+#[no_mangle]
+pub unsafe fn set_icount_rand_state(state: u32) { icount_rand_state = state }
+
+/// The time (as returned by cpu_microtick) at which the tsc reaches the given value
+pub unsafe fn tsc_to_microtick(tsc: u64) -> f64 { tsc.wrapping_add(tsc_offset) as f64 / TSC_RATE }
+pub unsafe fn microtick_to_tsc(time: f64) -> u64 {
+    ((time * TSC_RATE).round() as u64).wrapping_sub(tsc_offset)
+}
+
+/// The tsc as seen by the guest, which is recorded or replayed
 #[no_mangle]
-pub unsafe fn read_tsc() -> u64 {
-    let value = (microtick() * TSC_RATE) as u64 - tsc_offset;
+pub unsafe fn read_tsc() -> u64 { replay::input(replay::Event::Tsc, 0, || read_tsc_from_clock()) }
+
+pub unsafe fn read_tsc_from_clock() -> u64 {
+    let value = (cpu_microtick() * TSC_RATE) as u64 - tsc_offset;
 
-    if !TSC_ENABLE_IMPRECISE_BROWSER_WORKAROUND {
+    if !TSC_ENABLE_IMPRECISE_BROWSER_WORKAROUND || icount_rate != 0.0 {
         return value;
     }
 
@@ -4174,13 +5561,13 @@ pub unsafe fn update_eflags(new_flags: i32) {
             }
         }
     }
+    let old_flags = *flags;
     *flags = (new_flags ^ (*flags ^ new_flags) & dont_update) & clear | FLAGS_DEFAULT;
     *flags_changed = 0;
 
-    if *flags & FLAG_TRAP != 0 {
-        dbg_log!("Not supported: trap flag");
+    if (old_flags ^ *flags) & FLAG_AC != 0 {
+        update_state_flags();
     }
-    *flags &= !FLAG_TRAP;
 }
 
 #[no_mangle]
@@ -4227,6 +5614,18 @@ pub unsafe fn trigger_np(code: i32) {
     call_interrupt_vector(CPU_EXCEPTION_NP, false, Some(code));
 }
 
+#[inline(never)]
+pub unsafe fn trigger_ac() {
+    dbg_log!("#ac");
+    *instruction_pointer = *previous_ip;
+    if DEBUG {
+        if cpu_exception_hook(CPU_EXCEPTION_AC) {
+            return;
+        }
+    }
+    call_interrupt_vector(CPU_EXCEPTION_AC, false, Some(0));
+}
+
 #[inline(never)]
 pub unsafe fn trigger_ss(code: i32) {
     dbg_log!("#ss");
@@ -4239,11 +5638,44 @@ pub unsafe fn trigger_ss(code: i32) {
     call_interrupt_vector(CPU_EXCEPTION_SS, false, Some(code));
 }
 
+#[inline(never)]
+pub unsafe fn trigger_ts(code: i32) {
+    dbg_log!("#ts");
+    *instruction_pointer = *previous_ip;
+    if DEBUG {
+        if cpu_exception_hook(CPU_EXCEPTION_TS) {
+            return;
+        }
+    }
+    call_interrupt_vector(CPU_EXCEPTION_TS, false, Some(code));
+}
+
 #[no_mangle]
-pub unsafe fn store_current_tsc() { *current_tsc = read_tsc(); }
+pub unsafe fn store_current_tsc() { *current_tsc = read_tsc_from_clock(); }
+
+pub unsafe fn io_port_read8(port: i32) -> i32 {
+    replay::input(replay::Event::PortRead8, port as u32, || {
+        ext::io_port_read8(port) as u64
+    }) as i32
+}
+pub unsafe fn io_port_read16(port: i32) -> i32 {
+    replay::input(replay::Event::PortRead16, port as u32, || {
+        ext::io_port_read16(port) as u64
+    }) as i32
+}
+pub unsafe fn io_port_read32(port: i32) -> i32 {
+    replay::input(replay::Event::PortRead32, port as u32, || {
+        ext::io_port_read32(port) as u32 as u64
+    }) as i32
+}
 
 #[no_mangle]
 pub unsafe fn handle_irqs() {
+    if replay::replaying() {
+        // the devices' interrupts are ignored, the ones of the log are delivered instead
+        replay::handle_irqs();
+        return;
+    }
     if *flags & FLAG_INTERRUPT != 0 {
         if let Some(irq) = pic::pic_acknowledge_irq() {
             pic_call_irq(irq)
@@ -4255,15 +5687,94 @@ pub unsafe fn handle_irqs() {
             }
         }
     }
+    else if in_mwait
+        && mwait_break_on_masked_interrupts
+        && (pic::pic_has_pending_irq() || *acpi_enabled && apic_has_pending_irq())
+    {
+        // the interrupt stays pending until the guest sets IF
+        replay::record_mwait_wake();
+        mwait_wake();
+    }
 }
 
-unsafe fn pic_call_irq(interrupt_nr: u8) {
+pub unsafe fn pic_call_irq(interrupt_nr: u8) {
+    replay::record_irq(interrupt_nr);
     *previous_ip = *instruction_pointer; // XXX: What if called after instruction (port IO)
     if *in_hlt {
         stop_idling();
         *in_hlt = false;
+        mwait_end();
+    }
+    call_external_interrupt_vector(interrupt_nr as i32);
+}
+
+pub fn is_monitored_page(page: Page) -> bool {
+    unsafe { monitor_line.map_or(false, |line| Page::page_of(line) == page) }
+}
+
+pub unsafe fn monitor_arm(phys_addr: u32) {
+    monitor_disarm();
+    monitor_line = Some(phys_addr & !(MONITOR_LINE_SIZE - 1));
+    if !in_mapped_range(phys_addr) {
+        tlb_set_has_code(Page::page_of(phys_addr), true);
+    }
+}
+
+unsafe fn monitor_disarm() {
+    if let Some(line) = monitor_line.take() {
+        let page = Page::page_of(line);
+        if !in_mapped_range(line) && !jit::jit_page_has_code(page) {
+            tlb_set_has_code(page, false);
+        }
+    }
+}
+
+/// Called by jit_dirty_page. Writes anywhere in the page of the monitored line trigger the
+/// monitor, which may end mwait spuriously (permitted by the architecture)
+pub unsafe fn monitor_page_written(page: Page) {
+    if is_monitored_page(page) {
+        monitor_disarm();
+        if in_mwait {
+            mwait_wake();
+        }
+    }
+}
+
+pub unsafe fn mwait_wake() {
+    stop_idling();
+    *in_hlt = false;
+    mwait_end();
+}
+
+unsafe fn mwait_end() {
+    if in_mwait {
+        in_mwait = false;
+        monitor_disarm();
+    }
+}
+
+/// For saving and restoring the state: Field 0 is the monitored line (-1 if not armed), 1 is
+/// in_mwait and 2 is mwait_break_on_masked_interrupts
+#[no_mangle]
+pub unsafe fn get_mwait_state(field: u32) -> i32 {
+    match field {
+        0 => monitor_line.map_or(-1, |line| line as i32),
+        1 => in_mwait as i32,
+        2 => mwait_break_on_masked_interrupts as i32,
+        _ => {
+            dbg_assert!(false);
+            0
+        },
     }
-    call_interrupt_vector(interrupt_nr as i32, false, None);
+}
+#[no_mangle]
+pub unsafe fn set_mwait_state(line: i32, waiting: bool, break_on_masked_interrupts: bool) {
+    monitor_disarm();
+    if line != -1 {
+        monitor_arm(line as u32);
+    }
+    in_mwait = waiting;
+    mwait_break_on_masked_interrupts = break_on_masked_interrupts;
 }
 
 #[no_mangle]
@@ -4289,9 +5800,10 @@ pub unsafe fn check_page_switch(block_addr: u32, next_block_addr: u32) {
 pub unsafe fn reset_cpu() {
     for i in 0..8 {
         *segment_is_null.offset(i) = false;
-        *segment_limits.offset(i) = 0;
+        *segment_limits.offset(i) = 0xFFFF;
         *segment_offsets.offset(i) = 0;
         *segment_access_bytes.offset(i) = 0x80 | (0 << 5) | 0x10 | 0x02; // P dpl0 S RW
+        *segment_is_big.offset(i) = false;
 
         *reg32.offset(i) = 0;
 
@@ -4318,14 +5830,15 @@ pub unsafe fn reset_cpu() {
     *fpu_dp = 0;
     *fpu_dp_selector = 0;
 
-    *mxcsr = 0x1F80;
+    *mxcsr = MXCSR_DEFAULT;
+    *xcr0 = XSTATE_X87;
 
     full_clear_tlb();
 
     *protected_mode = false;
 
     // http://www.sandpile.org/x86/initial.htm
-    *idtr_size = 0;
+    *idtr_size = 0xFFFF;
     *idtr_offset = 0;
 
     *gdtr_size = 0;
@@ -4336,8 +5849,11 @@ pub unsafe fn reset_cpu() {
     *cr.offset(2) = 0;
     *cr.offset(3) = 0;
     *cr.offset(4) = 0;
-    *dreg.offset(6) = 0xFFFF0FF0u32 as i32;
-    *dreg.offset(7) = 0x400;
+    *dreg.offset(6) = DR6_RESERVED;
+    *dreg.offset(7) = DR7_RESERVED;
+    debug_trap_pending = 0;
+    debug_traps_inhibited = false;
+    debug_force_interpreter = false;
     *cpl = 0;
 
     *is_32 = false;
@@ -4346,14 +5862,24 @@ pub unsafe fn reset_cpu() {
 
     *last_virt_eip = -1;
 
+    instruction_count();
     *instruction_counter = 0;
+    instruction_count_last_counter = 0;
     *previous_ip = 0;
     *in_hlt = false;
+    in_mwait = false;
+    monitor_line = None;
+    shutdown_reason = None;
+    shutdown_pending = false;
 
     *sysenter_cs = 0;
+    *star = 0;
     *sysenter_esp = 0;
     *sysenter_eip = 0;
 
+    *efer = 0;
+    *tsc_aux = 0;
+
     *flags = FLAGS_DEFAULT;
     *flags_changed = 0;
     *last_result = 0;
@@ -4361,6 +5887,7 @@ pub unsafe fn reset_cpu() {
     *last_op_size = 0;
 
     set_tsc(0, 0);
+    icount_rand_state = ICOUNT_RAND_SEED;
 
     *instruction_pointer = 0xFFFF0;
     switch_cs_real_mode(0xF000);
@@ -4375,3 +5902,9 @@ pub unsafe fn reset_cpu() {
 
 #[no_mangle]
 pub unsafe fn set_cpuid_level(level: u32) { cpuid_level = level }
+
+#[no_mangle]
+pub unsafe fn set_segment_checks(enabled: bool) {
+    segment_checks = enabled;
+    update_state_flags();
+}
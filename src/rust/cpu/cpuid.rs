@@ -0,0 +1,494 @@
+//! The cpuid model: Either the built-in model, or a table of leaves installed by the embedder,
+//! optionally starting from one of the presets below. Instructions whose feature is masked off
+//! in the model raise #ud (see the cpuid fields in gen/x86_table.js), so that old operating
+//! systems see a consistent cpu.
+
+#![allow(non_upper_case_globals)]
+
+use cpu::cpu::{
+    cpuid_level, CR4_OSFXSR, CR4_OSXMMEXCPT, CR4_OSXSAVE, CR4_PAE, CR4_PGE, CR4_PSE, CR4_SMAP,
+    CR4_SMEP, CR4_TSD, TSC_RATE,
+};
+use cpu::global_pointers::acpi_enabled;
+use cpu::misc_instr::{XSAVE_AREA_SIZE, XSTATE_SSE, XSTATE_X87};
+use jit;
+
+pub const FEATURE_TSC: u32 = 1 << 0;
+pub const FEATURE_MSR: u32 = 1 << 1;
+pub const FEATURE_PSE: u32 = 1 << 2;
+pub const FEATURE_PAE: u32 = 1 << 3;
+pub const FEATURE_CX8: u32 = 1 << 4;
+pub const FEATURE_SEP: u32 = 1 << 5;
+pub const FEATURE_PGE: u32 = 1 << 6;
+pub const FEATURE_CMOV: u32 = 1 << 7;
+pub const FEATURE_CLFSH: u32 = 1 << 8;
+pub const FEATURE_MMX: u32 = 1 << 9;
+pub const FEATURE_FXSR: u32 = 1 << 10;
+pub const FEATURE_SSE: u32 = 1 << 11;
+pub const FEATURE_SSE2: u32 = 1 << 12;
+pub const FEATURE_SSE3: u32 = 1 << 13;
+pub const FEATURE_SSSE3: u32 = 1 << 14;
+pub const FEATURE_SSE4_1: u32 = 1 << 15;
+pub const FEATURE_SSE4_2: u32 = 1 << 16;
+pub const FEATURE_POPCNT: u32 = 1 << 17;
+pub const FEATURE_RDRAND: u32 = 1 << 18;
+pub const FEATURE_SMEP: u32 = 1 << 19;
+pub const FEATURE_SMAP: u32 = 1 << 20;
+pub const FEATURE_NX: u32 = 1 << 21;
+pub const FEATURE_MOVBE: u32 = 1 << 22;
+pub const FEATURE_LZCNT: u32 = 1 << 23;
+pub const FEATURE_BMI1: u32 = 1 << 24;
+pub const FEATURE_BMI2: u32 = 1 << 25;
+pub const FEATURE_MONITOR: u32 = 1 << 26;
+pub const FEATURE_RDTSCP: u32 = 1 << 27;
+pub const FEATURE_XSAVE: u32 = 1 << 28;
+pub const FEATURE_XSAVEOPT: u32 = 1 << 29;
+pub const FEATURE_XSAVEC: u32 = 1 << 30;
+pub const FEATURE_SYSCALL: u32 = 1 << 31;
+
+/// All of the above, except for syscall, which intel cpus only support in long mode
+const FEATURES_BUILTIN: u32 = !FEATURE_SYSCALL;
+
+const EAX: usize = 0;
+const EBX: usize = 1;
+const ECX: usize = 2;
+const EDX: usize = 3;
+
+// feature, leaf, subleaf, register, bit
+const FEATURE_BITS: [(u32, u32, u32, usize, u32); 32] = [
+    (FEATURE_PSE, 1, 0, EDX, 3),
+    (FEATURE_TSC, 1, 0, EDX, 4),
+    (FEATURE_MSR, 1, 0, EDX, 5),
+    (FEATURE_PAE, 1, 0, EDX, 6),
+    (FEATURE_CX8, 1, 0, EDX, 8),
+    (FEATURE_SEP, 1, 0, EDX, 11),
+    (FEATURE_PGE, 1, 0, EDX, 13),
+    (FEATURE_CMOV, 1, 0, EDX, 15),
+    (FEATURE_CLFSH, 1, 0, EDX, 19),
+    (FEATURE_MMX, 1, 0, EDX, 23),
+    (FEATURE_FXSR, 1, 0, EDX, 24),
+    (FEATURE_SSE, 1, 0, EDX, 25),
+    (FEATURE_SSE2, 1, 0, EDX, 26),
+    (FEATURE_SSE3, 1, 0, ECX, 0),
+    (FEATURE_MONITOR, 1, 0, ECX, 3),
+    (FEATURE_SSSE3, 1, 0, ECX, 9),
+    (FEATURE_SSE4_1, 1, 0, ECX, 19),
+    (FEATURE_SSE4_2, 1, 0, ECX, 20),
+    (FEATURE_MOVBE, 1, 0, ECX, 22),
+    (FEATURE_POPCNT, 1, 0, ECX, 23),
+    (FEATURE_XSAVE, 1, 0, ECX, 26),
+    (FEATURE_RDRAND, 1, 0, ECX, 30),
+    (FEATURE_BMI1, 7, 0, EBX, 3),
+    (FEATURE_SMEP, 7, 0, EBX, 7),
+    (FEATURE_BMI2, 7, 0, EBX, 8),
+    (FEATURE_SMAP, 7, 0, EBX, 20),
+    (FEATURE_XSAVEOPT, 0xD, 1, EAX, 0),
+    (FEATURE_XSAVEC, 0xD, 1, EAX, 1),
+    (FEATURE_LZCNT, 0x80000001, 0, ECX, 5),
+    (FEATURE_SYSCALL, 0x80000001, 0, EDX, 11),
+    (FEATURE_NX, 0x80000001, 0, EDX, 20),
+    (FEATURE_RDTSCP, 0x80000001, 0, EDX, 27),
+];
+
+pub const CPU_MODEL_BUILTIN: u32 = 0;
+pub const CPU_MODEL_486: u32 = 1;
+pub const CPU_MODEL_PENTIUM: u32 = 2;
+pub const CPU_MODEL_PENTIUM3: u32 = 3;
+pub const CPU_MODEL_PENTIUM4: u32 = 4;
+pub const CPU_MODEL_ATHLON: u32 = 5;
+
+struct Leaf {
+    leaf: u32,
+    /// None: The same values for all subleaves
+    subleaf: Option<u32>,
+    regs: [i32; 4],
+}
+
+/// The installed model, None for the built-in model
+static mut table: Option<Vec<Leaf>> = None;
+
+/// Features of the installed model
+static mut features: u32 = FEATURES_BUILTIN;
+
+#[inline(always)]
+pub fn has_feature(feature: u32) -> bool { unsafe { features & feature != 0 } }
+
+/// cr4 bits that can't be set, because their feature is masked off
+pub fn cr4_unsupported_bits() -> i32 {
+    let mut bits = 0;
+    for &(feature, bit) in [
+        (FEATURE_TSC, CR4_TSD),
+        (FEATURE_PSE, CR4_PSE),
+        (FEATURE_PAE, CR4_PAE),
+        (FEATURE_PGE, CR4_PGE),
+        (FEATURE_FXSR, CR4_OSFXSR),
+        (FEATURE_SSE, CR4_OSXMMEXCPT),
+        (FEATURE_SMEP, CR4_SMEP),
+        (FEATURE_SMAP, CR4_SMAP),
+        (FEATURE_XSAVE, CR4_OSXSAVE),
+    ]
+    .iter()
+    {
+        if !has_feature(feature) {
+            bits |= bit;
+        }
+    }
+    bits
+}
+
+/// eax, ebx, ecx and edx returned by cpuid for the given leaf and subleaf
+pub unsafe fn lookup(leaf: u32, subleaf: u32) -> [i32; 4] {
+    match &table {
+        None => builtin_leaf(leaf, subleaf),
+        Some(leaves) => find(leaves, leaf, subleaf).map_or([0; 4], |l| l.regs),
+    }
+}
+
+fn find(leaves: &[Leaf], leaf: u32, subleaf: u32) -> Option<&Leaf> {
+    leaves
+        .iter()
+        .find(|l| l.leaf == leaf && l.subleaf == Some(subleaf))
+        .or_else(|| leaves.iter().find(|l| l.leaf == leaf && l.subleaf.is_none()))
+}
+
+fn set_leaf(leaves: &mut Vec<Leaf>, leaf: u32, subleaf: Option<u32>, regs: [i32; 4]) {
+    if subleaf.is_none() {
+        leaves.retain(|l| l.leaf != leaf);
+    }
+    match leaves.iter_mut().find(|l| l.leaf == leaf && l.subleaf == subleaf) {
+        Some(l) => l.regs = regs,
+        None => leaves.push(Leaf {
+            leaf,
+            subleaf,
+            regs,
+        }),
+    }
+}
+
+/// Select the built-in model or one of the presets
+#[no_mangle]
+pub unsafe fn cpuid_set_model(model: u32) {
+    table = match model {
+        CPU_MODEL_BUILTIN => None,
+        CPU_MODEL_486 | CPU_MODEL_PENTIUM | CPU_MODEL_PENTIUM3 | CPU_MODEL_PENTIUM4
+        | CPU_MODEL_ATHLON => Some(preset(model)),
+        _ => {
+            dbg_log!("cpuid: unknown model {}", model);
+            dbg_assert!(false);
+            None
+        },
+    };
+    model_changed();
+}
+
+/// Install a leaf, replacing the previous values. A subleaf of -1 applies to all subleaves. When
+/// the built-in model is selected, it's first copied into a table
+#[no_mangle]
+pub unsafe fn cpuid_set_leaf(leaf: u32, subleaf: i32, eax: i32, ebx: i32, ecx: i32, edx: i32) {
+    if table.is_none() {
+        table = Some(builtin_table());
+    }
+    let subleaf = if subleaf == -1 { None } else { Some(subleaf as u32) };
+    set_leaf(table.as_mut().unwrap(), leaf, subleaf, [eax, ebx, ecx, edx]);
+    model_changed();
+}
+
+/// Register 0 to 3 (eax, ebx, ecx, edx) of the given leaf, for modifying the installed model
+#[no_mangle]
+pub unsafe fn cpuid_get_leaf(leaf: u32, subleaf: u32, register: u32) -> i32 {
+    dbg_assert!(register < 4);
+    lookup(leaf, subleaf)[register as usize & 3]
+}
+
+unsafe fn model_changed() {
+    features = 0;
+    for &(feature, leaf, subleaf, register, bit) in FEATURE_BITS.iter() {
+        if lookup(leaf, subleaf)[register] & 1 << bit != 0 {
+            features |= feature;
+        }
+    }
+
+    // #ud for masked-off features is generated at compile time
+    jit::jit_clear_cache(jit::get_jit_state());
+}
+
+fn vendor_leaf(max_leaf: u32, vendor: &[u8; 12]) -> [i32; 4] {
+    let dword =
+        |i: usize| i32::from_le_bytes([vendor[i], vendor[i + 1], vendor[i + 2], vendor[i + 3]]);
+    [max_leaf as i32, dword(0), dword(8), dword(4)]
+}
+
+/// Leaves 0x80000002 to 0x80000004
+fn brand_string_leaves(brand: &str) -> [[i32; 4]; 3] {
+    let mut bytes = [0u8; 48];
+    dbg_assert!(brand.len() < 48);
+    bytes[..brand.len()].copy_from_slice(brand.as_bytes());
+    let mut leaves = [[0; 4]; 3];
+    for (i, chunk) in bytes.chunks(4).enumerate() {
+        leaves[i / 4][i % 4] = i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    leaves
+}
+
+fn family_model_stepping(family: u32, model: u32, stepping: u32) -> i32 {
+    (family << 8 | model << 4 | stepping) as i32
+}
+
+unsafe fn preset(model: u32) -> Vec<Leaf> {
+    let mut leaves = Vec::new();
+    let apic = if *acpi_enabled { 1 << 9 } else { 0 };
+
+    match model {
+        CPU_MODEL_486 => {
+            // i486DX4
+            set_leaf(&mut leaves, 0, None, vendor_leaf(1, b"GenuineIntel"));
+            let edx = 1 << 0; // fpu
+            set_leaf(&mut leaves, 1, None, [family_model_stepping(4, 8, 0), 0, 0, edx]);
+        },
+        CPU_MODEL_PENTIUM => {
+            // P54C
+            set_leaf(&mut leaves, 0, None, vendor_leaf(1, b"GenuineIntel"));
+            let edx = 1 << 0 | 1 << 2 | 1 << 3 | 1 << 4 | 1 << 5 | // fpu, de, pse, tsc, msr
+                1 << 8 | apic; // cx8
+            set_leaf(&mut leaves, 1, None, [family_model_stepping(5, 2, 0xC), 0, 0, edx]);
+        },
+        CPU_MODEL_PENTIUM3 => {
+            // Katmai
+            set_leaf(&mut leaves, 0, None, vendor_leaf(2, b"GenuineIntel"));
+            let edx = 1 << 0 | 1 << 2 | 1 << 3 | 1 << 4 | 1 << 5 | 1 << 6 | // fpu, de, pse, tsc, msr, pae
+                1 << 8 | 1 << 11 | 1 << 13 | 1 << 15 | // cx8, sep, pge, cmov
+                1 << 23 | 1 << 24 | 1 << 25 | apic; // mmx, fxsr, sse
+            set_leaf(&mut leaves, 1, None, [family_model_stepping(6, 7, 3), 0, 0, edx]);
+            // cache and tlb descriptors
+            set_leaf(&mut leaves, 2, None, [0x03020101, 0, 0, 0x0C040843]);
+        },
+        CPU_MODEL_PENTIUM4 => {
+            // Northwood
+            set_leaf(&mut leaves, 0, None, vendor_leaf(2, b"GenuineIntel"));
+            let ebx = 1 << 16 | 8 << 8; // cpu count, clflush size
+            let edx = 1 << 0 | 1 << 2 | 1 << 3 | 1 << 4 | 1 << 5 | 1 << 6 | // fpu, de, pse, tsc, msr, pae
+                1 << 8 | 1 << 11 | 1 << 13 | 1 << 15 | // cx8, sep, pge, cmov
+                1 << 19 | // clfsh
+                1 << 23 | 1 << 24 | 1 << 25 | 1 << 26 | apic; // mmx, fxsr, sse, sse2
+            set_leaf(&mut leaves, 1, None, [family_model_stepping(15, 2, 9), ebx, 0, edx]);
+            // cache and tlb descriptors
+            set_leaf(&mut leaves, 2, None, [0x665B5001, 0, 0, 0x007A7040]);
+            set_leaf(&mut leaves, 0x80000000, None, [0x80000004u32 as i32, 0, 0, 0]);
+            let brand = brand_string_leaves("Intel(R) Pentium(R) 4 CPU 2.40GHz");
+            for (i, &regs) in brand.iter().enumerate() {
+                set_leaf(&mut leaves, 0x80000002 + i as u32, None, regs);
+            }
+        },
+        CPU_MODEL_ATHLON => {
+            // Athlon XP (Palomino), without 3dnow. Supports syscall in legacy mode
+            let vendor = b"AuthenticAMD";
+            set_leaf(&mut leaves, 0, None, vendor_leaf(1, vendor));
+            let signature = family_model_stepping(6, 6, 2);
+            let edx = 1 << 0 | 1 << 2 | 1 << 3 | 1 << 4 | 1 << 5 | 1 << 6 | // fpu, de, pse, tsc, msr, pae
+                1 << 8 | 1 << 11 | 1 << 13 | 1 << 15 | // cx8, sep, pge, cmov
+                1 << 23 | 1 << 24 | 1 << 25 | apic; // mmx, fxsr, sse
+            set_leaf(&mut leaves, 1, None, [signature, 0, 0, edx]);
+            set_leaf(&mut leaves, 0x80000000, None, vendor_leaf(0x80000004, vendor));
+            // leaf 1 is repeated, except for sse, and bit 11 means syscall instead of sep here
+            let edx_extended = edx & !(1 << 25);
+            set_leaf(&mut leaves, 0x80000001, None, [signature, 0, 0, edx_extended]);
+            let brand = brand_string_leaves("AMD Athlon(tm) XP");
+            for (i, &regs) in brand.iter().enumerate() {
+                set_leaf(&mut leaves, 0x80000002 + i as u32, None, regs);
+            }
+        },
+        _ => {
+            dbg_assert!(false);
+        },
+    }
+
+    leaves
+}
+
+/// The built-in model as a table, as the starting point for modifications by the embedder
+unsafe fn builtin_table() -> Vec<Leaf> {
+    let mut leaves = Vec::new();
+    let leaves_without_subleaves =
+        [0, 1, 2, 5, 0x15, 0x16, 0x40000000, 0x80000000, 0x80000001, 0x80000007];
+    for &leaf in leaves_without_subleaves.iter() {
+        set_leaf(&mut leaves, leaf, None, builtin_leaf(leaf, 0));
+    }
+    for subleaf in 0..3 {
+        set_leaf(&mut leaves, 4, Some(subleaf), builtin_leaf(4, subleaf));
+    }
+    set_leaf(&mut leaves, 7, Some(0), builtin_leaf(7, 0));
+    for subleaf in 0..2 {
+        set_leaf(&mut leaves, 0xD, Some(subleaf), builtin_leaf(0xD, subleaf));
+    }
+    leaves
+}
+
+/// The built-in model: A family 6 processor with all features implemented by the emulator
+unsafe fn builtin_leaf(level: u32, subleaf: u32) -> [i32; 4] {
+    // http://lxr.linux.no/linux+%2a/arch/x86/include/asm/cpufeature.h
+    // http://www.sandpile.org/x86/cpuid.htm
+    let mut eax = 0;
+    let mut ecx = 0;
+    let mut edx = 0;
+    let mut ebx = 0;
+
+    match level {
+        0 => {
+            // maximum supported level (default 0x16, overwritten to 2 as a workaround for Windows NT)
+            eax = cpuid_level as i32;
+
+            ebx = 0x756E6547 | 0; // Genu
+            edx = 0x49656E69 | 0; // ineI
+            ecx = 0x6C65746E | 0; // ntel
+        },
+
+        1 => {
+            // pentium
+            eax = 3 | 6 << 4 | 15 << 8;
+            ebx = 1 << 16 | 8 << 8; // cpu count, clflush size
+            ecx = 1 << 0 | 1 << 3 | 1 << 9 | // sse3, monitor, ssse3
+                1 << 19 | 1 << 20 | // sse4.1, sse4.2
+                1 << 22 | 1 << 23 | 1 << 26 | 1 << 30; // movbe, popcnt, xsave, rdrand
+            let vme = 0 << 1;
+            if ::config::VMWARE_HYPERVISOR_PORT {
+                ecx |= 1 << 31
+            }; // hypervisor
+            edx = (if true /* have fpu */ { 1 } else {  0 }) |      // fpu
+                    vme | 1 << 3 | 1 << 4 | 1 << 5 | 1 << 6 |  // vme, pse, tsc, msr, pae
+                    1 << 8 | 1 << 11 | 1 << 13 | 1 << 15 | // cx8, sep, pge, cmov
+                    1 << 19 | // clfsh
+                    1 << 23 | 1 << 24 | 1 << 25 | 1 << 26; // mmx, fxsr, sse1, sse2
+
+            if *acpi_enabled
+            //&& this.apic_enabled[0])
+            {
+                edx |= 1 << 9; // apic
+                ecx |= 1 << 24; // tsc deadline
+            }
+        },
+
+        2 => {
+            // Taken from http://siyobik.info.gf/main/reference/instruction/CPUID
+            eax = 0x665B5001;
+            ebx = 0;
+            ecx = 0;
+            edx = 0x007A7000;
+        },
+
+        4 => {
+            // from my local machine
+            match subleaf {
+                0 => {
+                    eax = 0x00000121;
+                    ebx = 0x01c0003f;
+                    ecx = 0x0000003f;
+                    edx = 0x00000001;
+                },
+                1 => {
+                    eax = 0x00000122;
+                    ebx = 0x01c0003f;
+                    ecx = 0x0000003f;
+                    edx = 0x00000001;
+                },
+                2 => {
+                    eax = 0x00000143;
+                    ebx = 0x05c0003f;
+                    ecx = 0x00000fff;
+                    edx = 0x00000001;
+                },
+                _ => {},
+            }
+        },
+
+        5 => {
+            // from my local machine
+            eax = 0x40;
+            ebx = 0x40;
+            ecx = 3;
+            edx = 0x00142120;
+        },
+
+        7 => {
+            if subleaf == 0 {
+                eax = 0; // maximum supported sub-level
+                ebx = 1 << 3 | 1 << 7 | 1 << 8 | // bmi1, smep, bmi2
+                    1 << 9 | 1 << 20; // enhanced REP MOVSB/STOSB, smap
+                ecx = 0;
+                edx = 0;
+            }
+        },
+
+        0xD => {
+            // xsave: Only the x87 and sse components, which are in the legacy region, so the size of
+            // the area is the same for all values of xcr0 and in both formats
+            match subleaf {
+                0 => {
+                    eax = XSTATE_X87 | XSTATE_SSE; // supported bits of xcr0
+                    ebx = XSAVE_AREA_SIZE; // size for the components enabled in xcr0
+                    ecx = XSAVE_AREA_SIZE; // size for all supported components
+                },
+                1 => {
+                    eax = 1 << 0 | 1 << 1; // xsaveopt, xsavec
+                    ebx = XSAVE_AREA_SIZE; // compacted size for the components enabled in xcr0
+                },
+                _ => {},
+            }
+        },
+
+        0x80000000 => {
+            // maximum supported extended level
+            eax = 0x80000007u32 as i32;
+            // other registers are reserved
+        },
+
+        0x80000001 => {
+            ecx = 1 << 5; // lzcnt
+            edx = 1 << 20 | 1 << 27; // nx, rdtscp
+        },
+
+        0x80000002..=0x80000006 => {
+            // no brand string and cache information
+        },
+
+        0x80000007 => {
+            // The tsc is derived from the host's clock, so its rate never changes
+            edx = 1 << 8; // invariant tsc
+        },
+
+        0x40000000 => {
+            // hypervisor
+            if ::config::VMWARE_HYPERVISOR_PORT {
+                // h("Ware".split("").reduce((a, c, i) => a | c.charCodeAt(0) << i * 8, 0))
+                ebx = 0x61774D56 | 0; // VMwa
+                ecx = 0x4D566572 | 0; // reVM
+                edx = 0x65726177 | 0; // ware
+            }
+        },
+
+        0x15 => {
+            eax = 1; // denominator
+            ebx = 1; // numerator
+            ecx = (TSC_RATE * 1000.0) as u32 as i32; // core crystal clock frequency in Hz
+            dbg_assert!(ecx > 0);
+            //  (TSC frequency = core crystal clock frequency * EBX/EAX)
+        },
+
+        0x16 => {
+            eax = (TSC_RATE / 1000.0).floor() as u32 as i32; // core base frequency in MHz
+            ebx = (TSC_RATE / 1000.0).floor() as u32 as i32; // core maximum frequency in MHz
+            ecx = 10; // bus (reference) frequency in MHz
+
+            // 16-bit values
+            dbg_assert!(eax < 0x10000);
+            dbg_assert!(ebx < 0x10000);
+            dbg_assert!(ecx < 0x10000);
+        },
+
+        x => {
+            dbg_log!("cpuid: unimplemented eax: {:x}", x);
+        },
+    }
+
+
+    [eax, ebx, ecx, edx]
+}
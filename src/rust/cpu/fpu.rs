@@ -20,6 +20,9 @@ const FPU_EX_U: u16 = 1 << 4; // underflow
 #[allow(dead_code)]
 const FPU_EX_P: u16 = 1 << 5; // precision
 const FPU_EX_SF: u16 = 1 << 6;
+const FPU_EX_ALL: u16 = 0x3F;
+const FPU_ES: u16 = 1 << 7; // error summary
+const FPU_B: u16 = 1 << 15; // busy, mirrors es
 
 pub fn fpu_write_st(index: i32, value: F80) {
     dbg_assert!(index >= 0 && index < 8);
@@ -39,19 +42,18 @@ pub unsafe fn fpu_get_st0() -> F80 {
         return *fpu_st.offset(*fpu_stack_ptr as isize);
     };
 }
-pub unsafe fn fpu_stack_fault() {
-    // TODO: Interrupt
-    *fpu_status_word |= FPU_EX_SF | FPU_EX_I;
-}
+// Unmasked exceptions are reported by the next waiting fpu instruction, see
+// fpu_pending_exception_test
+pub unsafe fn fpu_stack_fault() { *fpu_status_word |= FPU_EX_SF | FPU_EX_I; }
 
-pub unsafe fn fpu_zero_fault() {
-    // TODO: Interrupt
-    *fpu_status_word |= FPU_EX_Z;
-}
+pub unsafe fn fpu_zero_fault() { *fpu_status_word |= FPU_EX_Z; }
+
+pub unsafe fn fpu_underflow_fault() { *fpu_status_word |= FPU_EX_U; }
 
-pub unsafe fn fpu_underflow_fault() {
-    // TODO: Interrupt
-    *fpu_status_word |= FPU_EX_U;
+/// Whether an exception flag is set that isn't masked in the control word. Also reflected by the
+/// es and b bits of the status word, which are derived from it instead of being stored
+pub unsafe fn fpu_exception_pending() -> bool {
+    *fpu_status_word & !*fpu_control_word & FPU_EX_ALL != 0
 }
 
 pub unsafe fn fpu_sti_empty(mut i: i32) -> bool {
@@ -120,34 +122,34 @@ pub unsafe fn i32_to_f80(v: i32) -> F80 { F80::of_i32(v) }
 pub unsafe fn i64_to_f80_jit(dst: *mut F80, v: i64) { *dst = i64_to_f80(v) }
 pub unsafe fn i64_to_f80(v: i64) -> F80 { F80::of_i64(v) }
 
-pub unsafe fn fpu_load_i16(addr: i32) -> OrPageFault<F80> {
-    let v = safe_read16(addr)? as i16 as i32;
+pub unsafe fn fpu_load_i16(seg: i32, addr: i32) -> OrPageFault<F80> {
+    let v = safe_read16_seg(seg, addr)? as i16 as i32;
     Ok(F80::of_i32(v))
 }
-pub unsafe fn fpu_load_i32(addr: i32) -> OrPageFault<F80> {
-    let v = safe_read32s(addr)?;
+pub unsafe fn fpu_load_i32(seg: i32, addr: i32) -> OrPageFault<F80> {
+    let v = safe_read32s_seg(seg, addr)?;
     Ok(F80::of_i32(v))
 }
-pub unsafe fn fpu_load_i64(addr: i32) -> OrPageFault<F80> {
-    let v = safe_read64s(addr)? as i64;
+pub unsafe fn fpu_load_i64(seg: i32, addr: i32) -> OrPageFault<F80> {
+    let v = safe_read64s_seg(seg, addr)? as i64;
     Ok(F80::of_i64(v))
 }
 
-pub unsafe fn fpu_load_m32(addr: i32) -> OrPageFault<F80> {
+pub unsafe fn fpu_load_m32(seg: i32, addr: i32) -> OrPageFault<F80> {
     F80::clear_exception_flags();
-    let v = F80::of_f32(safe_read32s(addr)?);
+    let v = F80::of_f32(safe_read32s_seg(seg, addr)?);
     *fpu_status_word |= F80::get_exception_flags() as u16;
     Ok(v)
 }
-pub unsafe fn fpu_load_m64(addr: i32) -> OrPageFault<F80> {
+pub unsafe fn fpu_load_m64(seg: i32, addr: i32) -> OrPageFault<F80> {
     F80::clear_exception_flags();
-    let v = F80::of_f64(safe_read64s(addr)?);
+    let v = F80::of_f64(safe_read64s_seg(seg, addr)?);
     *fpu_status_word |= F80::get_exception_flags() as u16;
     Ok(v)
 }
-pub unsafe fn fpu_load_m80(addr: i32) -> OrPageFault<F80> {
-    let mantissa = safe_read64s(addr)?;
-    let sign_exponent = safe_read16(addr + 8)? as u16;
+pub unsafe fn fpu_load_m80(seg: i32, addr: i32) -> OrPageFault<F80> {
+    let mantissa = safe_read64s_seg(seg, addr)?;
+    let sign_exponent = safe_read16_seg(seg, addr + 8)? as u16;
     // TODO: Canonical form
     Ok(F80 {
         mantissa,
@@ -158,7 +160,11 @@ pub unsafe fn fpu_load_m80(addr: i32) -> OrPageFault<F80> {
 #[no_mangle]
 pub unsafe fn fpu_load_status_word() -> u16 {
     dbg_assert!(*fpu_stack_ptr < 8);
-    return *fpu_status_word & !(7 << 11) | (*fpu_stack_ptr as u16) << 11;
+    let mut sw = *fpu_status_word & !(7 << 11) | (*fpu_stack_ptr as u16) << 11;
+    if fpu_exception_pending() {
+        sw |= FPU_ES | FPU_B;
+    }
+    return sw;
 }
 #[no_mangle]
 pub unsafe fn fpu_fadd(target_index: i32, val: F80) {
@@ -249,9 +255,15 @@ pub unsafe fn fpu_fdivr(target_index: i32, val: F80) {
 #[no_mangle]
 pub unsafe fn fpu_ffree(r: i32) { *fpu_stack_empty |= 1 << (*fpu_stack_ptr as i32 + r & 7); }
 
-pub unsafe fn fpu_fildm16(addr: i32) { fpu_push(return_on_pagefault!(fpu_load_i16(addr))); }
-pub unsafe fn fpu_fildm32(addr: i32) { fpu_push(return_on_pagefault!(fpu_load_i32(addr))); }
-pub unsafe fn fpu_fildm64(addr: i32) { fpu_push(return_on_pagefault!(fpu_load_i64(addr))); }
+pub unsafe fn fpu_fildm16(seg: i32, addr: i32) {
+    fpu_push(return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
+pub unsafe fn fpu_fildm32(seg: i32, addr: i32) {
+    fpu_push(return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
+pub unsafe fn fpu_fildm64(seg: i32, addr: i32) {
+    fpu_push(return_on_pagefault!(fpu_load_i64(seg, addr)));
+}
 
 #[no_mangle]
 pub unsafe fn fpu_push(x: F80) {
@@ -319,15 +331,15 @@ pub unsafe fn fpu_convert_to_i16(f: F80) -> i16 {
         st0 as i16
     }
 }
-pub unsafe fn fpu_fistm16(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 2));
+pub unsafe fn fpu_fistm16(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 2));
     let v = fpu_convert_to_i16(fpu_get_st0());
-    safe_write16(addr, v as i32).unwrap();
+    safe_write16_seg(seg, addr, v as i32).unwrap();
 }
-pub unsafe fn fpu_fistm16p(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 2));
+pub unsafe fn fpu_fistm16p(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 2));
     let v = fpu_convert_to_i16(fpu_get_st0());
-    safe_write16(addr, v as i32).unwrap();
+    safe_write16_seg(seg, addr, v as i32).unwrap();
     fpu_pop();
 }
 #[no_mangle]
@@ -341,10 +353,10 @@ pub unsafe fn fpu_truncate_to_i16(f: F80) -> i16 {
         st0 as i16
     }
 }
-pub unsafe fn fpu_fisttpm16(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 2));
+pub unsafe fn fpu_fisttpm16(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 2));
     let v = fpu_truncate_to_i16(fpu_get_st0());
-    safe_write16(addr, v as i32).unwrap();
+    safe_write16_seg(seg, addr, v as i32).unwrap();
     fpu_pop();
 }
 
@@ -355,15 +367,15 @@ pub unsafe fn fpu_convert_to_i32(f: F80) -> i32 {
     *fpu_status_word |= F80::get_exception_flags() as u16;
     x
 }
-pub unsafe fn fpu_fistm32(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 4));
+pub unsafe fn fpu_fistm32(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 4));
     let v = fpu_convert_to_i32(fpu_get_st0());
-    safe_write32(addr, v).unwrap();
+    safe_write32_seg(seg, addr, v).unwrap();
 }
-pub unsafe fn fpu_fistm32p(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 4));
+pub unsafe fn fpu_fistm32p(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 4));
     let v = fpu_convert_to_i32(fpu_get_st0());
-    safe_write32(addr, v).unwrap();
+    safe_write32_seg(seg, addr, v).unwrap();
     fpu_pop();
 }
 #[no_mangle]
@@ -373,10 +385,10 @@ pub unsafe fn fpu_truncate_to_i32(f: F80) -> i32 {
     *fpu_status_word |= F80::get_exception_flags() as u16;
     x
 }
-pub unsafe fn fpu_fisttpm32(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 4));
+pub unsafe fn fpu_fisttpm32(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 4));
     let v = fpu_truncate_to_i32(fpu_get_st0());
-    safe_write32(addr, v).unwrap();
+    safe_write32_seg(seg, addr, v).unwrap();
     fpu_pop();
 }
 
@@ -387,10 +399,10 @@ pub unsafe fn fpu_convert_to_i64(f: F80) -> i64 {
     *fpu_status_word |= F80::get_exception_flags() as u16;
     x
 }
-pub unsafe fn fpu_fistm64p(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 8));
+pub unsafe fn fpu_fistm64p(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 8));
     let v = fpu_convert_to_i64(fpu_get_st0());
-    safe_write64(addr, v as u64).unwrap();
+    safe_write64_seg(seg, addr, v as u64).unwrap();
     fpu_pop();
 }
 #[no_mangle]
@@ -400,38 +412,105 @@ pub unsafe fn fpu_truncate_to_i64(f: F80) -> i64 {
     *fpu_status_word |= F80::get_exception_flags() as u16;
     x
 }
-pub unsafe fn fpu_fisttpm64(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 8));
+pub unsafe fn fpu_fisttpm64(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 8));
     let v = fpu_truncate_to_i64(fpu_get_st0());
-    safe_write64(addr, v as u64).unwrap();
+    safe_write64_seg(seg, addr, v as u64).unwrap();
     fpu_pop();
 }
 
-pub unsafe fn fpu_fldcw(addr: i32) {
-    let word = return_on_pagefault!(safe_read16(addr)) as u16;
+pub unsafe fn fpu_fldcw(seg: i32, addr: i32) {
+    let word = return_on_pagefault!(safe_read16_seg(seg, addr)) as u16;
     set_control_word(word);
 }
 
 #[no_mangle]
-pub unsafe fn fpu_fldenv16(_addr: i32) {
-    dbg_log!("fldenv16");
-    fpu_unimpl();
-}
+pub unsafe fn fpu_fldenv16(seg: i32, addr: i32) { fpu_fldenv(seg, addr, false) }
 #[no_mangle]
-pub unsafe fn fpu_fldenv32(addr: i32) {
-    if let Err(()) = readable_or_pagefault(addr, 28) {
+pub unsafe fn fpu_fldenv32(seg: i32, addr: i32) { fpu_fldenv(seg, addr, true) }
+unsafe fn fpu_fldenv(seg: i32, addr: i32, is_osize_32: bool) {
+    if let Err(()) = readable_or_pagefault_seg(seg, addr, fpu_env_size(is_osize_32)) {
         *page_fault = true;
         return;
     }
     *page_fault = false;
-    set_control_word(safe_read16(addr).unwrap() as u16);
-    fpu_set_status_word(safe_read16(addr + 4).unwrap() as u16);
-    fpu_set_tag_word(safe_read16(addr + 8).unwrap());
-    *fpu_ip = safe_read32s(addr + 12).unwrap();
-    *fpu_ip_selector = safe_read16(addr + 16).unwrap();
-    *fpu_opcode = safe_read16(addr + 18).unwrap();
-    *fpu_dp = safe_read32s(addr + 20).unwrap();
-    *fpu_dp_selector = safe_read16(addr + 24).unwrap()
+    fpu_load_env(seg, addr, is_osize_32);
+}
+
+// The environment consists of 7 fields, each 2 bytes (16-bit) or 4 bytes (32-bit) wide
+fn fpu_env_size(is_osize_32: bool) -> i32 { if is_osize_32 { 28 } else { 14 } }
+
+// In real and vm86 mode, the ip and dp are stored as 32-bit (20-bit for 16-bit environments)
+// linear addresses without selectors, with the upper bits packed into the following field
+unsafe fn fpu_env_is_real_mode() -> bool { !*protected_mode || vm86_mode() }
+
+unsafe fn fpu_read_env_field(seg: i32, addr: i32, index: i32, is_osize_32: bool) -> i32 {
+    // readable_or_pagefault must have been called by the caller!
+    if is_osize_32 {
+        safe_read32s_seg(seg, addr + 4 * index).unwrap()
+    }
+    else {
+        safe_read16_seg(seg, addr + 2 * index).unwrap()
+    }
+}
+unsafe fn fpu_write_env_field(seg: i32, addr: i32, index: i32, value: i32, is_osize_32: bool) {
+    // writable_or_pagefault must have been called by the caller!
+    if is_osize_32 {
+        safe_write32_seg(seg, addr + 4 * index, value).unwrap()
+    }
+    else {
+        safe_write16_seg(seg, addr + 2 * index, value & 0xFFFF).unwrap()
+    }
+}
+
+unsafe fn fpu_load_env(seg: i32, addr: i32, is_osize_32: bool) {
+    set_control_word(fpu_read_env_field(seg, addr, 0, is_osize_32) as u16);
+    fpu_set_status_word(fpu_read_env_field(seg, addr, 1, is_osize_32) as u16);
+    fpu_set_tag_word(fpu_read_env_field(seg, addr, 2, is_osize_32) & 0xFFFF);
+
+    if fpu_env_is_real_mode() {
+        let ip_high_and_opcode = fpu_read_env_field(seg, addr, 4, is_osize_32);
+        *fpu_ip = fpu_read_env_field(seg, addr, 3, is_osize_32) & 0xFFFF
+            | ((ip_high_and_opcode as u32 >> 12 & 0xFFFF) << 16) as i32;
+        *fpu_opcode = ip_high_and_opcode & 0x7FF;
+        *fpu_dp = fpu_read_env_field(seg, addr, 5, is_osize_32) & 0xFFFF
+            | ((fpu_read_env_field(seg, addr, 6, is_osize_32) as u32 >> 12 & 0xFFFF) << 16) as i32;
+    }
+    else if is_osize_32 {
+        *fpu_ip = safe_read32s_seg(seg, addr + 12).unwrap();
+        *fpu_ip_selector = safe_read16_seg(seg, addr + 16).unwrap();
+        *fpu_opcode = safe_read16_seg(seg, addr + 18).unwrap();
+        *fpu_dp = safe_read32s_seg(seg, addr + 20).unwrap();
+        *fpu_dp_selector = safe_read16_seg(seg, addr + 24).unwrap();
+    }
+    else {
+        // the 16-bit protected mode environment doesn't contain the opcode
+        *fpu_ip = safe_read16_seg(seg, addr + 6).unwrap();
+        *fpu_ip_selector = safe_read16_seg(seg, addr + 8).unwrap();
+        *fpu_dp = safe_read16_seg(seg, addr + 10).unwrap();
+        *fpu_dp_selector = safe_read16_seg(seg, addr + 12).unwrap();
+    }
+}
+unsafe fn fpu_store_env(seg: i32, addr: i32, is_osize_32: bool) {
+    // reserved upper halves of 32-bit fields are written as all ones
+    let high_bits = 0xFFFF0000u32 as i32;
+    fpu_write_env_field(seg, addr, 0, high_bits | *fpu_control_word as i32, is_osize_32);
+    fpu_write_env_field(seg, addr, 1, high_bits | fpu_load_status_word() as i32, is_osize_32);
+    fpu_write_env_field(seg, addr, 2, high_bits | fpu_load_tag_word(), is_osize_32);
+
+    if fpu_env_is_real_mode() {
+        let ip_high_and_opcode = (*fpu_ip as u32 >> 16 << 12) as i32 | *fpu_opcode & 0x7FF;
+        fpu_write_env_field(seg, addr, 3, high_bits | *fpu_ip & 0xFFFF, is_osize_32);
+        fpu_write_env_field(seg, addr, 4, ip_high_and_opcode, is_osize_32);
+        fpu_write_env_field(seg, addr, 5, high_bits | *fpu_dp & 0xFFFF, is_osize_32);
+        fpu_write_env_field(seg, addr, 6, (*fpu_dp as u32 >> 16 << 12) as i32, is_osize_32);
+    }
+    else {
+        fpu_write_env_field(seg, addr, 3, *fpu_ip, is_osize_32);
+        fpu_write_env_field(seg, addr, 4, *fpu_ip_selector | *fpu_opcode << 16, is_osize_32);
+        fpu_write_env_field(seg, addr, 5, *fpu_dp, is_osize_32);
+        fpu_write_env_field(seg, addr, 6, high_bits | *fpu_dp_selector, is_osize_32);
+    }
 }
 pub unsafe fn fpu_unimpl() {
     dbg_assert!(false);
@@ -445,15 +524,19 @@ pub unsafe fn fpu_set_tag_word(tag_word: i32) {
     }
 }
 pub unsafe fn fpu_set_status_word(sw: u16) {
-    *fpu_status_word = sw & !(7 << 11);
+    *fpu_status_word = sw & !(7 << 11) & !(FPU_ES | FPU_B);
     *fpu_stack_ptr = (sw >> 11 & 7) as u8;
 }
 
-pub unsafe fn fpu_fldm32(addr: i32) { fpu_push(return_on_pagefault!(fpu_load_m32(addr))); }
-pub unsafe fn fpu_fldm64(addr: i32) { fpu_push(return_on_pagefault!(fpu_load_m64(addr))); }
+pub unsafe fn fpu_fldm32(seg: i32, addr: i32) {
+    fpu_push(return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
+pub unsafe fn fpu_fldm64(seg: i32, addr: i32) {
+    fpu_push(return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
 #[no_mangle]
-pub unsafe fn fpu_fldm80(addr: i32) {
-    match fpu_load_m80(addr) {
+pub unsafe fn fpu_fldm80(seg: i32, addr: i32) {
+    match fpu_load_m80(seg, addr) {
         Ok(x) => {
             *page_fault = false;
             fpu_push(x)
@@ -469,8 +552,8 @@ pub unsafe fn fpu_fmul(target_index: i32, val: F80) {
     let st0 = fpu_get_st0();
     fpu_write_st(*fpu_stack_ptr as i32 + target_index & 7, st0 * val);
 }
-pub unsafe fn fpu_fnstsw_mem(addr: i32) {
-    return_on_pagefault!(safe_write16(addr, fpu_load_status_word().into()));
+pub unsafe fn fpu_fnstsw_mem(seg: i32, addr: i32) {
+    return_on_pagefault!(safe_write16_seg(seg, addr, fpu_load_status_word().into()));
 }
 pub unsafe fn fpu_fnstsw_reg() { write_reg16(AX, fpu_load_status_word().into()); }
 pub unsafe fn fpu_fprem(ieee: bool) {
@@ -523,67 +606,54 @@ pub unsafe fn fpu_fprem(ieee: bool) {
     }
 }
 
-pub unsafe fn fpu_frstor16(_addr: i32) {
-    dbg_log!("frstor16");
-    fpu_unimpl();
-}
-pub unsafe fn fpu_frstor32(mut addr: i32) {
-    return_on_pagefault!(readable_or_pagefault(addr, 28 + 8 * 10));
-    fpu_fldenv32(addr);
-    addr += 28;
+pub unsafe fn fpu_frstor16(seg: i32, addr: i32) { fpu_frstor(seg, addr, false) }
+pub unsafe fn fpu_frstor32(seg: i32, addr: i32) { fpu_frstor(seg, addr, true) }
+unsafe fn fpu_frstor(seg: i32, mut addr: i32, is_osize_32: bool) {
+    return_on_pagefault!(readable_or_pagefault_seg(seg, addr, fpu_env_size(is_osize_32) + 8 * 10));
+    fpu_load_env(seg, addr, is_osize_32);
+    addr += fpu_env_size(is_osize_32);
     for i in 0..8 {
         let reg_index = *fpu_stack_ptr as i32 + i & 7;
-        *fpu_st.offset(reg_index as isize) = fpu_load_m80(addr).unwrap();
+        *fpu_st.offset(reg_index as isize) = fpu_load_m80(seg, addr).unwrap();
         addr += 10;
     }
 }
 
-pub unsafe fn fpu_fsave16(_addr: i32) {
-    dbg_log!("fsave16");
-    fpu_unimpl();
-}
-pub unsafe fn fpu_fsave32(mut addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 108));
-    fpu_fstenv32(addr);
-    addr += 28;
+pub unsafe fn fpu_fsave16(seg: i32, addr: i32) { fpu_fsave(seg, addr, false) }
+pub unsafe fn fpu_fsave32(seg: i32, addr: i32) { fpu_fsave(seg, addr, true) }
+unsafe fn fpu_fsave(seg: i32, mut addr: i32, is_osize_32: bool) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, fpu_env_size(is_osize_32) + 8 * 10));
+    fpu_store_env(seg, addr, is_osize_32);
+    addr += fpu_env_size(is_osize_32);
     for i in 0..8 {
         let reg_index = i + *fpu_stack_ptr as i32 & 7;
-        fpu_store_m80(addr, *fpu_st.offset(reg_index as isize));
+        fpu_store_m80(seg, addr, *fpu_st.offset(reg_index as isize));
         addr += 10;
     }
     fpu_finit();
 }
 
-pub unsafe fn fpu_store_m80(addr: i32, f: F80) {
-    // writable_or_pagefault must have checked called by the caller!
-    safe_write64(addr, f.mantissa).unwrap();
-    safe_write16(addr + 8, f.sign_exponent as i32).unwrap();
+pub unsafe fn fpu_store_m80(seg: i32, addr: i32, f: F80) {
+    // writable_or_pagefault must have been called by the caller!
+    safe_write64_seg(seg, addr, f.mantissa).unwrap();
+    safe_write16_seg(seg, addr + 8, f.sign_exponent as i32).unwrap();
 }
 
 #[no_mangle]
-pub unsafe fn fpu_fstenv16(_addr: i32) {
-    dbg_log!("fstenv16");
-    fpu_unimpl();
-}
-
+pub unsafe fn fpu_fstenv16(seg: i32, addr: i32) { fpu_fstenv(seg, addr, false) }
 #[no_mangle]
-pub unsafe fn fpu_fstenv32(addr: i32) {
-    match writable_or_pagefault(addr, 26) {
+pub unsafe fn fpu_fstenv32(seg: i32, addr: i32) { fpu_fstenv(seg, addr, true) }
+unsafe fn fpu_fstenv(seg: i32, addr: i32, is_osize_32: bool) {
+    match writable_or_pagefault_seg(seg, addr, fpu_env_size(is_osize_32)) {
         Ok(()) => *page_fault = false,
         Err(()) => {
             *page_fault = true;
             return;
         },
     }
-    let high_bits = 0xFFFF0000u32 as i32;
-    safe_write32(addr + 0, high_bits + *fpu_control_word as i32).unwrap();
-    safe_write32(addr + 4, high_bits + fpu_load_status_word() as i32).unwrap();
-    safe_write32(addr + 8, high_bits + fpu_load_tag_word()).unwrap();
-    safe_write32(addr + 12, *fpu_ip).unwrap();
-    safe_write16(addr + 16, *fpu_ip_selector).unwrap();
-    safe_write16(addr + 18, *fpu_opcode).unwrap();
-    safe_write32(addr + 20, *fpu_dp).unwrap();
-    safe_write32(addr + 24, high_bits | *fpu_dp_selector).unwrap();
+    fpu_store_env(seg, addr, is_osize_32);
+    // exceptions are masked after storing the environment
+    set_control_word(*fpu_control_word | FPU_EX_ALL);
 }
 #[no_mangle]
 pub unsafe fn fpu_load_tag_word() -> i32 {
@@ -604,36 +674,38 @@ pub unsafe fn fpu_load_tag_word() -> i32 {
 }
 #[no_mangle]
 pub unsafe fn fpu_fst(r: i32) { fpu_write_st(*fpu_stack_ptr as i32 + r & 7, fpu_get_st0()); }
-pub unsafe fn fpu_fst80p(addr: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 10));
-    fpu_store_m80(addr, fpu_get_st0());
+pub unsafe fn fpu_fst80p(seg: i32, addr: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 10));
+    fpu_store_m80(seg, addr, fpu_get_st0());
     fpu_pop();
 }
 
-pub unsafe fn fpu_fstcw(addr: i32) {
-    return_on_pagefault!(safe_write16(addr, (*fpu_control_word).into()));
+pub unsafe fn fpu_fstcw(seg: i32, addr: i32) {
+    return_on_pagefault!(safe_write16_seg(seg, addr, (*fpu_control_word).into()));
 }
 
-pub unsafe fn fpu_fstm32(addr: i32) {
-    return_on_pagefault!(fpu_store_m32(addr, fpu_get_st0()));
+pub unsafe fn fpu_fstm32(seg: i32, addr: i32) {
+    return_on_pagefault!(fpu_store_m32(seg, addr, fpu_get_st0()));
 }
-pub unsafe fn fpu_store_m32(addr: i32, x: F80) -> OrPageFault<()> {
+pub unsafe fn fpu_store_m32(seg: i32, addr: i32, x: F80) -> OrPageFault<()> {
     F80::clear_exception_flags();
-    safe_write32(addr, x.to_f32())?;
+    safe_write32_seg(seg, addr, x.to_f32())?;
     *fpu_status_word |= F80::get_exception_flags() as u16;
     Ok(())
 }
-pub unsafe fn fpu_fstm32p(addr: i32) {
-    return_on_pagefault!(fpu_store_m32(addr, fpu_get_st0()));
+pub unsafe fn fpu_fstm32p(seg: i32, addr: i32) {
+    return_on_pagefault!(fpu_store_m32(seg, addr, fpu_get_st0()));
     fpu_pop();
 }
-pub unsafe fn fpu_fstm64(addr: i32) {
-    return_on_pagefault!(fpu_store_m64(addr, fpu_get_st0()));
+pub unsafe fn fpu_fstm64(seg: i32, addr: i32) {
+    return_on_pagefault!(fpu_store_m64(seg, addr, fpu_get_st0()));
+}
+pub unsafe fn fpu_store_m64(seg: i32, addr: i32, x: F80) -> OrPageFault<()> {
+    safe_write64_seg(seg, addr, x.to_f64())
 }
-pub unsafe fn fpu_store_m64(addr: i32, x: F80) -> OrPageFault<()> { safe_write64(addr, x.to_f64()) }
-pub unsafe fn fpu_fstm64p(addr: i32) {
+pub unsafe fn fpu_fstm64p(seg: i32, addr: i32) {
     // XXX: writable_or_pagefault before get_st0
-    return_on_pagefault!(fpu_store_m64(addr, fpu_get_st0()));
+    return_on_pagefault!(fpu_store_m64(seg, addr, fpu_get_st0()));
     fpu_pop();
 }
 #[no_mangle]
@@ -643,8 +715,8 @@ pub unsafe fn fpu_fstp(r: i32) {
 }
 
 #[no_mangle]
-pub unsafe fn fpu_fbstp(addr: i32) {
-    match writable_or_pagefault(addr, 26) {
+pub unsafe fn fpu_fbstp(seg: i32, addr: i32) {
+    match writable_or_pagefault_seg(seg, addr, 26) {
         Ok(()) => *page_fault = false,
         Err(()) => {
             *page_fault = true;
@@ -659,14 +731,14 @@ pub unsafe fn fpu_fbstp(addr: i32) {
             x /= 10;
             let high = x % 10;
             x /= 10;
-            safe_write8(addr + i, (high as i32) << 4 | low as i32).unwrap();
+            safe_write8_seg(seg, addr + i, (high as i32) << 4 | low as i32).unwrap();
         }
-        safe_write8(addr + 9, if st0.sign() { 0x80 } else { 0 }).unwrap();
+        safe_write8_seg(seg, addr + 9, if st0.sign() { 0x80 } else { 0 }).unwrap();
     }
     else {
         fpu_invalid_arithmetic();
-        safe_write64(addr + 0, 0xC000_0000_0000_0000).unwrap();
-        safe_write16(addr + 8, 0xFFFF).unwrap();
+        safe_write64_seg(seg, addr + 0, 0xC000_0000_0000_0000).unwrap();
+        safe_write16_seg(seg, addr + 8, 0xFFFF).unwrap();
     }
     fpu_pop();
 }
@@ -809,7 +881,9 @@ pub unsafe fn fpu_fxtract() {
 }
 
 pub unsafe fn fwait() {
-    // NOP unless FPU instructions run in parallel with CPU instructions
+    // Only delivers pending exceptions, as FPU instructions don't run in parallel with CPU
+    // instructions
+    fpu_pending_exception_test();
 }
 
 pub unsafe fn fpu_fchs() {
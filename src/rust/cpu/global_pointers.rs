@@ -16,6 +16,7 @@ pub const last_result: *mut i32 = 112 as *mut i32;
 pub const flags: *mut i32 = 120 as *mut i32;
 
 pub const segment_access_bytes: *mut u8 = 512 as *mut u8; // TODO: reorder below segment_limits
+pub const segment_is_big: *mut bool = 520 as *mut bool;
 
 pub const page_fault: *mut bool = 540 as *mut bool;
 
@@ -33,11 +34,15 @@ pub const cpl: *mut u8 = 612 as *mut u8;
 pub const in_hlt: *mut bool = 616 as *mut bool;
 pub const last_virt_eip: *mut i32 = 620 as *mut i32;
 pub const eip_phys: *mut i32 = 624 as *mut i32;
+pub const efer: *mut i32 = 628 as *mut i32;
+pub const tsc_aux: *mut i32 = 632 as *mut i32;
 
 pub const sysenter_cs: *mut i32 = 636 as *mut i32;
 pub const sysenter_esp: *mut i32 = 640 as *mut i32;
 pub const sysenter_eip: *mut i32 = 644 as *mut i32;
 pub const prefixes: *mut u8 = 648 as *mut u8;
+pub const xcr0: *mut i32 = 652 as *mut i32;
+pub const star: *mut u64 = 656 as *mut u64;
 pub const instruction_counter: *mut u32 = 664 as *mut u32;
 pub const sreg: *mut u16 = 668 as *mut u16;
 pub const dreg: *mut i32 = 684 as *mut i32;
@@ -2,6 +2,7 @@
 
 use cpu::arith::*;
 use cpu::cpu::*;
+use cpu::cpuid;
 use cpu::fpu::*;
 use cpu::global_pointers::*;
 use cpu::misc_instr::*;
@@ -9,41 +10,44 @@ use cpu::misc_instr::{pop16, pop32s, push16, push32};
 use cpu::string::*;
 use prefix;
 use softfloat::F80;
+use vex::{Vex, VexInstruction};
 
-pub unsafe fn instr_00_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| add8(x, read_reg8(r))) }
+pub unsafe fn instr_00_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| add8(x, read_reg8(r)))
+}
 pub unsafe fn instr_00_reg(r1: i32, r: i32) { write_reg8(r1, add8(read_reg8(r1), read_reg8(r))); }
-pub unsafe fn instr16_01_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| add16(x, read_reg16(r)))
+pub unsafe fn instr16_01_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| add16(x, read_reg16(r)))
 }
 pub unsafe fn instr16_01_reg(r1: i32, r: i32) {
     write_reg16(r1, add16(read_reg16(r1), read_reg16(r)));
 }
-pub unsafe fn instr32_01_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| add32(x, read_reg32(r)))
+pub unsafe fn instr32_01_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| add32(x, read_reg32(r)))
 }
 pub unsafe fn instr32_01_reg(r1: i32, r: i32) {
     write_reg32(r1, add32(read_reg32(r1), read_reg32(r)));
 }
-pub unsafe fn instr_02_mem(addr: i32, r: i32) {
+pub unsafe fn instr_02_mem(seg: i32, addr: i32, r: i32) {
     write_reg8(
         r,
-        add8(read_reg8(r), return_on_pagefault!(safe_read8(addr))),
+        add8(read_reg8(r), return_on_pagefault!(safe_read8_seg(seg, addr))),
     );
 }
 pub unsafe fn instr_02_reg(r1: i32, r: i32) { write_reg8(r, add8(read_reg8(r), read_reg8(r1))); }
-pub unsafe fn instr16_03_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_03_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        add16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        add16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_03_reg(r1: i32, r: i32) {
     write_reg16(r, add16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_03_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_03_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        add32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        add32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_03_reg(r1: i32, r: i32) {
@@ -59,7 +63,7 @@ pub unsafe fn instr32_06() { return_on_pagefault!(push32_sreg(ES)) }
 
 #[no_mangle]
 pub unsafe fn instr16_07() {
-    if !switch_seg(ES, return_on_pagefault!(safe_read16(get_stack_pointer(0)))) {
+    if !switch_seg(ES, return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0)))) {
         return;
     }
     adjust_stack_reg(2);
@@ -68,44 +72,46 @@ pub unsafe fn instr16_07() {
 pub unsafe fn instr32_07() {
     if !switch_seg(
         ES,
-        return_on_pagefault!(safe_read32s(get_stack_pointer(0))) & 0xFFFF,
+        return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0))) & 0xFFFF,
     ) {
         return;
     }
     adjust_stack_reg(4);
 }
 
-pub unsafe fn instr_08_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| or8(x, read_reg8(r))) }
+pub unsafe fn instr_08_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| or8(x, read_reg8(r)))
+}
 pub unsafe fn instr_08_reg(r1: i32, r: i32) { write_reg8(r1, or8(read_reg8(r1), read_reg8(r))); }
-pub unsafe fn instr16_09_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| or16(x, read_reg16(r)))
+pub unsafe fn instr16_09_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| or16(x, read_reg16(r)))
 }
 pub unsafe fn instr16_09_reg(r1: i32, r: i32) {
     write_reg16(r1, or16(read_reg16(r1), read_reg16(r)));
 }
-pub unsafe fn instr32_09_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| or32(x, read_reg32(r)))
+pub unsafe fn instr32_09_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| or32(x, read_reg32(r)))
 }
 pub unsafe fn instr32_09_reg(r1: i32, r: i32) {
     write_reg32(r1, or32(read_reg32(r1), read_reg32(r)));
 }
-pub unsafe fn instr_0A_mem(addr: i32, r: i32) {
-    write_reg8(r, or8(read_reg8(r), return_on_pagefault!(safe_read8(addr))));
+pub unsafe fn instr_0A_mem(seg: i32, addr: i32, r: i32) {
+    write_reg8(r, or8(read_reg8(r), return_on_pagefault!(safe_read8_seg(seg, addr))));
 }
 pub unsafe fn instr_0A_reg(r1: i32, r: i32) { write_reg8(r, or8(read_reg8(r), read_reg8(r1))); }
-pub unsafe fn instr16_0B_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_0B_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        or16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        or16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_0B_reg(r1: i32, r: i32) {
     write_reg16(r, or16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_0B_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_0B_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        or32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        or32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_0B_reg(r1: i32, r: i32) {
@@ -123,40 +129,42 @@ pub unsafe fn instr32_0E() { return_on_pagefault!(push32_sreg(CS)) }
 pub unsafe fn instr16_0F() { run_instruction0f_16(return_on_pagefault!(read_imm8())); }
 pub unsafe fn instr32_0F() { run_instruction0f_32(return_on_pagefault!(read_imm8())); }
 
-pub unsafe fn instr_10_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| adc8(x, read_reg8(r))) }
+pub unsafe fn instr_10_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| adc8(x, read_reg8(r)))
+}
 pub unsafe fn instr_10_reg(r1: i32, r: i32) { write_reg8(r1, adc8(read_reg8(r1), read_reg8(r))); }
-pub unsafe fn instr16_11_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| adc16(x, read_reg16(r)))
+pub unsafe fn instr16_11_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| adc16(x, read_reg16(r)))
 }
 pub unsafe fn instr16_11_reg(r1: i32, r: i32) {
     write_reg16(r1, adc16(read_reg16(r1), read_reg16(r)));
 }
-pub unsafe fn instr32_11_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| adc32(x, read_reg32(r)))
+pub unsafe fn instr32_11_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| adc32(x, read_reg32(r)))
 }
 pub unsafe fn instr32_11_reg(r1: i32, r: i32) {
     write_reg32(r1, adc32(read_reg32(r1), read_reg32(r)));
 }
-pub unsafe fn instr_12_mem(addr: i32, r: i32) {
+pub unsafe fn instr_12_mem(seg: i32, addr: i32, r: i32) {
     write_reg8(
         r,
-        adc8(read_reg8(r), return_on_pagefault!(safe_read8(addr))),
+        adc8(read_reg8(r), return_on_pagefault!(safe_read8_seg(seg, addr))),
     );
 }
 pub unsafe fn instr_12_reg(r1: i32, r: i32) { write_reg8(r, adc8(read_reg8(r), read_reg8(r1))); }
-pub unsafe fn instr16_13_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_13_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        adc16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        adc16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_13_reg(r1: i32, r: i32) {
     write_reg16(r, adc16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_13_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_13_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        adc32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        adc32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_13_reg(r1: i32, r: i32) {
@@ -173,56 +181,60 @@ pub unsafe fn instr32_16() { return_on_pagefault!(push32_sreg(SS)) }
 
 #[no_mangle]
 pub unsafe fn instr16_17() {
-    if !switch_seg(SS, return_on_pagefault!(safe_read16(get_stack_pointer(0)))) {
+    if !switch_seg(SS, return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0)))) {
         return;
     }
     adjust_stack_reg(2);
+    inhibit_debug_traps();
 }
 #[no_mangle]
 pub unsafe fn instr32_17() {
     if !switch_seg(
         SS,
-        return_on_pagefault!(safe_read32s(get_stack_pointer(0))) & 0xFFFF,
+        return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0))) & 0xFFFF,
     ) {
         return;
     }
     adjust_stack_reg(4);
+    inhibit_debug_traps();
 }
 
-pub unsafe fn instr_18_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| sbb8(x, read_reg8(r))) }
+pub unsafe fn instr_18_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sbb8(x, read_reg8(r)))
+}
 pub unsafe fn instr_18_reg(r1: i32, r: i32) { write_reg8(r1, sbb8(read_reg8(r1), read_reg8(r))); }
-pub unsafe fn instr16_19_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| sbb16(x, read_reg16(r)))
+pub unsafe fn instr16_19_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sbb16(x, read_reg16(r)))
 }
 pub unsafe fn instr16_19_reg(r1: i32, r: i32) {
     write_reg16(r1, sbb16(read_reg16(r1), read_reg16(r)));
 }
-pub unsafe fn instr32_19_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| sbb32(x, read_reg32(r)))
+pub unsafe fn instr32_19_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sbb32(x, read_reg32(r)))
 }
 pub unsafe fn instr32_19_reg(r1: i32, r: i32) {
     write_reg32(r1, sbb32(read_reg32(r1), read_reg32(r)));
 }
-pub unsafe fn instr_1A_mem(addr: i32, r: i32) {
+pub unsafe fn instr_1A_mem(seg: i32, addr: i32, r: i32) {
     write_reg8(
         r,
-        sbb8(read_reg8(r), return_on_pagefault!(safe_read8(addr))),
+        sbb8(read_reg8(r), return_on_pagefault!(safe_read8_seg(seg, addr))),
     );
 }
 pub unsafe fn instr_1A_reg(r1: i32, r: i32) { write_reg8(r, sbb8(read_reg8(r), read_reg8(r1))); }
-pub unsafe fn instr16_1B_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_1B_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        sbb16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        sbb16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_1B_reg(r1: i32, r: i32) {
     write_reg16(r, sbb16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_1B_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_1B_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        sbb32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        sbb32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_1B_reg(r1: i32, r: i32) {
@@ -239,7 +251,7 @@ pub unsafe fn instr32_1E() { return_on_pagefault!(push32_sreg(DS)) }
 
 #[no_mangle]
 pub unsafe fn instr16_1F() {
-    if !switch_seg(DS, return_on_pagefault!(safe_read16(get_stack_pointer(0)))) {
+    if !switch_seg(DS, return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0)))) {
         return;
     }
     adjust_stack_reg(2);
@@ -248,47 +260,49 @@ pub unsafe fn instr16_1F() {
 pub unsafe fn instr32_1F() {
     if !switch_seg(
         DS,
-        return_on_pagefault!(safe_read32s(get_stack_pointer(0))) & 0xFFFF,
+        return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0))) & 0xFFFF,
     ) {
         return;
     }
     adjust_stack_reg(4);
 }
 
-pub unsafe fn instr_20_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| and8(x, read_reg8(r))) }
+pub unsafe fn instr_20_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| and8(x, read_reg8(r)))
+}
 pub unsafe fn instr_20_reg(r1: i32, r: i32) { write_reg8(r1, and8(read_reg8(r1), read_reg8(r))); }
-pub unsafe fn instr16_21_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| and16(x, read_reg16(r)))
+pub unsafe fn instr16_21_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| and16(x, read_reg16(r)))
 }
 pub unsafe fn instr16_21_reg(r1: i32, r: i32) {
     write_reg16(r1, and16(read_reg16(r1), read_reg16(r)));
 }
-pub unsafe fn instr32_21_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| and32(x, read_reg32(r)))
+pub unsafe fn instr32_21_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| and32(x, read_reg32(r)))
 }
 pub unsafe fn instr32_21_reg(r1: i32, r: i32) {
     write_reg32(r1, and32(read_reg32(r1), read_reg32(r)));
 }
-pub unsafe fn instr_22_mem(addr: i32, r: i32) {
+pub unsafe fn instr_22_mem(seg: i32, addr: i32, r: i32) {
     write_reg8(
         r,
-        and8(read_reg8(r), return_on_pagefault!(safe_read8(addr))),
+        and8(read_reg8(r), return_on_pagefault!(safe_read8_seg(seg, addr))),
     );
 }
 pub unsafe fn instr_22_reg(r1: i32, r: i32) { write_reg8(r, and8(read_reg8(r), read_reg8(r1))); }
-pub unsafe fn instr16_23_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_23_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        and16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        and16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_23_reg(r1: i32, r: i32) {
     write_reg16(r, and16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_23_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_23_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        and32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        and32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_23_reg(r1: i32, r: i32) {
@@ -303,40 +317,42 @@ pub unsafe fn instr_26() { segment_prefix_op(ES); }
 #[no_mangle]
 pub unsafe fn instr_27() { bcd_daa(); }
 
-pub unsafe fn instr_28_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| sub8(x, read_reg8(r))) }
+pub unsafe fn instr_28_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sub8(x, read_reg8(r)))
+}
 pub unsafe fn instr_28_reg(r1: i32, r: i32) { write_reg8(r1, sub8(read_reg8(r1), read_reg8(r))); }
-pub unsafe fn instr16_29_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| sub16(x, read_reg16(r)))
+pub unsafe fn instr16_29_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sub16(x, read_reg16(r)))
 }
 pub unsafe fn instr16_29_reg(r1: i32, r: i32) {
     write_reg16(r1, sub16(read_reg16(r1), read_reg16(r)));
 }
-pub unsafe fn instr32_29_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| sub32(x, read_reg32(r)))
+pub unsafe fn instr32_29_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sub32(x, read_reg32(r)))
 }
 pub unsafe fn instr32_29_reg(r1: i32, r: i32) {
     write_reg32(r1, sub32(read_reg32(r1), read_reg32(r)));
 }
-pub unsafe fn instr_2A_mem(addr: i32, r: i32) {
+pub unsafe fn instr_2A_mem(seg: i32, addr: i32, r: i32) {
     write_reg8(
         r,
-        sub8(read_reg8(r), return_on_pagefault!(safe_read8(addr))),
+        sub8(read_reg8(r), return_on_pagefault!(safe_read8_seg(seg, addr))),
     );
 }
 pub unsafe fn instr_2A_reg(r1: i32, r: i32) { write_reg8(r, sub8(read_reg8(r), read_reg8(r1))); }
-pub unsafe fn instr16_2B_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_2B_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        sub16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        sub16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_2B_reg(r1: i32, r: i32) {
     write_reg16(r, sub16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_2B_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_2B_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        sub32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        sub32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_2B_reg(r1: i32, r: i32) {
@@ -351,40 +367,42 @@ pub unsafe fn instr_2E() { segment_prefix_op(CS); }
 #[no_mangle]
 pub unsafe fn instr_2F() { bcd_das(); }
 
-pub unsafe fn instr_30_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| xor8(x, read_reg8(r))) }
+pub unsafe fn instr_30_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| xor8(x, read_reg8(r)))
+}
 pub unsafe fn instr_30_reg(r1: i32, r: i32) { write_reg8(r1, xor8(read_reg8(r1), read_reg8(r))); }
-pub unsafe fn instr16_31_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| xor16(x, read_reg16(r)))
+pub unsafe fn instr16_31_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| xor16(x, read_reg16(r)))
 }
 pub unsafe fn instr16_31_reg(r1: i32, r: i32) {
     write_reg16(r1, xor16(read_reg16(r1), read_reg16(r)));
 }
-pub unsafe fn instr32_31_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| xor32(x, read_reg32(r)))
+pub unsafe fn instr32_31_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| xor32(x, read_reg32(r)))
 }
 pub unsafe fn instr32_31_reg(r1: i32, r: i32) {
     write_reg32(r1, xor32(read_reg32(r1), read_reg32(r)));
 }
-pub unsafe fn instr_32_mem(addr: i32, r: i32) {
+pub unsafe fn instr_32_mem(seg: i32, addr: i32, r: i32) {
     write_reg8(
         r,
-        xor8(read_reg8(r), return_on_pagefault!(safe_read8(addr))),
+        xor8(read_reg8(r), return_on_pagefault!(safe_read8_seg(seg, addr))),
     );
 }
 pub unsafe fn instr_32_reg(r1: i32, r: i32) { write_reg8(r, xor8(read_reg8(r), read_reg8(r1))); }
-pub unsafe fn instr16_33_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_33_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        xor16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        xor16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_33_reg(r1: i32, r: i32) {
     write_reg16(r, xor16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_33_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_33_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        xor32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        xor32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_33_reg(r1: i32, r: i32) {
@@ -399,28 +417,28 @@ pub unsafe fn instr_36() { segment_prefix_op(SS); }
 #[no_mangle]
 pub unsafe fn instr_37() { bcd_aaa(); }
 
-pub unsafe fn instr_38_mem(addr: i32, r: i32) {
-    cmp8(return_on_pagefault!(safe_read8(addr)), read_reg8(r));
+pub unsafe fn instr_38_mem(seg: i32, addr: i32, r: i32) {
+    cmp8(return_on_pagefault!(safe_read8_seg(seg, addr)), read_reg8(r));
 }
 pub unsafe fn instr_38_reg(r1: i32, r: i32) { cmp8(read_reg8(r1), read_reg8(r)); }
-pub unsafe fn instr16_39_mem(addr: i32, r: i32) {
-    cmp16(return_on_pagefault!(safe_read16(addr)), read_reg16(r));
+pub unsafe fn instr16_39_mem(seg: i32, addr: i32, r: i32) {
+    cmp16(return_on_pagefault!(safe_read16_seg(seg, addr)), read_reg16(r));
 }
 pub unsafe fn instr16_39_reg(r1: i32, r: i32) { cmp16(read_reg16(r1), read_reg16(r)); }
-pub unsafe fn instr32_39_mem(addr: i32, r: i32) {
-    cmp32(return_on_pagefault!(safe_read32s(addr)), read_reg32(r));
+pub unsafe fn instr32_39_mem(seg: i32, addr: i32, r: i32) {
+    cmp32(return_on_pagefault!(safe_read32s_seg(seg, addr)), read_reg32(r));
 }
 pub unsafe fn instr32_39_reg(r1: i32, r: i32) { cmp32(read_reg32(r1), read_reg32(r)); }
-pub unsafe fn instr_3A_mem(addr: i32, r: i32) {
-    cmp8(read_reg8(r), return_on_pagefault!(safe_read8(addr)));
+pub unsafe fn instr_3A_mem(seg: i32, addr: i32, r: i32) {
+    cmp8(read_reg8(r), return_on_pagefault!(safe_read8_seg(seg, addr)));
 }
 pub unsafe fn instr_3A_reg(r1: i32, r: i32) { cmp8(read_reg8(r), read_reg8(r1)); }
-pub unsafe fn instr16_3B_mem(addr: i32, r: i32) {
-    cmp16(read_reg16(r), return_on_pagefault!(safe_read16(addr)));
+pub unsafe fn instr16_3B_mem(seg: i32, addr: i32, r: i32) {
+    cmp16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 pub unsafe fn instr16_3B_reg(r1: i32, r: i32) { cmp16(read_reg16(r), read_reg16(r1)); }
-pub unsafe fn instr32_3B_mem(addr: i32, r: i32) {
-    cmp32(read_reg32(r), return_on_pagefault!(safe_read32s(addr)));
+pub unsafe fn instr32_3B_mem(seg: i32, addr: i32, r: i32) {
+    cmp32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr)));
 }
 pub unsafe fn instr32_3B_reg(r1: i32, r: i32) { cmp32(read_reg32(r), read_reg32(r1)); }
 pub unsafe fn instr_3C(imm8: i32) { cmp8(read_reg8(AL), imm8); }
@@ -497,12 +515,12 @@ pub unsafe fn instr32_5A() { write_reg32(EDX, return_on_pagefault!(pop32s())); }
 pub unsafe fn instr16_5B() { write_reg16(BX, return_on_pagefault!(pop16())); }
 pub unsafe fn instr32_5B() { write_reg32(EBX, return_on_pagefault!(pop32s())); }
 pub unsafe fn instr16_5C() {
-    write_reg16(SP, return_on_pagefault!(safe_read16(get_stack_pointer(0))));
+    write_reg16(SP, return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0))));
 }
 pub unsafe fn instr32_5C() {
     write_reg32(
         ESP,
-        return_on_pagefault!(safe_read32s(get_stack_pointer(0))),
+        return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0))),
     );
 }
 pub unsafe fn instr16_5D() { write_reg16(BP, return_on_pagefault!(pop16())); }
@@ -528,7 +546,7 @@ pub unsafe fn instr_62_reg(_r2: i32, _r: i32) {
     dbg_assert!(false);
 }
 #[no_mangle]
-pub unsafe fn instr_62_mem(_addr: i32, _r: i32) {
+pub unsafe fn instr_62_mem(_seg: i32, _addr: i32, _r: i32) {
     dbg_log!("Unimplemented BOUND instruction");
     dbg_assert!(false);
 }
@@ -547,13 +565,13 @@ pub unsafe fn arpl(seg: i32, r16: i32) -> i32 {
 }
 
 #[no_mangle]
-pub unsafe fn instr_63_mem(addr: i32, r: i32) {
+pub unsafe fn instr_63_mem(seg: i32, addr: i32, r: i32) {
     if !*protected_mode || vm86_mode() {
         dbg_log!("arpl #ud");
         trigger_ud();
         return;
     }
-    safe_read_write16(addr, &|x| arpl(x, read_reg16(r)))
+    safe_read_write16_seg(seg, addr, &|x| arpl(x, read_reg16(r)))
 }
 #[no_mangle]
 pub unsafe fn instr_63_reg(r1: i32, r: i32) {
@@ -588,14 +606,14 @@ pub unsafe fn instr16_68(imm16: i32) {
 pub unsafe fn instr32_68(imm32: i32) {
     return_on_pagefault!(push32(imm32));
 }
-pub unsafe fn instr16_69_mem(addr: i32, r: i32, imm: i32) {
-    write_reg16(r, imul_reg16(return_on_pagefault!(safe_read16(addr)), imm));
+pub unsafe fn instr16_69_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    write_reg16(r, imul_reg16(return_on_pagefault!(safe_read16_seg(seg, addr)), imm));
 }
 pub unsafe fn instr16_69_reg(r1: i32, r: i32, imm: i32) {
     write_reg16(r, imul_reg16(read_reg16(r1), imm));
 }
-pub unsafe fn instr32_69_mem(addr: i32, r: i32, imm: i32) {
-    write_reg32(r, imul_reg32(return_on_pagefault!(safe_read32s(addr)), imm));
+pub unsafe fn instr32_69_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    write_reg32(r, imul_reg32(return_on_pagefault!(safe_read32s_seg(seg, addr)), imm));
 }
 pub unsafe fn instr32_69_reg(r1: i32, r: i32, imm: i32) {
     write_reg32(r, imul_reg32(read_reg32(r1), imm));
@@ -607,14 +625,14 @@ pub unsafe fn instr16_6A(imm8: i32) {
 pub unsafe fn instr32_6A(imm8: i32) {
     return_on_pagefault!(push32(imm8));
 }
-pub unsafe fn instr16_6B_mem(addr: i32, r: i32, imm: i32) {
-    write_reg16(r, imul_reg16(return_on_pagefault!(safe_read16(addr)), imm));
+pub unsafe fn instr16_6B_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    write_reg16(r, imul_reg16(return_on_pagefault!(safe_read16_seg(seg, addr)), imm));
 }
 pub unsafe fn instr16_6B_reg(r1: i32, r: i32, imm: i32) {
     write_reg16(r, imul_reg16(read_reg16(r1), imm));
 }
-pub unsafe fn instr32_6B_mem(addr: i32, r: i32, imm: i32) {
-    write_reg32(r, imul_reg32(return_on_pagefault!(safe_read32s(addr)), imm));
+pub unsafe fn instr32_6B_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    write_reg32(r, imul_reg32(return_on_pagefault!(safe_read32s_seg(seg, addr)), imm));
 }
 pub unsafe fn instr32_6B_reg(r1: i32, r: i32, imm: i32) {
     write_reg32(r, imul_reg32(read_reg32(r1), imm));
@@ -674,184 +692,260 @@ pub unsafe fn instr32_7D(imm8: i32) { jmpcc32(!test_l(), imm8); }
 pub unsafe fn instr32_7E(imm8: i32) { jmpcc32(test_le(), imm8); }
 pub unsafe fn instr32_7F(imm8: i32) { jmpcc32(!test_le(), imm8); }
 
-pub unsafe fn instr_80_0_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| add8(x, imm)) }
+pub unsafe fn instr_80_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| add8(x, imm))
+}
 pub unsafe fn instr_80_0_reg(r1: i32, imm: i32) { write_reg8(r1, add8(read_reg8(r1), imm)); }
-pub unsafe fn instr_80_1_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| or8(x, imm)) }
+pub unsafe fn instr_80_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| or8(x, imm))
+}
 pub unsafe fn instr_80_1_reg(r1: i32, imm: i32) { write_reg8(r1, or8(read_reg8(r1), imm)); }
-pub unsafe fn instr_80_2_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| adc8(x, imm)) }
+pub unsafe fn instr_80_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| adc8(x, imm))
+}
 pub unsafe fn instr_80_2_reg(r1: i32, imm: i32) { write_reg8(r1, adc8(read_reg8(r1), imm)); }
-pub unsafe fn instr_80_3_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| sbb8(x, imm)) }
+pub unsafe fn instr_80_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sbb8(x, imm))
+}
 pub unsafe fn instr_80_3_reg(r1: i32, imm: i32) { write_reg8(r1, sbb8(read_reg8(r1), imm)); }
-pub unsafe fn instr_80_4_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| and8(x, imm)) }
+pub unsafe fn instr_80_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| and8(x, imm))
+}
 pub unsafe fn instr_80_4_reg(r1: i32, imm: i32) { write_reg8(r1, and8(read_reg8(r1), imm)); }
-pub unsafe fn instr_80_5_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| sub8(x, imm)) }
+pub unsafe fn instr_80_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sub8(x, imm))
+}
 pub unsafe fn instr_80_5_reg(r1: i32, imm: i32) { write_reg8(r1, sub8(read_reg8(r1), imm)); }
-pub unsafe fn instr_80_6_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| xor8(x, imm)) }
+pub unsafe fn instr_80_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| xor8(x, imm))
+}
 pub unsafe fn instr_80_6_reg(r1: i32, imm: i32) { write_reg8(r1, xor8(read_reg8(r1), imm)); }
 pub unsafe fn instr_80_7_reg(r: i32, imm: i32) { cmp8(read_reg8(r), imm); }
-pub unsafe fn instr_80_7_mem(addr: i32, imm: i32) {
-    cmp8(return_on_pagefault!(safe_read8(addr)), imm);
+pub unsafe fn instr_80_7_mem(seg: i32, addr: i32, imm: i32) {
+    cmp8(return_on_pagefault!(safe_read8_seg(seg, addr)), imm);
+}
+pub unsafe fn instr16_81_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| add16(x, imm))
 }
-pub unsafe fn instr16_81_0_mem(addr: i32, imm: i32) { safe_read_write16(addr, &|x| add16(x, imm)) }
 pub unsafe fn instr16_81_0_reg(r1: i32, imm: i32) { write_reg16(r1, add16(read_reg16(r1), imm)); }
-pub unsafe fn instr16_81_1_mem(addr: i32, imm: i32) { safe_read_write16(addr, &|x| or16(x, imm)) }
+pub unsafe fn instr16_81_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| or16(x, imm))
+}
 pub unsafe fn instr16_81_1_reg(r1: i32, imm: i32) { write_reg16(r1, or16(read_reg16(r1), imm)); }
-pub unsafe fn instr16_81_2_mem(addr: i32, imm: i32) { safe_read_write16(addr, &|x| adc16(x, imm)) }
+pub unsafe fn instr16_81_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| adc16(x, imm))
+}
 pub unsafe fn instr16_81_2_reg(r1: i32, imm: i32) { write_reg16(r1, adc16(read_reg16(r1), imm)); }
-pub unsafe fn instr16_81_3_mem(addr: i32, imm: i32) { safe_read_write16(addr, &|x| sbb16(x, imm)) }
+pub unsafe fn instr16_81_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sbb16(x, imm))
+}
 pub unsafe fn instr16_81_3_reg(r1: i32, imm: i32) { write_reg16(r1, sbb16(read_reg16(r1), imm)); }
-pub unsafe fn instr16_81_4_mem(addr: i32, imm: i32) { safe_read_write16(addr, &|x| and16(x, imm)) }
+pub unsafe fn instr16_81_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| and16(x, imm))
+}
 pub unsafe fn instr16_81_4_reg(r1: i32, imm: i32) { write_reg16(r1, and16(read_reg16(r1), imm)); }
-pub unsafe fn instr16_81_5_mem(addr: i32, imm: i32) { safe_read_write16(addr, &|x| sub16(x, imm)) }
+pub unsafe fn instr16_81_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sub16(x, imm))
+}
 pub unsafe fn instr16_81_5_reg(r1: i32, imm: i32) { write_reg16(r1, sub16(read_reg16(r1), imm)); }
-pub unsafe fn instr16_81_6_mem(addr: i32, imm: i32) { safe_read_write16(addr, &|x| xor16(x, imm)) }
+pub unsafe fn instr16_81_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| xor16(x, imm))
+}
 pub unsafe fn instr16_81_6_reg(r1: i32, imm: i32) { write_reg16(r1, xor16(read_reg16(r1), imm)); }
 pub unsafe fn instr16_81_7_reg(r: i32, imm: i32) { cmp16(read_reg16(r), imm); }
-pub unsafe fn instr16_81_7_mem(addr: i32, imm: i32) {
-    cmp16(return_on_pagefault!(safe_read16(addr)), imm);
+pub unsafe fn instr16_81_7_mem(seg: i32, addr: i32, imm: i32) {
+    cmp16(return_on_pagefault!(safe_read16_seg(seg, addr)), imm);
+}
+pub unsafe fn instr32_81_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| add32(x, imm))
 }
-pub unsafe fn instr32_81_0_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| add32(x, imm)) }
 pub unsafe fn instr32_81_0_reg(r1: i32, imm: i32) { write_reg32(r1, add32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_81_1_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| or32(x, imm)) }
+pub unsafe fn instr32_81_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| or32(x, imm))
+}
 pub unsafe fn instr32_81_1_reg(r1: i32, imm: i32) { write_reg32(r1, or32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_81_2_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| adc32(x, imm)) }
+pub unsafe fn instr32_81_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| adc32(x, imm))
+}
 pub unsafe fn instr32_81_2_reg(r1: i32, imm: i32) { write_reg32(r1, adc32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_81_3_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| sbb32(x, imm)) }
+pub unsafe fn instr32_81_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sbb32(x, imm))
+}
 pub unsafe fn instr32_81_3_reg(r1: i32, imm: i32) { write_reg32(r1, sbb32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_81_4_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| and32(x, imm)) }
+pub unsafe fn instr32_81_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| and32(x, imm))
+}
 pub unsafe fn instr32_81_4_reg(r1: i32, imm: i32) { write_reg32(r1, and32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_81_5_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| sub32(x, imm)) }
+pub unsafe fn instr32_81_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sub32(x, imm))
+}
 pub unsafe fn instr32_81_5_reg(r1: i32, imm: i32) { write_reg32(r1, sub32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_81_6_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| xor32(x, imm)) }
+pub unsafe fn instr32_81_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| xor32(x, imm))
+}
 pub unsafe fn instr32_81_6_reg(r1: i32, imm: i32) { write_reg32(r1, xor32(read_reg32(r1), imm)); }
 pub unsafe fn instr32_81_7_reg(r: i32, imm: i32) { cmp32(read_reg32(r), imm); }
-pub unsafe fn instr32_81_7_mem(addr: i32, imm: i32) {
-    cmp32(return_on_pagefault!(safe_read32s(addr)), imm);
+pub unsafe fn instr32_81_7_mem(seg: i32, addr: i32, imm: i32) {
+    cmp32(return_on_pagefault!(safe_read32s_seg(seg, addr)), imm);
+}
+pub unsafe fn instr_82_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| add8(x, imm))
 }
-pub unsafe fn instr_82_0_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| add8(x, imm)) }
 pub unsafe fn instr_82_0_reg(r1: i32, imm: i32) { write_reg8(r1, add8(read_reg8(r1), imm)); }
-pub unsafe fn instr_82_1_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| or8(x, imm)) }
+pub unsafe fn instr_82_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| or8(x, imm))
+}
 pub unsafe fn instr_82_1_reg(r1: i32, imm: i32) { write_reg8(r1, or8(read_reg8(r1), imm)); }
-pub unsafe fn instr_82_2_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| adc8(x, imm)) }
+pub unsafe fn instr_82_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| adc8(x, imm))
+}
 pub unsafe fn instr_82_2_reg(r1: i32, imm: i32) { write_reg8(r1, adc8(read_reg8(r1), imm)); }
-pub unsafe fn instr_82_3_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| sbb8(x, imm)) }
+pub unsafe fn instr_82_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sbb8(x, imm))
+}
 pub unsafe fn instr_82_3_reg(r1: i32, imm: i32) { write_reg8(r1, sbb8(read_reg8(r1), imm)); }
-pub unsafe fn instr_82_4_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| and8(x, imm)) }
+pub unsafe fn instr_82_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| and8(x, imm))
+}
 pub unsafe fn instr_82_4_reg(r1: i32, imm: i32) { write_reg8(r1, and8(read_reg8(r1), imm)); }
-pub unsafe fn instr_82_5_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| sub8(x, imm)) }
+pub unsafe fn instr_82_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sub8(x, imm))
+}
 pub unsafe fn instr_82_5_reg(r1: i32, imm: i32) { write_reg8(r1, sub8(read_reg8(r1), imm)); }
-pub unsafe fn instr_82_6_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| xor8(x, imm)) }
+pub unsafe fn instr_82_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| xor8(x, imm))
+}
 pub unsafe fn instr_82_6_reg(r1: i32, imm: i32) { write_reg8(r1, xor8(read_reg8(r1), imm)); }
 pub unsafe fn instr_82_7_reg(r: i32, imm: i32) { cmp8(read_reg8(r), imm); }
-pub unsafe fn instr_82_7_mem(addr: i32, imm: i32) {
-    cmp8(return_on_pagefault!(safe_read8(addr)), imm);
+pub unsafe fn instr_82_7_mem(seg: i32, addr: i32, imm: i32) {
+    cmp8(return_on_pagefault!(safe_read8_seg(seg, addr)), imm);
 }
-pub unsafe fn instr16_83_0_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| add16(x, imm & 0xFFFF))
+pub unsafe fn instr16_83_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| add16(x, imm & 0xFFFF))
 }
 pub unsafe fn instr16_83_0_reg(r1: i32, imm: i32) {
     write_reg16(r1, add16(read_reg16(r1), imm & 0xFFFF));
 }
-pub unsafe fn instr16_83_1_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| or16(x, imm & 0xFFFF))
+pub unsafe fn instr16_83_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| or16(x, imm & 0xFFFF))
 }
 pub unsafe fn instr16_83_1_reg(r1: i32, imm: i32) {
     write_reg16(r1, or16(read_reg16(r1), imm & 0xFFFF));
 }
-pub unsafe fn instr16_83_2_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| adc16(x, imm & 0xFFFF))
+pub unsafe fn instr16_83_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| adc16(x, imm & 0xFFFF))
 }
 pub unsafe fn instr16_83_2_reg(r1: i32, imm: i32) {
     write_reg16(r1, adc16(read_reg16(r1), imm & 0xFFFF));
 }
-pub unsafe fn instr16_83_3_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| sbb16(x, imm & 0xFFFF))
+pub unsafe fn instr16_83_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sbb16(x, imm & 0xFFFF))
 }
 pub unsafe fn instr16_83_3_reg(r1: i32, imm: i32) {
     write_reg16(r1, sbb16(read_reg16(r1), imm & 0xFFFF));
 }
-pub unsafe fn instr16_83_4_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| and16(x, imm & 0xFFFF))
+pub unsafe fn instr16_83_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| and16(x, imm & 0xFFFF))
 }
 pub unsafe fn instr16_83_4_reg(r1: i32, imm: i32) {
     write_reg16(r1, and16(read_reg16(r1), imm & 0xFFFF));
 }
-pub unsafe fn instr16_83_5_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| sub16(x, imm & 0xFFFF))
+pub unsafe fn instr16_83_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sub16(x, imm & 0xFFFF))
 }
 pub unsafe fn instr16_83_5_reg(r1: i32, imm: i32) {
     write_reg16(r1, sub16(read_reg16(r1), imm & 0xFFFF));
 }
-pub unsafe fn instr16_83_6_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| xor16(x, imm & 0xFFFF))
+pub unsafe fn instr16_83_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| xor16(x, imm & 0xFFFF))
 }
 pub unsafe fn instr16_83_6_reg(r1: i32, imm: i32) {
     write_reg16(r1, xor16(read_reg16(r1), imm & 0xFFFF));
 }
 pub unsafe fn instr16_83_7_reg(r: i32, imm: i32) { cmp16(read_reg16(r), imm & 0xFFFF); }
-pub unsafe fn instr16_83_7_mem(addr: i32, imm: i32) {
-    cmp16(return_on_pagefault!(safe_read16(addr)), imm & 0xFFFF);
+pub unsafe fn instr16_83_7_mem(seg: i32, addr: i32, imm: i32) {
+    cmp16(return_on_pagefault!(safe_read16_seg(seg, addr)), imm & 0xFFFF);
 }
 
-pub unsafe fn instr32_83_0_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| add32(x, imm)) }
+pub unsafe fn instr32_83_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| add32(x, imm))
+}
 pub unsafe fn instr32_83_0_reg(r1: i32, imm: i32) { write_reg32(r1, add32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_83_1_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| or32(x, imm)) }
+pub unsafe fn instr32_83_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| or32(x, imm))
+}
 pub unsafe fn instr32_83_1_reg(r1: i32, imm: i32) { write_reg32(r1, or32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_83_2_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| adc32(x, imm)) }
+pub unsafe fn instr32_83_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| adc32(x, imm))
+}
 pub unsafe fn instr32_83_2_reg(r1: i32, imm: i32) { write_reg32(r1, adc32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_83_3_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| sbb32(x, imm)) }
+pub unsafe fn instr32_83_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sbb32(x, imm))
+}
 pub unsafe fn instr32_83_3_reg(r1: i32, imm: i32) { write_reg32(r1, sbb32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_83_4_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| and32(x, imm)) }
+pub unsafe fn instr32_83_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| and32(x, imm))
+}
 pub unsafe fn instr32_83_4_reg(r1: i32, imm: i32) { write_reg32(r1, and32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_83_5_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| sub32(x, imm)) }
+pub unsafe fn instr32_83_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sub32(x, imm))
+}
 pub unsafe fn instr32_83_5_reg(r1: i32, imm: i32) { write_reg32(r1, sub32(read_reg32(r1), imm)); }
-pub unsafe fn instr32_83_6_mem(addr: i32, imm: i32) { safe_read_write32(addr, &|x| xor32(x, imm)) }
+pub unsafe fn instr32_83_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| xor32(x, imm))
+}
 pub unsafe fn instr32_83_6_reg(r1: i32, imm: i32) { write_reg32(r1, xor32(read_reg32(r1), imm)); }
 pub unsafe fn instr32_83_7_reg(r: i32, imm: i32) { cmp32(read_reg32(r), imm); }
-pub unsafe fn instr32_83_7_mem(addr: i32, imm: i32) {
-    cmp32(return_on_pagefault!(safe_read32s(addr)), imm);
+pub unsafe fn instr32_83_7_mem(seg: i32, addr: i32, imm: i32) {
+    cmp32(return_on_pagefault!(safe_read32s_seg(seg, addr)), imm);
 }
 
-pub unsafe fn instr_84_mem(addr: i32, r: i32) {
-    test8(return_on_pagefault!(safe_read8(addr)), read_reg8(r));
+pub unsafe fn instr_84_mem(seg: i32, addr: i32, r: i32) {
+    test8(return_on_pagefault!(safe_read8_seg(seg, addr)), read_reg8(r));
 }
 pub unsafe fn instr_84_reg(r1: i32, r: i32) { test8(read_reg8(r1), read_reg8(r)); }
-pub unsafe fn instr16_85_mem(addr: i32, r: i32) {
-    test16(return_on_pagefault!(safe_read16(addr)), read_reg16(r));
+pub unsafe fn instr16_85_mem(seg: i32, addr: i32, r: i32) {
+    test16(return_on_pagefault!(safe_read16_seg(seg, addr)), read_reg16(r));
 }
 pub unsafe fn instr16_85_reg(r1: i32, r: i32) { test16(read_reg16(r1), read_reg16(r)); }
-pub unsafe fn instr32_85_mem(addr: i32, r: i32) {
-    test32(return_on_pagefault!(safe_read32s(addr)), read_reg32(r));
+pub unsafe fn instr32_85_mem(seg: i32, addr: i32, r: i32) {
+    test32(return_on_pagefault!(safe_read32s_seg(seg, addr)), read_reg32(r));
 }
 pub unsafe fn instr32_85_reg(r1: i32, r: i32) { test32(read_reg32(r1), read_reg32(r)); }
-pub unsafe fn instr_86_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| xchg8(x, r)) }
+pub unsafe fn instr_86_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| xchg8(x, r))
+}
 pub unsafe fn instr_86_reg(r1: i32, r: i32) { write_reg8(r1, xchg8(read_reg8(r1), r)); }
-pub unsafe fn instr16_87_mem(addr: i32, r: i32) { safe_read_write16(addr, &|x| xchg16(x, r)) }
+pub unsafe fn instr16_87_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| xchg16(x, r))
+}
 pub unsafe fn instr16_87_reg(r1: i32, r: i32) { write_reg16(r1, xchg16(read_reg16(r1), r)); }
-pub unsafe fn instr32_87_mem(addr: i32, r: i32) { safe_read_write32(addr, &|x| xchg32(x, r)) }
+pub unsafe fn instr32_87_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| xchg32(x, r))
+}
 pub unsafe fn instr32_87_reg(r1: i32, r: i32) { write_reg32(r1, xchg32(read_reg32(r1), r)); }
 pub unsafe fn instr_88_reg(r2: i32, r: i32) { write_reg8(r2, read_reg8(r)); }
-pub unsafe fn instr_88_mem(addr: i32, r: i32) {
-    return_on_pagefault!(safe_write8(addr, read_reg8(r)));
+pub unsafe fn instr_88_mem(seg: i32, addr: i32, r: i32) {
+    return_on_pagefault!(safe_write8_seg(seg, addr, read_reg8(r)));
 }
 pub unsafe fn instr16_89_reg(r2: i32, r: i32) { write_reg16(r2, read_reg16(r)); }
-pub unsafe fn instr16_89_mem(addr: i32, r: i32) {
-    return_on_pagefault!(safe_write16(addr, read_reg16(r)));
+pub unsafe fn instr16_89_mem(seg: i32, addr: i32, r: i32) {
+    return_on_pagefault!(safe_write16_seg(seg, addr, read_reg16(r)));
 }
 pub unsafe fn instr32_89_reg(r2: i32, r: i32) { write_reg32(r2, read_reg32(r)); }
-pub unsafe fn instr32_89_mem(addr: i32, r: i32) {
-    return_on_pagefault!(safe_write32(addr, read_reg32(r)));
+pub unsafe fn instr32_89_mem(seg: i32, addr: i32, r: i32) {
+    return_on_pagefault!(safe_write32_seg(seg, addr, read_reg32(r)));
 }
-pub unsafe fn instr_8A_mem(addr: i32, r: i32) {
-    write_reg8(r, return_on_pagefault!(safe_read8(addr)));
+pub unsafe fn instr_8A_mem(seg: i32, addr: i32, r: i32) {
+    write_reg8(r, return_on_pagefault!(safe_read8_seg(seg, addr)));
 }
 pub unsafe fn instr_8A_reg(r1: i32, r: i32) { write_reg8(r, read_reg8(r1)); }
-pub unsafe fn instr16_8B_mem(addr: i32, r: i32) {
-    write_reg16(r, return_on_pagefault!(safe_read16(addr)));
+pub unsafe fn instr16_8B_mem(seg: i32, addr: i32, r: i32) {
+    write_reg16(r, return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 pub unsafe fn instr16_8B_reg(r1: i32, r: i32) { write_reg16(r, read_reg16(r1)); }
-pub unsafe fn instr32_8B_mem(addr: i32, r: i32) {
-    write_reg32(r, return_on_pagefault!(safe_read32s(addr)));
+pub unsafe fn instr32_8B_mem(seg: i32, addr: i32, r: i32) {
+    write_reg32(r, return_on_pagefault!(safe_read32s_seg(seg, addr)));
 }
 pub unsafe fn instr32_8B_reg(r1: i32, r: i32) { write_reg32(r, read_reg32(r1)); }
 
@@ -870,9 +964,9 @@ pub unsafe fn instr16_8C_reg(r: i32, seg: i32) {
         write_reg16(r, *sreg.offset(seg as isize) as i32);
     };
 }
-pub unsafe fn instr16_8C_mem(addr: i32, seg: i32) {
-    if instr_8C_check_sreg(seg) {
-        return_on_pagefault!(safe_write16(addr, *sreg.offset(seg as isize) as i32));
+pub unsafe fn instr16_8C_mem(seg: i32, addr: i32, seg_reg: i32) {
+    if instr_8C_check_sreg(seg_reg) {
+        return_on_pagefault!(safe_write16_seg(seg, addr, *sreg.offset(seg_reg as isize) as i32));
     };
 }
 pub unsafe fn instr32_8C_reg(r: i32, seg: i32) {
@@ -880,9 +974,9 @@ pub unsafe fn instr32_8C_reg(r: i32, seg: i32) {
         write_reg32(r, *sreg.offset(seg as isize) as i32);
     };
 }
-pub unsafe fn instr32_8C_mem(addr: i32, seg: i32) {
-    if instr_8C_check_sreg(seg) {
-        return_on_pagefault!(safe_write16(addr, *sreg.offset(seg as isize) as i32));
+pub unsafe fn instr32_8C_mem(seg: i32, addr: i32, seg_reg: i32) {
+    if instr_8C_check_sreg(seg_reg) {
+        return_on_pagefault!(safe_write16_seg(seg, addr, *sreg.offset(seg_reg as isize) as i32));
     };
 }
 
@@ -893,7 +987,7 @@ pub unsafe fn instr16_8D_reg(_r: i32, _r2: i32) {
 pub unsafe fn instr16_8D_mem(modrm_byte: i32, r: i32) {
     // lea
     *prefixes |= prefix::SEG_PREFIX_ZERO;
-    if let Ok(addr) = modrm_resolve(modrm_byte) {
+    if let Ok((_, addr)) = modrm_resolve(modrm_byte) {
         write_reg16(r, addr);
     }
     *prefixes = 0;
@@ -906,18 +1000,21 @@ pub unsafe fn instr32_8D_mem(modrm_byte: i32, r: i32) {
     // lea
     // override prefix, so modrm_resolve does not return the segment part
     *prefixes |= prefix::SEG_PREFIX_ZERO;
-    if let Ok(addr) = modrm_resolve(modrm_byte) {
+    if let Ok((_, addr)) = modrm_resolve(modrm_byte) {
         write_reg32(r, addr);
     }
     *prefixes = 0;
 }
 
 #[no_mangle]
-pub unsafe fn instr_8E_mem(addr: i32, r: i32) {
+pub unsafe fn instr_8E_mem(seg: i32, addr: i32, r: i32) {
     if r == ES || r == SS || r == DS || r == FS || r == GS {
-        if !switch_seg(r, return_on_pagefault!(safe_read16(addr))) {
+        if !switch_seg(r, return_on_pagefault!(safe_read16_seg(seg, addr))) {
             return;
         }
+        if r == SS {
+            inhibit_debug_traps();
+        }
     }
     else {
         dbg_log!("mov sreg #ud");
@@ -927,7 +1024,9 @@ pub unsafe fn instr_8E_mem(addr: i32, r: i32) {
 #[no_mangle]
 pub unsafe fn instr_8E_reg(r1: i32, r: i32) {
     if r == ES || r == SS || r == DS || r == FS || r == GS {
-        switch_seg(r, read_reg16(r1));
+        if switch_seg(r, read_reg16(r1)) && r == SS {
+            inhibit_debug_traps();
+        }
     }
     else {
         dbg_log!("mov sreg #ud");
@@ -944,10 +1043,10 @@ pub unsafe fn instr16_8F_0_mem(modrm_byte: i32) {
             // a pagefault happened, reset esp
             adjust_stack_reg(-2);
         },
-        Ok(addr) => {
+        Ok((seg, addr)) => {
             adjust_stack_reg(-2);
-            let stack_value = return_on_pagefault!(safe_read16(get_stack_pointer(0)));
-            return_on_pagefault!(safe_write16(addr, stack_value));
+            let stack_value = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0)));
+            return_on_pagefault!(safe_write16_seg(seg, addr, stack_value));
             adjust_stack_reg(2);
         },
     }
@@ -961,10 +1060,10 @@ pub unsafe fn instr32_8F_0_mem(modrm_byte: i32) {
             // a pagefault happened, reset esp
             adjust_stack_reg(-4);
         },
-        Ok(addr) => {
+        Ok((seg, addr)) => {
             adjust_stack_reg(-4);
-            let stack_value = return_on_pagefault!(safe_read32s(get_stack_pointer(0)));
-            return_on_pagefault!(safe_write32(addr, stack_value));
+            let stack_value = return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0)));
+            return_on_pagefault!(safe_write32_seg(seg, addr, stack_value));
             adjust_stack_reg(4);
         },
     }
@@ -1086,37 +1185,34 @@ pub unsafe fn instr_9F() {
 
 pub unsafe fn instr_A0(moffs: i32) {
     // mov
-    let data = return_on_pagefault!(safe_read8(return_on_pagefault!(get_seg_prefix_ds(moffs))));
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(moffs));
+    let data = return_on_pagefault!(safe_read8_seg(seg, addr));
     write_reg8(AL, data);
 }
 pub unsafe fn instr16_A1(moffs: i32) {
     // mov
-    let data = return_on_pagefault!(safe_read16(return_on_pagefault!(get_seg_prefix_ds(moffs))));
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(moffs));
+    let data = return_on_pagefault!(safe_read16_seg(seg, addr));
     write_reg16(AX, data);
 }
 pub unsafe fn instr32_A1(moffs: i32) {
-    let data = return_on_pagefault!(safe_read32s(return_on_pagefault!(get_seg_prefix_ds(moffs))));
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(moffs));
+    let data = return_on_pagefault!(safe_read32s_seg(seg, addr));
     write_reg32(EAX, data);
 }
 pub unsafe fn instr_A2(moffs: i32) {
     // mov
-    return_on_pagefault!(safe_write8(
-        return_on_pagefault!(get_seg_prefix_ds(moffs)),
-        read_reg8(AL)
-    ));
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(moffs));
+    return_on_pagefault!(safe_write8_seg(seg, addr, read_reg8(AL)));
 }
 pub unsafe fn instr16_A3(moffs: i32) {
     // mov
-    return_on_pagefault!(safe_write16(
-        return_on_pagefault!(get_seg_prefix_ds(moffs)),
-        read_reg16(AX)
-    ));
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(moffs));
+    return_on_pagefault!(safe_write16_seg(seg, addr, read_reg16(AX)));
 }
 pub unsafe fn instr32_A3(moffs: i32) {
-    return_on_pagefault!(safe_write32(
-        return_on_pagefault!(get_seg_prefix_ds(moffs)),
-        read_reg32(EAX)
-    ));
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(moffs));
+    return_on_pagefault!(safe_write32_seg(seg, addr, read_reg32(EAX)));
 }
 
 pub unsafe fn instr_A4() { movsb_no_rep(is_asize_32(), segment_prefix(DS)); }
@@ -1202,114 +1298,130 @@ pub unsafe fn instr32_BE(imm: i32) { write_reg32(ESI, imm); }
 pub unsafe fn instr16_BF(imm: i32) { write_reg16(DI, imm); }
 pub unsafe fn instr32_BF(imm: i32) { write_reg32(EDI, imm); }
 
-pub unsafe fn instr_C0_0_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| rol8(x, imm & 31)) }
+pub unsafe fn instr_C0_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rol8(x, imm & 31))
+}
 pub unsafe fn instr_C0_0_reg(r1: i32, imm: i32) { write_reg8(r1, rol8(read_reg8(r1), imm & 31)); }
-pub unsafe fn instr_C0_1_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| ror8(x, imm & 31)) }
+pub unsafe fn instr_C0_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| ror8(x, imm & 31))
+}
 pub unsafe fn instr_C0_1_reg(r1: i32, imm: i32) { write_reg8(r1, ror8(read_reg8(r1), imm & 31)); }
-pub unsafe fn instr_C0_2_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| rcl8(x, imm & 31)) }
+pub unsafe fn instr_C0_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rcl8(x, imm & 31))
+}
 pub unsafe fn instr_C0_2_reg(r1: i32, imm: i32) { write_reg8(r1, rcl8(read_reg8(r1), imm & 31)); }
-pub unsafe fn instr_C0_3_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| rcr8(x, imm & 31)) }
+pub unsafe fn instr_C0_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rcr8(x, imm & 31))
+}
 pub unsafe fn instr_C0_3_reg(r1: i32, imm: i32) { write_reg8(r1, rcr8(read_reg8(r1), imm & 31)); }
-pub unsafe fn instr_C0_4_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| shl8(x, imm & 31)) }
+pub unsafe fn instr_C0_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shl8(x, imm & 31))
+}
 pub unsafe fn instr_C0_4_reg(r1: i32, imm: i32) { write_reg8(r1, shl8(read_reg8(r1), imm & 31)); }
-pub unsafe fn instr_C0_5_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| shr8(x, imm & 31)) }
+pub unsafe fn instr_C0_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shr8(x, imm & 31))
+}
 pub unsafe fn instr_C0_5_reg(r1: i32, imm: i32) { write_reg8(r1, shr8(read_reg8(r1), imm & 31)); }
-pub unsafe fn instr_C0_6_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| shl8(x, imm & 31)) }
+pub unsafe fn instr_C0_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shl8(x, imm & 31))
+}
 pub unsafe fn instr_C0_6_reg(r1: i32, imm: i32) { write_reg8(r1, shl8(read_reg8(r1), imm & 31)); }
-pub unsafe fn instr_C0_7_mem(addr: i32, imm: i32) { safe_read_write8(addr, &|x| sar8(x, imm & 31)) }
+pub unsafe fn instr_C0_7_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sar8(x, imm & 31))
+}
 pub unsafe fn instr_C0_7_reg(r1: i32, imm: i32) { write_reg8(r1, sar8(read_reg8(r1), imm & 31)); }
-pub unsafe fn instr16_C1_0_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| rol16(x, imm & 31))
+pub unsafe fn instr16_C1_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rol16(x, imm & 31))
 }
 pub unsafe fn instr16_C1_0_reg(r1: i32, imm: i32) {
     write_reg16(r1, rol16(read_reg16(r1), imm & 31));
 }
-pub unsafe fn instr16_C1_1_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| ror16(x, imm & 31))
+pub unsafe fn instr16_C1_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| ror16(x, imm & 31))
 }
 pub unsafe fn instr16_C1_1_reg(r1: i32, imm: i32) {
     write_reg16(r1, ror16(read_reg16(r1), imm & 31));
 }
-pub unsafe fn instr16_C1_2_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| rcl16(x, imm & 31))
+pub unsafe fn instr16_C1_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rcl16(x, imm & 31))
 }
 pub unsafe fn instr16_C1_2_reg(r1: i32, imm: i32) {
     write_reg16(r1, rcl16(read_reg16(r1), imm & 31));
 }
-pub unsafe fn instr16_C1_3_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| rcr16(x, imm & 31))
+pub unsafe fn instr16_C1_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rcr16(x, imm & 31))
 }
 pub unsafe fn instr16_C1_3_reg(r1: i32, imm: i32) {
     write_reg16(r1, rcr16(read_reg16(r1), imm & 31));
 }
-pub unsafe fn instr16_C1_4_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| shl16(x, imm & 31))
+pub unsafe fn instr16_C1_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shl16(x, imm & 31))
 }
 pub unsafe fn instr16_C1_4_reg(r1: i32, imm: i32) {
     write_reg16(r1, shl16(read_reg16(r1), imm & 31));
 }
-pub unsafe fn instr16_C1_5_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| shr16(x, imm & 31))
+pub unsafe fn instr16_C1_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shr16(x, imm & 31))
 }
 pub unsafe fn instr16_C1_5_reg(r1: i32, imm: i32) {
     write_reg16(r1, shr16(read_reg16(r1), imm & 31));
 }
-pub unsafe fn instr16_C1_6_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| shl16(x, imm & 31))
+pub unsafe fn instr16_C1_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shl16(x, imm & 31))
 }
 pub unsafe fn instr16_C1_6_reg(r1: i32, imm: i32) {
     write_reg16(r1, shl16(read_reg16(r1), imm & 31));
 }
-pub unsafe fn instr16_C1_7_mem(addr: i32, imm: i32) {
-    safe_read_write16(addr, &|x| sar16(x, imm & 31))
+pub unsafe fn instr16_C1_7_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sar16(x, imm & 31))
 }
 pub unsafe fn instr16_C1_7_reg(r1: i32, imm: i32) {
     write_reg16(r1, sar16(read_reg16(r1), imm & 31));
 }
-pub unsafe fn instr32_C1_0_mem(addr: i32, imm: i32) {
-    safe_read_write32(addr, &|x| rol32(x, imm & 31))
+pub unsafe fn instr32_C1_0_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rol32(x, imm & 31))
 }
 pub unsafe fn instr32_C1_0_reg(r1: i32, imm: i32) {
     write_reg32(r1, rol32(read_reg32(r1), imm & 31));
 }
-pub unsafe fn instr32_C1_1_mem(addr: i32, imm: i32) {
-    safe_read_write32(addr, &|x| ror32(x, imm & 31))
+pub unsafe fn instr32_C1_1_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| ror32(x, imm & 31))
 }
 pub unsafe fn instr32_C1_1_reg(r1: i32, imm: i32) {
     write_reg32(r1, ror32(read_reg32(r1), imm & 31));
 }
-pub unsafe fn instr32_C1_2_mem(addr: i32, imm: i32) {
-    safe_read_write32(addr, &|x| rcl32(x, imm & 31))
+pub unsafe fn instr32_C1_2_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rcl32(x, imm & 31))
 }
 pub unsafe fn instr32_C1_2_reg(r1: i32, imm: i32) {
     write_reg32(r1, rcl32(read_reg32(r1), imm & 31));
 }
-pub unsafe fn instr32_C1_3_mem(addr: i32, imm: i32) {
-    safe_read_write32(addr, &|x| rcr32(x, imm & 31))
+pub unsafe fn instr32_C1_3_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rcr32(x, imm & 31))
 }
 pub unsafe fn instr32_C1_3_reg(r1: i32, imm: i32) {
     write_reg32(r1, rcr32(read_reg32(r1), imm & 31));
 }
-pub unsafe fn instr32_C1_4_mem(addr: i32, imm: i32) {
-    safe_read_write32(addr, &|x| shl32(x, imm & 31))
+pub unsafe fn instr32_C1_4_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shl32(x, imm & 31))
 }
 pub unsafe fn instr32_C1_4_reg(r1: i32, imm: i32) {
     write_reg32(r1, shl32(read_reg32(r1), imm & 31));
 }
-pub unsafe fn instr32_C1_5_mem(addr: i32, imm: i32) {
-    safe_read_write32(addr, &|x| shr32(x, imm & 31))
+pub unsafe fn instr32_C1_5_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shr32(x, imm & 31))
 }
 pub unsafe fn instr32_C1_5_reg(r1: i32, imm: i32) {
     write_reg32(r1, shr32(read_reg32(r1), imm & 31));
 }
-pub unsafe fn instr32_C1_6_mem(addr: i32, imm: i32) {
-    safe_read_write32(addr, &|x| shl32(x, imm & 31))
+pub unsafe fn instr32_C1_6_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shl32(x, imm & 31))
 }
 pub unsafe fn instr32_C1_6_reg(r1: i32, imm: i32) {
     write_reg32(r1, shl32(read_reg32(r1), imm & 31));
 }
-pub unsafe fn instr32_C1_7_mem(addr: i32, imm: i32) {
-    safe_read_write32(addr, &|x| sar32(x, imm & 31))
+pub unsafe fn instr32_C1_7_mem(seg: i32, addr: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sar32(x, imm & 31))
 }
 pub unsafe fn instr32_C1_7_reg(r1: i32, imm: i32) {
     write_reg32(r1, sar32(read_reg32(r1), imm & 31));
@@ -1343,34 +1455,104 @@ pub unsafe fn instr32_C3() {
     *instruction_pointer = cs + ip;
 }
 
+pub unsafe fn instr16_C4() {
+    let modrm_byte = return_on_pagefault!(read_imm8());
+    if modrm_byte < 0xC0 {
+        let (seg, addr) = return_on_pagefault!(modrm_resolve(modrm_byte));
+        instr16_C4_mem(seg, addr, modrm_byte >> 3 & 7);
+        after_block_boundary();
+    }
+    else if *protected_mode && !vm86_mode() {
+        instr_vex(modrm_byte as u8);
+    }
+    else {
+        trigger_ud();
+    }
+}
+pub unsafe fn instr32_C4() {
+    let modrm_byte = return_on_pagefault!(read_imm8());
+    if modrm_byte < 0xC0 {
+        let (seg, addr) = return_on_pagefault!(modrm_resolve(modrm_byte));
+        instr32_C4_mem(seg, addr, modrm_byte >> 3 & 7);
+        after_block_boundary();
+    }
+    else if *protected_mode && !vm86_mode() {
+        instr_vex(modrm_byte as u8);
+    }
+    else {
+        trigger_ud();
+    }
+}
 #[no_mangle]
-pub unsafe fn instr16_C4_reg(_unused1: i32, _unused2: i32) { trigger_ud(); }
-#[no_mangle]
-pub unsafe fn instr16_C4_mem(addr: i32, r: i32) { lss16(addr, r, ES); }
-#[no_mangle]
-pub unsafe fn instr32_C4_reg(_unused1: i32, _unused2: i32) { trigger_ud(); }
+pub unsafe fn instr16_C4_mem(seg: i32, addr: i32, r: i32) { lss16(seg, addr, r, ES); }
 #[no_mangle]
-pub unsafe fn instr32_C4_mem(addr: i32, r: i32) { lss32(addr, r, ES); }
+pub unsafe fn instr32_C4_mem(seg: i32, addr: i32, r: i32) { lss32(seg, addr, r, ES); }
+unsafe fn instr_vex(byte1: u8) {
+    let vex = Vex::decode(byte1, return_on_pagefault!(read_imm8()) as u8);
+    let opcode = return_on_pagefault!(read_imm8()) as u8;
+    let modrm_byte = return_on_pagefault!(read_imm8());
+    let instruction = vex.instruction(*prefixes, opcode, modrm_byte as u8);
+    if instruction == VexInstruction::Invalid || !cpuid::has_feature(instruction.feature()) {
+        trigger_ud();
+        return;
+    }
+    // bmi instructions always operate on 32-bit registers outside of 64-bit mode
+    let addr = if modrm_byte < 0xC0 {
+        Some(return_on_pagefault!(modrm_resolve(modrm_byte)))
+    }
+    else {
+        None
+    };
+    let imm8 = if vex.has_imm8() { return_on_pagefault!(read_imm8()) } else { 0 };
+    let source = match addr {
+        Some((seg, addr)) => return_on_pagefault!(safe_read32s_seg(seg, addr)),
+        None => read_reg32(modrm_byte & 7),
+    };
+    let r = modrm_byte >> 3 & 7;
+    match instruction {
+        VexInstruction::Andn => write_reg32(r, andn(read_reg32(vex.vvvv), source)),
+        VexInstruction::Bextr => write_reg32(r, bextr(source, read_reg32(vex.vvvv))),
+        VexInstruction::Blsi => write_reg32(vex.vvvv, blsi(source)),
+        VexInstruction::Blsmsk => write_reg32(vex.vvvv, blsmsk(source)),
+        VexInstruction::Blsr => write_reg32(vex.vvvv, blsr(source)),
+        VexInstruction::Bzhi => write_reg32(r, bzhi(source, read_reg32(vex.vvvv))),
+        VexInstruction::Mulx => {
+            let result = read_reg32(EDX) as u32 as u64 * source as u32 as u64;
+            // the high half wins if both destinations are the same register
+            write_reg32(vex.vvvv, result as i32);
+            write_reg32(r, (result >> 32) as i32);
+        },
+        VexInstruction::Pdep => write_reg32(r, pdep(read_reg32(vex.vvvv), source)),
+        VexInstruction::Pext => write_reg32(r, pext(read_reg32(vex.vvvv), source)),
+        VexInstruction::Rorx => write_reg32(r, source.rotate_right(imm8 as u32 & 31)),
+        VexInstruction::Sarx => write_reg32(r, source >> (read_reg32(vex.vvvv) & 31)),
+        VexInstruction::Shlx => write_reg32(r, source << (read_reg32(vex.vvvv) & 31)),
+        VexInstruction::Shrx => {
+            write_reg32(r, (source as u32 >> (read_reg32(vex.vvvv) & 31)) as i32)
+        },
+        VexInstruction::Invalid => dbg_assert!(false),
+    }
+}
 #[no_mangle]
 pub unsafe fn instr16_C5_reg(_unused1: i32, _unused2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr16_C5_mem(addr: i32, r: i32) { lss16(addr, r, DS); }
+pub unsafe fn instr16_C5_mem(seg: i32, addr: i32, r: i32) { lss16(seg, addr, r, DS); }
 #[no_mangle]
 pub unsafe fn instr32_C5_reg(_unused1: i32, _unused2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr32_C5_mem(addr: i32, r: i32) { lss32(addr, r, DS); }
+pub unsafe fn instr32_C5_mem(seg: i32, addr: i32, r: i32) { lss32(seg, addr, r, DS); }
 
 pub unsafe fn instr_C6_0_reg(r: i32, imm: i32) { write_reg8(r, imm); }
-pub unsafe fn instr_C6_0_mem(addr: i32, imm: i32) {
-    return_on_pagefault!(safe_write8(addr, imm));
+pub unsafe fn instr_C6_0_mem(seg: i32, addr: i32, imm: i32) {
+    return_on_pagefault!(safe_write8_seg(seg, addr, imm));
 }
 pub unsafe fn instr16_C7_0_reg(r: i32, imm: i32) { write_reg16(r, imm); }
-pub unsafe fn instr16_C7_0_mem(addr: i32, imm: i32) {
-    return_on_pagefault!(safe_write16(addr, imm));
+pub unsafe fn instr16_C7_0_mem(seg: i32, addr: i32, imm: i32) {
+    return_on_pagefault!(safe_write16_seg(seg, addr, imm));
 }
 pub unsafe fn instr32_C7_0_reg(r: i32, imm: i32) { write_reg32(r, imm); }
-pub unsafe fn instr32_C7_0_mem(addr: i32, imm: i32) {
-    return_on_pagefault!(safe_write32(addr, imm));
+pub unsafe fn instr32_C7_0_mem(seg: i32, addr: i32, imm: i32) {
+    return_on_pagefault!(safe_write32_seg(seg, addr, imm));
 }
 
 #[no_mangle]
@@ -1381,44 +1563,44 @@ pub unsafe fn instr32_C8(size: i32, nesting: i32) { enter32(size, nesting); }
 pub unsafe fn instr16_C9() {
     // leave
     let old_vbp = if *stack_size_32 { read_reg32(EBP) } else { read_reg16(BP) };
-    let new_bp = return_on_pagefault!(safe_read16(get_seg_ss() + old_vbp));
+    let new_bp = return_on_pagefault!(safe_read16_seg(SS, get_seg_ss() + old_vbp));
     set_stack_reg(old_vbp + 2);
     write_reg16(BP, new_bp);
 }
 pub unsafe fn instr32_C9() {
     let old_vbp = if *stack_size_32 { read_reg32(EBP) } else { read_reg16(BP) };
-    let new_ebp = return_on_pagefault!(safe_read32s(get_seg_ss() + old_vbp));
+    let new_ebp = return_on_pagefault!(safe_read32s_seg(SS, get_seg_ss() + old_vbp));
     set_stack_reg(old_vbp + 4);
     write_reg32(EBP, new_ebp);
 }
 #[no_mangle]
 pub unsafe fn instr16_CA(imm16: i32) {
     // retf
-    let ip = return_on_pagefault!(safe_read16(get_stack_pointer(0)));
-    let cs = return_on_pagefault!(safe_read16(get_stack_pointer(2)));
+    let ip = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0)));
+    let cs = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(2)));
     far_return(ip, cs, imm16, false);
 }
 #[no_mangle]
 pub unsafe fn instr32_CA(imm16: i32) {
     // retf
-    let ip = return_on_pagefault!(safe_read32s(get_stack_pointer(0)));
-    let cs = return_on_pagefault!(safe_read32s(get_stack_pointer(4))) & 0xFFFF;
+    let ip = return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0)));
+    let cs = return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(4))) & 0xFFFF;
     far_return(ip, cs, imm16, true);
     dbg_assert!(*is_32 || get_real_eip() < 0x10000);
 }
 #[no_mangle]
 pub unsafe fn instr16_CB() {
     // retf
-    let ip = return_on_pagefault!(safe_read16(get_stack_pointer(0)));
-    let cs = return_on_pagefault!(safe_read16(get_stack_pointer(2)));
+    let ip = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0)));
+    let cs = return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(2)));
     far_return(ip, cs, 0, false);
     dbg_assert!(*is_32 || get_real_eip() < 0x10000);
 }
 #[no_mangle]
 pub unsafe fn instr32_CB() {
     // retf
-    let ip = return_on_pagefault!(safe_read32s(get_stack_pointer(0)));
-    let cs = return_on_pagefault!(safe_read32s(get_stack_pointer(4))) & 0xFFFF;
+    let ip = return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0)));
+    let cs = return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(4))) & 0xFFFF;
     far_return(ip, cs, 0, true);
     dbg_assert!(*is_32 || get_real_eip() < 0x10000);
 }
@@ -1451,162 +1633,226 @@ pub unsafe fn instr16_CF() {
 #[no_mangle]
 pub unsafe fn instr32_CF() { iret32(); }
 
-pub unsafe fn instr_D0_0_mem(addr: i32) { safe_read_write8(addr, &|x| rol8(x, 1)) }
+pub unsafe fn instr_D0_0_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rol8(x, 1))
+}
 pub unsafe fn instr_D0_0_reg(r1: i32) { write_reg8(r1, rol8(read_reg8(r1), 1)); }
-pub unsafe fn instr_D0_1_mem(addr: i32) { safe_read_write8(addr, &|x| ror8(x, 1)) }
+pub unsafe fn instr_D0_1_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| ror8(x, 1))
+}
 pub unsafe fn instr_D0_1_reg(r1: i32) { write_reg8(r1, ror8(read_reg8(r1), 1)); }
-pub unsafe fn instr_D0_2_mem(addr: i32) { safe_read_write8(addr, &|x| rcl8(x, 1)) }
+pub unsafe fn instr_D0_2_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rcl8(x, 1))
+}
 pub unsafe fn instr_D0_2_reg(r1: i32) { write_reg8(r1, rcl8(read_reg8(r1), 1)); }
-pub unsafe fn instr_D0_3_mem(addr: i32) { safe_read_write8(addr, &|x| rcr8(x, 1)) }
+pub unsafe fn instr_D0_3_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rcr8(x, 1))
+}
 pub unsafe fn instr_D0_3_reg(r1: i32) { write_reg8(r1, rcr8(read_reg8(r1), 1)); }
-pub unsafe fn instr_D0_4_mem(addr: i32) { safe_read_write8(addr, &|x| shl8(x, 1)) }
+pub unsafe fn instr_D0_4_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shl8(x, 1))
+}
 pub unsafe fn instr_D0_4_reg(r1: i32) { write_reg8(r1, shl8(read_reg8(r1), 1)); }
-pub unsafe fn instr_D0_5_mem(addr: i32) { safe_read_write8(addr, &|x| shr8(x, 1)) }
+pub unsafe fn instr_D0_5_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shr8(x, 1))
+}
 pub unsafe fn instr_D0_5_reg(r1: i32) { write_reg8(r1, shr8(read_reg8(r1), 1)); }
-pub unsafe fn instr_D0_6_mem(addr: i32) { safe_read_write8(addr, &|x| shl8(x, 1)) }
+pub unsafe fn instr_D0_6_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shl8(x, 1))
+}
 pub unsafe fn instr_D0_6_reg(r1: i32) { write_reg8(r1, shl8(read_reg8(r1), 1)); }
-pub unsafe fn instr_D0_7_mem(addr: i32) { safe_read_write8(addr, &|x| sar8(x, 1)) }
+pub unsafe fn instr_D0_7_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sar8(x, 1))
+}
 pub unsafe fn instr_D0_7_reg(r1: i32) { write_reg8(r1, sar8(read_reg8(r1), 1)); }
-pub unsafe fn instr16_D1_0_mem(addr: i32) { safe_read_write16(addr, &|x| rol16(x, 1)) }
+pub unsafe fn instr16_D1_0_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rol16(x, 1))
+}
 pub unsafe fn instr16_D1_0_reg(r1: i32) { write_reg16(r1, rol16(read_reg16(r1), 1)); }
-pub unsafe fn instr16_D1_1_mem(addr: i32) { safe_read_write16(addr, &|x| ror16(x, 1)) }
+pub unsafe fn instr16_D1_1_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| ror16(x, 1))
+}
 pub unsafe fn instr16_D1_1_reg(r1: i32) { write_reg16(r1, ror16(read_reg16(r1), 1)); }
-pub unsafe fn instr16_D1_2_mem(addr: i32) { safe_read_write16(addr, &|x| rcl16(x, 1)) }
+pub unsafe fn instr16_D1_2_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rcl16(x, 1))
+}
 pub unsafe fn instr16_D1_2_reg(r1: i32) { write_reg16(r1, rcl16(read_reg16(r1), 1)); }
-pub unsafe fn instr16_D1_3_mem(addr: i32) { safe_read_write16(addr, &|x| rcr16(x, 1)) }
+pub unsafe fn instr16_D1_3_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rcr16(x, 1))
+}
 pub unsafe fn instr16_D1_3_reg(r1: i32) { write_reg16(r1, rcr16(read_reg16(r1), 1)); }
-pub unsafe fn instr16_D1_4_mem(addr: i32) { safe_read_write16(addr, &|x| shl16(x, 1)) }
+pub unsafe fn instr16_D1_4_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shl16(x, 1))
+}
 pub unsafe fn instr16_D1_4_reg(r1: i32) { write_reg16(r1, shl16(read_reg16(r1), 1)); }
-pub unsafe fn instr16_D1_5_mem(addr: i32) { safe_read_write16(addr, &|x| shr16(x, 1)) }
+pub unsafe fn instr16_D1_5_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shr16(x, 1))
+}
 pub unsafe fn instr16_D1_5_reg(r1: i32) { write_reg16(r1, shr16(read_reg16(r1), 1)); }
-pub unsafe fn instr16_D1_6_mem(addr: i32) { safe_read_write16(addr, &|x| shl16(x, 1)) }
+pub unsafe fn instr16_D1_6_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shl16(x, 1))
+}
 pub unsafe fn instr16_D1_6_reg(r1: i32) { write_reg16(r1, shl16(read_reg16(r1), 1)); }
-pub unsafe fn instr16_D1_7_mem(addr: i32) { safe_read_write16(addr, &|x| sar16(x, 1)) }
+pub unsafe fn instr16_D1_7_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sar16(x, 1))
+}
 pub unsafe fn instr16_D1_7_reg(r1: i32) { write_reg16(r1, sar16(read_reg16(r1), 1)); }
-pub unsafe fn instr32_D1_0_mem(addr: i32) { safe_read_write32(addr, &|x| rol32(x, 1)) }
+pub unsafe fn instr32_D1_0_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rol32(x, 1))
+}
 pub unsafe fn instr32_D1_0_reg(r1: i32) { write_reg32(r1, rol32(read_reg32(r1), 1)); }
-pub unsafe fn instr32_D1_1_mem(addr: i32) { safe_read_write32(addr, &|x| ror32(x, 1)) }
+pub unsafe fn instr32_D1_1_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| ror32(x, 1))
+}
 pub unsafe fn instr32_D1_1_reg(r1: i32) { write_reg32(r1, ror32(read_reg32(r1), 1)); }
-pub unsafe fn instr32_D1_2_mem(addr: i32) { safe_read_write32(addr, &|x| rcl32(x, 1)) }
+pub unsafe fn instr32_D1_2_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rcl32(x, 1))
+}
 pub unsafe fn instr32_D1_2_reg(r1: i32) { write_reg32(r1, rcl32(read_reg32(r1), 1)); }
-pub unsafe fn instr32_D1_3_mem(addr: i32) { safe_read_write32(addr, &|x| rcr32(x, 1)) }
+pub unsafe fn instr32_D1_3_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rcr32(x, 1))
+}
 pub unsafe fn instr32_D1_3_reg(r1: i32) { write_reg32(r1, rcr32(read_reg32(r1), 1)); }
-pub unsafe fn instr32_D1_4_mem(addr: i32) { safe_read_write32(addr, &|x| shl32(x, 1)) }
+pub unsafe fn instr32_D1_4_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shl32(x, 1))
+}
 pub unsafe fn instr32_D1_4_reg(r1: i32) { write_reg32(r1, shl32(read_reg32(r1), 1)); }
-pub unsafe fn instr32_D1_5_mem(addr: i32) { safe_read_write32(addr, &|x| shr32(x, 1)) }
+pub unsafe fn instr32_D1_5_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shr32(x, 1))
+}
 pub unsafe fn instr32_D1_5_reg(r1: i32) { write_reg32(r1, shr32(read_reg32(r1), 1)); }
-pub unsafe fn instr32_D1_6_mem(addr: i32) { safe_read_write32(addr, &|x| shl32(x, 1)) }
+pub unsafe fn instr32_D1_6_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shl32(x, 1))
+}
 pub unsafe fn instr32_D1_6_reg(r1: i32) { write_reg32(r1, shl32(read_reg32(r1), 1)); }
-pub unsafe fn instr32_D1_7_mem(addr: i32) { safe_read_write32(addr, &|x| sar32(x, 1)) }
+pub unsafe fn instr32_D1_7_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sar32(x, 1))
+}
 pub unsafe fn instr32_D1_7_reg(r1: i32) { write_reg32(r1, sar32(read_reg32(r1), 1)); }
-pub unsafe fn instr_D2_0_mem(addr: i32) { safe_read_write8(addr, &|x| rol8(x, read_reg8(CL) & 31)) }
+pub unsafe fn instr_D2_0_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rol8(x, read_reg8(CL) & 31))
+}
 pub unsafe fn instr_D2_0_reg(r1: i32) { write_reg8(r1, rol8(read_reg8(r1), read_reg8(CL) & 31)); }
-pub unsafe fn instr_D2_1_mem(addr: i32) { safe_read_write8(addr, &|x| ror8(x, read_reg8(CL) & 31)) }
+pub unsafe fn instr_D2_1_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| ror8(x, read_reg8(CL) & 31))
+}
 pub unsafe fn instr_D2_1_reg(r1: i32) { write_reg8(r1, ror8(read_reg8(r1), read_reg8(CL) & 31)); }
-pub unsafe fn instr_D2_2_mem(addr: i32) { safe_read_write8(addr, &|x| rcl8(x, read_reg8(CL) & 31)) }
+pub unsafe fn instr_D2_2_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rcl8(x, read_reg8(CL) & 31))
+}
 pub unsafe fn instr_D2_2_reg(r1: i32) { write_reg8(r1, rcl8(read_reg8(r1), read_reg8(CL) & 31)); }
-pub unsafe fn instr_D2_3_mem(addr: i32) { safe_read_write8(addr, &|x| rcr8(x, read_reg8(CL) & 31)) }
+pub unsafe fn instr_D2_3_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| rcr8(x, read_reg8(CL) & 31))
+}
 pub unsafe fn instr_D2_3_reg(r1: i32) { write_reg8(r1, rcr8(read_reg8(r1), read_reg8(CL) & 31)); }
-pub unsafe fn instr_D2_4_mem(addr: i32) { safe_read_write8(addr, &|x| shl8(x, read_reg8(CL) & 31)) }
+pub unsafe fn instr_D2_4_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shl8(x, read_reg8(CL) & 31))
+}
 pub unsafe fn instr_D2_4_reg(r1: i32) { write_reg8(r1, shl8(read_reg8(r1), read_reg8(CL) & 31)); }
-pub unsafe fn instr_D2_5_mem(addr: i32) { safe_read_write8(addr, &|x| shr8(x, read_reg8(CL) & 31)) }
+pub unsafe fn instr_D2_5_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shr8(x, read_reg8(CL) & 31))
+}
 pub unsafe fn instr_D2_5_reg(r1: i32) { write_reg8(r1, shr8(read_reg8(r1), read_reg8(CL) & 31)); }
-pub unsafe fn instr_D2_6_mem(addr: i32) { safe_read_write8(addr, &|x| shl8(x, read_reg8(CL) & 31)) }
+pub unsafe fn instr_D2_6_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| shl8(x, read_reg8(CL) & 31))
+}
 pub unsafe fn instr_D2_6_reg(r1: i32) { write_reg8(r1, shl8(read_reg8(r1), read_reg8(CL) & 31)); }
-pub unsafe fn instr_D2_7_mem(addr: i32) { safe_read_write8(addr, &|x| sar8(x, read_reg8(CL) & 31)) }
+pub unsafe fn instr_D2_7_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| sar8(x, read_reg8(CL) & 31))
+}
 pub unsafe fn instr_D2_7_reg(r1: i32) { write_reg8(r1, sar8(read_reg8(r1), read_reg8(CL) & 31)); }
-pub unsafe fn instr16_D3_0_mem(addr: i32) {
-    safe_read_write16(addr, &|x| rol16(x, read_reg8(CL) & 31))
+pub unsafe fn instr16_D3_0_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rol16(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_D3_0_reg(r1: i32) {
     write_reg16(r1, rol16(read_reg16(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr16_D3_1_mem(addr: i32) {
-    safe_read_write16(addr, &|x| ror16(x, read_reg8(CL) & 31))
+pub unsafe fn instr16_D3_1_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| ror16(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_D3_1_reg(r1: i32) {
     write_reg16(r1, ror16(read_reg16(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr16_D3_2_mem(addr: i32) {
-    safe_read_write16(addr, &|x| rcl16(x, read_reg8(CL) & 31))
+pub unsafe fn instr16_D3_2_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rcl16(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_D3_2_reg(r1: i32) {
     write_reg16(r1, rcl16(read_reg16(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr16_D3_3_mem(addr: i32) {
-    safe_read_write16(addr, &|x| rcr16(x, read_reg8(CL) & 31))
+pub unsafe fn instr16_D3_3_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| rcr16(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_D3_3_reg(r1: i32) {
     write_reg16(r1, rcr16(read_reg16(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr16_D3_4_mem(addr: i32) {
-    safe_read_write16(addr, &|x| shl16(x, read_reg8(CL) & 31))
+pub unsafe fn instr16_D3_4_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shl16(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_D3_4_reg(r1: i32) {
     write_reg16(r1, shl16(read_reg16(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr16_D3_5_mem(addr: i32) {
-    safe_read_write16(addr, &|x| shr16(x, read_reg8(CL) & 31))
+pub unsafe fn instr16_D3_5_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shr16(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_D3_5_reg(r1: i32) {
     write_reg16(r1, shr16(read_reg16(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr16_D3_6_mem(addr: i32) {
-    safe_read_write16(addr, &|x| shl16(x, read_reg8(CL) & 31))
+pub unsafe fn instr16_D3_6_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shl16(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_D3_6_reg(r1: i32) {
     write_reg16(r1, shl16(read_reg16(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr16_D3_7_mem(addr: i32) {
-    safe_read_write16(addr, &|x| sar16(x, read_reg8(CL) & 31))
+pub unsafe fn instr16_D3_7_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| sar16(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_D3_7_reg(r1: i32) {
     write_reg16(r1, sar16(read_reg16(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr32_D3_0_mem(addr: i32) {
-    safe_read_write32(addr, &|x| rol32(x, read_reg8(CL) & 31))
+pub unsafe fn instr32_D3_0_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rol32(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_D3_0_reg(r1: i32) {
     write_reg32(r1, rol32(read_reg32(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr32_D3_1_mem(addr: i32) {
-    safe_read_write32(addr, &|x| ror32(x, read_reg8(CL) & 31))
+pub unsafe fn instr32_D3_1_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| ror32(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_D3_1_reg(r1: i32) {
     write_reg32(r1, ror32(read_reg32(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr32_D3_2_mem(addr: i32) {
-    safe_read_write32(addr, &|x| rcl32(x, read_reg8(CL) & 31))
+pub unsafe fn instr32_D3_2_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rcl32(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_D3_2_reg(r1: i32) {
     write_reg32(r1, rcl32(read_reg32(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr32_D3_3_mem(addr: i32) {
-    safe_read_write32(addr, &|x| rcr32(x, read_reg8(CL) & 31))
+pub unsafe fn instr32_D3_3_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| rcr32(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_D3_3_reg(r1: i32) {
     write_reg32(r1, rcr32(read_reg32(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr32_D3_4_mem(addr: i32) {
-    safe_read_write32(addr, &|x| shl32(x, read_reg8(CL) & 31))
+pub unsafe fn instr32_D3_4_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shl32(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_D3_4_reg(r1: i32) {
     write_reg32(r1, shl32(read_reg32(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr32_D3_5_mem(addr: i32) {
-    safe_read_write32(addr, &|x| shr32(x, read_reg8(CL) & 31))
+pub unsafe fn instr32_D3_5_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shr32(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_D3_5_reg(r1: i32) {
     write_reg32(r1, shr32(read_reg32(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr32_D3_6_mem(addr: i32) {
-    safe_read_write32(addr, &|x| shl32(x, read_reg8(CL) & 31))
+pub unsafe fn instr32_D3_6_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shl32(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_D3_6_reg(r1: i32) {
     write_reg32(r1, shl32(read_reg32(r1), read_reg8(CL) & 31));
 }
-pub unsafe fn instr32_D3_7_mem(addr: i32) {
-    safe_read_write32(addr, &|x| sar32(x, read_reg8(CL) & 31))
+pub unsafe fn instr32_D3_7_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| sar32(x, read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_D3_7_reg(r1: i32) {
     write_reg32(r1, sar32(read_reg32(r1), read_reg8(CL) & 31));
@@ -1624,60 +1870,67 @@ pub unsafe fn instr_D6() {
 pub unsafe fn instr_D7() {
     // xlat
     dbg_assert!(!in_jit);
-    if is_asize_32() {
-        write_reg8(
-            AL,
-            return_on_pagefault!(safe_read8(
-                return_on_pagefault!(get_seg_prefix(DS)) + read_reg32(EBX) + read_reg8(AL),
-            )),
-        )
+    let offset = if is_asize_32() {
+        read_reg32(EBX) + read_reg8(AL)
     }
     else {
-        write_reg8(
-            AL,
-            return_on_pagefault!(safe_read8(
-                return_on_pagefault!(get_seg_prefix(DS))
-                    + (read_reg16(BX) + read_reg8(AL) & 0xFFFF),
-            )),
-        )
+        read_reg16(BX) + read_reg8(AL) & 0xFFFF
     };
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(offset));
+    write_reg8(AL, return_on_pagefault!(safe_read8_seg(seg, addr)));
 }
 
-pub unsafe fn instr_D8_0_mem(addr: i32) { fpu_fadd(0, return_on_pagefault!(fpu_load_m32(addr))); }
+pub unsafe fn instr_D8_0_mem(seg: i32, addr: i32) {
+    fpu_fadd(0, return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
 pub unsafe fn instr_D8_0_reg(r: i32) { fpu_fadd(0, fpu_get_sti(r)); }
-pub unsafe fn instr_D8_1_mem(addr: i32) { fpu_fmul(0, return_on_pagefault!(fpu_load_m32(addr))); }
+pub unsafe fn instr_D8_1_mem(seg: i32, addr: i32) {
+    fpu_fmul(0, return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
 pub unsafe fn instr_D8_1_reg(r: i32) { fpu_fmul(0, fpu_get_sti(r)); }
-pub unsafe fn instr_D8_2_mem(addr: i32) { fpu_fcom(return_on_pagefault!(fpu_load_m32(addr))); }
+pub unsafe fn instr_D8_2_mem(seg: i32, addr: i32) {
+    fpu_fcom(return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
 pub unsafe fn instr_D8_2_reg(r: i32) { fpu_fcom(fpu_get_sti(r)); }
-pub unsafe fn instr_D8_3_mem(addr: i32) { fpu_fcomp(return_on_pagefault!(fpu_load_m32(addr))); }
+pub unsafe fn instr_D8_3_mem(seg: i32, addr: i32) {
+    fpu_fcomp(return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
 pub unsafe fn instr_D8_3_reg(r: i32) { fpu_fcomp(fpu_get_sti(r)); }
-pub unsafe fn instr_D8_4_mem(addr: i32) { fpu_fsub(0, return_on_pagefault!(fpu_load_m32(addr))); }
+pub unsafe fn instr_D8_4_mem(seg: i32, addr: i32) {
+    fpu_fsub(0, return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
 pub unsafe fn instr_D8_4_reg(r: i32) { fpu_fsub(0, fpu_get_sti(r)); }
-pub unsafe fn instr_D8_5_mem(addr: i32) { fpu_fsubr(0, return_on_pagefault!(fpu_load_m32(addr))); }
+pub unsafe fn instr_D8_5_mem(seg: i32, addr: i32) {
+    fpu_fsubr(0, return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
 pub unsafe fn instr_D8_5_reg(r: i32) { fpu_fsubr(0, fpu_get_sti(r)); }
-pub unsafe fn instr_D8_6_mem(addr: i32) { fpu_fdiv(0, return_on_pagefault!(fpu_load_m32(addr))); }
+pub unsafe fn instr_D8_6_mem(seg: i32, addr: i32) {
+    fpu_fdiv(0, return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
 pub unsafe fn instr_D8_6_reg(r: i32) { fpu_fdiv(0, fpu_get_sti(r)); }
-pub unsafe fn instr_D8_7_mem(addr: i32) { fpu_fdivr(0, return_on_pagefault!(fpu_load_m32(addr))); }
+pub unsafe fn instr_D8_7_mem(seg: i32, addr: i32) {
+    fpu_fdivr(0, return_on_pagefault!(fpu_load_m32(seg, addr)));
+}
 pub unsafe fn instr_D8_7_reg(r: i32) { fpu_fdivr(0, fpu_get_sti(r)); }
 
-pub unsafe fn instr16_D9_0_mem(addr: i32) { fpu_fldm32(addr); }
+pub unsafe fn instr16_D9_0_mem(seg: i32, addr: i32) { fpu_fldm32(seg, addr); }
 pub unsafe fn instr16_D9_0_reg(r: i32) { fpu_push(fpu_get_sti(r)); }
-pub unsafe fn instr16_D9_1_mem(_addr: i32) {
+pub unsafe fn instr16_D9_1_mem(_seg: i32, _addr: i32) {
     dbg_log!("d9/1");
     trigger_ud();
 }
 pub unsafe fn instr16_D9_1_reg(r: i32) { fpu_fxch(r); }
-pub unsafe fn instr16_D9_2_mem(addr: i32) { fpu_fstm32(addr); }
+pub unsafe fn instr16_D9_2_mem(seg: i32, addr: i32) { fpu_fstm32(seg, addr); }
 pub unsafe fn instr16_D9_2_reg(r: i32) {
     if r != 0 {
         trigger_ud();
     };
 }
-pub unsafe fn instr16_D9_3_mem(addr: i32) { fpu_fstm32p(addr); }
+pub unsafe fn instr16_D9_3_mem(seg: i32, addr: i32) { fpu_fstm32p(seg, addr); }
 pub unsafe fn instr16_D9_3_reg(r: i32) { fpu_fstp(r) }
 #[no_mangle]
-pub unsafe fn instr16_D9_4_mem(addr: i32) { fpu_fldenv16(addr); }
-pub unsafe fn instr32_D9_4_mem(addr: i32) { fpu_fldenv32(addr); }
+pub unsafe fn instr16_D9_4_mem(seg: i32, addr: i32) { fpu_fldenv16(seg, addr); }
+pub unsafe fn instr32_D9_4_mem(seg: i32, addr: i32) { fpu_fldenv32(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr16_D9_4_reg(r: i32) {
     match r {
@@ -1692,7 +1945,7 @@ pub unsafe fn instr16_D9_4_reg(r: i32) {
     };
 }
 #[no_mangle]
-pub unsafe fn instr16_D9_5_mem(addr: i32) { fpu_fldcw(addr); }
+pub unsafe fn instr16_D9_5_mem(seg: i32, addr: i32) { fpu_fldcw(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr16_D9_5_reg(r: i32) {
     // fld1/fldl2t/fldl2e/fldpi/fldlg2/fldln2/fldz
@@ -1711,8 +1964,8 @@ pub unsafe fn instr16_D9_5_reg(r: i32) {
         _ => {},
     };
 }
-pub unsafe fn instr16_D9_6_mem(addr: i32) { fpu_fstenv16(addr); }
-pub unsafe fn instr32_D9_6_mem(addr: i32) { fpu_fstenv32(addr); }
+pub unsafe fn instr16_D9_6_mem(seg: i32, addr: i32) { fpu_fstenv16(seg, addr); }
+pub unsafe fn instr32_D9_6_mem(seg: i32, addr: i32) { fpu_fstenv32(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr16_D9_6_reg(r: i32) {
     match r {
@@ -1729,7 +1982,7 @@ pub unsafe fn instr16_D9_6_reg(r: i32) {
         },
     };
 }
-pub unsafe fn instr16_D9_7_mem(addr: i32) { fpu_fstcw(addr); }
+pub unsafe fn instr16_D9_7_mem(seg: i32, addr: i32) { fpu_fstcw(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr16_D9_7_reg(r: i32) {
     match r {
@@ -1756,21 +2009,37 @@ pub unsafe fn instr32_D9_5_reg(r: i32) { instr16_D9_5_reg(r) }
 pub unsafe fn instr32_D9_6_reg(r: i32) { instr16_D9_6_reg(r) }
 pub unsafe fn instr32_D9_7_reg(r: i32) { instr16_D9_7_reg(r) }
 
-pub unsafe fn instr32_D9_0_mem(r: i32) { instr16_D9_0_mem(r) }
-pub unsafe fn instr32_D9_1_mem(r: i32) { instr16_D9_1_mem(r) }
-pub unsafe fn instr32_D9_2_mem(r: i32) { instr16_D9_2_mem(r) }
-pub unsafe fn instr32_D9_3_mem(r: i32) { instr16_D9_3_mem(r) }
-pub unsafe fn instr32_D9_5_mem(r: i32) { instr16_D9_5_mem(r) }
-pub unsafe fn instr32_D9_7_mem(r: i32) { instr16_D9_7_mem(r) }
-
-pub unsafe fn instr_DA_0_mem(addr: i32) { fpu_fadd(0, return_on_pagefault!(fpu_load_i32(addr))); }
-pub unsafe fn instr_DA_1_mem(addr: i32) { fpu_fmul(0, return_on_pagefault!(fpu_load_i32(addr))); }
-pub unsafe fn instr_DA_2_mem(addr: i32) { fpu_fcom(return_on_pagefault!(fpu_load_i32(addr))); }
-pub unsafe fn instr_DA_3_mem(addr: i32) { fpu_fcomp(return_on_pagefault!(fpu_load_i32(addr))); }
-pub unsafe fn instr_DA_4_mem(addr: i32) { fpu_fsub(0, return_on_pagefault!(fpu_load_i32(addr))); }
-pub unsafe fn instr_DA_5_mem(addr: i32) { fpu_fsubr(0, return_on_pagefault!(fpu_load_i32(addr))); }
-pub unsafe fn instr_DA_6_mem(addr: i32) { fpu_fdiv(0, return_on_pagefault!(fpu_load_i32(addr))); }
-pub unsafe fn instr_DA_7_mem(addr: i32) { fpu_fdivr(0, return_on_pagefault!(fpu_load_i32(addr))); }
+pub unsafe fn instr32_D9_0_mem(seg: i32, r: i32) { instr16_D9_0_mem(seg, r) }
+pub unsafe fn instr32_D9_1_mem(seg: i32, r: i32) { instr16_D9_1_mem(seg, r) }
+pub unsafe fn instr32_D9_2_mem(seg: i32, r: i32) { instr16_D9_2_mem(seg, r) }
+pub unsafe fn instr32_D9_3_mem(seg: i32, r: i32) { instr16_D9_3_mem(seg, r) }
+pub unsafe fn instr32_D9_5_mem(seg: i32, r: i32) { instr16_D9_5_mem(seg, r) }
+pub unsafe fn instr32_D9_7_mem(seg: i32, r: i32) { instr16_D9_7_mem(seg, r) }
+
+pub unsafe fn instr_DA_0_mem(seg: i32, addr: i32) {
+    fpu_fadd(0, return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
+pub unsafe fn instr_DA_1_mem(seg: i32, addr: i32) {
+    fpu_fmul(0, return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
+pub unsafe fn instr_DA_2_mem(seg: i32, addr: i32) {
+    fpu_fcom(return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
+pub unsafe fn instr_DA_3_mem(seg: i32, addr: i32) {
+    fpu_fcomp(return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
+pub unsafe fn instr_DA_4_mem(seg: i32, addr: i32) {
+    fpu_fsub(0, return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
+pub unsafe fn instr_DA_5_mem(seg: i32, addr: i32) {
+    fpu_fsubr(0, return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
+pub unsafe fn instr_DA_6_mem(seg: i32, addr: i32) {
+    fpu_fdiv(0, return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
+pub unsafe fn instr_DA_7_mem(seg: i32, addr: i32) {
+    fpu_fdivr(0, return_on_pagefault!(fpu_load_i32(seg, addr)));
+}
 #[no_mangle]
 pub unsafe fn instr_DA_0_reg(r: i32) { fpu_fcmovcc(test_b(), r); }
 #[no_mangle]
@@ -1791,16 +2060,16 @@ pub unsafe fn instr_DA_5_reg(r: i32) {
 pub unsafe fn instr_DA_6_reg(_r: i32) { trigger_ud(); }
 pub unsafe fn instr_DA_7_reg(_r: i32) { trigger_ud(); }
 
-pub unsafe fn instr_DB_0_mem(addr: i32) { fpu_fildm32(addr); }
-pub unsafe fn instr_DB_1_mem(addr: i32) { fpu_fisttpm32(addr); }
-pub unsafe fn instr_DB_2_mem(addr: i32) { fpu_fistm32(addr); }
-pub unsafe fn instr_DB_3_mem(addr: i32) { fpu_fistm32p(addr); }
+pub unsafe fn instr_DB_0_mem(seg: i32, addr: i32) { fpu_fildm32(seg, addr); }
+pub unsafe fn instr_DB_1_mem(seg: i32, addr: i32) { fpu_fisttpm32(seg, addr); }
+pub unsafe fn instr_DB_2_mem(seg: i32, addr: i32) { fpu_fistm32(seg, addr); }
+pub unsafe fn instr_DB_3_mem(seg: i32, addr: i32) { fpu_fistm32p(seg, addr); }
 #[no_mangle]
-pub unsafe fn instr_DB_4_mem(_addr: i32) { trigger_ud(); }
-pub unsafe fn instr_DB_5_mem(addr: i32) { fpu_fldm80(addr); }
-pub unsafe fn instr_DB_6_mem(_addr: i32) { trigger_ud(); }
+pub unsafe fn instr_DB_4_mem(_seg: i32, _addr: i32) { trigger_ud(); }
+pub unsafe fn instr_DB_5_mem(seg: i32, addr: i32) { fpu_fldm80(seg, addr); }
+pub unsafe fn instr_DB_6_mem(_seg: i32, _addr: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr_DB_7_mem(addr: i32) { fpu_fst80p(addr); }
+pub unsafe fn instr_DB_7_mem(seg: i32, addr: i32) { fpu_fst80p(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr_DB_0_reg(r: i32) { fpu_fcmovcc(!test_b(), r); }
 #[no_mangle]
@@ -1829,14 +2098,30 @@ pub unsafe fn instr_DB_6_reg(r: i32) { fpu_fcomi(r); }
 #[no_mangle]
 pub unsafe fn instr_DB_7_reg(_r: i32) { trigger_ud(); }
 
-pub unsafe fn instr_DC_0_mem(addr: i32) { fpu_fadd(0, return_on_pagefault!(fpu_load_m64(addr))); }
-pub unsafe fn instr_DC_1_mem(addr: i32) { fpu_fmul(0, return_on_pagefault!(fpu_load_m64(addr))); }
-pub unsafe fn instr_DC_2_mem(addr: i32) { fpu_fcom(return_on_pagefault!(fpu_load_m64(addr))); }
-pub unsafe fn instr_DC_3_mem(addr: i32) { fpu_fcomp(return_on_pagefault!(fpu_load_m64(addr))); }
-pub unsafe fn instr_DC_4_mem(addr: i32) { fpu_fsub(0, return_on_pagefault!(fpu_load_m64(addr))); }
-pub unsafe fn instr_DC_5_mem(addr: i32) { fpu_fsubr(0, return_on_pagefault!(fpu_load_m64(addr))); }
-pub unsafe fn instr_DC_6_mem(addr: i32) { fpu_fdiv(0, return_on_pagefault!(fpu_load_m64(addr))); }
-pub unsafe fn instr_DC_7_mem(addr: i32) { fpu_fdivr(0, return_on_pagefault!(fpu_load_m64(addr))); }
+pub unsafe fn instr_DC_0_mem(seg: i32, addr: i32) {
+    fpu_fadd(0, return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
+pub unsafe fn instr_DC_1_mem(seg: i32, addr: i32) {
+    fpu_fmul(0, return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
+pub unsafe fn instr_DC_2_mem(seg: i32, addr: i32) {
+    fpu_fcom(return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
+pub unsafe fn instr_DC_3_mem(seg: i32, addr: i32) {
+    fpu_fcomp(return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
+pub unsafe fn instr_DC_4_mem(seg: i32, addr: i32) {
+    fpu_fsub(0, return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
+pub unsafe fn instr_DC_5_mem(seg: i32, addr: i32) {
+    fpu_fsubr(0, return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
+pub unsafe fn instr_DC_6_mem(seg: i32, addr: i32) {
+    fpu_fdiv(0, return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
+pub unsafe fn instr_DC_7_mem(seg: i32, addr: i32) {
+    fpu_fdivr(0, return_on_pagefault!(fpu_load_m64(seg, addr)));
+}
 pub unsafe fn instr_DC_0_reg(r: i32) { fpu_fadd(r, fpu_get_sti(r)); }
 pub unsafe fn instr_DC_1_reg(r: i32) { fpu_fmul(r, fpu_get_sti(r)); }
 pub unsafe fn instr_DC_2_reg(r: i32) { fpu_fcom(fpu_get_sti(r)); }
@@ -1846,24 +2131,24 @@ pub unsafe fn instr_DC_5_reg(r: i32) { fpu_fsubr(r, fpu_get_sti(r)); }
 pub unsafe fn instr_DC_6_reg(r: i32) { fpu_fdiv(r, fpu_get_sti(r)); }
 pub unsafe fn instr_DC_7_reg(r: i32) { fpu_fdivr(r, fpu_get_sti(r)); }
 
-pub unsafe fn instr16_DD_0_mem(addr: i32) { fpu_fldm64(addr); }
-pub unsafe fn instr16_DD_1_mem(addr: i32) { fpu_fisttpm64(addr); }
-pub unsafe fn instr16_DD_2_mem(addr: i32) { fpu_fstm64(addr); }
-pub unsafe fn instr16_DD_3_mem(addr: i32) { fpu_fstm64p(addr); }
+pub unsafe fn instr16_DD_0_mem(seg: i32, addr: i32) { fpu_fldm64(seg, addr); }
+pub unsafe fn instr16_DD_1_mem(seg: i32, addr: i32) { fpu_fisttpm64(seg, addr); }
+pub unsafe fn instr16_DD_2_mem(seg: i32, addr: i32) { fpu_fstm64(seg, addr); }
+pub unsafe fn instr16_DD_3_mem(seg: i32, addr: i32) { fpu_fstm64p(seg, addr); }
 #[no_mangle]
-pub unsafe fn instr16_DD_4_mem(addr: i32) { fpu_frstor16(addr); }
+pub unsafe fn instr16_DD_4_mem(seg: i32, addr: i32) { fpu_frstor16(seg, addr); }
 #[no_mangle]
-pub unsafe fn instr32_DD_4_mem(addr: i32) { fpu_frstor32(addr); }
-pub unsafe fn instr16_DD_5_mem(_addr: i32) {
+pub unsafe fn instr32_DD_4_mem(seg: i32, addr: i32) { fpu_frstor32(seg, addr); }
+pub unsafe fn instr16_DD_5_mem(_seg: i32, _addr: i32) {
     dbg_log!("dd/5");
     trigger_ud();
 }
 #[no_mangle]
-pub unsafe fn instr16_DD_6_mem(addr: i32) { fpu_fsave16(addr); }
+pub unsafe fn instr16_DD_6_mem(seg: i32, addr: i32) { fpu_fsave16(seg, addr); }
 #[no_mangle]
-pub unsafe fn instr32_DD_6_mem(addr: i32) { fpu_fsave32(addr); }
+pub unsafe fn instr32_DD_6_mem(seg: i32, addr: i32) { fpu_fsave32(seg, addr); }
 #[no_mangle]
-pub unsafe fn instr16_DD_7_mem(addr: i32) { fpu_fnstsw_mem(addr); }
+pub unsafe fn instr16_DD_7_mem(seg: i32, addr: i32) { fpu_fnstsw_mem(seg, addr); }
 pub unsafe fn instr16_DD_0_reg(r: i32) { fpu_ffree(r); }
 pub unsafe fn instr16_DD_1_reg(r: i32) { fpu_fxch(r) }
 pub unsafe fn instr16_DD_2_reg(r: i32) { fpu_fst(r); }
@@ -1889,31 +2174,47 @@ pub unsafe fn instr32_DD_6_reg(r: i32) { instr16_DD_6_reg(r) }
 #[no_mangle]
 pub unsafe fn instr32_DD_7_reg(r: i32) { instr16_DD_7_reg(r) }
 
-pub unsafe fn instr32_DD_0_mem(r: i32) { instr16_DD_0_mem(r) }
+pub unsafe fn instr32_DD_0_mem(seg: i32, r: i32) { instr16_DD_0_mem(seg, r) }
 #[no_mangle]
-pub unsafe fn instr32_DD_1_mem(r: i32) { instr16_DD_1_mem(r) }
-pub unsafe fn instr32_DD_2_mem(r: i32) { instr16_DD_2_mem(r) }
-pub unsafe fn instr32_DD_3_mem(r: i32) { instr16_DD_3_mem(r) }
-pub unsafe fn instr32_DD_5_mem(r: i32) { instr16_DD_5_mem(r) }
+pub unsafe fn instr32_DD_1_mem(seg: i32, r: i32) { instr16_DD_1_mem(seg, r) }
+pub unsafe fn instr32_DD_2_mem(seg: i32, r: i32) { instr16_DD_2_mem(seg, r) }
+pub unsafe fn instr32_DD_3_mem(seg: i32, r: i32) { instr16_DD_3_mem(seg, r) }
+pub unsafe fn instr32_DD_5_mem(seg: i32, r: i32) { instr16_DD_5_mem(seg, r) }
 #[no_mangle]
-pub unsafe fn instr32_DD_7_mem(r: i32) { instr16_DD_7_mem(r) }
+pub unsafe fn instr32_DD_7_mem(seg: i32, r: i32) { instr16_DD_7_mem(seg, r) }
 
 #[no_mangle]
-pub unsafe fn instr_DE_0_mem(addr: i32) { fpu_fadd(0, return_on_pagefault!(fpu_load_i16(addr))); }
+pub unsafe fn instr_DE_0_mem(seg: i32, addr: i32) {
+    fpu_fadd(0, return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
 #[no_mangle]
-pub unsafe fn instr_DE_1_mem(addr: i32) { fpu_fmul(0, return_on_pagefault!(fpu_load_i16(addr))); }
+pub unsafe fn instr_DE_1_mem(seg: i32, addr: i32) {
+    fpu_fmul(0, return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
 #[no_mangle]
-pub unsafe fn instr_DE_2_mem(addr: i32) { fpu_fcom(return_on_pagefault!(fpu_load_i16(addr))); }
+pub unsafe fn instr_DE_2_mem(seg: i32, addr: i32) {
+    fpu_fcom(return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
 #[no_mangle]
-pub unsafe fn instr_DE_3_mem(addr: i32) { fpu_fcomp(return_on_pagefault!(fpu_load_i16(addr))); }
+pub unsafe fn instr_DE_3_mem(seg: i32, addr: i32) {
+    fpu_fcomp(return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
 #[no_mangle]
-pub unsafe fn instr_DE_4_mem(addr: i32) { fpu_fsub(0, return_on_pagefault!(fpu_load_i16(addr))); }
+pub unsafe fn instr_DE_4_mem(seg: i32, addr: i32) {
+    fpu_fsub(0, return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
 #[no_mangle]
-pub unsafe fn instr_DE_5_mem(addr: i32) { fpu_fsubr(0, return_on_pagefault!(fpu_load_i16(addr))); }
+pub unsafe fn instr_DE_5_mem(seg: i32, addr: i32) {
+    fpu_fsubr(0, return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
 #[no_mangle]
-pub unsafe fn instr_DE_6_mem(addr: i32) { fpu_fdiv(0, return_on_pagefault!(fpu_load_i16(addr))); }
+pub unsafe fn instr_DE_6_mem(seg: i32, addr: i32) {
+    fpu_fdiv(0, return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
 #[no_mangle]
-pub unsafe fn instr_DE_7_mem(addr: i32) { fpu_fdivr(0, return_on_pagefault!(fpu_load_i16(addr))); }
+pub unsafe fn instr_DE_7_mem(seg: i32, addr: i32) {
+    fpu_fdivr(0, return_on_pagefault!(fpu_load_i16(seg, addr)));
+}
 
 #[no_mangle]
 pub unsafe fn instr_DE_0_reg(r: i32) {
@@ -1955,17 +2256,17 @@ pub unsafe fn instr_DE_7_reg(r: i32) {
 }
 
 #[no_mangle]
-pub unsafe fn instr_DF_0_mem(addr: i32) { fpu_fildm16(addr) }
-pub unsafe fn instr_DF_1_mem(addr: i32) { fpu_fisttpm16(addr); }
-pub unsafe fn instr_DF_2_mem(addr: i32) { fpu_fistm16(addr); }
-pub unsafe fn instr_DF_3_mem(addr: i32) { fpu_fistm16p(addr); }
-pub unsafe fn instr_DF_4_mem(_addr: i32) {
+pub unsafe fn instr_DF_0_mem(seg: i32, addr: i32) { fpu_fildm16(seg, addr) }
+pub unsafe fn instr_DF_1_mem(seg: i32, addr: i32) { fpu_fisttpm16(seg, addr); }
+pub unsafe fn instr_DF_2_mem(seg: i32, addr: i32) { fpu_fistm16(seg, addr); }
+pub unsafe fn instr_DF_3_mem(seg: i32, addr: i32) { fpu_fistm16p(seg, addr); }
+pub unsafe fn instr_DF_4_mem(_seg: i32, _addr: i32) {
     dbg_log!("fbld");
     fpu_unimpl();
 }
-pub unsafe fn instr_DF_5_mem(addr: i32) { fpu_fildm64(addr); }
-pub unsafe fn instr_DF_6_mem(addr: i32) { fpu_fbstp(addr); }
-pub unsafe fn instr_DF_7_mem(addr: i32) { fpu_fistm64p(addr); }
+pub unsafe fn instr_DF_5_mem(seg: i32, addr: i32) { fpu_fildm64(seg, addr); }
+pub unsafe fn instr_DF_6_mem(seg: i32, addr: i32) { fpu_fbstp(seg, addr); }
+pub unsafe fn instr_DF_7_mem(seg: i32, addr: i32) { fpu_fistm64p(seg, addr); }
 
 #[no_mangle]
 pub unsafe fn instr_DF_0_reg(r: i32) {
@@ -2136,7 +2437,9 @@ pub unsafe fn instr_F0() {
 pub unsafe fn instr_F1() {
     // INT1
     // https://code.google.com/p/corkami/wiki/x86oddities#IceBP
-    dbg_assert!(false);
+    // Raises #DB as a trap, without the privilege checks of int n and without modifying DR6
+    dbg_log!("INT1");
+    call_interrupt_vector(CPU_EXCEPTION_DB, false, None);
 }
 
 pub unsafe fn instr_F2() {
@@ -2168,7 +2471,7 @@ pub unsafe fn instr_F4() {
     // due it will immediately call call_interrupt_vector and continue
     // execution without an unnecessary cycle through do_run
     if *flags & FLAG_INTERRUPT != 0 {
-        run_hardware_timers(*acpi_enabled, microtick());
+        run_hardware_timers(*acpi_enabled, cpu_microtick());
         handle_irqs();
     }
     else {
@@ -2183,90 +2486,112 @@ pub unsafe fn instr_F5() {
     *flags_changed &= !1;
 }
 
-pub unsafe fn instr_F6_0_mem(addr: i32, imm: i32) {
-    test8(return_on_pagefault!(safe_read8(addr)), imm);
+pub unsafe fn instr_F6_0_mem(seg: i32, addr: i32, imm: i32) {
+    test8(return_on_pagefault!(safe_read8_seg(seg, addr)), imm);
 }
 pub unsafe fn instr_F6_0_reg(r1: i32, imm: i32) { test8(read_reg8(r1), imm); }
-pub unsafe fn instr_F6_1_mem(addr: i32, imm: i32) {
-    test8(return_on_pagefault!(safe_read8(addr)), imm);
+pub unsafe fn instr_F6_1_mem(seg: i32, addr: i32, imm: i32) {
+    test8(return_on_pagefault!(safe_read8_seg(seg, addr)), imm);
 }
 pub unsafe fn instr_F6_1_reg(r1: i32, imm: i32) { test8(read_reg8(r1), imm); }
 
 #[no_mangle]
-pub unsafe fn instr_F6_2_mem(addr: i32) { safe_read_write8(addr, &|x| !x & 0xFF) }
+pub unsafe fn instr_F6_2_mem(seg: i32, addr: i32) {
+    safe_read_write8_seg(seg, addr, &|x| !x & 0xFF)
+}
 #[no_mangle]
 pub unsafe fn instr_F6_2_reg(r1: i32) { write_reg8(r1, !read_reg8(r1)); }
 #[no_mangle]
-pub unsafe fn instr_F6_3_mem(addr: i32) { safe_read_write8(addr, &|x| neg8(x)) }
+pub unsafe fn instr_F6_3_mem(seg: i32, addr: i32) { safe_read_write8_seg(seg, addr, &|x| neg8(x)) }
 #[no_mangle]
 pub unsafe fn instr_F6_3_reg(r1: i32) { write_reg8(r1, neg8(read_reg8(r1))); }
 #[no_mangle]
-pub unsafe fn instr_F6_4_mem(addr: i32) { mul8(return_on_pagefault!(safe_read8(addr))); }
+pub unsafe fn instr_F6_4_mem(seg: i32, addr: i32) {
+    mul8(return_on_pagefault!(safe_read8_seg(seg, addr)));
+}
 #[no_mangle]
 pub unsafe fn instr_F6_4_reg(r1: i32) { mul8(read_reg8(r1)); }
 #[no_mangle]
-pub unsafe fn instr_F6_5_mem(addr: i32) {
-    imul8(return_on_pagefault!(safe_read8(addr)) << 24 >> 24);
+pub unsafe fn instr_F6_5_mem(seg: i32, addr: i32) {
+    imul8(return_on_pagefault!(safe_read8_seg(seg, addr)) << 24 >> 24);
 }
 #[no_mangle]
 pub unsafe fn instr_F6_5_reg(r1: i32) { imul8(read_reg8(r1) << 24 >> 24); }
 #[no_mangle]
-pub unsafe fn instr_F6_6_mem(addr: i32) { div8(return_on_pagefault!(safe_read8(addr)) as u32); }
+pub unsafe fn instr_F6_6_mem(seg: i32, addr: i32) {
+    div8(return_on_pagefault!(safe_read8_seg(seg, addr)) as u32);
+}
 #[no_mangle]
 pub unsafe fn instr_F6_6_reg(r1: i32) { div8(read_reg8(r1) as u32); }
 #[no_mangle]
-pub unsafe fn instr_F6_7_mem(addr: i32) {
-    idiv8(return_on_pagefault!(safe_read8(addr)) << 24 >> 24);
+pub unsafe fn instr_F6_7_mem(seg: i32, addr: i32) {
+    idiv8(return_on_pagefault!(safe_read8_seg(seg, addr)) << 24 >> 24);
 }
 #[no_mangle]
 pub unsafe fn instr_F6_7_reg(r1: i32) { idiv8(read_reg8(r1) << 24 >> 24); }
 
-pub unsafe fn instr16_F7_0_mem(addr: i32, imm: i32) {
-    test16(return_on_pagefault!(safe_read16(addr)), imm);
+pub unsafe fn instr16_F7_0_mem(seg: i32, addr: i32, imm: i32) {
+    test16(return_on_pagefault!(safe_read16_seg(seg, addr)), imm);
 }
 pub unsafe fn instr16_F7_0_reg(r1: i32, imm: i32) { test16(read_reg16(r1), imm); }
-pub unsafe fn instr16_F7_1_mem(addr: i32, imm: i32) {
-    test16(return_on_pagefault!(safe_read16(addr)), imm);
+pub unsafe fn instr16_F7_1_mem(seg: i32, addr: i32, imm: i32) {
+    test16(return_on_pagefault!(safe_read16_seg(seg, addr)), imm);
 }
 pub unsafe fn instr16_F7_1_reg(r1: i32, imm: i32) { test16(read_reg16(r1), imm); }
-pub unsafe fn instr16_F7_2_mem(addr: i32) { safe_read_write16(addr, &|x| !x & 0xFFFF) }
+pub unsafe fn instr16_F7_2_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| !x & 0xFFFF)
+}
 pub unsafe fn instr16_F7_2_reg(r1: i32) { write_reg16(r1, !read_reg16(r1)); }
-pub unsafe fn instr16_F7_3_mem(addr: i32) { safe_read_write16(addr, &|x| neg16(x)) }
+pub unsafe fn instr16_F7_3_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| neg16(x))
+}
 pub unsafe fn instr16_F7_3_reg(r1: i32) { write_reg16(r1, neg16(read_reg16(r1))); }
-pub unsafe fn instr16_F7_4_mem(addr: i32) { mul16(return_on_pagefault!(safe_read16(addr)) as u32); }
+pub unsafe fn instr16_F7_4_mem(seg: i32, addr: i32) {
+    mul16(return_on_pagefault!(safe_read16_seg(seg, addr)) as u32);
+}
 pub unsafe fn instr16_F7_4_reg(r1: i32) { mul16(read_reg16(r1) as u32); }
-pub unsafe fn instr16_F7_5_mem(addr: i32) {
-    imul16(return_on_pagefault!(safe_read16(addr)) << 16 >> 16);
+pub unsafe fn instr16_F7_5_mem(seg: i32, addr: i32) {
+    imul16(return_on_pagefault!(safe_read16_seg(seg, addr)) << 16 >> 16);
 }
 pub unsafe fn instr16_F7_5_reg(r1: i32) { imul16(read_reg16(r1) << 16 >> 16); }
-pub unsafe fn instr16_F7_6_mem(addr: i32) { div16(return_on_pagefault!(safe_read16(addr)) as u32); }
+pub unsafe fn instr16_F7_6_mem(seg: i32, addr: i32) {
+    div16(return_on_pagefault!(safe_read16_seg(seg, addr)) as u32);
+}
 pub unsafe fn instr16_F7_6_reg(r1: i32) { div16(read_reg16(r1) as u32); }
-pub unsafe fn instr16_F7_7_mem(addr: i32) {
-    idiv16(return_on_pagefault!(safe_read16(addr)) << 16 >> 16);
+pub unsafe fn instr16_F7_7_mem(seg: i32, addr: i32) {
+    idiv16(return_on_pagefault!(safe_read16_seg(seg, addr)) << 16 >> 16);
 }
 pub unsafe fn instr16_F7_7_reg(r1: i32) { idiv16(read_reg16(r1) << 16 >> 16); }
 
-pub unsafe fn instr32_F7_0_mem(addr: i32, imm: i32) {
-    test32(return_on_pagefault!(safe_read32s(addr)), imm);
+pub unsafe fn instr32_F7_0_mem(seg: i32, addr: i32, imm: i32) {
+    test32(return_on_pagefault!(safe_read32s_seg(seg, addr)), imm);
 }
 pub unsafe fn instr32_F7_0_reg(r1: i32, imm: i32) { test32(read_reg32(r1), imm); }
-pub unsafe fn instr32_F7_1_mem(addr: i32, imm: i32) {
-    test32(return_on_pagefault!(safe_read32s(addr)), imm);
+pub unsafe fn instr32_F7_1_mem(seg: i32, addr: i32, imm: i32) {
+    test32(return_on_pagefault!(safe_read32s_seg(seg, addr)), imm);
 }
 pub unsafe fn instr32_F7_1_reg(r1: i32, imm: i32) { test32(read_reg32(r1), imm); }
-pub unsafe fn instr32_F7_2_mem(addr: i32) { safe_read_write32(addr, &|x| !x) }
+pub unsafe fn instr32_F7_2_mem(seg: i32, addr: i32) { safe_read_write32_seg(seg, addr, &|x| !x) }
 pub unsafe fn instr32_F7_2_reg(r1: i32) { write_reg32(r1, !read_reg32(r1)); }
-pub unsafe fn instr32_F7_3_mem(addr: i32) { safe_read_write32(addr, &|x| neg32(x)) }
+pub unsafe fn instr32_F7_3_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| neg32(x))
+}
 pub unsafe fn instr32_F7_3_reg(r1: i32) { write_reg32(r1, neg32(read_reg32(r1))); }
-pub unsafe fn instr32_F7_4_mem(addr: i32) { mul32(return_on_pagefault!(safe_read32s(addr))); }
+pub unsafe fn instr32_F7_4_mem(seg: i32, addr: i32) {
+    mul32(return_on_pagefault!(safe_read32s_seg(seg, addr)));
+}
 pub unsafe fn instr32_F7_4_reg(r1: i32) { mul32(read_reg32(r1)); }
-pub unsafe fn instr32_F7_5_mem(addr: i32) { imul32(return_on_pagefault!(safe_read32s(addr))); }
+pub unsafe fn instr32_F7_5_mem(seg: i32, addr: i32) {
+    imul32(return_on_pagefault!(safe_read32s_seg(seg, addr)));
+}
 pub unsafe fn instr32_F7_5_reg(r1: i32) { imul32(read_reg32(r1)); }
-pub unsafe fn instr32_F7_6_mem(addr: i32) {
-    div32(return_on_pagefault!(safe_read32s(addr)) as u32);
+pub unsafe fn instr32_F7_6_mem(seg: i32, addr: i32) {
+    div32(return_on_pagefault!(safe_read32s_seg(seg, addr)) as u32);
 }
 pub unsafe fn instr32_F7_6_reg(r1: i32) { div32(read_reg32(r1) as u32); }
-pub unsafe fn instr32_F7_7_mem(addr: i32) { idiv32(return_on_pagefault!(safe_read32s(addr))); }
+pub unsafe fn instr32_F7_7_mem(seg: i32, addr: i32) {
+    idiv32(return_on_pagefault!(safe_read32s_seg(seg, addr)));
+}
 pub unsafe fn instr32_F7_7_reg(r1: i32) { idiv32(read_reg32(r1)); }
 
 pub unsafe fn instr_F8() {
@@ -2361,13 +2686,17 @@ pub unsafe fn instr_FD() {
     *flags |= FLAG_DIRECTION;
 }
 
-pub unsafe fn instr_FE_0_mem(addr: i32) { safe_read_write8(addr, &|x| inc8(x)) }
+pub unsafe fn instr_FE_0_mem(seg: i32, addr: i32) { safe_read_write8_seg(seg, addr, &|x| inc8(x)) }
 pub unsafe fn instr_FE_0_reg(r1: i32) { write_reg8(r1, inc8(read_reg8(r1))); }
-pub unsafe fn instr_FE_1_mem(addr: i32) { safe_read_write8(addr, &|x| dec8(x)) }
+pub unsafe fn instr_FE_1_mem(seg: i32, addr: i32) { safe_read_write8_seg(seg, addr, &|x| dec8(x)) }
 pub unsafe fn instr_FE_1_reg(r1: i32) { write_reg8(r1, dec8(read_reg8(r1))); }
-pub unsafe fn instr16_FF_0_mem(addr: i32) { safe_read_write16(addr, &|x| inc16(x)) }
+pub unsafe fn instr16_FF_0_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| inc16(x))
+}
 pub unsafe fn instr16_FF_0_reg(r1: i32) { write_reg16(r1, inc16(read_reg16(r1))); }
-pub unsafe fn instr16_FF_1_mem(addr: i32) { safe_read_write16(addr, &|x| dec16(x)) }
+pub unsafe fn instr16_FF_1_mem(seg: i32, addr: i32) {
+    safe_read_write16_seg(seg, addr, &|x| dec16(x))
+}
 pub unsafe fn instr16_FF_1_reg(r1: i32) { write_reg16(r1, dec16(read_reg16(r1))); }
 pub unsafe fn instr16_FF_2_helper(data: i32) {
     // call near
@@ -2376,8 +2705,8 @@ pub unsafe fn instr16_FF_2_helper(data: i32) {
     *instruction_pointer = cs + data;
     dbg_assert!(*is_32 || get_real_eip() < 0x10000);
 }
-pub unsafe fn instr16_FF_2_mem(addr: i32) {
-    instr16_FF_2_helper(return_on_pagefault!(safe_read16(addr)));
+pub unsafe fn instr16_FF_2_mem(seg: i32, addr: i32) {
+    instr16_FF_2_helper(return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 pub unsafe fn instr16_FF_2_reg(r1: i32) { instr16_FF_2_helper(read_reg16(r1)); }
 
@@ -2387,10 +2716,10 @@ pub unsafe fn instr16_FF_3_reg(_r: i32) {
     trigger_ud();
 }
 #[no_mangle]
-pub unsafe fn instr16_FF_3_mem(addr: i32) {
+pub unsafe fn instr16_FF_3_mem(seg: i32, addr: i32) {
     // callf
-    let new_ip = return_on_pagefault!(safe_read16(addr));
-    let new_cs = return_on_pagefault!(safe_read16(addr + 2));
+    let new_ip = return_on_pagefault!(safe_read16_seg(seg, addr));
+    let new_cs = return_on_pagefault!(safe_read16_seg(seg, addr + 2));
     far_jump(new_ip, new_cs, true, false);
     dbg_assert!(*is_32 || get_real_eip() < 0x10000);
 }
@@ -2399,8 +2728,8 @@ pub unsafe fn instr16_FF_4_helper(data: i32) {
     *instruction_pointer = get_seg_cs() + data;
     dbg_assert!(*is_32 || get_real_eip() < 0x10000);
 }
-pub unsafe fn instr16_FF_4_mem(addr: i32) {
-    instr16_FF_4_helper(return_on_pagefault!(safe_read16(addr)));
+pub unsafe fn instr16_FF_4_mem(seg: i32, addr: i32) {
+    instr16_FF_4_helper(return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 pub unsafe fn instr16_FF_4_reg(r1: i32) { instr16_FF_4_helper(read_reg16(r1)); }
 
@@ -2410,23 +2739,27 @@ pub unsafe fn instr16_FF_5_reg(_r: i32) {
     trigger_ud();
 }
 #[no_mangle]
-pub unsafe fn instr16_FF_5_mem(addr: i32) {
+pub unsafe fn instr16_FF_5_mem(seg: i32, addr: i32) {
     // jmpf
-    let new_ip = return_on_pagefault!(safe_read16(addr));
-    let new_cs = return_on_pagefault!(safe_read16(addr + 2));
+    let new_ip = return_on_pagefault!(safe_read16_seg(seg, addr));
+    let new_cs = return_on_pagefault!(safe_read16_seg(seg, addr + 2));
     far_jump(new_ip, new_cs, false, false);
     dbg_assert!(*is_32 || get_real_eip() < 0x10000);
 }
-pub unsafe fn instr16_FF_6_mem(addr: i32) {
-    return_on_pagefault!(push16(return_on_pagefault!(safe_read16(addr))));
+pub unsafe fn instr16_FF_6_mem(seg: i32, addr: i32) {
+    return_on_pagefault!(push16(return_on_pagefault!(safe_read16_seg(seg, addr))));
 }
 pub unsafe fn instr16_FF_6_reg(r1: i32) {
     return_on_pagefault!(push16(read_reg16(r1)));
 }
 
-pub unsafe fn instr32_FF_0_mem(addr: i32) { safe_read_write32(addr, &|x| inc32(x)) }
+pub unsafe fn instr32_FF_0_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| inc32(x))
+}
 pub unsafe fn instr32_FF_0_reg(r1: i32) { write_reg32(r1, inc32(read_reg32(r1))); }
-pub unsafe fn instr32_FF_1_mem(addr: i32) { safe_read_write32(addr, &|x| dec32(x)) }
+pub unsafe fn instr32_FF_1_mem(seg: i32, addr: i32) {
+    safe_read_write32_seg(seg, addr, &|x| dec32(x))
+}
 pub unsafe fn instr32_FF_1_reg(r1: i32) { write_reg32(r1, dec32(read_reg32(r1))); }
 
 pub unsafe fn instr32_FF_2_helper(data: i32) {
@@ -2436,8 +2769,8 @@ pub unsafe fn instr32_FF_2_helper(data: i32) {
     dbg_assert!(*is_32 || data < 0x10000);
     *instruction_pointer = cs + data;
 }
-pub unsafe fn instr32_FF_2_mem(addr: i32) {
-    instr32_FF_2_helper(return_on_pagefault!(safe_read32s(addr)));
+pub unsafe fn instr32_FF_2_mem(seg: i32, addr: i32) {
+    instr32_FF_2_helper(return_on_pagefault!(safe_read32s_seg(seg, addr)));
 }
 pub unsafe fn instr32_FF_2_reg(r1: i32) { instr32_FF_2_helper(read_reg32(r1)); }
 #[no_mangle]
@@ -2446,10 +2779,10 @@ pub unsafe fn instr32_FF_3_reg(_r: i32) {
     trigger_ud();
 }
 #[no_mangle]
-pub unsafe fn instr32_FF_3_mem(addr: i32) {
+pub unsafe fn instr32_FF_3_mem(seg: i32, addr: i32) {
     // callf
-    let new_ip = return_on_pagefault!(safe_read32s(addr));
-    let new_cs = return_on_pagefault!(safe_read16(addr + 4));
+    let new_ip = return_on_pagefault!(safe_read32s_seg(seg, addr));
+    let new_cs = return_on_pagefault!(safe_read16_seg(seg, addr + 4));
     if !*protected_mode || vm86_mode() {
         if 0 != new_ip as u32 & 0xFFFF0000 {
             dbg_assert!(false);
@@ -2464,8 +2797,8 @@ pub unsafe fn instr32_FF_4_helper(data: i32) {
     dbg_assert!(*is_32 || data < 0x10000);
     *instruction_pointer = get_seg_cs() + data;
 }
-pub unsafe fn instr32_FF_4_mem(addr: i32) {
-    instr32_FF_4_helper(return_on_pagefault!(safe_read32s(addr)));
+pub unsafe fn instr32_FF_4_mem(seg: i32, addr: i32) {
+    instr32_FF_4_helper(return_on_pagefault!(safe_read32s_seg(seg, addr)));
 }
 pub unsafe fn instr32_FF_4_reg(r1: i32) { instr32_FF_4_helper(read_reg32(r1)); }
 
@@ -2475,10 +2808,10 @@ pub unsafe fn instr32_FF_5_reg(_r: i32) {
     trigger_ud();
 }
 #[no_mangle]
-pub unsafe fn instr32_FF_5_mem(addr: i32) {
+pub unsafe fn instr32_FF_5_mem(seg: i32, addr: i32) {
     // jmpf
-    let new_ip = return_on_pagefault!(safe_read32s(addr));
-    let new_cs = return_on_pagefault!(safe_read16(addr + 4));
+    let new_ip = return_on_pagefault!(safe_read32s_seg(seg, addr));
+    let new_cs = return_on_pagefault!(safe_read16_seg(seg, addr + 4));
     if !*protected_mode || vm86_mode() {
         if 0 != new_ip as u32 & 0xFFFF0000 {
             dbg_assert!(false);
@@ -2487,8 +2820,8 @@ pub unsafe fn instr32_FF_5_mem(addr: i32) {
     far_jump(new_ip, new_cs, false, true);
     dbg_assert!(*is_32 || new_ip < 0x10000);
 }
-pub unsafe fn instr32_FF_6_mem(addr: i32) {
-    return_on_pagefault!(push32(return_on_pagefault!(safe_read32s(addr))));
+pub unsafe fn instr32_FF_6_mem(seg: i32, addr: i32) {
+    return_on_pagefault!(push32(return_on_pagefault!(safe_read32s_seg(seg, addr))));
 }
 pub unsafe fn instr32_FF_6_reg(r1: i32) {
     return_on_pagefault!(push32(read_reg32(r1)));
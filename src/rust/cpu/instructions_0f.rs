@@ -15,14 +15,15 @@ unsafe fn unimplemented_sse() {
 
 use cpu::arith::{
     bsf16, bsf32, bsr16, bsr32, bt_mem, bt_reg, btc_mem, btc_reg, btr_mem, btr_reg, bts_mem,
-    bts_reg, cmpxchg16, cmpxchg32, cmpxchg8, popcnt, shld16, shld32, shrd16, shrd32, xadd16,
-    xadd32, xadd8,
+    bts_reg, cmpxchg16, cmpxchg32, cmpxchg8, crc32, lzcnt16, lzcnt32, popcnt, shld16, shld32,
+    shrd16, shrd32, tzcnt16, tzcnt32, xadd16, xadd32, xadd8,
 };
 use cpu::arith::{
     imul_reg16, imul_reg32, saturate_sd_to_sb, saturate_sd_to_sw, saturate_sd_to_ub,
     saturate_sw_to_sb, saturate_sw_to_ub, saturate_ud_to_ub, saturate_uw,
 };
 use cpu::cpu::*;
+use cpu::cpuid;
 use cpu::fpu::fpu_set_tag_word;
 use cpu::global_pointers::*;
 use cpu::misc_instr::{
@@ -32,19 +33,21 @@ use cpu::misc_instr::{
 };
 use cpu::misc_instr::{lar, lsl, verr, verw};
 use cpu::misc_instr::{lss16, lss32};
+use cpu::misc_instr::{xcr0_supported, xrstor, xsave, XSTATE_X87};
+use cpu::replay;
 use cpu::sse_instr::*;
 
 #[no_mangle]
-pub unsafe fn instr16_0F00_0_mem(addr: i32) {
+pub unsafe fn instr16_0F00_0_mem(seg: i32, addr: i32) {
     // sldt
     if !*protected_mode || vm86_mode() {
         trigger_ud();
         return;
     }
-    return_on_pagefault!(safe_write16(addr, *sreg.offset(LDTR as isize) as i32));
+    return_on_pagefault!(safe_write16_seg(seg, addr, *sreg.offset(LDTR as isize) as i32));
 }
 #[no_mangle]
-pub unsafe fn instr32_0F00_0_mem(addr: i32) { instr16_0F00_0_mem(addr) }
+pub unsafe fn instr32_0F00_0_mem(seg: i32, addr: i32) { instr16_0F00_0_mem(seg, addr) }
 #[no_mangle]
 pub unsafe fn instr16_0F00_0_reg(r: i32) {
     if !*protected_mode || vm86_mode() {
@@ -63,16 +66,16 @@ pub unsafe fn instr32_0F00_0_reg(r: i32) {
 }
 
 #[no_mangle]
-pub unsafe fn instr16_0F00_1_mem(addr: i32) {
+pub unsafe fn instr16_0F00_1_mem(seg: i32, addr: i32) {
     // str
     if !*protected_mode || vm86_mode() {
         trigger_ud();
         return;
     }
-    return_on_pagefault!(safe_write16(addr, *sreg.offset(TR as isize) as i32));
+    return_on_pagefault!(safe_write16_seg(seg, addr, *sreg.offset(TR as isize) as i32));
 }
 #[no_mangle]
-pub unsafe fn instr32_0F00_1_mem(addr: i32) { instr16_0F00_1_mem(addr) }
+pub unsafe fn instr32_0F00_1_mem(seg: i32, addr: i32) { instr16_0F00_1_mem(seg, addr) }
 #[no_mangle]
 pub unsafe fn instr16_0F00_1_reg(r: i32) {
     if !*protected_mode || vm86_mode() {
@@ -91,7 +94,7 @@ pub unsafe fn instr32_0F00_1_reg(r: i32) {
 }
 
 #[no_mangle]
-pub unsafe fn instr16_0F00_2_mem(addr: i32) {
+pub unsafe fn instr16_0F00_2_mem(seg: i32, addr: i32) {
     // lldt
     if !*protected_mode || vm86_mode() {
         trigger_ud();
@@ -100,11 +103,11 @@ pub unsafe fn instr16_0F00_2_mem(addr: i32) {
         trigger_gp(0);
     }
     else {
-        return_on_pagefault!(load_ldt(return_on_pagefault!(safe_read16(addr))));
+        return_on_pagefault!(load_ldt(return_on_pagefault!(safe_read16_seg(seg, addr))));
     };
 }
 #[no_mangle]
-pub unsafe fn instr32_0F00_2_mem(addr: i32) { instr16_0F00_2_mem(addr) }
+pub unsafe fn instr32_0F00_2_mem(seg: i32, addr: i32) { instr16_0F00_2_mem(seg, addr) }
 #[no_mangle]
 pub unsafe fn instr16_0F00_2_reg(r: i32) {
     if !*protected_mode || vm86_mode() {
@@ -121,7 +124,7 @@ pub unsafe fn instr16_0F00_2_reg(r: i32) {
 pub unsafe fn instr32_0F00_2_reg(r: i32) { instr16_0F00_2_reg(r) }
 
 #[no_mangle]
-pub unsafe fn instr16_0F00_3_mem(addr: i32) {
+pub unsafe fn instr16_0F00_3_mem(seg: i32, addr: i32) {
     // ltr
     if !*protected_mode || vm86_mode() {
         trigger_ud();
@@ -130,11 +133,11 @@ pub unsafe fn instr16_0F00_3_mem(addr: i32) {
         trigger_gp(0);
     }
     else {
-        load_tr(return_on_pagefault!(safe_read16(addr)));
+        load_tr(return_on_pagefault!(safe_read16_seg(seg, addr)));
     };
 }
 #[no_mangle]
-pub unsafe fn instr32_0F00_3_mem(addr: i32) { instr16_0F00_3_mem(addr); }
+pub unsafe fn instr32_0F00_3_mem(seg: i32, addr: i32) { instr16_0F00_3_mem(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr16_0F00_3_reg(r: i32) {
     if !*protected_mode || vm86_mode() {
@@ -151,16 +154,16 @@ pub unsafe fn instr16_0F00_3_reg(r: i32) {
 pub unsafe fn instr32_0F00_3_reg(r: i32) { instr16_0F00_3_reg(r) }
 
 #[no_mangle]
-pub unsafe fn instr16_0F00_4_mem(addr: i32) {
+pub unsafe fn instr16_0F00_4_mem(seg: i32, addr: i32) {
     if !*protected_mode || vm86_mode() {
         dbg_log!("verr #ud");
         trigger_ud();
         return;
     }
-    verr(return_on_pagefault!(safe_read16(addr)));
+    verr(return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 #[no_mangle]
-pub unsafe fn instr32_0F00_4_mem(addr: i32) { instr16_0F00_4_mem(addr) }
+pub unsafe fn instr32_0F00_4_mem(seg: i32, addr: i32) { instr16_0F00_4_mem(seg, addr) }
 #[no_mangle]
 pub unsafe fn instr16_0F00_4_reg(r: i32) {
     if !*protected_mode || vm86_mode() {
@@ -173,16 +176,16 @@ pub unsafe fn instr16_0F00_4_reg(r: i32) {
 #[no_mangle]
 pub unsafe fn instr32_0F00_4_reg(r: i32) { instr16_0F00_4_reg(r) }
 #[no_mangle]
-pub unsafe fn instr16_0F00_5_mem(addr: i32) {
+pub unsafe fn instr16_0F00_5_mem(seg: i32, addr: i32) {
     if !*protected_mode || vm86_mode() {
         dbg_log!("verw #ud");
         trigger_ud();
         return;
     }
-    verw(return_on_pagefault!(safe_read16(addr)));
+    verw(return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 #[no_mangle]
-pub unsafe fn instr32_0F00_5_mem(addr: i32) { instr16_0F00_5_mem(addr) }
+pub unsafe fn instr32_0F00_5_mem(seg: i32, addr: i32) { instr16_0F00_5_mem(seg, addr) }
 #[no_mangle]
 pub unsafe fn instr16_0F00_5_reg(r: i32) {
     if !*protected_mode || vm86_mode() {
@@ -200,70 +203,168 @@ pub unsafe fn instr16_0F01_0_reg(_r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr32_0F01_0_reg(_r: i32) { trigger_ud(); }
 
-unsafe fn sgdt(addr: i32, mask: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 6));
-    safe_write16(addr, *gdtr_size).unwrap();
-    safe_write32(addr + 2, *gdtr_offset & mask).unwrap();
+unsafe fn sgdt(seg: i32, addr: i32, mask: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 6));
+    safe_write16_seg(seg, addr, *gdtr_size).unwrap();
+    safe_write32_seg(seg, addr + 2, *gdtr_offset & mask).unwrap();
 }
 #[no_mangle]
-pub unsafe fn instr16_0F01_0_mem(addr: i32) { sgdt(addr, 0xFFFFFF) }
+pub unsafe fn instr16_0F01_0_mem(seg: i32, addr: i32) { sgdt(seg, addr, 0xFFFFFF) }
 #[no_mangle]
-pub unsafe fn instr32_0F01_0_mem(addr: i32) { sgdt(addr, -1) }
+pub unsafe fn instr32_0F01_0_mem(seg: i32, addr: i32) { sgdt(seg, addr, -1) }
+
+unsafe fn monitor(is_asize_32: bool, seg: i32) {
+    if read_reg32(ECX) != 0 {
+        // no extensions
+        trigger_gp(0);
+        return;
+    }
+    let offset = if is_asize_32 { read_reg32(EAX) } else { read_reg16(AX) };
+    let addr = return_on_pagefault!(get_seg(seg)) + offset;
+    return_on_pagefault!(check_segment_access(seg, addr, 1, false));
+    let phys_addr = return_on_pagefault!(translate_address_read(addr));
+    monitor_arm(phys_addr);
+}
+
+unsafe fn mwait() {
+    let ecx = read_reg32(ECX);
+    if ecx & !1 != 0 {
+        trigger_gp(0);
+        return;
+    }
+    if monitor_line.is_none() {
+        // not armed, or already triggered by a write
+        return;
+    }
+
+    // The hint in eax (the target c-state) is ignored, all c-states are the same as hlt
+    *in_hlt = true;
+    in_mwait = true;
+    mwait_break_on_masked_interrupts = ecx & 1 != 0;
+
+    // As in hlt, this may end the wait right away
+    run_hardware_timers(*acpi_enabled, cpu_microtick());
+    handle_irqs();
+}
 
 #[no_mangle]
-pub unsafe fn instr16_0F01_1_reg(_r: i32) { trigger_ud(); }
+pub unsafe fn instr16_0F01_1_reg(r: i32) { instr_0F01_1_reg(r, is_asize_32(), segment_prefix(DS)) }
 #[no_mangle]
-pub unsafe fn instr32_0F01_1_reg(_r: i32) { trigger_ud(); }
+pub unsafe fn instr32_0F01_1_reg(r: i32) { instr16_0F01_1_reg(r) }
+/// Called by the jit with the address size and segment of monitor, which depend on the prefixes
+#[no_mangle]
+pub unsafe fn instr_0F01_1_reg(r: i32, is_asize_32: bool, seg: i32) {
+    // 0F 01 C8: monitor, 0F 01 C9: mwait
+    if r == 0 || r == 1 {
+        if 0 != *cpl || !cpuid::has_feature(cpuid::FEATURE_MONITOR) {
+            trigger_ud();
+        }
+        else if r == 0 {
+            monitor(is_asize_32, seg);
+        }
+        else {
+            mwait();
+        }
+        return;
+    }
+
+    // 0F 01 CA: clac, 0F 01 CB: stac
+    if r != 2 && r != 3 || 0 != *cpl || !cpuid::has_feature(cpuid::FEATURE_SMAP) {
+        trigger_ud();
+        return;
+    }
+    if r == 2 {
+        *flags &= !FLAG_AC;
+    }
+    else {
+        *flags |= FLAG_AC;
+    }
+}
 
-unsafe fn sidt(addr: i32, mask: i32) {
-    return_on_pagefault!(writable_or_pagefault(addr, 6));
-    safe_write16(addr, *idtr_size).unwrap();
-    safe_write32(addr + 2, *idtr_offset & mask).unwrap();
+unsafe fn sidt(seg: i32, addr: i32, mask: i32) {
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 6));
+    safe_write16_seg(seg, addr, *idtr_size).unwrap();
+    safe_write32_seg(seg, addr + 2, *idtr_offset & mask).unwrap();
 }
 #[no_mangle]
-pub unsafe fn instr16_0F01_1_mem(addr: i32) { sidt(addr, 0xFFFFFF) }
+pub unsafe fn instr16_0F01_1_mem(seg: i32, addr: i32) { sidt(seg, addr, 0xFFFFFF) }
 #[no_mangle]
-pub unsafe fn instr32_0F01_1_mem(addr: i32) { sidt(addr, -1) }
+pub unsafe fn instr32_0F01_1_mem(seg: i32, addr: i32) { sidt(seg, addr, -1) }
 
 #[no_mangle]
-pub unsafe fn instr16_0F01_2_reg(_r: i32) { trigger_ud(); }
+pub unsafe fn instr16_0F01_2_reg(r: i32) {
+    // 0F 01 D0: xgetbv, 0F 01 D1: xsetbv
+    if r != 0 && r != 1 || *cr.offset(4) & CR4_OSXSAVE == 0 {
+        trigger_ud();
+        return;
+    }
+    if r == 0 {
+        // only xcr0 (xgetbv with ecx=1 isn't enumerated)
+        if read_reg32(ECX) != 0 {
+            dbg_log!("#gp xgetbv ecx={:x}", read_reg32(ECX));
+            trigger_gp(0);
+            return;
+        }
+        write_reg32(EAX, *xcr0);
+        write_reg32(EDX, 0);
+    }
+    else {
+        let value = read_reg32(EAX);
+        if 0 != *cpl
+            || read_reg32(ECX) != 0
+            || read_reg32(EDX) != 0
+            || value & XSTATE_X87 == 0
+            || value & !xcr0_supported() != 0
+        {
+            dbg_log!(
+                "#gp xsetbv ecx={:x} edx:eax={:x}:{:x}",
+                read_reg32(ECX),
+                read_reg32(EDX),
+                value
+            );
+            trigger_gp(0);
+            return;
+        }
+        *xcr0 = value;
+    }
+}
 #[no_mangle]
-pub unsafe fn instr32_0F01_2_reg(_r: i32) { trigger_ud(); }
+pub unsafe fn instr32_0F01_2_reg(r: i32) { instr16_0F01_2_reg(r) }
 
-unsafe fn lgdt(addr: i32, mask: i32) {
+unsafe fn lgdt(seg: i32, addr: i32, mask: i32) {
     if 0 != *cpl {
         trigger_gp(0);
         return;
     }
-    let size = return_on_pagefault!(safe_read16(addr));
-    let offset = return_on_pagefault!(safe_read32s(addr + 2));
+    let size = return_on_pagefault!(safe_read16_seg(seg, addr));
+    let offset = return_on_pagefault!(safe_read32s_seg(seg, addr + 2));
     *gdtr_size = size;
     *gdtr_offset = offset & mask;
 }
 #[no_mangle]
-pub unsafe fn instr16_0F01_2_mem(addr: i32) { lgdt(addr, 0xFFFFFF); }
+pub unsafe fn instr16_0F01_2_mem(seg: i32, addr: i32) { lgdt(seg, addr, 0xFFFFFF); }
 #[no_mangle]
-pub unsafe fn instr32_0F01_2_mem(addr: i32) { lgdt(addr, -1); }
+pub unsafe fn instr32_0F01_2_mem(seg: i32, addr: i32) { lgdt(seg, addr, -1); }
 
 #[no_mangle]
 pub unsafe fn instr16_0F01_3_reg(_r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr32_0F01_3_reg(_r: i32) { trigger_ud(); }
 
-unsafe fn lidt(addr: i32, mask: i32) {
+unsafe fn lidt(seg: i32, addr: i32, mask: i32) {
     if 0 != *cpl {
         trigger_gp(0);
         return;
     }
-    let size = return_on_pagefault!(safe_read16(addr));
-    let offset = return_on_pagefault!(safe_read32s(addr + 2));
+    let size = return_on_pagefault!(safe_read16_seg(seg, addr));
+    let offset = return_on_pagefault!(safe_read32s_seg(seg, addr + 2));
     *idtr_size = size;
     *idtr_offset = offset & mask;
 }
 #[no_mangle]
-pub unsafe fn instr16_0F01_3_mem(addr: i32) { lidt(addr, 0xFFFFFF); }
+pub unsafe fn instr16_0F01_3_mem(seg: i32, addr: i32) { lidt(seg, addr, 0xFFFFFF); }
 #[no_mangle]
-pub unsafe fn instr32_0F01_3_mem(addr: i32) { lidt(addr, -1); }
+pub unsafe fn instr32_0F01_3_mem(seg: i32, addr: i32) { lidt(seg, addr, -1); }
 
 #[no_mangle]
 pub unsafe fn instr16_0F01_4_reg(r: i32) {
@@ -273,12 +374,12 @@ pub unsafe fn instr16_0F01_4_reg(r: i32) {
 #[no_mangle]
 pub unsafe fn instr32_0F01_4_reg(r: i32) { write_reg32(r, *cr); }
 #[no_mangle]
-pub unsafe fn instr16_0F01_4_mem(addr: i32) {
-    return_on_pagefault!(safe_write16(addr, *cr));
+pub unsafe fn instr16_0F01_4_mem(seg: i32, addr: i32) {
+    return_on_pagefault!(safe_write16_seg(seg, addr, *cr));
 }
 #[no_mangle]
-pub unsafe fn instr32_0F01_4_mem(addr: i32) {
-    return_on_pagefault!(safe_write16(addr, *cr));
+pub unsafe fn instr32_0F01_4_mem(seg: i32, addr: i32) {
+    return_on_pagefault!(safe_write16_seg(seg, addr, *cr));
 }
 
 #[no_mangle]
@@ -301,23 +402,38 @@ pub unsafe fn instr16_0F01_6_reg(r: i32) {
 #[no_mangle]
 pub unsafe fn instr32_0F01_6_reg(r: i32) { instr16_0F01_6_reg(r); }
 #[no_mangle]
-pub unsafe fn instr16_0F01_6_mem(addr: i32) {
+pub unsafe fn instr16_0F01_6_mem(seg: i32, addr: i32) {
     if 0 != *cpl {
         trigger_gp(0);
         return;
     }
-    lmsw(return_on_pagefault!(safe_read16(addr)));
+    lmsw(return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 #[no_mangle]
-pub unsafe fn instr32_0F01_6_mem(addr: i32) { instr16_0F01_6_mem(addr) }
+pub unsafe fn instr32_0F01_6_mem(seg: i32, addr: i32) { instr16_0F01_6_mem(seg, addr) }
 
 #[no_mangle]
-pub unsafe fn instr16_0F01_7_reg(_r: i32) { trigger_ud(); }
+pub unsafe fn instr16_0F01_7_reg(r: i32) {
+    // 0F 01 F9: rdtscp
+    // Instructions are executed in order, so all previous instructions have completed
+    if r != 1 {
+        trigger_ud();
+        return;
+    }
+    if 0 != *cpl && 0 != *cr.offset(4) & CR4_TSD {
+        trigger_gp(0);
+        return;
+    }
+    let tsc = read_tsc();
+    write_reg32(EAX, tsc as i32);
+    write_reg32(EDX, (tsc >> 32) as i32);
+    write_reg32(ECX, *tsc_aux);
+}
 #[no_mangle]
-pub unsafe fn instr32_0F01_7_reg(_r: i32) { trigger_ud(); }
+pub unsafe fn instr32_0F01_7_reg(r: i32) { instr16_0F01_7_reg(r) }
 
 #[no_mangle]
-pub unsafe fn instr16_0F01_7_mem(addr: i32) {
+pub unsafe fn instr16_0F01_7_mem(_seg: i32, addr: i32) {
     // invlpg
     if 0 != *cpl {
         trigger_gp(0);
@@ -326,10 +442,10 @@ pub unsafe fn instr16_0F01_7_mem(addr: i32) {
     invlpg(addr);
 }
 #[no_mangle]
-pub unsafe fn instr32_0F01_7_mem(addr: i32) { instr16_0F01_7_mem(addr) }
+pub unsafe fn instr32_0F01_7_mem(seg: i32, addr: i32) { instr16_0F01_7_mem(seg, addr) }
 
 #[no_mangle]
-pub unsafe fn instr16_0F02_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_0F02_mem(seg: i32, addr: i32, r: i32) {
     if !*protected_mode || vm86_mode() {
         dbg_log!("lar #ud");
         trigger_ud();
@@ -337,7 +453,7 @@ pub unsafe fn instr16_0F02_mem(addr: i32, r: i32) {
     }
     write_reg16(
         r,
-        lar(return_on_pagefault!(safe_read16(addr)), read_reg16(r)),
+        lar(return_on_pagefault!(safe_read16_seg(seg, addr)), read_reg16(r)),
     );
 }
 #[no_mangle]
@@ -350,7 +466,7 @@ pub unsafe fn instr16_0F02_reg(r1: i32, r: i32) {
     write_reg16(r, lar(read_reg16(r1), read_reg16(r)));
 }
 #[no_mangle]
-pub unsafe fn instr32_0F02_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_0F02_mem(seg: i32, addr: i32, r: i32) {
     if !*protected_mode || vm86_mode() {
         dbg_log!("lar #ud");
         trigger_ud();
@@ -358,7 +474,7 @@ pub unsafe fn instr32_0F02_mem(addr: i32, r: i32) {
     }
     write_reg32(
         r,
-        lar(return_on_pagefault!(safe_read16(addr)), read_reg32(r)),
+        lar(return_on_pagefault!(safe_read16_seg(seg, addr)), read_reg32(r)),
     );
 }
 #[no_mangle]
@@ -371,7 +487,7 @@ pub unsafe fn instr32_0F02_reg(r1: i32, r: i32) {
     write_reg32(r, lar(read_reg16(r1), read_reg32(r)));
 }
 #[no_mangle]
-pub unsafe fn instr16_0F03_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_0F03_mem(seg: i32, addr: i32, r: i32) {
     if !*protected_mode || vm86_mode() {
         dbg_log!("lsl #ud");
         trigger_ud();
@@ -379,7 +495,7 @@ pub unsafe fn instr16_0F03_mem(addr: i32, r: i32) {
     }
     write_reg16(
         r,
-        lsl(return_on_pagefault!(safe_read16(addr)), read_reg16(r)),
+        lsl(return_on_pagefault!(safe_read16_seg(seg, addr)), read_reg16(r)),
     );
 }
 #[no_mangle]
@@ -392,7 +508,7 @@ pub unsafe fn instr16_0F03_reg(r1: i32, r: i32) {
     write_reg16(r, lsl(read_reg16(r1), read_reg16(r)));
 }
 #[no_mangle]
-pub unsafe fn instr32_0F03_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_0F03_mem(seg: i32, addr: i32, r: i32) {
     if !*protected_mode || vm86_mode() {
         dbg_log!("lsl #ud");
         trigger_ud();
@@ -400,7 +516,7 @@ pub unsafe fn instr32_0F03_mem(addr: i32, r: i32) {
     }
     write_reg32(
         r,
-        lsl(return_on_pagefault!(safe_read16(addr)), read_reg32(r)),
+        lsl(return_on_pagefault!(safe_read16_seg(seg, addr)), read_reg32(r)),
     );
 }
 #[no_mangle]
@@ -415,7 +531,34 @@ pub unsafe fn instr32_0F03_reg(r1: i32, r: i32) {
 #[no_mangle]
 pub unsafe fn instr_0F04() { undefined_instruction(); }
 #[no_mangle]
-pub unsafe fn instr_0F05() { undefined_instruction(); }
+pub unsafe fn instr_0F05() {
+    // syscall, as in legacy mode on amd cpus: The selectors and eip come from the star msr, eflags
+    // are not saved
+    if *efer & EFER_SCE == 0 {
+        dbg_log!("syscall #ud");
+        trigger_ud();
+        return;
+    }
+    let seg = (*star >> 32) as i32 & 0xFFFC;
+    write_reg32(ECX, get_real_eip());
+    *flags &= !FLAG_VM & !FLAG_INTERRUPT & !FLAG_RF;
+    *instruction_pointer = *star as i32;
+    *sreg.offset(CS as isize) = seg as u16;
+    *segment_is_null.offset(CS as isize) = false;
+    *segment_limits.offset(CS as isize) = -1i32 as u32;
+    *segment_offsets.offset(CS as isize) = 0;
+    *segment_access_bytes.offset(CS as isize) = 0x80 | (0 << 5) | 0x10 | 0x08 | 0x02; // P dpl0 S E RW
+    update_cs_size(true);
+    *cpl = 0;
+    cpl_changed();
+    *sreg.offset(SS as isize) = (seg + 8) as u16;
+    *segment_is_null.offset(SS as isize) = false;
+    *segment_limits.offset(SS as isize) = -1i32 as u32;
+    *segment_offsets.offset(SS as isize) = 0;
+    *segment_access_bytes.offset(SS as isize) = 0x80 | (0 << 5) | 0x10 | 0x02; // P dpl0 S RW
+    *stack_size_32 = true;
+    update_state_flags();
+}
 #[no_mangle]
 pub unsafe fn instr_0F06() {
     // clts
@@ -431,7 +574,37 @@ pub unsafe fn instr_0F06() {
     };
 }
 #[no_mangle]
-pub unsafe fn instr_0F07() { undefined_instruction(); }
+pub unsafe fn instr_0F07() {
+    // sysret, as in legacy mode on amd cpus
+    if *efer & EFER_SCE == 0 {
+        dbg_log!("sysret #ud");
+        trigger_ud();
+        return;
+    }
+    if !*protected_mode || 0 != *cpl {
+        dbg_log!("sysret #gp");
+        trigger_gp(0);
+        return;
+    }
+    let seg = (*star >> 48) as i32 & 0xFFFC;
+    *flags |= FLAG_INTERRUPT;
+    *instruction_pointer = read_reg32(ECX);
+    *sreg.offset(CS as isize) = (seg | 3) as u16;
+    *segment_is_null.offset(CS as isize) = false;
+    *segment_limits.offset(CS as isize) = -1i32 as u32;
+    *segment_offsets.offset(CS as isize) = 0;
+    *segment_access_bytes.offset(CS as isize) = 0x80 | (3 << 5) | 0x10 | 0x08 | 0x02; // P dpl3 S E RW
+    update_cs_size(true);
+    *cpl = 3;
+    cpl_changed();
+    *sreg.offset(SS as isize) = ((seg + 8) | 3) as u16;
+    *segment_is_null.offset(SS as isize) = false;
+    *segment_limits.offset(SS as isize) = -1i32 as u32;
+    *segment_offsets.offset(SS as isize) = 0;
+    *segment_access_bytes.offset(SS as isize) = 0x80 | (3 << 5) | 0x10 | 0x02; // P dpl3 S RW
+    *stack_size_32 = true;
+    update_state_flags();
+}
 #[no_mangle]
 pub unsafe fn instr_0F08() {
     // invd
@@ -470,17 +643,17 @@ pub unsafe fn instr_0F10(source: reg128, r: i32) {
     mov_rm_r128(source, r);
 }
 pub unsafe fn instr_0F10_reg(r1: i32, r2: i32) { instr_0F10(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F10_mem(addr: i32, r: i32) {
-    instr_0F10(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F10_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F10(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_F30F10_reg(r1: i32, r2: i32) {
     // movss xmm, xmm/m32
     let data = read_xmm128s(r1);
     write_xmm32(r2, data.u32[0] as i32);
 }
-pub unsafe fn instr_F30F10_mem(addr: i32, r: i32) {
+pub unsafe fn instr_F30F10_mem(seg: i32, addr: i32, r: i32) {
     // movss xmm, xmm/m32
-    let data = return_on_pagefault!(safe_read32s(addr));
+    let data = return_on_pagefault!(safe_read32s_seg(seg, addr));
     write_xmm128(r, data, 0, 0, 0);
 }
 pub unsafe fn instr_660F10(source: reg128, r: i32) {
@@ -488,58 +661,58 @@ pub unsafe fn instr_660F10(source: reg128, r: i32) {
     mov_rm_r128(source, r);
 }
 pub unsafe fn instr_660F10_reg(r1: i32, r2: i32) { instr_660F10(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F10_mem(addr: i32, r: i32) {
-    instr_660F10(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F10_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F10(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_F20F10_reg(r1: i32, r2: i32) {
     // movsd xmm, xmm/m64
     let data = read_xmm128s(r1);
     write_xmm64(r2, data.u64[0]);
 }
-pub unsafe fn instr_F20F10_mem(addr: i32, r: i32) {
+pub unsafe fn instr_F20F10_mem(seg: i32, addr: i32, r: i32) {
     // movsd xmm, xmm/m64
-    let data = return_on_pagefault!(safe_read64s(addr));
+    let data = return_on_pagefault!(safe_read64s_seg(seg, addr));
     write_xmm128_2(r, data, 0);
 }
 pub unsafe fn instr_0F11_reg(r1: i32, r2: i32) {
     // movups xmm/m128, xmm
     mov_r_r128(r1, r2);
 }
-pub unsafe fn instr_0F11_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0F11_mem(seg: i32, addr: i32, r: i32) {
     // movups xmm/m128, xmm
-    mov_r_m128(addr, r);
+    mov_r_m128(seg, addr, r);
 }
 pub unsafe fn instr_F30F11_reg(rm_dest: i32, reg_src: i32) {
     // movss xmm/m32, xmm
     let data = read_xmm128s(reg_src);
     write_xmm32(rm_dest, data.u32[0] as i32);
 }
-pub unsafe fn instr_F30F11_mem(addr: i32, r: i32) {
+pub unsafe fn instr_F30F11_mem(seg: i32, addr: i32, r: i32) {
     // movss xmm/m32, xmm
     let data = read_xmm128s(r);
-    return_on_pagefault!(safe_write32(addr, data.u32[0] as i32));
+    return_on_pagefault!(safe_write32_seg(seg, addr, data.u32[0] as i32));
 }
 pub unsafe fn instr_660F11_reg(r1: i32, r2: i32) {
     // movupd xmm/m128, xmm
     mov_r_r128(r1, r2);
 }
-pub unsafe fn instr_660F11_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660F11_mem(seg: i32, addr: i32, r: i32) {
     // movupd xmm/m128, xmm
-    mov_r_m128(addr, r);
+    mov_r_m128(seg, addr, r);
 }
 pub unsafe fn instr_F20F11_reg(r1: i32, r2: i32) {
     // movsd xmm/m64, xmm
     let data = read_xmm128s(r2);
     write_xmm64(r1, data.u64[0]);
 }
-pub unsafe fn instr_F20F11_mem(addr: i32, r: i32) {
+pub unsafe fn instr_F20F11_mem(seg: i32, addr: i32, r: i32) {
     // movsd xmm/m64, xmm
     let data = read_xmm64s(r);
-    return_on_pagefault!(safe_write64(addr, data));
+    return_on_pagefault!(safe_write64_seg(seg, addr, data));
 }
-pub unsafe fn instr_0F12_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0F12_mem(seg: i32, addr: i32, r: i32) {
     // movlps xmm, m64
-    let data = return_on_pagefault!(safe_read64s(addr));
+    let data = return_on_pagefault!(safe_read64s_seg(seg, addr));
     write_xmm64(r, data);
 }
 pub unsafe fn instr_0F12_reg(r1: i32, r2: i32) {
@@ -548,9 +721,9 @@ pub unsafe fn instr_0F12_reg(r1: i32, r2: i32) {
     write_xmm64(r2, data.u64[1]);
 }
 pub unsafe fn instr_660F12_reg(_r1: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F12_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660F12_mem(seg: i32, addr: i32, r: i32) {
     // movlpd xmm, m64
-    let data = return_on_pagefault!(safe_read64s(addr));
+    let data = return_on_pagefault!(safe_read64s_seg(seg, addr));
     write_xmm64(r, data);
 }
 #[no_mangle]
@@ -564,8 +737,8 @@ pub unsafe fn instr_F20F12(source: u64, r: i32) {
     );
 }
 pub unsafe fn instr_F20F12_reg(r1: i32, r2: i32) { instr_F20F12(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F12_mem(addr: i32, r: i32) {
-    instr_F20F12(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F12_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F12(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F12(source: reg128, r: i32) {
@@ -578,18 +751,18 @@ pub unsafe fn instr_F30F12(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_F30F12_reg(r1: i32, r2: i32) { instr_F30F12(read_xmm128s(r1), r2); }
-pub unsafe fn instr_F30F12_mem(addr: i32, r: i32) {
-    instr_F30F12(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_F30F12_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F12(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
-pub unsafe fn instr_0F13_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0F13_mem(seg: i32, addr: i32, r: i32) {
     // movlps m64, xmm
-    movl_r128_m64(addr, r);
+    movl_r128_m64(seg, addr, r);
 }
 pub unsafe fn instr_0F13_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 pub unsafe fn instr_660F13_reg(_r1: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F13_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660F13_mem(seg: i32, addr: i32, r: i32) {
     // movlpd xmm/m64, xmm
-    movl_r128_m64(addr, r);
+    movl_r128_m64(seg, addr, r);
 }
 
 #[no_mangle]
@@ -606,8 +779,8 @@ pub unsafe fn instr_0F14(source: u64, r: i32) {
     );
 }
 pub unsafe fn instr_0F14_reg(r1: i32, r2: i32) { instr_0F14(read_xmm64s(r1), r2); }
-pub unsafe fn instr_0F14_mem(addr: i32, r: i32) {
-    instr_0F14(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F14_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F14(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F14(source: u64, r: i32) {
@@ -623,8 +796,8 @@ pub unsafe fn instr_660F14(source: u64, r: i32) {
     );
 }
 pub unsafe fn instr_660F14_reg(r1: i32, r2: i32) { instr_660F14(read_xmm64s(r1), r2); }
-pub unsafe fn instr_660F14_mem(addr: i32, r: i32) {
-    instr_660F14(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_660F14_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F14(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F15(source: reg128, r: i32) {
@@ -640,8 +813,8 @@ pub unsafe fn instr_0F15(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_0F15_reg(r1: i32, r2: i32) { instr_0F15(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F15_mem(addr: i32, r: i32) {
-    instr_0F15(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F15_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F15(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F15(source: reg128, r: i32) {
@@ -657,23 +830,23 @@ pub unsafe fn instr_660F15(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660F15_reg(r1: i32, r2: i32) { instr_660F15(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F15_mem(addr: i32, r: i32) {
-    instr_660F15(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F15_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F15(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
 pub unsafe fn instr_0F16(source: u64, r: i32) { (*reg_xmm.offset(r as isize)).u64[1] = source; }
-pub unsafe fn instr_0F16_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0F16_mem(seg: i32, addr: i32, r: i32) {
     // movhps xmm, m64
-    instr_0F16(return_on_pagefault!(safe_read64s(addr)), r);
+    instr_0F16(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_0F16_reg(r1: i32, r2: i32) {
     // movlhps xmm, xmm
     instr_0F16(read_xmm64s(r1), r2);
 }
-pub unsafe fn instr_660F16_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660F16_mem(seg: i32, addr: i32, r: i32) {
     // movhpd xmm, m64
-    instr_0F16(return_on_pagefault!(safe_read64s(addr)), r);
+    instr_0F16(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_660F16_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 #[no_mangle]
@@ -687,30 +860,30 @@ pub unsafe fn instr_F30F16(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_F30F16_reg(r1: i32, r2: i32) { instr_F30F16(read_xmm128s(r1), r2); }
-pub unsafe fn instr_F30F16_mem(addr: i32, r: i32) {
-    instr_F30F16(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_F30F16_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F16(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
-pub unsafe fn instr_0F17_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0F17_mem(seg: i32, addr: i32, r: i32) {
     // movhps m64, xmm
-    movh_r128_m64(addr, r);
+    movh_r128_m64(seg, addr, r);
 }
 pub unsafe fn instr_0F17_reg(_r1: i32, _r2: i32) { trigger_ud(); }
-pub unsafe fn instr_660F17_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660F17_mem(seg: i32, addr: i32, r: i32) {
     // movhpd m64, xmm
-    movh_r128_m64(addr, r);
+    movh_r128_m64(seg, addr, r);
 }
 pub unsafe fn instr_660F17_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 
 pub unsafe fn instr_0F18_reg(_r1: i32, _r2: i32) {
     // reserved nop
 }
-pub unsafe fn instr_0F18_mem(_addr: i32, _r: i32) {
+pub unsafe fn instr_0F18_mem(_seg: i32, _addr: i32, _r: i32) {
     // prefetch
     // nop for us
 }
 
 pub unsafe fn instr_0F19_reg(_r1: i32, _r2: i32) {}
-pub unsafe fn instr_0F19_mem(_addr: i32, _r: i32) {}
+pub unsafe fn instr_0F19_mem(_seg: i32, _addr: i32, _r: i32) {}
 
 #[no_mangle]
 pub unsafe fn instr_0F1A() { undefined_instruction(); }
@@ -718,13 +891,13 @@ pub unsafe fn instr_0F1A() { undefined_instruction(); }
 pub unsafe fn instr_0F1B() { undefined_instruction(); }
 
 pub unsafe fn instr_0F1C_reg(_r1: i32, _r2: i32) {}
-pub unsafe fn instr_0F1C_mem(_addr: i32, _r: i32) {}
+pub unsafe fn instr_0F1C_mem(_seg: i32, _addr: i32, _r: i32) {}
 pub unsafe fn instr_0F1D_reg(_r1: i32, _r2: i32) {}
-pub unsafe fn instr_0F1D_mem(_addr: i32, _r: i32) {}
+pub unsafe fn instr_0F1D_mem(_seg: i32, _addr: i32, _r: i32) {}
 pub unsafe fn instr_0F1E_reg(_r1: i32, _r2: i32) {}
-pub unsafe fn instr_0F1E_mem(_addr: i32, _r: i32) {}
+pub unsafe fn instr_0F1E_mem(_seg: i32, _addr: i32, _r: i32) {}
 pub unsafe fn instr_0F1F_reg(_r1: i32, _r2: i32) {}
-pub unsafe fn instr_0F1F_mem(_addr: i32, _r: i32) {}
+pub unsafe fn instr_0F1F_mem(_seg: i32, _addr: i32, _r: i32) {}
 
 #[no_mangle]
 pub unsafe fn instr_0F20(r: i32, creg: i32) {
@@ -759,6 +932,14 @@ pub unsafe fn instr_0F21(r: i32, mut dreg_index: i32) {
         return;
     }
 
+    if *dreg.offset(7) & DR7_GD != 0 {
+        // General detect: Debug register accesses fault, so that debuggers can intercept them
+        *dreg.offset(7) &= !DR7_GD;
+        *dreg.offset(6) |= DR6_BD;
+        trigger_db();
+        return;
+    }
+
     if dreg_index == 4 || dreg_index == 5 {
         if 0 != *cr.offset(4) & CR4_DE {
             dbg_log!("#ud mov dreg 4/5 with cr4.DE set");
@@ -805,13 +986,15 @@ pub unsafe fn instr_0F22(r: i32, creg: i32) {
             dbg_log!("cr4 <- {:x}", data);
             if 0 != data as u32
                 & ((1 << 11 | 1 << 12 | 1 << 15 | 1 << 16 | 1 << 19) as u32 | 0xFFC00000)
+                || 0 != data & cpuid::cr4_unsupported_bits()
             {
                 dbg_log!("trigger_gp: Invalid cr4 bit");
                 trigger_gp(0);
                 return;
             }
             else {
-                if 0 != (*cr.offset(4) ^ data) & (CR4_PGE | CR4_PSE | CR4_PAE) {
+                if 0 != (*cr.offset(4) ^ data) & (CR4_PGE | CR4_PSE | CR4_PAE | CR4_SMEP | CR4_SMAP)
+                {
                     full_clear_tlb();
                 }
                 if data & CR4_PAE != 0
@@ -835,6 +1018,14 @@ pub unsafe fn instr_0F23(r: i32, mut dreg_index: i32) {
         return;
     }
 
+    if *dreg.offset(7) & DR7_GD != 0 {
+        // General detect: Debug register accesses fault, so that debuggers can intercept them
+        *dreg.offset(7) &= !DR7_GD;
+        *dreg.offset(6) |= DR6_BD;
+        trigger_db();
+        return;
+    }
+
     if dreg_index == 4 || dreg_index == 5 {
         if 0 != *cr.offset(4) & CR4_DE {
             dbg_log!("#ud mov dreg 4/5 with cr4.DE set");
@@ -846,7 +1037,17 @@ pub unsafe fn instr_0F23(r: i32, mut dreg_index: i32) {
             dreg_index += 2
         }
     }
-    *dreg.offset(dreg_index as isize) = read_reg32(r);
+    let value = read_reg32(r);
+    let old_dr7 = *dreg.offset(7);
+    *dreg.offset(dreg_index as isize) = match dreg_index {
+        6 => value & (DR6_B0_B3 | DR6_BD | DR6_BS | DR6_BT) | DR6_RESERVED,
+        7 => value & !DR7_RESERVED_ZERO | DR7_RESERVED,
+        _ => value,
+    };
+    if dreg_index != 6 && (old_dr7 | *dreg.offset(7)) & DR7_ENABLE_MASK != 0 {
+        // Pages with breakpoints are marked in the tlb (TLB_BREAKPOINT)
+        full_clear_tlb();
+    }
     if false {
         dbg_log!(
             "write dr{}: {:x}",
@@ -870,8 +1071,8 @@ pub unsafe fn instr_0F28(source: reg128, r: i32) {
     mov_rm_r128(source, r);
 }
 pub unsafe fn instr_0F28_reg(r1: i32, r2: i32) { instr_0F28(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F28_mem(addr: i32, r: i32) {
-    instr_0F28(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F28_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F28(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_660F28(source: reg128, r: i32) {
     // movapd xmm, xmm/m128
@@ -880,24 +1081,24 @@ pub unsafe fn instr_660F28(source: reg128, r: i32) {
     mov_rm_r128(source, r);
 }
 pub unsafe fn instr_660F28_reg(r1: i32, r2: i32) { instr_660F28(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F28_mem(addr: i32, r: i32) {
-    instr_660F28(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F28_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F28(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
-pub unsafe fn instr_0F29_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0F29_mem(seg: i32, addr: i32, r: i32) {
     // movaps m128, xmm
     let data = read_xmm128s(r);
     // XXX: Aligned write or #gp
-    return_on_pagefault!(safe_write128(addr, data));
+    return_on_pagefault!(safe_write128_seg(seg, addr, data));
 }
 pub unsafe fn instr_0F29_reg(r1: i32, r2: i32) {
     // movaps xmm, xmm
     mov_r_r128(r1, r2);
 }
-pub unsafe fn instr_660F29_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660F29_mem(seg: i32, addr: i32, r: i32) {
     // movapd m128, xmm
     let data = read_xmm128s(r);
     // XXX: Aligned write or #gp
-    return_on_pagefault!(safe_write128(addr, data));
+    return_on_pagefault!(safe_write128_seg(seg, addr, data));
 }
 pub unsafe fn instr_660F29_reg(r1: i32, r2: i32) {
     // movapd xmm, xmm
@@ -915,8 +1116,8 @@ pub unsafe fn instr_0F2A(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F2A_reg(r1: i32, r2: i32) { instr_0F2A(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F2A_mem(addr: i32, r: i32) {
-    instr_0F2A(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F2A_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F2A(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F2A(source: u64, r: i32) {
@@ -930,8 +1131,8 @@ pub unsafe fn instr_660F2A(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_660F2A_reg(r1: i32, r2: i32) { instr_660F2A(read_mmx64s(r1), r2); }
-pub unsafe fn instr_660F2A_mem(addr: i32, r: i32) {
-    instr_660F2A(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_660F2A_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F2A(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F2A(source: i32, r: i32) {
@@ -940,8 +1141,8 @@ pub unsafe fn instr_F20F2A(source: i32, r: i32) {
     write_xmm_f64(r, source as f64);
 }
 pub unsafe fn instr_F20F2A_reg(r1: i32, r2: i32) { instr_F20F2A(read_reg32(r1), r2); }
-pub unsafe fn instr_F20F2A_mem(addr: i32, r: i32) {
-    instr_F20F2A(return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr_F20F2A_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F2A(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F2A(source: i32, r: i32) {
@@ -952,21 +1153,21 @@ pub unsafe fn instr_F30F2A(source: i32, r: i32) {
     write_xmm_f32(r, result);
 }
 pub unsafe fn instr_F30F2A_reg(r1: i32, r2: i32) { instr_F30F2A(read_reg32(r1), r2); }
-pub unsafe fn instr_F30F2A_mem(addr: i32, r: i32) {
-    instr_F30F2A(return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr_F30F2A_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F2A(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 
 pub unsafe fn instr_0F2B_reg(_r1: i32, _r2: i32) { trigger_ud(); }
-pub unsafe fn instr_0F2B_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0F2B_mem(seg: i32, addr: i32, r: i32) {
     // movntps m128, xmm
     // XXX: Aligned write or #gp
-    mov_r_m128(addr, r);
+    mov_r_m128(seg, addr, r);
 }
 pub unsafe fn instr_660F2B_reg(_r1: i32, _r2: i32) { trigger_ud(); }
-pub unsafe fn instr_660F2B_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660F2B_mem(seg: i32, addr: i32, r: i32) {
     // movntpd m128, xmm
     // XXX: Aligned write or #gp
-    mov_r_m128(addr, r);
+    mov_r_m128(seg, addr, r);
 }
 
 pub unsafe fn instr_0F2C(source: u64, r: i32) {
@@ -981,8 +1182,8 @@ pub unsafe fn instr_0F2C(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 #[no_mangle]
-pub unsafe fn instr_0F2C_mem(addr: i32, r: i32) {
-    instr_0F2C(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F2C_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F2C(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F2C_reg(r1: i32, r2: i32) { instr_0F2C(read_xmm64s(r1), r2); }
@@ -997,8 +1198,8 @@ pub unsafe fn instr_660F2C(source: reg128, r: i32) {
     transition_fpu_to_mmx();
 }
 #[no_mangle]
-pub unsafe fn instr_660F2C_mem(addr: i32, r: i32) {
-    instr_660F2C(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F2C_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F2C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F2C_reg(r1: i32, r2: i32) { instr_660F2C(read_xmm128s(r1), r2); }
@@ -1011,8 +1212,8 @@ pub unsafe fn instr_F20F2C(source: u64, r: i32) {
 #[no_mangle]
 pub unsafe fn instr_F20F2C_reg(r1: i32, r2: i32) { instr_F20F2C(read_xmm64s(r1), r2); }
 #[no_mangle]
-pub unsafe fn instr_F20F2C_mem(addr: i32, r: i32) {
-    instr_F20F2C(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F2C_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F2C(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 
 pub unsafe fn instr_F30F2C(source: f32, r: i32) {
@@ -1020,8 +1221,8 @@ pub unsafe fn instr_F30F2C(source: f32, r: i32) {
     write_reg32(r, sse_convert_with_truncation_f32_to_i32(source));
 }
 #[no_mangle]
-pub unsafe fn instr_F30F2C_mem(addr: i32, r: i32) {
-    instr_F30F2C(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F2C_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F2C(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F2C_reg(r1: i32, r2: i32) { instr_F30F2C(read_xmm_f32(r1), r2); }
@@ -1039,8 +1240,8 @@ pub unsafe fn instr_0F2D(source: u64, r: i32) {
 #[no_mangle]
 pub unsafe fn instr_0F2D_reg(r1: i32, r2: i32) { instr_0F2D(read_xmm64s(r1), r2); }
 #[no_mangle]
-pub unsafe fn instr_0F2D_mem(addr: i32, r: i32) {
-    instr_0F2D(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F2D_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F2D(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 
 pub unsafe fn instr_660F2D(source: reg128, r: i32) {
@@ -1055,24 +1256,24 @@ pub unsafe fn instr_660F2D(source: reg128, r: i32) {
 #[no_mangle]
 pub unsafe fn instr_660F2D_reg(r1: i32, r2: i32) { instr_660F2D(read_xmm128s(r1), r2); }
 #[no_mangle]
-pub unsafe fn instr_660F2D_mem(addr: i32, r: i32) {
-    instr_660F2D(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F2D_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F2D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_F20F2D(source: u64, r: i32) {
     // cvtsd2si r32, xmm/m64
     write_reg32(r, sse_convert_f64_to_i32(f64::from_bits(source)));
 }
 pub unsafe fn instr_F20F2D_reg(r1: i32, r2: i32) { instr_F20F2D(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F2D_mem(addr: i32, r: i32) {
-    instr_F20F2D(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F2D_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F2D(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_F30F2D(source: f32, r: i32) {
     // cvtss2si r32, xmm1/m32
     write_reg32(r, sse_convert_f32_to_i32(source));
 }
 pub unsafe fn instr_F30F2D_reg(r1: i32, r2: i32) { instr_F30F2D(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F2D_mem(addr: i32, r: i32) {
-    instr_F30F2D(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F2D_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F2D(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1096,8 +1297,8 @@ pub unsafe fn instr_0F2E(source: f32, r: i32) {
     }
 }
 pub unsafe fn instr_0F2E_reg(r1: i32, r2: i32) { instr_0F2E(read_xmm_f32(r1), r2) }
-pub unsafe fn instr_0F2E_mem(addr: i32, r: i32) {
-    instr_0F2E(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_0F2E_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F2E(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1122,8 +1323,8 @@ pub unsafe fn instr_660F2E(source: u64, r: i32) {
     }
 }
 pub unsafe fn instr_660F2E_reg(r1: i32, r: i32) { instr_660F2E(read_xmm64s(r1), r); }
-pub unsafe fn instr_660F2E_mem(addr: i32, r: i32) {
-    instr_660F2E(return_on_pagefault!(safe_read64s(addr)), r)
+pub unsafe fn instr_660F2E_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F2E(return_on_pagefault!(safe_read64s_seg(seg, addr)), r)
 }
 
 #[no_mangle]
@@ -1147,8 +1348,8 @@ pub unsafe fn instr_0F2F(source: f32, r: i32) {
     }
 }
 pub unsafe fn instr_0F2F_reg(r1: i32, r2: i32) { instr_0F2F(read_xmm_f32(r1), r2) }
-pub unsafe fn instr_0F2F_mem(addr: i32, r: i32) {
-    instr_0F2F(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_0F2F_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F2F(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1173,8 +1374,8 @@ pub unsafe fn instr_660F2F(source: u64, r: i32) {
     }
 }
 pub unsafe fn instr_660F2F_reg(r1: i32, r: i32) { instr_660F2F(read_xmm64s(r1), r); }
-pub unsafe fn instr_660F2F_mem(addr: i32, r: i32) {
-    instr_660F2F(return_on_pagefault!(safe_read64s(addr)), r)
+pub unsafe fn instr_660F2F_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F2F(return_on_pagefault!(safe_read64s_seg(seg, addr)), r)
 }
 
 #[no_mangle]
@@ -1221,10 +1422,42 @@ pub unsafe fn instr_0F30() {
             // Enable Misc. Processor Features
         },
         IA32_MCG_CAP => {}, // netbsd
+        IA32_EFER => {
+            // Only execute-disable and syscall are supported (no long mode)
+            if low & !(EFER_NXE | EFER_SCE) != 0
+                || high != 0
+                || low & EFER_NXE != 0 && !cpuid::has_feature(cpuid::FEATURE_NX)
+                || low & EFER_SCE != 0 && !cpuid::has_feature(cpuid::FEATURE_SYSCALL)
+            {
+                dbg_log!("Unsupported EFER bits: {:x}:{:x}", high, low);
+                trigger_gp(0);
+                return;
+            }
+            if (*efer ^ low) & EFER_NXE != 0 {
+                // tlb entries cache the execute permission
+                full_clear_tlb();
+            }
+            *efer = low;
+        },
+        MSR_STAR => *star = low as u32 as u64 | (high as u32 as u64) << 32,
         IA32_KERNEL_GS_BASE => {
             // Only used in 64 bit mode (by SWAPGS), but set by kvm-unit-test
             dbg_log!("GS Base written");
         },
+        IA32_TSC_AUX => {
+            if high != 0 {
+                trigger_gp(0);
+                return;
+            }
+            *tsc_aux = low;
+        },
+        IA32_TSC_DEADLINE => {
+            // Ignored by the apic unless its timer is in tsc-deadline mode, 0 disarms the timer
+            if *acpi_enabled {
+                let deadline = (high as u32 as u64) << 32 | low as u32 as u64;
+                apic_set_tsc_deadline(if deadline == 0 { 0.0 } else { tsc_to_microtick(deadline) });
+            }
+        },
         IA32_PAT => {},
         IA32_SPEC_CTRL => {},      // linux 5.19
         IA32_TSX_CTRL => {},       // linux 5.19
@@ -1303,6 +1536,21 @@ pub unsafe fn instr_0F32() {
             // netbsd
         },
         IA32_PAT => {},
+        IA32_EFER => low = *efer,
+        MSR_STAR => {
+            low = *star as i32;
+            high = (*star >> 32) as i32;
+        },
+        IA32_TSC_AUX => low = *tsc_aux,
+        IA32_TSC_DEADLINE => {
+            // 0 if disarmed (or already fired), or the timer is in another mode
+            let time = if *acpi_enabled { apic_get_tsc_deadline() } else { 0.0 };
+            if time != 0.0 {
+                let deadline = microtick_to_tsc(time);
+                low = deadline as i32;
+                high = (deadline >> 32) as i32;
+            }
+        },
         MSR_PKG_C2_RESIDENCY => {},
         IA32_SPEC_CTRL => {},      // linux 5.19
         IA32_TSX_CTRL => {},       // linux 5.19
@@ -1390,12 +1638,12 @@ pub unsafe fn instr_0F37() {
     // getsec
     undefined_instruction();
 }
-#[no_mangle]
-pub unsafe fn instr_0F38() { unimplemented_sse(); }
+pub unsafe fn instr16_0F38() { run_instruction0f38_16(return_on_pagefault!(read_imm8())); }
+pub unsafe fn instr32_0F38() { run_instruction0f38_32(return_on_pagefault!(read_imm8())); }
 #[no_mangle]
 pub unsafe fn instr_0F39() { unimplemented_sse(); }
-#[no_mangle]
-pub unsafe fn instr_0F3A() { unimplemented_sse(); }
+pub unsafe fn instr16_0F3A() { run_instruction0f3a_16(return_on_pagefault!(read_imm8())); }
+pub unsafe fn instr32_0F3A() { run_instruction0f3a_32(return_on_pagefault!(read_imm8())); }
 #[no_mangle]
 pub unsafe fn instr_0F3B() { unimplemented_sse(); }
 #[no_mangle]
@@ -1407,132 +1655,132 @@ pub unsafe fn instr_0F3E() { unimplemented_sse(); }
 #[no_mangle]
 pub unsafe fn instr_0F3F() { unimplemented_sse(); }
 
-pub unsafe fn instr16_0F40_mem(addr: i32, r: i32) {
-    cmovcc16(test_o(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F40_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(test_o(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F40_reg(r1: i32, r: i32) { cmovcc16(test_o(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F40_mem(addr: i32, r: i32) {
-    cmovcc32(test_o(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F40_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(test_o(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F40_reg(r1: i32, r: i32) { cmovcc32(test_o(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F41_mem(addr: i32, r: i32) {
-    cmovcc16(!test_o(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F41_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(!test_o(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F41_reg(r1: i32, r: i32) { cmovcc16(!test_o(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F41_mem(addr: i32, r: i32) {
-    cmovcc32(!test_o(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F41_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(!test_o(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F41_reg(r1: i32, r: i32) { cmovcc32(!test_o(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F42_mem(addr: i32, r: i32) {
-    cmovcc16(test_b(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F42_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(test_b(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F42_reg(r1: i32, r: i32) { cmovcc16(test_b(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F42_mem(addr: i32, r: i32) {
-    cmovcc32(test_b(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F42_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(test_b(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F42_reg(r1: i32, r: i32) { cmovcc32(test_b(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F43_mem(addr: i32, r: i32) {
-    cmovcc16(!test_b(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F43_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(!test_b(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F43_reg(r1: i32, r: i32) { cmovcc16(!test_b(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F43_mem(addr: i32, r: i32) {
-    cmovcc32(!test_b(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F43_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(!test_b(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F43_reg(r1: i32, r: i32) { cmovcc32(!test_b(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F44_mem(addr: i32, r: i32) {
-    cmovcc16(test_z(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F44_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(test_z(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F44_reg(r1: i32, r: i32) { cmovcc16(test_z(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F44_mem(addr: i32, r: i32) {
-    cmovcc32(test_z(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F44_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(test_z(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F44_reg(r1: i32, r: i32) { cmovcc32(test_z(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F45_mem(addr: i32, r: i32) {
-    cmovcc16(!test_z(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F45_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(!test_z(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F45_reg(r1: i32, r: i32) { cmovcc16(!test_z(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F45_mem(addr: i32, r: i32) {
-    cmovcc32(!test_z(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F45_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(!test_z(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F45_reg(r1: i32, r: i32) { cmovcc32(!test_z(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F46_mem(addr: i32, r: i32) {
-    cmovcc16(test_be(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F46_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(test_be(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F46_reg(r1: i32, r: i32) { cmovcc16(test_be(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F46_mem(addr: i32, r: i32) {
-    cmovcc32(test_be(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F46_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(test_be(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F46_reg(r1: i32, r: i32) { cmovcc32(test_be(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F47_mem(addr: i32, r: i32) {
-    cmovcc16(!test_be(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F47_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(!test_be(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F47_reg(r1: i32, r: i32) { cmovcc16(!test_be(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F47_mem(addr: i32, r: i32) {
-    cmovcc32(!test_be(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F47_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(!test_be(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F47_reg(r1: i32, r: i32) { cmovcc32(!test_be(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F48_mem(addr: i32, r: i32) {
-    cmovcc16(test_s(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F48_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(test_s(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F48_reg(r1: i32, r: i32) { cmovcc16(test_s(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F48_mem(addr: i32, r: i32) {
-    cmovcc32(test_s(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F48_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(test_s(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F48_reg(r1: i32, r: i32) { cmovcc32(test_s(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F49_mem(addr: i32, r: i32) {
-    cmovcc16(!test_s(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F49_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(!test_s(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F49_reg(r1: i32, r: i32) { cmovcc16(!test_s(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F49_mem(addr: i32, r: i32) {
-    cmovcc32(!test_s(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F49_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(!test_s(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F49_reg(r1: i32, r: i32) { cmovcc32(!test_s(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F4A_mem(addr: i32, r: i32) {
-    cmovcc16(test_p(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F4A_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(test_p(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F4A_reg(r1: i32, r: i32) { cmovcc16(test_p(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F4A_mem(addr: i32, r: i32) {
-    cmovcc32(test_p(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F4A_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(test_p(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F4A_reg(r1: i32, r: i32) { cmovcc32(test_p(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F4B_mem(addr: i32, r: i32) {
-    cmovcc16(!test_p(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F4B_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(!test_p(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F4B_reg(r1: i32, r: i32) { cmovcc16(!test_p(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F4B_mem(addr: i32, r: i32) {
-    cmovcc32(!test_p(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F4B_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(!test_p(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F4B_reg(r1: i32, r: i32) { cmovcc32(!test_p(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F4C_mem(addr: i32, r: i32) {
-    cmovcc16(test_l(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F4C_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(test_l(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F4C_reg(r1: i32, r: i32) { cmovcc16(test_l(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F4C_mem(addr: i32, r: i32) {
-    cmovcc32(test_l(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F4C_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(test_l(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F4C_reg(r1: i32, r: i32) { cmovcc32(test_l(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F4D_mem(addr: i32, r: i32) {
-    cmovcc16(!test_l(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F4D_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(!test_l(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F4D_reg(r1: i32, r: i32) { cmovcc16(!test_l(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F4D_mem(addr: i32, r: i32) {
-    cmovcc32(!test_l(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F4D_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(!test_l(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F4D_reg(r1: i32, r: i32) { cmovcc32(!test_l(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F4E_mem(addr: i32, r: i32) {
-    cmovcc16(test_le(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F4E_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(test_le(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F4E_reg(r1: i32, r: i32) { cmovcc16(test_le(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F4E_mem(addr: i32, r: i32) {
-    cmovcc32(test_le(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F4E_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(test_le(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F4E_reg(r1: i32, r: i32) { cmovcc32(test_le(), read_reg32(r1), r); }
-pub unsafe fn instr16_0F4F_mem(addr: i32, r: i32) {
-    cmovcc16(!test_le(), return_on_pagefault!(safe_read16(addr)), r);
+pub unsafe fn instr16_0F4F_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc16(!test_le(), return_on_pagefault!(safe_read16_seg(seg, addr)), r);
 }
 pub unsafe fn instr16_0F4F_reg(r1: i32, r: i32) { cmovcc16(!test_le(), read_reg16(r1), r); }
-pub unsafe fn instr32_0F4F_mem(addr: i32, r: i32) {
-    cmovcc32(!test_le(), return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr32_0F4F_mem(seg: i32, addr: i32, r: i32) {
+    cmovcc32(!test_le(), return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr32_0F4F_reg(r1: i32, r: i32) { cmovcc32(!test_le(), read_reg32(r1), r); }
 
@@ -1547,7 +1795,7 @@ pub unsafe fn instr_0F50_reg(r1: i32, r2: i32) {
     write_reg32(r2, data);
 }
 #[no_mangle]
-pub unsafe fn instr_0F50_mem(_addr: i32, _r1: i32) { trigger_ud(); }
+pub unsafe fn instr_0F50_mem(_seg: i32, _addr: i32, _r1: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_660F50_reg(r1: i32, r2: i32) {
     // movmskpd r, xmm
@@ -1556,7 +1804,7 @@ pub unsafe fn instr_660F50_reg(r1: i32, r2: i32) {
     write_reg32(r2, data);
 }
 #[no_mangle]
-pub unsafe fn instr_660F50_mem(_addr: i32, _r1: i32) { trigger_ud(); }
+pub unsafe fn instr_660F50_mem(_seg: i32, _addr: i32, _r1: i32) { trigger_ud(); }
 
 #[no_mangle]
 pub unsafe fn instr_0F51(source: reg128, r: i32) {
@@ -1573,8 +1821,8 @@ pub unsafe fn instr_0F51(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F51_reg(r1: i32, r2: i32) { instr_0F51(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F51_mem(addr: i32, r: i32) {
-    instr_0F51(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F51_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F51(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F51(source: reg128, r: i32) {
@@ -1586,8 +1834,8 @@ pub unsafe fn instr_660F51(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F51_reg(r1: i32, r2: i32) { instr_660F51(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F51_mem(addr: i32, r: i32) {
-    instr_660F51(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F51_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F51(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F51(source: u64, r: i32) {
@@ -1596,8 +1844,8 @@ pub unsafe fn instr_F20F51(source: u64, r: i32) {
     write_xmm_f64(r, f64::from_bits(source).sqrt());
 }
 pub unsafe fn instr_F20F51_reg(r1: i32, r2: i32) { instr_F20F51(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F51_mem(addr: i32, r: i32) {
-    instr_F20F51(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F51_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F51(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F51(source: f32, r: i32) {
@@ -1606,8 +1854,8 @@ pub unsafe fn instr_F30F51(source: f32, r: i32) {
     write_xmm_f32(r, source.sqrt());
 }
 pub unsafe fn instr_F30F51_reg(r1: i32, r2: i32) { instr_F30F51(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F51_mem(addr: i32, r: i32) {
-    instr_F30F51(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F51_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F51(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1624,8 +1872,8 @@ pub unsafe fn instr_0F52(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F52_reg(r1: i32, r2: i32) { instr_0F52(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F52_mem(addr: i32, r: i32) {
-    instr_0F52(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F52_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F52(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F52(source: f32, r: i32) {
@@ -1633,8 +1881,8 @@ pub unsafe fn instr_F30F52(source: f32, r: i32) {
     write_xmm_f32(r, 1.0 / source.sqrt());
 }
 pub unsafe fn instr_F30F52_reg(r1: i32, r2: i32) { instr_F30F52(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F52_mem(addr: i32, r: i32) {
-    instr_F30F52(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F52_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F52(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1651,8 +1899,8 @@ pub unsafe fn instr_0F53(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F53_reg(r1: i32, r2: i32) { instr_0F53(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F53_mem(addr: i32, r: i32) {
-    instr_0F53(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F53_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F53(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F53(source: f32, r: i32) {
@@ -1660,8 +1908,8 @@ pub unsafe fn instr_F30F53(source: f32, r: i32) {
     write_xmm_f32(r, 1.0 / source);
 }
 pub unsafe fn instr_F30F53_reg(r1: i32, r2: i32) { instr_F30F53(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F53_mem(addr: i32, r: i32) {
-    instr_F30F53(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F53_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F53(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1671,8 +1919,8 @@ pub unsafe fn instr_0F54(source: reg128, r: i32) {
     pand_r128(source, r);
 }
 pub unsafe fn instr_0F54_reg(r1: i32, r2: i32) { instr_0F54(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F54_mem(addr: i32, r: i32) {
-    instr_0F54(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F54_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F54(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F54(source: reg128, r: i32) {
@@ -1681,8 +1929,8 @@ pub unsafe fn instr_660F54(source: reg128, r: i32) {
     pand_r128(source, r);
 }
 pub unsafe fn instr_660F54_reg(r1: i32, r2: i32) { instr_660F54(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F54_mem(addr: i32, r: i32) {
-    instr_660F54(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F54_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F54(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F55(source: reg128, r: i32) {
@@ -1691,8 +1939,8 @@ pub unsafe fn instr_0F55(source: reg128, r: i32) {
     pandn_r128(source, r);
 }
 pub unsafe fn instr_0F55_reg(r1: i32, r2: i32) { instr_0F55(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F55_mem(addr: i32, r: i32) {
-    instr_0F55(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F55_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F55(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F55(source: reg128, r: i32) {
@@ -1701,8 +1949,8 @@ pub unsafe fn instr_660F55(source: reg128, r: i32) {
     pandn_r128(source, r);
 }
 pub unsafe fn instr_660F55_reg(r1: i32, r2: i32) { instr_660F55(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F55_mem(addr: i32, r: i32) {
-    instr_660F55(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F55_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F55(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F56(source: reg128, r: i32) {
@@ -1711,8 +1959,8 @@ pub unsafe fn instr_0F56(source: reg128, r: i32) {
     por_r128(source, r);
 }
 pub unsafe fn instr_0F56_reg(r1: i32, r2: i32) { instr_0F56(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F56_mem(addr: i32, r: i32) {
-    instr_0F56(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F56_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F56(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F56(source: reg128, r: i32) {
@@ -1721,8 +1969,8 @@ pub unsafe fn instr_660F56(source: reg128, r: i32) {
     por_r128(source, r);
 }
 pub unsafe fn instr_660F56_reg(r1: i32, r2: i32) { instr_660F56(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F56_mem(addr: i32, r: i32) {
-    instr_660F56(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F56_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F56(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F57(source: reg128, r: i32) {
@@ -1731,8 +1979,8 @@ pub unsafe fn instr_0F57(source: reg128, r: i32) {
     pxor_r128(source, r);
 }
 pub unsafe fn instr_0F57_reg(r1: i32, r2: i32) { instr_0F57(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F57_mem(addr: i32, r: i32) {
-    instr_0F57(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F57_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F57(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F57(source: reg128, r: i32) {
@@ -1741,8 +1989,8 @@ pub unsafe fn instr_660F57(source: reg128, r: i32) {
     pxor_r128(source, r);
 }
 pub unsafe fn instr_660F57_reg(r1: i32, r2: i32) { instr_660F57(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F57_mem(addr: i32, r: i32) {
-    instr_660F57(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F57_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F57(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1760,8 +2008,8 @@ pub unsafe fn instr_0F58(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F58_reg(r1: i32, r2: i32) { instr_0F58(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F58_mem(addr: i32, r: i32) {
-    instr_0F58(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F58_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F58(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F58(source: reg128, r: i32) {
@@ -1776,8 +2024,8 @@ pub unsafe fn instr_660F58(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F58_reg(r1: i32, r2: i32) { instr_660F58(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F58_mem(addr: i32, r: i32) {
-    instr_660F58(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F58_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F58(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F58(source: u64, r: i32) {
@@ -1786,8 +2034,8 @@ pub unsafe fn instr_F20F58(source: u64, r: i32) {
     write_xmm_f64(r, f64::from_bits(source) + f64::from_bits(destination));
 }
 pub unsafe fn instr_F20F58_reg(r1: i32, r2: i32) { instr_F20F58(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F58_mem(addr: i32, r: i32) {
-    instr_F20F58(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F58_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F58(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F58(source: f32, r: i32) {
@@ -1797,8 +2045,8 @@ pub unsafe fn instr_F30F58(source: f32, r: i32) {
     write_xmm_f32(r, result);
 }
 pub unsafe fn instr_F30F58_reg(r1: i32, r2: i32) { instr_F30F58(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F58_mem(addr: i32, r: i32) {
-    instr_F30F58(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F58_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F58(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1816,8 +2064,8 @@ pub unsafe fn instr_0F59(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F59_reg(r1: i32, r2: i32) { instr_0F59(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F59_mem(addr: i32, r: i32) {
-    instr_0F59(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F59_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F59(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F59(source: reg128, r: i32) {
@@ -1832,8 +2080,8 @@ pub unsafe fn instr_660F59(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F59_reg(r1: i32, r2: i32) { instr_660F59(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F59_mem(addr: i32, r: i32) {
-    instr_660F59(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F59_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F59(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F59(source: u64, r: i32) {
@@ -1842,8 +2090,8 @@ pub unsafe fn instr_F20F59(source: u64, r: i32) {
     write_xmm_f64(r, f64::from_bits(source) * f64::from_bits(destination));
 }
 pub unsafe fn instr_F20F59_reg(r1: i32, r2: i32) { instr_F20F59(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F59_mem(addr: i32, r: i32) {
-    instr_F20F59(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F59_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F59(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F59(source: f32, r: i32) {
@@ -1853,8 +2101,8 @@ pub unsafe fn instr_F30F59(source: f32, r: i32) {
     write_xmm_f32(r, result);
 }
 pub unsafe fn instr_F30F59_reg(r1: i32, r2: i32) { instr_F30F59(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F59_mem(addr: i32, r: i32) {
-    instr_F30F59(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F59_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F59(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1867,8 +2115,8 @@ pub unsafe fn instr_0F5A(source: u64, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F5A_reg(r1: i32, r2: i32) { instr_0F5A(read_xmm64s(r1), r2); }
-pub unsafe fn instr_0F5A_mem(addr: i32, r: i32) {
-    instr_0F5A(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F5A_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F5A(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F5A(source: reg128, r: i32) {
@@ -1880,8 +2128,8 @@ pub unsafe fn instr_660F5A(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F5A_reg(r1: i32, r2: i32) { instr_660F5A(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F5A_mem(addr: i32, r: i32) {
-    instr_660F5A(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F5A_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F5A(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F5A(source: u64, r: i32) {
@@ -1890,8 +2138,8 @@ pub unsafe fn instr_F20F5A(source: u64, r: i32) {
     write_xmm_f32(r, f64::from_bits(source) as f32);
 }
 pub unsafe fn instr_F20F5A_reg(r1: i32, r2: i32) { instr_F20F5A(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F5A_mem(addr: i32, r: i32) {
-    instr_F20F5A(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F5A_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F5A(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F5A(source: f32, r: i32) {
@@ -1899,8 +2147,8 @@ pub unsafe fn instr_F30F5A(source: f32, r: i32) {
     write_xmm_f64(r, source as f64);
 }
 pub unsafe fn instr_F30F5A_reg(r1: i32, r2: i32) { instr_F30F5A(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F5A_mem(addr: i32, r: i32) {
-    instr_F30F5A(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F5A_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F5A(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1919,8 +2167,8 @@ pub unsafe fn instr_0F5B(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F5B_reg(r1: i32, r2: i32) { instr_0F5B(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F5B_mem(addr: i32, r: i32) {
-    instr_0F5B(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F5B_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F5B(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F5B(source: reg128, r: i32) {
@@ -1937,8 +2185,8 @@ pub unsafe fn instr_660F5B(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F5B_reg(r1: i32, r2: i32) { instr_660F5B(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F5B_mem(addr: i32, r: i32) {
-    instr_660F5B(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F5B_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F5B(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F5B(source: reg128, r: i32) {
@@ -1954,8 +2202,8 @@ pub unsafe fn instr_F30F5B(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_F30F5B_reg(r1: i32, r2: i32) { instr_F30F5B(read_xmm128s(r1), r2); }
-pub unsafe fn instr_F30F5B_mem(addr: i32, r: i32) {
-    instr_F30F5B(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_F30F5B_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F5B(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -1973,8 +2221,8 @@ pub unsafe fn instr_0F5C(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F5C_reg(r1: i32, r2: i32) { instr_0F5C(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F5C_mem(addr: i32, r: i32) {
-    instr_0F5C(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F5C_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F5C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F5C(source: reg128, r: i32) {
@@ -1989,8 +2237,8 @@ pub unsafe fn instr_660F5C(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F5C_reg(r1: i32, r2: i32) { instr_660F5C(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F5C_mem(addr: i32, r: i32) {
-    instr_660F5C(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F5C_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F5C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F5C(source: u64, r: i32) {
@@ -1999,8 +2247,8 @@ pub unsafe fn instr_F20F5C(source: u64, r: i32) {
     write_xmm_f64(r, f64::from_bits(destination) - f64::from_bits(source));
 }
 pub unsafe fn instr_F20F5C_reg(r1: i32, r2: i32) { instr_F20F5C(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F5C_mem(addr: i32, r: i32) {
-    instr_F20F5C(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F5C_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F5C(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F5C(source: f32, r: i32) {
@@ -2010,8 +2258,8 @@ pub unsafe fn instr_F30F5C(source: f32, r: i32) {
     write_xmm_f32(r, result);
 }
 pub unsafe fn instr_F30F5C_reg(r1: i32, r2: i32) { instr_F30F5C(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F5C_mem(addr: i32, r: i32) {
-    instr_F30F5C(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F5C_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F5C(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F5D(source: reg128, r: i32) {
@@ -2028,8 +2276,8 @@ pub unsafe fn instr_0F5D(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F5D_reg(r1: i32, r2: i32) { instr_0F5D(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F5D_mem(addr: i32, r: i32) {
-    instr_0F5D(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F5D_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F5D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F5D(source: reg128, r: i32) {
@@ -2044,8 +2292,8 @@ pub unsafe fn instr_660F5D(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F5D_reg(r1: i32, r2: i32) { instr_660F5D(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F5D_mem(addr: i32, r: i32) {
-    instr_660F5D(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F5D_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F5D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F5D(source: u64, r: i32) {
@@ -2057,8 +2305,8 @@ pub unsafe fn instr_F20F5D(source: u64, r: i32) {
     );
 }
 pub unsafe fn instr_F20F5D_reg(r1: i32, r2: i32) { instr_F20F5D(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F5D_mem(addr: i32, r: i32) {
-    instr_F20F5D(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F5D_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F5D(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F5D(source: f32, r: i32) {
@@ -2068,8 +2316,8 @@ pub unsafe fn instr_F30F5D(source: f32, r: i32) {
     write_xmm_f32(r, result);
 }
 pub unsafe fn instr_F30F5D_reg(r1: i32, r2: i32) { instr_F30F5D(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F5D_mem(addr: i32, r: i32) {
-    instr_F30F5D(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F5D_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F5D(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F5E(source: reg128, r: i32) {
@@ -2086,8 +2334,8 @@ pub unsafe fn instr_0F5E(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F5E_reg(r1: i32, r2: i32) { instr_0F5E(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F5E_mem(addr: i32, r: i32) {
-    instr_0F5E(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F5E_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F5E(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F5E(source: reg128, r: i32) {
@@ -2102,8 +2350,8 @@ pub unsafe fn instr_660F5E(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F5E_reg(r1: i32, r2: i32) { instr_660F5E(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F5E_mem(addr: i32, r: i32) {
-    instr_660F5E(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F5E_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F5E(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F5E(source: u64, r: i32) {
@@ -2112,8 +2360,8 @@ pub unsafe fn instr_F20F5E(source: u64, r: i32) {
     write_xmm_f64(r, f64::from_bits(destination) / f64::from_bits(source));
 }
 pub unsafe fn instr_F20F5E_reg(r1: i32, r2: i32) { instr_F20F5E(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F5E_mem(addr: i32, r: i32) {
-    instr_F20F5E(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F5E_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F5E(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F5E(source: f32, r: i32) {
@@ -2123,8 +2371,8 @@ pub unsafe fn instr_F30F5E(source: f32, r: i32) {
     write_xmm_f32(r, result);
 }
 pub unsafe fn instr_F30F5E_reg(r1: i32, r2: i32) { instr_F30F5E(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F5E_mem(addr: i32, r: i32) {
-    instr_F30F5E(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F5E_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F5E(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F5F(source: reg128, r: i32) {
@@ -2141,8 +2389,8 @@ pub unsafe fn instr_0F5F(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0F5F_reg(r1: i32, r2: i32) { instr_0F5F(read_xmm128s(r1), r2); }
-pub unsafe fn instr_0F5F_mem(addr: i32, r: i32) {
-    instr_0F5F(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_0F5F_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F5F(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F5F(source: reg128, r: i32) {
@@ -2157,8 +2405,8 @@ pub unsafe fn instr_660F5F(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F5F_reg(r1: i32, r2: i32) { instr_660F5F(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F5F_mem(addr: i32, r: i32) {
-    instr_660F5F(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F5F_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F5F(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F5F(source: u64, r: i32) {
@@ -2170,8 +2418,8 @@ pub unsafe fn instr_F20F5F(source: u64, r: i32) {
     );
 }
 pub unsafe fn instr_F20F5F_reg(r1: i32, r2: i32) { instr_F20F5F(read_xmm64s(r1), r2); }
-pub unsafe fn instr_F20F5F_mem(addr: i32, r: i32) {
-    instr_F20F5F(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F20F5F_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F5F(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F5F(source: f32, r: i32) {
@@ -2181,8 +2429,8 @@ pub unsafe fn instr_F30F5F(source: f32, r: i32) {
     write_xmm_f32(r, result);
 }
 pub unsafe fn instr_F30F5F_reg(r1: i32, r2: i32) { instr_F30F5F(read_xmm_f32(r1), r2); }
-pub unsafe fn instr_F30F5F_mem(addr: i32, r: i32) {
-    instr_F30F5F(return_on_pagefault!(safe_read_f32(addr)), r);
+pub unsafe fn instr_F30F5F_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F5F(return_on_pagefault!(safe_read_f32_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -2199,8 +2447,8 @@ pub unsafe fn instr_0F60(source: i32, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F60_reg(r1: i32, r2: i32) { instr_0F60(read_mmx32s(r1), r2); }
-pub unsafe fn instr_0F60_mem(addr: i32, r: i32) {
-    instr_0F60(return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr_0F60_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F60(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F60(source: reg128, r: i32) {
@@ -2215,8 +2463,8 @@ pub unsafe fn instr_660F60(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F60_reg(r1: i32, r2: i32) { instr_660F60(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F60_mem(addr: i32, r: i32) {
-    instr_660F60(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F60_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F60(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F61(source: i32, r: i32) {
@@ -2232,8 +2480,8 @@ pub unsafe fn instr_0F61(source: i32, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F61_reg(r1: i32, r2: i32) { instr_0F61(read_mmx32s(r1), r2); }
-pub unsafe fn instr_0F61_mem(addr: i32, r: i32) {
-    instr_0F61(return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr_0F61_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F61(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F61(source: reg128, r: i32) {
@@ -2248,8 +2496,8 @@ pub unsafe fn instr_660F61(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F61_reg(r1: i32, r2: i32) { instr_660F61(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F61_mem(addr: i32, r: i32) {
-    instr_660F61(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F61_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F61(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F62(source: i32, r: i32) {
@@ -2262,8 +2510,8 @@ pub unsafe fn instr_0F62(source: i32, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F62_reg(r1: i32, r2: i32) { instr_0F62(read_mmx32s(r1), r2); }
-pub unsafe fn instr_0F62_mem(addr: i32, r: i32) {
-    instr_0F62(return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr_0F62_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F62(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_660F62(source: reg128, r: i32) {
     // punpckldq xmm, xmm/m128
@@ -2278,8 +2526,8 @@ pub unsafe fn instr_660F62(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660F62_reg(r1: i32, r2: i32) { instr_660F62(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F62_mem(addr: i32, r: i32) {
-    instr_660F62(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F62_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F62(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F63(source: u64, r: i32) {
@@ -2295,8 +2543,8 @@ pub unsafe fn instr_0F63(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F63_reg(r1: i32, r2: i32) { instr_0F63(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F63_mem(addr: i32, r: i32) {
-    instr_0F63(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F63_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F63(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F63(source: reg128, r: i32) {
@@ -2311,8 +2559,8 @@ pub unsafe fn instr_660F63(source: reg128, r: i32) {
     write_xmm_reg128(r, result)
 }
 pub unsafe fn instr_660F63_reg(r1: i32, r2: i32) { instr_660F63(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F63_mem(addr: i32, r: i32) {
-    instr_660F63(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F63_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F63(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F64(source: u64, r: i32) {
@@ -2327,8 +2575,8 @@ pub unsafe fn instr_0F64(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F64_reg(r1: i32, r2: i32) { instr_0F64(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F64_mem(addr: i32, r: i32) {
-    instr_0F64(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F64_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F64(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F64(source: reg128, r: i32) {
@@ -2342,8 +2590,8 @@ pub unsafe fn instr_660F64(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F64_reg(r1: i32, r2: i32) { instr_660F64(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F64_mem(addr: i32, r: i32) {
-    instr_660F64(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F64_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F64(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F65(source: u64, r: i32) {
@@ -2358,8 +2606,8 @@ pub unsafe fn instr_0F65(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F65_reg(r1: i32, r2: i32) { instr_0F65(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F65_mem(addr: i32, r: i32) {
-    instr_0F65(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F65_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F65(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F65(source: reg128, r: i32) {
@@ -2373,8 +2621,8 @@ pub unsafe fn instr_660F65(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F65_reg(r1: i32, r2: i32) { instr_660F65(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F65_mem(addr: i32, r: i32) {
-    instr_660F65(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F65_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F65(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F66(source: u64, r: i32) {
@@ -2389,8 +2637,8 @@ pub unsafe fn instr_0F66(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F66_reg(r1: i32, r2: i32) { instr_0F66(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F66_mem(addr: i32, r: i32) {
-    instr_0F66(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F66_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F66(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F66(source: reg128, r: i32) {
@@ -2406,8 +2654,8 @@ pub unsafe fn instr_660F66(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660F66_reg(r1: i32, r2: i32) { instr_660F66(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F66_mem(addr: i32, r: i32) {
-    instr_660F66(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F66_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F66(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F67(source: u64, r: i32) {
@@ -2423,8 +2671,8 @@ pub unsafe fn instr_0F67(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F67_reg(r1: i32, r2: i32) { instr_0F67(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F67_mem(addr: i32, r: i32) {
-    instr_0F67(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F67_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F67(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F67(source: reg128, r: i32) {
@@ -2439,8 +2687,8 @@ pub unsafe fn instr_660F67(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F67_reg(r1: i32, r2: i32) { instr_660F67(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F67_mem(addr: i32, r: i32) {
-    instr_660F67(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F67_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F67(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F68(source: u64, r: i32) {
@@ -2456,8 +2704,8 @@ pub unsafe fn instr_0F68(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F68_reg(r1: i32, r2: i32) { instr_0F68(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F68_mem(addr: i32, r: i32) {
-    instr_0F68(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F68_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F68(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F68(source: reg128, r: i32) {
@@ -2472,8 +2720,8 @@ pub unsafe fn instr_660F68(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F68_reg(r1: i32, r2: i32) { instr_660F68(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F68_mem(addr: i32, r: i32) {
-    instr_660F68(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F68_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F68(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F69(source: u64, r: i32) {
@@ -2485,8 +2733,8 @@ pub unsafe fn instr_0F69(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F69_reg(r1: i32, r2: i32) { instr_0F69(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F69_mem(addr: i32, r: i32) {
-    instr_0F69(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F69_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F69(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F69(source: reg128, r: i32) {
@@ -2501,8 +2749,8 @@ pub unsafe fn instr_660F69(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F69_reg(r1: i32, r2: i32) { instr_660F69(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F69_mem(addr: i32, r: i32) {
-    instr_660F69(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F69_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F69(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F6A(source: u64, r: i32) {
@@ -2512,8 +2760,8 @@ pub unsafe fn instr_0F6A(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F6A_reg(r1: i32, r2: i32) { instr_0F6A(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F6A_mem(addr: i32, r: i32) {
-    instr_0F6A(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F6A_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F6A(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F6A(source: reg128, r: i32) {
@@ -2529,8 +2777,8 @@ pub unsafe fn instr_660F6A(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660F6A_reg(r1: i32, r2: i32) { instr_660F6A(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F6A_mem(addr: i32, r: i32) {
-    instr_660F6A(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F6A_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F6A(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F6B(source: u64, r: i32) {
@@ -2546,8 +2794,8 @@ pub unsafe fn instr_0F6B(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F6B_reg(r1: i32, r2: i32) { instr_0F6B(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F6B_mem(addr: i32, r: i32) {
-    instr_0F6B(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F6B_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F6B(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F6B(source: reg128, r: i32) {
@@ -2562,11 +2810,11 @@ pub unsafe fn instr_660F6B(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F6B_reg(r1: i32, r2: i32) { instr_660F6B(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F6B_mem(addr: i32, r: i32) {
-    instr_660F6B(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F6B_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F6B(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
-pub unsafe fn instr_0F6C_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F6C_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0F6C_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 #[no_mangle]
@@ -2583,11 +2831,11 @@ pub unsafe fn instr_660F6C(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660F6C_reg(r1: i32, r2: i32) { instr_660F6C(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F6C_mem(addr: i32, r: i32) {
-    instr_660F6C(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F6C_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F6C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
-pub unsafe fn instr_0F6D_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F6D_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0F6D_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 #[no_mangle]
@@ -2604,8 +2852,8 @@ pub unsafe fn instr_660F6D(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660F6D_reg(r1: i32, r2: i32) { instr_660F6D(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F6D_mem(addr: i32, r: i32) {
-    instr_660F6D(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F6D_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F6D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -2615,16 +2863,16 @@ pub unsafe fn instr_0F6E(source: i32, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F6E_reg(r1: i32, r2: i32) { instr_0F6E(read_reg32(r1), r2); }
-pub unsafe fn instr_0F6E_mem(addr: i32, r: i32) {
-    instr_0F6E(return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr_0F6E_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F6E(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_660F6E(source: i32, r: i32) {
     // movd mm, r/m32
     write_xmm128(r, source, 0, 0, 0);
 }
 pub unsafe fn instr_660F6E_reg(r1: i32, r2: i32) { instr_660F6E(read_reg32(r1), r2); }
-pub unsafe fn instr_660F6E_mem(addr: i32, r: i32) {
-    instr_660F6E(return_on_pagefault!(safe_read32s(addr)), r);
+pub unsafe fn instr_660F6E_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F6E(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F6F(source: u64, r: i32) {
@@ -2634,8 +2882,8 @@ pub unsafe fn instr_0F6F(source: u64, r: i32) {
 }
 #[no_mangle]
 pub unsafe fn instr_0F6F_reg(r1: i32, r2: i32) { instr_0F6F(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F6F_mem(addr: i32, r: i32) {
-    instr_0F6F(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F6F_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F6F(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_660F6F(source: reg128, r: i32) {
     // movdqa xmm, xmm/mem128
@@ -2643,16 +2891,16 @@ pub unsafe fn instr_660F6F(source: reg128, r: i32) {
     mov_rm_r128(source, r);
 }
 pub unsafe fn instr_660F6F_reg(r1: i32, r2: i32) { instr_660F6F(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F6F_mem(addr: i32, r: i32) {
-    instr_660F6F(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F6F_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F6F(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_F30F6F(source: reg128, r: i32) {
     // movdqu xmm, xmm/m128
     mov_rm_r128(source, r);
 }
 pub unsafe fn instr_F30F6F_reg(r1: i32, r2: i32) { instr_F30F6F(read_xmm128s(r1), r2); }
-pub unsafe fn instr_F30F6F_mem(addr: i32, r: i32) {
-    instr_F30F6F(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_F30F6F_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30F6F(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F70(source: u64, r: i32, imm8: i32) {
@@ -2666,8 +2914,8 @@ pub unsafe fn instr_0F70(source: u64, r: i32, imm8: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F70_reg(r1: i32, r2: i32, imm: i32) { instr_0F70(read_mmx64s(r1), r2, imm); }
-pub unsafe fn instr_0F70_mem(addr: i32, r: i32, imm: i32) {
-    instr_0F70(return_on_pagefault!(safe_read64s(addr)), r, imm);
+pub unsafe fn instr_0F70_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_0F70(return_on_pagefault!(safe_read64s_seg(seg, addr)), r, imm);
 }
 pub unsafe fn instr_660F70(source: reg128, r: i32, imm8: i32) {
     // pshufd xmm, xmm/mem128, imm8
@@ -2683,8 +2931,8 @@ pub unsafe fn instr_660F70(source: reg128, r: i32, imm8: i32) {
 pub unsafe fn instr_660F70_reg(r1: i32, r2: i32, imm: i32) {
     instr_660F70(read_xmm128s(r1), r2, imm);
 }
-pub unsafe fn instr_660F70_mem(addr: i32, r: i32, imm: i32) {
-    instr_660F70(return_on_pagefault!(safe_read128s(addr)), r, imm);
+pub unsafe fn instr_660F70_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F70(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
 }
 
 #[no_mangle]
@@ -2704,8 +2952,8 @@ pub unsafe fn instr_F20F70(source: reg128, r: i32, imm8: i32) {
 pub unsafe fn instr_F20F70_reg(r1: i32, r2: i32, imm: i32) {
     instr_F20F70(read_xmm128s(r1), r2, imm);
 }
-pub unsafe fn instr_F20F70_mem(addr: i32, r: i32, imm: i32) {
-    instr_F20F70(return_on_pagefault!(safe_read128s(addr)), r, imm);
+pub unsafe fn instr_F20F70_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_F20F70(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
 }
 #[no_mangle]
 pub unsafe fn instr_F30F70(source: reg128, r: i32, imm8: i32) {
@@ -2724,12 +2972,12 @@ pub unsafe fn instr_F30F70(source: reg128, r: i32, imm8: i32) {
 pub unsafe fn instr_F30F70_reg(r1: i32, r2: i32, imm: i32) {
     instr_F30F70(read_xmm128s(r1), r2, imm);
 }
-pub unsafe fn instr_F30F70_mem(addr: i32, r: i32, imm: i32) {
-    instr_F30F70(return_on_pagefault!(safe_read128s(addr)), r, imm);
+pub unsafe fn instr_F30F70_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_F30F70(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
 }
-pub unsafe fn instr_0F71_2_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_0F71_4_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_0F71_6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F71_2_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F71_4_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F71_6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0F71_2_reg(r: i32, imm8: i32) {
     // psrlw mm, imm8
@@ -2745,9 +2993,9 @@ pub unsafe fn instr_0F71_6_reg(r: i32, imm8: i32) {
     // psllw mm, imm8
     psllw_r64(r, imm8 as u64);
 }
-pub unsafe fn instr_660F71_2_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F71_4_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F71_6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F71_2_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F71_4_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F71_6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_660F71_2_reg(r: i32, imm8: i32) {
     // psrlw xmm, imm8
@@ -2763,9 +3011,9 @@ pub unsafe fn instr_660F71_6_reg(r: i32, imm8: i32) {
     // psllw xmm, imm8
     psllw_r128(r, imm8 as u64);
 }
-pub unsafe fn instr_0F72_2_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_0F72_4_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_0F72_6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F72_2_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F72_4_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F72_6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0F72_2_reg(r: i32, imm8: i32) {
     // psrld mm, imm8
@@ -2781,9 +3029,9 @@ pub unsafe fn instr_0F72_6_reg(r: i32, imm8: i32) {
     // pslld mm, imm8
     pslld_r64(r, imm8 as u64);
 }
-pub unsafe fn instr_660F72_2_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F72_4_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F72_6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F72_2_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F72_4_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F72_6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_660F72_2_reg(r: i32, imm8: i32) {
     // psrld xmm, imm8
@@ -2799,8 +3047,8 @@ pub unsafe fn instr_660F72_6_reg(r: i32, imm8: i32) {
     // pslld xmm, imm8
     pslld_r128(r, imm8 as u64);
 }
-pub unsafe fn instr_0F73_2_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_0F73_6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F73_2_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0F73_6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0F73_2_reg(r: i32, imm8: i32) {
     // psrlq mm, imm8
@@ -2811,10 +3059,10 @@ pub unsafe fn instr_0F73_6_reg(r: i32, imm8: i32) {
     // psllq mm, imm8
     psllq_r64(r, imm8 as u64);
 }
-pub unsafe fn instr_660F73_2_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F73_3_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F73_6_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr_660F73_7_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F73_2_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F73_3_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F73_6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660F73_7_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_660F73_2_reg(r: i32, imm8: i32) {
     // psrlq xmm, imm8
@@ -2877,8 +3125,8 @@ pub unsafe fn instr_0F74(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F74_reg(r1: i32, r2: i32) { instr_0F74(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F74_mem(addr: i32, r: i32) {
-    instr_0F74(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F74_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F74(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F74(source: reg128, r: i32) {
@@ -2892,8 +3140,8 @@ pub unsafe fn instr_660F74(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F74_reg(r1: i32, r2: i32) { instr_660F74(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F74_mem(addr: i32, r: i32) {
-    instr_660F74(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F74_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F74(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F75(source: u64, r: i32) {
@@ -2908,8 +3156,8 @@ pub unsafe fn instr_0F75(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F75_reg(r1: i32, r2: i32) { instr_0F75(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F75_mem(addr: i32, r: i32) {
-    instr_0F75(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F75_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F75(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F75(source: reg128, r: i32) {
@@ -2924,8 +3172,8 @@ pub unsafe fn instr_660F75(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F75_reg(r1: i32, r2: i32) { instr_660F75(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F75_mem(addr: i32, r: i32) {
-    instr_660F75(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F75_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F75(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F76(source: u64, r: i32) {
@@ -2940,8 +3188,8 @@ pub unsafe fn instr_0F76(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0F76_reg(r1: i32, r2: i32) { instr_0F76(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0F76_mem(addr: i32, r: i32) {
-    instr_0F76(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0F76_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F76(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660F76(source: reg128, r: i32) {
@@ -2955,8 +3203,8 @@ pub unsafe fn instr_660F76(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660F76_reg(r1: i32, r2: i32) { instr_660F76(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F76_mem(addr: i32, r: i32) {
-    instr_660F76(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F76_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F76(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F77() {
@@ -2993,8 +3241,8 @@ pub unsafe fn instr_660F7C(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660F7C_reg(r1: i32, r2: i32) { instr_660F7C(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F7C_mem(addr: i32, r: i32) {
-    instr_660F7C(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F7C_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F7C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_F20F7C(source: reg128, r: i32) {
@@ -3013,8 +3261,8 @@ pub unsafe fn instr_F20F7C(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_F20F7C_reg(r1: i32, r2: i32) { instr_F20F7C(read_xmm128s(r1), r2); }
-pub unsafe fn instr_F20F7C_mem(addr: i32, r: i32) {
-    instr_F20F7C(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_F20F7C_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F7C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -3032,8 +3280,8 @@ pub unsafe fn instr_660F7D(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660F7D_reg(r1: i32, r2: i32) { instr_660F7D(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660F7D_mem(addr: i32, r: i32) {
-    instr_660F7D(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660F7D_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F7D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -3053,8 +3301,8 @@ pub unsafe fn instr_F20F7D(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_F20F7D_reg(r1: i32, r2: i32) { instr_F20F7D(read_xmm128s(r1), r2); }
-pub unsafe fn instr_F20F7D_mem(addr: i32, r: i32) {
-    instr_F20F7D(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_F20F7D_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20F7D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
@@ -3065,8 +3313,8 @@ pub unsafe fn instr_0F7E(r: i32) -> i32 {
     return data as i32;
 }
 pub unsafe fn instr_0F7E_reg(r1: i32, r2: i32) { write_reg32(r1, instr_0F7E(r2)); }
-pub unsafe fn instr_0F7E_mem(addr: i32, r: i32) {
-    return_on_pagefault!(safe_write32(addr, instr_0F7E(r)));
+pub unsafe fn instr_0F7E_mem(seg: i32, addr: i32, r: i32) {
+    return_on_pagefault!(safe_write32_seg(seg, addr, instr_0F7E(r)));
 }
 pub unsafe fn instr_660F7E(r: i32) -> i32 {
     // movd r/m32, xmm
@@ -3074,12 +3322,12 @@ pub unsafe fn instr_660F7E(r: i32) -> i32 {
     return data as i32;
 }
 pub unsafe fn instr_660F7E_reg(r1: i32, r2: i32) { write_reg32(r1, instr_660F7E(r2)); }
-pub unsafe fn instr_660F7E_mem(addr: i32, r: i32) {
-    return_on_pagefault!(safe_write32(addr, instr_660F7E(r)));
+pub unsafe fn instr_660F7E_mem(seg: i32, addr: i32, r: i32) {
+    return_on_pagefault!(safe_write32_seg(seg, addr, instr_660F7E(r)));
 }
-pub unsafe fn instr_F30F7E_mem(addr: i32, r: i32) {
+pub unsafe fn instr_F30F7E_mem(seg: i32, addr: i32, r: i32) {
     // movq xmm, xmm/mem64
-    let data = return_on_pagefault!(safe_read64s(addr));
+    let data = return_on_pagefault!(safe_read64s_seg(seg, addr));
     write_xmm128_2(r, data, 0);
 }
 pub unsafe fn instr_F30F7E_reg(r1: i32, r2: i32) {
@@ -3093,9 +3341,9 @@ pub unsafe fn instr_0F7F(r: i32) -> u64 {
     transition_fpu_to_mmx();
     read_mmx64s(r)
 }
-pub unsafe fn instr_0F7F_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0F7F_mem(seg: i32, addr: i32, r: i32) {
     // movq mm/m64, mm
-    mov_r_m64(addr, r);
+    mov_r_m64(seg, addr, r);
 }
 #[no_mangle]
 pub unsafe fn instr_0F7F_reg(r1: i32, r2: i32) {
@@ -3103,19 +3351,19 @@ pub unsafe fn instr_0F7F_reg(r1: i32, r2: i32) {
     write_mmx_reg64(r1, read_mmx64s(r2));
     transition_fpu_to_mmx();
 }
-pub unsafe fn instr_660F7F_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660F7F_mem(seg: i32, addr: i32, r: i32) {
     // movdqa xmm/m128, xmm
     // XXX: Aligned write or #gp
-    mov_r_m128(addr, r);
+    mov_r_m128(seg, addr, r);
 }
 pub unsafe fn instr_660F7F_reg(r1: i32, r2: i32) {
     // movdqa xmm/m128, xmm
     // XXX: Aligned access or #gp
     mov_r_r128(r1, r2);
 }
-pub unsafe fn instr_F30F7F_mem(addr: i32, r: i32) {
+pub unsafe fn instr_F30F7F_mem(seg: i32, addr: i32, r: i32) {
     // movdqu xmm/m128, xmm
-    mov_r_m128(addr, r);
+    mov_r_m128(seg, addr, r);
 }
 pub unsafe fn instr_F30F7F_reg(r1: i32, r2: i32) {
     // movdqu xmm/m128, xmm
@@ -3171,22 +3419,22 @@ pub unsafe fn instr_0F9C_reg(r: i32, _: i32) { setcc_reg(test_l(), r); }
 pub unsafe fn instr_0F9D_reg(r: i32, _: i32) { setcc_reg(!test_l(), r); }
 pub unsafe fn instr_0F9E_reg(r: i32, _: i32) { setcc_reg(test_le(), r); }
 pub unsafe fn instr_0F9F_reg(r: i32, _: i32) { setcc_reg(!test_le(), r); }
-pub unsafe fn instr_0F90_mem(addr: i32, _: i32) { setcc_mem(test_o(), addr); }
-pub unsafe fn instr_0F91_mem(addr: i32, _: i32) { setcc_mem(!test_o(), addr); }
-pub unsafe fn instr_0F92_mem(addr: i32, _: i32) { setcc_mem(test_b(), addr); }
-pub unsafe fn instr_0F93_mem(addr: i32, _: i32) { setcc_mem(!test_b(), addr); }
-pub unsafe fn instr_0F94_mem(addr: i32, _: i32) { setcc_mem(test_z(), addr); }
-pub unsafe fn instr_0F95_mem(addr: i32, _: i32) { setcc_mem(!test_z(), addr); }
-pub unsafe fn instr_0F96_mem(addr: i32, _: i32) { setcc_mem(test_be(), addr); }
-pub unsafe fn instr_0F97_mem(addr: i32, _: i32) { setcc_mem(!test_be(), addr); }
-pub unsafe fn instr_0F98_mem(addr: i32, _: i32) { setcc_mem(test_s(), addr); }
-pub unsafe fn instr_0F99_mem(addr: i32, _: i32) { setcc_mem(!test_s(), addr); }
-pub unsafe fn instr_0F9A_mem(addr: i32, _: i32) { setcc_mem(test_p(), addr); }
-pub unsafe fn instr_0F9B_mem(addr: i32, _: i32) { setcc_mem(!test_p(), addr); }
-pub unsafe fn instr_0F9C_mem(addr: i32, _: i32) { setcc_mem(test_l(), addr); }
-pub unsafe fn instr_0F9D_mem(addr: i32, _: i32) { setcc_mem(!test_l(), addr); }
-pub unsafe fn instr_0F9E_mem(addr: i32, _: i32) { setcc_mem(test_le(), addr); }
-pub unsafe fn instr_0F9F_mem(addr: i32, _: i32) { setcc_mem(!test_le(), addr); }
+pub unsafe fn instr_0F90_mem(seg: i32, addr: i32, _: i32) { setcc_mem(test_o(), seg, addr); }
+pub unsafe fn instr_0F91_mem(seg: i32, addr: i32, _: i32) { setcc_mem(!test_o(), seg, addr); }
+pub unsafe fn instr_0F92_mem(seg: i32, addr: i32, _: i32) { setcc_mem(test_b(), seg, addr); }
+pub unsafe fn instr_0F93_mem(seg: i32, addr: i32, _: i32) { setcc_mem(!test_b(), seg, addr); }
+pub unsafe fn instr_0F94_mem(seg: i32, addr: i32, _: i32) { setcc_mem(test_z(), seg, addr); }
+pub unsafe fn instr_0F95_mem(seg: i32, addr: i32, _: i32) { setcc_mem(!test_z(), seg, addr); }
+pub unsafe fn instr_0F96_mem(seg: i32, addr: i32, _: i32) { setcc_mem(test_be(), seg, addr); }
+pub unsafe fn instr_0F97_mem(seg: i32, addr: i32, _: i32) { setcc_mem(!test_be(), seg, addr); }
+pub unsafe fn instr_0F98_mem(seg: i32, addr: i32, _: i32) { setcc_mem(test_s(), seg, addr); }
+pub unsafe fn instr_0F99_mem(seg: i32, addr: i32, _: i32) { setcc_mem(!test_s(), seg, addr); }
+pub unsafe fn instr_0F9A_mem(seg: i32, addr: i32, _: i32) { setcc_mem(test_p(), seg, addr); }
+pub unsafe fn instr_0F9B_mem(seg: i32, addr: i32, _: i32) { setcc_mem(!test_p(), seg, addr); }
+pub unsafe fn instr_0F9C_mem(seg: i32, addr: i32, _: i32) { setcc_mem(test_l(), seg, addr); }
+pub unsafe fn instr_0F9D_mem(seg: i32, addr: i32, _: i32) { setcc_mem(!test_l(), seg, addr); }
+pub unsafe fn instr_0F9E_mem(seg: i32, addr: i32, _: i32) { setcc_mem(test_le(), seg, addr); }
+pub unsafe fn instr_0F9F_mem(seg: i32, addr: i32, _: i32) { setcc_mem(!test_le(), seg, addr); }
 
 pub unsafe fn instr16_0FA0() {
     return_on_pagefault!(push16(*sreg.offset(FS as isize) as i32));
@@ -3194,7 +3442,7 @@ pub unsafe fn instr16_0FA0() {
 pub unsafe fn instr32_0FA0() { return_on_pagefault!(push32_sreg(FS)) }
 #[no_mangle]
 pub unsafe fn instr16_0FA1() {
-    if !switch_seg(FS, return_on_pagefault!(safe_read16(get_stack_pointer(0)))) {
+    if !switch_seg(FS, return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0)))) {
         return;
     }
     else {
@@ -3206,7 +3454,7 @@ pub unsafe fn instr16_0FA1() {
 pub unsafe fn instr32_0FA1() {
     if !switch_seg(
         FS,
-        return_on_pagefault!(safe_read32s(get_stack_pointer(0))) & 0xFFFF,
+        return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0))) & 0xFFFF,
     ) {
         return;
     }
@@ -3218,139 +3466,18 @@ pub unsafe fn instr32_0FA1() {
 #[no_mangle]
 pub unsafe fn instr_0FA2() {
     // cpuid
-    // TODO: Fill in with less bogus values
-
-    // http://lxr.linux.no/linux+%2a/arch/x86/include/asm/cpufeature.h
-    // http://www.sandpile.org/x86/cpuid.htm
-    let mut eax = 0;
-    let mut ecx = 0;
-    let mut edx = 0;
-    let mut ebx = 0;
-
     let level = read_reg32(EAX) as u32;
+    let [eax, ebx, mut ecx, edx] = cpuid::lookup(level, read_reg32(ECX) as u32);
 
-    match level {
-        0 => {
-            // maximum supported level (default 0x16, overwritten to 2 as a workaround for Windows NT)
-            eax = cpuid_level as i32;
-
-            ebx = 0x756E6547 | 0; // Genu
-            edx = 0x49656E69 | 0; // ineI
-            ecx = 0x6C65746E | 0; // ntel
-        },
-
-        1 => {
-            // pentium
-            eax = 3 | 6 << 4 | 15 << 8;
-            ebx = 1 << 16 | 8 << 8; // cpu count, clflush size
-            ecx = 1 << 0 | 1 << 23 | 1 << 30; // sse3, popcnt, rdrand
-            let vme = 0 << 1;
-            if ::config::VMWARE_HYPERVISOR_PORT {
-                ecx |= 1 << 31
-            }; // hypervisor
-            edx = (if true /* have fpu */ { 1 } else {  0 }) |      // fpu
-                    vme | 1 << 3 | 1 << 4 | 1 << 5 | 1 << 6 |  // vme, pse, tsc, msr, pae
-                    1 << 8 | 1 << 11 | 1 << 13 | 1 << 15 | // cx8, sep, pge, cmov
-                    1 << 23 | 1 << 24 | 1 << 25 | 1 << 26; // mmx, fxsr, sse1, sse2
-
-            if *acpi_enabled
-            //&& this.apic_enabled[0])
-            {
-                edx |= 1 << 9; // apic
-            }
-        },
-
-        2 => {
-            // Taken from http://siyobik.info.gf/main/reference/instruction/CPUID
-            eax = 0x665B5001;
-            ebx = 0;
-            ecx = 0;
-            edx = 0x007A7000;
-        },
-
-        4 => {
-            // from my local machine
-            match read_reg32(ECX) {
-                0 => {
-                    eax = 0x00000121;
-                    ebx = 0x01c0003f;
-                    ecx = 0x0000003f;
-                    edx = 0x00000001;
-                },
-                1 => {
-                    eax = 0x00000122;
-                    ebx = 0x01c0003f;
-                    ecx = 0x0000003f;
-                    edx = 0x00000001;
-                },
-                2 => {
-                    eax = 0x00000143;
-                    ebx = 0x05c0003f;
-                    ecx = 0x00000fff;
-                    edx = 0x00000001;
-                },
-                _ => {},
-            }
-        },
-
-        5 => {
-            // from my local machine
-            eax = 0x40;
-            ebx = 0x40;
-            ecx = 3;
-            edx = 0x00142120;
-        },
-
-        7 => {
-            if read_reg32(ECX) == 0 {
-                eax = 0; // maximum supported sub-level
-                ebx = 1 << 9; // enhanced REP MOVSB/STOSB
-                ecx = 0;
-                edx = 0;
-            }
-        },
-
-        0x80000000 => {
-            // maximum supported extended level
-            eax = 5;
-            // other registers are reserved
-        },
-
-        0x40000000 => {
-            // hypervisor
-            if ::config::VMWARE_HYPERVISOR_PORT {
-                // h("Ware".split("").reduce((a, c, i) => a | c.charCodeAt(0) << i * 8, 0))
-                ebx = 0x61774D56 | 0; // VMwa
-                ecx = 0x4D566572 | 0; // reVM
-                edx = 0x65726177 | 0; // ware
-            }
-        },
-
-        0x15 => {
-            eax = 1; // denominator
-            ebx = 1; // numerator
-            ecx = (TSC_RATE * 1000.0) as u32 as i32; // core crystal clock frequency in Hz
-            dbg_assert!(ecx > 0);
-            //  (TSC frequency = core crystal clock frequency * EBX/EAX)
-        },
-
-        0x16 => {
-            eax = (TSC_RATE / 1000.0).floor() as u32 as i32; // core base frequency in MHz
-            ebx = (TSC_RATE / 1000.0).floor() as u32 as i32; // core maximum frequency in MHz
-            ecx = 10; // bus (reference) frequency in MHz
-
-            // 16-bit values
-            dbg_assert!(eax < 0x10000);
-            dbg_assert!(ebx < 0x10000);
-            dbg_assert!(ecx < 0x10000);
-        },
-
-        x => {
-            dbg_log!("cpuid: unimplemented eax: {:x}", x);
-        },
+    if level == 1 {
+        // osxsave reflects cr4
+        ecx &= !(1 << 27);
+        if *cr.offset(4) & CR4_OSXSAVE != 0 {
+            ecx |= 1 << 27;
+        }
     }
 
-    if level == 4 || level == 7 {
+    if level == 4 || level == 7 || level == 0xD {
         dbg_log!(
             "cpuid: eax={:08x} ecx={:02x}",
             read_reg32(EAX),
@@ -3367,23 +3494,25 @@ pub unsafe fn instr_0FA2() {
     write_reg32(EBX, ebx);
 }
 pub unsafe fn instr16_0FA3_reg(r1: i32, r2: i32) { bt_reg(read_reg16(r1), read_reg16(r2) & 15); }
-pub unsafe fn instr16_0FA3_mem(addr: i32, r: i32) { bt_mem(addr, read_reg16(r) << 16 >> 16); }
+pub unsafe fn instr16_0FA3_mem(seg: i32, addr: i32, r: i32) {
+    bt_mem(seg, addr, read_reg16(r) << 16 >> 16);
+}
 pub unsafe fn instr32_0FA3_reg(r1: i32, r2: i32) { bt_reg(read_reg32(r1), read_reg32(r2) & 31); }
-pub unsafe fn instr32_0FA3_mem(addr: i32, r: i32) { bt_mem(addr, read_reg32(r)); }
-pub unsafe fn instr16_0FA4_mem(addr: i32, r: i32, imm: i32) {
-    safe_read_write16(addr, &|x| shld16(x, read_reg16(r), imm & 31))
+pub unsafe fn instr32_0FA3_mem(seg: i32, addr: i32, r: i32) { bt_mem(seg, addr, read_reg32(r)); }
+pub unsafe fn instr16_0FA4_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shld16(x, read_reg16(r), imm & 31))
 }
 pub unsafe fn instr16_0FA4_reg(r1: i32, r: i32, imm: i32) {
     write_reg16(r1, shld16(read_reg16(r1), read_reg16(r), imm & 31));
 }
-pub unsafe fn instr32_0FA4_mem(addr: i32, r: i32, imm: i32) {
-    safe_read_write32(addr, &|x| shld32(x, read_reg32(r), imm & 31))
+pub unsafe fn instr32_0FA4_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shld32(x, read_reg32(r), imm & 31))
 }
 pub unsafe fn instr32_0FA4_reg(r1: i32, r: i32, imm: i32) {
     write_reg32(r1, shld32(read_reg32(r1), read_reg32(r), imm & 31));
 }
-pub unsafe fn instr16_0FA5_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| shld16(x, read_reg16(r), read_reg8(CL) & 31))
+pub unsafe fn instr16_0FA5_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shld16(x, read_reg16(r), read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_0FA5_reg(r1: i32, r: i32) {
     write_reg16(
@@ -3391,8 +3520,8 @@ pub unsafe fn instr16_0FA5_reg(r1: i32, r: i32) {
         shld16(read_reg16(r1), read_reg16(r), read_reg8(CL) & 31),
     );
 }
-pub unsafe fn instr32_0FA5_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| shld32(x, read_reg32(r), read_reg8(CL) & 31))
+pub unsafe fn instr32_0FA5_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shld32(x, read_reg32(r), read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_0FA5_reg(r1: i32, r: i32) {
     write_reg32(
@@ -3413,7 +3542,7 @@ pub unsafe fn instr16_0FA8() {
 pub unsafe fn instr32_0FA8() { return_on_pagefault!(push32_sreg(GS)) }
 #[no_mangle]
 pub unsafe fn instr16_0FA9() {
-    if !switch_seg(GS, return_on_pagefault!(safe_read16(get_stack_pointer(0)))) {
+    if !switch_seg(GS, return_on_pagefault!(safe_read16_seg(SS, get_stack_pointer(0)))) {
         return;
     }
     else {
@@ -3425,7 +3554,7 @@ pub unsafe fn instr16_0FA9() {
 pub unsafe fn instr32_0FA9() {
     if !switch_seg(
         GS,
-        return_on_pagefault!(safe_read32s(get_stack_pointer(0))) & 0xFFFF,
+        return_on_pagefault!(safe_read32s_seg(SS, get_stack_pointer(0))) & 0xFFFF,
     ) {
         return;
     }
@@ -3444,27 +3573,29 @@ pub unsafe fn instr16_0FAB_reg(r1: i32, r2: i32) {
     write_reg16(r1, bts_reg(read_reg16(r1), read_reg16(r2) & 15));
 }
 #[no_mangle]
-pub unsafe fn instr16_0FAB_mem(addr: i32, r: i32) { bts_mem(addr, read_reg16(r) << 16 >> 16); }
+pub unsafe fn instr16_0FAB_mem(seg: i32, addr: i32, r: i32) {
+    bts_mem(seg, addr, read_reg16(r) << 16 >> 16);
+}
 #[no_mangle]
 pub unsafe fn instr32_0FAB_reg(r1: i32, r2: i32) {
     write_reg32(r1, bts_reg(read_reg32(r1), read_reg32(r2) & 31));
 }
 #[no_mangle]
-pub unsafe fn instr32_0FAB_mem(addr: i32, r: i32) { bts_mem(addr, read_reg32(r)); }
-pub unsafe fn instr16_0FAC_mem(addr: i32, r: i32, imm: i32) {
-    safe_read_write16(addr, &|x| shrd16(x, read_reg16(r), imm & 31))
+pub unsafe fn instr32_0FAB_mem(seg: i32, addr: i32, r: i32) { bts_mem(seg, addr, read_reg32(r)); }
+pub unsafe fn instr16_0FAC_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shrd16(x, read_reg16(r), imm & 31))
 }
 pub unsafe fn instr16_0FAC_reg(r1: i32, r: i32, imm: i32) {
     write_reg16(r1, shrd16(read_reg16(r1), read_reg16(r), imm & 31));
 }
-pub unsafe fn instr32_0FAC_mem(addr: i32, r: i32, imm: i32) {
-    safe_read_write32(addr, &|x| shrd32(x, read_reg32(r), imm & 31))
+pub unsafe fn instr32_0FAC_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shrd32(x, read_reg32(r), imm & 31))
 }
 pub unsafe fn instr32_0FAC_reg(r1: i32, r: i32, imm: i32) {
     write_reg32(r1, shrd32(read_reg32(r1), read_reg32(r), imm & 31));
 }
-pub unsafe fn instr16_0FAD_mem(addr: i32, r: i32) {
-    safe_read_write16(addr, &|x| shrd16(x, read_reg16(r), read_reg8(CL) & 31))
+pub unsafe fn instr16_0FAD_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| shrd16(x, read_reg16(r), read_reg8(CL) & 31))
 }
 pub unsafe fn instr16_0FAD_reg(r1: i32, r: i32) {
     write_reg16(
@@ -3472,8 +3603,8 @@ pub unsafe fn instr16_0FAD_reg(r1: i32, r: i32) {
         shrd16(read_reg16(r1), read_reg16(r), read_reg8(CL) & 31),
     );
 }
-pub unsafe fn instr32_0FAD_mem(addr: i32, r: i32) {
-    safe_read_write32(addr, &|x| shrd32(x, read_reg32(r), read_reg8(CL) & 31))
+pub unsafe fn instr32_0FAD_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| shrd32(x, read_reg32(r), read_reg8(CL) & 31))
 }
 pub unsafe fn instr32_0FAD_reg(r1: i32, r: i32) {
     write_reg32(
@@ -3484,17 +3615,17 @@ pub unsafe fn instr32_0FAD_reg(r1: i32, r: i32) {
 #[no_mangle]
 pub unsafe fn instr_0FAE_0_reg(_r: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr_0FAE_0_mem(addr: i32) { fxsave(addr); }
+pub unsafe fn instr_0FAE_0_mem(seg: i32, addr: i32) { fxsave(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr_0FAE_1_reg(_r: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr_0FAE_1_mem(addr: i32) { fxrstor(addr); }
+pub unsafe fn instr_0FAE_1_mem(seg: i32, addr: i32) { fxrstor(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr_0FAE_2_reg(_r: i32) { unimplemented_sse(); }
 #[no_mangle]
-pub unsafe fn instr_0FAE_2_mem(addr: i32) {
+pub unsafe fn instr_0FAE_2_mem(seg: i32, addr: i32) {
     // ldmxcsr
-    let new_mxcsr = return_on_pagefault!(safe_read32s(addr));
+    let new_mxcsr = return_on_pagefault!(safe_read32s_seg(seg, addr));
     if 0 != new_mxcsr & !MXCSR_MASK {
         dbg_log!("Invalid mxcsr bits: {:x}", new_mxcsr & !MXCSR_MASK);
         trigger_gp(0);
@@ -3508,55 +3639,51 @@ pub unsafe fn instr_0FAE_2_mem(addr: i32) {
 #[no_mangle]
 pub unsafe fn instr_0FAE_3_reg(_r: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr_0FAE_3_mem(addr: i32) {
+pub unsafe fn instr_0FAE_3_mem(seg: i32, addr: i32) {
     // stmxcsr
-    return_on_pagefault!(safe_write32(addr, *mxcsr));
+    return_on_pagefault!(safe_write32_seg(seg, addr, *mxcsr));
 }
 #[no_mangle]
 pub unsafe fn instr_0FAE_4_reg(_r: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr_0FAE_4_mem(_addr: i32) {
-    // xsave
-    undefined_instruction();
-}
+pub unsafe fn instr_0FAE_4_mem(seg: i32, addr: i32) { xsave(seg, addr, false); }
+#[no_mangle]
 pub unsafe fn instr_0FAE_5_reg(_r: i32) {
     // lfence
 }
-pub unsafe fn instr_0FAE_5_mem(_addr: i32) {
-    // xrstor
-    undefined_instruction();
-}
+#[no_mangle]
+pub unsafe fn instr_0FAE_5_mem(seg: i32, addr: i32) { xrstor(seg, addr); }
 #[no_mangle]
 pub unsafe fn instr_0FAE_6_reg(_r: i32) {
     // mfence
 }
 #[no_mangle]
-pub unsafe fn instr_0FAE_6_mem(_addr: i32) {
+pub unsafe fn instr_0FAE_6_mem(seg: i32, addr: i32) {
     // xsaveopt
-    undefined_instruction();
+    xsave(seg, addr, false);
 }
 #[no_mangle]
 pub unsafe fn instr_0FAE_7_reg(_r: i32) {
     // sfence
 }
 #[no_mangle]
-pub unsafe fn instr_0FAE_7_mem(_addr: i32) {
+pub unsafe fn instr_0FAE_7_mem(_seg: i32, _addr: i32) {
     // clflush
     undefined_instruction();
 }
-pub unsafe fn instr16_0FAF_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_0FAF_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        imul_reg16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        imul_reg16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_0FAF_reg(r1: i32, r: i32) {
     write_reg16(r, imul_reg16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_0FAF_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_0FAF_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        imul_reg32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        imul_reg32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_0FAF_reg(r1: i32, r: i32) {
@@ -3566,78 +3693,86 @@ pub unsafe fn instr32_0FAF_reg(r1: i32, r: i32) {
 #[no_mangle]
 pub unsafe fn instr_0FB0_reg(r1: i32, r2: i32) { write_reg8(r1, cmpxchg8(read_reg8(r1), r2)); }
 #[no_mangle]
-pub unsafe fn instr_0FB0_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| cmpxchg8(x, r)) }
+pub unsafe fn instr_0FB0_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| cmpxchg8(x, r))
+}
 pub unsafe fn instr16_0FB1_reg(r1: i32, r2: i32) { write_reg16(r1, cmpxchg16(read_reg16(r1), r2)); }
-pub unsafe fn instr16_0FB1_mem(addr: i32, r: i32) { safe_read_write16(addr, &|x| cmpxchg16(x, r)) }
+pub unsafe fn instr16_0FB1_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| cmpxchg16(x, r))
+}
 pub unsafe fn instr32_0FB1_reg(r1: i32, r2: i32) { write_reg32(r1, cmpxchg32(read_reg32(r1), r2)); }
-pub unsafe fn instr32_0FB1_mem(addr: i32, r: i32) { safe_read_write32(addr, &|x| cmpxchg32(x, r)) }
+pub unsafe fn instr32_0FB1_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| cmpxchg32(x, r))
+}
 
 #[no_mangle]
 pub unsafe fn instr16_0FB2_reg(_unused: i32, _unused2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr16_0FB2_mem(addr: i32, r: i32) { lss16(addr, r, SS); }
+pub unsafe fn instr16_0FB2_mem(seg: i32, addr: i32, r: i32) { lss16(seg, addr, r, SS); }
 #[no_mangle]
 pub unsafe fn instr32_0FB2_reg(_unused: i32, _unused2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr32_0FB2_mem(addr: i32, r: i32) { lss32(addr, r, SS); }
+pub unsafe fn instr32_0FB2_mem(seg: i32, addr: i32, r: i32) { lss32(seg, addr, r, SS); }
 #[no_mangle]
 pub unsafe fn instr16_0FB3_reg(r1: i32, r2: i32) {
     write_reg16(r1, btr_reg(read_reg16(r1), read_reg16(r2) & 15));
 }
 #[no_mangle]
-pub unsafe fn instr16_0FB3_mem(addr: i32, r: i32) { btr_mem(addr, read_reg16(r) << 16 >> 16); }
+pub unsafe fn instr16_0FB3_mem(seg: i32, addr: i32, r: i32) {
+    btr_mem(seg, addr, read_reg16(r) << 16 >> 16);
+}
 #[no_mangle]
 pub unsafe fn instr32_0FB3_reg(r1: i32, r2: i32) {
     write_reg32(r1, btr_reg(read_reg32(r1), read_reg32(r2) & 31));
 }
 #[no_mangle]
-pub unsafe fn instr32_0FB3_mem(addr: i32, r: i32) { btr_mem(addr, read_reg32(r)); }
+pub unsafe fn instr32_0FB3_mem(seg: i32, addr: i32, r: i32) { btr_mem(seg, addr, read_reg32(r)); }
 #[no_mangle]
 pub unsafe fn instr16_0FB4_reg(_unused: i32, _unused2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr16_0FB4_mem(addr: i32, r: i32) { lss16(addr, r, FS); }
+pub unsafe fn instr16_0FB4_mem(seg: i32, addr: i32, r: i32) { lss16(seg, addr, r, FS); }
 #[no_mangle]
 pub unsafe fn instr32_0FB4_reg(_unused: i32, _unused2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr32_0FB4_mem(addr: i32, r: i32) { lss32(addr, r, FS); }
+pub unsafe fn instr32_0FB4_mem(seg: i32, addr: i32, r: i32) { lss32(seg, addr, r, FS); }
 #[no_mangle]
 pub unsafe fn instr16_0FB5_reg(_unused: i32, _unused2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr16_0FB5_mem(addr: i32, r: i32) { lss16(addr, r, GS); }
+pub unsafe fn instr16_0FB5_mem(seg: i32, addr: i32, r: i32) { lss16(seg, addr, r, GS); }
 #[no_mangle]
 pub unsafe fn instr32_0FB5_reg(_unused: i32, _unused2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr32_0FB5_mem(addr: i32, r: i32) { lss32(addr, r, GS); }
-pub unsafe fn instr16_0FB6_mem(addr: i32, r: i32) {
-    write_reg16(r, return_on_pagefault!(safe_read8(addr)));
+pub unsafe fn instr32_0FB5_mem(seg: i32, addr: i32, r: i32) { lss32(seg, addr, r, GS); }
+pub unsafe fn instr16_0FB6_mem(seg: i32, addr: i32, r: i32) {
+    write_reg16(r, return_on_pagefault!(safe_read8_seg(seg, addr)));
 }
 pub unsafe fn instr16_0FB6_reg(r1: i32, r: i32) { write_reg16(r, read_reg8(r1)); }
-pub unsafe fn instr32_0FB6_mem(addr: i32, r: i32) {
-    write_reg32(r, return_on_pagefault!(safe_read8(addr)));
+pub unsafe fn instr32_0FB6_mem(seg: i32, addr: i32, r: i32) {
+    write_reg32(r, return_on_pagefault!(safe_read8_seg(seg, addr)));
 }
 pub unsafe fn instr32_0FB6_reg(r1: i32, r: i32) { write_reg32(r, read_reg8(r1)); }
-pub unsafe fn instr16_0FB7_mem(addr: i32, r: i32) {
-    write_reg16(r, return_on_pagefault!(safe_read16(addr)));
+pub unsafe fn instr16_0FB7_mem(seg: i32, addr: i32, r: i32) {
+    write_reg16(r, return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 pub unsafe fn instr16_0FB7_reg(r1: i32, r: i32) { write_reg16(r, read_reg16(r1)); }
-pub unsafe fn instr32_0FB7_mem(addr: i32, r: i32) {
-    write_reg32(r, return_on_pagefault!(safe_read16(addr)));
+pub unsafe fn instr32_0FB7_mem(seg: i32, addr: i32, r: i32) {
+    write_reg32(r, return_on_pagefault!(safe_read16_seg(seg, addr)));
 }
 pub unsafe fn instr32_0FB7_reg(r1: i32, r: i32) { write_reg32(r, read_reg16(r1)); }
 #[no_mangle]
 pub unsafe fn instr16_0FB8_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr16_0FB8_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr16_F30FB8_mem(addr: i32, r: i32) {
-    write_reg16(r, popcnt(return_on_pagefault!(safe_read16(addr))));
+pub unsafe fn instr16_0FB8_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr16_F30FB8_mem(seg: i32, addr: i32, r: i32) {
+    write_reg16(r, popcnt(return_on_pagefault!(safe_read16_seg(seg, addr))));
 }
 pub unsafe fn instr16_F30FB8_reg(r1: i32, r: i32) { write_reg16(r, popcnt(read_reg16(r1))); }
 #[no_mangle]
 pub unsafe fn instr32_0FB8_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr32_0FB8_mem(_addr: i32, _r: i32) { trigger_ud(); }
-pub unsafe fn instr32_F30FB8_mem(addr: i32, r: i32) {
-    write_reg32(r, popcnt(return_on_pagefault!(safe_read32s(addr))));
+pub unsafe fn instr32_0FB8_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr32_F30FB8_mem(seg: i32, addr: i32, r: i32) {
+    write_reg32(r, popcnt(return_on_pagefault!(safe_read32s_seg(seg, addr))));
 }
 pub unsafe fn instr32_F30FB8_reg(r1: i32, r: i32) { write_reg32(r, popcnt(read_reg32(r1))); }
 #[no_mangle]
@@ -3646,116 +3781,172 @@ pub unsafe fn instr_0FB9() {
     trigger_ud();
 }
 pub unsafe fn instr16_0FBA_4_reg(r: i32, imm: i32) { bt_reg(read_reg16(r), imm & 15); }
-pub unsafe fn instr16_0FBA_4_mem(addr: i32, imm: i32) { bt_mem(addr, imm & 15); }
+pub unsafe fn instr16_0FBA_4_mem(seg: i32, addr: i32, imm: i32) { bt_mem(seg, addr, imm & 15); }
 #[no_mangle]
 pub unsafe fn instr16_0FBA_5_reg(r: i32, imm: i32) {
     write_reg16(r, bts_reg(read_reg16(r), imm & 15));
 }
 #[no_mangle]
-pub unsafe fn instr16_0FBA_5_mem(addr: i32, imm: i32) { bts_mem(addr, imm & 15); }
+pub unsafe fn instr16_0FBA_5_mem(seg: i32, addr: i32, imm: i32) { bts_mem(seg, addr, imm & 15); }
 #[no_mangle]
 pub unsafe fn instr16_0FBA_6_reg(r: i32, imm: i32) {
     write_reg16(r, btr_reg(read_reg16(r), imm & 15));
 }
 #[no_mangle]
-pub unsafe fn instr16_0FBA_6_mem(addr: i32, imm: i32) { btr_mem(addr, imm & 15); }
+pub unsafe fn instr16_0FBA_6_mem(seg: i32, addr: i32, imm: i32) { btr_mem(seg, addr, imm & 15); }
 #[no_mangle]
 pub unsafe fn instr16_0FBA_7_reg(r: i32, imm: i32) {
     write_reg16(r, btc_reg(read_reg16(r), imm & 15));
 }
 #[no_mangle]
-pub unsafe fn instr16_0FBA_7_mem(addr: i32, imm: i32) { btc_mem(addr, imm & 15); }
+pub unsafe fn instr16_0FBA_7_mem(seg: i32, addr: i32, imm: i32) { btc_mem(seg, addr, imm & 15); }
 pub unsafe fn instr32_0FBA_4_reg(r: i32, imm: i32) { bt_reg(read_reg32(r), imm & 31); }
-pub unsafe fn instr32_0FBA_4_mem(addr: i32, imm: i32) { bt_mem(addr, imm & 31); }
+pub unsafe fn instr32_0FBA_4_mem(seg: i32, addr: i32, imm: i32) { bt_mem(seg, addr, imm & 31); }
 #[no_mangle]
 pub unsafe fn instr32_0FBA_5_reg(r: i32, imm: i32) {
     write_reg32(r, bts_reg(read_reg32(r), imm & 31));
 }
 #[no_mangle]
-pub unsafe fn instr32_0FBA_5_mem(addr: i32, imm: i32) { bts_mem(addr, imm & 31); }
+pub unsafe fn instr32_0FBA_5_mem(seg: i32, addr: i32, imm: i32) { bts_mem(seg, addr, imm & 31); }
 #[no_mangle]
 pub unsafe fn instr32_0FBA_6_reg(r: i32, imm: i32) {
     write_reg32(r, btr_reg(read_reg32(r), imm & 31));
 }
 #[no_mangle]
-pub unsafe fn instr32_0FBA_6_mem(addr: i32, imm: i32) { btr_mem(addr, imm & 31); }
+pub unsafe fn instr32_0FBA_6_mem(seg: i32, addr: i32, imm: i32) { btr_mem(seg, addr, imm & 31); }
 #[no_mangle]
 pub unsafe fn instr32_0FBA_7_reg(r: i32, imm: i32) {
     write_reg32(r, btc_reg(read_reg32(r), imm & 31));
 }
 #[no_mangle]
-pub unsafe fn instr32_0FBA_7_mem(addr: i32, imm: i32) { btc_mem(addr, imm & 31); }
+pub unsafe fn instr32_0FBA_7_mem(seg: i32, addr: i32, imm: i32) { btc_mem(seg, addr, imm & 31); }
 #[no_mangle]
 pub unsafe fn instr16_0FBB_reg(r1: i32, r2: i32) {
     write_reg16(r1, btc_reg(read_reg16(r1), read_reg16(r2) & 15));
 }
 #[no_mangle]
-pub unsafe fn instr16_0FBB_mem(addr: i32, r: i32) { btc_mem(addr, read_reg16(r) << 16 >> 16); }
+pub unsafe fn instr16_0FBB_mem(seg: i32, addr: i32, r: i32) {
+    btc_mem(seg, addr, read_reg16(r) << 16 >> 16);
+}
 #[no_mangle]
 pub unsafe fn instr32_0FBB_reg(r1: i32, r2: i32) {
     write_reg32(r1, btc_reg(read_reg32(r1), read_reg32(r2) & 31));
 }
 #[no_mangle]
-pub unsafe fn instr32_0FBB_mem(addr: i32, r: i32) { btc_mem(addr, read_reg32(r)); }
-pub unsafe fn instr16_0FBC_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_0FBB_mem(seg: i32, addr: i32, r: i32) { btc_mem(seg, addr, read_reg32(r)); }
+pub unsafe fn instr16_0FBC_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        bsf16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        bsf16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_0FBC_reg(r1: i32, r: i32) {
     write_reg16(r, bsf16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_0FBC_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_0FBC_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        bsf32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        bsf32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_0FBC_reg(r1: i32, r: i32) {
     write_reg32(r, bsf32(read_reg32(r), read_reg32(r1)));
 }
-pub unsafe fn instr16_0FBD_mem(addr: i32, r: i32) {
+pub unsafe fn instr16_0FBD_mem(seg: i32, addr: i32, r: i32) {
     write_reg16(
         r,
-        bsr16(read_reg16(r), return_on_pagefault!(safe_read16(addr))),
+        bsr16(read_reg16(r), return_on_pagefault!(safe_read16_seg(seg, addr))),
     );
 }
 pub unsafe fn instr16_0FBD_reg(r1: i32, r: i32) {
     write_reg16(r, bsr16(read_reg16(r), read_reg16(r1)));
 }
-pub unsafe fn instr32_0FBD_mem(addr: i32, r: i32) {
+pub unsafe fn instr32_0FBD_mem(seg: i32, addr: i32, r: i32) {
     write_reg32(
         r,
-        bsr32(read_reg32(r), return_on_pagefault!(safe_read32s(addr))),
+        bsr32(read_reg32(r), return_on_pagefault!(safe_read32s_seg(seg, addr))),
     );
 }
 pub unsafe fn instr32_0FBD_reg(r1: i32, r: i32) {
     write_reg32(r, bsr32(read_reg32(r), read_reg32(r1)));
 }
-pub unsafe fn instr16_0FBE_mem(addr: i32, r: i32) {
-    write_reg16(r, return_on_pagefault!(safe_read8(addr)) << 24 >> 24);
+pub unsafe fn instr16_F30FBC_mem(seg: i32, addr: i32, r: i32) {
+    if !cpuid::has_feature(cpuid::FEATURE_BMI1) {
+        return instr16_0FBC_mem(seg, addr, r);
+    }
+    write_reg16(r, tzcnt16(return_on_pagefault!(safe_read16_seg(seg, addr))));
+}
+pub unsafe fn instr16_F30FBC_reg(r1: i32, r: i32) {
+    if !cpuid::has_feature(cpuid::FEATURE_BMI1) {
+        return instr16_0FBC_reg(r1, r);
+    }
+    write_reg16(r, tzcnt16(read_reg16(r1)));
+}
+pub unsafe fn instr32_F30FBC_mem(seg: i32, addr: i32, r: i32) {
+    if !cpuid::has_feature(cpuid::FEATURE_BMI1) {
+        return instr32_0FBC_mem(seg, addr, r);
+    }
+    write_reg32(r, tzcnt32(return_on_pagefault!(safe_read32s_seg(seg, addr))));
+}
+pub unsafe fn instr32_F30FBC_reg(r1: i32, r: i32) {
+    if !cpuid::has_feature(cpuid::FEATURE_BMI1) {
+        return instr32_0FBC_reg(r1, r);
+    }
+    write_reg32(r, tzcnt32(read_reg32(r1)));
+}
+pub unsafe fn instr16_F30FBD_mem(seg: i32, addr: i32, r: i32) {
+    if !cpuid::has_feature(cpuid::FEATURE_LZCNT) {
+        return instr16_0FBD_mem(seg, addr, r);
+    }
+    write_reg16(r, lzcnt16(return_on_pagefault!(safe_read16_seg(seg, addr))));
+}
+pub unsafe fn instr16_F30FBD_reg(r1: i32, r: i32) {
+    if !cpuid::has_feature(cpuid::FEATURE_LZCNT) {
+        return instr16_0FBD_reg(r1, r);
+    }
+    write_reg16(r, lzcnt16(read_reg16(r1)));
+}
+pub unsafe fn instr32_F30FBD_mem(seg: i32, addr: i32, r: i32) {
+    if !cpuid::has_feature(cpuid::FEATURE_LZCNT) {
+        return instr32_0FBD_mem(seg, addr, r);
+    }
+    write_reg32(r, lzcnt32(return_on_pagefault!(safe_read32s_seg(seg, addr))));
+}
+pub unsafe fn instr32_F30FBD_reg(r1: i32, r: i32) {
+    if !cpuid::has_feature(cpuid::FEATURE_LZCNT) {
+        return instr32_0FBD_reg(r1, r);
+    }
+    write_reg32(r, lzcnt32(read_reg32(r1)));
+}
+pub unsafe fn instr16_0FBE_mem(seg: i32, addr: i32, r: i32) {
+    write_reg16(r, return_on_pagefault!(safe_read8_seg(seg, addr)) << 24 >> 24);
 }
 pub unsafe fn instr16_0FBE_reg(r1: i32, r: i32) { write_reg16(r, read_reg8(r1) << 24 >> 24); }
-pub unsafe fn instr32_0FBE_mem(addr: i32, r: i32) {
-    write_reg32(r, return_on_pagefault!(safe_read8(addr)) << 24 >> 24);
+pub unsafe fn instr32_0FBE_mem(seg: i32, addr: i32, r: i32) {
+    write_reg32(r, return_on_pagefault!(safe_read8_seg(seg, addr)) << 24 >> 24);
 }
 pub unsafe fn instr32_0FBE_reg(r1: i32, r: i32) { write_reg32(r, read_reg8(r1) << 24 >> 24); }
-pub unsafe fn instr16_0FBF_mem(addr: i32, r: i32) {
-    write_reg16(r, return_on_pagefault!(safe_read16(addr)) << 16 >> 16);
+pub unsafe fn instr16_0FBF_mem(seg: i32, addr: i32, r: i32) {
+    write_reg16(r, return_on_pagefault!(safe_read16_seg(seg, addr)) << 16 >> 16);
 }
 pub unsafe fn instr16_0FBF_reg(r1: i32, r: i32) { write_reg16(r, read_reg16(r1) << 16 >> 16); }
-pub unsafe fn instr32_0FBF_mem(addr: i32, r: i32) {
-    write_reg32(r, return_on_pagefault!(safe_read16(addr)) << 16 >> 16);
+pub unsafe fn instr32_0FBF_mem(seg: i32, addr: i32, r: i32) {
+    write_reg32(r, return_on_pagefault!(safe_read16_seg(seg, addr)) << 16 >> 16);
 }
 pub unsafe fn instr32_0FBF_reg(r1: i32, r: i32) { write_reg32(r, read_reg16(r1) << 16 >> 16); }
 #[no_mangle]
-pub unsafe fn instr_0FC0_mem(addr: i32, r: i32) { safe_read_write8(addr, &|x| xadd8(x, r)) }
+pub unsafe fn instr_0FC0_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write8_seg(seg, addr, &|x| xadd8(x, r))
+}
 #[no_mangle]
 pub unsafe fn instr_0FC0_reg(r1: i32, r: i32) { write_reg8(r1, xadd8(read_reg8(r1), r)); }
-pub unsafe fn instr16_0FC1_mem(addr: i32, r: i32) { safe_read_write16(addr, &|x| xadd16(x, r)) }
+pub unsafe fn instr16_0FC1_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write16_seg(seg, addr, &|x| xadd16(x, r))
+}
 pub unsafe fn instr16_0FC1_reg(r1: i32, r: i32) { write_reg16(r1, xadd16(read_reg16(r1), r)); }
-pub unsafe fn instr32_0FC1_mem(addr: i32, r: i32) { safe_read_write32(addr, &|x| xadd32(x, r)) }
+pub unsafe fn instr32_0FC1_mem(seg: i32, addr: i32, r: i32) {
+    safe_read_write32_seg(seg, addr, &|x| xadd32(x, r))
+}
 pub unsafe fn instr32_0FC1_reg(r1: i32, r: i32) { write_reg32(r1, xadd32(read_reg32(r1), r)); }
 
 #[no_mangle]
@@ -3774,8 +3965,8 @@ pub unsafe fn instr_0FC2(source: reg128, r: i32, imm8: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_0FC2_reg(r1: i32, r2: i32, imm: i32) { instr_0FC2(read_xmm128s(r1), r2, imm); }
-pub unsafe fn instr_0FC2_mem(addr: i32, r: i32, imm: i32) {
-    instr_0FC2(return_on_pagefault!(safe_read128s(addr)), r, imm);
+pub unsafe fn instr_0FC2_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_0FC2(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
 }
 #[no_mangle]
 pub unsafe fn instr_660FC2(source: reg128, r: i32, imm8: i32) {
@@ -3792,8 +3983,8 @@ pub unsafe fn instr_660FC2(source: reg128, r: i32, imm8: i32) {
 pub unsafe fn instr_660FC2_reg(r1: i32, r2: i32, imm: i32) {
     instr_660FC2(read_xmm128s(r1), r2, imm);
 }
-pub unsafe fn instr_660FC2_mem(addr: i32, r: i32, imm: i32) {
-    instr_660FC2(return_on_pagefault!(safe_read128s(addr)), r, imm);
+pub unsafe fn instr_660FC2_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660FC2(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
 }
 #[no_mangle]
 pub unsafe fn instr_F20FC2(source: u64, r: i32, imm8: i32) {
@@ -3812,8 +4003,8 @@ pub unsafe fn instr_F20FC2(source: u64, r: i32, imm8: i32) {
 pub unsafe fn instr_F20FC2_reg(r1: i32, r2: i32, imm: i32) {
     instr_F20FC2(read_xmm64s(r1), r2, imm);
 }
-pub unsafe fn instr_F20FC2_mem(addr: i32, r: i32, imm: i32) {
-    instr_F20FC2(return_on_pagefault!(safe_read64s(addr)), r, imm);
+pub unsafe fn instr_F20FC2_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_F20FC2(return_on_pagefault!(safe_read64s_seg(seg, addr)), r, imm);
 }
 #[no_mangle]
 pub unsafe fn instr_F30FC2(source: i32, r: i32, imm8: i32) {
@@ -3826,14 +4017,14 @@ pub unsafe fn instr_F30FC2(source: i32, r: i32, imm8: i32) {
 pub unsafe fn instr_F30FC2_reg(r1: i32, r2: i32, imm: i32) {
     instr_F30FC2(read_xmm64s(r1) as i32, r2, imm);
 }
-pub unsafe fn instr_F30FC2_mem(addr: i32, r: i32, imm: i32) {
-    instr_F30FC2(return_on_pagefault!(safe_read32s(addr)), r, imm);
+pub unsafe fn instr_F30FC2_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_F30FC2(return_on_pagefault!(safe_read32s_seg(seg, addr)), r, imm);
 }
 
 pub unsafe fn instr_0FC3_reg(_r1: i32, _r2: i32) { trigger_ud(); }
-pub unsafe fn instr_0FC3_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0FC3_mem(seg: i32, addr: i32, r: i32) {
     // movnti
-    return_on_pagefault!(safe_write32(addr, read_reg32(r)));
+    return_on_pagefault!(safe_write32_seg(seg, addr, read_reg32(r)));
 }
 
 #[no_mangle]
@@ -3845,8 +4036,8 @@ pub unsafe fn instr_0FC4(source: i32, r: i32, imm8: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FC4_reg(r1: i32, r2: i32, imm: i32) { instr_0FC4(read_reg32(r1), r2, imm); }
-pub unsafe fn instr_0FC4_mem(addr: i32, r: i32, imm: i32) {
-    instr_0FC4(return_on_pagefault!(safe_read16(addr)), r, imm);
+pub unsafe fn instr_0FC4_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_0FC4(return_on_pagefault!(safe_read16_seg(seg, addr)), r, imm);
 }
 pub unsafe fn instr_660FC4(source: i32, r: i32, imm8: i32) {
     // pinsrw xmm, r32/m16, imm8
@@ -3858,10 +4049,10 @@ pub unsafe fn instr_660FC4(source: i32, r: i32, imm8: i32) {
 pub unsafe fn instr_660FC4_reg(r1: i32, r2: i32, imm: i32) {
     instr_660FC4(read_reg32(r1), r2, imm);
 }
-pub unsafe fn instr_660FC4_mem(addr: i32, r: i32, imm: i32) {
-    instr_660FC4(return_on_pagefault!(safe_read16(addr)), r, imm);
+pub unsafe fn instr_660FC4_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660FC4(return_on_pagefault!(safe_read16_seg(seg, addr)), r, imm);
 }
-pub unsafe fn instr_0FC5_mem(_addr: i32, _r: i32, _imm8: i32) { trigger_ud(); }
+pub unsafe fn instr_0FC5_mem(_seg: i32, _addr: i32, _r: i32, _imm8: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0FC5_reg(r1: i32, r2: i32, imm8: i32) {
     // pextrw r32, mm, imm8
@@ -3869,7 +4060,7 @@ pub unsafe fn instr_0FC5_reg(r1: i32, r2: i32, imm8: i32) {
     write_reg32(r2, data[(imm8 & 3) as usize] as i32);
     transition_fpu_to_mmx();
 }
-pub unsafe fn instr_660FC5_mem(_addr: i32, _r: i32, _imm8: i32) { trigger_ud(); }
+pub unsafe fn instr_660FC5_mem(_seg: i32, _addr: i32, _r: i32, _imm8: i32) { trigger_ud(); }
 pub unsafe fn instr_660FC5_reg(r1: i32, r2: i32, imm8: i32) {
     // pextrw r32, xmm, imm8
     let data = read_xmm128s(r1);
@@ -3892,8 +4083,8 @@ pub unsafe fn instr_0FC6(source: reg128, r: i32, imm8: i32) {
     );
 }
 pub unsafe fn instr_0FC6_reg(r1: i32, r2: i32, imm: i32) { instr_0FC6(read_xmm128s(r1), r2, imm); }
-pub unsafe fn instr_0FC6_mem(addr: i32, r: i32, imm: i32) {
-    instr_0FC6(return_on_pagefault!(safe_read128s(addr)), r, imm);
+pub unsafe fn instr_0FC6_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_0FC6(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
 }
 
 #[no_mangle]
@@ -3911,21 +4102,21 @@ pub unsafe fn instr_660FC6(source: reg128, r: i32, imm8: i32) {
 pub unsafe fn instr_660FC6_reg(r1: i32, r2: i32, imm: i32) {
     instr_660FC6(read_xmm128s(r1), r2, imm);
 }
-pub unsafe fn instr_660FC6_mem(addr: i32, r: i32, imm: i32) {
-    instr_660FC6(return_on_pagefault!(safe_read128s(addr)), r, imm);
+pub unsafe fn instr_660FC6_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660FC6(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
 }
 
 pub unsafe fn instr16_0FC7_1_reg(_r: i32) { trigger_ud(); }
 pub unsafe fn instr32_0FC7_1_reg(_r: i32) { trigger_ud(); }
-pub unsafe fn instr16_0FC7_1_mem(addr: i32) {
+pub unsafe fn instr16_0FC7_1_mem(seg: i32, addr: i32) {
     // cmpxchg8b
-    return_on_pagefault!(writable_or_pagefault(addr, 8));
-    let m64 = safe_read64s(addr).unwrap();
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 8));
+    let m64 = safe_read64s_seg(seg, addr).unwrap();
     let m64_low = m64 as i32;
     let m64_high = (m64 >> 32) as i32;
     if read_reg32(EAX) == m64_low && read_reg32(EDX) == m64_high {
         *flags |= FLAG_ZERO;
-        safe_write64(
+        safe_write64_seg(seg, 
             addr,
             read_reg32(EBX) as u32 as u64 | (read_reg32(ECX) as u32 as u64) << 32,
         )
@@ -3938,12 +4129,37 @@ pub unsafe fn instr16_0FC7_1_mem(addr: i32) {
     }
     *flags_changed &= !FLAG_ZERO;
 }
-pub unsafe fn instr32_0FC7_1_mem(addr: i32) { instr16_0FC7_1_mem(addr) }
+pub unsafe fn instr32_0FC7_1_mem(seg: i32, addr: i32) { instr16_0FC7_1_mem(seg, addr) }
+
+#[no_mangle]
+pub unsafe fn instr_0FC7_4_reg(_r: i32) { trigger_ud(); }
+#[no_mangle]
+pub unsafe fn instr_0FC7_4_mem(seg: i32, addr: i32) {
+    // xsavec
+    xsave(seg, addr, true);
+}
+
+unsafe fn rand_int() -> i32 {
+    replay::input(replay::Event::Rand, 0, || rand_int_unrecorded() as u32 as u64) as i32
+}
+
+// In icount mode, rdrand returns a fixed sequence (xorshift32), so that runs are reproducible
+unsafe fn rand_int_unrecorded() -> i32 {
+    if icount_rate == 0.0 {
+        return get_rand_int();
+    }
+    let mut x = icount_rand_state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    icount_rand_state = x;
+    x as i32
+}
 
 #[no_mangle]
 pub unsafe fn instr16_0FC7_6_reg(r: i32) {
     // rdrand
-    let rand = get_rand_int();
+    let rand = rand_int();
     write_reg16(r, rand);
     *flags &= !FLAGS_ALL;
     *flags |= 1;
@@ -3952,7 +4168,7 @@ pub unsafe fn instr16_0FC7_6_reg(r: i32) {
 #[no_mangle]
 pub unsafe fn instr32_0FC7_6_reg(r: i32) {
     // rdrand
-    let rand = get_rand_int();
+    let rand = rand_int();
     write_reg32(r, rand);
     *flags &= !FLAGS_ALL;
     *flags |= 1;
@@ -3960,9 +4176,9 @@ pub unsafe fn instr32_0FC7_6_reg(r: i32) {
 }
 
 #[no_mangle]
-pub unsafe fn instr16_0FC7_6_mem(_addr: i32) { trigger_ud(); }
+pub unsafe fn instr16_0FC7_6_mem(_seg: i32, _addr: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn instr32_0FC7_6_mem(_addr: i32) { trigger_ud(); }
+pub unsafe fn instr32_0FC7_6_mem(_seg: i32, _addr: i32) { trigger_ud(); }
 
 #[no_mangle]
 pub unsafe fn instr_0FC8() { bswap(EAX); }
@@ -3988,8 +4204,8 @@ pub unsafe fn instr_0FD1(source: u64, r: i32) {
     psrlw_r64(r, source);
 }
 pub unsafe fn instr_0FD1_reg(r1: i32, r2: i32) { instr_0FD1(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FD1_mem(addr: i32, r: i32) {
-    instr_0FD1(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FD1_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FD1(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FD1(source: reg128, r: i32) {
@@ -3998,8 +4214,8 @@ pub unsafe fn instr_660FD1(source: reg128, r: i32) {
     psrlw_r128(r, source.u64[0]);
 }
 pub unsafe fn instr_660FD1_reg(r1: i32, r2: i32) { instr_660FD1(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FD1_mem(addr: i32, r: i32) {
-    instr_660FD1(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FD1_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FD1(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FD2(source: u64, r: i32) {
@@ -4007,8 +4223,8 @@ pub unsafe fn instr_0FD2(source: u64, r: i32) {
     psrld_r64(r, source);
 }
 pub unsafe fn instr_0FD2_reg(r1: i32, r2: i32) { instr_0FD2(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FD2_mem(addr: i32, r: i32) {
-    instr_0FD2(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FD2_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FD2(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FD2(source: reg128, r: i32) {
@@ -4017,8 +4233,8 @@ pub unsafe fn instr_660FD2(source: reg128, r: i32) {
     psrld_r128(r, source.u64[0]);
 }
 pub unsafe fn instr_660FD2_reg(r1: i32, r2: i32) { instr_660FD2(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FD2_mem(addr: i32, r: i32) {
-    instr_660FD2(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FD2_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FD2(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FD3(source: u64, r: i32) {
@@ -4026,8 +4242,8 @@ pub unsafe fn instr_0FD3(source: u64, r: i32) {
     psrlq_r64(r, source);
 }
 pub unsafe fn instr_0FD3_reg(r1: i32, r2: i32) { instr_0FD3(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FD3_mem(addr: i32, r: i32) {
-    instr_0FD3(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FD3_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FD3(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FD3(source: reg128, r: i32) {
@@ -4035,8 +4251,8 @@ pub unsafe fn instr_660FD3(source: reg128, r: i32) {
     psrlq_r128(r, source.u64[0]);
 }
 pub unsafe fn instr_660FD3_reg(r1: i32, r2: i32) { instr_660FD3(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FD3_mem(addr: i32, r: i32) {
-    instr_660FD3(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FD3_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FD3(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FD4(source: u64, r: i32) {
@@ -4046,8 +4262,8 @@ pub unsafe fn instr_0FD4(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FD4_reg(r1: i32, r2: i32) { instr_0FD4(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FD4_mem(addr: i32, r: i32) {
-    instr_0FD4(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FD4_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FD4(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FD4(source: reg128, r: i32) {
@@ -4060,8 +4276,8 @@ pub unsafe fn instr_660FD4(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FD4_reg(r1: i32, r2: i32) { instr_660FD4(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FD4_mem(addr: i32, r: i32) {
-    instr_660FD4(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FD4_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FD4(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FD5(source: u64, r: i32) {
@@ -4076,8 +4292,8 @@ pub unsafe fn instr_0FD5(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FD5_reg(r1: i32, r2: i32) { instr_0FD5(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FD5_mem(addr: i32, r: i32) {
-    instr_0FD5(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FD5_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FD5(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FD5(source: reg128, r: i32) {
@@ -4091,17 +4307,17 @@ pub unsafe fn instr_660FD5(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FD5_reg(r1: i32, r2: i32) { instr_660FD5(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FD5_mem(addr: i32, r: i32) {
-    instr_660FD5(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FD5_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FD5(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
-pub unsafe fn instr_0FD6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0FD6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0FD6_reg(_r1: i32, _r2: i32) { trigger_ud(); }
-pub unsafe fn instr_660FD6_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660FD6_mem(seg: i32, addr: i32, r: i32) {
     // movq xmm/m64, xmm
-    movl_r128_m64(addr, r);
+    movl_r128_m64(seg, addr, r);
 }
 pub unsafe fn instr_660FD6_reg(r1: i32, r2: i32) {
     // movq xmm/m64, xmm
@@ -4109,7 +4325,7 @@ pub unsafe fn instr_660FD6_reg(r1: i32, r2: i32) {
 }
 
 #[no_mangle]
-pub unsafe fn instr_F20FD6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_F20FD6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_F20FD6_reg(r1: i32, r2: i32) {
     // movdq2q mm, xmm
@@ -4117,7 +4333,7 @@ pub unsafe fn instr_F20FD6_reg(r1: i32, r2: i32) {
     transition_fpu_to_mmx();
 }
 #[no_mangle]
-pub unsafe fn instr_F30FD6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_F30FD6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_F30FD6_reg(r1: i32, r2: i32) {
     // movq2dq xmm, mm
@@ -4126,7 +4342,7 @@ pub unsafe fn instr_F30FD6_reg(r1: i32, r2: i32) {
     transition_fpu_to_mmx();
 }
 
-pub unsafe fn instr_0FD7_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0FD7_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0FD7(r1: i32) -> i32 {
     // pmovmskb r, mm
@@ -4139,7 +4355,7 @@ pub unsafe fn instr_0FD7(r1: i32) -> i32 {
     result
 }
 pub unsafe fn instr_0FD7_reg(r1: i32, r2: i32) { write_reg32(r2, instr_0FD7(r1)); }
-pub unsafe fn instr_660FD7_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660FD7_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_660FD7(r1: i32) -> i32 {
     // pmovmskb reg, xmm
@@ -4164,8 +4380,8 @@ pub unsafe fn instr_0FD8(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FD8_reg(r1: i32, r2: i32) { instr_0FD8(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FD8_mem(addr: i32, r: i32) {
-    instr_0FD8(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FD8_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FD8(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FD8(source: reg128, r: i32) {
@@ -4178,8 +4394,8 @@ pub unsafe fn instr_660FD8(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FD8_reg(r1: i32, r2: i32) { instr_660FD8(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FD8_mem(addr: i32, r: i32) {
-    instr_660FD8(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FD8_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FD8(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FD9(source: u64, r: i32) {
@@ -4194,8 +4410,8 @@ pub unsafe fn instr_0FD9(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FD9_reg(r1: i32, r2: i32) { instr_0FD9(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FD9_mem(addr: i32, r: i32) {
-    instr_0FD9(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FD9_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FD9(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FD9(source: reg128, r: i32) {
@@ -4208,8 +4424,8 @@ pub unsafe fn instr_660FD9(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FD9_reg(r1: i32, r2: i32) { instr_660FD9(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FD9_mem(addr: i32, r: i32) {
-    instr_660FD9(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FD9_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FD9(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FDA(source: u64, r: i32) {
@@ -4224,8 +4440,8 @@ pub unsafe fn instr_0FDA(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FDA_reg(r1: i32, r2: i32) { instr_0FDA(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FDA_mem(addr: i32, r: i32) {
-    instr_0FDA(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FDA_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FDA(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FDA(source: reg128, r: i32) {
@@ -4239,8 +4455,8 @@ pub unsafe fn instr_660FDA(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FDA_reg(r1: i32, r2: i32) { instr_660FDA(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FDA_mem(addr: i32, r: i32) {
-    instr_660FDA(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FDA_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FDA(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FDB(source: u64, r: i32) {
@@ -4250,8 +4466,8 @@ pub unsafe fn instr_0FDB(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FDB_reg(r1: i32, r2: i32) { instr_0FDB(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FDB_mem(addr: i32, r: i32) {
-    instr_0FDB(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FDB_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FDB(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FDB(source: reg128, r: i32) {
@@ -4260,8 +4476,8 @@ pub unsafe fn instr_660FDB(source: reg128, r: i32) {
     pand_r128(source, r);
 }
 pub unsafe fn instr_660FDB_reg(r1: i32, r2: i32) { instr_660FDB(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FDB_mem(addr: i32, r: i32) {
-    instr_660FDB(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FDB_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FDB(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FDC(source: u64, r: i32) {
@@ -4276,8 +4492,8 @@ pub unsafe fn instr_0FDC(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FDC_reg(r1: i32, r2: i32) { instr_0FDC(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FDC_mem(addr: i32, r: i32) {
-    instr_0FDC(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FDC_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FDC(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FDC(source: reg128, r: i32) {
@@ -4291,8 +4507,8 @@ pub unsafe fn instr_660FDC(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FDC_reg(r1: i32, r2: i32) { instr_660FDC(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FDC_mem(addr: i32, r: i32) {
-    instr_660FDC(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FDC_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FDC(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FDD(source: u64, r: i32) {
@@ -4307,8 +4523,8 @@ pub unsafe fn instr_0FDD(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FDD_reg(r1: i32, r2: i32) { instr_0FDD(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FDD_mem(addr: i32, r: i32) {
-    instr_0FDD(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FDD_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FDD(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FDD(source: reg128, r: i32) {
@@ -4322,8 +4538,8 @@ pub unsafe fn instr_660FDD(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FDD_reg(r1: i32, r2: i32) { instr_660FDD(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FDD_mem(addr: i32, r: i32) {
-    instr_660FDD(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FDD_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FDD(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FDE(source: u64, r: i32) {
@@ -4338,8 +4554,8 @@ pub unsafe fn instr_0FDE(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FDE_reg(r1: i32, r2: i32) { instr_0FDE(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FDE_mem(addr: i32, r: i32) {
-    instr_0FDE(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FDE_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FDE(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FDE(source: reg128, r: i32) {
@@ -4353,8 +4569,8 @@ pub unsafe fn instr_660FDE(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FDE_reg(r1: i32, r2: i32) { instr_660FDE(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FDE_mem(addr: i32, r: i32) {
-    instr_660FDE(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FDE_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FDE(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FDF(source: u64, r: i32) {
@@ -4364,8 +4580,8 @@ pub unsafe fn instr_0FDF(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FDF_reg(r1: i32, r2: i32) { instr_0FDF(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FDF_mem(addr: i32, r: i32) {
-    instr_0FDF(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FDF_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FDF(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FDF(source: reg128, r: i32) {
@@ -4374,8 +4590,8 @@ pub unsafe fn instr_660FDF(source: reg128, r: i32) {
     pandn_r128(source, r);
 }
 pub unsafe fn instr_660FDF_reg(r1: i32, r2: i32) { instr_660FDF(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FDF_mem(addr: i32, r: i32) {
-    instr_660FDF(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FDF_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FDF(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE0(source: u64, r: i32) {
@@ -4390,8 +4606,8 @@ pub unsafe fn instr_0FE0(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FE0_reg(r1: i32, r2: i32) { instr_0FE0(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FE0_mem(addr: i32, r: i32) {
-    instr_0FE0(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FE0_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FE0(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FE0(source: reg128, r: i32) {
@@ -4405,8 +4621,8 @@ pub unsafe fn instr_660FE0(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FE0_reg(r1: i32, r2: i32) { instr_660FE0(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FE0_mem(addr: i32, r: i32) {
-    instr_660FE0(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE0_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE0(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE1(source: u64, r: i32) {
@@ -4414,8 +4630,8 @@ pub unsafe fn instr_0FE1(source: u64, r: i32) {
     psraw_r64(r, source);
 }
 pub unsafe fn instr_0FE1_reg(r1: i32, r2: i32) { instr_0FE1(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FE1_mem(addr: i32, r: i32) {
-    instr_0FE1(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FE1_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FE1(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FE1(source: reg128, r: i32) {
@@ -4424,8 +4640,8 @@ pub unsafe fn instr_660FE1(source: reg128, r: i32) {
     psraw_r128(r, source.u64[0]);
 }
 pub unsafe fn instr_660FE1_reg(r1: i32, r2: i32) { instr_660FE1(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FE1_mem(addr: i32, r: i32) {
-    instr_660FE1(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE1_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE1(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE2(source: u64, r: i32) {
@@ -4433,8 +4649,8 @@ pub unsafe fn instr_0FE2(source: u64, r: i32) {
     psrad_r64(r, source);
 }
 pub unsafe fn instr_0FE2_reg(r1: i32, r2: i32) { instr_0FE2(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FE2_mem(addr: i32, r: i32) {
-    instr_0FE2(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FE2_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FE2(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FE2(source: reg128, r: i32) {
@@ -4443,8 +4659,8 @@ pub unsafe fn instr_660FE2(source: reg128, r: i32) {
     psrad_r128(r, source.u64[0]);
 }
 pub unsafe fn instr_660FE2_reg(r1: i32, r2: i32) { instr_660FE2(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FE2_mem(addr: i32, r: i32) {
-    instr_660FE2(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE2_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE2(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE3(source: u64, r: i32) {
@@ -4459,8 +4675,8 @@ pub unsafe fn instr_0FE3(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FE3_reg(r1: i32, r2: i32) { instr_0FE3(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FE3_mem(addr: i32, r: i32) {
-    instr_0FE3(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FE3_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FE3(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FE3(source: reg128, r: i32) {
@@ -4473,8 +4689,8 @@ pub unsafe fn instr_660FE3(source: reg128, r: i32) {
     write_xmm_reg128(r, destination);
 }
 pub unsafe fn instr_660FE3_reg(r1: i32, r2: i32) { instr_660FE3(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FE3_mem(addr: i32, r: i32) {
-    instr_660FE3(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE3_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE3(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE4(source: u64, r: i32) {
@@ -4489,8 +4705,8 @@ pub unsafe fn instr_0FE4(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FE4_reg(r1: i32, r2: i32) { instr_0FE4(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FE4_mem(addr: i32, r: i32) {
-    instr_0FE4(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FE4_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FE4(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FE4(source: reg128, r: i32) {
@@ -4504,8 +4720,8 @@ pub unsafe fn instr_660FE4(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FE4_reg(r1: i32, r2: i32) { instr_660FE4(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FE4_mem(addr: i32, r: i32) {
-    instr_660FE4(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE4_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE4(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE5(source: u64, r: i32) {
@@ -4520,8 +4736,8 @@ pub unsafe fn instr_0FE5(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FE5_reg(r1: i32, r2: i32) { instr_0FE5(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FE5_mem(addr: i32, r: i32) {
-    instr_0FE5(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FE5_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FE5(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FE5(source: reg128, r: i32) {
@@ -4535,12 +4751,12 @@ pub unsafe fn instr_660FE5(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FE5_reg(r1: i32, r2: i32) { instr_660FE5(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FE5_mem(addr: i32, r: i32) {
-    instr_660FE5(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE5_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE5(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
 #[no_mangle]
-pub unsafe fn instr_0FE6_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0FE6_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
 pub unsafe fn instr_0FE6_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 
@@ -4557,8 +4773,8 @@ pub unsafe fn instr_660FE6(source: reg128, r: i32) {
     };
     write_xmm_reg128(r, result);
 }
-pub unsafe fn instr_660FE6_mem(addr: i32, r: i32) {
-    instr_660FE6(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE6_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE6(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_660FE6_reg(r1: i32, r2: i32) { instr_660FE6(read_xmm128s(r1), r2); }
 
@@ -4576,8 +4792,8 @@ pub unsafe fn instr_F20FE6(source: reg128, r: i32) {
     };
     write_xmm_reg128(r, result);
 }
-pub unsafe fn instr_F20FE6_mem(addr: i32, r: i32) {
-    instr_F20FE6(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_F20FE6_mem(seg: i32, addr: i32, r: i32) {
+    instr_F20FE6(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_F20FE6_reg(r1: i32, r2: i32) { instr_F20FE6(read_xmm128s(r1), r2); }
 
@@ -4593,22 +4809,22 @@ pub unsafe fn instr_F30FE6(source: u64, r: i32) {
     };
     write_xmm_reg128(r, result);
 }
-pub unsafe fn instr_F30FE6_mem(addr: i32, r: i32) {
-    instr_F30FE6(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_F30FE6_mem(seg: i32, addr: i32, r: i32) {
+    instr_F30FE6(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 pub unsafe fn instr_F30FE6_reg(r1: i32, r2: i32) { instr_F30FE6(read_xmm64s(r1), r2); }
 
 #[no_mangle]
-pub unsafe fn instr_0FE7_mem(addr: i32, r: i32) {
+pub unsafe fn instr_0FE7_mem(seg: i32, addr: i32, r: i32) {
     // movntq m64, mm
-    mov_r_m64(addr, r);
+    mov_r_m64(seg, addr, r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE7_reg(_r1: i32, _r2: i32) { trigger_ud(); }
 pub unsafe fn instr_660FE7_reg(_r1: i32, _r2: i32) { trigger_ud(); }
-pub unsafe fn instr_660FE7_mem(addr: i32, r: i32) {
+pub unsafe fn instr_660FE7_mem(seg: i32, addr: i32, r: i32) {
     // movntdq m128, xmm
-    mov_r_m128(addr, r);
+    mov_r_m128(seg, addr, r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE8(source: u64, r: i32) {
@@ -4623,8 +4839,8 @@ pub unsafe fn instr_0FE8(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FE8_reg(r1: i32, r2: i32) { instr_0FE8(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FE8_mem(addr: i32, r: i32) {
-    instr_0FE8(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FE8_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FE8(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FE8(source: reg128, r: i32) {
@@ -4638,8 +4854,8 @@ pub unsafe fn instr_660FE8(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FE8_reg(r1: i32, r2: i32) { instr_660FE8(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FE8_mem(addr: i32, r: i32) {
-    instr_660FE8(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE8_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE8(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FE9(source: u64, r: i32) {
@@ -4654,8 +4870,8 @@ pub unsafe fn instr_0FE9(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FE9_reg(r1: i32, r2: i32) { instr_0FE9(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FE9_mem(addr: i32, r: i32) {
-    instr_0FE9(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FE9_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FE9(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FE9(source: reg128, r: i32) {
@@ -4669,8 +4885,8 @@ pub unsafe fn instr_660FE9(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FE9_reg(r1: i32, r2: i32) { instr_660FE9(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FE9_mem(addr: i32, r: i32) {
-    instr_660FE9(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FE9_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FE9(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FEA(source: u64, r: i32) {
@@ -4685,8 +4901,8 @@ pub unsafe fn instr_0FEA(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FEA_reg(r1: i32, r2: i32) { instr_0FEA(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FEA_mem(addr: i32, r: i32) {
-    instr_0FEA(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FEA_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FEA(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FEA(source: reg128, r: i32) {
@@ -4700,8 +4916,8 @@ pub unsafe fn instr_660FEA(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FEA_reg(r1: i32, r2: i32) { instr_660FEA(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FEA_mem(addr: i32, r: i32) {
-    instr_660FEA(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FEA_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FEA(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FEB(source: u64, r: i32) {
@@ -4711,8 +4927,8 @@ pub unsafe fn instr_0FEB(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FEB_reg(r1: i32, r2: i32) { instr_0FEB(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FEB_mem(addr: i32, r: i32) {
-    instr_0FEB(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FEB_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FEB(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FEB(source: reg128, r: i32) {
@@ -4721,8 +4937,8 @@ pub unsafe fn instr_660FEB(source: reg128, r: i32) {
     por_r128(source, r);
 }
 pub unsafe fn instr_660FEB_reg(r1: i32, r2: i32) { instr_660FEB(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FEB_mem(addr: i32, r: i32) {
-    instr_660FEB(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FEB_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FEB(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FEC(source: u64, r: i32) {
@@ -4737,8 +4953,8 @@ pub unsafe fn instr_0FEC(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FEC_reg(r1: i32, r2: i32) { instr_0FEC(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FEC_mem(addr: i32, r: i32) {
-    instr_0FEC(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FEC_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FEC(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FEC(source: reg128, r: i32) {
@@ -4752,8 +4968,8 @@ pub unsafe fn instr_660FEC(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FEC_reg(r1: i32, r2: i32) { instr_660FEC(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FEC_mem(addr: i32, r: i32) {
-    instr_660FEC(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FEC_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FEC(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FED(source: u64, r: i32) {
@@ -4768,8 +4984,8 @@ pub unsafe fn instr_0FED(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FED_reg(r1: i32, r2: i32) { instr_0FED(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FED_mem(addr: i32, r: i32) {
-    instr_0FED(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FED_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FED(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FED(source: reg128, r: i32) {
@@ -4783,8 +4999,8 @@ pub unsafe fn instr_660FED(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FED_reg(r1: i32, r2: i32) { instr_660FED(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FED_mem(addr: i32, r: i32) {
-    instr_660FED(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FED_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FED(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FEE(source: u64, r: i32) {
@@ -4799,8 +5015,8 @@ pub unsafe fn instr_0FEE(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FEE_reg(r1: i32, r2: i32) { instr_0FEE(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FEE_mem(addr: i32, r: i32) {
-    instr_0FEE(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FEE_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FEE(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FEE(source: reg128, r: i32) {
@@ -4814,8 +5030,8 @@ pub unsafe fn instr_660FEE(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FEE_reg(r1: i32, r2: i32) { instr_660FEE(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FEE_mem(addr: i32, r: i32) {
-    instr_660FEE(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FEE_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FEE(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FEF(source: u64, r: i32) {
@@ -4825,8 +5041,8 @@ pub unsafe fn instr_0FEF(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FEF_reg(r1: i32, r2: i32) { instr_0FEF(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FEF_mem(addr: i32, r: i32) {
-    instr_0FEF(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FEF_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FEF(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FEF(source: reg128, r: i32) {
@@ -4835,8 +5051,8 @@ pub unsafe fn instr_660FEF(source: reg128, r: i32) {
     pxor_r128(source, r);
 }
 pub unsafe fn instr_660FEF_reg(r1: i32, r2: i32) { instr_660FEF(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FEF_mem(addr: i32, r: i32) {
-    instr_660FEF(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FEF_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FEF(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FF0() { unimplemented_sse(); }
@@ -4846,8 +5062,8 @@ pub unsafe fn instr_0FF1(source: u64, r: i32) {
     psllw_r64(r, source);
 }
 pub unsafe fn instr_0FF1_reg(r1: i32, r2: i32) { instr_0FF1(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FF1_mem(addr: i32, r: i32) {
-    instr_0FF1(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FF1_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FF1(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FF1(source: reg128, r: i32) {
@@ -4856,8 +5072,8 @@ pub unsafe fn instr_660FF1(source: reg128, r: i32) {
     psllw_r128(r, source.u64[0]);
 }
 pub unsafe fn instr_660FF1_reg(r1: i32, r2: i32) { instr_660FF1(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FF1_mem(addr: i32, r: i32) {
-    instr_660FF1(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FF1_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FF1(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FF2(source: u64, r: i32) {
@@ -4865,8 +5081,8 @@ pub unsafe fn instr_0FF2(source: u64, r: i32) {
     pslld_r64(r, source);
 }
 pub unsafe fn instr_0FF2_reg(r1: i32, r2: i32) { instr_0FF2(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FF2_mem(addr: i32, r: i32) {
-    instr_0FF2(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FF2_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FF2(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FF2(source: reg128, r: i32) {
@@ -4875,8 +5091,8 @@ pub unsafe fn instr_660FF2(source: reg128, r: i32) {
     pslld_r128(r, source.u64[0]);
 }
 pub unsafe fn instr_660FF2_reg(r1: i32, r2: i32) { instr_660FF2(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FF2_mem(addr: i32, r: i32) {
-    instr_660FF2(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FF2_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FF2(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FF3(source: u64, r: i32) {
@@ -4884,8 +5100,8 @@ pub unsafe fn instr_0FF3(source: u64, r: i32) {
     psllq_r64(r, source);
 }
 pub unsafe fn instr_0FF3_reg(r1: i32, r2: i32) { instr_0FF3(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FF3_mem(addr: i32, r: i32) {
-    instr_0FF3(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FF3_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FF3(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FF3(source: reg128, r: i32) {
@@ -4894,8 +5110,8 @@ pub unsafe fn instr_660FF3(source: reg128, r: i32) {
     psllq_r128(r, source.u64[0]);
 }
 pub unsafe fn instr_660FF3_reg(r1: i32, r2: i32) { instr_660FF3(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FF3_mem(addr: i32, r: i32) {
-    instr_660FF3(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FF3_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FF3(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FF4(source: u64, r: i32) {
@@ -4905,8 +5121,8 @@ pub unsafe fn instr_0FF4(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FF4_reg(r1: i32, r2: i32) { instr_0FF4(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FF4_mem(addr: i32, r: i32) {
-    instr_0FF4(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FF4_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FF4(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FF4(source: reg128, r: i32) {
@@ -4919,8 +5135,8 @@ pub unsafe fn instr_660FF4(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FF4_reg(r1: i32, r2: i32) { instr_660FF4(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FF4_mem(addr: i32, r: i32) {
-    instr_660FF4(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FF4_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FF4(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FF5(source: u64, r: i32) {
@@ -4937,8 +5153,8 @@ pub unsafe fn instr_0FF5(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FF5_reg(r1: i32, r2: i32) { instr_0FF5(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FF5_mem(addr: i32, r: i32) {
-    instr_0FF5(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FF5_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FF5(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FF5(source: reg128, r: i32) {
@@ -4953,8 +5169,8 @@ pub unsafe fn instr_660FF5(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FF5_reg(r1: i32, r2: i32) { instr_660FF5(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FF5_mem(addr: i32, r: i32) {
-    instr_660FF5(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FF5_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FF5(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FF6(source: u64, r: i32) {
@@ -4969,8 +5185,8 @@ pub unsafe fn instr_0FF6(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FF6_reg(r1: i32, r2: i32) { instr_0FF6(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FF6_mem(addr: i32, r: i32) {
-    instr_0FF6(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FF6_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FF6(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FF6(source: reg128, r: i32) {
@@ -4986,17 +5202,17 @@ pub unsafe fn instr_660FF6(source: reg128, r: i32) {
     write_xmm128(r, sum0 as i32, 0, sum1 as i32, 0);
 }
 pub unsafe fn instr_660FF6_reg(r1: i32, r2: i32) { instr_660FF6(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FF6_mem(addr: i32, r: i32) {
-    instr_660FF6(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FF6_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FF6(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 
-pub unsafe fn instr_0FF7_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_0FF7_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn maskmovq(r1: i32, r2: i32, addr: i32) {
+pub unsafe fn maskmovq(r1: i32, r2: i32, seg: i32, addr: i32) {
     // maskmovq mm, mm
     let source: [u8; 8] = std::mem::transmute(read_mmx64s(r2));
     let mask: [u8; 8] = std::mem::transmute(read_mmx64s(r1));
-    match writable_or_pagefault(addr, 8) {
+    match writable_or_pagefault_seg(seg, addr, 8) {
         Ok(()) => *page_fault = false,
         Err(()) => {
             *page_fault = true;
@@ -5005,26 +5221,23 @@ pub unsafe fn maskmovq(r1: i32, r2: i32, addr: i32) {
     }
     for i in 0..8 {
         if 0 != mask[i] & 0x80 {
-            safe_write8(addr + i as i32, source[i] as i32).unwrap();
+            safe_write8_seg(seg, addr + i as i32, source[i] as i32).unwrap();
         }
     }
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FF7_reg(r1: i32, r2: i32) {
-    maskmovq(
-        r1,
-        r2,
-        return_on_pagefault!(get_seg_prefix_ds(get_reg_asize(EDI))),
-    )
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(get_reg_asize(EDI)));
+    maskmovq(r1, r2, seg, addr)
 }
 
-pub unsafe fn instr_660FF7_mem(_addr: i32, _r: i32) { trigger_ud(); }
+pub unsafe fn instr_660FF7_mem(_seg: i32, _addr: i32, _r: i32) { trigger_ud(); }
 #[no_mangle]
-pub unsafe fn maskmovdqu(r1: i32, r2: i32, addr: i32) {
+pub unsafe fn maskmovdqu(r1: i32, r2: i32, seg: i32, addr: i32) {
     // maskmovdqu xmm, xmm
     let source = read_xmm128s(r2);
     let mask = read_xmm128s(r1);
-    match writable_or_pagefault(addr, 16) {
+    match writable_or_pagefault_seg(seg, addr, 16) {
         Ok(()) => *page_fault = false,
         Err(()) => {
             *page_fault = true;
@@ -5033,16 +5246,13 @@ pub unsafe fn maskmovdqu(r1: i32, r2: i32, addr: i32) {
     }
     for i in 0..16 {
         if 0 != mask.u8[i] & 0x80 {
-            safe_write8(addr + i as i32, source.u8[i] as i32).unwrap();
+            safe_write8_seg(seg, addr + i as i32, source.u8[i] as i32).unwrap();
         }
     }
 }
 pub unsafe fn instr_660FF7_reg(r1: i32, r2: i32) {
-    maskmovdqu(
-        r1,
-        r2,
-        return_on_pagefault!(get_seg_prefix_ds(get_reg_asize(EDI))),
-    )
+    let (seg, addr) = return_on_pagefault!(get_seg_prefix_ds(get_reg_asize(EDI)));
+    maskmovdqu(r1, r2, seg, addr)
 }
 #[no_mangle]
 pub unsafe fn instr_0FF8(source: u64, r: i32) {
@@ -5057,8 +5267,8 @@ pub unsafe fn instr_0FF8(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FF8_reg(r1: i32, r2: i32) { instr_0FF8(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FF8_mem(addr: i32, r: i32) {
-    instr_0FF8(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FF8_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FF8(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FF8(source: reg128, r: i32) {
@@ -5072,8 +5282,8 @@ pub unsafe fn instr_660FF8(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FF8_reg(r1: i32, r2: i32) { instr_660FF8(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FF8_mem(addr: i32, r: i32) {
-    instr_660FF8(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FF8_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FF8(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FF9(source: u64, r: i32) {
@@ -5088,8 +5298,8 @@ pub unsafe fn instr_0FF9(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FF9_reg(r1: i32, r2: i32) { instr_0FF9(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FF9_mem(addr: i32, r: i32) {
-    instr_0FF9(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FF9_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FF9(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FF9(source: reg128, r: i32) {
@@ -5103,8 +5313,8 @@ pub unsafe fn instr_660FF9(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FF9_reg(r1: i32, r2: i32) { instr_660FF9(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FF9_mem(addr: i32, r: i32) {
-    instr_660FF9(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FF9_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FF9(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FFA(source: u64, r: i32) {
@@ -5119,8 +5329,8 @@ pub unsafe fn instr_0FFA(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FFA_reg(r1: i32, r2: i32) { instr_0FFA(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FFA_mem(addr: i32, r: i32) {
-    instr_0FFA(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FFA_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FFA(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FFA(source: reg128, r: i32) {
@@ -5136,8 +5346,8 @@ pub unsafe fn instr_660FFA(source: reg128, r: i32) {
     );
 }
 pub unsafe fn instr_660FFA_reg(r1: i32, r2: i32) { instr_660FFA(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FFA_mem(addr: i32, r: i32) {
-    instr_660FFA(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FFA_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FFA(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FFB(source: u64, r: i32) {
@@ -5146,8 +5356,8 @@ pub unsafe fn instr_0FFB(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FFB_reg(r1: i32, r2: i32) { instr_0FFB(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FFB_mem(addr: i32, r: i32) {
-    instr_0FFB(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FFB_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FFB(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FFB(source: reg128, r: i32) {
@@ -5159,8 +5369,8 @@ pub unsafe fn instr_660FFB(source: reg128, r: i32) {
     write_xmm_reg128(r, destination);
 }
 pub unsafe fn instr_660FFB_reg(r1: i32, r2: i32) { instr_660FFB(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FFB_mem(addr: i32, r: i32) {
-    instr_660FFB(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FFB_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FFB(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FFC(source: u64, r: i32) {
@@ -5175,8 +5385,8 @@ pub unsafe fn instr_0FFC(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FFC_reg(r1: i32, r2: i32) { instr_0FFC(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FFC_mem(addr: i32, r: i32) {
-    instr_0FFC(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FFC_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FFC(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FFC(source: reg128, r: i32) {
@@ -5190,8 +5400,8 @@ pub unsafe fn instr_660FFC(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FFC_reg(r1: i32, r2: i32) { instr_660FFC(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FFC_mem(addr: i32, r: i32) {
-    instr_660FFC(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FFC_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FFC(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FFD(source: u64, r: i32) {
@@ -5206,8 +5416,8 @@ pub unsafe fn instr_0FFD(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FFD_reg(r1: i32, r2: i32) { instr_0FFD(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FFD_mem(addr: i32, r: i32) {
-    instr_0FFD(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FFD_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FFD(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FFD(source: reg128, r: i32) {
@@ -5221,8 +5431,8 @@ pub unsafe fn instr_660FFD(source: reg128, r: i32) {
     write_xmm_reg128(r, result);
 }
 pub unsafe fn instr_660FFD_reg(r1: i32, r2: i32) { instr_660FFD(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FFD_mem(addr: i32, r: i32) {
-    instr_660FFD(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FFD_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FFD(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FFE(source: u64, r: i32) {
@@ -5237,8 +5447,8 @@ pub unsafe fn instr_0FFE(source: u64, r: i32) {
     transition_fpu_to_mmx();
 }
 pub unsafe fn instr_0FFE_reg(r1: i32, r2: i32) { instr_0FFE(read_mmx64s(r1), r2); }
-pub unsafe fn instr_0FFE_mem(addr: i32, r: i32) {
-    instr_0FFE(return_on_pagefault!(safe_read64s(addr)), r);
+pub unsafe fn instr_0FFE_mem(seg: i32, addr: i32, r: i32) {
+    instr_0FFE(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_660FFE(source: reg128, r: i32) {
@@ -5252,8 +5462,8 @@ pub unsafe fn instr_660FFE(source: reg128, r: i32) {
     write_xmm128(r, dword0, dword1, dword2, dword3);
 }
 pub unsafe fn instr_660FFE_reg(r1: i32, r2: i32) { instr_660FFE(read_xmm128s(r1), r2); }
-pub unsafe fn instr_660FFE_mem(addr: i32, r: i32) {
-    instr_660FFE(return_on_pagefault!(safe_read128s(addr)), r);
+pub unsafe fn instr_660FFE_mem(seg: i32, addr: i32, r: i32) {
+    instr_660FFE(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
 }
 #[no_mangle]
 pub unsafe fn instr_0FFF() {
@@ -5261,3 +5471,1375 @@ pub unsafe fn instr_0FFF() {
     dbg_log!("#ud: 0F FF");
     trigger_ud();
 }
+
+#[no_mangle]
+pub unsafe fn instr_0F3800(source: u64, r: i32) {
+    // pshufb mm, mm/m64
+    let destination: [u8; 8] = std::mem::transmute(read_mmx64s(r));
+    let source: [u8; 8] = std::mem::transmute(source);
+    let mut result = [0; 8];
+    for i in 0..8 {
+        if source[i] & 0x80 == 0 {
+            result[i] = destination[(source[i] & 7) as usize];
+        }
+    }
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3800_reg(r1: i32, r2: i32) { instr_0F3800(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3800_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3800(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3800(source: reg128, r: i32) {
+    // pshufb xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..16 {
+        if source.u8[i] & 0x80 == 0 {
+            result.u8[i] = destination.u8[(source.u8[i] & 15) as usize];
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3800_reg(r1: i32, r2: i32) { instr_660F3800(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3800_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3800(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3801(source: u64, r: i32) {
+    // phaddw mm, mm/m64
+    let destination: [i16; 4] = std::mem::transmute(read_mmx64s(r));
+    let source: [i16; 4] = std::mem::transmute(source);
+    let result: [i16; 4] = [
+        destination[0].wrapping_add(destination[1]),
+        destination[2].wrapping_add(destination[3]),
+        source[0].wrapping_add(source[1]),
+        source[2].wrapping_add(source[3]),
+    ];
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3801_reg(r1: i32, r2: i32) { instr_0F3801(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3801_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3801(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3801(source: reg128, r: i32) {
+    // phaddw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i16[i] = destination.i16[2 * i].wrapping_add(destination.i16[2 * i + 1]);
+        result.i16[4 + i] = source.i16[2 * i].wrapping_add(source.i16[2 * i + 1]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3801_reg(r1: i32, r2: i32) { instr_660F3801(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3801_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3801(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3802(source: u64, r: i32) {
+    // phaddd mm, mm/m64
+    let destination: [i32; 2] = std::mem::transmute(read_mmx64s(r));
+    let source: [i32; 2] = std::mem::transmute(source);
+    let result: [i32; 2] = [
+        destination[0].wrapping_add(destination[1]),
+        source[0].wrapping_add(source[1]),
+    ];
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3802_reg(r1: i32, r2: i32) { instr_0F3802(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3802_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3802(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3802(source: reg128, r: i32) {
+    // phaddd xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    write_xmm128(
+        r,
+        destination.i32[0].wrapping_add(destination.i32[1]),
+        destination.i32[2].wrapping_add(destination.i32[3]),
+        source.i32[0].wrapping_add(source.i32[1]),
+        source.i32[2].wrapping_add(source.i32[3]),
+    );
+}
+pub unsafe fn instr_660F3802_reg(r1: i32, r2: i32) { instr_660F3802(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3802_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3802(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3803(source: u64, r: i32) {
+    // phaddsw mm, mm/m64
+    let destination: [i16; 4] = std::mem::transmute(read_mmx64s(r));
+    let source: [i16; 4] = std::mem::transmute(source);
+    let result: [i16; 4] = [
+        destination[0].saturating_add(destination[1]),
+        destination[2].saturating_add(destination[3]),
+        source[0].saturating_add(source[1]),
+        source[2].saturating_add(source[3]),
+    ];
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3803_reg(r1: i32, r2: i32) { instr_0F3803(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3803_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3803(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3803(source: reg128, r: i32) {
+    // phaddsw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i16[i] = destination.i16[2 * i].saturating_add(destination.i16[2 * i + 1]);
+        result.i16[4 + i] = source.i16[2 * i].saturating_add(source.i16[2 * i + 1]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3803_reg(r1: i32, r2: i32) { instr_660F3803(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3803_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3803(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3804(source: u64, r: i32) {
+    // pmaddubsw mm, mm/m64
+    let destination: [u8; 8] = std::mem::transmute(read_mmx64s(r));
+    let source: [i8; 8] = std::mem::transmute(source);
+    let mut result = [0; 4];
+    for i in 0..4 {
+        let low = destination[2 * i] as i32 * source[2 * i] as i32;
+        let high = destination[2 * i + 1] as i32 * source[2 * i + 1] as i32;
+        result[i] = saturate_sd_to_sw((low + high) as u32);
+    }
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3804_reg(r1: i32, r2: i32) { instr_0F3804(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3804_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3804(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3804(source: reg128, r: i32) {
+    // pmaddubsw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        let low = destination.u8[2 * i] as i32 * source.i8[2 * i] as i32;
+        let high = destination.u8[2 * i + 1] as i32 * source.i8[2 * i + 1] as i32;
+        result.u16[i] = saturate_sd_to_sw((low + high) as u32);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3804_reg(r1: i32, r2: i32) { instr_660F3804(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3804_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3804(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3805(source: u64, r: i32) {
+    // phsubw mm, mm/m64
+    let destination: [i16; 4] = std::mem::transmute(read_mmx64s(r));
+    let source: [i16; 4] = std::mem::transmute(source);
+    let result: [i16; 4] = [
+        destination[0].wrapping_sub(destination[1]),
+        destination[2].wrapping_sub(destination[3]),
+        source[0].wrapping_sub(source[1]),
+        source[2].wrapping_sub(source[3]),
+    ];
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3805_reg(r1: i32, r2: i32) { instr_0F3805(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3805_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3805(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3805(source: reg128, r: i32) {
+    // phsubw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i16[i] = destination.i16[2 * i].wrapping_sub(destination.i16[2 * i + 1]);
+        result.i16[4 + i] = source.i16[2 * i].wrapping_sub(source.i16[2 * i + 1]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3805_reg(r1: i32, r2: i32) { instr_660F3805(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3805_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3805(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3806(source: u64, r: i32) {
+    // phsubd mm, mm/m64
+    let destination: [i32; 2] = std::mem::transmute(read_mmx64s(r));
+    let source: [i32; 2] = std::mem::transmute(source);
+    let result: [i32; 2] = [
+        destination[0].wrapping_sub(destination[1]),
+        source[0].wrapping_sub(source[1]),
+    ];
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3806_reg(r1: i32, r2: i32) { instr_0F3806(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3806_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3806(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3806(source: reg128, r: i32) {
+    // phsubd xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    write_xmm128(
+        r,
+        destination.i32[0].wrapping_sub(destination.i32[1]),
+        destination.i32[2].wrapping_sub(destination.i32[3]),
+        source.i32[0].wrapping_sub(source.i32[1]),
+        source.i32[2].wrapping_sub(source.i32[3]),
+    );
+}
+pub unsafe fn instr_660F3806_reg(r1: i32, r2: i32) { instr_660F3806(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3806_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3806(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3807(source: u64, r: i32) {
+    // phsubsw mm, mm/m64
+    let destination: [i16; 4] = std::mem::transmute(read_mmx64s(r));
+    let source: [i16; 4] = std::mem::transmute(source);
+    let result: [i16; 4] = [
+        destination[0].saturating_sub(destination[1]),
+        destination[2].saturating_sub(destination[3]),
+        source[0].saturating_sub(source[1]),
+        source[2].saturating_sub(source[3]),
+    ];
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3807_reg(r1: i32, r2: i32) { instr_0F3807(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3807_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3807(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3807(source: reg128, r: i32) {
+    // phsubsw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i16[i] = destination.i16[2 * i].saturating_sub(destination.i16[2 * i + 1]);
+        result.i16[4 + i] = source.i16[2 * i].saturating_sub(source.i16[2 * i + 1]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3807_reg(r1: i32, r2: i32) { instr_660F3807(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3807_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3807(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3808(source: u64, r: i32) {
+    // psignb mm, mm/m64
+    let destination: [i8; 8] = std::mem::transmute(read_mmx64s(r));
+    let source: [i8; 8] = std::mem::transmute(source);
+    let mut result = [0; 8];
+    for i in 0..8 {
+        result[i] = destination[i].wrapping_mul(source[i].signum());
+    }
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3808_reg(r1: i32, r2: i32) { instr_0F3808(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3808_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3808(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3808(source: reg128, r: i32) {
+    // psignb xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..16 {
+        result.i8[i] = destination.i8[i].wrapping_mul(source.i8[i].signum());
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3808_reg(r1: i32, r2: i32) { instr_660F3808(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3808_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3808(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F3809(source: u64, r: i32) {
+    // psignw mm, mm/m64
+    let destination: [i16; 4] = std::mem::transmute(read_mmx64s(r));
+    let source: [i16; 4] = std::mem::transmute(source);
+    let mut result = [0; 4];
+    for i in 0..4 {
+        result[i] = destination[i].wrapping_mul(source[i].signum());
+    }
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3809_reg(r1: i32, r2: i32) { instr_0F3809(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F3809_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F3809(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3809(source: reg128, r: i32) {
+    // psignw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        result.i16[i] = destination.i16[i].wrapping_mul(source.i16[i].signum());
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3809_reg(r1: i32, r2: i32) { instr_660F3809(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3809_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3809(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F380A(source: u64, r: i32) {
+    // psignd mm, mm/m64
+    let destination: [i32; 2] = std::mem::transmute(read_mmx64s(r));
+    let source: [i32; 2] = std::mem::transmute(source);
+    let result: [i32; 2] = [
+        destination[0].wrapping_mul(source[0].signum()),
+        destination[1].wrapping_mul(source[1].signum()),
+    ];
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F380A_reg(r1: i32, r2: i32) { instr_0F380A(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F380A_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F380A(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F380A(source: reg128, r: i32) {
+    // psignd xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    write_xmm128(
+        r,
+        destination.i32[0].wrapping_mul(source.i32[0].signum()),
+        destination.i32[1].wrapping_mul(source.i32[1].signum()),
+        destination.i32[2].wrapping_mul(source.i32[2].signum()),
+        destination.i32[3].wrapping_mul(source.i32[3].signum()),
+    );
+}
+pub unsafe fn instr_660F380A_reg(r1: i32, r2: i32) { instr_660F380A(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F380A_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F380A(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F380B(source: u64, r: i32) {
+    // pmulhrsw mm, mm/m64
+    let destination: [i16; 4] = std::mem::transmute(read_mmx64s(r));
+    let source: [i16; 4] = std::mem::transmute(source);
+    let mut result = [0; 4];
+    for i in 0..4 {
+        result[i] = ((destination[i] as i32 * source[i] as i32 >> 14) + 1 >> 1) as i16;
+    }
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F380B_reg(r1: i32, r2: i32) { instr_0F380B(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F380B_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F380B(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F380B(source: reg128, r: i32) {
+    // pmulhrsw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        result.i16[i] =
+            ((destination.i16[i] as i32 * source.i16[i] as i32 >> 14) + 1 >> 1) as i16;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F380B_reg(r1: i32, r2: i32) { instr_660F380B(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F380B_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F380B(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F381C(source: u64, r: i32) {
+    // pabsb mm, mm/m64
+    let source: [i8; 8] = std::mem::transmute(source);
+    let mut result = [0; 8];
+    for i in 0..8 {
+        result[i] = source[i].wrapping_abs();
+    }
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F381C_reg(r1: i32, r2: i32) { instr_0F381C(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F381C_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F381C(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F381C(source: reg128, r: i32) {
+    // pabsb xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..16 {
+        result.i8[i] = source.i8[i].wrapping_abs();
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F381C_reg(r1: i32, r2: i32) { instr_660F381C(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F381C_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F381C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F381D(source: u64, r: i32) {
+    // pabsw mm, mm/m64
+    let source: [i16; 4] = std::mem::transmute(source);
+    let mut result = [0; 4];
+    for i in 0..4 {
+        result[i] = source[i].wrapping_abs();
+    }
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F381D_reg(r1: i32, r2: i32) { instr_0F381D(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F381D_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F381D(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F381D(source: reg128, r: i32) {
+    // pabsw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        result.i16[i] = source.i16[i].wrapping_abs();
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F381D_reg(r1: i32, r2: i32) { instr_660F381D(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F381D_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F381D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_0F381E(source: u64, r: i32) {
+    // pabsd mm, mm/m64
+    let source: [i32; 2] = std::mem::transmute(source);
+    let result: [i32; 2] = [source[0].wrapping_abs(), source[1].wrapping_abs()];
+    write_mmx_reg64(r, std::mem::transmute(result));
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F381E_reg(r1: i32, r2: i32) { instr_0F381E(read_mmx64s(r1), r2); }
+pub unsafe fn instr_0F381E_mem(seg: i32, addr: i32, r: i32) {
+    instr_0F381E(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F381E(source: reg128, r: i32) {
+    // pabsd xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    write_xmm128(
+        r,
+        source.i32[0].wrapping_abs(),
+        source.i32[1].wrapping_abs(),
+        source.i32[2].wrapping_abs(),
+        source.i32[3].wrapping_abs(),
+    );
+}
+pub unsafe fn instr_660F381E_reg(r1: i32, r2: i32) { instr_660F381E(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F381E_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F381E(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+
+#[no_mangle]
+pub unsafe fn instr_0F3A0F(source: u64, r: i32, imm8: i32) {
+    // palignr mm, mm/m64, imm8
+    let destination = read_mmx64s(r);
+    let shift = (imm8 & 0xFF) as u32 * 8;
+    let result = if shift == 0 {
+        source
+    }
+    else if shift < 64 {
+        source >> shift | destination << (64 - shift)
+    }
+    else if shift < 128 {
+        destination >> (shift - 64)
+    }
+    else {
+        0
+    };
+    write_mmx_reg64(r, result);
+    transition_fpu_to_mmx();
+}
+pub unsafe fn instr_0F3A0F_reg(r1: i32, r2: i32, imm: i32) {
+    instr_0F3A0F(read_mmx64s(r1), r2, imm);
+}
+pub unsafe fn instr_0F3A0F_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_0F3A0F(return_on_pagefault!(safe_read64s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A0F(source: reg128, r: i32, imm8: i32) {
+    // palignr xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let shift = (imm8 & 0xFF) as usize;
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..16 {
+        let index = i + shift;
+        result.u8[i] = if index < 16 {
+            source.u8[index]
+        }
+        else if index < 32 {
+            destination.u8[index - 16]
+        }
+        else {
+            0
+        };
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A0F_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A0F(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A0F_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A0F(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3810(source: reg128, r: i32) {
+    // pblendvb xmm, xmm/m128, <xmm0>
+    // XXX: Aligned access or #gp
+    let mask = read_xmm128s(0);
+    let mut result = read_xmm128s(r);
+    for i in 0..16 {
+        if mask.i8[i] < 0 {
+            result.u8[i] = source.u8[i];
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3810_reg(r1: i32, r2: i32) { instr_660F3810(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3810_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3810(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3814(source: reg128, r: i32) {
+    // blendvps xmm, xmm/m128, <xmm0>
+    // XXX: Aligned access or #gp
+    let mask = read_xmm128s(0);
+    let mut result = read_xmm128s(r);
+    for i in 0..4 {
+        if mask.i32[i] < 0 {
+            result.u32[i] = source.u32[i];
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3814_reg(r1: i32, r2: i32) { instr_660F3814(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3814_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3814(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3815(source: reg128, r: i32) {
+    // blendvpd xmm, xmm/m128, <xmm0>
+    // XXX: Aligned access or #gp
+    let mask = read_xmm128s(0);
+    let mut result = read_xmm128s(r);
+    for i in 0..2 {
+        if mask.i64[i] < 0 {
+            result.u64[i] = source.u64[i];
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3815_reg(r1: i32, r2: i32) { instr_660F3815(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3815_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3815(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3817(source: reg128, r: i32) {
+    // ptest xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    *flags_changed = 0;
+    *flags &= !FLAGS_ALL;
+    if source.u64[0] & destination.u64[0] == 0 && source.u64[1] & destination.u64[1] == 0 {
+        *flags |= FLAG_ZERO
+    }
+    if source.u64[0] & !destination.u64[0] == 0 && source.u64[1] & !destination.u64[1] == 0 {
+        *flags |= FLAG_CARRY
+    }
+}
+pub unsafe fn instr_660F3817_reg(r1: i32, r2: i32) { instr_660F3817(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3817_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3817(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3820(source: u64, r: i32) {
+    // pmovsxbw xmm, xmm/m64
+    let source: [i8; 8] = std::mem::transmute(source);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        result.i16[i] = source[i] as i16;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3820_reg(r1: i32, r2: i32) { instr_660F3820(read_xmm64s(r1), r2); }
+pub unsafe fn instr_660F3820_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3820(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3821(source: i32, r: i32) {
+    // pmovsxbd xmm, xmm/m32
+    let source: [i8; 4] = std::mem::transmute(source);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i32[i] = source[i] as i32;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3821_reg(r1: i32, r2: i32) { instr_660F3821(read_xmm32(r1), r2); }
+pub unsafe fn instr_660F3821_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3821(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3822(source: i32, r: i32) {
+    // pmovsxbq xmm, xmm/m16
+    let mut result = reg128 { i8: [0; 16] };
+    result.i64[0] = source as i8 as i64;
+    result.i64[1] = (source >> 8) as i8 as i64;
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3822_reg(r1: i32, r2: i32) { instr_660F3822(read_xmm32(r1), r2); }
+pub unsafe fn instr_660F3822_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3822(return_on_pagefault!(safe_read16_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3823(source: u64, r: i32) {
+    // pmovsxwd xmm, xmm/m64
+    let source: [i16; 4] = std::mem::transmute(source);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i32[i] = source[i] as i32;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3823_reg(r1: i32, r2: i32) { instr_660F3823(read_xmm64s(r1), r2); }
+pub unsafe fn instr_660F3823_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3823(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3824(source: i32, r: i32) {
+    // pmovsxwq xmm, xmm/m32
+    let mut result = reg128 { i8: [0; 16] };
+    result.i64[0] = source as i16 as i64;
+    result.i64[1] = (source >> 16) as i16 as i64;
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3824_reg(r1: i32, r2: i32) { instr_660F3824(read_xmm32(r1), r2); }
+pub unsafe fn instr_660F3824_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3824(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3825(source: u64, r: i32) {
+    // pmovsxdq xmm, xmm/m64
+    let mut result = reg128 { i8: [0; 16] };
+    result.i64[0] = source as i32 as i64;
+    result.i64[1] = (source >> 32) as i32 as i64;
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3825_reg(r1: i32, r2: i32) { instr_660F3825(read_xmm64s(r1), r2); }
+pub unsafe fn instr_660F3825_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3825(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3828(source: reg128, r: i32) {
+    // pmuldq xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    result.i64[0] = destination.i32[0] as i64 * source.i32[0] as i64;
+    result.i64[1] = destination.i32[2] as i64 * source.i32[2] as i64;
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3828_reg(r1: i32, r2: i32) { instr_660F3828(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3828_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3828(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3829(source: reg128, r: i32) {
+    // pcmpeqq xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..2 {
+        result.u64[i] = if destination.u64[i] == source.u64[i] { !0 } else { 0 };
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3829_reg(r1: i32, r2: i32) { instr_660F3829(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3829_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3829(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+pub unsafe fn instr_660F382A_reg(_r1: i32, _r2: i32) { trigger_ud(); }
+pub unsafe fn instr_660F382A_mem(seg: i32, addr: i32, r: i32) {
+    // movntdqa xmm, m128
+    // XXX: Aligned access or #gp
+    instr_660F6F_mem(seg, addr, r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F382B(source: reg128, r: i32) {
+    // packusdw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.u16[i] = saturate_uw(destination.u32[i]);
+        result.u16[i + 4] = saturate_uw(source.u32[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F382B_reg(r1: i32, r2: i32) { instr_660F382B(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F382B_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F382B(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3830(source: u64, r: i32) {
+    // pmovzxbw xmm, xmm/m64
+    let source: [u8; 8] = std::mem::transmute(source);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        result.u16[i] = source[i] as u16;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3830_reg(r1: i32, r2: i32) { instr_660F3830(read_xmm64s(r1), r2); }
+pub unsafe fn instr_660F3830_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3830(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3831(source: i32, r: i32) {
+    // pmovzxbd xmm, xmm/m32
+    let source: [u8; 4] = std::mem::transmute(source);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.u32[i] = source[i] as u32;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3831_reg(r1: i32, r2: i32) { instr_660F3831(read_xmm32(r1), r2); }
+pub unsafe fn instr_660F3831_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3831(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3832(source: i32, r: i32) {
+    // pmovzxbq xmm, xmm/m16
+    let mut result = reg128 { i8: [0; 16] };
+    result.u64[0] = (source & 0xFF) as u64;
+    result.u64[1] = (source >> 8 & 0xFF) as u64;
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3832_reg(r1: i32, r2: i32) { instr_660F3832(read_xmm32(r1), r2); }
+pub unsafe fn instr_660F3832_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3832(return_on_pagefault!(safe_read16_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3833(source: u64, r: i32) {
+    // pmovzxwd xmm, xmm/m64
+    let source: [u16; 4] = std::mem::transmute(source);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.u32[i] = source[i] as u32;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3833_reg(r1: i32, r2: i32) { instr_660F3833(read_xmm64s(r1), r2); }
+pub unsafe fn instr_660F3833_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3833(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3834(source: i32, r: i32) {
+    // pmovzxwq xmm, xmm/m32
+    let mut result = reg128 { i8: [0; 16] };
+    result.u64[0] = (source & 0xFFFF) as u64;
+    result.u64[1] = (source >> 16 & 0xFFFF) as u64;
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3834_reg(r1: i32, r2: i32) { instr_660F3834(read_xmm32(r1), r2); }
+pub unsafe fn instr_660F3834_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3834(return_on_pagefault!(safe_read32s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3835(source: u64, r: i32) {
+    // pmovzxdq xmm, xmm/m64
+    let mut result = reg128 { i8: [0; 16] };
+    result.u64[0] = source & 0xFFFF_FFFF;
+    result.u64[1] = source >> 32;
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3835_reg(r1: i32, r2: i32) { instr_660F3835(read_xmm64s(r1), r2); }
+pub unsafe fn instr_660F3835_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3835(return_on_pagefault!(safe_read64s_seg(seg, addr)), r);
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3838(source: reg128, r: i32) {
+    // pminsb xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..16 {
+        result.i8[i] = i8::min(destination.i8[i], source.i8[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3838_reg(r1: i32, r2: i32) { instr_660F3838(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3838_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3838(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3839(source: reg128, r: i32) {
+    // pminsd xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i32[i] = i32::min(destination.i32[i], source.i32[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3839_reg(r1: i32, r2: i32) { instr_660F3839(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3839_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3839(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F383A(source: reg128, r: i32) {
+    // pminuw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        result.u16[i] = u16::min(destination.u16[i], source.u16[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F383A_reg(r1: i32, r2: i32) { instr_660F383A(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F383A_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F383A(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F383B(source: reg128, r: i32) {
+    // pminud xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.u32[i] = u32::min(destination.u32[i], source.u32[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F383B_reg(r1: i32, r2: i32) { instr_660F383B(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F383B_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F383B(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F383C(source: reg128, r: i32) {
+    // pmaxsb xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..16 {
+        result.i8[i] = i8::max(destination.i8[i], source.i8[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F383C_reg(r1: i32, r2: i32) { instr_660F383C(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F383C_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F383C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F383D(source: reg128, r: i32) {
+    // pmaxsd xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i32[i] = i32::max(destination.i32[i], source.i32[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F383D_reg(r1: i32, r2: i32) { instr_660F383D(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F383D_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F383D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F383E(source: reg128, r: i32) {
+    // pmaxuw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        result.u16[i] = u16::max(destination.u16[i], source.u16[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F383E_reg(r1: i32, r2: i32) { instr_660F383E(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F383E_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F383E(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F383F(source: reg128, r: i32) {
+    // pmaxud xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.u32[i] = u32::max(destination.u32[i], source.u32[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F383F_reg(r1: i32, r2: i32) { instr_660F383F(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F383F_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F383F(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3840(source: reg128, r: i32) {
+    // pmulld xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.i32[i] = destination.i32[i].wrapping_mul(source.i32[i]);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3840_reg(r1: i32, r2: i32) { instr_660F3840(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3840_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3840(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3841(source: reg128, r: i32) {
+    // phminposuw xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let mut index = 0;
+    for i in 1..8 {
+        if source.u16[i] < source.u16[index] {
+            index = i;
+        }
+    }
+    let mut result = reg128 { i8: [0; 16] };
+    result.u16[0] = source.u16[index];
+    result.u16[1] = index as u16;
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3841_reg(r1: i32, r2: i32) { instr_660F3841(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3841_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3841(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3A08(source: reg128, r: i32, imm8: i32) {
+    // roundps xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        result.f32[i] = sse_round(source.f32[i] as f64, imm8) as f32;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A08_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A08(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A08_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A08(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A09(source: reg128, r: i32, imm8: i32) {
+    // roundpd xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..2 {
+        result.f64[i] = sse_round(source.f64[i], imm8);
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A09_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A09(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A09_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A09(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A0A(source: i32, r: i32, imm8: i32) {
+    // roundss xmm, xmm/m32, imm8
+    let source = f32::from_bits(source as u32);
+    write_xmm_f32(r, sse_round(source as f64, imm8) as f32);
+}
+pub unsafe fn instr_660F3A0A_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A0A(read_xmm32(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A0A_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A0A(return_on_pagefault!(safe_read32s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A0B(source: u64, r: i32, imm8: i32) {
+    // roundsd xmm, xmm/m64, imm8
+    let source = f64::from_bits(source);
+    write_xmm_f64(r, sse_round(source, imm8));
+}
+pub unsafe fn instr_660F3A0B_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A0B(read_xmm64s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A0B_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A0B(return_on_pagefault!(safe_read64s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A0C(source: reg128, r: i32, imm8: i32) {
+    // blendps xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let mut result = read_xmm128s(r);
+    for i in 0..4 {
+        if imm8 & 1 << i != 0 {
+            result.u32[i] = source.u32[i];
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A0C_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A0C(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A0C_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A0C(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A0D(source: reg128, r: i32, imm8: i32) {
+    // blendpd xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let mut result = read_xmm128s(r);
+    for i in 0..2 {
+        if imm8 & 1 << i != 0 {
+            result.u64[i] = source.u64[i];
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A0D_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A0D(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A0D_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A0D(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A0E(source: reg128, r: i32, imm8: i32) {
+    // pblendw xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let mut result = read_xmm128s(r);
+    for i in 0..8 {
+        if imm8 & 1 << i != 0 {
+            result.u16[i] = source.u16[i];
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A0E_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A0E(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A0E_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A0E(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+
+pub unsafe fn instr_660F3A14_reg(r1: i32, r2: i32, imm8: i32) {
+    // pextrb r32, xmm, imm8
+    let data = read_xmm128s(r2);
+    write_reg32(r1, data.u8[(imm8 & 15) as usize] as i32);
+}
+pub unsafe fn instr_660F3A14_mem(seg: i32, addr: i32, r: i32, imm8: i32) {
+    // pextrb m8, xmm, imm8
+    let data = read_xmm128s(r);
+    return_on_pagefault!(safe_write8_seg(seg, addr, data.u8[(imm8 & 15) as usize] as i32));
+}
+pub unsafe fn instr_660F3A15_reg(r1: i32, r2: i32, imm8: i32) {
+    // pextrw r32, xmm, imm8
+    let data = read_xmm128s(r2);
+    write_reg32(r1, data.u16[(imm8 & 7) as usize] as i32);
+}
+pub unsafe fn instr_660F3A15_mem(seg: i32, addr: i32, r: i32, imm8: i32) {
+    // pextrw m16, xmm, imm8
+    let data = read_xmm128s(r);
+    return_on_pagefault!(safe_write16_seg(seg, addr, data.u16[(imm8 & 7) as usize] as i32));
+}
+pub unsafe fn instr_660F3A16_reg(r1: i32, r2: i32, imm8: i32) {
+    // pextrd r32, xmm, imm8
+    let data = read_xmm128s(r2);
+    write_reg32(r1, data.i32[(imm8 & 3) as usize]);
+}
+pub unsafe fn instr_660F3A16_mem(seg: i32, addr: i32, r: i32, imm8: i32) {
+    // pextrd m32, xmm, imm8
+    let data = read_xmm128s(r);
+    return_on_pagefault!(safe_write32_seg(seg, addr, data.i32[(imm8 & 3) as usize]));
+}
+pub unsafe fn instr_660F3A17_reg(r1: i32, r2: i32, imm8: i32) {
+    // extractps r32, xmm, imm8
+    instr_660F3A16_reg(r1, r2, imm8);
+}
+pub unsafe fn instr_660F3A17_mem(seg: i32, addr: i32, r: i32, imm8: i32) {
+    // extractps m32, xmm, imm8
+    instr_660F3A16_mem(seg, addr, r, imm8);
+}
+
+pub unsafe fn instr_660F3A20(source: i32, r: i32, imm8: i32) {
+    // pinsrb xmm, r32/m8, imm8
+    let mut destination = read_xmm128s(r);
+    destination.u8[(imm8 & 15) as usize] = (source & 0xFF) as u8;
+    write_xmm_reg128(r, destination);
+}
+pub unsafe fn instr_660F3A20_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A20(read_reg32(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A20_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A20(return_on_pagefault!(safe_read8_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A21(source: i32, r: i32, imm8: i32) {
+    // insertps xmm, xmm/m32, imm8
+    let mut destination = read_xmm128s(r);
+    destination.i32[(imm8 >> 4 & 3) as usize] = source;
+    for i in 0..4 {
+        if imm8 & 1 << i != 0 {
+            destination.i32[i] = 0;
+        }
+    }
+    write_xmm_reg128(r, destination);
+}
+pub unsafe fn instr_660F3A21_reg(r1: i32, r2: i32, imm: i32) {
+    // the source element is selected by bits 6-7 of imm8, and only for register operands
+    instr_660F3A21(read_xmm128s(r1).i32[(imm >> 6 & 3) as usize], r2, imm);
+}
+pub unsafe fn instr_660F3A21_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A21(return_on_pagefault!(safe_read32s_seg(seg, addr)), r, imm);
+}
+pub unsafe fn instr_660F3A22(source: i32, r: i32, imm8: i32) {
+    // pinsrd xmm, r/m32, imm8
+    let mut destination = read_xmm128s(r);
+    destination.i32[(imm8 & 3) as usize] = source;
+    write_xmm_reg128(r, destination);
+}
+pub unsafe fn instr_660F3A22_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A22(read_reg32(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A22_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A22(return_on_pagefault!(safe_read32s_seg(seg, addr)), r, imm);
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3A40(source: reg128, r: i32, imm8: i32) {
+    // dpps xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut product = [0.0f32; 4];
+    for i in 0..4 {
+        if imm8 & 0x10 << i != 0 {
+            product[i] = destination.f32[i] * source.f32[i];
+        }
+    }
+    let sum = (product[0] + product[1]) + (product[2] + product[3]);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..4 {
+        if imm8 & 1 << i != 0 {
+            result.f32[i] = sum;
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A40_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A40(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A40_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A40(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A41(source: reg128, r: i32, imm8: i32) {
+    // dppd xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut product = [0.0f64; 2];
+    for i in 0..2 {
+        if imm8 & 0x10 << i != 0 {
+            product[i] = destination.f64[i] * source.f64[i];
+        }
+    }
+    let sum = product[0] + product[1];
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..2 {
+        if imm8 & 1 << i != 0 {
+            result.f64[i] = sum;
+        }
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A41_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A41(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A41_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A41(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A42(source: reg128, r: i32, imm8: i32) {
+    // mpsadbw xmm, xmm/m128, imm8
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let destination_offset = (imm8 >> 2 & 1) as usize * 4;
+    let source_offset = (imm8 & 3) as usize * 4;
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..8 {
+        let mut sum = 0;
+        for j in 0..4 {
+            let a = destination.u8[destination_offset + i + j] as i32;
+            let b = source.u8[source_offset + j] as i32;
+            sum += (a - b).abs();
+        }
+        result.u16[i] = sum as u16;
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3A42_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A42(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A42_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A42(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3837(source: reg128, r: i32) {
+    // pcmpgtq xmm, xmm/m128
+    // XXX: Aligned access or #gp
+    let destination = read_xmm128s(r);
+    let mut result = reg128 { i8: [0; 16] };
+    for i in 0..2 {
+        result.u64[i] = if destination.i64[i] > source.i64[i] { !0 } else { 0 };
+    }
+    write_xmm_reg128(r, result);
+}
+pub unsafe fn instr_660F3837_reg(r1: i32, r2: i32) { instr_660F3837(read_xmm128s(r1), r2); }
+pub unsafe fn instr_660F3837_mem(seg: i32, addr: i32, r: i32) {
+    instr_660F3837(return_on_pagefault!(safe_read128s_seg(seg, addr)), r);
+}
+
+pub unsafe fn instr16_0F38F0_reg(_r1: i32, _r2: i32) { trigger_ud(); }
+pub unsafe fn instr32_0F38F0_reg(_r1: i32, _r2: i32) { trigger_ud(); }
+pub unsafe fn instr16_0F38F1_reg(_r1: i32, _r2: i32) { trigger_ud(); }
+pub unsafe fn instr32_0F38F1_reg(_r1: i32, _r2: i32) { trigger_ud(); }
+pub unsafe fn instr16_0F38F0_mem(seg: i32, addr: i32, r: i32) {
+    // movbe r16, m16
+    write_reg16(r, (return_on_pagefault!(safe_read16_seg(seg, addr)) as u16).swap_bytes() as i32);
+}
+pub unsafe fn instr32_0F38F0_mem(seg: i32, addr: i32, r: i32) {
+    // movbe r32, m32
+    write_reg32(r, return_on_pagefault!(safe_read32s_seg(seg, addr)).swap_bytes());
+}
+pub unsafe fn instr16_0F38F1_mem(seg: i32, addr: i32, r: i32) {
+    // movbe m16, r16
+    return_on_pagefault!(safe_write16_seg(seg, addr, (read_reg16(r) as u16).swap_bytes() as i32));
+}
+pub unsafe fn instr32_0F38F1_mem(seg: i32, addr: i32, r: i32) {
+    // movbe m32, r32
+    return_on_pagefault!(safe_write32_seg(seg, addr, read_reg32(r).swap_bytes()));
+}
+
+pub unsafe fn instr_F20F38F0_mem(seg: i32, addr: i32, r: i32) {
+    // crc32 r32, r/m8
+    let source = return_on_pagefault!(safe_read8_seg(seg, addr));
+    write_reg32(r, crc32(read_reg32(r), source, 1));
+}
+pub unsafe fn instr_F20F38F0_reg(r1: i32, r: i32) {
+    write_reg32(r, crc32(read_reg32(r), read_reg8(r1), 1));
+}
+pub unsafe fn instr16_F20F38F1_mem(seg: i32, addr: i32, r: i32) {
+    // crc32 r32, r/m16
+    let source = return_on_pagefault!(safe_read16_seg(seg, addr));
+    write_reg32(r, crc32(read_reg32(r), source, 2));
+}
+pub unsafe fn instr16_F20F38F1_reg(r1: i32, r: i32) {
+    write_reg32(r, crc32(read_reg32(r), read_reg16(r1), 2));
+}
+pub unsafe fn instr32_F20F38F1_mem(seg: i32, addr: i32, r: i32) {
+    // crc32 r32, r/m32
+    let source = return_on_pagefault!(safe_read32s_seg(seg, addr));
+    write_reg32(r, crc32(read_reg32(r), source, 4));
+}
+pub unsafe fn instr32_F20F38F1_reg(r1: i32, r: i32) {
+    write_reg32(r, crc32(read_reg32(r), read_reg32(r1), 4));
+}
+
+#[no_mangle]
+pub unsafe fn instr_660F3A60(source: reg128, r: i32, imm8: i32) {
+    // pcmpestrm xmm, xmm/m128, imm8
+    let length_a = sse_string_explicit_length(read_reg32(EAX), imm8);
+    let length_b = sse_string_explicit_length(read_reg32(EDX), imm8);
+    let result = sse_string_compare(read_xmm128s(r), length_a, source, length_b, imm8);
+    sse_string_set_flags(result, length_a, length_b, imm8);
+    write_xmm_reg128(0, sse_string_mask(result, imm8));
+}
+pub unsafe fn instr_660F3A60_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A60(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A60_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A60(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A61(source: reg128, r: i32, imm8: i32) {
+    // pcmpestri xmm, xmm/m128, imm8
+    let length_a = sse_string_explicit_length(read_reg32(EAX), imm8);
+    let length_b = sse_string_explicit_length(read_reg32(EDX), imm8);
+    let result = sse_string_compare(read_xmm128s(r), length_a, source, length_b, imm8);
+    sse_string_set_flags(result, length_a, length_b, imm8);
+    write_reg32(ECX, sse_string_index(result, imm8));
+}
+pub unsafe fn instr_660F3A61_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A61(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A61_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A61(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A62(source: reg128, r: i32, imm8: i32) {
+    // pcmpistrm xmm, xmm/m128, imm8
+    let destination = read_xmm128s(r);
+    let length_a = sse_string_implicit_length(destination, imm8);
+    let length_b = sse_string_implicit_length(source, imm8);
+    let result = sse_string_compare(destination, length_a, source, length_b, imm8);
+    sse_string_set_flags(result, length_a, length_b, imm8);
+    write_xmm_reg128(0, sse_string_mask(result, imm8));
+}
+pub unsafe fn instr_660F3A62_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A62(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A62_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A62(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
+#[no_mangle]
+pub unsafe fn instr_660F3A63(source: reg128, r: i32, imm8: i32) {
+    // pcmpistri xmm, xmm/m128, imm8
+    let destination = read_xmm128s(r);
+    let length_a = sse_string_implicit_length(destination, imm8);
+    let length_b = sse_string_implicit_length(source, imm8);
+    let result = sse_string_compare(destination, length_a, source, length_b, imm8);
+    sse_string_set_flags(result, length_a, length_b, imm8);
+    write_reg32(ECX, sse_string_index(result, imm8));
+}
+pub unsafe fn instr_660F3A63_reg(r1: i32, r2: i32, imm: i32) {
+    instr_660F3A63(read_xmm128s(r1), r2, imm);
+}
+pub unsafe fn instr_660F3A63_mem(seg: i32, addr: i32, r: i32, imm: i32) {
+    instr_660F3A63(return_on_pagefault!(safe_read128s_seg(seg, addr)), r, imm);
+}
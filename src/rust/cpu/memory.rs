@@ -14,12 +14,25 @@ mod ext {
 
 use cpu::cpu::reg128;
 use cpu::global_pointers::memory_size;
+use cpu::replay;
 use cpu::vga;
 use page::Page;
 
 use std::alloc;
 use std::ptr;
 
+unsafe fn mmap_read8(addr: u32) -> i32 {
+    replay::input(replay::Event::MmioRead8, addr, || ext::mmap_read8(addr) as u64) as i32
+}
+unsafe fn mmap_read16(addr: u32) -> i32 {
+    replay::input(replay::Event::MmioRead16, addr, || ext::mmap_read16(addr) as u64) as i32
+}
+unsafe fn mmap_read32(addr: u32) -> i32 {
+    replay::input(replay::Event::MmioRead32, addr, || {
+        ext::mmap_read32(addr) as u32 as u64
+    }) as i32
+}
+
 #[allow(non_upper_case_globals)]
 pub static mut mem8: *mut u8 = ptr::null_mut();
 
@@ -81,7 +94,7 @@ pub fn read8(addr: u32) -> i32 {
             unsafe { *vga_mem8.offset((addr - VGA_LFB_ADDRESS) as isize) as i32 }
         }
         else {
-            unsafe { ext::mmap_read8(addr) }
+            unsafe { mmap_read8(addr) }
         }
     }
     else {
@@ -100,7 +113,7 @@ pub fn read16(addr: u32) -> i32 {
             }
         }
         else {
-            unsafe { ext::mmap_read16(addr) }
+            unsafe { mmap_read16(addr) }
         }
     }
     else {
@@ -120,7 +133,7 @@ pub fn read32s(addr: u32) -> i32 {
             } // XXX
         }
         else {
-            unsafe { ext::mmap_read32(addr) }
+            unsafe { mmap_read32(addr) }
         }
     }
     else {
@@ -137,7 +150,7 @@ pub unsafe fn read64s(addr: u32) -> i64 {
             ptr::read_unaligned(vga_mem8.offset((addr - VGA_LFB_ADDRESS) as isize) as *const i64)
         }
         else {
-            ext::mmap_read32(addr) as i64 | (ext::mmap_read32(addr + 4) as i64) << 32
+            mmap_read32(addr) as i64 | (mmap_read32(addr + 4) as i64) << 32
         }
     }
     else {
@@ -153,10 +166,10 @@ pub unsafe fn read128(addr: u32) -> reg128 {
         else {
             reg128 {
                 i32: [
-                    ext::mmap_read32(addr + 0),
-                    ext::mmap_read32(addr + 4),
-                    ext::mmap_read32(addr + 8),
-                    ext::mmap_read32(addr + 12),
+                    mmap_read32(addr + 0),
+                    mmap_read32(addr + 4),
+                    mmap_read32(addr + 8),
+                    mmap_read32(addr + 12),
                 ],
             }
         }
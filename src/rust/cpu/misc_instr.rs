@@ -1,9 +1,12 @@
 use cpu::cpu::*;
+use cpu::cpuid;
 use cpu::fpu::{
-    fpu_load_m80, fpu_load_status_word, fpu_set_status_word, fpu_store_m80, set_control_word,
+    fpu_finit, fpu_load_m80, fpu_load_status_word, fpu_set_status_word, fpu_store_m80,
+    set_control_word,
 };
 use cpu::global_pointers::*;
 use paging::OrPageFault;
+use softfloat::F80;
 
 pub unsafe fn getcf() -> bool {
     if 0 != *flags_changed & 1 {
@@ -149,19 +152,23 @@ pub unsafe fn adjust_stack_reg(adjustment: i32) {
 
 pub unsafe fn push16_ss16(imm16: i32) -> OrPageFault<()> {
     let sp = get_seg_ss() + (read_reg16(SP) - 2 & 0xFFFF);
-    safe_write16(sp, imm16)?;
+    safe_write16_seg(SS, sp, imm16)?;
     write_reg16(SP, read_reg16(SP) - 2);
     Ok(())
 }
 pub unsafe fn push16_ss32(imm16: i32) -> OrPageFault<()> {
     let sp = get_seg_ss() + read_reg32(ESP) - 2;
-    safe_write16(sp, imm16)?;
+    safe_write16_seg(SS, sp, imm16)?;
     write_reg32(ESP, read_reg32(ESP) - 2);
     Ok(())
 }
 
-pub unsafe fn push16_ss16_mem(addr: i32) -> OrPageFault<()> { push16_ss16(safe_read16(addr)?) }
-pub unsafe fn push16_ss32_mem(addr: i32) -> OrPageFault<()> { push16_ss32(safe_read16(addr)?) }
+pub unsafe fn push16_ss16_mem(seg: i32, addr: i32) -> OrPageFault<()> {
+    push16_ss16(safe_read16_seg(seg, addr)?)
+}
+pub unsafe fn push16_ss32_mem(seg: i32, addr: i32) -> OrPageFault<()> {
+    push16_ss32(safe_read16_seg(seg, addr)?)
+}
 
 pub unsafe fn push16(imm16: i32) -> OrPageFault<()> {
     if *stack_size_32 {
@@ -174,19 +181,23 @@ pub unsafe fn push16(imm16: i32) -> OrPageFault<()> {
 
 pub unsafe fn push32_ss16(imm32: i32) -> OrPageFault<()> {
     let new_sp = read_reg16(SP) - 4 & 0xFFFF;
-    safe_write32(get_seg_ss() + new_sp, imm32)?;
+    safe_write32_seg(SS, get_seg_ss() + new_sp, imm32)?;
     write_reg16(SP, new_sp);
     Ok(())
 }
 pub unsafe fn push32_ss32(imm32: i32) -> OrPageFault<()> {
     let new_esp = read_reg32(ESP) - 4;
-    safe_write32(get_seg_ss() + new_esp, imm32)?;
+    safe_write32_seg(SS, get_seg_ss() + new_esp, imm32)?;
     write_reg32(ESP, new_esp);
     Ok(())
 }
 
-pub unsafe fn push32_ss16_mem(addr: i32) -> OrPageFault<()> { push32_ss16(safe_read32s(addr)?) }
-pub unsafe fn push32_ss32_mem(addr: i32) -> OrPageFault<()> { push32_ss32(safe_read32s(addr)?) }
+pub unsafe fn push32_ss16_mem(seg: i32, addr: i32) -> OrPageFault<()> {
+    push32_ss16(safe_read32s_seg(seg, addr)?)
+}
+pub unsafe fn push32_ss32_mem(seg: i32, addr: i32) -> OrPageFault<()> {
+    push32_ss32(safe_read32s_seg(seg, addr)?)
+}
 
 pub unsafe fn push32(imm32: i32) -> OrPageFault<()> {
     if *stack_size_32 {
@@ -201,12 +212,12 @@ pub unsafe fn push32_sreg(i: i32) -> OrPageFault<()> {
     // you can't make this up ...
     if *stack_size_32 {
         let new_esp = read_reg32(ESP) - 4;
-        safe_write16(get_seg_ss() + new_esp, *sreg.offset(i as isize) as i32)?;
+        safe_write16_seg(SS, get_seg_ss() + new_esp, *sreg.offset(i as isize) as i32)?;
         write_reg32(ESP, new_esp);
     }
     else {
         let new_sp = read_reg16(SP) - 4 & 0xFFFF;
-        safe_write16(get_seg_ss() + new_sp, *sreg.offset(i as isize) as i32)?;
+        safe_write16_seg(SS, get_seg_ss() + new_sp, *sreg.offset(i as isize) as i32)?;
         write_reg16(SP, new_sp);
     }
     Ok(())
@@ -222,13 +233,13 @@ pub unsafe fn pop16() -> OrPageFault<i32> {
 }
 pub unsafe fn pop16_ss16() -> OrPageFault<i32> {
     let sp = get_seg_ss() + read_reg16(SP);
-    let result = safe_read16(sp)?;
+    let result = safe_read16_seg(SS, sp)?;
     write_reg16(SP, read_reg16(SP) + 2);
     Ok(result)
 }
 pub unsafe fn pop16_ss32() -> OrPageFault<i32> {
     let esp = get_seg_ss() + read_reg32(ESP);
-    let result = safe_read16(esp)?;
+    let result = safe_read16_seg(SS, esp)?;
     write_reg32(ESP, read_reg32(ESP) + 2);
     Ok(result)
 }
@@ -242,13 +253,13 @@ pub unsafe fn pop32s() -> OrPageFault<i32> {
 }
 pub unsafe fn pop32s_ss16() -> OrPageFault<i32> {
     let sp = read_reg16(SP);
-    let result = safe_read32s(get_seg_ss() + sp)?;
+    let result = safe_read32s_seg(SS, get_seg_ss() + sp)?;
     write_reg16(SP, sp + 4);
     Ok(result)
 }
 pub unsafe fn pop32s_ss32() -> OrPageFault<i32> {
     let esp = read_reg32(ESP);
-    let result = safe_read32s(get_seg_ss() + esp)?;
+    let result = safe_read32s_seg(SS, get_seg_ss() + esp)?;
     write_reg32(ESP, read_reg32(ESP) + 4);
     Ok(result)
 }
@@ -256,7 +267,7 @@ pub unsafe fn pusha16() {
     let temp = read_reg16(SP);
     // make sure we don't get a pagefault after having
     // pushed several registers already
-    return_on_pagefault!(writable_or_pagefault(get_stack_pointer(-16), 16));
+    return_on_pagefault!(writable_or_pagefault_seg(SS, get_stack_pointer(-16), 16));
     push16(read_reg16(AX)).unwrap();
     push16(read_reg16(CX)).unwrap();
     push16(read_reg16(DX)).unwrap();
@@ -268,7 +279,7 @@ pub unsafe fn pusha16() {
 }
 pub unsafe fn pusha32() {
     let temp = read_reg32(ESP);
-    return_on_pagefault!(writable_or_pagefault(get_stack_pointer(-32), 32));
+    return_on_pagefault!(writable_or_pagefault_seg(SS, get_stack_pointer(-32), 32));
     push32(read_reg32(EAX)).unwrap();
     push32(read_reg32(ECX)).unwrap();
     push32(read_reg32(EDX)).unwrap();
@@ -279,22 +290,22 @@ pub unsafe fn pusha32() {
     push32(read_reg32(EDI)).unwrap();
 }
 
-pub unsafe fn lss16(addr: i32, reg: i32, seg: i32) {
-    let new_reg = return_on_pagefault!(safe_read16(addr));
-    let new_seg = return_on_pagefault!(safe_read16(addr + 2));
+pub unsafe fn lss16(seg: i32, addr: i32, reg: i32, seg_reg: i32) {
+    let new_reg = return_on_pagefault!(safe_read16_seg(seg, addr));
+    let new_seg = return_on_pagefault!(safe_read16_seg(seg, addr + 2));
 
-    if !switch_seg(seg, new_seg) {
+    if !switch_seg(seg_reg, new_seg) {
         return;
     }
 
     write_reg16(reg, new_reg);
 }
 
-pub unsafe fn lss32(addr: i32, reg: i32, seg: i32) {
-    let new_reg = return_on_pagefault!(safe_read32s(addr));
-    let new_seg = return_on_pagefault!(safe_read16(addr + 4));
+pub unsafe fn lss32(seg: i32, addr: i32, reg: i32, seg_reg: i32) {
+    let new_reg = return_on_pagefault!(safe_read32s_seg(seg, addr));
+    let new_seg = return_on_pagefault!(safe_read16_seg(seg, addr + 4));
 
-    if !switch_seg(seg, new_seg) {
+    if !switch_seg(seg_reg, new_seg) {
         return;
     }
 
@@ -321,12 +332,12 @@ pub unsafe fn enter16(size: i32, mut nesting_level: i32) {
         let mut tmp_ebp = read_reg32(EBP);
         for _ in 1..nesting_level {
             tmp_ebp -= 2;
-            push16(safe_read16(ss + (tmp_ebp & ss_mask)).unwrap()).unwrap();
+            push16(safe_read16_seg(SS, ss + (tmp_ebp & ss_mask)).unwrap()).unwrap();
         }
         push16(frame_temp).unwrap();
     }
 
-    return_on_pagefault!(safe_write16(ss + (frame_temp & ss_mask), read_reg16(BP)));
+    return_on_pagefault!(safe_write16_seg(SS, ss + (frame_temp & ss_mask), read_reg16(BP)));
     write_reg16(BP, frame_temp);
     adjust_stack_reg(-size - 2);
 }
@@ -351,54 +362,59 @@ pub unsafe fn enter32(size: i32, mut nesting_level: i32) {
         let mut tmp_ebp = read_reg32(EBP);
         for _ in 1..nesting_level {
             tmp_ebp -= 4;
-            push32(safe_read32s(ss + (tmp_ebp & ss_mask)).unwrap()).unwrap();
+            push32(safe_read32s_seg(SS, ss + (tmp_ebp & ss_mask)).unwrap()).unwrap();
         }
         push32(frame_temp).unwrap();
     }
 
-    return_on_pagefault!(safe_write32(ss + (frame_temp & ss_mask), read_reg32(EBP)));
+    return_on_pagefault!(safe_write32_seg(SS, ss + (frame_temp & ss_mask), read_reg32(EBP)));
     write_reg32(EBP, frame_temp);
     adjust_stack_reg(-size - 4);
 }
 
 pub unsafe fn setcc_reg(condition: bool, r: i32) { write_reg8(r, condition as i32); }
-pub unsafe fn setcc_mem(condition: bool, addr: i32) {
-    return_on_pagefault!(safe_write8(addr, condition as i32));
+pub unsafe fn setcc_mem(condition: bool, seg: i32, addr: i32) {
+    return_on_pagefault!(safe_write8_seg(seg, addr, condition as i32));
 }
 
-pub unsafe fn fxsave(addr: i32) {
+pub unsafe fn fxsave(seg: i32, addr: i32) {
     dbg_assert!(addr & 0xF == 0, "TODO: #gp");
-    return_on_pagefault!(writable_or_pagefault(addr, 288));
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, 288));
 
-    safe_write16(addr + 0, (*fpu_control_word).into()).unwrap();
-    safe_write16(addr + 2, fpu_load_status_word().into()).unwrap();
-    safe_write8(addr + 4, !*fpu_stack_empty as i32 & 0xFF).unwrap();
-    safe_write16(addr + 6, *fpu_opcode).unwrap();
-    safe_write32(addr + 8, *fpu_ip).unwrap();
-    safe_write16(addr + 12, *fpu_ip_selector).unwrap();
-    safe_write32(addr + 16, *fpu_dp).unwrap();
-    safe_write16(addr + 20, *fpu_dp_selector).unwrap();
-
-    safe_write32(addr + 24, *mxcsr).unwrap();
-    safe_write32(addr + 28, MXCSR_MASK).unwrap();
+    fxsave_x87(seg, addr);
+    fxsave_sse(seg, addr);
+}
+unsafe fn fxsave_x87(seg: i32, addr: i32) {
+    safe_write16_seg(seg, addr + 0, (*fpu_control_word).into()).unwrap();
+    safe_write16_seg(seg, addr + 2, fpu_load_status_word().into()).unwrap();
+    safe_write8_seg(seg, addr + 4, !*fpu_stack_empty as i32 & 0xFF).unwrap();
+    safe_write16_seg(seg, addr + 6, *fpu_opcode).unwrap();
+    safe_write32_seg(seg, addr + 8, *fpu_ip).unwrap();
+    safe_write16_seg(seg, addr + 12, *fpu_ip_selector).unwrap();
+    safe_write32_seg(seg, addr + 16, *fpu_dp).unwrap();
+    safe_write16_seg(seg, addr + 20, *fpu_dp_selector).unwrap();
 
     for i in 0..8 {
         let reg_index = i + *fpu_stack_ptr as i32 & 7;
-        fpu_store_m80(addr + 32 + (i << 4), *fpu_st.offset(reg_index as isize));
+        fpu_store_m80(seg, addr + 32 + (i << 4), *fpu_st.offset(reg_index as isize));
     }
+}
+unsafe fn fxsave_sse(seg: i32, addr: i32) {
+    safe_write32_seg(seg, addr + 24, *mxcsr).unwrap();
+    safe_write32_seg(seg, addr + 28, MXCSR_MASK).unwrap();
 
     // If the OSFXSR bit in control register CR4 is not set, the FXSAVE
     // instruction may not save these registers. This behavior is
     // implementation dependent.
     for i in 0..8 {
-        safe_write128(addr + 160 + (i << 4), *reg_xmm.offset(i as isize)).unwrap();
+        safe_write128_seg(seg, addr + 160 + (i << 4), *reg_xmm.offset(i as isize)).unwrap();
     }
 }
-pub unsafe fn fxrstor(addr: i32) {
+pub unsafe fn fxrstor(seg: i32, addr: i32) {
     dbg_assert!(addr & 0xF == 0, "TODO: #gp");
-    return_on_pagefault!(readable_or_pagefault(addr, 288));
+    return_on_pagefault!(readable_or_pagefault_seg(seg, addr, 288));
 
-    let new_mxcsr = safe_read32s(addr + 24).unwrap();
+    let new_mxcsr = safe_read32s_seg(seg, addr + 24).unwrap();
 
     if 0 != new_mxcsr & !MXCSR_MASK {
         dbg_log!("#gp Invalid mxcsr bits");
@@ -406,24 +422,163 @@ pub unsafe fn fxrstor(addr: i32) {
         return;
     }
 
-    set_control_word(safe_read16(addr + 0).unwrap() as u16);
-    fpu_set_status_word(safe_read16(addr + 2).unwrap() as u16);
-    *fpu_stack_empty = !safe_read8(addr + 4).unwrap() as u8;
-    *fpu_opcode = safe_read16(addr + 6).unwrap();
-    *fpu_ip = safe_read32s(addr + 8).unwrap();
-    *fpu_ip_selector = safe_read16(addr + 12).unwrap();
-    *fpu_dp = safe_read32s(addr + 16).unwrap();
-    *fpu_dp_selector = safe_read16(addr + 20).unwrap();
-
+    fxrstor_x87(seg, addr);
     set_mxcsr(new_mxcsr);
+    fxrstor_xmm(seg, addr);
+}
+unsafe fn fxrstor_x87(seg: i32, addr: i32) {
+    set_control_word(safe_read16_seg(seg, addr + 0).unwrap() as u16);
+    fpu_set_status_word(safe_read16_seg(seg, addr + 2).unwrap() as u16);
+    *fpu_stack_empty = !safe_read8_seg(seg, addr + 4).unwrap() as u8;
+    *fpu_opcode = safe_read16_seg(seg, addr + 6).unwrap();
+    *fpu_ip = safe_read32s_seg(seg, addr + 8).unwrap();
+    *fpu_ip_selector = safe_read16_seg(seg, addr + 12).unwrap();
+    *fpu_dp = safe_read32s_seg(seg, addr + 16).unwrap();
+    *fpu_dp_selector = safe_read16_seg(seg, addr + 20).unwrap();
 
     for i in 0..8 {
         let reg_index = *fpu_stack_ptr as i32 + i & 7;
-        *fpu_st.offset(reg_index as isize) = fpu_load_m80(addr + 32 + (i << 4)).unwrap();
+        *fpu_st.offset(reg_index as isize) = fpu_load_m80(seg, addr + 32 + (i << 4)).unwrap();
     }
-
+}
+unsafe fn fxrstor_xmm(seg: i32, addr: i32) {
     for i in 0..8 {
-        *reg_xmm.offset(i as isize) = safe_read128s(addr + 160 + (i << 4)).unwrap();
+        *reg_xmm.offset(i as isize) = safe_read128s_seg(seg, addr + 160 + (i << 4)).unwrap();
+    }
+}
+
+pub const XSTATE_X87: i32 = 1 << 0;
+pub const XSTATE_SSE: i32 = 1 << 1;
+
+/// The legacy region (the fxsave layout) followed by the xsave header. Both supported state
+/// components are in the legacy region, so the standard and the compacted format have this size
+pub const XSAVE_AREA_SIZE: i32 = 576;
+const XSAVE_HEADER: i32 = 512;
+const XCOMP_BV_COMPACTED: u64 = 1 << 63;
+
+/// The state components that can be enabled in xcr0
+pub fn xcr0_supported() -> i32 {
+    XSTATE_X87 | if cpuid::has_feature(cpuid::FEATURE_SSE) { XSTATE_SSE } else { 0 }
+}
+
+/// The requested-feature bitmap of xsave and xrstor
+unsafe fn xsave_rfbm() -> i32 { *xcr0 & read_reg32(EAX) }
+
+unsafe fn xsave_check(addr: i32) -> bool {
+    if *cr.offset(4) & CR4_OSXSAVE == 0 {
+        dbg_log!("#ud xsave/xrstor without cr4.osxsave");
+        trigger_ud();
+        return false;
+    }
+    if *cr & CR0_TS != 0 {
+        trigger_nm();
+        return false;
+    }
+    if addr & 63 != 0 {
+        dbg_log!("#gp xsave/xrstor unaligned area");
+        trigger_gp(0);
+        return false;
+    }
+    true
+}
+
+/// xsave and xsaveopt (which doesn't track modified state and behaves like xsave) write the
+/// standard format, xsavec the compacted format. The x87 and sse components are always considered
+/// to be in use
+pub unsafe fn xsave(seg: i32, addr: i32, compacted: bool) {
+    if !xsave_check(addr) {
+        return;
+    }
+    return_on_pagefault!(writable_or_pagefault_seg(seg, addr, XSAVE_AREA_SIZE));
+
+    let rfbm = xsave_rfbm();
+    if rfbm & XSTATE_X87 != 0 {
+        fxsave_x87(seg, addr);
+    }
+    if rfbm & XSTATE_SSE != 0 {
+        fxsave_sse(seg, addr);
+    }
+
+    if compacted {
+        safe_write64_seg(seg, addr + XSAVE_HEADER, rfbm as u64).unwrap();
+        safe_write64_seg(seg, addr + XSAVE_HEADER + 8, rfbm as u64 | XCOMP_BV_COMPACTED).unwrap();
+    }
+    else {
+        let xstate_bv = safe_read64s_seg(seg, addr + XSAVE_HEADER).unwrap();
+        safe_write64_seg(seg, addr + XSAVE_HEADER, xstate_bv | rfbm as u64).unwrap();
+    }
+}
+
+/// xrstor of either format. Requested components that aren't in xstate_bv are initialised
+pub unsafe fn xrstor(seg: i32, addr: i32) {
+    if !xsave_check(addr) {
+        return;
+    }
+    return_on_pagefault!(readable_or_pagefault_seg(seg, addr, XSAVE_AREA_SIZE));
+
+    let rfbm = xsave_rfbm();
+    let xstate_bv = safe_read64s_seg(seg, addr + XSAVE_HEADER).unwrap();
+    let xcomp_bv = safe_read64s_seg(seg, addr + XSAVE_HEADER + 8).unwrap();
+    let compacted = xcomp_bv & XCOMP_BV_COMPACTED != 0;
+
+    // the reserved bytes of the header: 16 to 63 in the compacted format, 16 to 23 otherwise
+    let reserved_end = if compacted { 64 } else { 24 };
+    let mut reserved = 0;
+    for offset in (16..reserved_end).step_by(8) {
+        reserved |= safe_read64s_seg(seg, addr + XSAVE_HEADER + offset).unwrap();
+    }
+
+    let xcr0_mask = *xcr0 as u64;
+    let invalid_header = if compacted {
+        xcomp_bv & !XCOMP_BV_COMPACTED & !xcr0_mask != 0 || xstate_bv & !xcomp_bv != 0
+    }
+    else {
+        xstate_bv & !xcr0_mask != 0 || xcomp_bv != 0
+    };
+    if invalid_header || reserved != 0 {
+        dbg_log!("#gp xrstor: Invalid header xstate_bv={:x} xcomp_bv={:x}", xstate_bv, xcomp_bv);
+        trigger_gp(0);
+        return;
+    }
+
+    let restore_x87 = xstate_bv & XSTATE_X87 as u64 != 0;
+    let restore_sse = xstate_bv & XSTATE_SSE as u64 != 0;
+
+    // the standard format always loads mxcsr, the compacted format only if the component is set
+    let mut new_mxcsr = MXCSR_DEFAULT;
+    if rfbm & XSTATE_SSE != 0 && (restore_sse || !compacted) {
+        new_mxcsr = safe_read32s_seg(seg, addr + 24).unwrap();
+        if 0 != new_mxcsr & !MXCSR_MASK {
+            dbg_log!("#gp Invalid mxcsr bits");
+            trigger_gp(0);
+            return;
+        }
+    }
+
+    if rfbm & XSTATE_X87 != 0 {
+        if restore_x87 {
+            fxrstor_x87(seg, addr);
+        }
+        else {
+            fpu_finit();
+            *fpu_ip_selector = 0;
+            *fpu_dp_selector = 0;
+            for i in 0..8 {
+                *fpu_st.offset(i) = F80::ZERO;
+            }
+        }
+    }
+
+    if rfbm & XSTATE_SSE != 0 {
+        set_mxcsr(new_mxcsr);
+        if restore_sse {
+            fxrstor_xmm(seg, addr);
+        }
+        else {
+            for i in 0..8 {
+                *reg_xmm.offset(i) = reg128 { u64: [0, 0] };
+            }
+        }
     }
 }
 
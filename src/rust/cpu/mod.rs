@@ -1,6 +1,7 @@
 pub mod arith;
 pub mod call_indirect;
 pub mod cpu;
+pub mod cpuid;
 pub mod fpu;
 pub mod global_pointers;
 pub mod instructions;
@@ -9,6 +10,7 @@ pub mod memory;
 pub mod misc_instr;
 pub mod modrm;
 pub mod pic;
+pub mod replay;
 pub mod sse_instr;
 pub mod string;
 pub mod vga;
@@ -1,7 +1,7 @@
 use cpu::cpu::*;
 use paging::OrPageFault;
 
-pub unsafe fn resolve_modrm16(modrm_byte: i32) -> OrPageFault<i32> {
+pub unsafe fn resolve_modrm16(modrm_byte: i32) -> OrPageFault<(i32, i32)> {
     match modrm_byte & !0o070 {
         0o000 => get_seg_prefix_ds(read_reg16(BX) + read_reg16(SI) & 0xFFFF),
         0o100 => get_seg_prefix_ds(read_reg16(BX) + read_reg16(SI) + read_imm8s()? & 0xFFFF),
@@ -34,7 +34,7 @@ pub unsafe fn resolve_modrm16(modrm_byte: i32) -> OrPageFault<i32> {
     }
 }
 
-pub unsafe fn resolve_modrm32_(modrm_byte: i32) -> OrPageFault<i32> {
+pub unsafe fn resolve_modrm32_(modrm_byte: i32) -> OrPageFault<(i32, i32)> {
     let r = (modrm_byte & 7) as u8;
     dbg_assert!(modrm_byte < 192);
     Ok(if r as i32 == 4 {
@@ -42,7 +42,8 @@ pub unsafe fn resolve_modrm32_(modrm_byte: i32) -> OrPageFault<i32> {
             resolve_sib(false)?
         }
         else {
-            resolve_sib(true)? + if modrm_byte < 128 { read_imm8s()? } else { read_imm32s()? }
+            let (seg, addr) = resolve_sib(true)?;
+            (seg, addr + if modrm_byte < 128 { read_imm8s()? } else { read_imm32s()? })
         }
     }
     else if r as i32 == 5 {
@@ -64,7 +65,7 @@ pub unsafe fn resolve_modrm32_(modrm_byte: i32) -> OrPageFault<i32> {
         )?
     })
 }
-unsafe fn resolve_sib(with_imm: bool) -> OrPageFault<i32> {
+unsafe fn resolve_sib(with_imm: bool) -> OrPageFault<(i32, i32)> {
     let sib_byte = read_imm8()?;
     let r = sib_byte & 7;
     let m = sib_byte >> 3 & 7;
@@ -96,10 +97,10 @@ unsafe fn resolve_sib(with_imm: bool) -> OrPageFault<i32> {
         let s = sib_byte >> 6 & 3;
         offset = read_reg32(m) << s
     }
-    Ok(get_seg_prefix(seg)? + base + offset)
+    get_seg_prefix_address(seg, base + offset)
 }
 
-pub unsafe fn resolve_modrm32(modrm_byte: i32) -> OrPageFault<i32> {
+pub unsafe fn resolve_modrm32(modrm_byte: i32) -> OrPageFault<(i32, i32)> {
     match modrm_byte & !0o070 {
         0o000 => get_seg_prefix_ds(read_reg32(EAX)),
         0o100 => get_seg_prefix_ds(read_reg32(EAX) + read_imm8s()?),
@@ -114,8 +115,14 @@ pub unsafe fn resolve_modrm32(modrm_byte: i32) -> OrPageFault<i32> {
         0o103 => get_seg_prefix_ds(read_reg32(EBX) + read_imm8s()?),
         0o203 => get_seg_prefix_ds(read_reg32(EBX) + read_imm32s()?),
         0o004 => resolve_sib(false),
-        0o104 => Ok(resolve_sib(true)? + read_imm8s()?),
-        0o204 => Ok(resolve_sib(true)? + read_imm32s()?),
+        0o104 => {
+            let (seg, addr) = resolve_sib(true)?;
+            Ok((seg, addr + read_imm8s()?))
+        },
+        0o204 => {
+            let (seg, addr) = resolve_sib(true)?;
+            Ok((seg, addr + read_imm32s()?))
+        },
         0o005 => get_seg_prefix_ds(read_imm32s()?),
         0o105 => get_seg_prefix_ss(read_reg32(EBP) + read_imm8s()?),
         0o205 => get_seg_prefix_ss(read_reg32(EBP) + read_imm32s()?),
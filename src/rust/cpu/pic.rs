@@ -284,6 +284,15 @@ impl Pic {
     }
 }
 
+// called by the cpu, to end mwait on masked interrupts
+pub unsafe fn pic_has_pending_irq() -> bool {
+    match master.get_irq() {
+        Some(2) => slave.get_irq().is_some(),
+        Some(_) => true,
+        None => false,
+    }
+}
+
 // called by the cpu
 pub unsafe fn pic_acknowledge_irq() -> Option<u8> {
     let irq = match master.get_irq() {
@@ -0,0 +1,310 @@
+//! Recording and replay of the nondeterministic inputs the cpu observes: Delivered interrupts,
+//! port and mmio reads, rdtsc and rdrand. Each event is keyed by the number of instructions
+//! executed since the start of the recording, so that during replay, interrupts are delivered at
+//! exactly the same instruction and reads return the recorded values instead of consulting the
+//! devices. Requires the jit to be disabled, see instruction_count. Memory written by devices
+//! (dma) isn't recorded, so a replay needs the same disk images, loaded synchronously.
+//!
+//! The log starts with a header, the magic "v86r" followed by the version as a little-endian u32,
+//! and is followed by the events. Each event is its kind (one byte), followed by three unsigned
+//! LEB128 numbers: The number of instructions since the previous event, a key (the vector of an
+//! interrupt, the port or the physical address of a read) and the value that was read.
+
+#![allow(non_upper_case_globals)]
+
+use cpu::cpu::{in_mwait, instruction_count, mwait_wake, pic_call_irq, FLAG_INTERRUPT};
+use cpu::global_pointers::{flags, in_hlt};
+
+use std::ptr;
+
+const MAGIC: [u8; 4] = *b"v86r";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 8;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+    Irq = 0,
+    /// mwait ended by a masked interrupt
+    MwaitWake = 1,
+    PortRead8 = 2,
+    PortRead16 = 3,
+    PortRead32 = 4,
+    MmioRead8 = 5,
+    MmioRead16 = 6,
+    MmioRead32 = 7,
+    Tsc = 8,
+    Rand = 9,
+}
+
+impl Event {
+    fn of_u8(kind: u8) -> Option<Event> {
+        Some(match kind {
+            0 => Event::Irq,
+            1 => Event::MwaitWake,
+            2 => Event::PortRead8,
+            3 => Event::PortRead16,
+            4 => Event::PortRead32,
+            5 => Event::MmioRead8,
+            6 => Event::MmioRead16,
+            7 => Event::MmioRead32,
+            8 => Event::Tsc,
+            9 => Event::Rand,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Record {
+    kind: Event,
+    count: u64,
+    key: u32,
+    value: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    Off = 0,
+    Record = 1,
+    Replay = 2,
+    /// like Off, after the replay stopped because execution didn't match the log or the log is
+    /// corrupt
+    Diverged = 3,
+}
+
+struct Log {
+    bytes: Vec<u8>,
+    // replay: the next event and the position of the event after it
+    next: Option<Record>,
+    position: usize,
+}
+
+static mut mode: Mode = Mode::Off;
+// the log being recorded or replayed, only accessed through log()
+static mut log_state: Log = Log {
+    bytes: Vec::new(),
+    next: None,
+    position: 0,
+};
+// instruction_count at the start of the recording or replay
+static mut start_count: u64 = 0;
+// the count of the previously recorded or replayed event
+static mut last_count: u64 = 0;
+
+unsafe fn log() -> &'static mut Log { &mut *ptr::addr_of_mut!(log_state) }
+
+unsafe fn now() -> u64 { instruction_count() - start_count }
+
+fn write_leb128(out: &mut Vec<u8>, mut x: u64) {
+    loop {
+        let byte = x as u8 & 0x7F;
+        x >>= 7;
+        if x == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_leb128(input: &[u8], offset: &mut usize) -> Option<u64> {
+    let mut x = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *input.get(*offset)?;
+        *offset += 1;
+        if shift >= 64 {
+            return None;
+        }
+        x |= ((byte & 0x7F) as u64) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(x);
+        }
+    }
+}
+
+/// Start recording, discarding any previous log
+#[no_mangle]
+pub unsafe fn replay_record_start() {
+    let log = log();
+    log.bytes.clear();
+    log.bytes.extend_from_slice(&MAGIC);
+    log.bytes.extend_from_slice(&VERSION.to_le_bytes());
+    mode = Mode::Record;
+    start_count = instruction_count();
+    last_count = 0;
+}
+
+/// Space for a log of the given length, to be filled in by the caller before replay_start
+#[no_mangle]
+pub unsafe fn replay_allocate_log(length: u32) -> *mut u8 {
+    mode = Mode::Off;
+    let log = log();
+    log.bytes.clear();
+    log.bytes.resize(length as usize, 0);
+    log.bytes.as_mut_ptr()
+}
+
+/// Start replaying the log. Returns false if the header is invalid
+#[no_mangle]
+pub unsafe fn replay_start() -> bool {
+    let log = log();
+    if log.bytes.len() < HEADER_SIZE || log.bytes[0..4] != MAGIC {
+        dbg_log!("replay: Invalid log");
+        return false;
+    }
+    let version = u32::from_le_bytes([log.bytes[4], log.bytes[5], log.bytes[6], log.bytes[7]]);
+    if version != VERSION {
+        dbg_log!("replay: Unsupported log version {}", version);
+        return false;
+    }
+    mode = Mode::Replay;
+    start_count = instruction_count();
+    last_count = 0;
+    log.position = HEADER_SIZE;
+    advance();
+    true
+}
+
+#[no_mangle]
+pub unsafe fn replay_stop() { mode = Mode::Off; }
+
+/// 0: off, 1: recording, 2: replaying, 3: the replay diverged from the log and was stopped
+#[no_mangle]
+pub unsafe fn replay_get_mode() -> u8 { mode as u8 }
+
+#[no_mangle]
+pub unsafe fn replay_log_pointer() -> *const u8 { log().bytes.as_ptr() }
+#[no_mangle]
+pub unsafe fn replay_log_length() -> u32 { log().bytes.len() as u32 }
+
+pub unsafe fn replaying() -> bool { mode == Mode::Replay }
+
+unsafe fn record(kind: Event, key: u32, value: u64) {
+    let count = now();
+    let log = &mut log().bytes;
+    log.push(kind as u8);
+    write_leb128(log, count - last_count);
+    write_leb128(log, key as u64);
+    write_leb128(log, value);
+    last_count = count;
+}
+
+/// Decode the next event of the log being replayed, ending the replay at the end of the log
+unsafe fn advance() {
+    let log = log();
+    log.next = None;
+    if log.position == log.bytes.len() {
+        dbg_log!("replay: End of log");
+        mode = Mode::Off;
+        return;
+    }
+    let kind = Event::of_u8(log.bytes[log.position]);
+    log.position += 1;
+    let delta = read_leb128(&log.bytes, &mut log.position);
+    let key = read_leb128(&log.bytes, &mut log.position);
+    let value = read_leb128(&log.bytes, &mut log.position);
+    match (kind, delta, key, value) {
+        (Some(kind), Some(delta), Some(key), Some(value)) => {
+            last_count += delta;
+            log.next = Some(Record {
+                kind,
+                count: last_count,
+                key: key as u32,
+                value,
+            });
+        },
+        _ => {
+            dbg_log!("replay: Corrupt log at offset {:x}", log.position);
+            mode = Mode::Diverged;
+        },
+    }
+}
+
+unsafe fn diverged(observed: &str) {
+    if let Some(r) = log().next {
+        dbg_log!(
+            "replay: Diverged at instruction {} ({}), expected {:?} {:x} at instruction {}",
+            now(),
+            observed,
+            r.kind,
+            r.key,
+            r.count
+        );
+    }
+    log().next = None;
+    mode = Mode::Diverged;
+}
+
+/// A read whose result is recorded, or taken from the log instead of calling `read` during replay
+pub unsafe fn input(kind: Event, key: u32, read: impl FnOnce() -> u64) -> u64 {
+    match mode {
+        Mode::Off | Mode::Diverged => read(),
+        Mode::Record => {
+            let value = read();
+            record(kind, key, value);
+            value
+        },
+        Mode::Replay => match log().next {
+            Some(r) if r.kind == kind && r.key == key && r.count == now() => {
+                advance();
+                r.value
+            },
+            _ => {
+                diverged(&format!("{:?} {:x}", kind, key));
+                read()
+            },
+        },
+    }
+}
+
+/// Called when an interrupt is delivered to the cpu
+pub unsafe fn record_irq(vector: u8) {
+    if mode == Mode::Record {
+        record(Event::Irq, vector as u32, 0);
+    }
+}
+
+/// Called when mwait is ended by a masked interrupt
+pub unsafe fn record_mwait_wake() {
+    if mode == Mode::Record {
+        record(Event::MwaitWake, 0, 0);
+    }
+}
+
+/// Whether the next interrupt in the log is due, so that the interpreter stops after the current
+/// instruction and handle_irqs gets called
+pub unsafe fn irq_due() -> bool {
+    mode == Mode::Replay
+        && match log().next {
+            Some(r) => (r.kind == Event::Irq || r.kind == Event::MwaitWake) && r.count <= now(),
+            None => false,
+        }
+}
+
+/// Replaces handle_irqs during replay: Delivers the interrupts of the log instead of the ones of
+/// the devices
+pub unsafe fn handle_irqs() {
+    if !irq_due() {
+        if *in_hlt {
+            // the cpu was woken up at this instruction during the recording
+            diverged("halted");
+        }
+        return;
+    }
+    let r = log().next.unwrap();
+    if r.count == now() {
+        if r.kind == Event::Irq && *flags & FLAG_INTERRUPT != 0 {
+            advance();
+            pic_call_irq(r.key as u8);
+            return;
+        }
+        if r.kind == Event::MwaitWake && in_mwait {
+            advance();
+            mwait_wake();
+            return;
+        }
+    }
+    diverged("interrupt not deliverable");
+}
@@ -1,35 +1,35 @@
 use cpu::cpu::*;
-use cpu::global_pointers::mxcsr;
+use cpu::global_pointers::{flags, flags_changed, mxcsr};
 
-pub unsafe fn mov_r_m64(addr: i32, r: i32) {
+pub unsafe fn mov_r_m64(seg: i32, addr: i32, r: i32) {
     // mov* m64, mm
     let data = read_mmx64s(r);
-    return_on_pagefault!(safe_write64(addr, data));
+    return_on_pagefault!(safe_write64_seg(seg, addr, data));
     transition_fpu_to_mmx();
 }
-pub unsafe fn movl_r128_m64(addr: i32, r: i32) {
+pub unsafe fn movl_r128_m64(seg: i32, addr: i32, r: i32) {
     // mov* m64, xmm
     let data = read_xmm64s(r);
-    return_on_pagefault!(safe_write64(addr, data));
+    return_on_pagefault!(safe_write64_seg(seg, addr, data));
 }
 pub unsafe fn mov_r_r128(r1: i32, r2: i32) {
     // mov* xmm, xmm
     let data = read_xmm128s(r2);
     write_xmm_reg128(r1, data);
 }
-pub unsafe fn mov_r_m128(addr: i32, r: i32) {
+pub unsafe fn mov_r_m128(seg: i32, addr: i32, r: i32) {
     // mov* m128, xmm
     let data = read_xmm128s(r);
-    return_on_pagefault!(safe_write128(addr, data));
+    return_on_pagefault!(safe_write128_seg(seg, addr, data));
 }
 pub unsafe fn mov_rm_r128(source: reg128, r: i32) {
     // mov* xmm, xmm/m128
     write_xmm_reg128(r, source);
 }
-pub unsafe fn movh_r128_m64(addr: i32, r: i32) {
+pub unsafe fn movh_r128_m64(seg: i32, addr: i32, r: i32) {
     // movhp* m64, xmm
     let data = read_xmm128s(r);
-    return_on_pagefault!(safe_write64(addr, data.u64[1]));
+    return_on_pagefault!(safe_write64_seg(seg, addr, data.u64[1]));
 }
 
 pub unsafe fn pand_r128(source: reg128, r: i32) {
@@ -340,8 +340,27 @@ pub unsafe fn sse_convert_f64_to_i32(x: f64) -> i32 {
 }
 
 pub unsafe fn sse_integer_round(f: f64) -> f64 {
+    sse_integer_round_with_mode(f, *mxcsr >> MXCSR_RC_SHIFT & 3)
+}
+
+pub unsafe fn sse_round(f: f64, imm8: i32) -> f64 {
+    // round{ps,pd,ss,sd}: bit 2 of imm8 selects MXCSR.RC, otherwise bits 0-1 are the rounding mode
+    let rounded = if imm8 & 4 != 0 {
+        sse_integer_round(f)
+    }
+    else {
+        sse_integer_round_with_mode(f, imm8 & 3)
+    };
+    // Bit 3 suppresses the precision exception. Only the flag is set, as unmasked simd
+    // exceptions aren't implemented (see set_mxcsr)
+    if imm8 & 8 == 0 && rounded != f && !f.is_nan() {
+        *mxcsr |= MXCSR_PE;
+    }
+    rounded
+}
+
+pub fn sse_integer_round_with_mode(f: f64, rc: i32) -> f64 {
     // see fpu_integer_round
-    let rc = *mxcsr >> MXCSR_RC_SHIFT & 3;
     if rc == 0 {
         // Round to nearest, or even if equidistant
         let mut rounded = f.round();
@@ -359,3 +378,280 @@ pub unsafe fn sse_integer_round(f: f64) -> f64 {
         return f.ceil();
     };
 }
+
+unsafe fn sse_string_element(v: &reg128, i: i32, imm8: i32) -> i32 {
+    let i = i as usize;
+    match imm8 & 3 {
+        0 => v.u8[i] as i32,
+        1 => v.u16[i] as i32,
+        2 => v.i8[i] as i32,
+        _ => v.i16[i] as i32,
+    }
+}
+
+fn sse_string_element_count(imm8: i32) -> i32 { if imm8 & 1 == 0 { 16 } else { 8 } }
+
+pub unsafe fn sse_string_implicit_length(v: reg128, imm8: i32) -> i32 {
+    // pcmpistr*: the string ends at the first null element
+    let n = sse_string_element_count(imm8);
+    for i in 0..n {
+        if sse_string_element(&v, i, imm8) == 0 {
+            return i;
+        }
+    }
+    return n;
+}
+
+pub fn sse_string_explicit_length(length: i32, imm8: i32) -> i32 {
+    // pcmpestr*: the absolute value of eax/edx, saturated to the number of elements
+    let n = sse_string_element_count(imm8);
+    return i64::min((length as i64).abs(), n as i64) as i32;
+}
+
+pub unsafe fn sse_string_compare(
+    a: reg128,
+    length_a: i32,
+    b: reg128,
+    length_b: i32,
+    imm8: i32,
+) -> u32 {
+    // pcmpestri, pcmpestrm, pcmpistri, pcmpistrm: Returns IntRes2, with one bit per element of b
+    let n = sse_string_element_count(imm8);
+    let mut result: u32 = 0;
+
+    match imm8 >> 2 & 3 {
+        0 => {
+            // equal any
+            for j in 0..length_b {
+                let x = sse_string_element(&b, j, imm8);
+                for i in 0..length_a {
+                    if sse_string_element(&a, i, imm8) == x {
+                        result |= 1 << j;
+                        break;
+                    }
+                }
+            }
+        },
+        1 => {
+            // ranges
+            for j in 0..length_b {
+                let x = sse_string_element(&b, j, imm8);
+                let mut i = 0;
+                while i + 1 < length_a {
+                    if sse_string_element(&a, i, imm8) <= x
+                        && x <= sse_string_element(&a, i + 1, imm8)
+                    {
+                        result |= 1 << j;
+                        break;
+                    }
+                    i += 2;
+                }
+            }
+        },
+        2 => {
+            // equal each: invalid elements only match other invalid elements
+            for i in 0..n {
+                let matches = if i < length_a && i < length_b {
+                    sse_string_element(&a, i, imm8) == sse_string_element(&b, i, imm8)
+                }
+                else {
+                    i >= length_a && i >= length_b
+                };
+                if matches {
+                    result |= 1 << i;
+                }
+            }
+        },
+        _ => {
+            // equal ordered: search for the substring a in b, an invalid element in a always
+            // matches
+            for j in 0..n {
+                let mut matches = true;
+                for i in 0..i32::min(n - j, length_a) {
+                    if i + j >= length_b
+                        || sse_string_element(&a, i, imm8) != sse_string_element(&b, i + j, imm8)
+                    {
+                        matches = false;
+                        break;
+                    }
+                }
+                if matches {
+                    result |= 1 << j;
+                }
+            }
+        },
+    }
+
+    match imm8 >> 4 & 3 {
+        1 => result = !result & ((1 << n) - 1),
+        3 => result ^= (1 << length_b) - 1,
+        _ => {},
+    }
+
+    return result;
+}
+
+pub unsafe fn sse_string_set_flags(result: u32, length_a: i32, length_b: i32, imm8: i32) {
+    let n = sse_string_element_count(imm8);
+    *flags_changed = 0;
+    *flags &= !FLAGS_ALL;
+    if result != 0 {
+        *flags |= FLAG_CARRY;
+    }
+    if length_b < n {
+        *flags |= FLAG_ZERO;
+    }
+    if length_a < n {
+        *flags |= FLAG_SIGN;
+    }
+    if result & 1 != 0 {
+        *flags |= FLAG_OVERFLOW;
+    }
+}
+
+pub fn sse_string_index(result: u32, imm8: i32) -> i32 {
+    // pcmp*stri: index of the least or most significant set bit, or the number of elements
+    if result == 0 {
+        sse_string_element_count(imm8)
+    }
+    else if imm8 & 0x40 != 0 {
+        31 - result.leading_zeros() as i32
+    }
+    else {
+        result.trailing_zeros() as i32
+    }
+}
+
+pub unsafe fn sse_string_mask(result: u32, imm8: i32) -> reg128 {
+    // pcmp*strm: either a bit mask or one byte/word mask per element
+    let mut mask = reg128 { i8: [0; 16] };
+    if imm8 & 0x40 == 0 {
+        mask.u32[0] = result;
+    }
+    else if imm8 & 1 == 0 {
+        for i in 0..16 {
+            if result & 1 << i != 0 {
+                mask.u8[i] = 0xFF;
+            }
+        }
+    }
+    else {
+        for i in 0..8 {
+            if result & 1 << i != 0 {
+                mask.u16[i] = 0xFFFF;
+            }
+        }
+    }
+    return mask;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(s: &[u8]) -> reg128 {
+        let mut r = reg128 { i8: [0; 16] };
+        unsafe { r.u8[..s.len()].copy_from_slice(s) };
+        r
+    }
+    fn words(s: &[i16]) -> reg128 {
+        let mut r = reg128 { i8: [0; 16] };
+        unsafe { r.i16[..s.len()].copy_from_slice(s) };
+        r
+    }
+    fn implicit_compare(a: reg128, b: reg128, imm8: i32) -> u32 {
+        unsafe {
+            let length_a = sse_string_implicit_length(a, imm8);
+            let length_b = sse_string_implicit_length(b, imm8);
+            sse_string_compare(a, length_a, b, length_b, imm8)
+        }
+    }
+
+    #[test]
+    fn string_lengths() {
+        unsafe {
+            assert_eq!(sse_string_implicit_length(bytes(b"aeiou"), 0), 5);
+            assert_eq!(sse_string_implicit_length(bytes(b""), 0), 0);
+            assert_eq!(sse_string_implicit_length(bytes(b"0123456789abcdef"), 0), 16);
+            assert_eq!(sse_string_implicit_length(words(&[1, 2, 3]), 1), 3);
+            assert_eq!(sse_string_implicit_length(bytes(b"ab"), 1), 1);
+        }
+        assert_eq!(sse_string_explicit_length(3, 0), 3);
+        assert_eq!(sse_string_explicit_length(-3, 0), 3);
+        assert_eq!(sse_string_explicit_length(100, 0), 16);
+        assert_eq!(sse_string_explicit_length(100, 1), 8);
+        assert_eq!(sse_string_explicit_length(i32::MIN, 1), 8);
+    }
+
+    #[test]
+    fn string_compare_equal_any() {
+        let result = implicit_compare(bytes(b"aeiou"), bytes(b"hello world"), 0x00);
+        assert_eq!(result, 0b1001_0010);
+        assert_eq!(sse_string_index(result, 0x00), 1);
+        assert_eq!(sse_string_index(result, 0x40), 7);
+        assert_eq!(implicit_compare(bytes(b""), bytes(b"hello"), 0x00), 0);
+        assert_eq!(sse_string_index(0, 0x00), 16);
+        assert_eq!(sse_string_index(0, 0x01), 8);
+
+        let result = implicit_compare(words(&[1, 2]), words(&[3, 2, 1]), 0x01);
+        assert_eq!(result, 0b110);
+    }
+
+    #[test]
+    fn string_compare_ranges() {
+        let a = bytes(b"azAZ");
+        let b = bytes(b"Hello, World!");
+        assert_eq!(implicit_compare(a, b, 0x04), 0x0F9F);
+        assert_eq!(implicit_compare(a, b, 0x14), 0xF060);
+        assert_eq!(implicit_compare(a, b, 0x24), 0x0F9F);
+        assert_eq!(implicit_compare(a, b, 0x34), 0x1060);
+
+        // signed bytes: [-5, 5] against [-6, -5, 0, 5, 6]
+        let a = bytes(&[0xFB, 0x05]);
+        let b = bytes(&[0xFA, 0xFB, 0x00, 0x05, 0x06]);
+        unsafe {
+            assert_eq!(sse_string_compare(a, 2, b, 5, 0x06), 0b01110);
+            // unsigned: 0xFB..0x05 is an empty range
+            assert_eq!(sse_string_compare(a, 2, b, 5, 0x04), 0);
+            // odd number of range elements: the last one is ignored
+            assert_eq!(sse_string_compare(a, 1, b, 5, 0x06), 0);
+        }
+    }
+
+    #[test]
+    fn string_compare_equal_each() {
+        let result = implicit_compare(bytes(b"hello"), bytes(b"hellx"), 0x08);
+        assert_eq!(result, 0xFFEF);
+        let result = implicit_compare(bytes(b"hello"), bytes(b"hellx"), 0x18);
+        assert_eq!(result, 0x0010);
+        assert_eq!(sse_string_index(result, 0x18), 4);
+        assert_eq!(implicit_compare(bytes(b"hello"), bytes(b"hello"), 0x18), 0);
+        assert_eq!(implicit_compare(bytes(b"hello"), bytes(b"hell"), 0x08), 0xFFEF);
+    }
+
+    #[test]
+    fn string_compare_equal_ordered() {
+        assert_eq!(implicit_compare(bytes(b"lo"), bytes(b"hello world"), 0x0C), 1 << 3);
+        assert_eq!(implicit_compare(bytes(b"o"), bytes(b"hello world"), 0x0C), 1 << 4 | 1 << 7);
+        // a partial match at the end of b doesn't count
+        assert_eq!(implicit_compare(bytes(b"ld!"), bytes(b"hello world"), 0x0C), 0);
+        // the empty string matches everywhere
+        assert_eq!(implicit_compare(bytes(b""), bytes(b"hello"), 0x0C), 0xFFFF);
+        assert_eq!(implicit_compare(words(&[2, 3]), words(&[1, 2, 3, 2]), 0x0D), 1 << 1);
+    }
+
+    #[test]
+    fn string_mask() {
+        unsafe {
+            assert_eq!(sse_string_mask(0b1001_0010, 0x00).u64, [0b1001_0010, 0]);
+            assert_eq!(
+                sse_string_mask(0b1001_0010, 0x40).u8,
+                [0, 0xFF, 0, 0, 0xFF, 0, 0, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0]
+            );
+            assert_eq!(
+                sse_string_mask(0b1000_0001, 0x41).u16,
+                [0xFFFF, 0, 0, 0, 0, 0, 0, 0xFFFF]
+            );
+        }
+    }
+}
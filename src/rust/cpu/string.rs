@@ -11,11 +11,13 @@
 
 use cpu::arith::{cmp16, cmp32, cmp8};
 use cpu::cpu::{
-    get_seg, io_port_read16, io_port_read32, io_port_read8, io_port_write16, io_port_write32,
-    io_port_write8, read_reg16, read_reg32, safe_read16, safe_read32s, safe_read8, safe_write16,
-    safe_write32, safe_write8, set_reg_asize, test_privileges_for_io, translate_address_read,
-    translate_address_write_and_can_skip_dirty, writable_or_pagefault, write_reg16, write_reg32,
-    write_reg8, AL, AX, DX, EAX, ECX, EDI, ES, ESI, FLAG_DIRECTION,
+    alignment_check_enabled, breakpoints_enabled, debug_trap_pending, get_seg, io_port_read16,
+    io_port_read32, io_port_read8, io_port_write16, io_port_write32, io_port_write8, read_reg16,
+    read_reg32, safe_read16_seg, safe_read32s_seg, safe_read8_seg, safe_write16_seg,
+    safe_write32_seg, safe_write8_seg, segment_checks_active, segment_is_unrestricted,
+    set_reg_asize, test_privileges_for_io, translate_address_read,
+    translate_address_write_and_can_skip_dirty, writable_or_pagefault_seg, write_reg16,
+    write_reg32, write_reg8, AL, AX, DX, EAX, ECX, EDI, ES, ESI, FLAG_DIRECTION,
 };
 use cpu::global_pointers::{flags, instruction_pointer, previous_ip};
 use cpu::memory::{
@@ -145,9 +147,13 @@ unsafe fn string_instruction(
 
     let is_aligned = (ds + src) & (size_bytes - 1) == 0 && (es + dst) & (size_bytes - 1) == 0;
 
-    // unaligned movs is properly handled in the fast path
-    let mut rep_fast = (instruction == Instruction::Movs || is_aligned)
+    // unaligned movs is properly handled in the fast path, unless it needs to raise #ac
+    let mut rep_fast = (instruction == Instruction::Movs && !alignment_check_enabled()
+        || is_aligned)
         && is_asize_32 // 16-bit address wraparound
+        && !breakpoints_enabled() // data breakpoints are checked by the slow path
+        && !(segment_checks_active() // as are segment limits
+            && !(segment_is_unrestricted(ES) && segment_is_unrestricted(ds_or_prefix)))
         && match rep {
             Rep::NZ | Rep::Z => true,
             Rep::None => false,
@@ -395,16 +401,16 @@ unsafe fn string_instruction(
                 Instruction::Ins => {
                     // check fault *before* reading from port
                     // (technically not necessary according to Intel manuals)
-                    break_on_pagefault!(writable_or_pagefault(es + dst, size_bytes));
+                    break_on_pagefault!(writable_or_pagefault_seg(ES, es + dst, size_bytes));
                 },
                 _ => {},
             };
             let src_val = match instruction {
                 Instruction::Movs | Instruction::Cmps | Instruction::Lods | Instruction::Outs => {
                     break_on_pagefault!(match size {
-                        Size::B => safe_read8(ds + src),
-                        Size::W => safe_read16(ds + src),
-                        Size::D => safe_read32s(ds + src),
+                        Size::B => safe_read8_seg(ds_or_prefix, ds + src),
+                        Size::W => safe_read16_seg(ds_or_prefix, ds + src),
+                        Size::D => safe_read32s_seg(ds_or_prefix, ds + src),
                     })
                 },
                 Instruction::Scas | Instruction::Stos => data & size_mask,
@@ -419,9 +425,9 @@ unsafe fn string_instruction(
 
             match instruction {
                 Instruction::Cmps | Instruction::Scas => match size {
-                    Size::B => dst_val = break_on_pagefault!(safe_read8(es + dst)),
-                    Size::W => dst_val = break_on_pagefault!(safe_read16(es + dst)),
-                    Size::D => dst_val = break_on_pagefault!(safe_read32s(es + dst)),
+                    Size::B => dst_val = break_on_pagefault!(safe_read8_seg(ES, es + dst)),
+                    Size::W => dst_val = break_on_pagefault!(safe_read16_seg(ES, es + dst)),
+                    Size::D => dst_val = break_on_pagefault!(safe_read32s_seg(ES, es + dst)),
                 },
                 Instruction::Outs => match size {
                     Size::B => io_port_write8(port, src_val),
@@ -434,9 +440,9 @@ unsafe fn string_instruction(
                     Size::D => write_reg32(EAX, src_val),
                 },
                 Instruction::Movs | Instruction::Stos | Instruction::Ins => match size {
-                    Size::B => break_on_pagefault!(safe_write8(es + dst, src_val)),
-                    Size::W => break_on_pagefault!(safe_write16(es + dst, src_val)),
-                    Size::D => break_on_pagefault!(safe_write32(es + dst, src_val)),
+                    Size::B => break_on_pagefault!(safe_write8_seg(ES, es + dst, src_val)),
+                    Size::W => break_on_pagefault!(safe_write16_seg(ES, es + dst, src_val)),
+                    Size::D => break_on_pagefault!(safe_write32_seg(ES, es + dst, src_val)),
                 },
             };
 
@@ -480,6 +486,15 @@ unsafe fn string_instruction(
                 Rep::None => true,
             };
 
+            let finished = if !finished && debug_trap_pending != 0 {
+                // Stop at a data breakpoint, the instruction is continued after #DB
+                *instruction_pointer = *previous_ip;
+                true
+            }
+            else {
+                finished
+            };
+
             if finished {
                 match instruction {
                     Instruction::Scas | Instruction::Cmps => match size {
@@ -66,4 +66,7 @@ impl CpuContext {
         self.state_flags.is_32() != (self.prefixes & PREFIX_MASK_ADDRSIZE != 0)
     }
     pub fn ssize_32(&self) -> bool { self.state_flags.ssize_32() }
+    pub fn alignment_check(&self) -> bool { self.state_flags.alignment_check() }
+    pub fn protected_mode(&self) -> bool { self.state_flags.protected_mode() }
+    pub fn segment_checks(&self) -> bool { self.state_flags.segment_checks() }
 }
@@ -1,8 +1,14 @@
 pub mod interpreter;
 pub mod interpreter0f;
+pub mod interpreter0f38;
+pub mod interpreter0f3a;
 
 pub mod jit;
 pub mod jit0f;
+pub mod jit0f38;
+pub mod jit0f3a;
 
 pub mod analyzer;
 pub mod analyzer0f;
+pub mod analyzer0f38;
+pub mod analyzer0f3a;
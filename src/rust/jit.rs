@@ -405,9 +405,9 @@ fn jit_find_basic_blocks(
         if is_near_end_of_page(virt_target as u32) {
             return None;
         }
-        let phys_target = match cpu::translate_address_read_no_side_effects(virt_target) {
+        let phys_target = match cpu::translate_address_execute_no_side_effects(virt_target) {
             Err(()) => {
-                dbg_log!("Not analysing {:x} (page not mapped)", virt_target);
+                dbg_log!("Not analysing {:x} (page not mapped or not executable)", virt_target);
                 return None;
             },
             Ok(t) => t,
@@ -492,9 +492,9 @@ fn jit_find_basic_blocks(
     }
 
     while let Some(to_visit) = to_visit_stack.pop() {
-        let phys_addr = match cpu::translate_address_read_no_side_effects(to_visit) {
+        let phys_addr = match cpu::translate_address_execute_no_side_effects(to_visit) {
             Err(()) => {
-                dbg_log!("Not analysing {:x} (page not mapped)", to_visit);
+                dbg_log!("Not analysing {:x} (page not mapped or not executable)", to_visit);
                 continue;
             },
             Ok(phys_addr) => phys_addr,
@@ -820,7 +820,7 @@ fn jit_analyze_and_generate(
     };
 
     dbg_assert!(
-        cpu::translate_address_read_no_side_effects(virt_entry_point).unwrap() == phys_entry_point
+        cpu::translate_address_execute_no_side_effects(virt_entry_point).unwrap() == phys_entry_point
     );
     let virt_page = Page::page_of(virt_entry_point as u32);
     let entry_points: HashSet<i32> = entry_points
@@ -1109,6 +1109,11 @@ pub fn set_tlb_code(
     entries: &Vec<(u16, u16)>,
     state_flags: CachedStateFlags,
 ) {
+    if unsafe { cpu::tlb_data[virt_page.to_u32() as usize] } & cpu::TLB_BREAKPOINT != 0 {
+        // Code in pages with breakpoints is run by the interpreter
+        return;
+    }
+
     let c = match unsafe { cpu::tlb_code[virt_page.to_u32() as usize] } {
         None => {
             let state_table = [u16::MAX; 0x1000];
@@ -2107,8 +2112,14 @@ pub fn jit_increase_hotness_and_maybe_compile(
         if ctx.compiling.is_some() {
             return;
         }
+        if unsafe {
+            cpu::breakpoints_enabled() && cpu::page_has_breakpoint(virt_address as u32 >> 12)
+        } {
+            // Don't compile pages with breakpoints, they are always interpreted
+            return;
+        }
         // only try generating if we're in the correct address space
-        if cpu::translate_address_read_no_side_effects(virt_address) == Ok(phys_address) {
+        if cpu::translate_address_execute_no_side_effects(virt_address) == Ok(phys_address) {
             *hotness = 0;
             jit_analyze_and_generate(ctx, virt_address, phys_address, cs_offset, state_flags)
         }
@@ -2197,7 +2208,9 @@ pub fn jit_dirty_page(ctx: &mut JitState, page: Page) {
                             if wasm_table_index == w {
                                 drop(Box::from_raw(c.as_ptr()));
                                 cpu::tlb_code[page as usize] = None;
-                                if !ctx.entry_points.contains_key(&tlb_physical_page) {
+                                if !ctx.entry_points.contains_key(&tlb_physical_page)
+                                    && !cpu::is_monitored_page(tlb_physical_page)
+                                {
                                     // XXX
                                     cpu::tlb_data[page as usize] &= !cpu::TLB_HAS_CODE;
                                 }
@@ -2259,6 +2272,8 @@ pub fn jit_dirty_page(ctx: &mut JitState, page: Page) {
     if !did_have_code {
         profiler::stat_increment(stat::DIRTY_PAGE_DID_NOT_HAVE_CODE);
     }
+
+    unsafe { cpu::monitor_page_written(page) }
 }
 
 #[no_mangle]
@@ -3,9 +3,11 @@
 use codegen;
 use codegen::{BitSize, ConditionNegate};
 use cpu::cpu::{
-    FLAGS_ALL, FLAGS_DEFAULT, FLAGS_MASK, FLAG_ADJUST, FLAG_CARRY, FLAG_DIRECTION, FLAG_INTERRUPT,
-    FLAG_IOPL, FLAG_OVERFLOW, FLAG_SUB, FLAG_VM, FLAG_ZERO, OPSIZE_16, OPSIZE_32, OPSIZE_8,
+    FLAGS_ALL, FLAGS_DEFAULT, FLAGS_MASK, FLAG_AC, FLAG_ADJUST, FLAG_CARRY, FLAG_DIRECTION,
+    FLAG_INTERRUPT, FLAG_IOPL, FLAG_OVERFLOW, FLAG_SUB, FLAG_TRAP, FLAG_VM, FLAG_ZERO, OPSIZE_16,
+    OPSIZE_32, OPSIZE_8,
 };
+use cpu::cpuid;
 use cpu::global_pointers;
 use jit::{Instruction, InstructionOperand, InstructionOperandDest, JitContext};
 use modrm::{jit_add_seg_offset, jit_add_seg_offset_no_override, ModrmByte};
@@ -15,6 +17,7 @@ use regs;
 use regs::{AX, BP, BX, CX, DI, DX, SI, SP};
 use regs::{CS, DS, ES, FS, GS, SS};
 use regs::{EAX, EBP, EBX, ECX, EDI, EDX, ESI, ESP};
+use vex::{Vex, VexInstruction};
 use wasmgen::wasm_builder::{WasmBuilder, WasmLocal};
 
 enum LocalOrImmediate<'a> {
@@ -102,6 +105,155 @@ pub fn instr16_0F_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
 pub fn instr32_0F_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
     ::gen::jit0f::jit(ctx.cpu.read_imm8() as u32 | 0x100, ctx, instr_flags)
 }
+pub fn instr16_0F38_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
+    ::gen::jit0f38::jit(ctx.cpu.read_imm8() as u32, ctx, instr_flags)
+}
+pub fn instr32_0F38_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
+    ::gen::jit0f38::jit(ctx.cpu.read_imm8() as u32 | 0x100, ctx, instr_flags)
+}
+pub fn instr16_0F3A_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
+    ::gen::jit0f3a::jit(ctx.cpu.read_imm8() as u32, ctx, instr_flags)
+}
+pub fn instr32_0F3A_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
+    ::gen::jit0f3a::jit(ctx.cpu.read_imm8() as u32 | 0x100, ctx, instr_flags)
+}
+pub fn instr16_C4_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
+    les_or_vex_jit(ctx, instr_flags, "instr16_C4_mem")
+}
+pub fn instr32_C4_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
+    les_or_vex_jit(ctx, instr_flags, "instr32_C4_mem")
+}
+fn les_or_vex_jit(ctx: &mut JitContext, instr_flags: &mut u32, les: &str) {
+    let modrm_byte = ctx.cpu.read_imm8();
+    if modrm_byte < 0xC0 {
+        codegen::gen_move_registers_from_locals_to_memory(ctx);
+        let addr = ::modrm::decode(ctx.cpu, modrm_byte);
+        codegen::gen_modrm_resolve_with_segment(ctx, addr);
+        codegen::gen_modrm_fn1(ctx.builder, les, (modrm_byte >> 3 & 7) as u32);
+        codegen::gen_move_registers_from_memory_to_locals(ctx);
+        *instr_flags |= ::jit::JIT_INSTR_BLOCK_BOUNDARY_FLAG;
+    }
+    else if ctx.cpu.protected_mode() {
+        gen_vex(ctx, modrm_byte);
+    }
+    else {
+        codegen::gen_trigger_ud(ctx);
+        *instr_flags |= ::jit::JIT_INSTR_BLOCK_BOUNDARY_FLAG;
+    }
+}
+fn gen_vex(ctx: &mut JitContext, byte1: u8) {
+    let vex = Vex::decode(byte1, ctx.cpu.read_imm8());
+    let opcode = ctx.cpu.read_imm8();
+    let modrm_byte = ctx.cpu.read_imm8();
+    let instruction = vex.instruction(ctx.cpu.prefixes, opcode, modrm_byte);
+    let addr = if modrm_byte < 0xC0 { Some(::modrm::decode(ctx.cpu, modrm_byte)) } else { None };
+    let imm8 = if vex.has_imm8() { ctx.cpu.read_imm8() as i32 } else { 0 };
+
+    if instruction == VexInstruction::Invalid || !cpuid::has_feature(instruction.feature()) {
+        codegen::gen_trigger_ud(ctx);
+        return;
+    }
+
+    match addr {
+        Some(addr) => codegen::gen_modrm_resolve_safe_read32(ctx, addr),
+        None => codegen::gen_get_reg32(ctx, (modrm_byte & 7) as u32),
+    }
+    let source = ctx.builder.set_new_local();
+    let r = (modrm_byte >> 3 & 7) as u32;
+    let v = vex.vvvv as u32;
+
+    match instruction {
+        VexInstruction::Andn => {
+            codegen::gen_get_reg32(ctx, v);
+            ctx.builder.const_i32(-1);
+            ctx.builder.xor_i32();
+            ctx.builder.get_local(&source);
+            ctx.builder.and_i32();
+            codegen::gen_set_reg32(ctx, r);
+
+            let dest = ctx.reg(r);
+            ctx.current_instruction = Instruction::Bitwise {
+                opsize: OPSIZE_32,
+                dest: local_to_instruction_operand(ctx, &dest),
+            };
+            codegen::gen_set_last_result(ctx.builder, &dest);
+            codegen::gen_set_last_op_size_and_flags_changed(
+                ctx.builder,
+                OPSIZE_32,
+                FLAGS_ALL & !FLAG_CARRY & !FLAG_OVERFLOW & !FLAG_ADJUST,
+            );
+            codegen::gen_clear_flags_bits(ctx.builder, FLAG_CARRY | FLAG_OVERFLOW | FLAG_ADJUST);
+        },
+        VexInstruction::Bextr | VexInstruction::Bzhi => {
+            ctx.builder.get_local(&source);
+            codegen::gen_get_reg32(ctx, v);
+            ctx.builder.call_fn2_ret(if instruction == VexInstruction::Bextr {
+                "bextr"
+            }
+            else {
+                "bzhi"
+            });
+            codegen::gen_set_reg32(ctx, r);
+        },
+        VexInstruction::Blsi | VexInstruction::Blsmsk | VexInstruction::Blsr => {
+            ctx.builder.get_local(&source);
+            ctx.builder.call_fn1_ret(match instruction {
+                VexInstruction::Blsi => "blsi",
+                VexInstruction::Blsmsk => "blsmsk",
+                _ => "blsr",
+            });
+            codegen::gen_set_reg32(ctx, v);
+        },
+        VexInstruction::Pdep | VexInstruction::Pext => {
+            codegen::gen_get_reg32(ctx, v);
+            ctx.builder.get_local(&source);
+            ctx.builder.call_fn2_ret(if instruction == VexInstruction::Pdep {
+                "pdep"
+            }
+            else {
+                "pext"
+            });
+            codegen::gen_set_reg32(ctx, r);
+        },
+        VexInstruction::Mulx => {
+            codegen::gen_get_reg32(ctx, regs::EDX);
+            ctx.builder.extend_unsigned_i32_to_i64();
+            ctx.builder.get_local(&source);
+            ctx.builder.extend_unsigned_i32_to_i64();
+            ctx.builder.mul_i64();
+            let result = ctx.builder.tee_new_local_i64();
+            ctx.builder.wrap_i64_to_i32();
+            codegen::gen_set_reg32(ctx, v);
+            // the high half wins if both destinations are the same register
+            ctx.builder.get_local_i64(&result);
+            ctx.builder.const_i64(32);
+            ctx.builder.shr_u_i64();
+            ctx.builder.wrap_i64_to_i32();
+            codegen::gen_set_reg32(ctx, r);
+            ctx.builder.free_local_i64(result);
+        },
+        VexInstruction::Rorx => {
+            ctx.builder.get_local(&source);
+            ctx.builder.const_i32(imm8 & 31);
+            ctx.builder.rotr_i32();
+            codegen::gen_set_reg32(ctx, r);
+        },
+        VexInstruction::Sarx | VexInstruction::Shlx | VexInstruction::Shrx => {
+            // wasm masks the shift count like x86
+            ctx.builder.get_local(&source);
+            codegen::gen_get_reg32(ctx, v);
+            match instruction {
+                VexInstruction::Sarx => ctx.builder.shr_s_i32(),
+                VexInstruction::Shlx => ctx.builder.shl_i32(),
+                _ => ctx.builder.shr_u_i32(),
+            }
+            codegen::gen_set_reg32(ctx, r);
+        },
+        VexInstruction::Invalid => dbg_assert!(false),
+    }
+
+    ctx.builder.free_local(source);
+}
 pub fn instr_26_jit(ctx: &mut JitContext, instr_flags: &mut u32) {
     jit_handle_segment_prefix(ES, ctx, instr_flags)
 }
@@ -170,6 +322,24 @@ fn sse_read64_xmm_xmm(ctx: &mut JitContext, name: &str, r1: u32, r2: u32) {
     ctx.builder.call_fn2_i64_i32(name);
 }
 
+fn sse_read16_xmm_mem(ctx: &mut JitContext, name: &str, modrm_byte: ModrmByte, r: u32) {
+    codegen::gen_modrm_resolve_safe_read16(ctx, modrm_byte);
+    ctx.builder.const_i32(r as i32);
+    ctx.builder.call_fn2(name);
+}
+fn sse_read32_xmm_mem(ctx: &mut JitContext, name: &str, modrm_byte: ModrmByte, r: u32) {
+    codegen::gen_modrm_resolve_safe_read32(ctx, modrm_byte);
+    ctx.builder.const_i32(r as i32);
+    ctx.builder.call_fn2(name);
+}
+fn sse_read32_xmm_xmm(ctx: &mut JitContext, name: &str, r1: u32, r2: u32) {
+    ctx.builder
+        .const_i32(global_pointers::get_reg_xmm_offset(r1) as i32);
+    ctx.builder.load_aligned_i32(0);
+    ctx.builder.const_i32(r2 as i32);
+    ctx.builder.call_fn2(name);
+}
+
 fn sse_read128_xmm_mem(ctx: &mut JitContext, name: &str, modrm_byte: ModrmByte, r: u32) {
     let dest = global_pointers::sse_scratch_register as u32;
     codegen::gen_modrm_resolve_safe_read128(ctx, modrm_byte, dest);
@@ -534,8 +704,8 @@ macro_rules! mask_imm(
 macro_rules! define_instruction_read_write_mem8(
     ($fn:expr, $name_mem:ident, $name_reg:ident, reg) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::BYTE, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::BYTE, seg, &addr, &|ref mut ctx| {
                     let dest_operand = ctx.builder.set_new_local();
                     let source_operand = codegen::gen_get_reg8_or_alias_to_reg32(ctx, r);
                     $fn(ctx, &dest_operand, &LocalOrImmediate::WasmLocal(&source_operand));
@@ -557,8 +727,8 @@ macro_rules! define_instruction_read_write_mem8(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, constant_one) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::BYTE, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::BYTE, seg, &addr, &|ref mut ctx| {
                     ctx.builder.const_i32(1);
                     ctx.builder.call_fn2_ret($fn);
                 });
@@ -575,8 +745,8 @@ macro_rules! define_instruction_read_write_mem8(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, cl) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::BYTE, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::BYTE, seg, &addr, &|ref mut ctx| {
                     codegen::gen_get_reg8(ctx, regs::CL);
                     ctx.builder.const_i32(31);
                     ctx.builder.and_i32();
@@ -597,8 +767,8 @@ macro_rules! define_instruction_read_write_mem8(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, none) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::BYTE, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::BYTE, seg, &addr, &|ref mut ctx| {
                     ctx.builder.call_fn1_ret($fn);
                 });
             });
@@ -613,8 +783,8 @@ macro_rules! define_instruction_read_write_mem8(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, ximm8) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, imm: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::BYTE, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::BYTE, seg, &addr, &|ref mut ctx| {
                     let dest_operand = ctx.builder.set_new_local();
                     $fn(ctx, &dest_operand, &LocalOrImmediate::Immediate(imm as i32));
                     ctx.builder.free_local(dest_operand);
@@ -632,9 +802,9 @@ macro_rules! define_instruction_read_write_mem8(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, $imm:ident) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, imm: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
                 let imm = mask_imm!(imm, $imm) as i32;
-                codegen::gen_safe_read_write(ctx, BitSize::BYTE, &addr, &|ref mut ctx| {
+                codegen::gen_safe_read_write(ctx, BitSize::BYTE, seg, &addr, &|ref mut ctx| {
                     ctx.builder.const_i32(imm as i32);
                     ctx.builder.call_fn2_ret($fn);
                 });
@@ -654,8 +824,8 @@ macro_rules! define_instruction_read_write_mem8(
 macro_rules! define_instruction_read_write_mem16(
     ($fn:expr, $name_mem:ident, $name_reg:ident, reg) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::WORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &addr, &|ref mut ctx| {
                     codegen::gen_get_reg16(ctx, r);
                     ctx.builder.call_fn2_ret($fn);
                 });
@@ -672,8 +842,8 @@ macro_rules! define_instruction_read_write_mem16(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, constant_one) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::WORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &addr, &|ref mut ctx| {
                     ctx.builder.const_i32(1);
                     ctx.builder.call_fn2_ret($fn);
                 });
@@ -690,8 +860,8 @@ macro_rules! define_instruction_read_write_mem16(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, cl) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::WORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &addr, &|ref mut ctx| {
                     codegen::gen_get_reg8(ctx, regs::CL);
                     ctx.builder.const_i32(31);
                     ctx.builder.and_i32();
@@ -712,8 +882,8 @@ macro_rules! define_instruction_read_write_mem16(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, reg, cl) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::WORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &addr, &|ref mut ctx| {
                     codegen::gen_get_reg16(ctx, r);
                     codegen::gen_get_reg8(ctx, regs::CL);
                     ctx.builder.const_i32(31);
@@ -736,9 +906,9 @@ macro_rules! define_instruction_read_write_mem16(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, reg, $imm:ident) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
                 let imm = mask_imm!(imm, $imm);
-                codegen::gen_safe_read_write(ctx, BitSize::WORD, &addr, &|ref mut ctx| {
+                codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &addr, &|ref mut ctx| {
                     codegen::gen_get_reg16(ctx, r);
                     ctx.builder.const_i32(imm as i32);
                     ctx.builder.call_fn3_ret($fn);
@@ -758,8 +928,8 @@ macro_rules! define_instruction_read_write_mem16(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, none) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::WORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &addr, &|ref mut ctx| {
                     let mut dest_operand = ctx.builder.set_new_local();
                     $fn(ctx, &mut dest_operand);
                     ctx.builder.get_local(&dest_operand);
@@ -775,9 +945,9 @@ macro_rules! define_instruction_read_write_mem16(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, $imm:ident) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, imm: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
                 let imm = mask_imm!(imm, $imm) as i32;
-                codegen::gen_safe_read_write(ctx, BitSize::WORD, &addr, &|ref mut ctx| {
+                codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &addr, &|ref mut ctx| {
                     ctx.builder.const_i32(imm as i32);
                     ctx.builder.call_fn2_ret($fn);
                 });
@@ -797,8 +967,8 @@ macro_rules! define_instruction_read_write_mem16(
 macro_rules! define_instruction_read_write_mem32(
     ($fn:expr, $name_mem:ident, $name_reg:ident, reg) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::DWORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &addr, &|ref mut ctx| {
                     let dest_operand = ctx.builder.set_new_local();
                     $fn(
                         ctx,
@@ -822,8 +992,8 @@ macro_rules! define_instruction_read_write_mem32(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, constant_one) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::DWORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &addr, &|ref mut ctx| {
                     let dest_operand = ctx.builder.set_new_local();
                     $fn(ctx, &dest_operand, &LocalOrImmediate::Immediate(1));
                     ctx.builder.get_local(&dest_operand);
@@ -839,8 +1009,8 @@ macro_rules! define_instruction_read_write_mem32(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, cl) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::DWORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &addr, &|ref mut ctx| {
                     let dest_operand = ctx.builder.set_new_local();
                     $fn(
                         ctx,
@@ -864,8 +1034,8 @@ macro_rules! define_instruction_read_write_mem32(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, reg, cl) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::DWORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &addr, &|ref mut ctx| {
                     codegen::gen_get_reg32(ctx, r);
                     codegen::gen_get_reg8(ctx, regs::CL);
                     ctx.builder.const_i32(31);
@@ -888,9 +1058,9 @@ macro_rules! define_instruction_read_write_mem32(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, reg, $imm:ident) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
                 let imm = mask_imm!(imm, $imm) as i32;
-                codegen::gen_safe_read_write(ctx, BitSize::DWORD, &addr, &|ref mut ctx| {
+                codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &addr, &|ref mut ctx| {
                     codegen::gen_get_reg32(ctx, r);
                     ctx.builder.const_i32(imm as i32);
                     ctx.builder.call_fn3_ret($fn);
@@ -910,8 +1080,8 @@ macro_rules! define_instruction_read_write_mem32(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, none) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-                codegen::gen_safe_read_write(ctx, BitSize::DWORD, &addr, &|ref mut ctx| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+                codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &addr, &|ref mut ctx| {
                     let mut dest_operand = ctx.builder.set_new_local();
                     $fn(ctx, &mut dest_operand);
                     ctx.builder.get_local(&dest_operand);
@@ -927,9 +1097,9 @@ macro_rules! define_instruction_read_write_mem32(
 
     ($fn:expr, $name_mem:ident, $name_reg:ident, $imm:ident) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, imm: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
                 let imm = mask_imm!(imm, $imm);
-                codegen::gen_safe_read_write(ctx, BitSize::DWORD, &addr, &|ref mut ctx| {
+                codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &addr, &|ref mut ctx| {
                     let dest_operand = ctx.builder.set_new_local();
                     $fn(
                         ctx,
@@ -2106,6 +2276,7 @@ fn gen_bit_rmw(
     opsize: i32,
 ) {
     dbg_assert!(opsize == 16 || opsize == 32);
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     match source_operand {
         LocalOrImmediate::WasmLocal(l) => {
@@ -2126,7 +2297,7 @@ fn gen_bit_rmw(
         },
     }
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read_write(ctx, BitSize::BYTE, &address_local, &|ref mut ctx| {
+    codegen::gen_safe_read_write(ctx, BitSize::BYTE, seg, &address_local, &|ref mut ctx| {
         let value_local = ctx.builder.set_new_local();
         op(ctx.builder, &value_local, source_operand, 7);
         ctx.builder.get_local(&value_local);
@@ -2151,22 +2322,35 @@ fn gen_bsr32(ctx: &mut JitContext, dest_operand: &WasmLocal, source_operand: &Lo
 
 fn gen_bswap(ctx: &mut JitContext, reg: i32) {
     let l = &ctx.register_locals[reg as usize];
+    gen_bswap32_local(ctx.builder, l);
+    ctx.builder.set_local(l);
+}
 
-    ctx.builder.get_local(l);
-    ctx.builder.const_i32(8);
-    ctx.builder.rotl_i32();
-    ctx.builder.const_i32(0xFF00FF);
-    ctx.builder.and_i32();
+fn gen_bswap32_local(builder: &mut WasmBuilder, l: &WasmLocal) {
+    builder.get_local(l);
+    builder.const_i32(8);
+    builder.rotl_i32();
+    builder.const_i32(0xFF00FF);
+    builder.and_i32();
 
-    ctx.builder.get_local(l);
-    ctx.builder.const_i32(24);
-    ctx.builder.rotl_i32();
-    ctx.builder.const_i32(0xFF00FF00u32 as i32);
-    ctx.builder.and_i32();
+    builder.get_local(l);
+    builder.const_i32(24);
+    builder.rotl_i32();
+    builder.const_i32(0xFF00FF00u32 as i32);
+    builder.and_i32();
 
-    ctx.builder.or_i32();
+    builder.or_i32();
+}
 
-    ctx.builder.set_local(l);
+fn gen_bswap16_local(builder: &mut WasmBuilder, l: &WasmLocal) {
+    // the upper half of the local is zero
+    builder.get_local(l);
+    builder.const_i32(8);
+    builder.shl_i32();
+    builder.get_local(l);
+    builder.const_i32(8);
+    builder.shr_u_i32();
+    builder.or_i32();
 }
 
 define_instruction_read_write_mem8!(gen_add8, instr_00_mem_jit, instr_00_reg_jit, reg);
@@ -2828,8 +3012,8 @@ define_instruction_read16!(gen_test16, instr16_85_mem_jit, instr16_85_reg_jit);
 define_instruction_read32!(gen_test32, instr32_85_mem_jit, instr32_85_reg_jit);
 
 pub fn instr_86_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-        codegen::gen_safe_read_write(ctx, BitSize::BYTE, &addr, &|ref mut ctx| {
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        codegen::gen_safe_read_write(ctx, BitSize::BYTE, seg, &addr, &|ref mut ctx| {
             codegen::gen_get_reg8(ctx, r);
             let tmp = ctx.builder.set_new_local();
             codegen::gen_set_reg8(ctx, r);
@@ -2848,8 +3032,8 @@ pub fn instr_86_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     ctx.builder.free_local(tmp);
 }
 pub fn instr16_87_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-        codegen::gen_safe_read_write(ctx, BitSize::WORD, &addr, &|ref mut ctx| {
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &addr, &|ref mut ctx| {
             codegen::gen_get_reg16(ctx, r);
             let tmp = ctx.builder.set_new_local();
             codegen::gen_set_reg16(ctx, r);
@@ -2859,8 +3043,8 @@ pub fn instr16_87_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
     });
 }
 pub fn instr32_87_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-        codegen::gen_safe_read_write(ctx, BitSize::DWORD, &addr, &|ref mut ctx| {
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &addr, &|ref mut ctx| {
             codegen::gen_get_reg32(ctx, r);
             let tmp = ctx.builder.set_new_local();
             codegen::gen_set_reg32(ctx, r);
@@ -2889,10 +3073,10 @@ pub fn instr32_87_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr_88_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
         codegen::gen_get_reg8(ctx, r);
         let value_local = ctx.builder.set_new_local();
-        codegen::gen_safe_write8(ctx, &addr, &value_local);
+        codegen::gen_safe_write8(ctx, seg, &addr, &value_local);
         ctx.builder.free_local(value_local);
     });
 }
@@ -2901,16 +3085,16 @@ pub fn instr_88_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr16_89_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-        codegen::gen_safe_write16(ctx, addr, &ctx.reg(r));
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        codegen::gen_safe_write16(ctx, seg, addr, &ctx.reg(r));
     });
 }
 pub fn instr16_89_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     codegen::gen_set_reg16_r(ctx, r1, r2);
 }
 pub fn instr32_89_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
-        codegen::gen_safe_write32(ctx, &addr, &ctx.reg(r));
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        codegen::gen_safe_write32(ctx, seg, &addr, &ctx.reg(r));
     });
 }
 pub fn instr32_89_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
@@ -2942,6 +3126,7 @@ pub fn instr32_8B_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr16_8C_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     if r >= 6 {
@@ -2950,12 +3135,13 @@ pub fn instr16_8C_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
     else {
         codegen::gen_get_sreg(ctx, r);
         let value_local = ctx.builder.set_new_local();
-        codegen::gen_safe_write16(ctx, &address_local, &value_local);
+        codegen::gen_safe_write16(ctx, seg, &address_local, &value_local);
         ctx.builder.free_local(value_local);
     }
     ctx.builder.free_local(address_local);
 }
 pub fn instr32_8C_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     if r >= 6 {
@@ -2964,7 +3150,7 @@ pub fn instr32_8C_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
     else {
         codegen::gen_get_sreg(ctx, r);
         let value_local = ctx.builder.set_new_local();
-        codegen::gen_safe_write16(ctx, &address_local, &value_local);
+        codegen::gen_safe_write16(ctx, seg, &address_local, &value_local);
         ctx.builder.free_local(value_local);
     }
     ctx.builder.free_local(address_local);
@@ -3010,6 +3196,7 @@ pub fn instr32_8D_reg_jit(ctx: &mut JitContext, _r1: u32, _r2: u32) {
 }
 
 pub fn instr16_8F_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve_with_esp_offset(ctx, modrm_byte, 2);
     let address_local = ctx.builder.set_new_local();
 
@@ -3019,7 +3206,7 @@ pub fn instr16_8F_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     // undo the esp change of pop, as safe_write16 can fail
     codegen::gen_adjust_stack_reg(ctx, (-2i32) as u32);
 
-    codegen::gen_safe_write16(ctx, &address_local, &value_local);
+    codegen::gen_safe_write16(ctx, seg, &address_local, &value_local);
 
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
@@ -3029,6 +3216,7 @@ pub fn instr16_8F_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
 }
 pub fn instr16_8F_0_reg_jit(ctx: &mut JitContext, r: u32) { pop16_reg_jit(ctx, r); }
 pub fn instr32_8F_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve_with_esp_offset(ctx, modrm_byte, 4);
     let address_local = ctx.builder.set_new_local();
 
@@ -3036,7 +3224,7 @@ pub fn instr32_8F_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     let value_local = ctx.builder.set_new_local();
     codegen::gen_adjust_stack_reg(ctx, (-4i32) as u32);
 
-    codegen::gen_safe_write32(ctx, &address_local, &value_local);
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
 
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
@@ -3416,9 +3604,9 @@ pub fn instr_D7_jit(ctx: &mut JitContext) {
         ctx.builder.const_i32(0xFFFF);
         ctx.builder.and_i32();
     }
-    jit_add_seg_offset(ctx, regs::DS);
+    let seg = jit_add_seg_offset(ctx, regs::DS);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read8(ctx, &address_local);
+    codegen::gen_safe_read8(ctx, seg, &address_local);
     ctx.builder.free_local(address_local);
     codegen::gen_set_reg8(ctx, regs::AL);
 }
@@ -3514,12 +3702,13 @@ pub fn instr32_D9_1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
 }
 
 pub fn instr16_D9_2_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret("f80_to_f32");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &value_local);
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
 }
@@ -3534,12 +3723,13 @@ pub fn instr32_D9_2_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
 }
 
 pub fn instr16_D9_3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret("f80_to_f32");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &value_local);
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
     codegen::gen_fn0_const(ctx.builder, "fpu_pop");
@@ -3552,8 +3742,8 @@ pub fn instr32_D9_3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     instr16_D9_3_mem_jit(ctx, modrm_byte)
 }
 
-pub fn instr16_D9_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    codegen::gen_modrm_resolve(ctx, modrm_byte);
+fn fpu_env_jit(ctx: &mut JitContext, name: &str, modrm_byte: ModrmByte) {
+    codegen::gen_modrm_resolve_with_segment(ctx, modrm_byte);
 
     codegen::gen_set_previous_eip_offset_from_eip_with_low_bits(
         ctx.builder,
@@ -3561,7 +3751,7 @@ pub fn instr16_D9_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     );
 
     codegen::gen_move_registers_from_locals_to_memory(ctx);
-    ctx.builder.call_fn1("fpu_fldenv32");
+    ctx.builder.call_fn2(name);
     codegen::gen_move_registers_from_memory_to_locals(ctx);
 
     codegen::gen_get_page_fault(ctx.builder);
@@ -3570,6 +3760,10 @@ pub fn instr16_D9_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     ctx.builder.br(ctx.exit_label);
     ctx.builder.block_end();
 }
+
+pub fn instr16_D9_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    fpu_env_jit(ctx, "fpu_fldenv16", modrm_byte)
+}
 pub fn instr16_D9_4_reg_jit(ctx: &mut JitContext, r: u32) {
     match r {
         0 | 1 | 4 | 5 => {
@@ -3581,7 +3775,7 @@ pub fn instr16_D9_4_reg_jit(ctx: &mut JitContext, r: u32) {
 }
 pub fn instr32_D9_4_reg_jit(ctx: &mut JitContext, r: u32) { instr16_D9_4_reg_jit(ctx, r) }
 pub fn instr32_D9_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    instr16_D9_4_mem_jit(ctx, modrm_byte)
+    fpu_env_jit(ctx, "fpu_fldenv32", modrm_byte)
 }
 
 pub fn instr16_D9_5_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
@@ -3602,39 +3796,25 @@ pub fn instr32_D9_5_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
 }
 
 pub fn instr16_D9_6_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    codegen::gen_modrm_resolve(ctx, modrm_byte);
-
-    codegen::gen_set_previous_eip_offset_from_eip_with_low_bits(
-        ctx.builder,
-        ctx.start_of_current_instruction as i32 & 0xFFF,
-    );
-
-    codegen::gen_move_registers_from_locals_to_memory(ctx);
-    ctx.builder.call_fn1("fpu_fstenv32");
-    codegen::gen_move_registers_from_memory_to_locals(ctx);
-
-    codegen::gen_get_page_fault(ctx.builder);
-    ctx.builder.if_void();
-    codegen::gen_debug_track_jit_exit(ctx.builder, ctx.start_of_current_instruction);
-    ctx.builder.br(ctx.exit_label);
-    ctx.builder.block_end();
+    fpu_env_jit(ctx, "fpu_fstenv16", modrm_byte)
 }
 pub fn instr16_D9_6_reg_jit(ctx: &mut JitContext, r: u32) {
     codegen::gen_fn1_const(ctx.builder, "instr16_D9_6_reg", r);
 }
 pub fn instr32_D9_6_reg_jit(ctx: &mut JitContext, r: u32) { instr16_D9_6_reg_jit(ctx, r) }
 pub fn instr32_D9_6_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    instr16_D9_6_mem_jit(ctx, modrm_byte)
+    fpu_env_jit(ctx, "fpu_fstenv32", modrm_byte)
 }
 
 pub fn instr16_D9_7_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     ctx.builder
         .const_i32(global_pointers::fpu_control_word as i32);
     ctx.builder.load_aligned_u16(0);
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write16(ctx, &address_local, &value_local);
+    codegen::gen_safe_write16(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
 }
@@ -3711,12 +3891,13 @@ pub fn instr_DB_0_reg_jit(ctx: &mut JitContext, r: u32) {
 }
 
 pub fn instr_DB_1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret("fpu_truncate_to_i32");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &value_local);
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
     codegen::gen_fn0_const(ctx.builder, "fpu_pop");
@@ -3726,12 +3907,13 @@ pub fn instr_DB_1_reg_jit(ctx: &mut JitContext, r: u32) {
 }
 
 pub fn instr_DB_2_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret("fpu_convert_to_i32");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &value_local);
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
 }
@@ -3739,12 +3921,13 @@ pub fn instr_DB_2_reg_jit(ctx: &mut JitContext, r: u32) {
     codegen::gen_fn1_const(ctx.builder, "instr_DB_2_reg", r);
 }
 pub fn instr_DB_3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret("fpu_convert_to_i32");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &value_local);
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
     codegen::gen_fn0_const(ctx.builder, "fpu_pop");
@@ -3754,7 +3937,7 @@ pub fn instr_DB_3_reg_jit(ctx: &mut JitContext, r: u32) {
 }
 
 pub fn instr_DB_5_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    codegen::gen_modrm_resolve(ctx, modrm_byte);
+    codegen::gen_modrm_resolve_with_segment(ctx, modrm_byte);
 
     codegen::gen_set_previous_eip_offset_from_eip_with_low_bits(
         ctx.builder,
@@ -3762,7 +3945,7 @@ pub fn instr_DB_5_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     );
 
     codegen::gen_move_registers_from_locals_to_memory(ctx);
-    ctx.builder.call_fn1("fpu_fldm80");
+    ctx.builder.call_fn2("fpu_fldm80");
     codegen::gen_move_registers_from_memory_to_locals(ctx);
 
     codegen::gen_get_page_fault(ctx.builder);
@@ -3861,12 +4044,13 @@ pub fn instr32_DD_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
 }
 
 pub fn instr16_DD_1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret_i64("fpu_truncate_to_i64");
     let value_local = ctx.builder.set_new_local_i64();
-    codegen::gen_safe_write64(ctx, &address_local, &value_local);
+    codegen::gen_safe_write64(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local_i64(value_local);
     codegen::gen_fn0_const(ctx.builder, "fpu_pop");
@@ -3880,12 +4064,13 @@ pub fn instr32_DD_1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
 }
 
 pub fn instr16_DD_2_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret_i64("f80_to_f64");
     let value_local = ctx.builder.set_new_local_i64();
-    codegen::gen_safe_write64(ctx, &address_local, &value_local);
+    codegen::gen_safe_write64(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local_i64(value_local);
 }
@@ -3898,12 +4083,13 @@ pub fn instr32_DD_2_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
 }
 
 pub fn instr16_DD_3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret_i64("f80_to_f64");
     let value_local = ctx.builder.set_new_local_i64();
-    codegen::gen_safe_write64(ctx, &address_local, &value_local);
+    codegen::gen_safe_write64(ctx, seg, &address_local, &value_local);
     codegen::gen_fn0_const(ctx.builder, "fpu_pop");
     ctx.builder.free_local(address_local);
     ctx.builder.free_local_i64(value_local);
@@ -4002,12 +4188,13 @@ pub fn instr_DE_7_reg_jit(ctx: &mut JitContext, r: u32) {
 }
 
 pub fn instr_DF_1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret("fpu_truncate_to_i16");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write16(ctx, &address_local, &value_local);
+    codegen::gen_safe_write16(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
     codegen::gen_fn0_const(ctx.builder, "fpu_pop");
@@ -4017,12 +4204,13 @@ pub fn instr_DF_1_reg_jit(ctx: &mut JitContext, r: u32) {
 }
 
 pub fn instr_DF_2_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret("fpu_convert_to_i16");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write16(ctx, &address_local, &value_local);
+    codegen::gen_safe_write16(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
 }
@@ -4030,12 +4218,13 @@ pub fn instr_DF_2_reg_jit(ctx: &mut JitContext, r: u32) {
     codegen::gen_fn1_const(ctx.builder, "fpu_fstp", r);
 }
 pub fn instr_DF_3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret("fpu_convert_to_i16");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write16(ctx, &address_local, &value_local);
+    codegen::gen_safe_write16(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
     codegen::gen_fn0_const(ctx.builder, "fpu_pop");
@@ -4068,7 +4257,7 @@ pub fn instr_DF_5_reg_jit(ctx: &mut JitContext, r: u32) {
 }
 
 pub fn instr_DF_6_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
-    codegen::gen_modrm_resolve(ctx, modrm_byte);
+    codegen::gen_modrm_resolve_with_segment(ctx, modrm_byte);
 
     codegen::gen_set_previous_eip_offset_from_eip_with_low_bits(
         ctx.builder,
@@ -4076,7 +4265,7 @@ pub fn instr_DF_6_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     );
 
     codegen::gen_move_registers_from_locals_to_memory(ctx);
-    ctx.builder.call_fn1("fpu_fbstp");
+    ctx.builder.call_fn2("fpu_fbstp");
     codegen::gen_move_registers_from_memory_to_locals(ctx);
 
     codegen::gen_get_page_fault(ctx.builder);
@@ -4092,12 +4281,13 @@ pub fn instr_DF_6_reg_jit(ctx: &mut JitContext, r: u32) {
 
 pub fn instr_DF_7_reg_jit(ctx: &mut JitContext, _r: u32) { codegen::gen_trigger_ud(ctx); }
 pub fn instr_DF_7_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     codegen::gen_fpu_get_sti(ctx, 0);
     ctx.builder.call_fn2_i64_i32_ret_i64("fpu_convert_to_i64");
     let value_local = ctx.builder.set_new_local_i64();
-    codegen::gen_safe_write64(ctx, &address_local, &value_local);
+    codegen::gen_safe_write64(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local_i64(value_local);
     codegen::gen_fn0_const(ctx.builder, "fpu_pop");
@@ -4601,7 +4791,6 @@ fn gen_popf(ctx: &mut JitContext, is_32: bool) {
     ctx.builder.call_fn1("update_eflags");
 
     ctx.builder.get_local(&old_eflags);
-    ctx.builder.free_local(old_eflags);
     ctx.builder.const_i32(FLAG_INTERRUPT);
     ctx.builder.and_i32();
     ctx.builder.eqz_i32();
@@ -4613,6 +4802,26 @@ fn gen_popf(ctx: &mut JitContext, is_32: bool) {
     ctx.builder.eqz_i32();
 
     ctx.builder.and_i32();
+
+    // Leave jitted code when the trap flag has been set, single-stepping is handled by the
+    // interpreter
+    codegen::gen_get_flags(ctx.builder);
+    ctx.builder.const_i32(FLAG_TRAP);
+    ctx.builder.and_i32();
+    ctx.builder.or_i32();
+
+    if ctx.cpu.cpl3() {
+        // Toggling the alignment check flag changes the state flags, leave jitted code so that
+        // code compiled for the new state is used
+        codegen::gen_get_flags(ctx.builder);
+        ctx.builder.get_local(&old_eflags);
+        ctx.builder.xor_i32();
+        ctx.builder.const_i32(FLAG_AC);
+        ctx.builder.and_i32();
+        ctx.builder.or_i32();
+    }
+    ctx.builder.free_local(old_eflags);
+
     ctx.builder.if_void();
     {
         codegen::gen_set_eip_to_after_current_instruction(ctx);
@@ -4654,48 +4863,48 @@ pub fn instr_9F_jit(ctx: &mut JitContext) {
 
 pub fn instr_A0_jit(ctx: &mut JitContext, immaddr: u32) {
     ctx.builder.const_i32(immaddr as i32);
-    jit_add_seg_offset(ctx, regs::DS);
+    let seg = jit_add_seg_offset(ctx, regs::DS);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read8(ctx, &address_local);
+    codegen::gen_safe_read8(ctx, seg, &address_local);
     ctx.builder.free_local(address_local);
     codegen::gen_set_reg8(ctx, regs::AL);
 }
 pub fn instr16_A1_jit(ctx: &mut JitContext, immaddr: u32) {
     ctx.builder.const_i32(immaddr as i32);
-    jit_add_seg_offset(ctx, regs::DS);
+    let seg = jit_add_seg_offset(ctx, regs::DS);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read16(ctx, &address_local);
+    codegen::gen_safe_read16(ctx, seg, &address_local);
     ctx.builder.free_local(address_local);
     codegen::gen_set_reg16(ctx, regs::AX);
 }
 pub fn instr32_A1_jit(ctx: &mut JitContext, immaddr: u32) {
     ctx.builder.const_i32(immaddr as i32);
-    jit_add_seg_offset(ctx, regs::DS);
+    let seg = jit_add_seg_offset(ctx, regs::DS);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read32(ctx, &address_local);
+    codegen::gen_safe_read32(ctx, seg, &address_local);
     ctx.builder.free_local(address_local);
     codegen::gen_set_reg32(ctx, regs::EAX);
 }
 
 pub fn instr_A2_jit(ctx: &mut JitContext, immaddr: u32) {
     ctx.builder.const_i32(immaddr as i32);
-    jit_add_seg_offset(ctx, regs::DS);
+    let seg = jit_add_seg_offset(ctx, regs::DS);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write8(ctx, &address_local, &ctx.reg(regs::EAX));
+    codegen::gen_safe_write8(ctx, seg, &address_local, &ctx.reg(regs::EAX));
     ctx.builder.free_local(address_local);
 }
 pub fn instr16_A3_jit(ctx: &mut JitContext, immaddr: u32) {
     ctx.builder.const_i32(immaddr as i32);
-    jit_add_seg_offset(ctx, regs::DS);
+    let seg = jit_add_seg_offset(ctx, regs::DS);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write16(ctx, &address_local, &ctx.reg(regs::EAX));
+    codegen::gen_safe_write16(ctx, seg, &address_local, &ctx.reg(regs::EAX));
     ctx.builder.free_local(address_local);
 }
 pub fn instr32_A3_jit(ctx: &mut JitContext, immaddr: u32) {
     ctx.builder.const_i32(immaddr as i32);
-    jit_add_seg_offset(ctx, regs::DS);
+    let seg = jit_add_seg_offset(ctx, regs::DS);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &ctx.reg(regs::EAX));
+    codegen::gen_safe_write32(ctx, seg, &address_local, &ctx.reg(regs::EAX));
     ctx.builder.free_local(address_local);
 }
 
@@ -4745,20 +4954,20 @@ fn gen_string_ins(ctx: &mut JitContext, ins: String, size: u8, prefix: u8) {
                 else {
                     codegen::gen_get_reg16(ctx, regs::ESI);
                 }
-                jit_add_seg_offset(ctx, regs::DS);
+                let seg = jit_add_seg_offset(ctx, regs::DS);
                 let address_local = ctx.builder.set_new_local();
                 if size == 8 {
-                    codegen::gen_safe_read8(ctx, &address_local);
+                    codegen::gen_safe_read8(ctx, seg, &address_local);
                     ctx.builder.free_local(address_local);
                     codegen::gen_set_reg8_unmasked(ctx, regs::AL);
                 }
                 else if size == 16 {
-                    codegen::gen_safe_read16(ctx, &address_local);
+                    codegen::gen_safe_read16(ctx, seg, &address_local);
                     ctx.builder.free_local(address_local);
                     codegen::gen_set_reg16(ctx, regs::AX);
                 }
                 else {
-                    codegen::gen_safe_read32(ctx, &address_local);
+                    codegen::gen_safe_read32(ctx, seg, &address_local);
                     ctx.builder.free_local(address_local);
                     codegen::gen_set_reg32(ctx, regs::EAX);
                 }
@@ -4784,7 +4993,7 @@ fn gen_string_ins(ctx: &mut JitContext, ins: String, size: u8, prefix: u8) {
                 jit_add_seg_offset_no_override(ctx, regs::ES);
                 let address_local = ctx.builder.set_new_local();
                 if size == 8 {
-                    codegen::gen_safe_read8(ctx, &address_local);
+                    codegen::gen_safe_read8(ctx, regs::ES, &address_local);
                     ctx.builder.free_local(address_local);
                     let value = ctx.builder.set_new_local();
                     gen_cmp8(
@@ -4795,7 +5004,7 @@ fn gen_string_ins(ctx: &mut JitContext, ins: String, size: u8, prefix: u8) {
                     ctx.builder.free_local(value);
                 }
                 else if size == 16 {
-                    codegen::gen_safe_read16(ctx, &address_local);
+                    codegen::gen_safe_read16(ctx, regs::ES, &address_local);
                     ctx.builder.free_local(address_local);
                     let value = ctx.builder.set_new_local();
                     gen_cmp16(
@@ -4806,7 +5015,7 @@ fn gen_string_ins(ctx: &mut JitContext, ins: String, size: u8, prefix: u8) {
                     ctx.builder.free_local(value);
                 }
                 else {
-                    codegen::gen_safe_read32(ctx, &address_local);
+                    codegen::gen_safe_read32(ctx, regs::ES, &address_local);
                     ctx.builder.free_local(address_local);
                     let value = ctx.builder.set_new_local();
                     gen_cmp32(
@@ -4838,15 +5047,15 @@ fn gen_string_ins(ctx: &mut JitContext, ins: String, size: u8, prefix: u8) {
                 jit_add_seg_offset_no_override(ctx, regs::ES);
                 let address_local = ctx.builder.set_new_local();
                 if size == 8 {
-                    codegen::gen_safe_write8(ctx, &address_local, &ctx.reg(regs::AL));
+                    codegen::gen_safe_write8(ctx, regs::ES, &address_local, &ctx.reg(regs::AL));
                     ctx.builder.free_local(address_local);
                 }
                 else if size == 16 {
-                    codegen::gen_safe_write16(ctx, &address_local, &ctx.reg(regs::AX));
+                    codegen::gen_safe_write16(ctx, regs::ES, &address_local, &ctx.reg(regs::AX));
                     ctx.builder.free_local(address_local);
                 }
                 else {
-                    codegen::gen_safe_write32(ctx, &address_local, &ctx.reg(regs::EAX));
+                    codegen::gen_safe_write32(ctx, regs::ES, &address_local, &ctx.reg(regs::EAX));
                     ctx.builder.free_local(address_local);
                 }
 
@@ -4877,28 +5086,28 @@ fn gen_string_ins(ctx: &mut JitContext, ins: String, size: u8, prefix: u8) {
                 else {
                     codegen::gen_get_reg16(ctx, regs::ESI);
                 }
-                jit_add_seg_offset(ctx, regs::DS);
+                let seg = jit_add_seg_offset(ctx, regs::DS);
                 let source_address = ctx.builder.set_new_local();
 
                 if size == 8 {
-                    codegen::gen_safe_read8(ctx, &source_address);
+                    codegen::gen_safe_read8(ctx, seg, &source_address);
                     ctx.builder.free_local(source_address);
                     let value = ctx.builder.set_new_local();
-                    codegen::gen_safe_write8(ctx, &dest_address, &value);
+                    codegen::gen_safe_write8(ctx, regs::ES, &dest_address, &value);
                     ctx.builder.free_local(value);
                 }
                 else if size == 16 {
-                    codegen::gen_safe_read16(ctx, &source_address);
+                    codegen::gen_safe_read16(ctx, seg, &source_address);
                     ctx.builder.free_local(source_address);
                     let value = ctx.builder.set_new_local();
-                    codegen::gen_safe_write16(ctx, &dest_address, &value);
+                    codegen::gen_safe_write16(ctx, regs::ES, &dest_address, &value);
                     ctx.builder.free_local(value);
                 }
                 else {
-                    codegen::gen_safe_read32(ctx, &source_address);
+                    codegen::gen_safe_read32(ctx, seg, &source_address);
                     ctx.builder.free_local(source_address);
                     let value = ctx.builder.set_new_local();
-                    codegen::gen_safe_write32(ctx, &dest_address, &value);
+                    codegen::gen_safe_write32(ctx, regs::ES, &dest_address, &value);
                     ctx.builder.free_local(value);
                 }
 
@@ -5088,6 +5297,31 @@ pub fn instr_0F31_jit(ctx: &mut JitContext) {
     ctx.builder.block_end();
 }
 
+pub fn instr16_0F01_1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    codegen::gen_modrm_resolve_with_segment(ctx, modrm_byte);
+    codegen::gen_modrm_fn0(ctx.builder, "instr16_0F01_1_mem");
+    codegen::gen_move_registers_from_memory_to_locals(ctx);
+}
+pub fn instr32_0F01_1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    codegen::gen_modrm_resolve_with_segment(ctx, modrm_byte);
+    codegen::gen_modrm_fn0(ctx.builder, "instr32_0F01_1_mem");
+    codegen::gen_move_registers_from_memory_to_locals(ctx);
+}
+pub fn instr16_0F01_1_reg_jit(ctx: &mut JitContext, r: u32) {
+    // the interpreter reads the address size and segment of monitor from the prefixes
+    let prefix = ctx.cpu.prefixes & PREFIX_MASK_SEGMENT;
+    let seg = if prefix != 0 { (prefix - 1) as u32 } else { regs::DS };
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    ctx.builder.const_i32(r as i32);
+    ctx.builder.const_i32(ctx.cpu.asize_32() as i32);
+    ctx.builder.const_i32(seg as i32);
+    ctx.builder.call_fn3("instr_0F01_1_reg");
+    codegen::gen_move_registers_from_memory_to_locals(ctx);
+}
+pub fn instr32_0F01_1_reg_jit(ctx: &mut JitContext, r: u32) { instr16_0F01_1_reg_jit(ctx, r) }
+
 pub fn instr_0F0B_jit(ctx: &mut JitContext) { codegen::gen_trigger_ud(ctx) }
 
 pub fn instr_0F18_mem_jit(_ctx: &mut JitContext, _modrm_byte: ModrmByte, _reg: u32) {}
@@ -5172,9 +5406,10 @@ pub fn instr16_0FB1_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     codegen::gen_set_reg16(ctx, r1);
 }
 pub fn instr16_0FB1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read_write(ctx, BitSize::WORD, &address_local, &|ref mut ctx| {
+    codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &address_local, &|ref mut ctx| {
         ctx.builder.const_i32(r as i32);
         codegen::gen_move_registers_from_locals_to_memory(ctx);
         ctx.builder.call_fn2_ret("cmpxchg16");
@@ -5189,9 +5424,10 @@ pub fn instr32_0FB1_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     codegen::gen_set_reg32(ctx, r1);
 }
 pub fn instr32_0FB1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read_write(ctx, BitSize::DWORD, &address_local, &|ref mut ctx| {
+    codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &address_local, &|ref mut ctx| {
         gen_cmpxchg32(ctx, r);
     });
     ctx.builder.free_local(address_local);
@@ -5258,6 +5494,71 @@ define_instruction_write_reg32!(gen_bsf32, instr32_0FBC_mem_jit, instr32_0FBC_re
 define_instruction_write_reg16!("bsr16", instr16_0FBD_mem_jit, instr16_0FBD_reg_jit);
 define_instruction_write_reg32!(gen_bsr32, instr32_0FBD_mem_jit, instr32_0FBD_reg_jit);
 
+pub fn instr16_F30FBC_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    if !cpuid::has_feature(cpuid::FEATURE_BMI1) {
+        return instr16_0FBC_mem_jit(ctx, modrm_byte, r);
+    }
+    codegen::gen_modrm_resolve_safe_read16(ctx, modrm_byte);
+    ctx.builder.call_fn1_ret("tzcnt16");
+    codegen::gen_set_reg16(ctx, r);
+}
+pub fn instr16_F30FBC_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    if !cpuid::has_feature(cpuid::FEATURE_BMI1) {
+        return instr16_0FBC_reg_jit(ctx, r1, r2);
+    }
+    codegen::gen_get_reg16(ctx, r1);
+    ctx.builder.call_fn1_ret("tzcnt16");
+    codegen::gen_set_reg16(ctx, r2);
+}
+pub fn instr32_F30FBC_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    if !cpuid::has_feature(cpuid::FEATURE_BMI1) {
+        return instr32_0FBC_mem_jit(ctx, modrm_byte, r);
+    }
+    codegen::gen_modrm_resolve_safe_read32(ctx, modrm_byte);
+    ctx.builder.call_fn1_ret("tzcnt32");
+    codegen::gen_set_reg32(ctx, r);
+}
+pub fn instr32_F30FBC_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    if !cpuid::has_feature(cpuid::FEATURE_BMI1) {
+        return instr32_0FBC_reg_jit(ctx, r1, r2);
+    }
+    codegen::gen_get_reg32(ctx, r1);
+    ctx.builder.call_fn1_ret("tzcnt32");
+    codegen::gen_set_reg32(ctx, r2);
+}
+pub fn instr16_F30FBD_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    if !cpuid::has_feature(cpuid::FEATURE_LZCNT) {
+        return instr16_0FBD_mem_jit(ctx, modrm_byte, r);
+    }
+    codegen::gen_modrm_resolve_safe_read16(ctx, modrm_byte);
+    ctx.builder.call_fn1_ret("lzcnt16");
+    codegen::gen_set_reg16(ctx, r);
+}
+pub fn instr16_F30FBD_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    if !cpuid::has_feature(cpuid::FEATURE_LZCNT) {
+        return instr16_0FBD_reg_jit(ctx, r1, r2);
+    }
+    codegen::gen_get_reg16(ctx, r1);
+    ctx.builder.call_fn1_ret("lzcnt16");
+    codegen::gen_set_reg16(ctx, r2);
+}
+pub fn instr32_F30FBD_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    if !cpuid::has_feature(cpuid::FEATURE_LZCNT) {
+        return instr32_0FBD_mem_jit(ctx, modrm_byte, r);
+    }
+    codegen::gen_modrm_resolve_safe_read32(ctx, modrm_byte);
+    ctx.builder.call_fn1_ret("lzcnt32");
+    codegen::gen_set_reg32(ctx, r);
+}
+pub fn instr32_F30FBD_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    if !cpuid::has_feature(cpuid::FEATURE_LZCNT) {
+        return instr32_0FBD_reg_jit(ctx, r1, r2);
+    }
+    codegen::gen_get_reg32(ctx, r1);
+    ctx.builder.call_fn1_ret("lzcnt32");
+    codegen::gen_set_reg32(ctx, r2);
+}
+
 pub fn instr16_0FBE_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     codegen::gen_get_reg8(ctx, r1);
     codegen::sign_extend_i8(ctx.builder);
@@ -5315,9 +5616,10 @@ pub fn instr32_0FBF_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32)
 }
 
 pub fn instr16_0FC1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read_write(ctx, BitSize::WORD, &address_local, &|ref mut ctx| {
+    codegen::gen_safe_read_write(ctx, BitSize::WORD, seg, &address_local, &|ref mut ctx| {
         ctx.builder.const_i32(r as i32);
         codegen::gen_move_registers_from_locals_to_memory(ctx);
         ctx.builder.call_fn2_ret("xadd16");
@@ -5335,9 +5637,10 @@ pub fn instr16_0FC1_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr32_0FC1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read_write(ctx, BitSize::DWORD, &address_local, &|ref mut ctx| {
+    codegen::gen_safe_read_write(ctx, BitSize::DWORD, seg, &address_local, &|ref mut ctx| {
         let dest_operand = ctx.builder.set_new_local();
         gen_xadd32(ctx, &dest_operand, r);
         ctx.builder.get_local(&dest_operand);
@@ -5355,17 +5658,19 @@ pub fn instr32_0FC1_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr_0FC3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &ctx.reg(r));
+    codegen::gen_safe_write32(ctx, seg, &address_local, &ctx.reg(r));
     ctx.builder.free_local(address_local);
 }
 pub fn instr_0FC3_reg_jit(ctx: &mut JitContext, _r1: u32, _r2: u32) { codegen::gen_trigger_ud(ctx) }
 
 pub fn instr_0FC4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read16(ctx, &address_local);
+    codegen::gen_safe_read16(ctx, seg, &address_local);
     ctx.builder.const_i32(r as i32);
     ctx.builder.const_i32(imm8 as i32);
     ctx.builder.call_fn3("instr_0FC4");
@@ -5380,9 +5685,10 @@ pub fn instr_0FC4_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
 
 pub fn instr_660FC4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
     ctx.builder.const_i32(0);
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read16(ctx, &address_local);
+    codegen::gen_safe_read16(ctx, seg, &address_local);
     ctx.builder
         .store_aligned_u16(global_pointers::get_reg_xmm_offset(r) + ((imm8 & 7) << 1));
     ctx.builder.free_local(address_local);
@@ -5417,9 +5723,10 @@ pub fn instr_660FC5_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
 
 pub fn instr16_0FC7_1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
     // cmpxchg8b
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read_write(ctx, BitSize::QWORD, &address_local, &|ref mut ctx| {
+    codegen::gen_safe_read_write(ctx, BitSize::QWORD, seg, &address_local, &|ref mut ctx| {
         let dest_operand = ctx.builder.tee_new_local_i64();
         codegen::gen_get_reg32(ctx, regs::EDX);
         ctx.builder.extend_unsigned_i32_to_i64();
@@ -5526,10 +5833,10 @@ pub fn instr_C6_0_reg_jit(ctx: &mut JitContext, r: u32, imm: u32) {
 }
 
 pub fn instr_C6_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, imm: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
         ctx.builder.const_i32(imm as i32);
         let value_local = ctx.builder.set_new_local();
-        codegen::gen_safe_write8(ctx, &addr, &value_local);
+        codegen::gen_safe_write8(ctx, seg, &addr, &value_local);
         ctx.builder.free_local(value_local);
     });
 }
@@ -5541,10 +5848,10 @@ pub fn instr16_C7_0_reg_jit(ctx: &mut JitContext, r: u32, imm: u32) {
 }
 
 pub fn instr16_C7_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, imm: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
         ctx.builder.const_i32(imm as i32);
         let value_local = ctx.builder.set_new_local();
-        codegen::gen_safe_write16(ctx, &addr, &value_local);
+        codegen::gen_safe_write16(ctx, seg, &addr, &value_local);
         ctx.builder.free_local(value_local);
     });
 }
@@ -5556,10 +5863,10 @@ pub fn instr32_C7_0_reg_jit(ctx: &mut JitContext, r: u32, imm: u32) {
 }
 
 pub fn instr32_C7_0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, imm: u32) {
-    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
         ctx.builder.const_i32(imm as i32);
         let value_local = ctx.builder.set_new_local();
-        codegen::gen_safe_write32(ctx, &addr, &value_local);
+        codegen::gen_safe_write32(ctx, seg, &addr, &value_local);
         ctx.builder.free_local(value_local);
     });
 }
@@ -5661,12 +5968,12 @@ define_cmovcc32!(0xF, instr32_0F4F_mem_jit, instr32_0F4F_reg_jit);
 macro_rules! define_setcc(
     ($cond:expr, $name_mem:ident, $name_reg:ident) => (
         pub fn $name_mem(ctx: &mut JitContext, modrm_byte: ModrmByte, _r: u32) {
-            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, addr| {
+            codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
                 codegen::gen_condition_fn(ctx, $cond);
                 ctx.builder.const_i32(0);
                 ctx.builder.ne_i32();
                 let value_local = ctx.builder.set_new_local();
-                codegen::gen_safe_write8(ctx, &addr, &value_local);
+                codegen::gen_safe_write8(ctx, seg, &addr, &value_local);
                 ctx.builder.free_local(value_local);
             });
         }
@@ -5855,13 +6162,14 @@ pub fn instr_F30F16_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr_0F17_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     ctx.builder
         .const_i32(global_pointers::get_reg_xmm_offset(r) as i32);
     ctx.builder.load_aligned_i64(8);
     let value_local = ctx.builder.set_new_local_i64();
-    codegen::gen_safe_write64(ctx, &address_local, &value_local);
+    codegen::gen_safe_write64(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local_i64(value_local);
 }
@@ -5888,6 +6196,7 @@ pub fn instr_660F28_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) { sse_mov_xm
 
 pub fn instr_0F29_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
     // XXX: Aligned write or #gp
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     ctx.builder
@@ -5898,7 +6207,7 @@ pub fn instr_0F29_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
         .const_i32(global_pointers::get_reg_xmm_offset(r) as i32 + 8);
     ctx.builder.load_aligned_i64(0);
     let value_local_high = ctx.builder.set_new_local_i64();
-    codegen::gen_safe_write128(ctx, &address_local, &value_local_low, &value_local_high);
+    codegen::gen_safe_write128(ctx, seg, &address_local, &value_local_low, &value_local_high);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local_i64(value_local_low);
     ctx.builder.free_local_i64(value_local_high);
@@ -6850,12 +7159,13 @@ pub fn instr_F20F7D_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr_0F7E_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     ctx.builder.const_i32(r as i32);
     ctx.builder.call_fn1_ret("instr_0F7E");
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &value_local);
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
 }
@@ -6866,12 +7176,13 @@ pub fn instr_0F7E_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr_660F7E_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     ctx.builder
         .load_fixed_i32(global_pointers::get_reg_xmm_offset(r));
     let value_local = ctx.builder.set_new_local();
-    codegen::gen_safe_write32(ctx, &address_local, &value_local);
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local(value_local);
 }
@@ -6882,12 +7193,13 @@ pub fn instr_660F7E_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr_0F7F_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     ctx.builder.const_i32(r as i32);
     ctx.builder.call_fn1_ret_i64("instr_0F7F");
     let value_local = ctx.builder.set_new_local_i64();
-    codegen::gen_safe_write64(ctx, &address_local, &value_local);
+    codegen::gen_safe_write64(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local_i64(value_local);
 }
@@ -6945,6 +7257,7 @@ pub fn instr16_0FA3_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     )
 }
 pub fn instr16_0FA3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     codegen::gen_get_reg16(ctx, r);
     codegen::sign_extend_i16(ctx.builder);
@@ -6952,7 +7265,7 @@ pub fn instr16_0FA3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32)
     ctx.builder.shr_s_i32();
     ctx.builder.add_i32();
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read8(ctx, &address_local);
+    codegen::gen_safe_read8(ctx, seg, &address_local);
     ctx.builder.free_local(address_local);
     let value = ctx.builder.set_new_local();
     gen_bt(
@@ -6972,13 +7285,14 @@ pub fn instr32_0FA3_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     )
 }
 pub fn instr32_0FA3_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     codegen::gen_get_reg32(ctx, r);
     ctx.builder.const_i32(3);
     ctx.builder.shr_s_i32();
     ctx.builder.add_i32();
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read8(ctx, &address_local);
+    codegen::gen_safe_read8(ctx, seg, &address_local);
     ctx.builder.free_local(address_local);
     let value = ctx.builder.set_new_local();
     gen_bt(
@@ -7104,6 +7418,7 @@ pub fn instr16_0FBA_4_reg_jit(ctx: &mut JitContext, r: u32, imm8: u32) {
     )
 }
 pub fn instr16_0FBA_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, imm8: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let offset = (imm8 as i32 & 15) >> 3;
     if offset != 0 {
@@ -7111,7 +7426,7 @@ pub fn instr16_0FBA_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, imm8:
         ctx.builder.add_i32();
     }
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read8(ctx, &address_local);
+    codegen::gen_safe_read8(ctx, seg, &address_local);
     ctx.builder.free_local(address_local);
     let value = ctx.builder.set_new_local();
     gen_bt(
@@ -7131,6 +7446,7 @@ pub fn instr32_0FBA_4_reg_jit(ctx: &mut JitContext, r: u32, imm8: u32) {
     )
 }
 pub fn instr32_0FBA_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, imm8: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let offset = (imm8 as i32 & 31) >> 3;
     if offset != 0 {
@@ -7138,7 +7454,7 @@ pub fn instr32_0FBA_4_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, imm8:
         ctx.builder.add_i32();
     }
     let address_local = ctx.builder.set_new_local();
-    codegen::gen_safe_read8(ctx, &address_local);
+    codegen::gen_safe_read8(ctx, seg, &address_local);
     ctx.builder.free_local(address_local);
     let value = ctx.builder.set_new_local();
     gen_bt(
@@ -7255,12 +7571,15 @@ pub fn instr32_0FBA_7_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, imm8:
     );
 }
 
-pub fn instr_0FAE_5_mem_jit(ctx: &mut JitContext, _modrm_byte: ModrmByte) {
-    dbg_log!("Generating #ud for unimplemented instruction: instr_0FAE_5_mem_jit");
-    codegen::gen_trigger_ud(ctx);
+pub fn instr_0FAE_5_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte) {
+    // xrstor
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    codegen::gen_modrm_resolve_with_segment(ctx, modrm_byte);
+    codegen::gen_modrm_fn0(ctx.builder, "instr_0FAE_5_mem");
+    codegen::gen_move_registers_from_memory_to_locals(ctx);
 }
 pub fn instr_0FAE_5_reg_jit(_ctx: &mut JitContext, _r: u32) {
-    // For this instruction, the processor ignores the r/m field of the ModR/M byte.
+    // lfence: For this instruction, the processor ignores the r/m field of the ModR/M byte.
 }
 
 pub fn instr_0FD1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
@@ -7384,12 +7703,13 @@ pub fn instr_660FD5_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
 }
 
 pub fn instr_660FD6_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
     codegen::gen_modrm_resolve(ctx, modrm_byte);
     let address_local = ctx.builder.set_new_local();
     ctx.builder
         .load_fixed_i64(global_pointers::get_reg_xmm_offset(r));
     let value_local = ctx.builder.set_new_local_i64();
-    codegen::gen_safe_write64(ctx, &address_local, &value_local);
+    codegen::gen_safe_write64(ctx, seg, &address_local, &value_local);
     ctx.builder.free_local(address_local);
     ctx.builder.free_local_i64(value_local);
 }
@@ -7716,8 +8036,12 @@ pub fn instr_0FF7_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     else {
         codegen::gen_get_reg16(ctx, regs::DI);
     }
-    jit_add_seg_offset(ctx, regs::DS);
-    ctx.builder.call_fn3("maskmovq");
+    let seg = jit_add_seg_offset(ctx, regs::DS);
+    let address_local = ctx.builder.set_new_local();
+    ctx.builder.const_i32(seg as i32);
+    ctx.builder.get_local(&address_local);
+    ctx.builder.free_local(address_local);
+    ctx.builder.call_fn4("maskmovq");
     codegen::gen_move_registers_from_memory_to_locals(ctx);
 
     codegen::gen_get_page_fault(ctx.builder);
@@ -7825,8 +8149,12 @@ pub fn instr_660FF7_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     else {
         codegen::gen_get_reg16(ctx, regs::DI);
     }
-    jit_add_seg_offset(ctx, regs::DS);
-    ctx.builder.call_fn3("maskmovdqu");
+    let seg = jit_add_seg_offset(ctx, regs::DS);
+    let address_local = ctx.builder.set_new_local();
+    ctx.builder.const_i32(seg as i32);
+    ctx.builder.get_local(&address_local);
+    ctx.builder.free_local(address_local);
+    ctx.builder.call_fn4("maskmovdqu");
     codegen::gen_move_registers_from_memory_to_locals(ctx);
 
     codegen::gen_get_page_fault(ctx.builder);
@@ -7878,3 +8206,704 @@ pub fn instr_660FFE_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32)
 pub fn instr_660FFE_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
     sse_read128_xmm_xmm(ctx, "instr_660FFE", r1, r2);
 }
+
+pub fn instr_0F3800_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3800", modrm_byte, r);
+}
+pub fn instr_0F3800_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3800", r1, r2);
+}
+pub fn instr_660F3800_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3800", modrm_byte, r);
+}
+pub fn instr_660F3800_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3800", r1, r2);
+}
+pub fn instr_0F3801_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3801", modrm_byte, r);
+}
+pub fn instr_0F3801_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3801", r1, r2);
+}
+pub fn instr_660F3801_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3801", modrm_byte, r);
+}
+pub fn instr_660F3801_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3801", r1, r2);
+}
+pub fn instr_0F3802_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3802", modrm_byte, r);
+}
+pub fn instr_0F3802_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3802", r1, r2);
+}
+pub fn instr_660F3802_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3802", modrm_byte, r);
+}
+pub fn instr_660F3802_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3802", r1, r2);
+}
+pub fn instr_0F3803_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3803", modrm_byte, r);
+}
+pub fn instr_0F3803_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3803", r1, r2);
+}
+pub fn instr_660F3803_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3803", modrm_byte, r);
+}
+pub fn instr_660F3803_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3803", r1, r2);
+}
+pub fn instr_0F3804_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3804", modrm_byte, r);
+}
+pub fn instr_0F3804_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3804", r1, r2);
+}
+pub fn instr_660F3804_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3804", modrm_byte, r);
+}
+pub fn instr_660F3804_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3804", r1, r2);
+}
+pub fn instr_0F3805_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3805", modrm_byte, r);
+}
+pub fn instr_0F3805_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3805", r1, r2);
+}
+pub fn instr_660F3805_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3805", modrm_byte, r);
+}
+pub fn instr_660F3805_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3805", r1, r2);
+}
+pub fn instr_0F3806_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3806", modrm_byte, r);
+}
+pub fn instr_0F3806_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3806", r1, r2);
+}
+pub fn instr_660F3806_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3806", modrm_byte, r);
+}
+pub fn instr_660F3806_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3806", r1, r2);
+}
+pub fn instr_0F3807_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3807", modrm_byte, r);
+}
+pub fn instr_0F3807_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3807", r1, r2);
+}
+pub fn instr_660F3807_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3807", modrm_byte, r);
+}
+pub fn instr_660F3807_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3807", r1, r2);
+}
+pub fn instr_0F3808_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3808", modrm_byte, r);
+}
+pub fn instr_0F3808_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3808", r1, r2);
+}
+pub fn instr_660F3808_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3808", modrm_byte, r);
+}
+pub fn instr_660F3808_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3808", r1, r2);
+}
+pub fn instr_0F3809_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F3809", modrm_byte, r);
+}
+pub fn instr_0F3809_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F3809", r1, r2);
+}
+pub fn instr_660F3809_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3809", modrm_byte, r);
+}
+pub fn instr_660F3809_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3809", r1, r2);
+}
+pub fn instr_0F380A_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F380A", modrm_byte, r);
+}
+pub fn instr_0F380A_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F380A", r1, r2);
+}
+pub fn instr_660F380A_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F380A", modrm_byte, r);
+}
+pub fn instr_660F380A_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F380A", r1, r2);
+}
+pub fn instr_0F380B_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F380B", modrm_byte, r);
+}
+pub fn instr_0F380B_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F380B", r1, r2);
+}
+pub fn instr_660F380B_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F380B", modrm_byte, r);
+}
+pub fn instr_660F380B_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F380B", r1, r2);
+}
+pub fn instr_0F381C_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F381C", modrm_byte, r);
+}
+pub fn instr_0F381C_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F381C", r1, r2);
+}
+pub fn instr_660F381C_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F381C", modrm_byte, r);
+}
+pub fn instr_660F381C_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F381C", r1, r2);
+}
+pub fn instr_0F381D_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F381D", modrm_byte, r);
+}
+pub fn instr_0F381D_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F381D", r1, r2);
+}
+pub fn instr_660F381D_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F381D", modrm_byte, r);
+}
+pub fn instr_660F381D_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F381D", r1, r2);
+}
+pub fn instr_0F381E_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    mmx_read64_mm_mem(ctx, "instr_0F381E", modrm_byte, r);
+}
+pub fn instr_0F381E_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    mmx_read64_mm_mm(ctx, "instr_0F381E", r1, r2);
+}
+pub fn instr_660F381E_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F381E", modrm_byte, r);
+}
+pub fn instr_660F381E_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F381E", r1, r2);
+}
+pub fn instr_0F3A0F_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    codegen::gen_modrm_resolve_safe_read64(ctx, modrm_byte);
+    ctx.builder.const_i32(r as i32);
+    ctx.builder.const_i32(imm8 as i32);
+    ctx.builder.call_fn3_i64_i32_i32("instr_0F3A0F");
+}
+pub fn instr_0F3A0F_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder
+        .const_i32(global_pointers::get_reg_mmx_offset(r1) as i32);
+    ctx.builder.load_aligned_i64(0);
+    ctx.builder.const_i32(r2 as i32);
+    ctx.builder.const_i32(imm8 as i32);
+    ctx.builder.call_fn3_i64_i32_i32("instr_0F3A0F");
+}
+pub fn instr_660F3A0F_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A0F", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A0F_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A0F", r1, r2, imm8)
+}
+
+pub fn instr_660F3810_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3810", modrm_byte, r);
+}
+pub fn instr_660F3810_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3810", r1, r2);
+}
+pub fn instr_660F3814_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3814", modrm_byte, r);
+}
+pub fn instr_660F3814_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3814", r1, r2);
+}
+pub fn instr_660F3815_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3815", modrm_byte, r);
+}
+pub fn instr_660F3815_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3815", r1, r2);
+}
+pub fn instr_660F3817_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3817", modrm_byte, r);
+}
+pub fn instr_660F3817_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3817", r1, r2);
+}
+pub fn instr_660F3820_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read64_xmm_mem(ctx, "instr_660F3820", modrm_byte, r);
+}
+pub fn instr_660F3820_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read64_xmm_xmm(ctx, "instr_660F3820", r1, r2);
+}
+pub fn instr_660F3821_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read32_xmm_mem(ctx, "instr_660F3821", modrm_byte, r);
+}
+pub fn instr_660F3821_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read32_xmm_xmm(ctx, "instr_660F3821", r1, r2);
+}
+pub fn instr_660F3822_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read16_xmm_mem(ctx, "instr_660F3822", modrm_byte, r);
+}
+pub fn instr_660F3822_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read32_xmm_xmm(ctx, "instr_660F3822", r1, r2);
+}
+pub fn instr_660F3823_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read64_xmm_mem(ctx, "instr_660F3823", modrm_byte, r);
+}
+pub fn instr_660F3823_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read64_xmm_xmm(ctx, "instr_660F3823", r1, r2);
+}
+pub fn instr_660F3824_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read32_xmm_mem(ctx, "instr_660F3824", modrm_byte, r);
+}
+pub fn instr_660F3824_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read32_xmm_xmm(ctx, "instr_660F3824", r1, r2);
+}
+pub fn instr_660F3825_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read64_xmm_mem(ctx, "instr_660F3825", modrm_byte, r);
+}
+pub fn instr_660F3825_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read64_xmm_xmm(ctx, "instr_660F3825", r1, r2);
+}
+pub fn instr_660F3828_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3828", modrm_byte, r);
+}
+pub fn instr_660F3828_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3828", r1, r2);
+}
+pub fn instr_660F3829_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3829", modrm_byte, r);
+}
+pub fn instr_660F3829_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3829", r1, r2);
+}
+pub fn instr_660F382A_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    instr_660F6F_mem_jit(ctx, modrm_byte, r)
+}
+pub fn instr_660F382A_reg_jit(ctx: &mut JitContext, _r1: u32, _r2: u32) {
+    codegen::gen_trigger_ud(ctx);
+}
+pub fn instr_660F382B_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F382B", modrm_byte, r);
+}
+pub fn instr_660F382B_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F382B", r1, r2);
+}
+pub fn instr_660F3830_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read64_xmm_mem(ctx, "instr_660F3830", modrm_byte, r);
+}
+pub fn instr_660F3830_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read64_xmm_xmm(ctx, "instr_660F3830", r1, r2);
+}
+pub fn instr_660F3831_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read32_xmm_mem(ctx, "instr_660F3831", modrm_byte, r);
+}
+pub fn instr_660F3831_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read32_xmm_xmm(ctx, "instr_660F3831", r1, r2);
+}
+pub fn instr_660F3832_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read16_xmm_mem(ctx, "instr_660F3832", modrm_byte, r);
+}
+pub fn instr_660F3832_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read32_xmm_xmm(ctx, "instr_660F3832", r1, r2);
+}
+pub fn instr_660F3833_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read64_xmm_mem(ctx, "instr_660F3833", modrm_byte, r);
+}
+pub fn instr_660F3833_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read64_xmm_xmm(ctx, "instr_660F3833", r1, r2);
+}
+pub fn instr_660F3834_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read32_xmm_mem(ctx, "instr_660F3834", modrm_byte, r);
+}
+pub fn instr_660F3834_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read32_xmm_xmm(ctx, "instr_660F3834", r1, r2);
+}
+pub fn instr_660F3835_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read64_xmm_mem(ctx, "instr_660F3835", modrm_byte, r);
+}
+pub fn instr_660F3835_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read64_xmm_xmm(ctx, "instr_660F3835", r1, r2);
+}
+pub fn instr_660F3838_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3838", modrm_byte, r);
+}
+pub fn instr_660F3838_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3838", r1, r2);
+}
+pub fn instr_660F3839_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3839", modrm_byte, r);
+}
+pub fn instr_660F3839_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3839", r1, r2);
+}
+pub fn instr_660F383A_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F383A", modrm_byte, r);
+}
+pub fn instr_660F383A_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F383A", r1, r2);
+}
+pub fn instr_660F383B_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F383B", modrm_byte, r);
+}
+pub fn instr_660F383B_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F383B", r1, r2);
+}
+pub fn instr_660F383C_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F383C", modrm_byte, r);
+}
+pub fn instr_660F383C_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F383C", r1, r2);
+}
+pub fn instr_660F383D_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F383D", modrm_byte, r);
+}
+pub fn instr_660F383D_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F383D", r1, r2);
+}
+pub fn instr_660F383E_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F383E", modrm_byte, r);
+}
+pub fn instr_660F383E_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F383E", r1, r2);
+}
+pub fn instr_660F383F_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F383F", modrm_byte, r);
+}
+pub fn instr_660F383F_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F383F", r1, r2);
+}
+pub fn instr_660F3840_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3840", modrm_byte, r);
+}
+pub fn instr_660F3840_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3840", r1, r2);
+}
+pub fn instr_660F3841_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3841", modrm_byte, r);
+}
+pub fn instr_660F3841_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3841", r1, r2);
+}
+
+pub fn instr_660F3A08_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A08", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A08_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A08", r1, r2, imm8)
+}
+pub fn instr_660F3A09_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A09", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A09_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A09", r1, r2, imm8)
+}
+pub fn instr_660F3A0A_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    codegen::gen_modrm_resolve_safe_read32(ctx, modrm_byte);
+    ctx.builder.const_i32(r as i32);
+    ctx.builder.const_i32(imm8 as i32);
+    ctx.builder.call_fn3("instr_660F3A0A");
+}
+pub fn instr_660F3A0A_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder
+        .load_fixed_i32(global_pointers::get_reg_xmm_offset(r1));
+    ctx.builder.const_i32(r2 as i32);
+    ctx.builder.const_i32(imm8 as i32);
+    ctx.builder.call_fn3("instr_660F3A0A");
+}
+pub fn instr_660F3A0B_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    codegen::gen_modrm_resolve_safe_read64(ctx, modrm_byte);
+    ctx.builder.const_i32(r as i32);
+    ctx.builder.const_i32(imm8 as i32);
+    ctx.builder.call_fn3_i64_i32_i32("instr_660F3A0B");
+}
+pub fn instr_660F3A0B_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder
+        .load_fixed_i64(global_pointers::get_reg_xmm_offset(r1));
+    ctx.builder.const_i32(r2 as i32);
+    ctx.builder.const_i32(imm8 as i32);
+    ctx.builder.call_fn3_i64_i32_i32("instr_660F3A0B");
+}
+pub fn instr_660F3A0C_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A0C", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A0C_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A0C", r1, r2, imm8)
+}
+pub fn instr_660F3A0D_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A0D", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A0D_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A0D", r1, r2, imm8)
+}
+pub fn instr_660F3A0E_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A0E", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A0E_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A0E", r1, r2, imm8)
+}
+
+pub fn instr_660F3A14_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
+    codegen::gen_modrm_resolve(ctx, modrm_byte);
+    let address_local = ctx.builder.set_new_local();
+    ctx.builder
+        .load_fixed_u8(global_pointers::get_reg_xmm_offset(r) + (imm8 & 15));
+    let value_local = ctx.builder.set_new_local();
+    codegen::gen_safe_write8(ctx, seg, &address_local, &value_local);
+    ctx.builder.free_local(address_local);
+    ctx.builder.free_local(value_local);
+}
+pub fn instr_660F3A14_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder
+        .load_fixed_u8(global_pointers::get_reg_xmm_offset(r2) + (imm8 & 15));
+    codegen::gen_set_reg32(ctx, r1);
+}
+pub fn instr_660F3A15_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
+    codegen::gen_modrm_resolve(ctx, modrm_byte);
+    let address_local = ctx.builder.set_new_local();
+    ctx.builder
+        .load_fixed_u16(global_pointers::get_reg_xmm_offset(r) + ((imm8 & 7) << 1));
+    let value_local = ctx.builder.set_new_local();
+    codegen::gen_safe_write16(ctx, seg, &address_local, &value_local);
+    ctx.builder.free_local(address_local);
+    ctx.builder.free_local(value_local);
+}
+pub fn instr_660F3A15_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder
+        .load_fixed_u16(global_pointers::get_reg_xmm_offset(r2) + ((imm8 & 7) << 1));
+    codegen::gen_set_reg32(ctx, r1);
+}
+pub fn instr_660F3A16_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
+    codegen::gen_modrm_resolve(ctx, modrm_byte);
+    let address_local = ctx.builder.set_new_local();
+    ctx.builder
+        .load_fixed_i32(global_pointers::get_reg_xmm_offset(r) + ((imm8 & 3) << 2));
+    let value_local = ctx.builder.set_new_local();
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
+    ctx.builder.free_local(address_local);
+    ctx.builder.free_local(value_local);
+}
+pub fn instr_660F3A16_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder
+        .load_fixed_i32(global_pointers::get_reg_xmm_offset(r2) + ((imm8 & 3) << 2));
+    codegen::gen_set_reg32(ctx, r1);
+}
+pub fn instr_660F3A17_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    let seg = ::modrm::segment(ctx, &modrm_byte);
+    codegen::gen_modrm_resolve(ctx, modrm_byte);
+    let address_local = ctx.builder.set_new_local();
+    ctx.builder
+        .load_fixed_i32(global_pointers::get_reg_xmm_offset(r) + ((imm8 & 3) << 2));
+    let value_local = ctx.builder.set_new_local();
+    codegen::gen_safe_write32(ctx, seg, &address_local, &value_local);
+    ctx.builder.free_local(address_local);
+    ctx.builder.free_local(value_local);
+}
+pub fn instr_660F3A17_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder
+        .load_fixed_i32(global_pointers::get_reg_xmm_offset(r2) + ((imm8 & 3) << 2));
+    codegen::gen_set_reg32(ctx, r1);
+}
+
+pub fn instr_660F3A20_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    ctx.builder.const_i32(0);
+    let seg = ::modrm::segment(ctx, &modrm_byte);
+    codegen::gen_modrm_resolve(ctx, modrm_byte);
+    let address_local = ctx.builder.set_new_local();
+    codegen::gen_safe_read8(ctx, seg, &address_local);
+    ctx.builder
+        .store_u8(global_pointers::get_reg_xmm_offset(r) + (imm8 & 15));
+    ctx.builder.free_local(address_local);
+}
+pub fn instr_660F3A20_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder.const_i32(0);
+    codegen::gen_get_reg32(ctx, r1);
+    ctx.builder
+        .store_u8(global_pointers::get_reg_xmm_offset(r2) + (imm8 & 15));
+}
+pub fn instr_660F3A21_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    codegen::gen_modrm_resolve_safe_read32(ctx, modrm_byte);
+    ctx.builder.const_i32(r as i32);
+    ctx.builder.const_i32(imm8 as i32);
+    ctx.builder.call_fn3("instr_660F3A21");
+}
+pub fn instr_660F3A21_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder
+        .load_fixed_i32(global_pointers::get_reg_xmm_offset(r1) + ((imm8 >> 6 & 3) << 2));
+    ctx.builder.const_i32(r2 as i32);
+    ctx.builder.const_i32(imm8 as i32);
+    ctx.builder.call_fn3("instr_660F3A21");
+}
+pub fn instr_660F3A22_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    ctx.builder.const_i32(0);
+    let seg = ::modrm::segment(ctx, &modrm_byte);
+    codegen::gen_modrm_resolve(ctx, modrm_byte);
+    let address_local = ctx.builder.set_new_local();
+    codegen::gen_safe_read32(ctx, seg, &address_local);
+    ctx.builder
+        .store_aligned_i32(global_pointers::get_reg_xmm_offset(r) + ((imm8 & 3) << 2));
+    ctx.builder.free_local(address_local);
+}
+pub fn instr_660F3A22_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    ctx.builder.const_i32(0);
+    codegen::gen_get_reg32(ctx, r1);
+    ctx.builder
+        .store_aligned_i32(global_pointers::get_reg_xmm_offset(r2) + ((imm8 & 3) << 2));
+}
+
+pub fn instr_660F3A40_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A40", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A40_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A40", r1, r2, imm8)
+}
+pub fn instr_660F3A41_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A41", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A41_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A41", r1, r2, imm8)
+}
+pub fn instr_660F3A42_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A42", modrm_byte, r, imm8)
+}
+pub fn instr_660F3A42_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A42", r1, r2, imm8)
+}
+
+pub fn instr_660F3837_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    sse_read128_xmm_mem(ctx, "instr_660F3837", modrm_byte, r);
+}
+pub fn instr_660F3837_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    sse_read128_xmm_xmm(ctx, "instr_660F3837", r1, r2);
+}
+
+pub fn instr16_0F38F0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    codegen::gen_modrm_resolve_safe_read16(ctx, modrm_byte);
+    let value = ctx.builder.set_new_local();
+    gen_bswap16_local(ctx.builder, &value);
+    codegen::gen_set_reg16(ctx, r);
+    ctx.builder.free_local(value);
+}
+pub fn instr16_0F38F0_reg_jit(ctx: &mut JitContext, _r1: u32, _r2: u32) {
+    codegen::gen_trigger_ud(ctx);
+}
+pub fn instr32_0F38F0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    codegen::gen_modrm_resolve_safe_read32(ctx, modrm_byte);
+    let value = ctx.builder.set_new_local();
+    gen_bswap32_local(ctx.builder, &value);
+    codegen::gen_set_reg32(ctx, r);
+    ctx.builder.free_local(value);
+}
+pub fn instr32_0F38F0_reg_jit(ctx: &mut JitContext, _r1: u32, _r2: u32) {
+    codegen::gen_trigger_ud(ctx);
+}
+pub fn instr16_0F38F1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    codegen::gen_get_reg16(ctx, r);
+    let register = ctx.builder.set_new_local();
+    gen_bswap16_local(ctx.builder, &register);
+    let value = ctx.builder.set_new_local();
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        codegen::gen_safe_write16(ctx, seg, addr, &value);
+    });
+    ctx.builder.free_local(value);
+    ctx.builder.free_local(register);
+}
+pub fn instr16_0F38F1_reg_jit(ctx: &mut JitContext, _r1: u32, _r2: u32) {
+    codegen::gen_trigger_ud(ctx);
+}
+pub fn instr32_0F38F1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    let register = ctx.reg(r);
+    gen_bswap32_local(ctx.builder, &register);
+    let value = ctx.builder.set_new_local();
+    codegen::gen_modrm_resolve_with_local(ctx, modrm_byte, &|ctx, seg, addr| {
+        codegen::gen_safe_write32(ctx, seg, addr, &value);
+    });
+    ctx.builder.free_local(value);
+}
+pub fn instr32_0F38F1_reg_jit(ctx: &mut JitContext, _r1: u32, _r2: u32) {
+    codegen::gen_trigger_ud(ctx);
+}
+
+pub fn instr_F20F38F0_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    codegen::gen_get_reg32(ctx, r);
+    codegen::gen_modrm_resolve_safe_read8(ctx, modrm_byte);
+    ctx.builder.const_i32(1);
+    ctx.builder.call_fn3_ret("crc32");
+    codegen::gen_set_reg32(ctx, r);
+}
+pub fn instr_F20F38F0_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    codegen::gen_get_reg32(ctx, r2);
+    codegen::gen_get_reg8(ctx, r1);
+    ctx.builder.const_i32(1);
+    ctx.builder.call_fn3_ret("crc32");
+    codegen::gen_set_reg32(ctx, r2);
+}
+pub fn instr16_F20F38F1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    codegen::gen_get_reg32(ctx, r);
+    codegen::gen_modrm_resolve_safe_read16(ctx, modrm_byte);
+    ctx.builder.const_i32(2);
+    ctx.builder.call_fn3_ret("crc32");
+    codegen::gen_set_reg32(ctx, r);
+}
+pub fn instr16_F20F38F1_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    codegen::gen_get_reg32(ctx, r2);
+    codegen::gen_get_reg16(ctx, r1);
+    ctx.builder.const_i32(2);
+    ctx.builder.call_fn3_ret("crc32");
+    codegen::gen_set_reg32(ctx, r2);
+}
+pub fn instr32_F20F38F1_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32) {
+    codegen::gen_get_reg32(ctx, r);
+    codegen::gen_modrm_resolve_safe_read32(ctx, modrm_byte);
+    ctx.builder.const_i32(4);
+    ctx.builder.call_fn3_ret("crc32");
+    codegen::gen_set_reg32(ctx, r);
+}
+pub fn instr32_F20F38F1_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32) {
+    codegen::gen_get_reg32(ctx, r2);
+    codegen::gen_get_reg32(ctx, r1);
+    ctx.builder.const_i32(4);
+    ctx.builder.call_fn3_ret("crc32");
+    codegen::gen_set_reg32(ctx, r2);
+}
+
+pub fn instr_660F3A60_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A60", modrm_byte, r, imm8);
+}
+pub fn instr_660F3A60_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A60", r1, r2, imm8);
+}
+pub fn instr_660F3A61_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A61", modrm_byte, r, imm8);
+    codegen::gen_move_registers_from_memory_to_locals(ctx);
+}
+pub fn instr_660F3A61_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A61", r1, r2, imm8);
+    codegen::gen_move_registers_from_memory_to_locals(ctx);
+}
+pub fn instr_660F3A62_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A62", modrm_byte, r, imm8);
+}
+pub fn instr_660F3A62_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A62", r1, r2, imm8);
+}
+pub fn instr_660F3A63_mem_jit(ctx: &mut JitContext, modrm_byte: ModrmByte, r: u32, imm8: u32) {
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    sse_read128_xmm_mem_imm(ctx, "instr_660F3A63", modrm_byte, r, imm8);
+    codegen::gen_move_registers_from_memory_to_locals(ctx);
+}
+pub fn instr_660F3A63_reg_jit(ctx: &mut JitContext, r1: u32, r2: u32, imm8: u32) {
+    codegen::gen_move_registers_from_locals_to_memory(ctx);
+    sse_read128_xmm_xmm_imm(ctx, "instr_660F3A63", r1, r2, imm8);
+    codegen::gen_move_registers_from_memory_to_locals(ctx);
+}
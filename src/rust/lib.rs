@@ -28,5 +28,6 @@ mod regs;
 mod softfloat;
 mod state_flags;
 mod util;
+mod vex;
 mod wasmgen;
 mod zstd;
@@ -249,9 +249,14 @@ pub fn gen(ctx: &mut JitContext, modrm_byte: ModrmByte, esp_offset: i32) {
     jit_add_seg_offset(ctx, modrm_byte.segment);
 }
 
-pub fn get_as_reg_index_if_possible(ctx: &mut JitContext, modrm_byte: &ModrmByte) -> Option<u32> {
+/// The segment register of the memory operand, taking segment prefixes into account
+pub fn segment(ctx: &JitContext, modrm_byte: &ModrmByte) -> u32 {
     let prefix = ctx.cpu.prefixes & PREFIX_MASK_SEGMENT;
-    let seg = if prefix != 0 { (prefix - 1) as u32 } else { modrm_byte.segment };
+    if prefix != 0 && prefix != SEG_PREFIX_ZERO { (prefix - 1) as u32 } else { modrm_byte.segment }
+}
+
+pub fn get_as_reg_index_if_possible(ctx: &mut JitContext, modrm_byte: &ModrmByte) -> Option<u32> {
+    let seg = segment(ctx, modrm_byte);
     if can_optimize_get_seg(ctx, seg)
         && modrm_byte.second_reg.is_none()
         && modrm_byte.immediate == 0
@@ -278,15 +283,18 @@ fn can_optimize_get_seg(ctx: &mut JitContext, segment: u32) -> bool {
     (segment == DS || segment == SS || segment == CS) && ctx.cpu.has_flat_segmentation()
 }
 
-pub fn jit_add_seg_offset(ctx: &mut JitContext, default_segment: u32) {
+/// Add the offset of the segment (the default segment or the one given by a prefix) and return
+/// the segment register
+pub fn jit_add_seg_offset(ctx: &mut JitContext, default_segment: u32) -> u32 {
     let prefix = ctx.cpu.prefixes & PREFIX_MASK_SEGMENT;
 
     if prefix == SEG_PREFIX_ZERO {
-        return;
+        return default_segment;
     }
 
     let seg = if prefix != 0 { (prefix - 1) as u32 } else { default_segment };
     jit_add_seg_offset_no_override(ctx, seg);
+    seg
 }
 
 pub fn jit_add_seg_offset_no_override(ctx: &mut JitContext, seg: u32) {
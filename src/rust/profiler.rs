@@ -64,12 +64,14 @@ pub enum stat {
     SAFE_READ_SLOW_NOT_VALID,
     SAFE_READ_SLOW_NOT_USER,
     SAFE_READ_SLOW_IN_MAPPED_RANGE,
+    SAFE_READ_SLOW_SMAP,
 
     SAFE_WRITE_FAST,
     SAFE_WRITE_SLOW_PAGE_CROSSED,
     SAFE_WRITE_SLOW_NOT_VALID,
     SAFE_WRITE_SLOW_NOT_USER,
     SAFE_WRITE_SLOW_IN_MAPPED_RANGE,
+    SAFE_WRITE_SLOW_SMAP,
     SAFE_WRITE_SLOW_READ_ONLY,
     SAFE_WRITE_SLOW_HAS_CODE,
 
@@ -78,6 +80,7 @@ pub enum stat {
     SAFE_READ_WRITE_SLOW_NOT_VALID,
     SAFE_READ_WRITE_SLOW_NOT_USER,
     SAFE_READ_WRITE_SLOW_IN_MAPPED_RANGE,
+    SAFE_READ_WRITE_SLOW_SMAP,
     SAFE_READ_WRITE_SLOW_READ_ONLY,
     SAFE_READ_WRITE_SLOW_HAS_CODE,
 
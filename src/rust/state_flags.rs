@@ -7,13 +7,22 @@ impl CachedStateFlags {
     const MASK_SS32: u8 = 1 << 1;
     const MASK_CPL3: u8 = 1 << 2;
     const MASK_FLAT_SEGS: u8 = 1 << 3;
+    const MASK_ALIGNMENT_CHECK: u8 = 1 << 4;
+    const MASK_PROTECTED_MODE: u8 = 1 << 5;
+    const MASK_SEGMENT_CHECKS: u8 = 1 << 6;
 
     pub const EMPTY: CachedStateFlags = CachedStateFlags(0);
 
     pub fn of_u32(f: u32) -> CachedStateFlags {
         dbg_assert!(
             f as u8
-                & !(Self::MASK_IS_32 | Self::MASK_SS32 | Self::MASK_CPL3 | Self::MASK_FLAT_SEGS)
+                & !(Self::MASK_IS_32
+                    | Self::MASK_SS32
+                    | Self::MASK_CPL3
+                    | Self::MASK_FLAT_SEGS
+                    | Self::MASK_ALIGNMENT_CHECK
+                    | Self::MASK_PROTECTED_MODE
+                    | Self::MASK_SEGMENT_CHECKS)
                 == 0
         );
         CachedStateFlags(f as u8)
@@ -24,4 +33,11 @@ impl CachedStateFlags {
     pub fn has_flat_segmentation(&self) -> bool { self.0 & CachedStateFlags::MASK_FLAT_SEGS != 0 }
     pub fn is_32(&self) -> bool { self.0 & CachedStateFlags::MASK_IS_32 != 0 }
     pub fn ssize_32(&self) -> bool { self.0 & CachedStateFlags::MASK_SS32 != 0 }
+    pub fn alignment_check(&self) -> bool {
+        self.0 & CachedStateFlags::MASK_ALIGNMENT_CHECK != 0
+    }
+    /// Protected mode, but not vm86 mode
+    pub fn protected_mode(&self) -> bool { self.0 & CachedStateFlags::MASK_PROTECTED_MODE != 0 }
+    /// Segment limits and types are checked (only in protected mode)
+    pub fn segment_checks(&self) -> bool { self.0 & CachedStateFlags::MASK_SEGMENT_CHECKS != 0 }
 }
@@ -0,0 +1,97 @@
+//! Decoding of the three-byte vex prefix (c4), which is only used for the bmi1 and bmi2 general
+//! purpose register instructions. In real and vm86 mode, and with a memory operand in protected
+//! mode, c4 is les instead.
+
+use cpu::cpuid;
+use prefix::{PREFIX_66, PREFIX_F2, PREFIX_F3};
+
+const VEX_MAP_0F38: u8 = 2;
+const VEX_MAP_0F3A: u8 = 3;
+
+const VEX_PP_NONE: u8 = 0;
+const VEX_PP_66: u8 = 1;
+const VEX_PP_F3: u8 = 2;
+const VEX_PP_F2: u8 = 3;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum VexInstruction {
+    Andn,
+    Bextr,
+    Blsi,
+    Blsmsk,
+    Blsr,
+    Bzhi,
+    Mulx,
+    Pdep,
+    Pext,
+    Rorx,
+    Sarx,
+    Shlx,
+    Shrx,
+    Invalid,
+}
+
+impl VexInstruction {
+    pub fn feature(&self) -> u32 {
+        match self {
+            VexInstruction::Andn
+            | VexInstruction::Bextr
+            | VexInstruction::Blsi
+            | VexInstruction::Blsmsk
+            | VexInstruction::Blsr => cpuid::FEATURE_BMI1,
+            _ => cpuid::FEATURE_BMI2,
+        }
+    }
+}
+
+pub struct Vex {
+    map: u8,
+    pp: u8,
+    l: bool,
+    /// Register encoded in vex.vvvv (its inverted upper bit is ignored outside of 64-bit mode)
+    pub vvvv: i32,
+    vvvv_unused: bool,
+}
+
+impl Vex {
+    /// Decode the two bytes following c4. The first one must have the form of a register modrm
+    /// byte (vex.r and vex.x set), vex.b and vex.w are ignored outside of 64-bit mode
+    pub fn decode(byte1: u8, byte2: u8) -> Vex {
+        dbg_assert!(byte1 >= 0xC0);
+        Vex {
+            map: byte1 & 0x1F,
+            pp: byte2 & 3,
+            l: byte2 & 4 != 0,
+            vvvv: (!byte2 >> 3 & 7) as i32,
+            vvvv_unused: byte2 >> 3 & 0xF == 0xF,
+        }
+    }
+
+    /// Whether an imm8 follows the modrm operand
+    pub fn has_imm8(&self) -> bool { self.map == VEX_MAP_0F3A }
+
+    pub fn instruction(&self, prefixes: u8, opcode: u8, modrm_byte: u8) -> VexInstruction {
+        if self.l || prefixes & (PREFIX_66 | PREFIX_F2 | PREFIX_F3) != 0 {
+            return VexInstruction::Invalid;
+        }
+        match (self.map, self.pp, opcode) {
+            (VEX_MAP_0F38, VEX_PP_NONE, 0xF2) => VexInstruction::Andn,
+            (VEX_MAP_0F38, VEX_PP_NONE, 0xF3) => match modrm_byte >> 3 & 7 {
+                1 => VexInstruction::Blsr,
+                2 => VexInstruction::Blsmsk,
+                3 => VexInstruction::Blsi,
+                _ => VexInstruction::Invalid,
+            },
+            (VEX_MAP_0F38, VEX_PP_NONE, 0xF5) => VexInstruction::Bzhi,
+            (VEX_MAP_0F38, VEX_PP_F3, 0xF5) => VexInstruction::Pext,
+            (VEX_MAP_0F38, VEX_PP_F2, 0xF5) => VexInstruction::Pdep,
+            (VEX_MAP_0F38, VEX_PP_F2, 0xF6) => VexInstruction::Mulx,
+            (VEX_MAP_0F38, VEX_PP_NONE, 0xF7) => VexInstruction::Bextr,
+            (VEX_MAP_0F38, VEX_PP_66, 0xF7) => VexInstruction::Shlx,
+            (VEX_MAP_0F38, VEX_PP_F3, 0xF7) => VexInstruction::Sarx,
+            (VEX_MAP_0F38, VEX_PP_F2, 0xF7) => VexInstruction::Shrx,
+            (VEX_MAP_0F3A, VEX_PP_F2, 0xF0) if self.vvvv_unused => VexInstruction::Rorx,
+            _ => VexInstruction::Invalid,
+        }
+    }
+}
@@ -0,0 +1,73 @@
+#!/usr/bin/env node
+"use strict";
+
+// This test records a boot and checks that replaying it produces the same serial output
+
+const TEST_RELEASE_BUILD = +process.env.TEST_RELEASE_BUILD;
+
+var V86 = require(`../../build/${TEST_RELEASE_BUILD ? "libv86" : "libv86-debug"}.js`).V86;
+
+process.on("unhandledRejection", exn => { throw exn; });
+
+const config = {
+    bios: { url: __dirname + "/../../bios/seabios.bin" },
+    vga_bios: { url: __dirname + "/../../bios/vgabios.bin" },
+    cdrom: { url: __dirname + "/../../images/linux4.iso" },
+    network_relay_url: "<UNUSED>",
+    autostart: true,
+    memory_size: 32 * 1024 * 1024,
+    filesystem: {},
+    log_level: 0,
+    icount: 100 * 1000 * 1000,
+};
+
+const DONE_MESSAGE = "Files send via emulator appear in /mnt/";
+
+function record()
+{
+    const emulator = new V86({ ...config, record: true });
+    let serial_text = "";
+
+    emulator.add_listener("serial0-output-byte", function(byte)
+    {
+        serial_text += String.fromCharCode(byte);
+
+        if(serial_text.endsWith(DONE_MESSAGE))
+        {
+            emulator.stop();
+            const log = emulator.get_record_log();
+            console.log("Recorded " + log.byteLength + " bytes");
+            replay(log, serial_text);
+        }
+    });
+}
+
+function replay(log, expected_serial_text)
+{
+    const emulator = new V86({ ...config, replay: log });
+    let serial_text = "";
+
+    emulator.add_listener("serial0-output-byte", function(byte)
+    {
+        serial_text += String.fromCharCode(byte);
+
+        if(serial_text.endsWith(DONE_MESSAGE))
+        {
+            emulator.stop();
+
+            if(emulator.replay_diverged())
+            {
+                console.log("Fail: Replay diverged");
+                process.exit(1);
+            }
+            if(serial_text !== expected_serial_text)
+            {
+                console.log("Fail: Serial output differs");
+                process.exit(1);
+            }
+            console.log("Ok");
+        }
+    });
+}
+
+record();
@@ -20,6 +20,8 @@
   (type $t18 (func (param i32 i64 i32)))
   (type $t19 (func (param i32 i64 i32) (result i32)))
   (type $t20 (func (param i32 i64 i64 i32) (result i32)))
+  (type $t21 (func (param i32 i32 i32 i32)))
+  (type $t22 (func (param i32 i32 i32 i32) (result i32)))
   (import "e" "instr_F4" (func $e.instr_F4 (type $t0)))
   (import "e" "instr_FB_without_fault" (func $e.instr_FB_without_fault (type $t4)))
   (import "e" "trigger_gp_jit" (func $e.trigger_gp_jit (type $t2)))
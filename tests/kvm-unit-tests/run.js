@@ -4,6 +4,11 @@
 process.on("unhandledRejection", exn => { throw exn; });
 
 const TEST_RELEASE_BUILD = +process.env.TEST_RELEASE_BUILD;
+const CPU_MODEL = process.env.CPU_MODEL;
+// passed to the test as its first argument, instead of the cpu model
+const TEST_ARGS = process.env.TEST_ARGS || CPU_MODEL;
+// the exceptions of a triple fault that the test is expected to end with, e.g. "14,14,14"
+const EXPECT_TRIPLE_FAULT = process.env.EXPECT_TRIPLE_FAULT;
 
 var V86 = require(`../../build/${TEST_RELEASE_BUILD ? "libv86" : "libv86-debug"}.js`).V86;
 var fs = require("fs");
@@ -31,9 +36,14 @@ var emulator = new V86({
     bios: { buffer: bios },
     vga_bios: { buffer: vga_bios },
     multiboot: new Loader(process.argv[2]),
+    cmdline: TEST_ARGS ? process.argv[2] + " " + TEST_ARGS : "",
+    cpu_model: CPU_MODEL,
     autostart: true,
     memory_size: 64 * 1024 * 1024,
     disable_jit: +process.env.DISABLE_JIT,
+    segment_checks: true,
+    acpi: true,
+    stop_on_triple_fault: true,
     log_level: 0,
 });
 
@@ -43,6 +53,11 @@ emulator.bus.register("emulator-started", function()
         function(value)
         {
             console.log("Test exited with code " + value);
+            if(EXPECT_TRIPLE_FAULT)
+            {
+                console.log("Expected a triple fault with exceptions=" + EXPECT_TRIPLE_FAULT);
+                process.exit(1);
+            }
             process.exit(value);
         },
         function() {},
@@ -50,6 +65,19 @@ emulator.bus.register("emulator-started", function()
         function() {});
 });
 
+emulator.add_listener("cpu-event-shutdown", function(reason)
+{
+    const vectors = reason.vectors.join(",");
+    console.log("Test crashed with a triple fault: exceptions=" + vectors +
+                " eip=0x" + reason.eip.toString(16) + " cr2=0x" + reason.cr2.toString(16));
+    if(EXPECT_TRIPLE_FAULT && vectors === EXPECT_TRIPLE_FAULT)
+    {
+        console.log("The triple fault was expected");
+        process.exit(0);
+    }
+    process.exit(1);
+});
+
 emulator.add_listener("serial0-output-byte", function(byte)
 {
     var chr = String.fromCharCode(byte);
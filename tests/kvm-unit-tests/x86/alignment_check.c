@@ -0,0 +1,158 @@
+/*
+ * Test alignment checking (#AC) of data accesses at cpl 3
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "x86/desc.h"
+
+#define AC_VECTOR	17
+#define RETURN_VECTOR	0x80
+
+static unsigned fault_vector;
+static unsigned fault_error_code;
+static unsigned long fault_resume;
+
+jmp_buf return_to_kernel;
+static u8 user_stack[4096] __attribute__((aligned(16)));
+static u32 data[4] __attribute__((aligned(16)));
+
+extern char return_from_user[];
+asm (
+	"return_from_user:\n"
+	"	push $1\n"
+	"	push $return_to_kernel\n"
+	"	call longjmp\n"
+);
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	fault_error_code = regs->error_code;
+	regs->rip = fault_resume;
+}
+
+/* Run fn at cpl 3 with the given eflags.AC */
+static void run_in_user(void (*fn)(void), bool ac)
+{
+	if (setjmp(return_to_kernel)) {
+		write_ds(KERNEL_DS);
+		write_es(KERNEL_DS);
+		write_rflags(read_rflags() & ~X86_EFLAGS_AC);
+		return;
+	}
+
+	write_ds(USER_DS);
+	write_es(USER_DS);
+	asm volatile("push %0\n\t"
+		     "push %1\n\t"
+		     "pushf\n\t"
+		     "orl %2, (%%esp)\n\t"
+		     "push %3\n\t"
+		     "push $1f\n\t"
+		     "iret\n\t"
+		     "1: call *%4\n\t"
+		     "int $" xstr(RETURN_VECTOR)
+		     : : "i"(USER_DS), "r"(user_stack + sizeof(user_stack)),
+		       "r"(ac ? X86_EFLAGS_AC : 0), "i"(USER_CS), "r"(fn)
+		     : "memory");
+}
+
+static unsigned read_dword(void *p)
+{
+	u32 value;
+
+	fault_vector = 0;
+	asm volatile("movl $1f, %1\n\t"
+		     "movl (%2), %0\n\t"
+		     "1:"
+		     : "=r"(value), "=m"(fault_resume) : "r"(p) : "memory");
+	return fault_vector;
+}
+
+static unsigned write_word(void *p)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "movw $0x1234, (%1)\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : "r"(p) : "memory");
+	return fault_vector;
+}
+
+/*
+ * Read an aligned dword many times, so that the loop is compiled, and then a
+ * misaligned one. Returns the remaining iterations
+ */
+static unsigned read_dword_hot_loop(unsigned iterations)
+{
+	u8 *p = (u8 *)data;
+
+	fault_vector = 0;
+	asm volatile("movl $2f, %2\n\t"
+		     "1: movl (%0), %%eax\n\t"
+		     "decl %1\n\t"
+		     "jz 2f\n\t"
+		     "cmpl $1, %1\n\t"
+		     "jne 1b\n\t"
+		     "incl %0\n\t"
+		     "jmp 1b\n\t"
+		     "2:"
+		     : "+r"(p), "+r"(iterations), "=m"(fault_resume)
+		     : : "eax", "memory");
+	return iterations;
+}
+
+static unsigned result_aligned, result_misaligned_read, result_misaligned_write;
+static unsigned result_error_code, result_hot_loop;
+
+static void user_accesses(void)
+{
+	result_aligned = read_dword(&data[1]);
+	result_misaligned_read = read_dword((u8 *)data + 2);
+	result_error_code = fault_error_code;
+	result_misaligned_write = write_word((u8 *)data + 1);
+}
+
+static void user_hot_loop(void)
+{
+	result_hot_loop = read_dword_hot_loop(1000000) == 1 &&
+		fault_vector == AC_VECTOR && fault_error_code == 0;
+}
+
+int main(int ac, char **av)
+{
+	setup_idt();
+	handle_exception(AC_VECTOR, record_fault);
+	set_idt_entry(RETURN_VECTOR, return_from_user, 3);
+
+	write_cr0(read_cr0() | X86_CR0_AM);
+
+	run_in_user(user_accesses, true);
+	report("cpl 3, AM=1, AC=1: aligned read", result_aligned == 0);
+	report("cpl 3, AM=1, AC=1: misaligned read",
+	       result_misaligned_read == AC_VECTOR && result_error_code == 0);
+	report("cpl 3, AM=1, AC=1: misaligned write",
+	       result_misaligned_write == AC_VECTOR);
+
+	run_in_user(user_hot_loop, true);
+	report("cpl 3, AM=1, AC=1: misaligned read in a hot loop", result_hot_loop);
+
+	run_in_user(user_accesses, false);
+	report("cpl 3, AM=1, AC=0: misaligned accesses",
+	       result_misaligned_read == 0 && result_misaligned_write == 0);
+
+	write_rflags(read_rflags() | X86_EFLAGS_AC);
+	report("cpl 0, AM=1, AC=1: misaligned read", read_dword((u8 *)data + 2) == 0);
+	write_rflags(read_rflags() & ~X86_EFLAGS_AC);
+
+	write_cr0(read_cr0() & ~X86_CR0_AM);
+
+	run_in_user(user_accesses, true);
+	report("cpl 3, AM=0, AC=1: misaligned accesses",
+	       result_misaligned_read == 0 && result_misaligned_write == 0);
+
+	return report_summary();
+}
@@ -0,0 +1,198 @@
+/*
+ * Test the cpuid presets (see cpu_model in v86) and #UD for instructions of
+ * masked features. The name of the preset is passed on the command line
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "x86/desc.h"
+
+#define UD_VECTOR	6
+#define GP_VECTOR	13
+
+/* Enough to get the probes compiled by the jit */
+#define PROBE_ITERATIONS	100000
+
+enum { EAX, EBX, ECX, EDX };
+
+static unsigned fault_vector;
+static unsigned long fault_resume;
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	regs->rip = fault_resume;
+}
+
+static u32 cpuid_reg(u32 leaf, int reg)
+{
+	struct cpuid r = cpuid(leaf);
+
+	return reg == EAX ? r.a : reg == EBX ? r.b : reg == ECX ? r.c : r.d;
+}
+
+#define PROBE(name, insn)						\
+static unsigned probe_##name(void)					\
+{									\
+	fault_vector = 0;						\
+	asm volatile("movl $1f, %0\n\t"					\
+		     insn "\n\t"					\
+		     "1:"						\
+		     : "=m"(fault_resume) : :				\
+		     "eax", "ebx", "ecx", "edx", "memory");		\
+	return fault_vector;						\
+}
+
+PROBE(tsc, "rdtsc")
+PROBE(msr, "movl $0x10, %%ecx\n\trdmsr")
+PROBE(cx8, "movl (%%esp), %%eax\n\tmovl 4(%%esp), %%edx\n\t"
+	   "movl %%eax, %%ebx\n\tmovl %%edx, %%ecx\n\t"
+	   "cmpxchg8b (%%esp)")
+PROBE(cmov, "cmovz %%eax, %%eax")
+PROBE(mmx, "pxor %%mm0, %%mm0\n\temms")
+PROBE(sse, "xorps %%xmm0, %%xmm0")
+PROBE(sse2, "pxor %%xmm0, %%xmm0")
+PROBE(lfence, "lfence")
+PROBE(sse3, "movddup %%xmm0, %%xmm0")
+PROBE(ssse3, "pabsb %%xmm0, %%xmm0")
+PROBE(sse4_1, "pmulld %%xmm0, %%xmm0")
+PROBE(sse4_2, "pcmpgtq %%xmm0, %%xmm0")
+PROBE(movbe, "movbe (%%esp), %%eax")
+PROBE(popcnt, "popcnt %%eax, %%eax")
+PROBE(rdtscp, "rdtscp")
+
+static const struct {
+	const char *name;
+	u32 leaf;
+	int reg;
+	int bit;
+	unsigned (*probe)(void);
+} features[] = {
+	{ "tsc", 1, EDX, 4, probe_tsc },
+	{ "msr", 1, EDX, 5, probe_msr },
+	{ "cx8", 1, EDX, 8, probe_cx8 },
+	{ "cmov", 1, EDX, 15, probe_cmov },
+	{ "mmx", 1, EDX, 23, probe_mmx },
+	{ "sse", 1, EDX, 25, probe_sse },
+	{ "sse2", 1, EDX, 26, probe_sse2 },
+	{ "lfence", 1, EDX, 26, probe_lfence },
+	{ "sse3", 1, ECX, 0, probe_sse3 },
+	{ "ssse3", 1, ECX, 9, probe_ssse3 },
+	{ "sse4.1", 1, ECX, 19, probe_sse4_1 },
+	{ "sse4.2", 1, ECX, 20, probe_sse4_2 },
+	{ "movbe", 1, ECX, 22, probe_movbe },
+	{ "popcnt", 1, ECX, 23, probe_popcnt },
+	{ "rdtscp", 0x80000001, EDX, 27, probe_rdtscp },
+};
+
+/*
+ * Leaf 1 edx without the apic bit, which depends on acpi. The 486 and pentium
+ * presets are missing, as the tests are compiled for i686 and use cmov
+ */
+static const struct {
+	const char *name;
+	const char *vendor;
+	u32 max_leaf;
+	u32 signature;
+	u32 edx;
+} presets[] = {
+	{ "builtin", "GenuineIntel", 0, 0xf63, 0x0788a979 },
+	{ "pentium3", "GenuineIntel", 2, 0x673, 0x0380a97d },
+	{ "pentium4", "GenuineIntel", 2, 0xf29, 0x0788a97d },
+	{ "athlon", "AuthenticAMD", 1, 0x662, 0x0380a97d },
+};
+
+static void test_preset(const char *name)
+{
+	struct cpuid r = cpuid(0);
+	char vendor[13];
+	int i;
+
+	memcpy(vendor, &r.b, 4);
+	memcpy(vendor + 4, &r.d, 4);
+	memcpy(vendor + 8, &r.c, 4);
+	vendor[12] = 0;
+
+	for (i = 0; i < ARRAY_SIZE(presets); i++) {
+		if (strcmp(presets[i].name, name) == 0)
+			break;
+	}
+	if (i == ARRAY_SIZE(presets)) {
+		report("unknown preset %s", false, name);
+		return;
+	}
+
+	report("%s: vendor %s", strcmp(vendor, presets[i].vendor) == 0,
+	       name, vendor);
+	/* The maximum leaf of the built-in model is configurable */
+	report("%s: maximum leaf %x", presets[i].max_leaf == 0 ||
+	       r.a == presets[i].max_leaf, name, r.a);
+	report("%s: signature %x", cpuid(1).a == presets[i].signature,
+	       name, cpuid(1).a);
+	report("%s: features %x", (cpuid(1).d & ~(1 << 9)) == presets[i].edx,
+	       name, cpuid(1).d);
+}
+
+/* Writing cr4 bits of masked features raises #GP */
+static void test_cr4(u32 bit, bool supported, const char *name)
+{
+	u32 cr4 = read_cr4();
+
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "mov %1, %%cr4\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : "r"(cr4 | bit) : "memory");
+	report("cr4.%s: %s", fault_vector == (supported ? 0 : GP_VECTOR),
+	       name, supported ? "can be set" : "#GP");
+	write_cr4(cr4);
+}
+
+/*
+ * Execute each instruction often enough that it's compiled, and check that it
+ * raises #UD exactly when its feature is masked off
+ */
+static void test_features(void)
+{
+	int i, j;
+
+	for (i = 0; i < ARRAY_SIZE(features); i++) {
+		bool supported = cpuid_reg(features[i].leaf, features[i].reg) &
+			1 << features[i].bit;
+		unsigned expected = supported ? 0 : UD_VECTOR;
+		unsigned mismatches = 0;
+
+		for (j = 0; j < PROBE_ITERATIONS; j++) {
+			if (features[i].probe() != expected)
+				mismatches++;
+		}
+		report("%s: %s", mismatches == 0, features[i].name,
+		       supported ? "executes" : "#UD");
+	}
+}
+
+int main(int ac, char **av)
+{
+	const char *name = ac > 1 ? av[1] : "builtin";
+	bool fxsr = cpuid(1).d & 1 << 24;
+	bool pge = cpuid(1).d & 1 << 13;
+
+	setup_idt();
+	handle_exception(UD_VECTOR, record_fault);
+	handle_exception(GP_VECTOR, record_fault);
+
+	test_preset(name);
+
+	test_cr4(X86_CR4_PGE, pge, "pge");
+	test_cr4(X86_CR4_OSFXSR, fxsr, "osfxsr");
+
+	/* sse instructions raise #UD without osfxsr */
+	if (fxsr)
+		write_cr4(read_cr4() | X86_CR4_OSFXSR);
+
+	test_features();
+
+	return report_summary();
+}
@@ -10,9 +10,11 @@
  */
 
 #include "libcflat.h"
+#include "processor.h"
 #include "desc.h"
 
 static volatile unsigned long bp_addr[10], dr6[10];
+static unsigned long step[7];
 static volatile unsigned int n;
 static volatile unsigned long value;
 
@@ -63,13 +65,21 @@ static void handle_bp(struct ex_regs *regs)
 	bp_addr[0] = regs->rip;
 }
 
-int main(int ac, char **av)
+static volatile unsigned int gp_count;
+static unsigned long gp_resume;
+
+static void handle_gp(struct ex_regs *regs)
 {
-	unsigned long start;
+	gp_count++;
+	regs->rip = gp_resume;
+}
 
+int main(int ac, char **av)
+{
 	setup_idt();
 	handle_exception(DB_VECTOR, handle_db);
 	handle_exception(BP_VECTOR, handle_bp);
+	handle_exception(GP_VECTOR, handle_gp);
 
 sw_bp:
 	asm volatile("int3");
@@ -97,20 +107,23 @@ hw_bp2:
 	set_dr6(0);
 	asm volatile(
 		"pushf\n\t"
-		"pop %%rax\n\t"
-		"or $(1<<8),%%rax\n\t"
-		"push %%rax\n\t"
-		"lea (%%rip),%0\n\t"
-		"popf\n\t"
-		"and $~(1<<8),%%rax\n\t"
-		"push %%rax\n\t"
+		"pop %%" R "ax\n\t"
+		"or $(1<<8),%%" R "ax\n\t"
+		"push %%" R "ax\n\t"
 		"popf\n\t"
-		: "=g" (start) : : "rax");
+		"and $~(1<<8),%%" R "ax\n\t"
+		"1: push %%" R "ax\n\t"
+		"2: popf\n\t"
+		"3:\n\t"
+		"mov" W " $1b,%0\n\t"
+		"mov" W " $2b,%1\n\t"
+		"mov" W " $3b,%2\n\t"
+		: "=m" (step[0]), "=m" (step[1]), "=m" (step[2]) : : "eax");
 	report("single step",
 	       n == 3 &&
-	       bp_addr[0] == start+1+6 && dr6[0] == 0xffff4ff0 &&
-	       bp_addr[1] == start+1+6+1 && dr6[1] == 0xffff4ff0 &&
-	       bp_addr[2] == start+1+6+1+1 && dr6[2] == 0xffff4ff0);
+	       bp_addr[0] == step[0] && dr6[0] == 0xffff4ff0 &&
+	       bp_addr[1] == step[1] && dr6[1] == 0xffff4ff0 &&
+	       bp_addr[2] == step[2] && dr6[2] == 0xffff4ff0);
 
 	/*
 	 * cpuid and rdmsr (among others) trigger VM exits and are then
@@ -120,53 +133,118 @@ hw_bp2:
 	set_dr6(0);
 	asm volatile(
 		"pushf\n\t"
-		"pop %%rax\n\t"
-		"or $(1<<8),%%rax\n\t"
-		"push %%rax\n\t"
-		"lea (%%rip),%0\n\t"
+		"pop %%" R "ax\n\t"
+		"or $(1<<8),%%" R "ax\n\t"
+		"push %%" R "ax\n\t"
 		"popf\n\t"
-		"and $~(1<<8),%%rax\n\t"
-		"push %%rax\n\t"
-		"xor %%rax,%%rax\n\t"
-		"cpuid\n\t"
-		"movl $0x1a0,%%ecx\n\t"
-		"rdmsr\n\t"
-		"popf\n\t"
-		: "=g" (start) : : "rax", "ebx", "ecx", "edx");
+		"and $~(1<<8),%%" R "ax\n\t"
+		"1: push %%" R "ax\n\t"
+		"2: xor %%" R "ax,%%" R "ax\n\t"
+		"3: cpuid\n\t"
+		"4: movl $0x1a0,%%ecx\n\t"
+		"5: rdmsr\n\t"
+		"6: popf\n\t"
+		"7:\n\t"
+		"mov" W " $1b,%0\n\t"
+		"mov" W " $2b,%1\n\t"
+		"mov" W " $3b,%2\n\t"
+		"mov" W " $4b,%3\n\t"
+		"mov" W " $5b,%4\n\t"
+		"mov" W " $6b,%5\n\t"
+		"mov" W " $7b,%6\n\t"
+		: "=m" (step[0]), "=m" (step[1]), "=m" (step[2]), "=m" (step[3]),
+		  "=m" (step[4]), "=m" (step[5]), "=m" (step[6])
+		: : "eax", "ebx", "ecx", "edx");
 	report("single step emulated instructions",
 	       n == 7 &&
-	       bp_addr[0] == start+1+6 && dr6[0] == 0xffff4ff0 &&
-	       bp_addr[1] == start+1+6+1 && dr6[1] == 0xffff4ff0 &&
-	       bp_addr[2] == start+1+6+1+3 && dr6[2] == 0xffff4ff0 &&
-	       bp_addr[3] == start+1+6+1+3+2 && dr6[3] == 0xffff4ff0 &&
-	       bp_addr[4] == start+1+6+1+3+2+5 && dr6[4] == 0xffff4ff0 &&
-	       bp_addr[5] == start+1+6+1+3+2+5+2 && dr6[5] == 0xffff4ff0 &&
-	       bp_addr[6] == start+1+6+1+3+2+5+2+1 && dr6[6] == 0xffff4ff0);
+	       bp_addr[0] == step[0] && dr6[0] == 0xffff4ff0 &&
+	       bp_addr[1] == step[1] && dr6[1] == 0xffff4ff0 &&
+	       bp_addr[2] == step[2] && dr6[2] == 0xffff4ff0 &&
+	       bp_addr[3] == step[3] && dr6[3] == 0xffff4ff0 &&
+	       bp_addr[4] == step[4] && dr6[4] == 0xffff4ff0 &&
+	       bp_addr[5] == step[5] && dr6[5] == 0xffff4ff0 &&
+	       bp_addr[6] == step[6] && dr6[6] == 0xffff4ff0);
+
+	/* the single-step trap of mov ss is delayed until after the next instruction */
+	n = 0;
+	set_dr6(0);
+	asm volatile(
+		"pushf\n\t"
+		"pop %%" R "ax\n\t"
+		"or $(1<<8),%%" R "ax\n\t"
+		"push %%" R "ax\n\t"
+		"popf\n\t"
+		"mov %%ss,%%ecx\n\t"
+		"1: mov %%ecx,%%ss\n\t"
+		"and $~(1<<8),%%" R "ax\n\t"
+		"2: push %%" R "ax\n\t"
+		"3: popf\n\t"
+		"4:\n\t"
+		"mov" W " $1b,%0\n\t"
+		"mov" W " $2b,%1\n\t"
+		"mov" W " $3b,%2\n\t"
+		"mov" W " $4b,%3\n\t"
+		: "=m" (step[0]), "=m" (step[1]), "=m" (step[2]), "=m" (step[3])
+		: : "eax", "ecx");
+	report("single step mov ss",
+	       n == 4 &&
+	       bp_addr[0] == step[0] && dr6[0] == 0xffff4ff0 &&
+	       bp_addr[1] == step[1] && dr6[1] == 0xffff4ff0 &&
+	       bp_addr[2] == step[2] && dr6[2] == 0xffff4ff0 &&
+	       bp_addr[3] == step[3] && dr6[3] == 0xffff4ff0);
+
+	/* no single-step trap is raised for an instruction that faults */
+	n = 0;
+	gp_count = 0;
+	set_dr6(0);
+	asm volatile(
+		"mov" W " $1f,%4\n\t"
+		"pushf\n\t"
+		"pop %%" R "ax\n\t"
+		"or $(1<<8),%%" R "ax\n\t"
+		"push %%" R "ax\n\t"
+		"popf\n\t"
+		"mov %%cx,%%fs\n\t"
+		"1: and $~(1<<8),%%" R "ax\n\t"
+		"2: push %%" R "ax\n\t"
+		"3: popf\n\t"
+		"4:\n\t"
+		"mov" W " $2b,%0\n\t"
+		"mov" W " $3b,%1\n\t"
+		"mov" W " $4b,%2\n\t"
+		: "=m" (step[0]), "=m" (step[1]), "=m" (step[2]), "=m" (step[3]),
+		  "=m" (gp_resume)
+		: "c" (0xfff8) : "eax");
+	report("single step faulting instruction",
+	       gp_count == 1 && n == 3 &&
+	       bp_addr[0] == step[0] && dr6[0] == 0xffff4ff0 &&
+	       bp_addr[1] == step[1] && dr6[1] == 0xffff4ff0 &&
+	       bp_addr[2] == step[2] && dr6[2] == 0xffff4ff0);
 
 	n = 0;
 	set_dr1((void *)&value);
 	set_dr7(0x00d0040a);
 
 	asm volatile(
-		"mov $42,%%rax\n\t"
-		"mov %%rax,%0\n\t"
-		: "=m" (value) : : "rax");
-hw_wp1:
+		"mov $42,%%" R "ax\n\t"
+		"mov %%" R "ax,%0\n\t"
+		"1: mov" W " $1b,%1\n\t"
+		: "=m" (value), "=m" (step[0]) : : "eax");
 	report("hw watchpoint (test that dr6.BS is not cleared)",
 	       n == 1 &&
-	       bp_addr[0] == ((unsigned long)&&hw_wp1) && dr6[0] == 0xffff4ff2);
+	       bp_addr[0] == step[0] && dr6[0] == 0xffff4ff2);
 
 	n = 0;
 	set_dr6(0);
 
 	asm volatile(
-		"mov $42,%%rax\n\t"
-		"mov %%rax,%0\n\t"
-		: "=m" (value) : : "rax");
-hw_wp2:
+		"mov $42,%%" R "ax\n\t"
+		"mov %%" R "ax,%0\n\t"
+		"1: mov" W " $1b,%1\n\t"
+		: "=m" (value), "=m" (step[0]) : : "eax");
 	report("hw watchpoint (test that dr6.BS is not set)",
 	       n == 1 &&
-	       bp_addr[0] == ((unsigned long)&&hw_wp2) && dr6[0] == 0xffff0ff2);
+	       bp_addr[0] == step[0] && dr6[0] == 0xffff0ff2);
 
 	n = 0;
 	set_dr6(0);
@@ -177,5 +255,33 @@ sw_icebp:
 	       bp_addr[0] == (unsigned long)&&sw_icebp + 1 &&
 	       dr6[0] == 0xffff0ff0);
 
+	/* i/o breakpoints are enabled by cr4.DE */
+	write_cr4(read_cr4() | X86_CR4_DE);
+	n = 0;
+	set_dr6(0);
+	set_dr0((void *)0x80);
+	set_dr7(0x00020402);
+	asm volatile("outb %%al,$0x80\n\t"
+		     "1: mov" W " $1b,%0" : "=m" (step[0]) : "a" (0));
+	report("i/o breakpoint",
+	       n == 1 && bp_addr[0] == step[0] && dr6[0] == 0xffff0ff1);
+
+	n = 0;
+	set_dr6(0);
+	asm volatile("inw $0x7f,%%ax\n\t"
+		     "1: mov" W " $1b,%0" : "=m" (step[0]) : : "eax");
+	report("i/o breakpoint (overlapping access)",
+	       n == 1 && bp_addr[0] == step[0] && dr6[0] == 0xffff0ff1);
+
+	n = 0;
+	asm volatile("outb %%al,$0x81" : : "a" (0));
+	report("i/o breakpoint (other port)", n == 0);
+
+	n = 0;
+	write_cr4(read_cr4() & ~X86_CR4_DE);
+	asm volatile("outb %%al,$0x80" : : "a" (0));
+	report("i/o breakpoint (ignored without cr4.DE)", n == 0);
+	set_dr7(0x00000400);
+
 	return report_summary();
 }
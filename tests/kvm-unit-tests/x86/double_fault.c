@@ -0,0 +1,83 @@
+/*
+ * Test that exceptions raised while delivering an exception are combined
+ * according to their classes
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "x86/desc.h"
+
+#define DF_VECTOR	8
+#define NP_VECTOR	11
+
+/* outside of the gdt */
+#define INVALID_SEL	0xfff8
+
+static unsigned fault_vector;
+static unsigned fault_error_code;
+static unsigned long fault_resume;
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	fault_error_code = regs->error_code;
+	regs->rip = fault_resume;
+}
+
+static unsigned try_load_fs(u16 sel)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "mov %1, %%fs\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : "r"(sel) : "memory");
+	return fault_vector;
+}
+
+static unsigned try_ud2(void)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "ud2\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : : "memory");
+	return fault_vector;
+}
+
+static void test_contributory(void)
+{
+	/* #gp, then #np for its gate: two contributory exceptions */
+	boot_idt[GP_VECTOR].p = 0;
+	report("#np while delivering #gp",
+	       try_load_fs(INVALID_SEL) == DF_VECTOR && fault_error_code == 0);
+	boot_idt[GP_VECTOR].p = 1;
+
+	report("#gp without double fault",
+	       try_load_fs(INVALID_SEL) == GP_VECTOR &&
+	       fault_error_code == INVALID_SEL);
+}
+
+static void test_benign(void)
+{
+	/* #ud is benign, so the #np for its gate is handled serially */
+	boot_idt[UD_VECTOR].p = 0;
+	report("#np while delivering #ud",
+	       try_ud2() == NP_VECTOR &&
+	       fault_error_code == (UD_VECTOR << 3 | 2 | 1));
+	boot_idt[UD_VECTOR].p = 1;
+}
+
+int main(int ac, char **av)
+{
+	setup_idt();
+	handle_exception(DF_VECTOR, record_fault);
+	handle_exception(NP_VECTOR, record_fault);
+	handle_exception(GP_VECTOR, record_fault);
+
+	test_contributory();
+	test_benign();
+
+	return report_summary();
+}
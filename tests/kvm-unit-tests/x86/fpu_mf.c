@@ -0,0 +1,182 @@
+/*
+ * Test the delivery of unmasked x87 exceptions: #MF on the next waiting
+ * fpu instruction while CR0.NE is set, IRQ13 through the pic while it is clear
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "asm/io.h"
+#include "x86/desc.h"
+
+#define MF_VECTOR	16
+
+#define X86_CR0_NE	0x00000020
+
+/* the slave pic is remapped to 0x28, irq13 is its line 5 */
+#define PIC_BASE	0x20
+#define IRQ13_VECTOR	(PIC_BASE + 13)
+
+#define FPU_SW_ZE	(1 << 2)
+#define FPU_SW_ES	(1 << 7)
+
+/* the default control word with the zero divide exception unmasked */
+static u16 unmask_ze = 0x37f & ~FPU_SW_ZE;
+static float one = 1, zero = 0;
+
+static unsigned fault_vector;
+static unsigned long fault_ip;
+static unsigned long fault_resume;
+static unsigned long expected_ip;
+static u16 status_word;
+
+volatile unsigned irq13_count;
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	fault_ip = regs->rip;
+	regs->rip = fault_resume;
+	asm volatile("fnclex");
+}
+
+/* clears ferr# through port 0xf0 and the exception, then acknowledges the irq */
+extern char irq13_entry[];
+asm (
+	"irq13_entry:\n\t"
+	"push %eax\n\t"
+	"incl irq13_count\n\t"
+	"xor %eax, %eax\n\t"
+	"outb %al, $0xf0\n\t"
+	"fnclex\n\t"
+	"movb $0x20, %al\n\t"
+	"outb %al, $0xa0\n\t"
+	"outb %al, $0x20\n\t"
+	"pop %eax\n\t"
+	"iret\n\t"
+);
+
+static void reset(void)
+{
+	fault_vector = -1;
+	fault_ip = 0;
+	expected_ip = 0;
+	irq13_count = 0;
+	asm volatile("fninit\n\t"
+		     "fldcw %0" : : "m"(unmask_ze));
+}
+
+/* divides by zero, followed by a waiting fwait */
+static void fdiv_zero_fwait(void)
+{
+	asm volatile("movl $1f, %[resume]\n\t"
+		     "flds %[one]\n\t"
+		     "fdivs %[zero]\n\t"
+		     "fnstsw %[sw]\n\t"
+		     "2: fwait\n\t"
+		     "nop\n\t"
+		     "1: movl $2b, %[expected]\n\t"
+		     "fninit"
+		     : [resume] "=m"(fault_resume), [expected] "=m"(expected_ip),
+		       [sw] "=m"(status_word)
+		     : [one] "m"(one), [zero] "m"(zero)
+		     : "memory");
+}
+
+static void test_mf(void)
+{
+	write_cr0(read_cr0() | X86_CR0_NE);
+
+	reset();
+	fdiv_zero_fwait();
+	report("#MF on fwait", fault_vector == MF_VECTOR && fault_ip == expected_ip);
+	report("es set before fwait", status_word & FPU_SW_ES);
+
+	reset();
+	asm volatile("movl $1f, %[resume]\n\t"
+		     "flds %[one]\n\t"
+		     "fdivs %[zero]\n\t"
+		     "fnstsw %[sw]\n\t"
+		     "fnstcw %[sw]\n\t"
+		     "2: fld1\n\t"
+		     "nop\n\t"
+		     "1: movl $2b, %[expected]\n\t"
+		     "fninit"
+		     : [resume] "=m"(fault_resume), [expected] "=m"(expected_ip),
+		       [sw] "=m"(status_word)
+		     : [one] "m"(one), [zero] "m"(zero)
+		     : "memory");
+	report("#MF on next fpu instruction",
+	       fault_vector == MF_VECTOR && fault_ip == expected_ip);
+
+	reset();
+	asm volatile("movl $1f, %[resume]\n\t"
+		     "flds %[one]\n\t"
+		     "fdivs %[zero]\n\t"
+		     "fninit\n\t"
+		     "fwait\n\t"
+		     "1:"
+		     : [resume] "=m"(fault_resume)
+		     : [one] "m"(one), [zero] "m"(zero)
+		     : "memory");
+	report("fninit discards pending exception", fault_vector == -1);
+
+	reset();
+	asm volatile("fldcw %0" : : "m"((u16){0x37f}));
+	fdiv_zero_fwait();
+	report("masked exception", fault_vector == -1 && !(status_word & FPU_SW_ES));
+}
+
+static void setup_pic(void)
+{
+	/* icw1-icw4: edge triggered, cascaded, 8086 mode */
+	outb(0x11, 0x20);
+	outb(0x11, 0xa0);
+	outb(PIC_BASE, 0x21);
+	outb(PIC_BASE + 8, 0xa1);
+	outb(4, 0x21);
+	outb(2, 0xa1);
+	outb(1, 0x21);
+	outb(1, 0xa1);
+
+	/* unmask the cascade and irq13 only */
+	outb(~(1 << 2), 0x21);
+	outb(~(1 << 5), 0xa1);
+}
+
+static void test_irq13(void)
+{
+	setup_pic();
+	set_idt_entry(IRQ13_VECTOR, irq13_entry, 0);
+	write_cr0(read_cr0() & ~X86_CR0_NE);
+
+	reset();
+	irq_enable();
+	fdiv_zero_fwait();
+	irq_disable();
+	report("irq13 on fwait", fault_vector == -1 && irq13_count == 1);
+
+	reset();
+	fdiv_zero_fwait();
+	irq_enable();
+	asm volatile("nop");
+	irq_disable();
+	report("irq13 while interrupts are disabled",
+	       fault_vector == -1 && irq13_count == 1);
+
+	outb(0xff, 0x21);
+	outb(0xff, 0xa1);
+	write_cr0(read_cr0() | X86_CR0_NE);
+}
+
+int main(void)
+{
+	setup_idt();
+	handle_exception(MF_VECTOR, record_fault);
+
+	test_mf();
+	test_irq13();
+
+	return report_summary();
+}
@@ -0,0 +1,156 @@
+/*
+ * Test the checks done by ltr and lldt
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "x86/desc.h"
+
+#define NP_VECTOR	11
+
+#define TEST_TSS_SEL	(FIRST_SPARE_SEL)
+#define NP_TSS_SEL	(FIRST_SPARE_SEL + 0x08)
+#define TEST_LDT_SEL	(FIRST_SPARE_SEL + 0x10)
+#define NP_LDT_SEL	(FIRST_SPARE_SEL + 0x18)
+
+/* index 1 of the ldt, index 2 is outside of its limit */
+#define LDT_DATA_SEL	0x0c
+#define LDT_OUTSIDE_SEL	0x14
+
+static tss32_t test_tss;
+static u64 test_ldt[2] = { 0, 0x00cf93000000ffffull };
+
+static unsigned fault_vector;
+static unsigned fault_error_code;
+static unsigned long fault_resume;
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	fault_error_code = regs->error_code;
+	regs->rip = fault_resume;
+}
+
+static unsigned try_ltr(u16 sel)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "ltr %1\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : "r"(sel) : "memory");
+	return fault_vector;
+}
+
+static unsigned try_lldt(u16 sel)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "lldt %1\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : "r"(sel) : "memory");
+	return fault_vector;
+}
+
+static unsigned try_load_fs(u16 sel)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "mov %1, %%fs\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : "r"(sel) : "memory");
+	return fault_vector;
+}
+
+static bool faulted(unsigned vector, unsigned expected_vector, u16 error_code)
+{
+	return vector == expected_vector && fault_error_code == error_code;
+}
+
+static u16 first_sel_outside_gdt(void)
+{
+	struct descriptor_table_ptr gdt;
+
+	sgdt(&gdt);
+	return (gdt.limit + 1) & ~7;
+}
+
+static void test_ltr(void)
+{
+	u16 outside = first_sel_outside_gdt();
+
+	set_gdt_entry(TEST_TSS_SEL, (u32)&test_tss, sizeof(test_tss) - 1, 0x89, 0);
+	set_gdt_entry(NP_TSS_SEL, (u32)&test_tss, sizeof(test_tss) - 1, 0x09, 0);
+	set_gdt_entry(TEST_LDT_SEL, (u32)test_ldt, sizeof(test_ldt) - 1, 0x82, 0);
+
+	report("ltr: null selector", faulted(try_ltr(0), GP_VECTOR, 0));
+	report("ltr: null selector with rpl",
+	       faulted(try_ltr(3), GP_VECTOR, 0));
+	report("ltr: ldt selector",
+	       faulted(try_ltr(TEST_TSS_SEL | 4), GP_VECTOR, TEST_TSS_SEL | 4));
+	report("ltr: outside of gdt limit",
+	       faulted(try_ltr(outside), GP_VECTOR, outside));
+	report("ltr: data segment",
+	       faulted(try_ltr(KERNEL_DS), GP_VECTOR, KERNEL_DS));
+	report("ltr: ldt descriptor",
+	       faulted(try_ltr(TEST_LDT_SEL), GP_VECTOR, TEST_LDT_SEL));
+	report("ltr: busy tss",
+	       faulted(try_ltr(TSS_MAIN), GP_VECTOR, TSS_MAIN));
+	report("ltr: not present",
+	       faulted(try_ltr(NP_TSS_SEL | 3), NP_VECTOR, NP_TSS_SEL));
+	report("ltr: faults leave tr unchanged", str() == TSS_MAIN);
+
+	report("ltr: valid tss",
+	       try_ltr(TEST_TSS_SEL) == 0 && str() == TEST_TSS_SEL);
+	report("ltr: marks tss busy", gdt32[TEST_TSS_SEL / 8].access == 0x8b);
+
+	/* restore the original task register */
+	gdt32[TSS_MAIN / 8].access &= ~2;
+	ltr(TSS_MAIN);
+}
+
+static void test_lldt(void)
+{
+	u16 outside = first_sel_outside_gdt();
+
+	set_gdt_entry(TEST_LDT_SEL, (u32)test_ldt, sizeof(test_ldt) - 1, 0x82, 0);
+	set_gdt_entry(NP_LDT_SEL, (u32)test_ldt, sizeof(test_ldt) - 1, 0x02, 0);
+
+	report("lldt: null selector", try_lldt(0) == 0);
+	report("lldt: null selector with ti", try_lldt(4) == 0);
+	report("lldt: ldt access with null ldtr",
+	       faulted(try_load_fs(LDT_DATA_SEL), GP_VECTOR, LDT_DATA_SEL));
+
+	report("lldt: ldt selector",
+	       faulted(try_lldt(TEST_LDT_SEL | 4), GP_VECTOR, TEST_LDT_SEL | 4));
+	report("lldt: outside of gdt limit",
+	       faulted(try_lldt(outside), GP_VECTOR, outside));
+	report("lldt: data segment",
+	       faulted(try_lldt(KERNEL_DS), GP_VECTOR, KERNEL_DS));
+	report("lldt: tss descriptor",
+	       faulted(try_lldt(TSS_MAIN), GP_VECTOR, TSS_MAIN));
+	report("lldt: not present",
+	       faulted(try_lldt(NP_LDT_SEL | 3), NP_VECTOR, NP_LDT_SEL));
+
+	report("lldt: valid ldt",
+	       try_lldt(TEST_LDT_SEL) == 0 && sldt() == TEST_LDT_SEL);
+	report("lldt: load from ldt", try_load_fs(LDT_DATA_SEL) == 0);
+	report("lldt: outside of ldt limit",
+	       faulted(try_load_fs(LDT_OUTSIDE_SEL), GP_VECTOR, LDT_OUTSIDE_SEL));
+
+	write_fs(KERNEL_DS);
+	try_lldt(0);
+}
+
+int main(int ac, char **av)
+{
+	setup_idt();
+	handle_exception(GP_VECTOR, record_fault);
+	handle_exception(NP_VECTOR, record_fault);
+
+	test_ltr();
+	test_lldt();
+
+	return report_summary();
+}
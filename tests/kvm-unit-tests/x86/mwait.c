@@ -0,0 +1,131 @@
+/*
+ * Test the wakeups of mwait: writes to the monitored line and interrupts,
+ * including masked ones with ecx bit 0
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "apic.h"
+#include "vm.h"
+#include "smp.h"
+#include "isr.h"
+
+#define TIMER_VECTOR	0xee
+
+/* At 1 GHz: The safety net for waits that should end without the timer */
+#define TIMER_LONG	100000000
+#define TIMER_SHORT	1000000
+
+static u8 monitored[4096] __attribute__((aligned(4096)));
+static u8 other[4096] __attribute__((aligned(4096)));
+
+static volatile unsigned timer_count;
+
+static void timer_handler(isr_regs_t *regs)
+{
+	timer_count++;
+	eoi();
+}
+
+static void start_timer(u32 count)
+{
+	timer_count = 0;
+	apic_write(APIC_TMICT, count);
+}
+
+static void stop_timer(void)
+{
+	apic_write(APIC_TMICT, 0);
+}
+
+static void monitor(void *p)
+{
+	asm volatile("monitor" : : "a"(p), "c"(0), "d"(0));
+}
+
+static void mwait(u32 ecx)
+{
+	asm volatile("mwait" : : "a"(0), "c"(ecx) : "memory");
+}
+
+/*
+ * Write many times to another page, so that the loop is compiled, and then to
+ * the monitored line
+ */
+static void write_hot_loop(unsigned iterations)
+{
+	u8 *p = other;
+
+	asm volatile("1: movb $1, (%0)\n\t"
+		     "decl %1\n\t"
+		     "jz 2f\n\t"
+		     "cmpl $1, %1\n\t"
+		     "jne 1b\n\t"
+		     "movl %2, %0\n\t"
+		     "jmp 1b\n\t"
+		     "2:"
+		     : "+r"(p), "+r"(iterations) : "r"(monitored) : "memory");
+}
+
+static void test_monitored_write(void)
+{
+	irq_enable();
+
+	monitor(monitored + 8);
+	*(volatile u8 *)(monitored + 16) = 1;
+	start_timer(TIMER_LONG);
+	mwait(0);
+	report("write to the monitored line before mwait", timer_count == 0);
+	stop_timer();
+
+	monitor(monitored + 8);
+	*(volatile u8 *)other = 1;
+	start_timer(TIMER_SHORT);
+	mwait(0);
+	report("write to another page: mwait waits for an interrupt",
+	       timer_count == 1);
+
+	monitor(monitored + 8);
+	write_hot_loop(1000000);
+	start_timer(TIMER_LONG);
+	mwait(0);
+	report("write to the monitored line in a hot loop", timer_count == 0);
+	stop_timer();
+
+	irq_disable();
+}
+
+static void test_masked_interrupt(void)
+{
+	irq_disable();
+
+	monitor(monitored);
+	start_timer(TIMER_SHORT);
+	mwait(1);
+	report("ecx=1: masked interrupt ends mwait without being delivered",
+	       timer_count == 0);
+
+	irq_enable();
+	asm volatile("nop");
+	report("ecx=1: interrupt is delivered after sti", timer_count == 1);
+	irq_disable();
+}
+
+int main(int ac, char **av)
+{
+	setup_vm();
+	smp_init();
+	mask_pic_interrupts();
+
+	handle_irq(TIMER_VECTOR, timer_handler);
+	apic_write(APIC_LVTT, APIC_LVT_TIMER_ONESHOT | TIMER_VECTOR);
+	/* Divider 1 */
+	apic_write(APIC_TDCR, 0xb);
+
+	test_monitored_write();
+	test_masked_interrupt();
+
+	return report_summary();
+}
@@ -0,0 +1,156 @@
+/*
+ * Test the execute-disable bit of PAE page directory and page table entries,
+ * and the reserved bit page faults it causes while EFER.NXE is clear
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "msr.h"
+#include "asm/page.h"
+#include "x86/desc.h"
+
+#define PF_VECTOR	14
+
+#define HUGE_PAGE_SIZE	(1ul << 21)
+
+/* aliases of the low 1GB of physical memory, mapped by huge pages and by page tables */
+#define HUGE_ALIAS	0x40000000u
+#define TABLE_ALIAS	0xc0000000u
+
+#define PF_PRESENT	(1 << 0)
+#define PF_RSVD		(1 << 3)
+#define PF_FETCH	(1 << 4)
+
+static u64 pdpt[4] __attribute__((aligned(0x20)));
+static u64 page_dirs[4 * 512] __attribute__((aligned(0x1000)));
+static u64 page_table[512] __attribute__((aligned(0x1000)));
+
+/* jmp *%ecx */
+static u8 code[] = { 0xff, 0xe1 };
+
+static unsigned fault_vector;
+static unsigned fault_error_code;
+static unsigned long fault_cr2;
+static unsigned long fault_resume;
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	fault_error_code = regs->error_code;
+	fault_cr2 = read_cr2();
+	regs->rip = fault_resume;
+}
+
+static void setup_mmu(void)
+{
+	u32 code_region = (u32)code & ~(HUGE_PAGE_SIZE - 1);
+	u64 phys = 0;
+
+	for (int i = 0; i < 4; i++)
+		pdpt[i] = (u32)&page_dirs[i * 512] | PT_PRESENT_MASK;
+
+	/* identity map everything, so that the apic stays accessible */
+	for (int i = 0; i < 4 * 512; i++, phys += HUGE_PAGE_SIZE)
+		page_dirs[i] = phys | PT_PRESENT_MASK | PT_WRITABLE_MASK |
+			PT_PAGE_SIZE_MASK;
+	for (int i = 0; i < 512; i++)
+		page_dirs[HUGE_ALIAS / HUGE_PAGE_SIZE + i] -= HUGE_ALIAS;
+
+	/* map the 2MB region containing the code through a page table as well */
+	for (int i = 0; i < 512; i++)
+		page_table[i] = (code_region + i * PAGE_SIZE) | PT_PRESENT_MASK |
+			PT_WRITABLE_MASK;
+	page_dirs[(TABLE_ALIAS + code_region) >> 21] =
+		(u32)page_table | PT_PRESENT_MASK | PT_WRITABLE_MASK;
+
+	write_cr0(read_cr0() & ~X86_CR0_PG);
+	write_cr4(read_cr4() | X86_CR4_PAE);
+	write_cr3((u32)pdpt);
+	write_cr0(read_cr0() | X86_CR0_PG | X86_CR0_WP);
+}
+
+static u64 *huge_entry(void)
+{
+	return &page_dirs[(HUGE_ALIAS + (u32)code) >> 21];
+}
+
+static u64 *table_entry(void)
+{
+	return &page_table[((u32)code >> 12) & 511];
+}
+
+static unsigned try_execute(u32 address)
+{
+	fault_vector = 0;
+	invlpg((void *)address);
+	asm volatile("movl $1f, %0\n\t"
+		     "movl $1f, %%ecx\n\t"
+		     "jmp *%1\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : "r"(address) : "ecx", "memory");
+	return fault_vector;
+}
+
+static unsigned try_read(u32 address)
+{
+	u32 value;
+
+	fault_vector = 0;
+	invlpg((void *)address);
+	asm volatile("movl $1f, %1\n\t"
+		     "movl (%2), %0\n\t"
+		     "1:"
+		     : "=r"(value), "=m"(fault_resume) : "r"(address) : "memory");
+	return fault_vector;
+}
+
+static bool page_fault(unsigned vector, u32 address, unsigned error_code)
+{
+	return vector == PF_VECTOR && fault_cr2 == address &&
+		fault_error_code == error_code;
+}
+
+static void test_entry(const char *level, u64 *entry, u32 address)
+{
+	wrmsr(MSR_EFER, rdmsr(MSR_EFER) | EFER_NX);
+
+	report("%s: execute", try_execute(address) == 0, level);
+	*entry |= PT64_NX_MASK;
+	report("%s: execute-disable",
+	       page_fault(try_execute(address), address, PF_PRESENT | PF_FETCH),
+	       level);
+	report("%s: read from execute-disable page", try_read(address) == 0, level);
+
+	wrmsr(MSR_EFER, rdmsr(MSR_EFER) & ~EFER_NX);
+
+	report("%s: reserved bit on read",
+	       page_fault(try_read(address), address, PF_PRESENT | PF_RSVD),
+	       level);
+	report("%s: reserved bit on execute",
+	       page_fault(try_execute(address), address, PF_PRESENT | PF_RSVD),
+	       level);
+	*entry &= ~PT64_NX_MASK;
+	report("%s: read", try_read(address) == 0, level);
+}
+
+int main(int ac, char **av)
+{
+	struct cpuid c = cpuid(0x80000001);
+
+	setup_idt();
+	handle_exception(PF_VECTOR, record_fault);
+
+	if (!(c.d & (1 << 20))) {
+		report_skip("execute-disable not supported");
+		return report_summary();
+	}
+
+	setup_mmu();
+
+	test_entry("page directory", huge_entry(), HUGE_ALIAS + (u32)code);
+	test_entry("page table", table_entry(), TABLE_ALIAS + (u32)code);
+
+	return report_summary();
+}
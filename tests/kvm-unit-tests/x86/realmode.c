@@ -832,6 +832,51 @@ static void test_int(void)
 	report("int 1", 0, 1);
 }
 
+extern struct table_descr r_idt_descr;
+void moved_ivt_handler(void);
+void moved_ivt_gp_handler(void);
+
+/* The #GP handler skips the two bytes of the faulting int instruction */
+asm(
+	"moved_ivt_handler: \n\t"
+	"mov $1, %ax \n\t"
+	"iretw \n\t"
+	"moved_ivt_gp_handler: \n\t"
+	"push %bp \n\t"
+	"mov %sp, %bp \n\t"
+	"addw $2, 2(%bp) \n\t"
+	"pop %bp \n\t"
+	"mov $2, %ax \n\t"
+	"iretw \n\t"
+   );
+
+static void test_int_moved_ivt(void)
+{
+	u32 *ivt = (u32 *)0x2000;
+	struct table_descr moved = { 0x3ff, ivt };
+	struct table_descr small = { 0x11 * 4 + 3, ivt };
+
+	ivt[0x11] = (u32)moved_ivt_handler;
+	ivt[13] = (u32)moved_ivt_gp_handler;
+
+	MK_INSN(int_moved_ivt, "lidtw (%ebx)\n\t"
+			       "int $0x11\n\t"
+			       "lidtw (%ecx)\n\t");
+	MK_INSN(int_ivt_limit, "lidtw (%ebx)\n\t"
+			       "int $0x12\n\t"
+			       "lidtw (%ecx)\n\t");
+
+	init_inregs(&(struct regs){ .ebx = (unsigned)&moved,
+				    .ecx = (unsigned)&r_idt_descr });
+	exec_in_big_real_mode(&insn_int_moved_ivt);
+	report("int with moved ivt", R_AX, outregs.eax == 1);
+
+	init_inregs(&(struct regs){ .ebx = (unsigned)&small,
+				    .ecx = (unsigned)&r_idt_descr });
+	exec_in_big_real_mode(&insn_int_ivt_limit);
+	report("int beyond ivt limit: #GP", R_AX, outregs.eax == 2);
+}
+
 static void test_sti_inhibit(void)
 {
 	init_inregs(NULL);
@@ -1802,6 +1847,7 @@ void realmode_start(void)
 	test_xchg();
 	test_iret();
 	test_int();
+	test_int_moved_ivt();
 	test_sti_inhibit();
 	test_imul();
 	test_mul();
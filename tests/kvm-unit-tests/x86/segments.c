@@ -0,0 +1,443 @@
+/*
+ * Test segment limits and far transfers through call gates, also from cpl 3
+ * with parameters, and onto expand-down stacks
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "x86/desc.h"
+
+#define LIMITED_DS_SEL		(FIRST_SPARE_SEL)
+#define LIMITED_CS_SEL		(FIRST_SPARE_SEL + 0x08)
+#define EXPAND_DOWN_SS_SEL	(FIRST_SPARE_SEL + 0x10)
+#define CALL_GATE_SEL		(FIRST_SPARE_SEL + 0x18)
+#define JUMP_GATE_SEL		(FIRST_SPARE_SEL + 0x20)
+
+/*
+ * The inter-privilege tests run last and reuse the spare selectors: Code
+ * segments with the base below the stubs, so that their offsets fit into 16
+ * bits, a 16-bit user stack and gates that can be called from cpl 3
+ */
+#define GATE_KERNEL_CS_SEL	(FIRST_SPARE_SEL)
+#define GATE_USER_CS_SEL	(FIRST_SPARE_SEL + 0x08)
+#define GATE_USER_SS16_SEL	(FIRST_SPARE_SEL + 0x10)
+#define USER_CALL_GATE16_SEL	(FIRST_SPARE_SEL + 0x18)
+#define USER_CALL_GATE32_SEL	(FIRST_SPARE_SEL + 0x20)
+
+/* And the tests of stacks at cpl 1, for faults on them to be delivered at cpl 0 */
+#define RING1_SS_SEL		(FIRST_SPARE_SEL)
+#define RING1_CALL_GATE_SEL	(FIRST_SPARE_SEL + 0x08)
+#define RING1_CS_SEL		(FIRST_SPARE_SEL + 0x28)
+
+#define SS_VECTOR		12
+
+#define RETURN_VECTOR		0x80
+
+unsigned fault_vector;
+static unsigned fault_error_code;
+unsigned long fault_resume;
+
+unsigned long gate_caller_cs;
+unsigned long gate_return;
+bool jump_gate_reached;
+
+void call_gate_target(void);
+void jump_gate_target(void);
+void far_call_target(void);
+
+asm (
+	"call_gate_target:\n"
+	"	mov 4(%esp), %eax\n"
+	"	mov %eax, gate_caller_cs\n"
+	"	lret\n"
+	"jump_gate_target:\n"
+	"	movb $1, jump_gate_reached\n"
+	"	jmp *gate_return\n"
+	"far_call_target:\n"
+	"	mov 4(%esp), %ebx\n"
+	"	lret\n"
+);
+
+jmp_buf return_to_kernel;
+static u8 user_stack[4096] __attribute__((aligned(4096)));
+static u8 ring1_stack[8192] __attribute__((aligned(4096)));
+static u8 ring1_stack16[0x10000] __attribute__((aligned(4096)));
+
+/* The frame on the cpl 0 stack and the esp of the caller, written by the stubs */
+u32 gate_frame[6];
+u16 gate_target_cs, gate_target_ss;
+u32 gate_caller_esp, gate_return_esp;
+u32 gate_target_esp;
+
+extern char gate_stubs[], gate_target16[], gate_target32[];
+extern char user_gate16_caller[], user_gate16_return[];
+extern char user_gate32_caller[], user_gate32_return[];
+extern char ring1_target[], user_ring1_caller[], user_ring1_return[];
+
+extern char return_from_user[];
+asm (
+	"return_from_user:\n"
+	"	push $1\n"
+	"	push $return_to_kernel\n"
+	"	call longjmp\n"
+);
+
+/*
+ * Position-independent, as they run in segments with a base: The targets copy
+ * their stack frame and return, removing the parameters from both stacks, and
+ * the cpl 3 callers push two parameters
+ */
+asm (
+	".balign 256\n"
+	"gate_stubs:\n"
+	"gate_target16:\n"
+	"	movw %cs, gate_target_cs\n"
+	"	movw %ss, gate_target_ss\n"
+	"	mov %esp, %esi\n"
+	"	mov $gate_frame, %edi\n"
+	"	mov $3, %ecx\n"
+	"	rep movsl\n"
+	"	lretw $4\n"
+	"gate_target32:\n"
+	"	movw %cs, gate_target_cs\n"
+	"	movw %ss, gate_target_ss\n"
+	"	mov %esp, %esi\n"
+	"	mov $gate_frame, %edi\n"
+	"	mov $6, %ecx\n"
+	"	rep movsl\n"
+	"	lret $8\n"
+	"user_gate16_caller:\n"
+	"	pushw $0x1111\n"
+	"	pushw $0x2222\n"
+	"	mov %esp, gate_caller_esp\n"
+	"	lcall $" xstr(USER_CALL_GATE16_SEL) " | 3, $0\n"
+	"user_gate16_return:\n"
+	"	mov %esp, gate_return_esp\n"
+	"	int $" xstr(RETURN_VECTOR) "\n"
+	"user_gate32_caller:\n"
+	"	push $0x11111111\n"
+	"	push $0x22222222\n"
+	"	mov %esp, gate_caller_esp\n"
+	"	lcall $" xstr(USER_CALL_GATE32_SEL) " | 3, $0\n"
+	"user_gate32_return:\n"
+	"	mov %esp, gate_return_esp\n"
+	"	int $" xstr(RETURN_VECTOR) "\n"
+);
+
+/* The caller runs in the flat user code segment, so that faults can resume it */
+asm (
+	"ring1_target:\n"
+	"	movw %ss, gate_target_ss\n"
+	"	mov %esp, gate_target_esp\n"
+	"	lret $8\n"
+	"user_ring1_caller:\n"
+	"	push $0x11111111\n"
+	"	push $0x22222222\n"
+	"	movl $0, fault_vector\n"
+	"	movl $user_ring1_return, fault_resume\n"
+	"	mov %esp, gate_caller_esp\n"
+	"	lcall $" xstr(RING1_CALL_GATE_SEL) " | 3, $0\n"
+	"user_ring1_return:\n"
+	"	mov %esp, gate_return_esp\n"
+	"	int $" xstr(RETURN_VECTOR) "\n"
+);
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	fault_error_code = regs->error_code;
+	regs->rip = fault_resume;
+}
+
+static void set_gate(int sel, u16 cs, u32 target, u8 access, int params)
+{
+	u64 offset = target;
+
+	*(u64 *)&gdt32[sel / 8] = (offset & 0xffff) | (cs << 16) |
+		((u64)params << 32) | ((u64)access << 40) |
+		((offset >> 16) << 48);
+}
+
+static void set_call_gate(int sel, void (*target)(void))
+{
+	/* 32-bit call gate to the kernel code segment without parameters */
+	set_gate(sel, KERNEL_CS, (u32)target, 0x8c, 0);
+}
+
+/* Enter cpl 3 at cs:eip with the stack ss:esp, until int RETURN_VECTOR */
+static void run_in_user(u16 cs, u32 eip, u16 ss, u32 esp)
+{
+	if (setjmp(return_to_kernel)) {
+		write_ds(KERNEL_DS);
+		write_es(KERNEL_DS);
+		return;
+	}
+
+	write_ds(USER_DS);
+	write_es(USER_DS);
+	asm volatile("push %0\n\t"
+		     "push %1\n\t"
+		     "pushf\n\t"
+		     "push %2\n\t"
+		     "push %3\n\t"
+		     "iret"
+		     : : "r"((u32)ss), "r"(esp), "r"((u32)cs), "r"(eip)
+		     : "memory");
+}
+
+static unsigned try_read_fs(u32 offset)
+{
+	u32 value;
+
+	fault_vector = 0;
+	asm volatile("movl $1f, %1\n\t"
+		     "movl %%fs:(%2), %0\n\t"
+		     "1:"
+		     : "=r"(value), "=m"(fault_resume) : "r"(offset)
+		     : "memory");
+	return fault_vector;
+}
+
+/*
+ * Read the last dword of fs many times, so that the loop is compiled, and
+ * then the dword crossing the limit. Returns the remaining iterations
+ */
+static unsigned read_fs_hot_loop(unsigned iterations)
+{
+	u32 offset = 0xffc;
+
+	fault_vector = 0;
+	asm volatile("movl $2f, %2\n\t"
+		     "1: movl %%fs:(%0), %%eax\n\t"
+		     "decl %1\n\t"
+		     "jz 2f\n\t"
+		     "cmpl $1, %1\n\t"
+		     "jne 1b\n\t"
+		     "movl $0xffe, %0\n\t"
+		     "jmp 1b\n\t"
+		     "2:"
+		     : "+r"(offset), "+r"(iterations), "=m"(fault_resume)
+		     : : "eax", "memory");
+	return iterations;
+}
+
+static void test_segment_limit(void)
+{
+	/* 32-bit data segment, base 0, limit 0xfff */
+	set_gdt_entry(LIMITED_DS_SEL, 0, 0xfff, 0x92, 0x40);
+	write_fs(LIMITED_DS_SEL);
+
+	report("segment limit: last dword", try_read_fs(0xffc) == 0);
+	report("segment limit: crossing the limit",
+	       try_read_fs(0xffe) == GP_VECTOR && fault_error_code == 0);
+	report("segment limit: crossing the limit in a hot loop",
+	       read_fs_hot_loop(1000000) == 1 &&
+	       fault_vector == GP_VECTOR && fault_error_code == 0);
+
+	write_fs(KERNEL_DS);
+}
+
+static void test_far_call_limit(void)
+{
+	/* 32-bit code segment, base 0, limit 0xfff */
+	set_gdt_entry(LIMITED_CS_SEL, 0, 0xfff, 0x9a, 0x40);
+
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "lcall $" xstr(LIMITED_CS_SEL) ", $0x2000\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : : "memory");
+	report("far call: offset outside of the limit",
+	       fault_vector == GP_VECTOR && fault_error_code == 0);
+}
+
+static void test_call_gate(void)
+{
+	set_call_gate(CALL_GATE_SEL, call_gate_target);
+	set_call_gate(JUMP_GATE_SEL, jump_gate_target);
+
+	/* the offset of the instruction is ignored, the one of the gate is used */
+	gate_caller_cs = 0;
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "lcall $" xstr(CALL_GATE_SEL) ", $0\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : : "eax", "memory");
+	report("call gate", fault_vector == 0 && gate_caller_cs == KERNEL_CS);
+
+	jump_gate_reached = false;
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "movl $1f, gate_return\n\t"
+		     "ljmp $" xstr(JUMP_GATE_SEL) ", $0\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : : "memory");
+	report("jump through call gate", fault_vector == 0 && jump_gate_reached);
+}
+
+/*
+ * Call from cpl 3 through 16-bit and 32-bit call gates that copy two
+ * parameters, and return with lret $n
+ */
+static void test_call_gate_to_cpl0(void)
+{
+	u32 base = (u32)gate_stubs & ~0xffff;
+	u16 *frame16 = (u16 *)gate_frame;
+
+	set_gdt_entry(GATE_KERNEL_CS_SEL, base, 0xffff, 0x9a, 0x40);
+	set_gdt_entry(GATE_USER_CS_SEL, base, 0xffff, 0xfa, 0x40);
+	/* 16-bit stack, so that the 16-bit gate saves all of esp */
+	set_gdt_entry(GATE_USER_SS16_SEL, (u32)user_stack, 0xfff, 0xf2, 0);
+	set_gate(USER_CALL_GATE16_SEL, GATE_KERNEL_CS_SEL,
+		 gate_target16 - (char *)base, 0xe4, 2);
+	set_gate(USER_CALL_GATE32_SEL, KERNEL_CS, (u32)gate_target32, 0xec, 2);
+
+	memset(gate_frame, 0, sizeof(gate_frame));
+	run_in_user(GATE_USER_CS_SEL | 3, user_gate16_caller - (char *)base,
+		    GATE_USER_SS16_SEL | 3, 0xff0);
+	report("16-bit call gate from cpl 3: cs %x, ss %x",
+	       gate_target_cs == GATE_KERNEL_CS_SEL &&
+	       gate_target_ss == KERNEL_DS, gate_target_cs, gate_target_ss);
+	report("16-bit call gate from cpl 3: return address",
+	       frame16[0] == user_gate16_return - (char *)base &&
+	       frame16[1] == (GATE_USER_CS_SEL | 3));
+	report("16-bit call gate from cpl 3: parameters %x %x",
+	       frame16[2] == 0x2222 && frame16[3] == 0x1111,
+	       frame16[2], frame16[3]);
+	/* The upper half of esp isn't changed by iret to a 16-bit stack */
+	report("16-bit call gate from cpl 3: outer ss:sp %x:%x",
+	       frame16[4] == (u16)gate_caller_esp &&
+	       frame16[5] == (GATE_USER_SS16_SEL | 3), frame16[5], frame16[4]);
+	report("16-bit call gate from cpl 3: lret $4",
+	       (u16)gate_return_esp == (u16)gate_caller_esp + 4);
+
+	memset(gate_frame, 0, sizeof(gate_frame));
+	run_in_user(GATE_USER_CS_SEL | 3, user_gate32_caller - (char *)base,
+		    USER_DS, (u32)user_stack + sizeof(user_stack));
+	report("32-bit call gate from cpl 3: cs %x, ss %x",
+	       gate_target_cs == KERNEL_CS && gate_target_ss == KERNEL_DS,
+	       gate_target_cs, gate_target_ss);
+	report("32-bit call gate from cpl 3: return address",
+	       gate_frame[0] == user_gate32_return - (char *)base &&
+	       gate_frame[1] == (GATE_USER_CS_SEL | 3));
+	report("32-bit call gate from cpl 3: parameters %x %x",
+	       gate_frame[2] == 0x22222222 && gate_frame[3] == 0x11111111,
+	       gate_frame[2], gate_frame[3]);
+	report("32-bit call gate from cpl 3: outer ss:esp %x:%x",
+	       gate_frame[4] == gate_caller_esp && gate_frame[5] == USER_DS,
+	       gate_frame[5], gate_frame[4]);
+	report("32-bit call gate from cpl 3: lret $8",
+	       gate_return_esp == gate_caller_esp + 8);
+}
+
+static void test_expand_down_stack(void)
+{
+	u32 popped = 0, caller_cs = 0;
+
+	/* 32-bit expand-down stack, base 0, valid offsets from 0x1000 to 4G */
+	set_gdt_entry(EXPAND_DOWN_SS_SEL, 0, 0xfff, 0x96, 0x40);
+
+	fault_vector = 0;
+	asm volatile("movl $1f, %2\n\t"
+		     "mov %%ss, %%ecx\n\t"
+		     "mov %3, %%ss\n\t"
+		     "push $0x1234\n\t"
+		     "pop %0\n\t"
+		     "lcall $" xstr(KERNEL_CS) ", $far_call_target\n\t"
+		     "1:\n\t"
+		     "mov %%ecx, %%ss"
+		     : "+d"(popped), "+b"(caller_cs), "=m"(fault_resume)
+		     : "r"(EXPAND_DOWN_SS_SEL) : "ecx", "memory");
+	report("expand-down stack: push and pop",
+	       fault_vector == 0 && popped == 0x1234);
+	report("expand-down stack: far call", caller_cs == KERNEL_CS);
+}
+
+/*
+ * Call from cpl 3 through a 32-bit gate to cpl 1, with the stack from the tss.
+ * Returns whether the call succeeded, or 0 for #SS
+ */
+static bool call_to_ring1(u32 ss_base, u32 ss_limit, u8 ss_access, u8 ss_flags,
+			  u32 esp1)
+{
+	set_gdt_entry(RING1_SS_SEL, ss_base, ss_limit, ss_access, ss_flags);
+	tss.ss1 = RING1_SS_SEL | 1;
+	tss.esp1 = esp1;
+
+	gate_target_ss = 0;
+	run_in_user(USER_CS32, (u32)user_ring1_caller, USER_DS,
+		    (u32)user_stack + sizeof(user_stack));
+	return fault_vector == 0;
+}
+
+/* Check the frame of call_to_ring1, which is split if a 16-bit sp wraps */
+static bool ring1_frame_ok(u32 *low, u32 *high)
+{
+	return high[0] == (u32)user_ring1_return && high[1] == USER_CS32 &&
+		high[2] == 0x22222222 && high[3] == 0x11111111 &&
+		low[0] == gate_caller_esp && low[1] == USER_DS &&
+		gate_target_ss == (RING1_SS_SEL | 1) &&
+		gate_return_esp == gate_caller_esp + 8;
+}
+
+/*
+ * Expand-down and wrapping stacks at a privilege change: Pushes at the upper
+ * bound, which is 64k or 4G depending on the B bit, are valid, and pushes
+ * below the limit raise #SS with the new stack selector
+ */
+static void test_expand_down_stack_privilege_change(void)
+{
+	u32 top = (u32)ring1_stack + sizeof(ring1_stack);
+	u32 *frame16 = (u32 *)(ring1_stack16 + 0x10000 - 24);
+
+	set_gdt_entry(RING1_CS_SEL, 0, 0xfffff, 0xba, 0xc0);
+	set_gate(RING1_CALL_GATE_SEL, RING1_CS_SEL, (u32)ring1_target, 0xec, 2);
+
+	/* esp 0 is the upper bound: The frame is right below the base */
+	report("expand-down stack, B=1: upper bound",
+	       call_to_ring1(top, 0xfff, 0xb6, 0x40, 0) &&
+	       gate_target_esp == 0xffffffe8 &&
+	       ring1_frame_ok((u32 *)(top - 8), (u32 *)(top - 24)));
+	report("expand-down stack, B=1: #SS below the limit",
+	       !call_to_ring1(top, 0xfff, 0xb6, 0x40, 0x1010) &&
+	       fault_vector == SS_VECTOR && fault_error_code == RING1_SS_SEL);
+
+	report("expand-down stack, B=0: upper bound",
+	       call_to_ring1((u32)ring1_stack16, 0xfff, 0xb6, 0, 0) &&
+	       (u16)gate_target_esp == 0xffe8 &&
+	       ring1_frame_ok(frame16 + 4, frame16));
+	report("expand-down stack, B=0: #SS below the limit",
+	       !call_to_ring1((u32)ring1_stack16, 0xfff, 0xb6, 0, 0x1010) &&
+	       fault_vector == SS_VECTOR && fault_error_code == RING1_SS_SEL);
+	report("expand-down stack, B=0: #SS for pushes that wrap sp",
+	       !call_to_ring1((u32)ring1_stack16, 0xfff, 0xb6, 0, 8) &&
+	       fault_vector == SS_VECTOR && fault_error_code == RING1_SS_SEL);
+
+	/* 16-bit expand-up stack with limit 0xffff: sp wraps between two pushes */
+	report("16-bit stack: pushes that wrap sp",
+	       call_to_ring1((u32)ring1_stack16, 0xffff, 0xb2, 0, 8) &&
+	       (u16)gate_target_esp == 0xfff0 &&
+	       ring1_frame_ok((u32 *)ring1_stack16, frame16 + 2));
+
+	tss.ss1 = 0;
+	tss.esp1 = 0;
+}
+
+int main(int ac, char **av)
+{
+	setup_idt();
+	handle_exception(GP_VECTOR, record_fault);
+	handle_exception(SS_VECTOR, record_fault);
+	set_idt_entry(RETURN_VECTOR, return_from_user, 3);
+
+	test_segment_limit();
+	test_far_call_limit();
+	test_call_gate();
+	test_expand_down_stack();
+	test_call_gate_to_cpl0();
+	test_expand_down_stack_privilege_change();
+
+	return report_summary();
+}
@@ -3,9 +3,15 @@
 #include "x86/processor.h"
 #include "x86/vm.h"
 
+#define X86_FEATURE_SMEP	7
 #define X86_FEATURE_SMAP	20
 
+#define PFERR_PRESENT_MASK	(1U << 0)
+#define PFERR_WRITE_MASK	(1U << 1)
+#define PFERR_FETCH_MASK	(1U << 4)
+
 volatile int pf_count = 0;
+volatile unsigned long pf_error_code;
 volatile int save;
 volatile unsigned test;
 
@@ -17,8 +23,13 @@ volatile unsigned test;
 void do_pf_tss(unsigned long error_code)
 {
 	pf_count++;
+	pf_error_code = error_code;
 	save = test;
 
+	// Instruction fetches are retried with SMEP disabled
+	if (error_code & PFERR_FETCH_MASK)
+		write_cr4(read_cr4() & ~X86_CR4_SMEP);
+
 #ifndef __x86_64__
 	tss.eflags |= X86_EFLAGS_AC;
 #endif
@@ -87,6 +98,33 @@ static void check_smap_nowp(void)
 	write_cr3(read_cr3());
 }
 
+static void check_smep(void)
+{
+	if (!(cpuid_indexed(7, 0).b & (1 << X86_FEATURE_SMEP))) {
+		printf("SMEP not enabled\n");
+		return;
+	}
+
+	init_test(1);
+	stac();
+	write_cr4(read_cr4() | X86_CR4_SMEP);
+	asm("jmp 1f + "xstr(USER_BASE)" \n"
+	    "1: jmp 2f - "xstr(USER_BASE)" \n"
+	    "2:");
+	report("executing on user page with SMEP=1, AC=1",
+	       pf_count == 1 && pf_error_code == (PFERR_PRESENT_MASK | PFERR_FETCH_MASK) &&
+	       !(read_cr4() & X86_CR4_SMEP));
+
+	init_test(1);
+	write_cr4(read_cr4() | X86_CR4_SMEP);
+	test = 46;
+	(void)USER_VAR(test);
+	report("read from user page with SMEP=1, AC=1", pf_count == 0);
+
+	write_cr4(read_cr4() & ~X86_CR4_SMEP);
+	clac();
+}
+
 int main(int ac, char **av)
 {
 	unsigned long i;
@@ -135,7 +173,8 @@ int main(int ac, char **av)
 		init_test(i);
 		clac();
 		(void)USER_VAR(test);
-		report("read from user page with AC=0", pf_count == 1 && save == 42);
+		report("read from user page with AC=0", pf_count == 1 && save == 42 &&
+		       pf_error_code == PFERR_PRESENT_MASK);
 
 		init_test(i);
 		stac();
@@ -146,7 +185,8 @@ int main(int ac, char **av)
 		init_test(i);
 		clac();
 		USER_VAR(test) = 44;
-		report("read from user page with AC=0", pf_count == 1 && test == 44 && save == 43);
+		report("read from user page with AC=0", pf_count == 1 && test == 44 && save == 43 &&
+		       pf_error_code == (PFERR_PRESENT_MASK | PFERR_WRITE_MASK));
 
 		init_test(i);
 		stac();
@@ -180,6 +220,7 @@ int main(int ac, char **av)
 	}
 
 	check_smap_nowp();
+	check_smep();
 
 	// TODO: implicit kernel access from ring 3 (e.g. int)
 
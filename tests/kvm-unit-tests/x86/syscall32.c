@@ -0,0 +1,175 @@
+/*
+ * Test syscall and sysret in legacy (32-bit protected) mode, as on amd cpus,
+ * and the star msr. Run with the athlon cpu model; the other models don't
+ * have syscall, which is tested to raise #UD
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "msr.h"
+#include "x86/desc.h"
+
+#define UD_VECTOR	6
+#define GP_VECTOR	13
+
+unsigned fault_vector;
+unsigned long fault_resume;
+
+jmp_buf return_to_kernel;
+static u8 user_stack[4096] __attribute__((aligned(16)));
+
+/* Written by the assembly below */
+u16 user_cs, user_ss, kernel_cs, kernel_ss;
+u32 user_eflags, kernel_eflags, kernel_esp, syscall_ecx;
+unsigned user_sysret_vector;
+
+extern char syscall_entry[], user_entry[], user_syscall_return[];
+
+/*
+ * At cpl 0 after syscall, still on the user stack: Record the state and go
+ * back to the test
+ */
+asm (
+	"syscall_entry:\n"
+	"	movw %cs, kernel_cs\n"
+	"	movw %ss, kernel_ss\n"
+	"	movl %esp, kernel_esp\n"
+	"	movl %ecx, syscall_ecx\n"
+	"	pushf\n"
+	"	popl kernel_eflags\n"
+	"	push $1\n"
+	"	push $return_to_kernel\n"
+	"	call longjmp\n"
+);
+
+/* At cpl 3 after sysret: sysret raises #GP, and syscall returns to the kernel */
+asm (
+	"user_entry:\n"
+	"	movw %cs, user_cs\n"
+	"	movw %ss, user_ss\n"
+	"	pushf\n"
+	"	popl user_eflags\n"
+	"	movl $0, fault_vector\n"
+	"	movl $1f, fault_resume\n"
+	"	sysret\n"
+	"1:	movl fault_vector, %eax\n"
+	"	movl %eax, user_sysret_vector\n"
+	"	syscall\n"
+	"user_syscall_return:\n"
+	"	ud2\n"
+);
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	regs->rip = fault_resume;
+}
+
+static unsigned probe_syscall(void)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "syscall\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : : "ecx", "memory");
+	return fault_vector;
+}
+
+static unsigned probe_sysret(void)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "movl $1f, %%ecx\n\t"
+		     "sysret\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : : "ecx", "memory");
+	return fault_vector;
+}
+
+static unsigned write_efer(u64 value)
+{
+	fault_vector = 0;
+	asm volatile("movl $1f, %0\n\t"
+		     "wrmsr\n\t"
+		     "1:"
+		     : "=m"(fault_resume)
+		     : "c"(MSR_EFER), "a"((u32)value), "d"((u32)(value >> 32))
+		     : "memory");
+	return fault_vector;
+}
+
+static void test_unsupported(void)
+{
+	report("efer.sce: #GP",
+	       write_efer(rdmsr(MSR_EFER) | EFER_SCE) == GP_VECTOR);
+	report("syscall: #UD", probe_syscall() == UD_VECTOR);
+	report("sysret: #UD", probe_sysret() == UD_VECTOR);
+}
+
+static void test_star(void)
+{
+	u64 star = 0x123b4567ull << 32 | 0x89abcdef;
+
+	wrmsr(MSR_STAR, star);
+	report("star: read back", rdmsr(MSR_STAR) == star);
+}
+
+static void test_sce_clear(void)
+{
+	report("efer.sce clear: syscall #UD", probe_syscall() == UD_VECTOR);
+	report("efer.sce clear: sysret #UD", probe_sysret() == UD_VECTOR);
+}
+
+/* sysret to cpl 3, and syscall back to cpl 0 */
+static void test_round_trip(void)
+{
+	u8 *user_esp = user_stack + sizeof(user_stack);
+
+	wrmsr(MSR_STAR, (u64)(USER_CS32 & ~3) << 48 | (u64)KERNEL_CS << 32 |
+	      (u32)syscall_entry);
+
+	if (setjmp(return_to_kernel) == 0) {
+		write_ds(USER_DS);
+		write_es(USER_DS);
+		asm volatile("movl %1, %%esp\n\t"
+			     "sysret"
+			     : : "c"(user_entry), "r"(user_esp) : "memory");
+	}
+
+	write_ds(KERNEL_DS);
+	write_es(KERNEL_DS);
+
+	report("sysret: cs %x, ss %x", user_cs == USER_CS32 && user_ss == USER_DS,
+	       user_cs, user_ss);
+	report("sysret: sets if", user_eflags & X86_EFLAGS_IF);
+	report("sysret at cpl 3: #GP", user_sysret_vector == GP_VECTOR);
+	report("syscall: cs %x, ss %x",
+	       kernel_cs == KERNEL_CS && kernel_ss == KERNEL_DS,
+	       kernel_cs, kernel_ss);
+	report("syscall: ecx is the return address",
+	       syscall_ecx == (u32)user_syscall_return);
+	report("syscall: clears if", !(kernel_eflags & X86_EFLAGS_IF));
+	report("syscall: esp is unchanged", kernel_esp == (u32)user_esp);
+}
+
+int main(int ac, char **av)
+{
+	setup_idt();
+	handle_exception(UD_VECTOR, record_fault);
+	handle_exception(GP_VECTOR, record_fault);
+
+	if (!(cpuid(0x80000001).d & 1 << 11)) {
+		test_unsupported();
+		return report_summary();
+	}
+
+	test_star();
+	test_sce_clear();
+	wrmsr(MSR_EFER, rdmsr(MSR_EFER) | EFER_SCE);
+	test_round_trip();
+	wrmsr(MSR_EFER, rdmsr(MSR_EFER) & ~EFER_SCE);
+
+	return report_summary();
+}
@@ -125,6 +125,8 @@ void test_kernel_mode_int()
 	printf("Return from nmi %d\n", test_count);
 	report("NMI int $2", test_count == 1);
 
+	// Disabled in v86: NMIs are not supported
+	if (false) {
 	/* test that external NMI triggers task gate */
 	test_count = 0;
 	set_intr_task_gate(2, nmi_tss);
@@ -133,6 +135,7 @@ void test_kernel_mode_int()
 	io_delay();
 	printf("Return from APIC nmi\n");
 	report("NMI external", test_count == 1);
+	}
 
 	/* test that external interrupt triggesr task gate */
 	test_count = 0;
@@ -0,0 +1,344 @@
+/*
+ * Test the faults raised by task switches for an unusable tss, ldt and
+ * segments of the new task, and that they leave the current task unchanged
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "vm.h"
+#include "x86/desc.h"
+
+#define TS_VECTOR	10
+#define NP_VECTOR	11
+#define SS_VECTOR	12
+#define PF_VECTOR	14
+#define RETURN_VECTOR	0x80
+
+#define TSS_NEW		(FIRST_SPARE_SEL + 0)
+#define TEST_SEL	(FIRST_SPARE_SEL + 8)
+#define LDT_SEL		(FIRST_SPARE_SEL + 16)
+#define TSS_USER	(FIRST_SPARE_SEL + 24)
+
+/* code and data segments in the ldt of the new task */
+#define LDT_CS		(0 | 4)
+#define LDT_DS		(8 | 4)
+
+#define FLAG_NT		(1 << 14)
+
+static tss32_t new_tss;
+static gdt_entry_t new_ldt[2];
+static char new_stack[4096] __attribute__((aligned(16)));
+static char user_stack[4096] __attribute__((aligned(16)));
+static char user_kernel_stack[4096] __attribute__((aligned(16)));
+
+static volatile int switched;
+static u8 new_tss_access;
+
+static unsigned fault_vector;
+static unsigned fault_error_code;
+static unsigned long fault_cr2;
+static unsigned long fault_resume;
+
+static void record_fault(struct ex_regs *regs)
+{
+	fault_vector = regs->vector;
+	fault_error_code = regs->error_code;
+	fault_cr2 = read_cr2();
+	regs->rip = fault_resume;
+}
+
+/* the new task returns to the main task right away (ds may be null) */
+extern void task_entry(void);
+asm (
+	"task_entry:\n\t"
+	"movl $1, %ss:switched\n\t"
+	"ljmp $" xstr(TSS_MAIN) ", $0\n\t"
+);
+
+/*
+ * The user task returns to the nested task in its backlink. If that fails,
+ * the fault handler resumes at user_task_return, which switches back to the
+ * main task through a task gate
+ */
+extern void user_task_entry(void);
+extern char user_task_return[];
+asm (
+	"user_task_entry:\n\t"
+	"iret\n\t"
+	"user_task_return:\n\t"
+	"int $" xstr(RETURN_VECTOR) "\n\t"
+);
+
+static void reset_new_task(void)
+{
+	/* switching back to the main task loads its cr3 */
+	tss.cr3 = read_cr3();
+	new_tss = (tss32_t) {
+		.cr3	= read_cr3(),
+		.eip	= (u32)task_entry,
+		.eflags	= 2,
+		.esp	= (u32)new_stack + sizeof(new_stack),
+		.cs	= KERNEL_CS,
+		.ss	= KERNEL_DS,
+		.ds	= KERNEL_DS,
+		.es	= KERNEL_DS,
+		.fs	= KERNEL_DS,
+		.gs	= KERNEL_DS,
+		.iomap_base = sizeof(tss32_t),
+	};
+	set_gdt_entry(TSS_NEW, (u32)&new_tss, sizeof(tss32_t) - 1, 0x89, 0);
+	switched = 0;
+	fault_vector = -1;
+	fault_error_code = -1;
+}
+
+static void jmp_new_task(void)
+{
+	new_tss_access = gdt32[TSS_NEW / 8].access;
+	asm volatile("movl $1f, %0\n\t"
+		     "ljmp $" xstr(TSS_NEW) ", $0\n\t"
+		     "1:"
+		     : "=m"(fault_resume) : : "memory");
+}
+
+/* iret to the task in the backlink of the current tss */
+static void iret_nested_task(void)
+{
+	new_tss_access = gdt32[TSS_NEW / 8].access;
+	asm volatile("movl $1f, %0\n\t"
+		     "pushf\n\t"
+		     "orl $" xstr(FLAG_NT) ", (%%esp)\n\t"
+		     "popf\n\t"
+		     "iret\n\t"
+		     "1:\n\t"
+		     "pushf\n\t"
+		     "andl $~" xstr(FLAG_NT) ", (%%esp)\n\t"
+		     "popf"
+		     : "=m"(fault_resume) : : "memory");
+}
+
+static bool unchanged(void)
+{
+	return !switched && str() == TSS_MAIN &&
+		gdt32[TSS_MAIN / 8].access == 0x8b &&
+		gdt32[TSS_NEW / 8].access == new_tss_access;
+}
+
+static void report_fault(const char *msg, unsigned vector, unsigned error_code)
+{
+	report("%s", fault_vector == vector && fault_error_code == error_code &&
+	       unchanged(), msg);
+}
+
+static void test_switch(void)
+{
+	reset_new_task();
+	jmp_new_task();
+	report("jmp to task", switched && fault_vector == -1 &&
+	       str() == TSS_MAIN && gdt32[TSS_NEW / 8].access == 0x89);
+
+	reset_new_task();
+	new_tss.ds = 0;
+	jmp_new_task();
+	report("null ds", switched && fault_vector == -1);
+}
+
+/* The ldt of the new task is loaded before its cs and ss */
+static void test_switch_ldt(void)
+{
+	reset_new_task();
+	new_tss.ldt = LDT_SEL;
+	new_tss.cs = LDT_CS;
+	new_tss.ss = LDT_DS;
+	set_gdt_entry(LDT_SEL, (u32)new_ldt, sizeof(new_ldt) - 1, 0x82, 0);
+	new_ldt[0] = gdt32[KERNEL_CS / 8];
+	new_ldt[1] = gdt32[KERNEL_DS / 8];
+	jmp_new_task();
+	report("jmp to task with cs and ss in its ldt",
+	       switched && fault_vector == -1 && str() == TSS_MAIN &&
+	       sldt() == 0);
+
+	/* an unusable ldt is caught before the current task is saved */
+	reset_new_task();
+	tss.eip = 0;
+	new_tss.ldt = LDT_SEL;
+	gdt32[LDT_SEL / 8].access = 0x02;
+	jmp_new_task();
+	report_fault("ldt not present", TS_VECTOR, LDT_SEL);
+	report("ldt not present: current tss not written", tss.eip == 0);
+}
+
+static void test_tss_faults(void)
+{
+	reset_new_task();
+	set_gdt_entry(TSS_NEW, (u32)&new_tss, 0x66, 0x89, 0);
+	jmp_new_task();
+	report_fault("tss limit too small", TS_VECTOR, TSS_NEW);
+
+	reset_new_task();
+	gdt32[TSS_NEW / 8].access = 0x09;
+	jmp_new_task();
+	report_fault("tss not present", NP_VECTOR, TSS_NEW);
+
+	reset_new_task();
+	gdt32[TSS_NEW / 8].access = 0x8b;
+	jmp_new_task();
+	report_fault("jmp to busy tss", GP_VECTOR, TSS_NEW);
+
+	reset_new_task();
+	gdt32[TSS_NEW / 8].access = 0x93;
+	jmp_new_task();
+	report_fault("jmp to data segment", GP_VECTOR, TSS_NEW);
+
+	reset_new_task();
+	tss.prev = TSS_NEW;
+	iret_nested_task();
+	report_fault("iret to available tss", TS_VECTOR, TSS_NEW);
+	tss.prev = 0;
+}
+
+static void test_segment_faults(void)
+{
+	reset_new_task();
+	new_tss.ldt = TEST_SEL;
+	set_gdt_entry(TEST_SEL, 0, 0xfffff, 0x93, 0xc0);
+	jmp_new_task();
+	report_fault("ldt is a data segment", TS_VECTOR, TEST_SEL);
+
+	reset_new_task();
+	new_tss.ldt = TEST_SEL | 4;
+	jmp_new_task();
+	report_fault("ldt selector in ldt", TS_VECTOR, TEST_SEL | 4);
+
+	reset_new_task();
+	new_tss.ldt = 0xfff8;
+	jmp_new_task();
+	report_fault("ldt selector outside of gdt", TS_VECTOR, 0xfff8);
+
+	reset_new_task();
+	new_tss.cs = TEST_SEL;
+	set_gdt_entry(TEST_SEL, 0, 0xfffff, 0x93, 0xc0);
+	jmp_new_task();
+	report_fault("cs is a data segment", TS_VECTOR, TEST_SEL);
+
+	reset_new_task();
+	new_tss.cs = 0;
+	jmp_new_task();
+	report_fault("null cs", TS_VECTOR, 0);
+
+	reset_new_task();
+	new_tss.cs = TEST_SEL;
+	set_gdt_entry(TEST_SEL, 0, 0xfffff, 0x1b, 0xc0);
+	jmp_new_task();
+	report_fault("cs not present", NP_VECTOR, TEST_SEL);
+
+	reset_new_task();
+	new_tss.cs = TEST_SEL;
+	set_gdt_entry(TEST_SEL, 0, 0xfff, 0x9b, 0x40);
+	jmp_new_task();
+	report_fault("eip outside of cs limit", GP_VECTOR, 0);
+
+	reset_new_task();
+	new_tss.ss = 0;
+	jmp_new_task();
+	report_fault("null ss", TS_VECTOR, 0);
+
+	reset_new_task();
+	new_tss.ss = TEST_SEL;
+	set_gdt_entry(TEST_SEL, 0, 0xfffff, 0x13, 0xc0);
+	jmp_new_task();
+	report_fault("ss not present", SS_VECTOR, TEST_SEL);
+
+	reset_new_task();
+	new_tss.ss = USER_DS;
+	jmp_new_task();
+	report_fault("ss with dpl 3", TS_VECTOR, USER_DS & ~3);
+
+	reset_new_task();
+	new_tss.ds = TEST_SEL;
+	set_gdt_entry(TEST_SEL, 0, 0xfffff, 0x13, 0xc0);
+	jmp_new_task();
+	report_fault("ds not present", NP_VECTOR, TEST_SEL);
+
+	reset_new_task();
+	new_tss.gs = TEST_SEL;
+	set_gdt_entry(TEST_SEL, 0, 0xfffff, 0x99, 0xc0);
+	jmp_new_task();
+	report_fault("gs is execute-only", TS_VECTOR, TEST_SEL);
+}
+
+static void test_page_fault(void)
+{
+	void *unmapped = alloc_vpage();
+
+	reset_new_task();
+	set_gdt_entry(TSS_NEW, (u32)unmapped, sizeof(tss32_t) - 1, 0x89, 0);
+	jmp_new_task();
+	report_fault("tss not mapped", PF_VECTOR, 0);
+	report("tss not mapped: cr2", fault_cr2 >= (u32)unmapped &&
+	       fault_cr2 < (u32)unmapped + sizeof(tss32_t));
+}
+
+/*
+ * The backlink is read with a supervisor access, even from cpl 3, so the
+ * tss of the user task is on a supervisor page
+ */
+static void test_iret_from_user(void)
+{
+	tss32_t *user_tss = vmalloc(PAGE_SIZE);
+
+	*get_pte(current_page_table(), user_tss) &= ~PT_USER_MASK;
+	invlpg(user_tss);
+	*user_tss = (tss32_t) {
+		.prev	= TSS_NEW,
+		.cr3	= read_cr3(),
+		.eip	= (u32)user_task_entry,
+		.eflags	= 2 | FLAG_NT,
+		.esp	= (u32)user_stack + sizeof(user_stack),
+		.esp0	= (u32)user_kernel_stack + sizeof(user_kernel_stack),
+		.ss0	= KERNEL_DS,
+		.cs	= USER_CS,
+		.ss	= USER_DS,
+		.ds	= USER_DS,
+		.es	= USER_DS,
+		.fs	= USER_DS,
+		.gs	= USER_DS,
+		.iomap_base = sizeof(tss32_t),
+	};
+	set_gdt_entry(TSS_USER, (u32)user_tss, sizeof(tss32_t) - 1, 0x89, 0);
+	set_idt_task_gate(RETURN_VECTOR, TSS_MAIN);
+	boot_idt[RETURN_VECTOR].dpl = 3;
+
+	reset_new_task();
+	gdt32[TSS_NEW / 8].access = 0x8b;
+	fault_resume = (u32)user_task_return;
+	asm volatile("ljmp $" xstr(TSS_USER) ", $0" : : : "memory");
+	report("iret from cpl 3 to nested task", switched && fault_vector == -1 &&
+	       str() == TSS_MAIN && gdt32[TSS_NEW / 8].access == 0x89);
+
+	/* the task gate of the failure path nests the main task */
+	write_rflags(read_rflags() & ~FLAG_NT);
+	tss.prev = 0;
+}
+
+int main(void)
+{
+	setup_idt();
+	setup_vm();
+	handle_exception(TS_VECTOR, record_fault);
+	handle_exception(NP_VECTOR, record_fault);
+	handle_exception(SS_VECTOR, record_fault);
+	handle_exception(GP_VECTOR, record_fault);
+	handle_exception(PF_VECTOR, record_fault);
+
+	test_switch();
+	test_switch_ldt();
+	test_tss_faults();
+	test_segment_faults();
+	test_page_fault();
+	test_iret_from_user();
+
+	return report_summary();
+}
@@ -0,0 +1,59 @@
+/*
+ * Test that an exception while delivering a double fault shuts down the cpu.
+ * The first argument selects the case; the test never returns when it
+ * passes, so run.js checks the exceptions of the shutdown instead:
+ *
+ *   df-not-present: #gp, #np for its gate, #np for the #df gate (13,11,11)
+ *   pf-stack: #pf, #pf and #pf for each push to an unmapped stack (14,14,14)
+ *
+ * This work is licensed under the terms of the GNU GPL, version 2.
+ */
+
+#include "libcflat.h"
+#include "processor.h"
+#include "vm.h"
+#include "x86/desc.h"
+
+#define DF_VECTOR	8
+
+/* outside of the gdt */
+#define INVALID_SEL	0xfff8
+
+static void df_not_present(void)
+{
+	boot_idt[GP_VECTOR].p = 0;
+	boot_idt[DF_VECTOR].p = 0;
+	asm volatile("mov %0, %%fs" : : "r"((u16)INVALID_SEL));
+}
+
+static void pf_stack(void)
+{
+	u8 *unmapped;
+
+	setup_vm();
+	unmapped = alloc_vpage();
+
+	asm volatile("movl %0, %%esp\n\t"
+		     "movl (%1), %%eax"
+		     : : "r"(unmapped + PAGE_SIZE), "r"(unmapped)
+		     : "eax", "memory");
+}
+
+int main(int ac, char **av)
+{
+	const char *name = ac > 1 ? av[1] : "";
+
+	setup_idt();
+
+	if (strcmp(name, "df-not-present") == 0) {
+		df_not_present();
+	} else if (strcmp(name, "pf-stack") == 0) {
+		pf_stack();
+	} else {
+		report("unknown case '%s'", false, name);
+		return report_summary();
+	}
+
+	report("%s: triple fault", false, name);
+	return report_summary();
+}
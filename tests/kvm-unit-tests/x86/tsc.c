@@ -1,7 +1,9 @@
 #include "libcflat.h"
 #include "processor.h"
+#include "desc.h"
 
 #define CPUID_80000001_EDX_RDTSCP	    (1 << 27)
+#define CPUID_80000007_EDX_INVARIANT_TSC    (1 << 8)
 int check_cpuid_80000001_edx(unsigned int bit)
 {
     return (cpuid(0x80000001).d & bit) != 0;
@@ -15,6 +17,7 @@ void test_wrtsc(u64 t1)
 	wrtsc(t1);
 	t2 = rdtsc();
 	printf("rdtsc after wrtsc(%" PRId64 "): %" PRId64 "\n", t1, t2);
+	report("rdtsc after wrtsc(%" PRId64 ")", t2 >= t1, t1);
 }
 
 void test_rdtscp(u64 aux)
@@ -26,10 +29,36 @@ void test_rdtscp(u64 aux)
        report("Test RDTSCP %" PRIu64, ecx == aux, aux);
 }
 
+void test_rdtscp_order(void)
+{
+	u64 t1, t2, t3;
+	u32 ecx;
+
+	t1 = rdtsc();
+	t2 = rdtscp(&ecx);
+	t3 = rdtsc();
+	report("RDTSCP between two RDTSC", t1 <= t2 && t2 <= t3);
+}
+
+static void do_write_tsc_aux(void *data)
+{
+	wrmsr(MSR_TSC_AUX, *(u64 *)data);
+}
+
+void test_tsc_aux_reserved(void)
+{
+	u64 aux = 1ull << 32;
+
+	report("TSC_AUX: reserved bits raise #GP",
+	       test_for_exception(GP_VECTOR, do_write_tsc_aux, &aux));
+}
+
 int main()
 {
 	u64 t1, t2;
 
+	setup_idt();
+
 	t1 = rdtsc();
 	t2 = rdtsc();
 	printf("rdtsc latency %u\n", (unsigned)(t2 - t1));
@@ -41,7 +70,12 @@ int main()
 		test_rdtscp(0);
 		test_rdtscp(10);
 		test_rdtscp(0x100);
+		test_rdtscp_order();
+		test_tsc_aux_reserved();
 	} else
 		printf("rdtscp not supported\n");
+
+	report("invariant tsc",
+	       cpuid(0x80000007).d & CPUID_80000007_EDX_INVARIANT_TSC);
 	return report_summary();
 }
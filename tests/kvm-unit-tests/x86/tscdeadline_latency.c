@@ -67,6 +67,32 @@ static void tsc_deadline_timer_isr(isr_regs_t *regs)
     apic_write(APIC_EOI, 0);
 }
 
+static void past_deadline_isr(isr_regs_t *regs)
+{
+    ++tdt_count;
+    apic_write(APIC_EOI, 0);
+}
+
+/* A deadline that has passed fires right away, and reads back as 0 afterwards */
+static void test_past_deadline(void)
+{
+    handle_irq(TSC_DEADLINE_TIMER_VECTOR, past_deadline_isr);
+    irq_enable();
+
+    wrmsr(MSR_IA32_TSCDEADLINE, rdmsr(MSR_IA32_TSC));
+    asm volatile ("nop");
+    report("tsc deadline in the past", tdt_count == 1);
+    report("tsc deadline clearing", rdmsr(MSR_IA32_TSCDEADLINE) == 0);
+
+    wrmsr(MSR_IA32_TSCDEADLINE, rdmsr(MSR_IA32_TSC) + 1000000000ull);
+    report("tsc deadline readback", rdmsr(MSR_IA32_TSCDEADLINE) != 0);
+    wrmsr(MSR_IA32_TSCDEADLINE, 0);
+    report("tsc deadline disarming", rdmsr(MSR_IA32_TSCDEADLINE) == 0);
+
+    irq_disable();
+    tdt_count = 0;
+}
+
 static void start_tsc_deadline_timer(void)
 {
     handle_irq(TSC_DEADLINE_TIMER_VECTOR, tsc_deadline_timer_isr);
@@ -83,6 +109,7 @@ static int enable_tsc_deadline_timer(void)
     if (cpuid(1).c & (1 << 24)) {
         lvtt = APIC_LVT_TIMER_TSCDEADLINE | TSC_DEADLINE_TIMER_VECTOR;
         apic_write(APIC_LVTT, lvtt);
+        test_past_deadline();
         start_tsc_deadline_timer();
         return 1;
     } else {
@@ -128,5 +155,10 @@ int main(int argc, char **argv)
         printf("latency: %" PRId64 "\n", table[i]);
     }
 
+    /* The latency is unsigned, so a deadline that fired early shows up as huge */
+    for (i = 0; i < table_idx && table[i] < 1000000000ull; i++)
+        ;
+    report("tsc deadlines fire after the deadline", i == table_idx);
+
     return report_summary();
 }
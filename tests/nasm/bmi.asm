@@ -0,0 +1,92 @@
+global _start
+
+section .data
+	align 16
+mydword:
+	dd	0xf0f0ff00
+
+%include "header.inc"
+
+	; cf is collected in esi (shifted in), results are saved in xmm registers
+	xor		esi, esi
+
+	mov		eax, 0x12345678
+	mov		ebx, 0x00000c04
+	andn		ecx, eax, ebx
+	movd		xmm0, ecx
+	bextr		edx, eax, ebx
+	movd		xmm1, edx
+	bextr		edx, [mydword], ebx
+	pinsrd		xmm1, edx, 1
+	mov		ebx, 0x00002004
+	bextr		edx, eax, ebx
+	pinsrd		xmm1, edx, 2
+
+	blsi		ecx, eax
+	rcl		esi, 1
+	blsmsk		edx, eax
+	rcl		esi, 1
+	blsr		edi, [mydword]
+	rcl		esi, 1
+	movd		xmm2, ecx
+	pinsrd		xmm2, edx, 1
+	pinsrd		xmm2, edi, 2
+	xor		ecx, ecx
+	blsi		edx, ecx
+	rcl		esi, 1
+	blsmsk		edx, ecx
+	rcl		esi, 1
+	pinsrd		xmm2, edx, 3
+	blsr		edx, ecx
+	rcl		esi, 1
+
+	mov		ebx, 12
+	bzhi		ecx, eax, ebx
+	rcl		esi, 1
+	movd		xmm3, ecx
+	mov		ebx, 0x140
+	bzhi		ecx, eax, ebx
+	rcl		esi, 1
+	pinsrd		xmm3, ecx, 1
+
+	tzcnt		ecx, eax
+	rcl		esi, 1
+	lzcnt		edx, eax
+	rcl		esi, 1
+	pinsrd		xmm3, ecx, 2
+	pinsrd		xmm3, edx, 3
+	xor		ebx, ebx
+	tzcnt		cx, bx
+	rcl		esi, 1
+	lzcnt		edx, ebx
+	rcl		esi, 1
+	movd		xmm4, ecx
+	pinsrd		xmm4, edx, 1
+
+	mov		ebx, 0xff00ff0f
+	pdep		ecx, eax, ebx
+	pext		edx, eax, ebx
+	pinsrd		xmm4, ecx, 2
+	pinsrd		xmm4, edx, 3
+
+	mov		edx, 0x87654321
+	mulx		ecx, ebx, eax
+	movd		xmm5, ecx
+	pinsrd		xmm5, ebx, 1
+	mulx		ecx, ecx, [mydword]
+	pinsrd		xmm5, ecx, 2
+
+	rorx		ecx, eax, 12
+	pinsrd		xmm5, ecx, 3
+
+	mov		ebx, 36
+	sarx		ecx, [mydword], ebx
+	movd		xmm6, ecx
+	shlx		ecx, eax, ebx
+	pinsrd		xmm6, ecx, 1
+	shrx		ecx, [mydword], ebx
+	pinsrd		xmm6, ecx, 2
+
+	cmp		eax, ebx
+
+%include "footer.inc"
@@ -0,0 +1,31 @@
+global _start
+
+section .data
+	align 16
+check:
+	db	"123456789"
+mydword:
+	dd	0xcafebabe
+
+%include "header.inc"
+
+	; crc32c("123456789") = 0xe3069283
+	mov		eax, -1
+	mov		esi, check
+	mov		ecx, 9
+.next:
+	crc32		eax, byte [esi]
+	inc		esi
+	loop		.next
+	not		eax
+
+	mov		ebx, 0x12345678
+	crc32		ebx, dword [mydword]
+	mov		edx, 0x12345678
+	crc32		edx, word [mydword]
+	mov		edi, ebx
+	crc32		edi, dl
+	mov		ebp, edx
+	crc32		ebp, bx
+
+%include "footer.inc"
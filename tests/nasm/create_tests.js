@@ -24,6 +24,7 @@ const fs = require("fs");
 const fse = require("fs/promises");
 const path = require("path");
 const encodings = require("../../gen/x86_table.js");
+const { is_three_byte_opcode } = require("../../gen/util.js");
 const util = require("util");
 const execFile = util.promisify(require("child_process").execFile);
 const Rand = require("./rand.js");
@@ -181,7 +182,7 @@ function create_tests()
 
 function format_opcode(n)
 {
-    let x = n.toString(16);
+    let x = (n >>> 0).toString(16);
     return (x.length === 1 || x.length === 3) ? "0" + x : x;
 }
 
@@ -421,6 +422,18 @@ function create_instruction_test(op, config, nth_test)
     }
     else
     {
+        if(is_three_byte_opcode(opcode))
+        {
+            let c = opcode >>> 24;
+            if(c !== 0)
+            {
+                assert(c === 0x66 || c === 0xF3 || c === 0xF2);
+                codes.push("db " + c);
+            }
+            codes.push("db 0fh");
+            codes.push("db " + (opcode >> 8 & 0xFF));
+            opcode &= 0xFF;
+        }
         assert(opcode < 0x1000000);
         if(opcode >= 0x10000)
         {
@@ -0,0 +1,31 @@
+global _start
+
+section .data
+	align 16
+
+%include "header.inc"
+
+    ; unmask the zero divide exception
+    fnstcw [esp]
+    and word [esp], ~4
+    fldcw [esp]
+
+    push 1234
+    fild dword [esp]
+    push 0
+    fild dword [esp]
+    fdiv
+
+    ; es and b are set in the status word, but no exception is raised by non-waiting instructions
+    fnstsw ax
+    fnstenv [esp + 8]
+    mov word [esp + 8 + 0 + 2], 0
+    mov word [esp + 8 + 4 + 2], 0
+    mov word [esp + 8 + 8 + 2], 0
+    mov dword [esp + 8 + 12], 0 ; fpu eip (currently not emulated)
+    mov dword [esp + 8 + 16], 0 ; fpu cs/opcode (currently not emulated)
+    mov dword [esp + 8 + 20], 0 ; fpu dp (currently not emulated)
+    mov dword [esp + 8 + 24], 0 ; fpu ds (currently not emulated)
+    fnclex
+
+%include "footer.inc"
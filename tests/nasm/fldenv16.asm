@@ -0,0 +1,18 @@
+global _start
+
+%include "header.inc"
+
+    fldz
+    fld1
+    o16 fstenv [esp]
+    mov word [esp + 0], 0x0F7F ; control word: round toward zero, all exceptions masked
+    o16 fldenv [esp]
+    o16 fstenv [esp + 16]
+
+    ; zero fpu ip, cs, dp and ds (currently not emulated)
+    mov dword [esp + 6], 0
+    mov dword [esp + 10], 0
+    mov dword [esp + 16 + 6], 0
+    mov dword [esp + 16 + 10], 0
+
+%include "footer.inc"
@@ -0,0 +1,14 @@
+global _start
+
+%include "header.inc"
+
+    sub esp, 128
+    fldz
+    fld1
+    fldpi
+    o16 fsave [esp]
+    o16 frstor [esp]
+    mov dword [esp + 6], 0 ; fpu ip and cs (currently not emulated)
+    mov dword [esp + 10], 0 ; fpu dp and ds (currently not emulated)
+
+%include "footer.inc"
@@ -0,0 +1,13 @@
+global _start
+
+%include "header.inc"
+
+    fldz
+    fld1
+    o16 fstenv [esp]
+
+    ; zero fpu ip, cs, dp and ds (currently not emulated)
+    mov dword [esp + 6], 0
+    mov dword [esp + 10], 0
+
+%include "footer.inc"
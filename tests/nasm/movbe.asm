@@ -0,0 +1,21 @@
+global _start
+
+section .data
+	align 16
+mydword:
+	dd	0x12345678
+myword:
+	dw	0xabcd
+
+%include "header.inc"
+
+	movbe		eax, [mydword]
+	movbe		bx, [myword]
+	mov		ecx, 0xcafebabe
+	movbe		[mydword], ecx
+	mov		edx, [mydword]
+	mov		si, 0x1122
+	movbe		[myword], si
+	movzx		edi, word [myword]
+
+%include "footer.inc"
@@ -0,0 +1,65 @@
+global _start
+
+section .data
+	align 16
+vowels:
+	db	"aeiou", 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+hello:
+	db	"hello world", 0, 0, 0, 0, 0
+letters:
+	db	"azAZ", 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+hellx:
+	db	"hellx", 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+lo:
+	db	"lo", 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+
+; Moves EFLAGS into specified register
+%macro moveflags 1
+	pushf
+	and			dword [esp], 0x8d5
+	pop			eax
+	movd		%1, eax
+%endmacro
+
+%include "header.inc"
+
+	movdqa		xmm1, [vowels]
+	movdqa		xmm2, [hello]
+	movdqa		xmm3, [letters]
+	movdqa		xmm4, [hellx]
+	movdqa		xmm5, [lo]
+
+	; equal any
+	pcmpistri	xmm1, xmm2, 0x00
+	moveflags	mm0
+	mov		ebx, ecx
+	pcmpistri	xmm1, [hello], 0x40
+	mov		esi, ecx
+
+	; ranges, negative polarity
+	pcmpistrm	xmm3, xmm2, 0x14
+	moveflags	mm1
+	movdqa		xmm6, xmm0
+
+	; equal each, negative polarity (strcmp)
+	pcmpistri	xmm4, [vowels + 16], 0x18
+	moveflags	mm2
+	mov		edi, ecx
+
+	; equal ordered (strstr), byte mask
+	pcmpistrm	xmm5, xmm2, 0x4c
+	moveflags	mm3
+	movdqa		xmm7, xmm0
+
+	; explicit lengths, masked negative polarity
+	mov		eax, 3
+	mov		edx, -9
+	pcmpestri	xmm1, xmm2, 0x30
+	moveflags	mm4
+	mov		ebp, ecx
+	mov		eax, 20
+	mov		edx, 5
+	pcmpestrm	xmm4, xmm2, 0x08
+	moveflags	mm5
+
+%include "footer.inc"
@@ -0,0 +1,77 @@
+global _start
+
+section .data
+	align 16
+packed_single:
+	dd	1.5, -2.5, 2.7, -0.3
+packed_double:
+	dq	2.5, -1.5
+single:
+	dd	-2.5
+double:
+	dq	0.5
+mxcsr:
+	dd	0
+
+; Set mxcsr rounding bits, clearing the exception flags
+%macro  setRoundingBits 1
+	stmxcsr		[mxcsr]
+	mov			eax, [mxcsr]
+	and 		ax, 0x9F80
+	or			ah, %1
+	mov			[mxcsr], eax
+	ldmxcsr		[mxcsr]
+%endmacro
+
+%include "header.inc"
+
+	; roundss and roundsd with the rounding mode in imm8[1:0]
+	roundss		xmm0, [single], 0
+	movdq2q		mm0, xmm0
+	roundss		xmm0, [single], 1
+	movdq2q		mm1, xmm0
+	roundss		xmm0, [single], 2
+	movdq2q		mm2, xmm0
+	roundss		xmm0, [single], 3
+	movdq2q		mm3, xmm0
+	roundsd		xmm0, [double], 0
+	movdq2q		mm4, xmm0
+	roundsd		xmm0, [double], 1
+	movdq2q		mm5, xmm0
+	roundsd		xmm0, [double], 2
+	movdq2q		mm6, xmm0
+	movsd		xmm1, [double]
+	roundsd		xmm0, xmm1, 3
+	movdq2q		mm7, xmm0
+
+	; imm8[2] selects the rounding mode in mxcsr
+	setRoundingBits 0x20 ; Round down
+	roundss		xmm1, [single], 4
+	movd		ebx, xmm1
+	setRoundingBits 0x40 ; Round up
+	roundsd		xmm1, [double], 4
+	movd		ecx, xmm1
+	pextrd		edx, xmm1, 1
+
+	; imm8[3] suppresses the precision exception
+	setRoundingBits 0x00 ; Round to nearest
+	roundps		xmm1, [packed_single], 8
+	stmxcsr		[mxcsr]
+	mov			esi, [mxcsr]
+	roundps		xmm1, [packed_single], 0
+	stmxcsr		[mxcsr]
+	mov			edi, [mxcsr]
+
+	; roundps and roundpd with the rounding mode in imm8[1:0]
+	roundps		xmm0, [packed_single], 0
+	roundps		xmm1, [packed_single], 1
+	movaps		xmm7, [packed_single]
+	roundps		xmm2, xmm7, 2
+	roundps		xmm3, [packed_single], 3
+	roundpd		xmm4, [packed_double], 0
+	roundpd		xmm5, [packed_double], 1
+	roundpd		xmm6, [packed_double], 2
+	movapd		xmm7, [packed_double]
+	roundpd		xmm7, xmm7, 3
+
+%include "footer.inc"
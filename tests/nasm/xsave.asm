@@ -0,0 +1,56 @@
+global _start
+
+section .data
+	align 64
+area:
+	times 576 db 0
+	align 64
+compacted_area:
+	times 576 db 0
+	align 16
+value:
+	dd	0x11111111, 0x22222222, 0x33333333, 0x44444444
+
+%include "header.inc"
+
+	mov		eax, cr4
+	or		eax, 1 << 18 ; osxsave
+	mov		cr4, eax
+
+	xor		ecx, ecx
+	xgetbv
+	mov		ebx, eax ; x87 after reset
+
+	mov		eax, 3 ; x87, sse
+	xor		edx, edx
+	xsetbv
+
+	fld1
+	fldpi
+	movdqu		xmm1, [value]
+
+	mov		eax, -1
+	mov		edx, -1
+	xsave		[area]
+	mov		esi, [area + 512] ; xstate_bv
+
+	pxor		xmm1, xmm1
+	fninit
+	xrstor		[area]
+	movd		edi, xmm1
+	fstp		st0
+
+	xsavec		[compacted_area]
+	mov		ebp, [compacted_area + 520] ; xcomp_bv, low half
+	mov		edx, [compacted_area + 524] ; xcomp_bv, high half
+
+	; restore with only x87 requested: sse is left alone
+	mov		eax, 1
+	xor		edx, edx
+	pxor		xmm1, xmm1
+	xrstor		[area]
+	movd		ecx, xmm1
+
+	xgetbv
+
+%include "footer.inc"